@@ -0,0 +1,173 @@
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::thread;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    middleware::Next,
+    response::Response,
+};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::Serialize;
+use tracing::error;
+
+use crate::auth::AuthenticatedKey;
+use crate::message::{QuotaPeriod, QuotaUnit};
+
+// Audit trail of authentication, access and administrative events, kept apart from the debug log
+#[derive(Debug, Clone, Default, Args)]
+pub struct AuditOptions {
+    /// Append audit events as JSON lines to this file, or `-` for stdout (default: disabled)
+    #[arg(long, value_name = "PATH")]
+    pub audit_log: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    AuthSucceeded {
+        api_key: String,
+        peer: Option<IpAddr>,
+    },
+    AuthFailed {
+        api_key: Option<String>,
+        peer: Option<IpAddr>,
+        reason: String,
+    },
+    SessionResumed {
+        api_key: String,
+        peer: Option<IpAddr>,
+    },
+    SessionRenewed {
+        api_key: String,
+        peer: Option<IpAddr>,
+    },
+    SessionExpired {
+        api_key: String,
+        peer: Option<IpAddr>,
+    },
+    AccessDenied {
+        peer: IpAddr,
+    },
+    QuotaExceeded {
+        api_key: String,
+        period: QuotaPeriod,
+        unit: QuotaUnit,
+        limit: u64,
+    },
+    AdminRequest {
+        method: String,
+        path: String,
+        status: u16,
+        api_key: Option<String>,
+        peer: Option<IpAddr>,
+    },
+}
+
+#[derive(Serialize)]
+struct AuditRecord<'a> {
+    sequence: u64,
+    timestamp: DateTime<Utc>,
+    #[serde(flatten)]
+    event: &'a AuditEvent,
+}
+
+// Append-only sink; records are numbered so gaps are detectable and written by a
+// dedicated thread so callers never block on the file
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    sequence: AtomicU64,
+    sink: Option<mpsc::Sender<String>>,
+}
+
+impl AuditLog {
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn open(options: &AuditOptions) -> io::Result<Self> {
+        let Some(path) = &options.audit_log else {
+            return Ok(Self::disabled());
+        };
+
+        let mut writer: Box<dyn Write + Send> = if path.as_os_str() == "-" {
+            Box::new(io::stdout())
+        } else {
+            Box::new(OpenOptions::new().create(true).append(true).open(path)?)
+        };
+
+        let (sink, records) = mpsc::channel::<String>();
+        thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || {
+                for record in records {
+                    if let Err(e) = writeln!(writer, "{}", record).and_then(|()| writer.flush()) {
+                        error!("Failed to write audit record: {}", e);
+                    }
+                }
+            })?;
+
+        Ok(Self {
+            sequence: AtomicU64::new(0),
+            sink: Some(sink),
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.sink.is_some()
+    }
+
+    pub fn record(&self, event: AuditEvent) {
+        let Some(sink) = &self.sink else {
+            return;
+        };
+
+        let record = AuditRecord {
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            timestamp: Utc::now(),
+            event: &event,
+        };
+
+        match serde_json::to_string(&record) {
+            Ok(line) => {
+                let _ = sink.send(line);
+            }
+            Err(e) => error!("Failed to serialize audit record: {}", e),
+        }
+    }
+}
+
+// Axum middleware recording every request to the routes it wraps as an admin action
+pub async fn audit_middleware(
+    State(audit): State<Arc<AuditLog>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let api_key = request
+        .extensions()
+        .get::<AuthenticatedKey>()
+        .map(|AuthenticatedKey(api_key)| api_key.clone());
+
+    let response = next.run(request).await;
+
+    audit.record(AuditEvent::AdminRequest {
+        method,
+        path,
+        status: response.status().as_u16(),
+        api_key,
+        peer,
+    });
+
+    response
+}
@@ -0,0 +1,147 @@
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+use anyhow::Context;
+use axum::http::{HeaderName, HeaderValue, Method};
+use clap::Args;
+use serde::Deserialize;
+use tower_http::cors::{Any, CorsLayer};
+
+use crate::auth::SIGNATURE_HEADER;
+use crate::rate_limit::API_KEY_HEADER;
+
+// Cross-origin policy for browser clients. Settings given on the command line or in the
+// environment override the config file; without either only same-origin pages may connect.
+#[derive(Debug, Clone, Default, Args)]
+pub struct CorsOptions {
+    /// JSON file with the CORS policy: allowed_origins, allowed_methods, allowed_headers,
+    /// allow_credentials and max_age_secs
+    #[arg(long, env = "SSE_CORS_CONFIG", value_name = "PATH")]
+    pub cors_config: Option<PathBuf>,
+
+    /// Origin allowed to make cross-origin requests, repeatable or comma-separated
+    #[arg(long = "cors-origin", env = "SSE_CORS_ORIGINS", value_name = "ORIGIN", value_delimiter = ',')]
+    pub origins: Vec<String>,
+
+    /// Method allowed in cross-origin requests, repeatable or comma-separated (default: GET)
+    #[arg(long = "cors-method", env = "SSE_CORS_METHODS", value_name = "METHOD", value_delimiter = ',')]
+    pub methods: Vec<String>,
+
+    /// Request header allowed in cross-origin requests, repeatable or comma-separated
+    /// (default: content-type, x-api-key, x-signature)
+    #[arg(long = "cors-header", env = "SSE_CORS_HEADERS", value_name = "HEADER", value_delimiter = ',')]
+    pub headers: Vec<String>,
+
+    /// Allow cross-origin requests with credentials such as cookies
+    #[arg(long, env = "SSE_CORS_ALLOW_CREDENTIALS")]
+    pub cors_allow_credentials: bool,
+
+    /// Allow any origin, method and header; for local development only
+    #[arg(long, env = "SSE_CORS_PERMISSIVE")]
+    pub cors_permissive: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CorsPolicy {
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    pub allow_credentials: bool,
+    pub max_age_secs: Option<u64>,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: vec!["GET".to_string()],
+            allowed_headers: vec![
+                "content-type".to_string(),
+                API_KEY_HEADER.to_string(),
+                SIGNATURE_HEADER.to_string(),
+            ],
+            allow_credentials: false,
+            max_age_secs: Some(600),
+        }
+    }
+}
+
+impl CorsOptions {
+    // Resolves the policy from the config file, then command line and environment overrides
+    pub fn policy(&self) -> anyhow::Result<CorsPolicy> {
+        let mut policy = match &self.cors_config {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .with_context(|| format!("Failed to read CORS config {}", path.display()))?;
+                serde_json::from_str(&contents)
+                    .with_context(|| format!("Invalid CORS config {}", path.display()))?
+            }
+            None => CorsPolicy::default(),
+        };
+
+        if !self.origins.is_empty() {
+            policy.allowed_origins = self.origins.clone();
+        }
+        if !self.methods.is_empty() {
+            policy.allowed_methods = self.methods.clone();
+        }
+        if !self.headers.is_empty() {
+            policy.allowed_headers = self.headers.clone();
+        }
+        policy.allow_credentials |= self.cors_allow_credentials;
+
+        Ok(policy)
+    }
+
+    pub fn layer(&self) -> anyhow::Result<CorsLayer> {
+        if self.cors_permissive {
+            return Ok(CorsLayer::new()
+                .allow_origin(Any)
+                .allow_methods(Any)
+                .allow_headers(Any));
+        }
+
+        self.policy()?.layer()
+    }
+}
+
+impl CorsPolicy {
+    pub fn layer(&self) -> anyhow::Result<CorsLayer> {
+        let any_origin = self.allowed_origins.iter().any(|origin| origin == "*");
+        if any_origin && self.allow_credentials {
+            anyhow::bail!("CORS credentials cannot be allowed for any origin");
+        }
+
+        let mut layer = CorsLayer::new().allow_credentials(self.allow_credentials);
+
+        layer = if any_origin {
+            layer.allow_origin(Any)
+        } else {
+            let origins = self
+                .allowed_origins
+                .iter()
+                .map(|origin| HeaderValue::from_str(origin).with_context(|| format!("Invalid CORS origin: {}", origin)))
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            layer.allow_origin(origins)
+        };
+
+        let methods = self
+            .allowed_methods
+            .iter()
+            .map(|method| Method::from_bytes(method.to_ascii_uppercase().as_bytes()).with_context(|| format!("Invalid CORS method: {}", method)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let headers = self
+            .allowed_headers
+            .iter()
+            .map(|header| HeaderName::from_bytes(header.as_bytes()).with_context(|| format!("Invalid CORS header: {}", header)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        layer = layer.allow_methods(methods).allow_headers(headers);
+
+        if let Some(secs) = self.max_age_secs {
+            layer = layer.max_age(Duration::from_secs(secs));
+        }
+
+        Ok(layer)
+    }
+}
@@ -0,0 +1,83 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use clap::Parser;
+use ts_rs::{Config, TS};
+
+use market_depth_sse_server::*;
+
+/// Write the TypeScript definitions of the SSE protocol
+#[derive(Parser)]
+#[command(author, version, about, long_about = None)]
+struct Args {
+    /// Output file
+    #[arg(default_value = "../frontend/src/types/sse-market-data.ts")]
+    output: PathBuf,
+}
+
+// Every type reachable from SSEMessage
+macro_rules! declarations {
+    ($cfg:expr, $($ty:ty),+ $(,)?) => {
+        vec![$(<$ty as TS>::decl($cfg)),+]
+    };
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    // Sequences and counters stay well below 2^53, so they are plain numbers in JSON
+    let cfg = Config::new().with_large_int("number");
+
+    let declarations = declarations!(
+        &cfg,
+        SSEMessage,
+        DataType,
+        MarketDataUpdate,
+        OrderId,
+        Quantity,
+        MBOLevel,
+        MBPLevel,
+        ActorClass,
+        AggressorFlowStats,
+        PairKind,
+        PairQuote,
+        SymbolSummary,
+        SymbolType,
+        SymbolStatus,
+        SymbolInfo,
+        MarketSummary,
+        StreamUsage,
+        StreamDefinition,
+        StreamRejection,
+        InvalidStreams,
+        Side,
+        StreamPriority,
+        DisconnectReason,
+        QuotaPeriod,
+        QuotaUnit,
+        QuotaRemaining,
+    );
+
+    write_module(&args.output, &declarations)
+}
+
+fn write_module(output: &Path, declarations: &[String]) -> anyhow::Result<()> {
+    let version = env!("CARGO_PKG_VERSION");
+    let mut module = format!(
+        "// Generated by `cargo run --features ts --bin gen-ts` from {} {}. Do not edit.\n\n\
+         export const PROTOCOL_VERSION = \"{}\";\n",
+        env!("CARGO_PKG_NAME"),
+        version,
+        version,
+    );
+    for declaration in declarations {
+        module.push_str("\nexport ");
+        module.push_str(declaration);
+        module.push('\n');
+    }
+
+    if let Some(parent) = output.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(output, module)?;
+    println!("Wrote {}", output.display());
+    Ok(())
+}
@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use dashmap::DashMap;
+use tokio::time::{sleep_until, Instant};
+
+use crate::message::MBPLevel;
+use crate::order_book::OrderBook;
+
+// Replays run at most this many times faster than recorded
+pub const MAX_REPLAY_SPEED: f64 = 1000.0;
+
+// MBP history of every book, kept in memory for replays of a recent window
+#[derive(Debug, Clone, Args)]
+pub struct HistoryOptions {
+    /// Keep this much MBP history of every book for replays (default: no history)
+    #[arg(long, value_name = "SECS")]
+    pub history_secs: Option<u64>,
+
+    /// Price levels per side kept in the history
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub history_levels: u32,
+}
+
+impl Default for HistoryOptions {
+    fn default() -> Self {
+        Self { history_secs: None, history_levels: 20 }
+    }
+}
+
+impl HistoryOptions {
+    pub fn history(&self) -> Option<BookHistory> {
+        self.history_secs.map(|secs| BookHistory::new(Duration::from_secs(secs), self.history_levels))
+    }
+}
+
+// One recorded state of a book
+#[derive(Debug)]
+pub struct HistoryFrame {
+    pub sequence: u64,
+    pub epoch: u64,
+    pub event_ts: DateTime<Utc>,
+    pub bids: Vec<MBPLevel>,
+    pub asks: Vec<MBPLevel>,
+}
+
+impl HistoryFrame {
+    pub fn levels(&self, max_levels: u32) -> (Vec<MBPLevel>, Vec<MBPLevel>) {
+        let depth = max_levels as usize;
+        (
+            self.bids.iter().take(depth).cloned().collect(),
+            self.asks.iter().take(depth).cloned().collect(),
+        )
+    }
+}
+
+// Frames of each book over the retention window, oldest first
+#[derive(Debug)]
+pub struct BookHistory {
+    retention: chrono::Duration,
+    levels: u32,
+    frames: DashMap<String, VecDeque<Arc<HistoryFrame>>>,
+}
+
+impl BookHistory {
+    pub fn new(retention: Duration, levels: u32) -> Self {
+        Self {
+            retention: chrono::Duration::from_std(retention).unwrap_or(chrono::Duration::MAX),
+            levels,
+            frames: DashMap::new(),
+        }
+    }
+
+    pub fn levels(&self) -> u32 {
+        self.levels
+    }
+
+    // Records the book unless its sequence is already the latest frame
+    pub fn record(&self, symbol: &str, order_book: &OrderBook) {
+        let (sequence, epoch) = (order_book.get_sequence(), order_book.get_epoch());
+        let event_ts = order_book.get_event_timestamp();
+        let mut frames = self.frames.entry(symbol.to_string()).or_default();
+        if frames.back().is_some_and(|last| last.sequence == sequence && last.epoch == epoch) {
+            return;
+        }
+
+        let (bids, asks) = order_book.get_mbp_data(self.levels);
+        frames.push_back(Arc::new(HistoryFrame { sequence, epoch, event_ts, bids, asks }));
+        while frames.front().is_some_and(|first| event_ts - first.event_ts > self.retention) {
+            frames.pop_front();
+        }
+    }
+
+    pub fn remove(&self, symbol: &str) {
+        self.frames.remove(symbol);
+    }
+
+    // Frames recorded between `from` and `to`, both inclusive
+    pub fn window(&self, symbol: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<Vec<Arc<HistoryFrame>>, String> {
+        if from >= to {
+            return Err("from must be before to".to_string());
+        }
+        let Some(frames) = self.frames.get(symbol) else {
+            return Err(format!("No history of {}", symbol));
+        };
+
+        let window: Vec<_> = frames
+            .iter()
+            .filter(|frame| frame.event_ts >= from && frame.event_ts <= to)
+            .cloned()
+            .collect();
+        if window.is_empty() {
+            let oldest = frames.front().map_or_else(|| "nothing".to_string(), |frame| frame.event_ts.to_rfc3339());
+            return Err(format!("No history of {} in the window, oldest kept is {}", symbol, oldest));
+        }
+        Ok(window)
+    }
+}
+
+pub fn validate_replay_speed(speed: Option<f64>) -> Result<f64, String> {
+    let speed = speed.unwrap_or(1.0);
+    if !(speed > 0.0 && speed <= MAX_REPLAY_SPEED) {
+        return Err(format!("speed must be above 0 and at most {}, got {}", MAX_REPLAY_SPEED, speed));
+    }
+    Ok(speed)
+}
+
+// Hands out the frames of a window as they fall due, `speed` times faster than they
+// were recorded
+pub struct Replay {
+    frames: std::vec::IntoIter<Arc<HistoryFrame>>,
+    first_event: DateTime<Utc>,
+    started: Instant,
+    speed: f64,
+}
+
+impl Replay {
+    pub fn new(frames: Vec<Arc<HistoryFrame>>, speed: f64) -> Self {
+        let first_event = frames.first().map_or_else(Utc::now, |frame| frame.event_ts);
+        Self {
+            frames: frames.into_iter(),
+            first_event,
+            started: Instant::now(),
+            speed,
+        }
+    }
+
+    pub async fn next(&mut self) -> Option<Arc<HistoryFrame>> {
+        let frame = self.frames.next()?;
+        let offset = (frame.event_ts - self.first_event).to_std().unwrap_or_default();
+        sleep_until(self.started + offset.div_f64(self.speed)).await;
+        Some(frame)
+    }
+}
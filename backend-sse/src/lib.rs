@@ -1,9 +1,57 @@
+pub mod access;
+pub mod audit;
+pub mod auth;
+pub mod clock;
+pub mod cors;
+pub mod flow;
+pub mod history;
 pub mod message;
+pub mod limits;
+pub mod load_shedding;
+pub mod metrics;
 pub mod order_book;
+pub mod outbound_queue;
+pub mod pairs;
+pub mod quota;
+pub mod rate_limit;
+pub mod runtime;
+pub mod snapshot_cache;
+pub mod snapshot_schedule;
 pub mod stream_manager;
+pub mod stream_validation;
 pub mod sse_handler;
+pub mod subscriptions;
+pub mod summary;
+pub mod symbol_config;
+pub mod symbols;
+pub mod unix_socket;
+pub mod usage;
 
+pub use access::*;
+pub use audit::*;
+pub use auth::*;
+pub use clock::*;
+pub use cors::*;
+pub use flow::*;
+pub use history::*;
 pub use message::*;
+pub use limits::*;
+pub use load_shedding::*;
+pub use metrics::*;
 pub use order_book::*;
+pub use outbound_queue::*;
+pub use pairs::*;
+pub use quota::*;
+pub use rate_limit::*;
+pub use runtime::*;
+pub use snapshot_cache::*;
+pub use snapshot_schedule::*;
 pub use stream_manager::*;
-pub use sse_handler::*;
\ No newline at end of file
+pub use stream_validation::*;
+pub use sse_handler::*;
+pub use subscriptions::*;
+pub use summary::*;
+pub use symbol_config::*;
+pub use symbols::*;
+pub use unix_socket::*;
+pub use usage::*;
@@ -0,0 +1,131 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use clap::Args;
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use uuid::Uuid;
+
+// How often load is evaluated against the thresholds
+pub const LOAD_WINDOW: Duration = Duration::from_secs(1);
+
+// Interval low-priority streams are first conflated to. It doubles every window the load
+// stays over a threshold and halves every window it is under half of each threshold.
+const BASE_INTERVAL: Duration = Duration::from_millis(500);
+
+// Conflation of low-priority streams while fan-out latency or client queues run high
+#[derive(Debug, Clone, Default, Args)]
+pub struct LoadSheddingOptions {
+    /// Fan-out latency of a symbol update, in milliseconds, past which low-priority streams are conflated (default: disabled)
+    #[arg(long, value_name = "MS")]
+    pub shed_fanout_latency_ms: Option<f64>,
+
+    /// Messages queued for a client past which low-priority streams are conflated (default: disabled)
+    #[arg(long, value_name = "N")]
+    pub shed_queue_depth: Option<usize>,
+
+    /// Longest interval low-priority streams are conflated to under sustained load
+    #[arg(long, value_name = "MS", default_value_t = 8000)]
+    pub shed_max_interval_ms: u64,
+}
+
+impl LoadSheddingOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.shed_fanout_latency_ms.is_some() || self.shed_queue_depth.is_some()
+    }
+}
+
+// Peak load over the current window, and the conflation interval it has led to
+#[derive(Debug)]
+pub struct LoadShedder {
+    fanout_threshold: Option<Duration>,
+    queue_threshold: Option<usize>,
+    max_interval: Duration,
+    peak_fanout_ns: AtomicU64,
+    peak_queue_depth: AtomicUsize,
+    interval_ms: AtomicU64, // 0 at full rate
+    last_delivery: DashMap<(Uuid, String), Instant>,
+}
+
+impl LoadShedder {
+    pub fn new(options: &LoadSheddingOptions) -> Self {
+        Self {
+            fanout_threshold: options.shed_fanout_latency_ms.map(|ms| Duration::from_secs_f64(ms.max(0.0) / 1000.0)),
+            queue_threshold: options.shed_queue_depth,
+            max_interval: Duration::from_millis(options.shed_max_interval_ms).max(BASE_INTERVAL),
+            peak_fanout_ns: AtomicU64::new(0),
+            peak_queue_depth: AtomicUsize::new(0),
+            interval_ms: AtomicU64::new(0),
+            last_delivery: DashMap::new(),
+        }
+    }
+
+    pub fn observe_fanout(&self, elapsed: Duration) {
+        self.peak_fanout_ns.fetch_max(elapsed.as_nanos() as u64, Ordering::Relaxed);
+    }
+
+    pub fn observe_queue_depth(&self, depth: usize) {
+        self.peak_queue_depth.fetch_max(depth, Ordering::Relaxed);
+    }
+
+    // How often low-priority streams are updated, or None at full rate
+    pub fn interval(&self) -> Option<Duration> {
+        match self.interval_ms.load(Ordering::Relaxed) {
+            0 => None,
+            interval_ms => Some(Duration::from_millis(interval_ms)),
+        }
+    }
+
+    // Closes the current window, returning the new interval when the window's load changed it
+    pub fn evaluate(&self) -> Option<Option<Duration>> {
+        let fanout = Duration::from_nanos(self.peak_fanout_ns.swap(0, Ordering::Relaxed));
+        let queue_depth = self.peak_queue_depth.swap(0, Ordering::Relaxed);
+
+        let over = self.fanout_threshold.is_some_and(|threshold| fanout > threshold)
+            || self.queue_threshold.is_some_and(|threshold| queue_depth > threshold);
+        let under = self.fanout_threshold.is_none_or(|threshold| fanout <= threshold / 2)
+            && self.queue_threshold.is_none_or(|threshold| queue_depth <= threshold / 2);
+
+        let current = self.interval();
+        let next = match current {
+            _ if over => Some(current.map_or(BASE_INTERVAL, |interval| interval * 2).min(self.max_interval)),
+            Some(interval) if under => Some(interval / 2).filter(|interval| *interval >= BASE_INTERVAL),
+            _ => current,
+        };
+        if next == current {
+            return None;
+        }
+
+        self.interval_ms.store(next.map_or(0, |interval| interval.as_millis() as u64), Ordering::Relaxed);
+        if next.is_none() {
+            self.last_delivery.clear();
+        }
+        Some(next)
+    }
+
+    // Whether an update of a low-priority stream goes out now rather than being conflated
+    pub fn deliver(&self, client_id: &Uuid, stream_id: &str, now: Instant) -> bool {
+        let Some(interval) = self.interval() else {
+            return true;
+        };
+
+        match self.last_delivery.entry((*client_id, stream_id.to_string())) {
+            Entry::Occupied(last) if now.duration_since(*last.get()) < interval => false,
+            Entry::Occupied(mut last) => {
+                last.insert(now);
+                true
+            }
+            Entry::Vacant(last) => {
+                last.insert(now);
+                true
+            }
+        }
+    }
+
+    pub fn forget_stream(&self, client_id: &Uuid, stream_id: &str) {
+        self.last_delivery.remove(&(*client_id, stream_id.to_string()));
+    }
+
+    pub fn forget_client(&self, client_id: &Uuid) {
+        self.last_delivery.retain(|(id, _), _| id != client_id);
+    }
+}
@@ -1,14 +1,20 @@
+use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
 use axum::{
-    routing::get,
+    middleware,
+    routing::{delete, get},
     Router,
 };
 use clap::Parser;
-use tower_http::cors::{CorsLayer, Any};
-use tracing::{info, error};
+use tracing::{info, warn};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
-use market_depth_sse_server::{SSEStreamManager, sse_handler, health_check, symbols_handler, api_info};
+use market_depth_sse_server::{access_control_middleware, audit_middleware, rate_limit_middleware, signature_middleware, AccessControl, AccessOptions, AuditLog, AuditOptions, AuthOptions, Authenticator, CorsOptions, DataType, DEFAULT_STREAMS, disconnect_handler, DisconnectReason, HistoryOptions, key_rate_limit_middleware, LoadShedder, LoadSheddingOptions, parse_quantity_decimals, parse_stream_definitions_strict, QuotaOptions, QuotaTracker, RateLimitOptions, RateLimiter, ResourceLimits, RuntimeOptions, shutdown_signal, SSEStreamManager, SymbolConfigOptions, SyntheticPair, sse_handler, UnixSocketOptions, health_check, symbols_handler, summary_handler, usage_handler, quota_handler, metrics_handler, api_info};
+#[cfg(unix)]
+use market_depth_sse_server::serve_unix;
+#[cfg(not(unix))]
+use market_depth_sse_server::unsupported_unix_socket;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -20,10 +26,90 @@ struct Args {
     /// Log level (trace, debug, info, warn, error)
     #[arg(short, long, default_value = "info")]
     log_level: String,
+
+    /// Synthetic pair to publish, repeatable: BASE/QUOTE (ratio) or BASE-QUOTE (spread)
+    #[arg(long = "pair", value_name = "PAIR")]
+    pairs: Vec<String>,
+
+    /// Venue reported for every symbol by symbol discovery
+    #[arg(long, default_value = "SIM")]
+    venue: String,
+
+    /// Decimals a symbol's order sizes are quoted to, repeatable: SYMBOL:DECIMALS, e.g.
+    /// BTCUSD:4 for lots of 0.0001 (default: whole units)
+    #[arg(long = "quantity-decimals", value_name = "SYMBOL:DECIMALS")]
+    quantity_decimals: Vec<String>,
+
+    /// Send each client a usage_report event at this interval (e.g. 86400 for daily)
+    #[arg(long, value_name = "SECS")]
+    usage_report_interval_secs: Option<u64>,
+
+    /// Streams a connection naming none is subscribed to, in the syntax of the `streams`
+    /// query parameter, or "none" to refuse such connections with 400 Bad Request
+    #[arg(long, value_name = "STREAMS", default_value = DEFAULT_STREAMS)]
+    default_streams: String,
+
+    /// Validate every connection's stream parameters as with `strict=true`, refusing
+    /// invalid ones with 422 instead of coercing them
+    #[arg(long)]
+    strict_subscriptions: bool,
+
+    /// Tag the orders of simulated books on MBO streams with the class of actor that placed
+    /// them (maker, taker or noise), as ground truth for research datasets
+    #[arg(long)]
+    research_actor_tags: bool,
+
+    /// Maximum number of symbols with a simulated book (default: unlimited)
+    #[arg(long, value_name = "N")]
+    max_symbols: Option<usize>,
+
+    /// Maximum resting orders per simulated book (default: unlimited)
+    #[arg(long, value_name = "N")]
+    max_orders_per_book: Option<usize>,
+
+    /// Drop books that have had no subscribers for this long (default: never)
+    #[arg(long, value_name = "SECS")]
+    idle_symbol_ttl_secs: Option<u64>,
+
+    /// Disconnect clients with more messages than this queued as slow consumers (default: never)
+    #[arg(long, value_name = "N")]
+    max_client_queue: Option<usize>,
+
+    #[command(flatten)]
+    access: AccessOptions,
+
+    #[command(flatten)]
+    auth: AuthOptions,
+
+    #[command(flatten)]
+    cors: CorsOptions,
+
+    #[command(flatten)]
+    quotas: QuotaOptions,
+
+    #[command(flatten)]
+    audit: AuditOptions,
+
+    #[command(flatten)]
+    rate_limits: RateLimitOptions,
+
+    #[command(flatten)]
+    load_shedding: LoadSheddingOptions,
+
+    #[command(flatten)]
+    runtime: RuntimeOptions,
+
+    #[command(flatten)]
+    unix_socket: UnixSocketOptions,
+
+    #[command(flatten)]
+    history: HistoryOptions,
+
+    #[command(flatten)]
+    symbols: SymbolConfigOptions,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
     // Initialize tracing
@@ -40,30 +126,130 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::subscriber::set_global_default(subscriber)?;
 
+    let runtime = args.runtime.build_io_runtime()?;
+    runtime.block_on(run(args))
+}
+
+async fn run(args: Args) -> anyhow::Result<()> {
+
     info!("Starting Market Depth SSE Server");
     info!("Log level: {}", args.log_level);
 
+    let audit = Arc::new(AuditLog::open(&args.audit)?);
+    if let Some(path) = &args.audit.audit_log {
+        info!("Writing audit log to {}", path.display());
+    }
+
     // Create stream manager
-    let stream_manager = Arc::new(SSEStreamManager::new());
+    let mut stream_manager = SSEStreamManager::new();
+    stream_manager.set_venue(&args.venue);
+
+    if args.default_streams.eq_ignore_ascii_case("none") {
+        stream_manager.set_default_streams(Vec::new());
+        info!("Connections must name their streams");
+    } else {
+        let default_streams = parse_stream_definitions_strict("--default-streams", &args.default_streams, &DataType::MBP, 20)
+            .map_err(|rejected| {
+                let errors: Vec<String> = rejected.iter().map(|rejection| format!("'{}': {}", rejection.value, rejection.error)).collect();
+                anyhow::anyhow!("Invalid --default-streams {}", errors.join("; "))
+            })?;
+        stream_manager.set_default_streams(default_streams);
+    }
+    if args.strict_subscriptions {
+        stream_manager.enable_strict_subscriptions();
+    }
+    if args.research_actor_tags {
+        stream_manager.enable_actor_tags();
+    }
+
+    if let Some(secs) = args.usage_report_interval_secs {
+        stream_manager.enable_usage_reports(Duration::from_secs(secs.max(1)));
+    }
+
+    stream_manager.set_resource_limits(ResourceLimits {
+        max_symbols: args.max_symbols,
+        max_orders_per_book: args.max_orders_per_book,
+        idle_symbol_ttl: args.idle_symbol_ttl_secs.map(|secs| Duration::from_secs(secs.max(1))),
+        max_client_queue: args.max_client_queue,
+    });
+
+    if args.quotas.is_enabled() {
+        let mut quotas = QuotaTracker::new(&args.quotas);
+        quotas.set_audit_log(Arc::clone(&audit));
+        stream_manager.set_quotas(quotas);
+    }
+
+    if args.load_shedding.is_enabled() {
+        stream_manager.set_load_shedding(LoadShedder::new(&args.load_shedding));
+    }
+
+    if let Some(history) = args.history.history() {
+        stream_manager.enable_history(history);
+    }
+
+    if let Some(handle) = args.runtime.spawn_simulation_runtime()? {
+        stream_manager.set_simulation_runtime(handle);
+    }
+
+    for definition in &args.quantity_decimals {
+        let (symbol, decimals) = parse_quantity_decimals(definition).map_err(anyhow::Error::msg)?;
+        stream_manager.set_quantity_decimals(&symbol, decimals);
+    }
+
+    if let Some(symbols) = args.symbols.symbols().map_err(anyhow::Error::msg)? {
+        stream_manager.set_symbol_config(symbols);
+    }
+
+    for definition in &args.pairs {
+        let pair = SyntheticPair::parse(definition).map_err(anyhow::Error::msg)?;
+        stream_manager.add_synthetic_pair(pair);
+    }
+
+    let stream_manager = Arc::new(stream_manager);
 
     // Start stream manager background tasks
     stream_manager.start().await;
 
+    // Rate limits, access rules and signed requests apply to the SSE endpoint and every REST endpoint
+    let rate_limiter = Arc::new(RateLimiter::new(&args.rate_limits));
+    rate_limiter.start_pruning();
+    let mut access_control = AccessControl::new(&args.access);
+    access_control.set_audit_log(Arc::clone(&audit));
+    let access_control = Arc::new(access_control);
+    let mut authenticator = Authenticator::new(&args.auth);
+    authenticator.set_audit_log(Arc::clone(&audit));
+    let authenticator = Arc::new(authenticator);
+    authenticator.start_pruning();
+
     // Create CORS layer
-    let cors = CorsLayer::new()
-        .allow_origin(Any)
-        .allow_methods(Any)
-        .allow_headers(Any);
+    let cors = args.cors.layer()?;
+    if args.cors.cors_permissive {
+        warn!("CORS is permissive: any origin may connect");
+    }
+
+    // Administrative endpoints are recorded in the audit log
+    let admin = Router::new()
+        .route("/admin/usage", get(usage_handler))
+        .route("/admin/clients/:client_id", delete(disconnect_handler))
+        .route("/metrics", get(metrics_handler))
+        .route_layer(middleware::from_fn_with_state(audit, audit_middleware));
 
     // Build our application with routes
     let app = Router::new()
         .route("/stream", get(sse_handler))
         .route("/health", get(health_check))
         .route("/symbols", get(symbols_handler))
+        .route("/summary", get(summary_handler))
+        .route("/quota", get(quota_handler))
         .route("/api", get(api_info))
         .route("/", get(api_info))
+        .merge(admin)
+        .layer(middleware::from_fn_with_state(Arc::clone(&rate_limiter), key_rate_limit_middleware))
+        .layer(middleware::from_fn_with_state(authenticator, signature_middleware))
+        .layer(middleware::from_fn_with_state(rate_limiter, rate_limit_middleware))
+        .layer(middleware::from_fn_with_state(access_control, access_control_middleware))
         .layer(cors)
-        .with_state(stream_manager);
+        .with_state(Arc::clone(&stream_manager));
 
     info!("Server starting on: {}", args.addr);
 
@@ -71,7 +257,35 @@ async fn main() -> anyhow::Result<()> {
     let listener = tokio::net::TcpListener::bind(&args.addr).await?;
     info!("SSE server listening on: {}", args.addr);
 
-    axum::serve(listener, app).await?;
+    // Unix socket clients are served until the TCP listener has shut down
+    let unix_server = match &args.unix_socket.unix_socket {
+        #[cfg(unix)]
+        Some(path) => {
+            let listener = args.unix_socket.bind(path)?;
+            info!("SSE server listening on Unix socket: {}", path);
+            Some(serve_unix(listener, app.clone()))
+        }
+        #[cfg(not(unix))]
+        Some(_) => return Err(unsupported_unix_socket().into()),
+        None => None,
+    };
+
+    // On shutdown every stream ends with a terminal error event, which lets the connections drain
+    let server = axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .tcp_nodelay(args.runtime.tcp_nodelay())
+        .with_graceful_shutdown(async move {
+            shutdown_signal().await;
+            let disconnected = stream_manager.disconnect_all(DisconnectReason::ServerShutdown);
+            info!("Shutting down, disconnecting {} clients", disconnected);
+        });
+
+    match unix_server {
+        Some(unix_server) => tokio::select! {
+            result = server => result?,
+            result = unix_server => result?,
+        },
+        None => server.await?,
+    }
 
     Ok(())
 }
\ No newline at end of file
@@ -1,18 +1,47 @@
-use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+use bytes::Bytes;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use chrono::{DateTime, Utc};
 use uuid::Uuid;
 
+use crate::clock::{clock, ClockSource, EventOrdering};
+use crate::order_book::default_max_orders;
+use crate::outbound_queue::{DeliveryTier, Prioritized};
+use crate::stream_validation::{validate_levels, validate_symbol};
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 #[serde(tag = "event")]
 pub enum SSEMessage {
+    // `event_ts` is when the book last changed, never earlier than the stream's previous
+    // event; `send_ts` is when the event was serialized for the client, and `timestamp`
+    // when the update was built for fan-out
     #[serde(rename = "market_data")]
     MarketData {
+        stream_id: String,
+        symbol: Arc<str>,
+        data: MarketDataUpdate,
+        sequence: u64,
+        timestamp: DateTime<Utc>,
+        event_ts: DateTime<Utc>,
+        send_ts: DateTime<Utc>,
+    },
+    // Every level (and for MBO every order) of the book, sent every `snapshot_interval_ms`
+    // on the book streams of a connection that asked for it; it replaces the client's book
+    #[serde(rename = "snapshot")]
+    Snapshot {
         stream_id: String,
         symbol: String,
         data: MarketDataUpdate,
         sequence: u64,
         timestamp: DateTime<Utc>,
     },
+    #[serde(rename = "market_summary")]
+    MarketSummary {
+        summary: MarketSummary,
+    },
     #[serde(rename = "heartbeat")]
     HeartBeat {
         timestamp: DateTime<Utc>,
@@ -22,22 +51,79 @@ pub enum SSEMessage {
         client_id: String,
         server_time: DateTime<Utc>,
         supported_symbols: Vec<String>,
+        subscriptions: Vec<StreamDefinition>, // What the connection was subscribed to, defaults included
+        summary: bool,
+    },
+    #[serde(rename = "usage_report")]
+    UsageReport {
+        connected_at: DateTime<Utc>,
+        messages: u64,
+        bytes: u64,
+        streams: Vec<StreamUsage>,
+        timestamp: DateTime<Utc>,
     },
+    #[serde(rename = "quota_exceeded")]
+    QuotaExceeded {
+        quota: QuotaRemaining,
+    },
+    // Sent when server load changes how often low-priority streams are updated: at most
+    // once per `interval_ms`, or at full rate again once it is null
+    #[serde(rename = "throttled")]
+    Throttled {
+        stream_ids: Vec<String>,
+        interval_ms: Option<u64>,
+        timestamp: DateTime<Utc>,
+    },
+    // Sent ahead of a replay's updates; `from` and `to` are the event times of the first
+    // and last update to be replayed
+    #[serde(rename = "replay_started")]
+    ReplayStarted {
+        stream_id: String,
+        symbol: String,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        updates: usize,
+        speed: f64,
+    },
+    #[serde(rename = "replay_complete")]
+    ReplayComplete {
+        stream_id: String,
+    },
+    // `terminal` on the last event of a connection the server is closing, whose `reason`
+    // says why and whose `code` is the reason's WebSocket close code; reconnecting after
+    // one is up to the reason, not to EventSource's automatic retry
     #[serde(rename = "error")]
     Error {
         code: u32,
         message: String,
         stream_id: Option<String>,
+        #[serde(default)]
+        terminal: bool,
+        #[serde(default)]
+        reason: Option<DisconnectReason>,
     },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub enum DataType {
     MBO, // Market By Order
     MBP, // Market By Price
+    AggressorFlow, // Rolling aggressor volume and cumulative volume delta
+}
+
+impl DataType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataType::MBO => "MBO",
+            DataType::MBP => "MBP",
+            DataType::AggressorFlow => "AggressorFlow",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 #[serde(tag = "format")]
 pub enum MarketDataUpdate {
     MBO {
@@ -48,29 +134,455 @@ pub enum MarketDataUpdate {
         bids: Vec<MBPLevel>,
         asks: Vec<MBPLevel>,
     },
+    Pair {
+        quote: PairQuote,
+    },
+    AggressorFlow {
+        flow: AggressorFlowStats,
+    },
+}
+
+// Numeric order identifier used inside the book; serialized as a string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(type = "string"))]
+pub struct OrderId(pub u64);
+
+impl fmt::Display for OrderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for OrderId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map(OrderId).map_err(serde::de::Error::custom)
+    }
+}
+
+// Order size as a fixed-point number with Quantity::DECIMALS places, so fractional sizes
+// such as 0.05 BTC add up exactly. Serialized as a JSON number of units, or as a decimal
+// string such as "123456789.12345678" when no JSON number parses back to it exactly;
+// strings are accepted for any quantity. Arithmetic saturates rather than wrapping.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(type = "number | string"))]
+pub struct Quantity(u64);
+
+impl Quantity {
+    pub const DECIMALS: u32 = 8;
+    pub const ZERO: Quantity = Quantity(0);
+    // Largest size one order or trade may have, so that a book's levels and volumes add
+    // up far from what a quantity holds
+    pub const MAX_ORDER: Quantity = Quantity::from_units(1_000_000_000);
+    const SCALE: u64 = 10u64.pow(Self::DECIMALS);
+
+    // MAX for more units than a quantity holds
+    pub const fn from_units(units: u64) -> Self {
+        Self(units.saturating_mul(Self::SCALE))
+    }
+
+    // `lots` increments of 10^-decimals, e.g. 5 lots at 2 decimals is 0.05
+    pub fn from_lots(lots: u64, decimals: u32) -> Self {
+        Self(lots.saturating_mul(Self::lot_scale(decimals)))
+    }
+
+    // Whole increments of 10^-decimals, rounding down
+    pub fn lots(self, decimals: u32) -> u64 {
+        self.0 / Self::lot_scale(decimals)
+    }
+
+    // Nearest quantity to a non-negative finite value
+    pub fn from_f64(value: f64) -> Option<Self> {
+        let scaled = (value * Self::SCALE as f64).round();
+        (scaled.is_finite() && scaled >= 0.0 && scaled < u64::MAX as f64).then_some(Self(scaled as u64))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    // Whether the quantity is a whole number of 10^-decimals increments
+    pub fn has_decimals(self, decimals: u32) -> bool {
+        self.0.is_multiple_of(Self::lot_scale(decimals))
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+
+    fn lot_scale(decimals: u32) -> u64 {
+        10u64.pow(Self::DECIMALS - decimals.min(Self::DECIMALS))
+    }
+}
+
+impl std::ops::Add for Quantity {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl std::ops::Sub for Quantity {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl std::ops::AddAssign for Quantity {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::SubAssign for Quantity {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::iter::Sum for Quantity {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, |total, quantity| total + quantity)
+    }
+}
+
+// Shortest exact decimal: 1500, 0.05
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (units, fraction) = (self.0 / Self::SCALE, self.0 % Self::SCALE);
+        if fraction == 0 {
+            return f.pad(&units.to_string());
+        }
+
+        let fraction = format!("{:0width$}", fraction, width = Self::DECIMALS as usize);
+        f.pad(&format!("{}.{}", units, fraction.trim_end_matches('0')))
+    }
+}
+
+impl std::str::FromStr for Quantity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid quantity '{}'", s);
+        let (units, fraction) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+        if units.is_empty() && fraction.is_empty() {
+            return Err(invalid());
+        }
+        if fraction.len() > Self::DECIMALS as usize {
+            return Err(format!("Quantity '{}' has more than {} decimals", s, Self::DECIMALS));
+        }
+
+        let digits = |part: &str| -> Result<u64, String> {
+            if !part.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            Ok(if part.is_empty() { 0 } else { part.parse().map_err(|_| invalid())? })
+        };
+        let (units, fraction) = (digits(units)?, digits(fraction)? * 10u64.pow(Self::DECIMALS - fraction.len() as u32));
+
+        units
+            .checked_mul(Self::SCALE)
+            .and_then(|units| units.checked_add(fraction))
+            .map(Self)
+            .ok_or_else(invalid)
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_multiple_of(Self::SCALE) {
+            return serializer.serialize_u64(self.0 / Self::SCALE);
+        }
+        // The nearest f64 is written as the shortest decimal that parses back to it, which
+        // rounds to this quantity again unless it has too many significant digits
+        let value = self.to_f64();
+        if Quantity::from_f64(value) == Some(*self) {
+            serializer.serialize_f64(value)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct QuantityVisitor;
+
+        impl serde::de::Visitor<'_> for QuantityVisitor {
+            type Value = Quantity;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a non-negative quantity")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Quantity, E> {
+                value
+                    .checked_mul(Quantity::SCALE)
+                    .map(Quantity)
+                    .ok_or_else(|| E::custom(format!("Quantity {} is too large", value)))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Quantity, E> {
+                let value = u64::try_from(value).map_err(|_| E::custom("Quantity must not be negative"))?;
+                self.visit_u64(value)
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<Quantity, E> {
+                Quantity::from_f64(value).ok_or_else(|| E::custom(format!("Invalid quantity {}", value)))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Quantity, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(QuantityVisitor)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub struct MBOLevel {
-    pub order_id: String,
+    pub order_id: OrderId,
     pub price: f64,
-    pub quantity: u64,
+    pub quantity: Quantity,
     pub side: Side,
     pub timestamp: DateTime<Utc>,
     pub age_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub actor: Option<ActorClass>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub struct MBPLevel {
     pub price: f64,
-    pub quantity: u64,
+    pub quantity: Quantity,
     pub order_count: u32,
     pub side: Side,
-    pub total_quantity: u64,
+    pub total_quantity: Quantity,
     pub avg_age_ms: u64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum PairKind {
+    Ratio,
+    Spread,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct PairQuote {
+    pub pair: String,
+    pub kind: PairKind,
+    pub base: String,
+    pub quote: String,
+    pub bid: Option<f64>,
+    pub ask: Option<f64>,
+    pub value: Option<f64>,
+    pub spread: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct SymbolSummary {
+    pub symbol: String,
+    pub last_price: Option<f64>,
+    pub open_price: Option<f64>,
+    pub change_pct: Option<f64>,
+    pub volume: Quantity,
+    pub spread: Option<f64>,
+    pub spread_bps: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolType {
+    Spot,
+    Pair,
+    Perpetual,
+    Future,
+    Option,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolStatus {
+    Open,
+    Halted,
+}
+
+// Discovery metadata for one subscribable symbol. Base and quote are the currencies
+// of a spot-style name such as BTCUSD, or the two legs of a synthetic pair.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct SymbolInfo {
+    pub symbol: String,
+    #[serde(rename = "type")]
+    pub symbol_type: SymbolType,
+    pub status: SymbolStatus,
+    pub venue: String,
+    pub base: Option<String>,
+    pub quote: Option<String>,
+    pub tick_size: Option<f64>, // None for synthetic pairs, whose prices are not on a grid
+    pub quantity_decimals: Option<u32>, // Decimals order sizes are quoted to; None for synthetic pairs
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct MarketSummary {
+    pub top_gainers: Vec<SymbolSummary>,
+    pub top_losers: Vec<SymbolSummary>,
+    pub highest_volume: Vec<SymbolSummary>,
+    pub widest_spreads: Vec<SymbolSummary>,
+    pub symbol_count: usize,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct StreamUsage {
+    pub stream_id: String,
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum QuotaPeriod {
+    Daily,
+    Monthly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum QuotaUnit {
+    Messages,
+    Bytes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct QuotaRemaining {
+    pub period: QuotaPeriod,
+    pub unit: QuotaUnit,
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    pub resets_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct QuotaStatus {
+    pub api_key: String,
+    pub quotas: Vec<QuotaRemaining>,
+    pub throttled: bool,
+    pub exceeded: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Low-priority streams are the first to be conflated when the server is under load
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum StreamPriority {
+    #[default]
+    Normal,
+    Low,
+}
+
+// Why the server ended a connection. WebSocket clients get it as the close code and
+// reason of the Close frame, SSE clients as a terminal `error` event; a connection that
+// ends without one was lost to the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectReason {
+    // The server is shutting down; reconnect to another instance or after a delay
+    ServerShutdown,
+    // The session expired and the connection could not continue unauthenticated; reconnect with fresh credentials
+    SessionExpired,
+    // The client fell too far behind its updates; reconnect and consume faster, or subscribe to less
+    SlowConsumer,
+    // An operator disconnected the client; do not reconnect automatically
+    Kicked,
+}
+
+impl DisconnectReason {
+    pub const ALL: [DisconnectReason; 4] = [
+        DisconnectReason::ServerShutdown,
+        DisconnectReason::SessionExpired,
+        DisconnectReason::SlowConsumer,
+        DisconnectReason::Kicked,
+    ];
+
+    // Shutdown uses the standard 1001 Going Away; the rest are application codes in 4000-4999
+    pub fn close_code(self) -> u16 {
+        match self {
+            DisconnectReason::ServerShutdown => 1001,
+            DisconnectReason::SessionExpired => 4001,
+            DisconnectReason::SlowConsumer => 4002,
+            DisconnectReason::Kicked => 4003,
+        }
+    }
+
+    pub fn from_close_code(code: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|reason| reason.close_code() == code)
+    }
+
+    // The close frame's reason string, and the `reason` of the SSE event
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DisconnectReason::ServerShutdown => "server_shutdown",
+            DisconnectReason::SessionExpired => "session_expired",
+            DisconnectReason::SlowConsumer => "slow_consumer",
+            DisconnectReason::Kicked => "kicked",
+        }
+    }
+
+    pub fn should_reconnect(self) -> bool {
+        self != DisconnectReason::Kicked
+    }
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DisconnectReason::ServerShutdown => "Server shutting down",
+            DisconnectReason::SessionExpired => "Session expired",
+            DisconnectReason::SlowConsumer => "Client too slow to keep up with its updates",
+            DisconnectReason::Kicked => "Disconnected by an operator",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub enum Side {
     Bid,
     Ask,
@@ -82,7 +594,9 @@ pub struct SSESubscription {
     pub symbol: String,
     pub data_type: DataType,
     pub max_levels: u32,
+    pub max_orders: u32,
     pub client_id: Uuid,
+    pub priority: StreamPriority,
 }
 
 impl SSESubscription {
@@ -91,42 +605,235 @@ impl SSESubscription {
         symbol: String,
         data_type: DataType,
         max_levels: Option<u32>,
+        max_orders: Option<u32>,
         client_id: Uuid,
     ) -> Self {
+        let max_levels = max_levels.unwrap_or(20);
+
         Self {
             stream_id,
             symbol,
             data_type,
-            max_levels: max_levels.unwrap_or(20),
+            max_levels,
+            max_orders: max_orders.unwrap_or_else(|| default_max_orders(max_levels)),
             client_id,
+            priority: StreamPriority::Normal,
         }
     }
 }
 
 // SSE formatting helpers
 impl SSEMessage {
-    pub fn to_sse_data(&self) -> String {
-        match serde_json::to_string(self) {
-            Ok(json) => format!("data: {}\n\n", json),
-            Err(_) => "data: {\"event\":\"error\",\"message\":\"Serialization failed\"}\n\n".to_string(),
+    // The terminal error event of a connection the server closes
+    pub fn disconnected(reason: DisconnectReason) -> Self {
+        SSEMessage::Error {
+            code: reason.close_code().into(),
+            message: reason.to_string(),
+            stream_id: None,
+            terminal: true,
+            reason: Some(reason),
         }
     }
 
-    pub fn to_sse_event(&self) -> String {
-        let event_name = match self {
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, SSEMessage::Error { terminal: true, .. })
+    }
+
+    pub fn stream_id(&self) -> Option<&str> {
+        match self {
+            SSEMessage::MarketData { stream_id, .. }
+            | SSEMessage::Snapshot { stream_id, .. }
+            | SSEMessage::ReplayStarted { stream_id, .. }
+            | SSEMessage::ReplayComplete { stream_id } => Some(stream_id),
+            SSEMessage::Error { stream_id, .. } => stream_id.as_deref(),
+            _ => None,
+        }
+    }
+
+    pub fn event_name(&self) -> &'static str {
+        match self {
             SSEMessage::MarketData { .. } => "market_data",
+            SSEMessage::Snapshot { .. } => "snapshot",
+            SSEMessage::MarketSummary { .. } => "market_summary",
             SSEMessage::HeartBeat { .. } => "heartbeat",
             SSEMessage::ConnectionInfo { .. } => "connection_info",
+            SSEMessage::UsageReport { .. } => "usage_report",
+            SSEMessage::QuotaExceeded { .. } => "quota_exceeded",
+            SSEMessage::Throttled { .. } => "throttled",
+            SSEMessage::ReplayStarted { .. } => "replay_started",
+            SSEMessage::ReplayComplete { .. } => "replay_complete",
             SSEMessage::Error { .. } => "error",
-        };
+        }
+    }
+
+    pub fn to_sse_data(&self) -> String {
+        match serde_json::to_string(self) {
+            Ok(json) => format!("data: {}\n\n", json),
+            Err(_) => "data: {\"event\":\"error\",\"message\":\"Serialization failed\"}\n\n".to_string(),
+        }
+    }
 
+    pub fn to_sse_event(&self) -> String {
         match serde_json::to_string(self) {
-            Ok(json) => format!("event: {}\ndata: {}\n\n", event_name, json),
+            Ok(json) => format!("event: {}\ndata: {}\n\n", self.event_name(), json),
             Err(_) => "event: error\ndata: {\"message\":\"Serialization failed\"}\n\n".to_string(),
         }
     }
 }
 
+// Item queued for a client's event stream. Market data views are serialized once
+// per tick and the payload bytes are shared by every subscriber of the same view.
+#[derive(Debug, Clone)]
+pub enum SSEOutbound {
+    Message(SSEMessage),
+    SharedMarketData {
+        stream_id: String,
+        symbol: Arc<str>,
+        data: Bytes,
+        sequence: u64,
+        timestamp: DateTime<Utc>,
+        event_ts: DateTime<Utc>,
+    },
+}
+
+impl From<SSEMessage> for SSEOutbound {
+    fn from(message: SSEMessage) -> Self {
+        SSEOutbound::Message(message)
+    }
+}
+
+impl SSEOutbound {
+    pub fn stream_id(&self) -> Option<&str> {
+        match self {
+            SSEOutbound::Message(message) => message.stream_id(),
+            SSEOutbound::SharedMarketData { stream_id, .. } => Some(stream_id),
+        }
+    }
+
+    pub fn event_name(&self) -> &'static str {
+        match self {
+            SSEOutbound::Message(message) => message.event_name(),
+            SSEOutbound::SharedMarketData { .. } => "market_data",
+        }
+    }
+
+    // Market data and summaries, as opposed to control events; the part metered by quotas
+    pub fn is_stream_update(&self) -> bool {
+        matches!(
+            self,
+            SSEOutbound::SharedMarketData { .. }
+                | SSEOutbound::Message(
+                    SSEMessage::MarketData { .. } | SSEMessage::Snapshot { .. } | SSEMessage::MarketSummary { .. }
+                )
+        )
+    }
+
+    // Appends the same JSON as the equivalent SSEMessage::MarketData to `out`, stamping
+    // send_ts now and holding event_ts in order for the client's stream
+    pub fn write_json(&self, out: &mut Vec<u8>, ordering: &mut EventOrdering) -> serde_json::Result<()> {
+        match self {
+            SSEOutbound::Message(SSEMessage::MarketData { stream_id, symbol, data, sequence, timestamp, event_ts, .. }) => {
+                let data = serde_json::to_vec(data)?;
+                write_market_data(out, stream_id, symbol, &data, *sequence, timestamp, ordering.order(stream_id, *event_ts))
+            }
+            SSEOutbound::Message(message) => serde_json::to_writer(out, message),
+            SSEOutbound::SharedMarketData { stream_id, symbol, data, sequence, timestamp, event_ts } => {
+                write_market_data(out, stream_id, symbol, data, *sequence, timestamp, ordering.order(stream_id, *event_ts))
+            }
+        }
+    }
+}
+
+// Control events jump any backlog, then full-book snapshots; flow and reports wait
+// behind book updates
+impl Prioritized for SSEOutbound {
+    fn tier(&self) -> DeliveryTier {
+        match self {
+            SSEOutbound::SharedMarketData { .. } => DeliveryTier::Delta,
+            SSEOutbound::Message(SSEMessage::Snapshot { .. }) => DeliveryTier::Snapshot,
+            SSEOutbound::Message(SSEMessage::MarketData { data: MarketDataUpdate::AggressorFlow { .. }, .. }) => {
+                DeliveryTier::Analytics
+            }
+            SSEOutbound::Message(SSEMessage::MarketData { .. }) => DeliveryTier::Delta,
+            // Queued behind the replay's last update
+            SSEOutbound::Message(SSEMessage::ReplayComplete { .. }) => DeliveryTier::Delta,
+            SSEOutbound::Message(SSEMessage::MarketSummary { .. } | SSEMessage::UsageReport { .. }) => DeliveryTier::Analytics,
+            SSEOutbound::Message(_) => DeliveryTier::Control,
+        }
+    }
+
+    fn stream(&self) -> Option<&str> {
+        self.stream_id()
+    }
+
+    fn supersedes(&self) -> Option<&str> {
+        match self {
+            SSEOutbound::Message(SSEMessage::Snapshot { stream_id, .. }) => Some(stream_id),
+            _ => None,
+        }
+    }
+
+    // Live views are whole books, so a client that falls behind skips to the latest one.
+    // Replayed updates are all delivered.
+    fn conflates(&self) -> bool {
+        matches!(self, SSEOutbound::SharedMarketData { .. })
+    }
+}
+
+fn write_market_data(
+    out: &mut Vec<u8>,
+    stream_id: &str,
+    symbol: &str,
+    data: &[u8],
+    sequence: u64,
+    timestamp: &DateTime<Utc>,
+    event_ts: DateTime<Utc>,
+) -> serde_json::Result<()> {
+    out.extend_from_slice(br#"{"event":"market_data","stream_id":"#);
+    serde_json::to_writer(&mut *out, stream_id)?;
+    out.extend_from_slice(br#","symbol":"#);
+    serde_json::to_writer(&mut *out, symbol)?;
+    out.extend_from_slice(br#","data":"#);
+    out.extend_from_slice(data);
+    write!(out, r#","sequence":{},"timestamp":"#, sequence).map_err(serde_json::Error::io)?;
+    serde_json::to_writer(&mut *out, timestamp)?;
+    out.extend_from_slice(br#","event_ts":"#);
+    serde_json::to_writer(&mut *out, &event_ts)?;
+    out.extend_from_slice(br#","send_ts":"#);
+    serde_json::to_writer(&mut *out, &clock().now())?;
+    out.push(b'}');
+    Ok(())
+}
+
+// A stream a connection was subscribed to; pairs are published without a level limit
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct StreamDefinition {
+    pub stream_id: String,
+    pub symbol: String,
+    pub data_type: DataType,
+    pub max_levels: Option<u32>,
+}
+
+// A stream parameter strict validation refused; `index` is the entry's position in a
+// comma-separated `streams` or `symbols` list
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct StreamRejection {
+    pub parameter: String,
+    pub index: Option<usize>,
+    pub value: String,
+    pub error: String,
+}
+
+// Body of the 422 response to a strict request with invalid stream parameters
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct InvalidStreams {
+    pub error: String,
+    pub rejected: Vec<StreamRejection>,
+}
+
 // Query parameters for SSE endpoint
 #[derive(Debug, Deserialize)]
 pub struct StreamQuery {
@@ -134,43 +841,83 @@ pub struct StreamQuery {
     pub symbols: Option<String>, // Comma-separated symbols: "BTCUSD,ETHUSD"
     pub data_type: Option<String>, // Default data type: "MBP" or "MBO"
     pub max_levels: Option<u32>, // Default max levels
+    pub max_orders: Option<u32>, // Orders per side for MBO streams (default: 3 per level)
+    pub summary: Option<bool>, // Include periodic market_summary events
+    pub snapshot_interval_ms: Option<u64>, // Also send the whole book of each MBP and MBO stream this often
+    pub priority: Option<StreamPriority>, // Of every requested stream, Normal by default
+    pub strict: Option<bool>, // Refuse invalid stream parameters with 422 instead of coercing them
+    pub replay: Option<String>, // Symbol whose recorded history is replayed on the {SYMBOL}_REPLAY stream
+    pub replay_from: Option<DateTime<Utc>>,
+    pub replay_to: Option<DateTime<Utc>>,
+    pub replay_speed: Option<f64>, // Times faster than recorded, 1 by default
 }
 
 impl StreamQuery {
     pub fn parse_streams(&self) -> Vec<(String, DataType, u32)> {
-        let mut streams = Vec::new();
+        let data_type = self.get_default_data_type();
+        let max_levels = self.max_levels.unwrap_or(20);
 
         if let Some(stream_str) = &self.streams {
-            for stream_def in stream_str.split(',') {
-                let parts: Vec<&str> = stream_def.trim().split(':').collect();
-                if parts.len() >= 1 {
-                    let symbol = parts[0].to_string();
-                    let data_type = if parts.len() >= 2 {
-                        match parts[1].to_uppercase().as_str() {
-                            "MBO" => DataType::MBO,
-                            _ => DataType::MBP,
-                        }
-                    } else {
-                        self.get_default_data_type()
-                    };
-                    let max_levels = if parts.len() >= 3 {
-                        parts[2].parse().unwrap_or(self.max_levels.unwrap_or(20))
-                    } else {
-                        self.max_levels.unwrap_or(20)
-                    };
-                    streams.push((symbol, data_type, max_levels));
-                }
-            }
+            parse_stream_definitions(stream_str, data_type, max_levels)
         } else if let Some(symbols_str) = &self.symbols {
-            let data_type = self.get_default_data_type();
-            let max_levels = self.max_levels.unwrap_or(20);
+            symbols_str
+                .split(',')
+                .map(str::trim)
+                .filter(|symbol| !symbol.is_empty())
+                .map(|symbol| (symbol.to_string(), data_type.clone(), max_levels))
+                .collect()
+        } else {
+            Vec::new()
+        }
+    }
+
+    // Like parse_streams, but every value it would coerce or ignore is rejected instead
+    pub fn parse_streams_strict(&self) -> Result<Vec<(String, DataType, u32)>, Vec<StreamRejection>> {
+        let mut rejected = Vec::new();
+        let reject = |parameter: &str, value: &str, error: String| StreamRejection {
+            parameter: parameter.to_string(),
+            index: None,
+            value: value.to_string(),
+            error,
+        };
 
-            for symbol in symbols_str.split(',') {
-                streams.push((symbol.trim().to_string(), data_type.clone(), max_levels));
+        let data_type = match self.data_type.as_deref() {
+            None | Some("MBP") => DataType::MBP,
+            Some("MBO") => DataType::MBO,
+            Some(other) => {
+                rejected.push(reject("data_type", other, format!("Unknown data type '{}', expected MBP or MBO", other)));
+                DataType::MBP
             }
+        };
+        let max_levels = self.max_levels.unwrap_or(20);
+        if let Err(e) = validate_levels(max_levels) {
+            rejected.push(reject("max_levels", &max_levels.to_string(), e));
         }
 
-        streams
+        let streams = if let Some(stream_str) = &self.streams {
+            if let Some(symbols_str) = &self.symbols {
+                rejected.push(reject("symbols", symbols_str, "symbols is ignored when streams is given".to_string()));
+            }
+            parse_stream_definitions_strict("streams", stream_str, &data_type, max_levels)
+        } else if let Some(symbols_str) = &self.symbols {
+            let definitions = symbols_str.split(',').enumerate().map(|(index, symbol)| {
+                validate_symbol(symbol.trim())
+                    .map(|()| (symbol.trim().to_string(), data_type.clone(), max_levels))
+                    .map_err(|error| StreamRejection { index: Some(index), ..reject("symbols", symbol, error) })
+            });
+            collect_definitions(definitions)
+        } else {
+            Ok(Vec::new())
+        };
+
+        match streams {
+            Ok(streams) if rejected.is_empty() => Ok(streams),
+            Ok(_) => Err(rejected),
+            Err(stream_rejections) => {
+                rejected.extend(stream_rejections);
+                Err(rejected)
+            }
+        }
     }
 
     fn get_default_data_type(&self) -> DataType {
@@ -181,20 +928,146 @@ impl StreamQuery {
     }
 }
 
+// Comma-separated symbol:type:levels definitions, such as "BTCUSD:MBP:20,ETHUSD:MBO:10".
+// Type and levels fall back to the given defaults when left out or unrecognised.
+pub fn parse_stream_definitions(definitions: &str, default_type: DataType, default_levels: u32) -> Vec<(String, DataType, u32)> {
+    let mut streams = Vec::new();
+
+    for stream_def in definitions.split(',') {
+        let parts: Vec<&str> = stream_def.trim().split(':').collect();
+        if parts[0].is_empty() {
+            continue;
+        }
+
+        let symbol = parts[0].to_string();
+        let data_type = if parts.len() >= 2 {
+            match parts[1].to_uppercase().as_str() {
+                "MBO" => DataType::MBO,
+                "FLOW" => DataType::AggressorFlow,
+                _ => DataType::MBP,
+            }
+        } else {
+            default_type.clone()
+        };
+        let max_levels = if parts.len() >= 3 {
+            parts[2].parse().unwrap_or(default_levels)
+        } else {
+            default_levels
+        };
+        streams.push((symbol, data_type, max_levels));
+    }
+
+    streams
+}
+
+// The strict counterpart of parse_stream_definitions: a malformed definition, an unknown
+// type, a malformed symbol or levels outside 1-500 reject the entry
+pub fn parse_stream_definitions_strict(
+    parameter: &str,
+    definitions: &str,
+    default_type: &DataType,
+    default_levels: u32,
+) -> Result<Vec<(String, DataType, u32)>, Vec<StreamRejection>> {
+    let parsed = definitions.split(',').enumerate().map(|(index, definition)| {
+        parse_stream_definition_strict(definition.trim(), default_type, default_levels).map_err(|error| StreamRejection {
+            parameter: parameter.to_string(),
+            index: Some(index),
+            value: definition.to_string(),
+            error,
+        })
+    });
+    collect_definitions(parsed)
+}
+
+fn parse_stream_definition_strict(definition: &str, default_type: &DataType, default_levels: u32) -> Result<(String, DataType, u32), String> {
+    let parts: Vec<&str> = definition.split(':').collect();
+    if parts.len() > 3 {
+        return Err(format!("Expected SYMBOL[:TYPE[:LEVELS]], got {} fields", parts.len()));
+    }
+
+    validate_symbol(parts[0])?;
+    let data_type = match parts.get(1).map(|name| name.to_uppercase()) {
+        None => default_type.clone(),
+        Some(name) => match name.as_str() {
+            "MBP" => DataType::MBP,
+            "MBO" => DataType::MBO,
+            "FLOW" => DataType::AggressorFlow,
+            _ => return Err(format!("Unknown data type '{}', expected MBP, MBO or FLOW", parts[1])),
+        },
+    };
+    let max_levels = match parts.get(2) {
+        None => default_levels,
+        Some(levels) => levels.parse().map_err(|_| format!("max_levels '{}' is not a whole number", levels))?,
+    };
+    validate_levels(max_levels)?;
+
+    Ok((parts[0].to_string(), data_type, max_levels))
+}
+
+// Every definition, or the rejections of those that failed
+fn collect_definitions(
+    definitions: impl Iterator<Item = Result<(String, DataType, u32), StreamRejection>>,
+) -> Result<Vec<(String, DataType, u32)>, Vec<StreamRejection>> {
+    let (streams, rejected): (Vec<_>, Vec<_>) = definitions.partition(Result::is_ok);
+    if rejected.is_empty() {
+        Ok(streams.into_iter().flatten().collect())
+    } else {
+        Err(rejected.into_iter().filter_map(Result::err).collect())
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct OrderActivity {
     pub activity_type: ActivityType,
-    pub order_id: String,
-    pub symbol: String,
+    pub order_id: OrderId,
+    pub symbol: Arc<str>,
     pub price: Option<f64>,
-    pub quantity: Option<u64>,
+    pub quantity: Option<Quantity>,
     pub side: Option<Side>,
     pub timestamp: DateTime<Utc>,
+    pub actor: Option<ActorClass>,
+}
+
+// Simulated participant an order is attributed to with --research-actor-tags: makers join
+// or improve the touch, takers post marketable orders and noise traders rest behind it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum ActorClass {
+    Maker,
+    Taker,
+    Noise,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub enum ActivityType {
     Add,
     Update,
     Cancel,
+    Fill, // Resting order traded against; quantity is the remaining size
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct Trade {
+    pub trade_id: String,
+    pub symbol: Arc<str>,
+    pub price: f64,
+    pub quantity: Quantity,
+    pub aggressor_side: Side,
+    pub maker_order_id: OrderId,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct AggressorFlowStats {
+    pub window_secs: u64,
+    pub buy_volume: Quantity,
+    pub sell_volume: Quantity,
+    pub delta: f64,
+    pub cumulative_buy_volume: Quantity,
+    pub cumulative_sell_volume: Quantity,
+    pub cvd: f64,
+    pub trade_count: u64,
 }
\ No newline at end of file
@@ -0,0 +1,165 @@
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+use prometheus::{
+    exponential_buckets, Encoder, GaugeVec, Histogram, HistogramOpts, IntCounter, IntGauge,
+    IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+// Core server metrics exported in the Prometheus text format on /metrics
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    connected_clients: IntGauge,
+    subscriptions: IntGaugeVec,
+    messages_sent: IntCounter,
+    bytes_sent: IntCounter,
+    conflated_updates: IntCounter,
+    fanout_latency: Histogram,
+    queue_depth: Histogram,
+    tick_duration: Histogram,
+    feed_staleness: GaugeVec,
+    last_updates: DashMap<String, Instant>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let connected_clients = IntGauge::new(
+            "market_data_connected_clients",
+            "Number of connected clients",
+        ).unwrap();
+        let subscriptions = IntGaugeVec::new(
+            Opts::new("market_data_subscriptions", "Active subscriptions per symbol"),
+            &["symbol"],
+        ).unwrap();
+        let messages_sent = IntCounter::new(
+            "market_data_messages_sent_total",
+            "Messages delivered to clients",
+        ).unwrap();
+        let bytes_sent = IntCounter::new(
+            "market_data_bytes_sent_total",
+            "Serialized bytes delivered to clients",
+        ).unwrap();
+        let conflated_updates = IntCounter::new(
+            "market_data_conflated_updates_total",
+            "Queued book updates replaced by a later one of the same stream before being sent",
+        ).unwrap();
+        let fanout_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_fanout_latency_seconds",
+                "Time to fan a symbol update out to all of its subscribers",
+            )
+            .buckets(exponential_buckets(0.00005, 2.0, 14).unwrap()),
+        ).unwrap();
+        let queue_depth = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_client_queue_depth",
+                "Messages still queued for a client when one is dequeued for sending",
+            )
+            .buckets(vec![0.0, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0]),
+        ).unwrap();
+        let tick_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_simulation_tick_duration_seconds",
+                "Duration of one market simulation tick across all symbols",
+            )
+            .buckets(exponential_buckets(0.0001, 2.0, 14).unwrap()),
+        ).unwrap();
+        let feed_staleness = GaugeVec::new(
+            Opts::new(
+                "market_data_feed_staleness_seconds",
+                "Seconds since the order book of a symbol was last updated",
+            ),
+            &["symbol"],
+        ).unwrap();
+
+        registry.register(Box::new(connected_clients.clone())).unwrap();
+        registry.register(Box::new(subscriptions.clone())).unwrap();
+        registry.register(Box::new(messages_sent.clone())).unwrap();
+        registry.register(Box::new(bytes_sent.clone())).unwrap();
+        registry.register(Box::new(conflated_updates.clone())).unwrap();
+        registry.register(Box::new(fanout_latency.clone())).unwrap();
+        registry.register(Box::new(queue_depth.clone())).unwrap();
+        registry.register(Box::new(tick_duration.clone())).unwrap();
+        registry.register(Box::new(feed_staleness.clone())).unwrap();
+
+        Self {
+            registry,
+            connected_clients,
+            subscriptions,
+            messages_sent,
+            bytes_sent,
+            conflated_updates,
+            fanout_latency,
+            queue_depth,
+            tick_duration,
+            feed_staleness,
+            last_updates: DashMap::new(),
+        }
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.messages_sent.inc();
+        self.bytes_sent.inc_by(bytes as u64);
+    }
+
+    pub fn record_conflated(&self, updates: usize) {
+        self.conflated_updates.inc_by(updates as u64);
+    }
+
+    pub fn observe_fanout(&self, elapsed: Duration) {
+        self.fanout_latency.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_queue_depth(&self, depth: usize) {
+        self.queue_depth.observe(depth as f64);
+    }
+
+    pub fn observe_tick(&self, elapsed: Duration) {
+        self.tick_duration.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn mark_updated(&self, symbol: &str) {
+        self.last_updates.insert(symbol.to_string(), Instant::now());
+    }
+
+    // Drops per-symbol series of a symbol that no longer has a book
+    pub fn forget_symbol(&self, symbol: &str) {
+        self.last_updates.remove(symbol);
+        let _ = self.feed_staleness.remove_label_values(&[symbol]);
+    }
+
+    pub fn set_connected_clients(&self, count: usize) {
+        self.connected_clients.set(count as i64);
+    }
+
+    // Replaces all per-symbol subscription counts so removed symbols drop out
+    pub fn set_subscriptions<'a>(&self, counts: impl IntoIterator<Item = (&'a str, usize)>) {
+        self.subscriptions.reset();
+        for (symbol, count) in counts {
+            self.subscriptions.with_label_values(&[symbol]).set(count as i64);
+        }
+    }
+
+    pub fn render(&self) -> String {
+        for entry in self.last_updates.iter() {
+            self.feed_staleness
+                .with_label_values(&[entry.key()])
+                .set(entry.value().elapsed().as_secs_f64());
+        }
+
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            return format!("# failed to encode metrics: {}\n", e);
+        }
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
@@ -1,21 +1,37 @@
 use std::collections::{BTreeMap, HashMap};
+use std::ops::{Index, IndexMut};
+use std::sync::{Arc, Mutex};
+use arc_swap::ArcSwap;
 use chrono::{DateTime, Utc};
-use rand::{thread_rng, Rng};
+use rand::rngs::StdRng;
+use rand::{thread_rng, Rng, SeedableRng};
 
-use crate::message::{MBOLevel, MBPLevel, Side, OrderActivity, ActivityType};
+use crate::clock::{clock, ClockSource};
+use crate::message::{ActorClass, MBOLevel, MBPLevel, Side, OrderActivity, OrderId, ActivityType, Quantity, SymbolSummary, Trade};
+
+// Orders shown per price level when a subscriber doesn't set `max_orders`
+pub const DEFAULT_ORDERS_PER_LEVEL: u32 = 3;
+
+pub fn default_max_orders(max_levels: u32) -> u32 {
+    max_levels.saturating_mul(DEFAULT_ORDERS_PER_LEVEL)
+}
+
+// Simulated order prices are rounded to the cent
+pub const SIMULATED_TICK_SIZE: f64 = 0.01;
 
 #[derive(Debug, Clone)]
 pub struct Order {
-    pub id: String,
+    pub id: OrderId,
     pub price: f64,
-    pub quantity: u64,
+    pub quantity: Quantity,
     pub side: Side,
     pub timestamp: DateTime<Utc>,
-    pub original_quantity: u64,
+    pub original_quantity: Quantity,
+    pub actor: Option<ActorClass>, // Only set on simulated orders of books tagging actors
 }
 
 impl Order {
-    pub fn new(id: String, price: f64, quantity: u64, side: Side) -> Self {
+    pub fn new(id: OrderId, price: f64, quantity: Quantity, side: Side) -> Self {
         let timestamp = Utc::now();
         Self {
             id,
@@ -24,10 +40,16 @@ impl Order {
             side,
             timestamp,
             original_quantity: quantity,
+            actor: None,
         }
     }
 
-    pub fn update_quantity(&mut self, new_quantity: u64) {
+    pub fn with_actor(mut self, actor: Option<ActorClass>) -> Self {
+        self.actor = actor;
+        self
+    }
+
+    pub fn update_quantity(&mut self, new_quantity: Quantity) {
         self.quantity = new_quantity;
         self.timestamp = Utc::now();
     }
@@ -38,21 +60,274 @@ impl Order {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct OrderBook {
-    pub symbol: String,
-    orders: HashMap<String, Order>,
-    bids_by_price: BTreeMap<OrderedFloat, Vec<String>>,
-    asks_by_price: BTreeMap<OrderedFloat, Vec<String>>,
+    pub symbol: Arc<str>,
+    orders: OrderSlab,
+    // Each side is shared with published snapshots until it next changes
+    bids_by_price: Arc<BTreeMap<OrderedFloat, PriceLevel>>,
+    asks_by_price: Arc<BTreeMap<OrderedFloat, PriceLevel>>,
     sequence: u64,
+    epoch: u64, // Incarnation of the symbol's book; its sequence restarts with each one
+    event_timestamp: DateTime<Utc>, // When the sequence last advanced
+    open_price: Option<f64>,
+    volume: Quantity,
+    trade_count: u64,
+    pending_trades: Vec<Trade>,
+    next_order_id: u64,
+    order_limit: Option<usize>,
+    quantity_decimals: u32,
+    rng: Option<StdRng>, // Seeded for a reproducible simulation; thread RNG otherwise
+    actor_tags: bool,
+}
+
+// Arena of resting orders addressed by slot index. Freed slots are reused through
+// a free list, and each slot carries the links of its price level's FIFO queue.
+#[derive(Debug, Clone, Default)]
+struct OrderSlab {
+    slots: SlotChunks,
+    free_head: Option<usize>,
+    index: OrderIndex,
+}
+
+// Slots per chunk of a slab, and bits of an order id's hash choosing its index shard
+const SLOT_CHUNK: usize = 64;
+const INDEX_SHARD_BITS: u32 = 8;
+
+// A slab's slots in fixed-size chunks that copies of the book share, so publishing a
+// snapshot copies one pointer per chunk, and a write after it copies only its chunk
+#[derive(Debug, Clone, Default)]
+struct SlotChunks {
+    chunks: Vec<Arc<Vec<Slot>>>,
+    len: usize,
+}
+
+impl SlotChunks {
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn push(&mut self, entry: Slot) {
+        if self.len.is_multiple_of(SLOT_CHUNK) {
+            self.chunks.push(Arc::new(Vec::with_capacity(SLOT_CHUNK)));
+        }
+        let chunk = self.chunks.last_mut().expect("a chunk has room for the slot");
+        Arc::make_mut(chunk).push(entry);
+        self.len += 1;
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &Slot> {
+        self.chunks.iter().flat_map(|chunk| chunk.iter())
+    }
+}
+
+impl Index<usize> for SlotChunks {
+    type Output = Slot;
+
+    fn index(&self, slot: usize) -> &Slot {
+        &self.chunks[slot / SLOT_CHUNK][slot % SLOT_CHUNK]
+    }
+}
+
+impl IndexMut<usize> for SlotChunks {
+    fn index_mut(&mut self, slot: usize) -> &mut Slot {
+        &mut Arc::make_mut(&mut self.chunks[slot / SLOT_CHUNK])[slot % SLOT_CHUNK]
+    }
+}
+
+// Slot of each order id, in shards shared between copies of the book like the slots
+#[derive(Debug, Clone, Default)]
+struct OrderIndex {
+    // Empty until the first order, then 2^INDEX_SHARD_BITS shards
+    shards: Vec<Arc<HashMap<OrderId, usize>>>,
+    len: usize,
+}
+
+impl OrderIndex {
+    // Fibonacci hashing, so ids a feed hands out in strides still spread over the shards
+    fn shard(order_id: OrderId) -> usize {
+        (order_id.0.wrapping_mul(0x9E37_79B9_7F4A_7C15) >> (u64::BITS - INDEX_SHARD_BITS)) as usize
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn get(&self, order_id: &OrderId) -> Option<&usize> {
+        self.shards.get(Self::shard(*order_id))?.get(order_id)
+    }
+
+    fn contains_key(&self, order_id: &OrderId) -> bool {
+        self.get(order_id).is_some()
+    }
+
+    fn insert(&mut self, order_id: OrderId, slot: usize) {
+        if self.shards.is_empty() {
+            self.shards = (0..1 << INDEX_SHARD_BITS).map(|_| Arc::default()).collect();
+        }
+        if Arc::make_mut(&mut self.shards[Self::shard(order_id)]).insert(order_id, slot).is_none() {
+            self.len += 1;
+        }
+    }
+
+    fn remove(&mut self, order_id: &OrderId) {
+        // Looked up first, so removing an unknown id copies no shard
+        if !self.contains_key(order_id) {
+            return;
+        }
+        Arc::make_mut(&mut self.shards[Self::shard(*order_id)]).remove(order_id);
+        self.len -= 1;
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Slot {
+    order: Option<Order>,
+    prev: Option<usize>,
+    next: Option<usize>, // Next order in the level queue, or next free slot
+}
+
+impl OrderSlab {
+    fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    fn slot_of(&self, order_id: OrderId) -> Option<usize> {
+        self.index.get(&order_id).copied()
+    }
+
+    fn find(&self, order_id: OrderId) -> Option<&Order> {
+        self.slot_of(order_id).map(|slot| self.get(slot))
+    }
+
+    // In slot order rather than the index's, so a seeded simulation picks the same orders
+    fn ids(&self) -> impl Iterator<Item = OrderId> + '_ {
+        self.slots.iter().filter_map(|slot| slot.order.as_ref().map(|order| order.id))
+    }
+
+    fn get(&self, slot: usize) -> &Order {
+        self.slots[slot].order.as_ref().expect("order slot is occupied")
+    }
+
+    fn get_mut(&mut self, slot: usize) -> &mut Order {
+        self.slots[slot].order.as_mut().expect("order slot is occupied")
+    }
+
+    fn insert(&mut self, order: Order) -> usize {
+        let order_id = order.id;
+        let entry = Slot { order: Some(order), prev: None, next: None };
+
+        let slot = match self.free_head {
+            Some(slot) => {
+                self.free_head = self.slots[slot].next;
+                self.slots[slot] = entry;
+                slot
+            }
+            None => {
+                self.slots.push(entry);
+                self.slots.len() - 1
+            }
+        };
+
+        self.index.insert(order_id, slot);
+        slot
+    }
+
+    // The slot must already be unlinked from its price level
+    fn remove(&mut self, slot: usize) -> Order {
+        let order = self.slots[slot].order.take().expect("order slot is occupied");
+        self.index.remove(&order.id);
+
+        self.slots[slot].prev = None;
+        self.slots[slot].next = self.free_head;
+        self.free_head = Some(slot);
+        order
+    }
+}
+
+// Orders resting at one price as an intrusive FIFO list over the slab, with aggregates
+// kept up to date on every add/update/cancel so MBP snapshots don't walk the orders
+#[derive(Debug, Clone, Default)]
+struct PriceLevel {
+    head: Option<usize>,
+    tail: Option<usize>,
+    order_count: usize,
+    total_quantity: Quantity,
+    timestamp_sum_ms: i64,
+}
+
+impl PriceLevel {
+    fn is_empty(&self) -> bool {
+        self.order_count == 0
+    }
+
+    fn push_back(&mut self, orders: &mut OrderSlab, slot: usize) {
+        orders.slots[slot].prev = self.tail;
+        orders.slots[slot].next = None;
+        match self.tail {
+            Some(tail) => orders.slots[tail].next = Some(slot),
+            None => self.head = Some(slot),
+        }
+        self.tail = Some(slot);
+
+        let order = orders.get(slot);
+        self.order_count += 1;
+        self.total_quantity += order.quantity;
+        self.timestamp_sum_ms += order.timestamp.timestamp_millis();
+    }
+
+    fn unlink(&mut self, orders: &mut OrderSlab, slot: usize) {
+        let (prev, next) = (orders.slots[slot].prev, orders.slots[slot].next);
+        match prev {
+            Some(prev) => orders.slots[prev].next = next,
+            None => self.head = next,
+        }
+        match next {
+            Some(next) => orders.slots[next].prev = prev,
+            None => self.tail = prev,
+        }
+
+        let order = orders.get(slot);
+        self.order_count -= 1;
+        self.total_quantity -= order.quantity;
+        self.timestamp_sum_ms -= order.timestamp.timestamp_millis();
+    }
+
+    fn iter<'a>(&self, orders: &'a OrderSlab) -> impl Iterator<Item = &'a Order> + 'a {
+        std::iter::successors(self.head, move |&slot| orders.slots[slot].next)
+            .map(move |slot| orders.get(slot))
+    }
+
+    fn avg_age_ms(&self, now_ms: i64) -> u64 {
+        if self.order_count == 0 {
+            return 0;
+        }
+
+        let count = self.order_count as i64;
+        (now_ms - self.timestamp_sum_ms / count).max(0) as u64
+    }
 }
 
 // Wrapper for f64 to make it Ord for BTreeMap
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 struct OrderedFloat(f64);
 
 impl Eq for OrderedFloat {}
 
+impl PartialOrd for OrderedFloat {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
 impl Ord for OrderedFloat {
     fn cmp(&self, other: &Self) -> std::cmp::Ordering {
         self.0.partial_cmp(&other.0).unwrap_or(std::cmp::Ordering::Equal)
@@ -68,130 +343,281 @@ impl From<f64> for OrderedFloat {
 impl OrderBook {
     pub fn new(symbol: String) -> Self {
         Self {
-            symbol,
-            orders: HashMap::new(),
-            bids_by_price: BTreeMap::new(),
-            asks_by_price: BTreeMap::new(),
+            symbol: symbol.into(),
+            orders: OrderSlab::default(),
+            bids_by_price: Arc::default(),
+            asks_by_price: Arc::default(),
             sequence: 0,
+            epoch: 0,
+            event_timestamp: clock().now(),
+            open_price: None,
+            volume: Quantity::ZERO,
+            trade_count: 0,
+            pending_trades: Vec::new(),
+            next_order_id: 1,
+            order_limit: None,
+            quantity_decimals: 0,
+            rng: None,
+            actor_tags: false,
         }
     }
 
+    // Attributes simulated orders to an actor class, reported on MBO data and order
+    // activity. The simulation itself is unchanged, so seeded books stay reproducible.
+    pub fn set_actor_tags(&mut self, enabled: bool) {
+        self.actor_tags = enabled;
+    }
+
+    pub fn set_epoch(&mut self, epoch: u64) {
+        self.epoch = epoch;
+    }
+
+    // Caps the number of resting orders; new orders are rejected once the book is full
+    pub fn set_order_limit(&mut self, limit: usize) {
+        self.order_limit = Some(limit);
+    }
+
+    // Sizes are whole multiples of 10^-decimals; simulated orders are sized in these lots
+    pub fn set_quantity_decimals(&mut self, decimals: u32) {
+        self.quantity_decimals = decimals.min(Quantity::DECIMALS);
+    }
+
+    pub fn quantity_decimals(&self) -> u32 {
+        self.quantity_decimals
+    }
+
+    // Sample data and simulated activity are drawn from a generator with this seed, so the
+    // same seed gives the same order flow
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Some(StdRng::seed_from_u64(seed));
+    }
+
+    fn with_rng<R>(&mut self, apply: impl FnOnce(&mut Self, &mut dyn rand::RngCore) -> R) -> R {
+        match self.rng.take() {
+            Some(mut rng) => {
+                let result = apply(self, &mut rng);
+                self.rng = Some(rng);
+                result
+            }
+            None => apply(self, &mut thread_rng()),
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.order_limit.is_some_and(|limit| self.orders.len() >= limit)
+    }
+
+    pub fn next_order_id(&mut self) -> OrderId {
+        let id = OrderId(self.next_order_id);
+        self.next_order_id += 1;
+        id
+    }
+
     pub fn add_order(&mut self, order: Order) -> bool {
-        if self.orders.contains_key(&order.id) {
-            self.remove_order(&order.id);
+        if self.orders.slot_of(order.id).is_some() {
+            self.remove_order(order.id);
+        } else if self.is_full() {
+            return false;
         }
 
         let price_key = OrderedFloat::from(order.price);
-        let order_id = order.id.clone();
         let side = order.side.clone();
+        let slot = self.orders.insert(order);
 
-        self.orders.insert(order_id.clone(), order);
-
-        match side {
-            Side::Bid => {
-                self.bids_by_price
-                    .entry(price_key)
-                    .or_insert_with(Vec::new)
-                    .push(order_id);
-            }
-            Side::Ask => {
-                self.asks_by_price
-                    .entry(price_key)
-                    .or_insert_with(Vec::new)
-                    .push(order_id);
-            }
-        }
+        let levels = match side {
+            Side::Bid => Arc::make_mut(&mut self.bids_by_price),
+            Side::Ask => Arc::make_mut(&mut self.asks_by_price),
+        };
+        levels.entry(price_key).or_default().push_back(&mut self.orders, slot);
 
-        self.sequence += 1;
+        self.advance_sequence();
         true
     }
 
-    pub fn remove_order(&mut self, order_id: &str) -> bool {
-        if let Some(order) = self.orders.remove(order_id) {
-            let price_key = OrderedFloat::from(order.price);
+    pub fn get_order(&self, order_id: OrderId) -> Option<&Order> {
+        self.orders.find(order_id)
+    }
 
-            match order.side {
-                Side::Bid => {
-                    if let Some(orders_at_price) = self.bids_by_price.get_mut(&price_key) {
-                        orders_at_price.retain(|id| id != order_id);
-                        if orders_at_price.is_empty() {
-                            self.bids_by_price.remove(&price_key);
-                        }
-                    }
-                }
-                Side::Ask => {
-                    if let Some(orders_at_price) = self.asks_by_price.get_mut(&price_key) {
-                        orders_at_price.retain(|id| id != order_id);
-                        if orders_at_price.is_empty() {
-                            self.asks_by_price.remove(&price_key);
-                        }
-                    }
-                }
-            }
+    pub fn remove_order(&mut self, order_id: OrderId) -> bool {
+        let Some(slot) = self.orders.slot_of(order_id) else {
+            return false;
+        };
 
-            self.sequence += 1;
-            true
-        } else {
-            false
+        let order = self.orders.get(slot);
+        let price_key = OrderedFloat::from(order.price);
+        let levels = match order.side {
+            Side::Bid => Arc::make_mut(&mut self.bids_by_price),
+            Side::Ask => Arc::make_mut(&mut self.asks_by_price),
+        };
+
+        if let Some(level) = levels.get_mut(&price_key) {
+            level.unlink(&mut self.orders, slot);
+            if level.is_empty() {
+                levels.remove(&price_key);
+            }
         }
+
+        self.orders.remove(slot);
+        self.advance_sequence();
+        true
     }
 
-    pub fn update_order(&mut self, order_id: &str, new_quantity: u64) -> bool {
-        if new_quantity == 0 {
+    pub fn update_order(&mut self, order_id: OrderId, new_quantity: Quantity) -> bool {
+        if new_quantity.is_zero() {
             return self.remove_order(order_id);
         }
 
-        if let Some(order) = self.orders.get_mut(order_id) {
-            order.update_quantity(new_quantity);
-            self.sequence += 1;
-            true
+        self.set_resting_quantity(order_id, new_quantity, true)
+    }
+
+    // Changes the quantity of a resting order and keeps its price level aggregate in step.
+    // Fills keep the order's timestamp (and queue position); client updates refresh it.
+    fn set_resting_quantity(&mut self, order_id: OrderId, quantity: Quantity, refresh_timestamp: bool) -> bool {
+        let Some(slot) = self.orders.slot_of(order_id) else {
+            return false;
+        };
+
+        let order = self.orders.get_mut(slot);
+        let previous_quantity = order.quantity;
+        let previous_timestamp_ms = order.timestamp.timestamp_millis();
+        if refresh_timestamp {
+            order.update_quantity(quantity);
         } else {
-            false
+            order.quantity = quantity;
         }
+
+        let price_key = OrderedFloat::from(order.price);
+        let timestamp_delta_ms = order.timestamp.timestamp_millis() - previous_timestamp_ms;
+        let levels = match order.side {
+            Side::Bid => Arc::make_mut(&mut self.bids_by_price),
+            Side::Ask => Arc::make_mut(&mut self.asks_by_price),
+        };
+
+        if let Some(level) = levels.get_mut(&price_key) {
+            level.total_quantity = level.total_quantity - previous_quantity + quantity;
+            level.timestamp_sum_ms += timestamp_delta_ms;
+        }
+
+        self.advance_sequence();
+        true
     }
 
-    pub fn get_mbo_data(&self, max_levels: u32) -> (Vec<MBOLevel>, Vec<MBOLevel>) {
-        let bids = self.get_mbo_side(&Side::Bid, max_levels);
-        let asks = self.get_mbo_side(&Side::Ask, max_levels);
-        (bids, asks)
+    // Matches an aggressive order against the opposite side in price-time priority.
+    // Returns one Fill activity per resting order touched; trades are queued for take_trades().
+    pub fn execute_market_order(&mut self, aggressor_side: Side, quantity: Quantity) -> Vec<OrderActivity> {
+        let mut fills = Vec::new();
+        let mut remaining = quantity;
+
+        while !remaining.is_zero() {
+            let best_level = match aggressor_side {
+                Side::Bid => self.asks_by_price.iter().next(),      // Buyers lift the lowest ask
+                Side::Ask => self.bids_by_price.iter().next_back(), // Sellers hit the highest bid
+            };
+
+            let Some(slot) = best_level.and_then(|(_, level)| level.head) else {
+                break;
+            };
+
+            let (fill_quantity, fill) = self.fill_slot(slot, remaining, &aggressor_side);
+            remaining -= fill_quantity;
+            fills.push(fill);
+        }
+
+        fills
     }
 
-    fn get_mbo_side(&self, side: &Side, max_levels: u32) -> Vec<MBOLevel> {
-        let price_map = match side {
-            Side::Bid => &self.bids_by_price,
-            Side::Ask => &self.asks_by_price,
+    // Trades up to `quantity` against one named resting order, as exchanges report
+    // executions. Returns None when the order is not resting.
+    pub fn execute_against_order(&mut self, order_id: OrderId, quantity: Quantity) -> Option<OrderActivity> {
+        let slot = self.orders.slot_of(order_id)?;
+        let aggressor_side = match self.orders.get(slot).side {
+            Side::Bid => Side::Ask,
+            Side::Ask => Side::Bid,
         };
 
-        let mut result = Vec::new();
-        let mut levels_count = 0;
+        Some(self.fill_slot(slot, quantity, &aggressor_side).1)
+    }
 
-        let prices: Box<dyn Iterator<Item = _>> = match side {
-            Side::Bid => Box::new(price_map.iter().rev()), // Bids: highest to lowest
-            Side::Ask => Box::new(price_map.iter()),        // Asks: lowest to highest
+    // Fills the resting order in `slot` and queues the trade; returns the filled quantity
+    fn fill_slot(&mut self, slot: usize, quantity: Quantity, aggressor_side: &Side) -> (Quantity, OrderActivity) {
+        let resting = self.orders.get(slot);
+        let order_id = resting.id;
+        let fill_quantity = quantity.min(resting.quantity);
+        let price = resting.price;
+        let resting_side = resting.side.clone();
+        let actor = resting.actor;
+        let left = resting.quantity - fill_quantity;
+
+        if left.is_zero() {
+            self.remove_order(order_id);
+        } else {
+            self.set_resting_quantity(order_id, left, false);
+        }
+
+        let timestamp = Utc::now();
+        self.trade_count += 1;
+        self.volume += fill_quantity;
+        self.pending_trades.push(Trade {
+            trade_id: format!("{}-{}", self.symbol, self.trade_count),
+            symbol: self.symbol.clone(),
+            price,
+            quantity: fill_quantity,
+            aggressor_side: aggressor_side.clone(),
+            maker_order_id: order_id,
+            timestamp,
+        });
+
+        let fill = OrderActivity {
+            activity_type: ActivityType::Fill,
+            order_id,
+            symbol: self.symbol.clone(),
+            price: Some(price),
+            quantity: if left.is_zero() { None } else { Some(left) },
+            side: Some(resting_side),
+            timestamp,
+            actor,
         };
+        (fill_quantity, fill)
+    }
 
-        for (&_price_key, order_ids) in prices {
-            if levels_count >= max_levels {
-                break;
-            }
+    pub fn take_trades(&mut self) -> Vec<Trade> {
+        std::mem::take(&mut self.pending_trades)
+    }
 
-            for order_id in order_ids {
-                if let Some(order) = self.orders.get(order_id) {
-                    result.push(MBOLevel {
-                        order_id: order.id.clone(),
-                        price: order.price,
-                        quantity: order.quantity,
-                        side: order.side.clone(),
-                        timestamp: order.timestamp,
-                        age_ms: order.age_ms(),
-                    });
-                }
-            }
-            levels_count += 1;
-        }
+    // Up to `max_orders` orders per side, taken in price-time priority from the best `max_levels` levels
+    pub fn get_mbo_data(&self, max_levels: u32, max_orders: u32) -> (Vec<MBOLevel>, Vec<MBOLevel>) {
+        let mut bids = Vec::new();
+        let mut asks = Vec::new();
+        self.write_mbo_side(&Side::Bid, max_levels, max_orders, &mut bids);
+        self.write_mbo_side(&Side::Ask, max_levels, max_orders, &mut asks);
+        (bids, asks)
+    }
 
-        result.truncate((max_levels * 3) as usize); // Limit total orders shown
-        result
+    // Appends to a caller-provided buffer, reserving exactly the number of orders written
+    pub fn write_mbo_side(&self, side: &Side, max_levels: u32, max_orders: u32, out: &mut Vec<MBOLevel>) {
+        let available: usize = self.best_levels(side)
+            .take(max_levels as usize)
+            .map(|level| level.order_count)
+            .sum();
+        let count = available.min(max_orders as usize);
+        out.reserve_exact(count);
+
+        let orders = self.best_levels(side)
+            .take(max_levels as usize)
+            .flat_map(|level| level.iter(&self.orders))
+            .take(count);
+
+        for order in orders {
+            out.push(MBOLevel {
+                order_id: order.id,
+                price: order.price,
+                quantity: order.quantity,
+                side: order.side.clone(),
+                timestamp: order.timestamp,
+                age_ms: order.age_ms(),
+                actor: order.actor,
+            });
+        }
     }
 
     pub fn get_mbp_data(&self, max_levels: u32) -> (Vec<MBPLevel>, Vec<MBPLevel>) {
@@ -206,45 +632,60 @@ impl OrderBook {
             Side::Ask => &self.asks_by_price,
         };
 
-        let mut result = Vec::new();
-        let mut cumulative_quantity = 0;
+        let mut result = Vec::with_capacity(price_map.len().min(max_levels as usize));
+        let mut cumulative_quantity = Quantity::ZERO;
 
         let prices: Box<dyn Iterator<Item = _>> = match side {
             Side::Bid => Box::new(price_map.iter().rev()), // Bids: highest to lowest
             Side::Ask => Box::new(price_map.iter()),        // Asks: lowest to highest
         };
 
-        for (&price_key, order_ids) in prices.take(max_levels as usize) {
-            let orders: Vec<&Order> = order_ids
-                .iter()
-                .filter_map(|id| self.orders.get(id))
-                .collect();
-
-            if !orders.is_empty() {
-                let quantity: u64 = orders.iter().map(|o| o.quantity).sum();
-                let order_count = orders.len() as u32;
-                let avg_age_ms = if !orders.is_empty() {
-                    orders.iter().map(|o| o.age_ms()).sum::<u64>() / orders.len() as u64
-                } else {
-                    0
-                };
-
-                cumulative_quantity += quantity;
-
-                result.push(MBPLevel {
-                    price: price_key.0,
-                    quantity,
-                    order_count,
-                    side: side.clone(),
-                    total_quantity: cumulative_quantity,
-                    avg_age_ms,
-                });
-            }
+        let now_ms = Utc::now().timestamp_millis();
+
+        for (&price_key, level) in prices.take(max_levels as usize) {
+            cumulative_quantity += level.total_quantity;
+
+            result.push(MBPLevel {
+                price: price_key.0,
+                quantity: level.total_quantity,
+                order_count: level.order_count as u32,
+                side: side.clone(),
+                total_quantity: cumulative_quantity,
+                avg_age_ms: level.avg_age_ms(now_ms),
+            });
         }
 
         result
     }
 
+    // Price, size and order count of each level on a side priced within [low, high], lowest first
+    pub fn levels_between(&self, side: &Side, low: f64, high: f64) -> impl Iterator<Item = (f64, Quantity, u32)> + '_ {
+        let price_map = match side {
+            Side::Bid => &self.bids_by_price,
+            Side::Ask => &self.asks_by_price,
+        };
+
+        (low <= high)
+            .then(|| price_map.range(OrderedFloat::from(low)..=OrderedFloat::from(high)))
+            .into_iter()
+            .flatten()
+            .map(|(price_key, level)| (price_key.0, level.total_quantity, level.order_count as u32))
+    }
+
+    // Orders of the best `max_levels` levels on a side in price-time priority
+    pub fn resting_orders(&self, side: &Side, max_levels: u32) -> impl Iterator<Item = &Order> + '_ {
+        self.best_levels(side)
+            .take(max_levels as usize)
+            .flat_map(|level| level.iter(&self.orders))
+    }
+
+    fn best_levels(&self, side: &Side) -> Box<dyn Iterator<Item = &PriceLevel> + '_> {
+        match side {
+            Side::Bid => Box::new(self.bids_by_price.values().rev()), // Bids: highest to lowest
+            Side::Ask => Box::new(self.asks_by_price.values()),        // Asks: lowest to highest
+        }
+    }
+
     pub fn get_best_bid_ask(&self) -> (Option<f64>, Option<f64>) {
         let best_bid = self.bids_by_price.keys().next_back().map(|k| k.0);
         let best_ask = self.asks_by_price.keys().next().map(|k| k.0);
@@ -264,13 +705,42 @@ impl OrderBook {
         }
     }
 
+    pub fn get_symbol_summary(&self) -> SymbolSummary {
+        let (spread, mid_price, spread_bps) = self.get_spread_info();
+        let change_pct = match (self.open_price, mid_price) {
+            (Some(open), Some(mid)) if open > 0.0 => Some((mid - open) / open * 100.0),
+            _ => None,
+        };
+
+        SymbolSummary {
+            symbol: self.symbol.to_string(),
+            last_price: mid_price,
+            open_price: self.open_price,
+            change_pct,
+            volume: self.volume,
+            spread,
+            spread_bps,
+        }
+    }
+
     pub fn simulate_activity(&mut self) -> Vec<OrderActivity> {
+        self.with_rng(|book, rng| book.simulate_activity_with(rng))
+    }
+
+    fn simulate_activity_with(&mut self, mut rng: &mut dyn rand::RngCore) -> Vec<OrderActivity> {
         let mut activities = Vec::new();
-        let mut rng = thread_rng();
 
         let num_activities = rng.gen_range(1..=8);
 
         for _ in 0..num_activities {
+            // 15% aggressive orders that trade against the book
+            if rng.gen::<f64>() < 0.15 && !self.orders.is_empty() {
+                let side = if rng.gen() { Side::Bid } else { Side::Ask };
+                let quantity = Quantity::from_lots(rng.gen_range(500..=5000), self.quantity_decimals);
+                activities.extend(self.execute_market_order(side, quantity));
+                continue;
+            }
+
             let activity = self.generate_random_activity(&mut rng);
             activities.push(activity.clone());
             self.execute_activity(&activity);
@@ -279,7 +749,7 @@ impl OrderBook {
         activities
     }
 
-    fn generate_random_activity(&self, rng: &mut impl Rng) -> OrderActivity {
+    fn generate_random_activity(&mut self, rng: &mut impl Rng) -> OrderActivity {
         let (best_bid, best_ask) = self.get_best_bid_ask();
         let mid_price = match (best_bid, best_ask) {
             (Some(bid), Some(ask)) => (bid + ask) / 2.0,
@@ -289,8 +759,8 @@ impl OrderBook {
         let activity_type_rand = rng.gen::<f64>();
         let side = if rng.gen() { Side::Bid } else { Side::Ask };
 
-        if activity_type_rand < 0.4 {
-            // 40% new orders
+        if activity_type_rand < 0.4 && !self.is_full() {
+            // 40% new orders, falling through to updates and cancellations while the book is full
             let base_price = match (&side, best_bid, best_ask) {
                 (Side::Bid, Some(bid), _) => bid,
                 (Side::Ask, _, Some(ask)) => ask,
@@ -298,42 +768,43 @@ impl OrderBook {
             };
 
             let price_variation = (rng.gen::<f64>() - 0.5) * 0.2;
-            let price = (base_price + price_variation).max(0.01);
-            let quantity = rng.gen_range(1000..=10000);
+            let price = ((base_price + price_variation).max(0.01) * 100.0).round() / 100.0;
+            let quantity = Quantity::from_lots(rng.gen_range(1000..=10000), self.quantity_decimals);
 
             OrderActivity {
                 activity_type: ActivityType::Add,
-                order_id: format!("order_{}_{}", Utc::now().timestamp_millis(), rng.gen::<u32>()),
+                order_id: self.next_order_id(),
                 symbol: self.symbol.clone(),
-                price: Some((price * 100.0).round() / 100.0),
+                price: Some(price),
                 quantity: Some(quantity),
+                actor: self.actor_tags.then(|| actor_class(&side, price, best_bid, best_ask)),
                 side: Some(side),
                 timestamp: Utc::now(),
             }
         } else if activity_type_rand < 0.7 && !self.orders.is_empty() {
             // 30% order updates
-            let order_ids: Vec<_> = self.orders.keys().cloned().collect();
-            let order_id = order_ids[rng.gen_range(0..order_ids.len())].clone();
+            let order_id = self.random_order_id(rng);
 
-            if let Some(order) = self.orders.get(&order_id) {
-                let new_quantity = (order.quantity as i64 + rng.gen_range(-2000..=1000)).max(0) as u64;
+            if let Some(order) = self.orders.find(order_id) {
+                let lots = (order.quantity.lots(self.quantity_decimals) as i64 + rng.gen_range(-2000..=1000)).max(0) as u64;
+                let new_quantity = Quantity::from_lots(lots, self.quantity_decimals);
 
                 OrderActivity {
-                    activity_type: if new_quantity > 0 { ActivityType::Update } else { ActivityType::Cancel },
+                    activity_type: if new_quantity.is_zero() { ActivityType::Cancel } else { ActivityType::Update },
                     order_id,
                     symbol: self.symbol.clone(),
                     price: None,
-                    quantity: if new_quantity > 0 { Some(new_quantity) } else { None },
+                    quantity: if new_quantity.is_zero() { None } else { Some(new_quantity) },
                     side: None,
                     timestamp: Utc::now(),
+                    actor: order.actor,
                 }
             } else {
                 self.generate_random_activity(rng)
             }
         } else if !self.orders.is_empty() {
             // 30% order cancellations
-            let order_ids: Vec<_> = self.orders.keys().cloned().collect();
-            let order_id = order_ids[rng.gen_range(0..order_ids.len())].clone();
+            let order_id = self.random_order_id(rng);
 
             OrderActivity {
                 activity_type: ActivityType::Cancel,
@@ -343,52 +814,134 @@ impl OrderBook {
                 quantity: None,
                 side: None,
                 timestamp: Utc::now(),
+                actor: self.orders.find(order_id).and_then(|order| order.actor),
             }
         } else {
             self.generate_random_activity(rng)
         }
     }
 
+    // Picks a resting order uniformly at random; the book must not be empty
+    fn random_order_id(&self, rng: &mut impl Rng) -> OrderId {
+        let position = rng.gen_range(0..self.orders.len());
+        self.orders.ids().nth(position).expect("position is within the book")
+    }
+
     fn execute_activity(&mut self, activity: &OrderActivity) {
         match activity.activity_type {
             ActivityType::Add => {
                 if let (Some(price), Some(quantity), Some(side)) =
                     (activity.price, activity.quantity, &activity.side) {
                     let order = Order::new(
-                        activity.order_id.clone(),
+                        activity.order_id,
                         price,
                         quantity,
                         side.clone(),
-                    );
+                    ).with_actor(activity.actor);
                     self.add_order(order);
                 }
             }
             ActivityType::Update => {
                 if let Some(quantity) = activity.quantity {
-                    self.update_order(&activity.order_id, quantity);
+                    self.update_order(activity.order_id, quantity);
                 }
             }
             ActivityType::Cancel => {
-                self.remove_order(&activity.order_id);
+                self.remove_order(activity.order_id);
             }
+            ActivityType::Fill => match activity.quantity {
+                Some(remaining) if !remaining.is_zero() => {
+                    self.set_resting_quantity(activity.order_id, remaining, false);
+                }
+                _ => {
+                    self.remove_order(activity.order_id);
+                }
+            },
         }
     }
 
     pub fn initialize_with_sample_data(&mut self) {
-        let mut rng = thread_rng();
-        let base_price = 100.0;
+        self.initialize_with_sample_data_around(100.0);
+    }
+
+    // Sample orders either side of `base_price`, e.g. a future's price with carry
+    pub fn initialize_with_sample_data_around(&mut self, base_price: f64) {
+        self.with_rng(|book, rng| book.add_sample_orders(base_price, rng));
+
+        // Reference price for session change calculations
+        self.open_price = self.get_spread_info().1;
+    }
+
+    // Replaces every resting order with sample orders around `price`, returning the
+    // cancellations and additions. The session's open price is kept.
+    pub fn recenter(&mut self, price: f64) -> Vec<OrderActivity> {
+        let timestamp = Utc::now();
+        let mut activities = Vec::new();
+        for order_id in self.orders.ids().collect::<Vec<_>>() {
+            let actor = self.orders.find(order_id).and_then(|order| order.actor);
+            self.remove_order(order_id);
+            activities.push(OrderActivity {
+                activity_type: ActivityType::Cancel,
+                order_id,
+                symbol: self.symbol.clone(),
+                price: None,
+                quantity: None,
+                side: None,
+                timestamp,
+                actor,
+            });
+        }
+
+        let first_new = self.next_order_id;
+        self.with_rng(|book, rng| book.add_sample_orders(price, rng));
+        for id in first_new..self.next_order_id {
+            if let Some(order) = self.orders.find(OrderId(id)) {
+                activities.push(OrderActivity {
+                    activity_type: ActivityType::Add,
+                    order_id: order.id,
+                    symbol: self.symbol.clone(),
+                    price: Some(order.price),
+                    quantity: Some(order.quantity),
+                    side: Some(order.side.clone()),
+                    timestamp,
+                    actor: order.actor,
+                });
+            }
+        }
+        activities
+    }
+
+    // Sells through every bid down to `percent` below the mid, then rebuilds the book
+    // around that price. Returns the fills, then the recentering activities.
+    pub fn crash(&mut self, percent: f64) -> Vec<OrderActivity> {
+        let Some(mid_price) = self.get_spread_info().1 else {
+            return Vec::new();
+        };
+        let target = (mid_price * (1.0 - percent / 100.0)).max(0.01);
+
+        let swept = self
+            .bids_by_price
+            .range(OrderedFloat::from(target)..)
+            .map(|(_, level)| level.total_quantity)
+            .sum();
+        let mut activities = self.execute_market_order(Side::Ask, swept);
+        activities.extend(self.recenter(target));
+        activities
+    }
 
+    fn add_sample_orders(&mut self, base_price: f64, rng: &mut dyn rand::RngCore) {
         // Add initial bid orders
         for i in 0..30 {
             let price = base_price - 0.05 - (i as f64 * 0.01);
-            let quantity = rng.gen_range(1000..=10000);
+            let quantity = Quantity::from_lots(rng.gen_range(1000..=10000), self.quantity_decimals);
             let order = Order {
-                id: format!("bid_{}", i),
+                id: self.next_order_id(),
                 price: (price * 100.0).round() / 100.0,
                 quantity,
                 side: Side::Bid,
                 timestamp: Utc::now() - chrono::Duration::milliseconds(rng.gen_range(0..60000)),
                 original_quantity: quantity,
+                actor: self.actor_tags.then_some(ActorClass::Maker),
             };
             self.add_order(order);
         }
@@ -396,14 +949,15 @@ impl OrderBook {
         // Add initial ask orders
         for i in 0..30 {
             let price = base_price + (i as f64 * 0.01);
-            let quantity = rng.gen_range(1000..=10000);
+            let quantity = Quantity::from_lots(rng.gen_range(1000..=10000), self.quantity_decimals);
             let order = Order {
-                id: format!("ask_{}", i),
+                id: self.next_order_id(),
                 price: (price * 100.0).round() / 100.0,
                 quantity,
                 side: Side::Ask,
                 timestamp: Utc::now() - chrono::Duration::milliseconds(rng.gen_range(0..60000)),
                 original_quantity: quantity,
+                actor: self.actor_tags.then_some(ActorClass::Maker),
             };
             self.add_order(order);
         }
@@ -412,4 +966,62 @@ impl OrderBook {
     pub fn get_sequence(&self) -> u64 {
         self.sequence
     }
-}
\ No newline at end of file
+
+    pub fn get_epoch(&self) -> u64 {
+        self.epoch
+    }
+
+    pub fn get_event_timestamp(&self) -> DateTime<Utc> {
+        self.event_timestamp
+    }
+
+    fn advance_sequence(&mut self) {
+        self.sequence += 1;
+        self.event_timestamp = clock().now();
+    }
+}
+
+// Class of a simulated order added on `side` at `price`: marketable against the other side's
+// best price, joining or improving its own side's, or resting behind it
+fn actor_class(side: &Side, price: f64, best_bid: Option<f64>, best_ask: Option<f64>) -> ActorClass {
+    let (marketable, at_touch) = match side {
+        Side::Bid => (best_ask.is_some_and(|ask| price >= ask), best_bid.is_none_or(|bid| price >= bid)),
+        Side::Ask => (best_bid.is_some_and(|bid| price <= bid), best_ask.is_none_or(|ask| price <= ask)),
+    };
+    if marketable {
+        ActorClass::Taker
+    } else if at_touch {
+        ActorClass::Maker
+    } else {
+        ActorClass::Noise
+    }
+}
+
+// Order book with a single writer that republishes an immutable copy after every
+// update, so snapshot readers never contend with the simulation. Copies share slab
+// chunks, index shards and level maps, so an update copies only what it touches
+#[derive(Debug)]
+pub struct PublishedBook {
+    book: Mutex<OrderBook>,
+    snapshot: ArcSwap<OrderBook>,
+}
+
+impl PublishedBook {
+    pub fn new(book: OrderBook) -> Self {
+        Self {
+            snapshot: ArcSwap::from_pointee(book.clone()),
+            book: Mutex::new(book),
+        }
+    }
+
+    pub fn snapshot(&self) -> Arc<OrderBook> {
+        self.snapshot.load_full()
+    }
+
+    pub fn update<R>(&self, apply: impl FnOnce(&mut OrderBook) -> R) -> R {
+        let mut book = self.book.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let result = apply(&mut book);
+        self.snapshot.store(Arc::new(book.clone()));
+        result
+    }
+}
@@ -0,0 +1,83 @@
+use crate::message::{PairKind, PairQuote};
+use crate::order_book::OrderBook;
+
+// Synthetic instrument derived from two underlying books.
+// "ETHUSD/BTCUSD" defines a ratio pair, "BTCUSD-ETHUSD" a spread pair.
+#[derive(Debug, Clone)]
+pub struct SyntheticPair {
+    pub name: String,
+    pub kind: PairKind,
+    pub base: String,
+    pub quote: String,
+}
+
+impl SyntheticPair {
+    pub fn parse(definition: &str) -> Result<Self, String> {
+        let definition = definition.trim();
+
+        let (kind, base, quote) = if let Some((base, quote)) = definition.split_once('/') {
+            (PairKind::Ratio, base, quote)
+        } else if let Some((base, quote)) = definition.split_once('-') {
+            (PairKind::Spread, base, quote)
+        } else {
+            return Err(format!(
+                "Invalid pair '{}': expected BASE/QUOTE (ratio) or BASE-QUOTE (spread)",
+                definition
+            ));
+        };
+
+        let base = base.trim();
+        let quote = quote.trim();
+
+        if base.is_empty() || quote.is_empty() {
+            return Err(format!("Invalid pair '{}': both legs must be named", definition));
+        }
+
+        if base == quote {
+            return Err(format!("Invalid pair '{}': legs must differ", definition));
+        }
+
+        Ok(Self {
+            name: definition.to_string(),
+            kind,
+            base: base.to_string(),
+            quote: quote.to_string(),
+        })
+    }
+
+    pub fn compute_quote(&self, base_book: &OrderBook, quote_book: &OrderBook) -> PairQuote {
+        let (base_bid, base_ask) = base_book.get_best_bid_ask();
+        let (quote_bid, quote_ask) = quote_book.get_best_bid_ask();
+        let (_, base_mid, _) = base_book.get_spread_info();
+        let (_, quote_mid, _) = quote_book.get_spread_info();
+
+        // Selling the synthetic means selling the base leg and buying the quote leg
+        let bid = self.combine(base_bid, quote_ask);
+        let ask = self.combine(base_ask, quote_bid);
+
+        PairQuote {
+            pair: self.name.clone(),
+            kind: self.kind.clone(),
+            base: self.base.clone(),
+            quote: self.quote.clone(),
+            bid,
+            ask,
+            value: self.combine(base_mid, quote_mid),
+            spread: match (bid, ask) {
+                (Some(bid), Some(ask)) => Some(ask - bid),
+                _ => None,
+            },
+        }
+    }
+
+    fn combine(&self, base: Option<f64>, quote: Option<f64>) -> Option<f64> {
+        match (base, quote) {
+            (Some(base), Some(quote)) => match self.kind {
+                PairKind::Ratio if quote > 0.0 => Some(base / quote),
+                PairKind::Ratio => None,
+                PairKind::Spread => Some(base - quote),
+            },
+            _ => None,
+        }
+    }
+}
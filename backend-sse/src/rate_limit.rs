@@ -0,0 +1,236 @@
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use clap::Args;
+use dashmap::DashMap;
+use tokio::time::interval;
+
+use crate::auth::AuthenticatedKey;
+use crate::unix_socket::peer_ip;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+// Request and connection limits, applied per client IP and per API key
+#[derive(Debug, Clone, Default, Args)]
+pub struct RateLimitOptions {
+    /// Requests and connections allowed per second from one IP address (default: unlimited)
+    #[arg(long, value_name = "N")]
+    pub ip_rate_limit: Option<f64>,
+
+    /// Burst allowed above --ip-rate-limit (default: one second's worth)
+    #[arg(long, value_name = "N")]
+    pub ip_rate_burst: Option<f64>,
+
+    /// Requests and connections allowed per second for one API key (default: unlimited)
+    #[arg(long, value_name = "N")]
+    pub key_rate_limit: Option<f64>,
+
+    /// Burst allowed above --key-rate-limit (default: one second's worth)
+    #[arg(long, value_name = "N")]
+    pub key_rate_burst: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Rate {
+    per_second: f64,
+    burst: f64,
+}
+
+impl Rate {
+    fn new(per_second: Option<f64>, burst: Option<f64>) -> Option<Self> {
+        let per_second = per_second.filter(|rate| *rate > 0.0)?;
+
+        Some(Self {
+            per_second,
+            burst: burst.unwrap_or(per_second).max(1.0),
+        })
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn full(rate: Rate, now: Instant) -> Self {
+        Self { tokens: rate.burst, updated: now }
+    }
+
+    fn refill(&mut self, rate: Rate, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate.per_second).min(rate.burst);
+        self.updated = now;
+    }
+
+    // Takes one token, or returns how long until one is available
+    fn take(&mut self, rate: Rate, now: Instant) -> Result<(), Duration> {
+        self.refill(rate, now);
+
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / rate.per_second))
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimited {
+    pub retry_after: Duration,
+}
+
+impl IntoResponse for RateLimited {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(header::RETRY_AFTER, retry_after_secs(self.retry_after).to_string())],
+            "Rate limit exceeded",
+        ).into_response()
+    }
+}
+
+// Token buckets shared by every listener of the server
+#[derive(Debug)]
+pub struct RateLimiter {
+    ip_rate: Option<Rate>,
+    key_rate: Option<Rate>,
+    ip_buckets: DashMap<IpAddr, TokenBucket>,
+    key_buckets: DashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new(options: &RateLimitOptions) -> Self {
+        Self {
+            ip_rate: Rate::new(options.ip_rate_limit, options.ip_rate_burst),
+            key_rate: Rate::new(options.key_rate_limit, options.key_rate_burst),
+            ip_buckets: DashMap::new(),
+            key_buckets: DashMap::new(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.ip_rate.is_some() || self.key_rate.is_some()
+    }
+
+    // The per-IP limit, applied before anything about the client is verified
+    pub fn check_ip(&self, ip: Option<IpAddr>) -> Result<(), RateLimited> {
+        let (Some(rate), Some(ip)) = (self.ip_rate, ip) else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        self.ip_buckets
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::full(rate, now))
+            .take(rate, now)
+            .map_err(|retry_after| RateLimited { retry_after })
+    }
+
+    // The per-key limit, only for keys whose signature was verified: limiting a key a
+    // request merely claims would let anyone drain its owner's bucket, and fill the map
+    // with made-up keys
+    pub fn check_key(&self, api_key: &str) -> Result<(), RateLimited> {
+        let Some(rate) = self.key_rate else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        let mut bucket = match self.key_buckets.get_mut(api_key) {
+            Some(bucket) => bucket,
+            None => self.key_buckets.entry(api_key.to_string()).or_insert_with(|| TokenBucket::full(rate, now)),
+        };
+        bucket.take(rate, now).map_err(|retry_after| RateLimited { retry_after })
+    }
+
+    // Periodically drops buckets that have refilled, so one-off clients do not accumulate
+    pub fn start_pruning(self: &Arc<Self>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let limiter = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(60));
+
+            loop {
+                interval.tick().await;
+                let now = Instant::now();
+
+                if let Some(rate) = limiter.ip_rate {
+                    limiter.ip_buckets.retain(|_, bucket| !is_refilled(bucket, rate, now));
+                }
+                if let Some(rate) = limiter.key_rate {
+                    limiter.key_buckets.retain(|_, bucket| !is_refilled(bucket, rate, now));
+                }
+            }
+        });
+    }
+}
+
+// Axum middleware rejecting requests over the per-IP limit with 429 Too Many Requests
+pub async fn rate_limit_middleware(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .and_then(|ConnectInfo(addr)| peer_ip(*addr));
+
+    match limiter.check_ip(ip) {
+        Ok(()) => next.run(request).await,
+        Err(limited) => limited.into_response(),
+    }
+}
+
+// Axum middleware rejecting requests over their API key's limit with 429 Too Many
+// Requests. It goes inside signature_middleware, whose verified key it limits; unsigned
+// requests only have the per-IP limit.
+pub async fn key_rate_limit_middleware(
+    State(limiter): State<Arc<RateLimiter>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let limited = request
+        .extensions()
+        .get::<AuthenticatedKey>()
+        .and_then(|AuthenticatedKey(api_key)| limiter.check_key(api_key).err());
+
+    match limited {
+        Some(limited) => limited.into_response(),
+        None => next.run(request).await,
+    }
+}
+
+// API key from the X-API-Key header, or the api_key query parameter for clients
+// such as EventSource that cannot set headers
+pub fn api_key<'a>(headers: &'a HeaderMap, uri: &'a Uri) -> Option<&'a str> {
+    headers
+        .get(API_KEY_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .or_else(|| {
+            uri.query()?
+                .split('&')
+                .find_map(|pair| pair.strip_prefix("api_key="))
+        })
+        .filter(|key| !key.is_empty())
+}
+
+pub fn retry_after_secs(retry_after: Duration) -> u64 {
+    retry_after.as_secs_f64().ceil().max(1.0) as u64
+}
+
+fn is_refilled(bucket: &TokenBucket, rate: Rate, now: Instant) -> bool {
+    let elapsed = now.duration_since(bucket.updated).as_secs_f64();
+    bucket.tokens + elapsed * rate.per_second >= rate.burst
+}
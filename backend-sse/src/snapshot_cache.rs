@@ -0,0 +1,44 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use dashmap::DashMap;
+
+// Latest views built for each symbol, tagged with the book sequence they were built from.
+// Fan-out refreshes the views that have subscribers every tick, so new subscribers to a
+// popular stream are served from here instead of building their own snapshot.
+#[derive(Debug)]
+pub struct SnapshotCache<K, V> {
+    symbols: DashMap<String, HashMap<K, (u64, V)>>,
+}
+
+impl<K: Eq + Hash, V: Clone> Default for SnapshotCache<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Eq + Hash, V: Clone> SnapshotCache<K, V> {
+    pub fn new() -> Self {
+        Self {
+            symbols: DashMap::new(),
+        }
+    }
+
+    // Returns the cached view only if it is still current for the given sequence
+    pub fn get(&self, symbol: &str, key: &K, sequence: u64) -> Option<V> {
+        let views = self.symbols.get(symbol)?;
+        let (cached_sequence, view) = views.get(key)?;
+
+        (*cached_sequence == sequence).then(|| view.clone())
+    }
+
+    // Stores a view, dropping views of the symbol built from older sequences
+    pub fn insert(&self, symbol: &str, key: K, sequence: u64, view: V) {
+        let mut views = self.symbols.entry(symbol.to_string()).or_default();
+        views.retain(|_, (cached_sequence, _)| *cached_sequence >= sequence);
+        views.insert(key, (sequence, view));
+    }
+
+    pub fn remove_symbol(&self, symbol: &str) {
+        self.symbols.remove(symbol);
+    }
+}
@@ -1,41 +1,55 @@
+use std::cell::RefCell;
 use std::sync::Arc;
 use std::time::Duration;
 use axum::{
-    extract::{Query, State},
-    response::Sse,
-    http::StatusCode,
+    extract::{Path, Query, State},
+    response::{IntoResponse, Response, Sse},
+    http::{header, StatusCode},
+    Extension,
 };
 use axum::response::sse::{Event, KeepAlive};
-use tokio::sync::mpsc;
-use tokio_stream::wrappers::UnboundedReceiverStream;
 use uuid::Uuid;
-use tracing::{info, debug, error};
+use tracing::{info, error};
 use futures::stream::Stream;
-use pin_project::pin_project;
 use std::pin::Pin;
 use std::task::{Context, Poll};
 
+use crate::auth::AuthenticatedKey;
+use crate::clock::EventOrdering;
+use crate::outbound_queue::{outbound_queue, OutboundReceiver};
+use crate::quota::QuotaDecision;
 use crate::stream_manager::SSEStreamManager;
-use crate::message::{SSEMessage, StreamQuery};
+use crate::symbols::SymbolQuery;
+use crate::message::{DisconnectReason, InvalidStreams, SSEMessage, SSEOutbound, StreamQuery, MarketSummary, QuotaStatus, SymbolInfo};
+use crate::usage::UsageSnapshot;
 
-#[pin_project]
+thread_local! {
+    // Scratch space for formatting events, reused across clients polled on the same worker
+    static EVENT_BUFFER: RefCell<Vec<u8>> = RefCell::new(Vec::with_capacity(16 * 1024));
+}
+
+// Events of one client, most urgent first
 pub struct SSEStream {
-    #[pin]
-    inner: UnboundedReceiverStream<SSEMessage>,
+    inner: OutboundReceiver<SSEOutbound>,
     client_id: Uuid,
     stream_manager: Arc<SSEStreamManager>,
+    ordering: EventOrdering,
+    // Set once the terminal event is sent; the stream ends after it
+    closed: bool,
 }
 
 impl SSEStream {
     pub fn new(
-        receiver: mpsc::UnboundedReceiver<SSEMessage>,
+        receiver: OutboundReceiver<SSEOutbound>,
         client_id: Uuid,
         stream_manager: Arc<SSEStreamManager>,
     ) -> Self {
         Self {
-            inner: UnboundedReceiverStream::new(receiver),
+            inner: receiver,
             client_id,
             stream_manager,
+            ordering: EventOrdering::default(),
+            closed: false,
         }
     }
 }
@@ -44,41 +58,70 @@ impl Stream for SSEStream {
     type Item = Result<Event, axum::Error>;
 
     fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
-        let this = self.project();
-
-        match this.inner.poll_next(cx) {
-            Poll::Ready(Some(message)) => {
-                let event = match &message {
-                    SSEMessage::MarketData { stream_id, .. } => {
-                        Event::default()
-                            .event("market_data")
-                            .data(serde_json::to_string(&message).unwrap_or_default())
-                            .id(stream_id)
-                    }
-                    SSEMessage::HeartBeat { .. } => {
-                        Event::default()
-                            .event("heartbeat")
-                            .data(serde_json::to_string(&message).unwrap_or_default())
-                    }
-                    SSEMessage::ConnectionInfo { .. } => {
-                        Event::default()
-                            .event("connection_info")
-                            .data(serde_json::to_string(&message).unwrap_or_default())
-                    }
-                    SSEMessage::Error { .. } => {
-                        Event::default()
-                            .event("error")
-                            .data(serde_json::to_string(&message).unwrap_or_default())
+        let this = self.get_mut();
+
+        loop {
+            if this.closed {
+                this.stream_manager.unregister_client(&this.client_id);
+                return Poll::Ready(None);
+            }
+
+            let mut message = match this.inner.poll_recv(cx) {
+                Poll::Ready(Some(message)) => message,
+                Poll::Ready(None) => {
+                    // Stream ended, clean up
+                    this.stream_manager.unregister_client(&this.client_id);
+                    return Poll::Ready(None);
+                }
+                Poll::Pending => return Poll::Pending,
+            };
+
+            // The backlog is dropped with the connection; the client resynchronizes on reconnect
+            let queued = this.inner.len();
+            this.stream_manager.record_queue_depth(queued);
+            if this.stream_manager.is_slow_consumer(queued) {
+                info!("Client {} has {} events queued, disconnecting", this.client_id, queued);
+                message = SSEMessage::disconnected(DisconnectReason::SlowConsumer).into();
+            }
+
+            if message.is_stream_update() {
+                let stream = message.stream_id().unwrap_or(message.event_name());
+                match this.stream_manager.check_quota(&this.client_id, stream) {
+                    QuotaDecision::Deliver => {}
+                    QuotaDecision::Skip => continue,
+                    QuotaDecision::Exceeded(quota) => {
+                        this.stream_manager.cut_off_quota(&this.client_id, quota);
+                        continue;
                     }
-                };
-                Poll::Ready(Some(Ok(event)))
+                }
             }
-            Poll::Ready(None) => {
-                // Stream ended, clean up
-                this.stream_manager.unregister_client(this.client_id);
-                Poll::Ready(None)
+
+            if let SSEOutbound::Message(message) = &message {
+                this.closed = message.is_terminal();
             }
-            Poll::Pending => Poll::Pending,
+
+            let event = EVENT_BUFFER.with(|buffer| {
+                let mut buffer = buffer.borrow_mut();
+                buffer.clear();
+                if let Err(e) = message.write_json(&mut buffer, &mut this.ordering) {
+                    error!("Failed to serialize event for client {}: {}", this.client_id, e);
+                    buffer.clear();
+                }
+                this.stream_manager.record_usage(&this.client_id, message.stream_id(), buffer.len());
+
+                let event = Event::default()
+                    .event(message.event_name())
+                    .data(std::str::from_utf8(&buffer).unwrap_or_default());
+
+                match &message {
+                    SSEOutbound::SharedMarketData { stream_id, .. }
+                    | SSEOutbound::Message(
+                        SSEMessage::MarketData { stream_id, .. } | SSEMessage::Snapshot { stream_id, .. },
+                    ) => event.id(stream_id),
+                    SSEOutbound::Message(_) => event,
+                }
+            });
+            return Poll::Ready(Some(Ok(event)));
         }
     }
 }
@@ -87,40 +130,91 @@ impl Stream for SSEStream {
 pub async fn sse_handler(
     Query(query): Query<StreamQuery>,
     State(stream_manager): State<Arc<SSEStreamManager>>,
-) -> Result<Sse<SSEStream>, StatusCode> {
+    authenticated: Option<Extension<AuthenticatedKey>>,
+) -> Result<Sse<SSEStream>, Response> {
+    // Connections naming no streams get the configured defaults, and are refused when there
+    // are none unless they only want the market summary
+    let summary = query.summary.unwrap_or(false);
+    let mut stream_definitions = if query.strict.unwrap_or(false) || stream_manager.strict_subscriptions() {
+        query.parse_streams_strict().map_err(|rejected| {
+            info!("Rejected SSE connection with {} invalid stream parameters", rejected.len());
+            let body = InvalidStreams {
+                error: "Invalid stream parameters".to_string(),
+                rejected,
+            };
+            (StatusCode::UNPROCESSABLE_ENTITY, axum::Json(body)).into_response()
+        })?
+    } else {
+        query.parse_streams()
+    };
+    let replay = query.replay.as_deref().map(str::trim).filter(|symbol| !symbol.is_empty());
+    let defaulted = stream_definitions.is_empty() && replay.is_none();
+    if defaulted {
+        stream_definitions = stream_manager.default_streams().to_vec();
+        if stream_definitions.is_empty() && !summary {
+            info!("Rejected SSE connection naming no streams: no default streams are configured");
+            return Err(StatusCode::BAD_REQUEST.into_response());
+        }
+    }
+
     let client_id = Uuid::new_v4();
-    let (tx, rx) = mpsc::unbounded_channel();
+    let (tx, rx) = outbound_queue();
 
     // Register the client
     stream_manager.register_client(client_id, tx);
 
+    // Deliveries to signed clients count against their key's quotas
+    if let Some(Extension(AuthenticatedKey(api_key))) = &authenticated {
+        stream_manager.bind_api_key(client_id, api_key);
+
+        if let Some(quota) = stream_manager.quota_exceeded(&client_id) {
+            info!("Rejected client {}: {:?} {:?} quota of {} exceeded", client_id, quota.period, quota.unit, quota.limit);
+            stream_manager.unregister_client(&client_id);
+            return Err(StatusCode::TOO_MANY_REQUESTS.into_response());
+        }
+    }
+
     // Send connection info
-    stream_manager.send_connection_info(client_id).await;
+    stream_manager.send_connection_info(client_id, &stream_definitions, summary).await;
 
-    // Parse and subscribe to requested streams
-    let stream_definitions = query.parse_streams();
+    // Subscribe to the requested or default streams
     if !stream_definitions.is_empty() {
-        match stream_manager
-            .subscribe_to_streams(client_id, stream_definitions)
-            .await
-        {
-            Ok(()) => {
-                info!("Client {} subscribed to requested streams", client_id);
-            }
-            Err(e) => {
-                error!("Failed to subscribe client {} to streams: {}", client_id, e);
-                return Err(StatusCode::BAD_REQUEST);
-            }
-        }
-    } else {
-        // If no specific streams requested, subscribe to default BTCUSD MBP
-        let default_streams = vec![("BTCUSD".to_string(), crate::message::DataType::MBP, 20)];
+        let (kind, failure) = if defaulted {
+            ("default", StatusCode::INTERNAL_SERVER_ERROR)
+        } else {
+            ("requested", StatusCode::BAD_REQUEST)
+        };
+
         if let Err(e) = stream_manager
-            .subscribe_to_streams(client_id, default_streams)
+            .subscribe_to_streams(client_id, stream_definitions, query.max_orders, query.snapshot_interval_ms, query.priority)
             .await
         {
-            error!("Failed to subscribe client {} to default streams: {}", client_id, e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            error!("Failed to subscribe client {} to {} streams: {}", client_id, kind, e);
+            stream_manager.unregister_client(&client_id);
+            return Err(failure.into_response());
+        }
+        info!("Client {} subscribed to {} streams", client_id, kind);
+    }
+
+    if summary {
+        if let Err(e) = stream_manager.subscribe_to_summary(client_id).await {
+            error!("Failed to subscribe client {} to market summary: {}", client_id, e);
+            stream_manager.unregister_client(&client_id);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR.into_response());
+        }
+    }
+
+    // A replay runs on its own {SYMBOL}_REPLAY stream, interleaved with the live ones
+    if let Some(symbol) = replay {
+        let (Some(from), Some(to)) = (query.replay_from, query.replay_to) else {
+            stream_manager.unregister_client(&client_id);
+            return Err(invalid_replay("replay_from and replay_to are required".to_string()));
+        };
+        let max_levels = query.max_levels.unwrap_or(20);
+        if let Err(e) = stream_manager.start_replay(client_id, symbol, from, to, query.replay_speed, max_levels) {
+            info!("Refused replay of {} for client {}: {}", symbol, client_id, e);
+            stream_manager.unregister_client(&client_id);
+            return Err(invalid_replay(e));
         }
     }
 
@@ -133,15 +227,71 @@ pub async fn sse_handler(
     ))
 }
 
+fn invalid_replay(error: String) -> Response {
+    let body = serde_json::json!({ "error": "Invalid replay", "message": error });
+    (StatusCode::UNPROCESSABLE_ENTITY, axum::Json(body)).into_response()
+}
+
 pub async fn health_check() -> &'static str {
     "SSE Market Depth Server is running"
 }
 
 pub async fn symbols_handler(
     State(stream_manager): State<Arc<SSEStreamManager>>,
-) -> Result<axum::Json<Vec<String>>, StatusCode> {
-    let symbols = stream_manager.get_symbols().await;
-    Ok(axum::Json(symbols))
+    Query(query): Query<SymbolQuery>,
+) -> axum::Json<Vec<SymbolInfo>> {
+    axum::Json(stream_manager.search_symbols(&query))
+}
+
+pub async fn summary_handler(
+    State(stream_manager): State<Arc<SSEStreamManager>>,
+) -> axum::Json<MarketSummary> {
+    axum::Json(stream_manager.get_market_summary().await)
+}
+
+pub async fn usage_handler(
+    State(stream_manager): State<Arc<SSEStreamManager>>,
+) -> axum::Json<UsageSnapshot> {
+    axum::Json(stream_manager.get_usage_snapshot())
+}
+
+// Remaining quota of the key the request was signed with
+pub async fn quota_handler(
+    State(stream_manager): State<Arc<SSEStreamManager>>,
+    authenticated: Option<Extension<AuthenticatedKey>>,
+) -> Result<axum::Json<QuotaStatus>, (StatusCode, &'static str)> {
+    let Some(Extension(AuthenticatedKey(api_key))) = authenticated else {
+        return Err((StatusCode::UNAUTHORIZED, "Request must be signed"));
+    };
+
+    stream_manager
+        .quota_status(&api_key)
+        .map(axum::Json)
+        .ok_or((StatusCode::NOT_FOUND, "Quotas are not enabled"))
+}
+
+// Closes a client's connection with a terminal `kicked` error event
+pub async fn disconnect_handler(
+    State(stream_manager): State<Arc<SSEStreamManager>>,
+    Path(client_id): Path<String>,
+) -> StatusCode {
+    let Ok(client_id) = client_id.parse::<Uuid>() else {
+        return StatusCode::NOT_FOUND;
+    };
+    if stream_manager.disconnect_client(&client_id, DisconnectReason::Kicked) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+pub async fn metrics_handler(
+    State(stream_manager): State<Arc<SSEStreamManager>>,
+) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        stream_manager.render_metrics(),
+    )
 }
 
 pub async fn api_info() -> axum::Json<serde_json::Value> {
@@ -151,17 +301,26 @@ pub async fn api_info() -> axum::Json<serde_json::Value> {
         "endpoints": {
             "/stream": {
                 "method": "GET",
-                "description": "SSE endpoint for market data streams",
+                "description": "SSE endpoint for market data streams. Without streams or symbols the server's default streams are used, or the request is refused with 400 when it has none; connection_info echoes the streams subscribed",
                 "parameters": {
                     "streams": "Comma-separated stream definitions (symbol:type:levels): BTCUSD:MBP:20,ETHUSD:MBO:10",
                     "symbols": "Comma-separated symbols: BTCUSD,ETHUSD (uses default type and levels)",
                     "data_type": "Default data type: MBP or MBO (default: MBP)",
-                    "max_levels": "Default max levels (default: 20)"
+                    "max_levels": "Default max levels (default: 20)",
+                    "max_orders": "Orders per side for MBO streams (default: 3 per level)",
+                    "summary": "Include periodic market_summary events: true or false (default: false)",
+                    "strict": "Refuse malformed definitions, unknown types, malformed symbols and levels outside 1-500 with 422 and the rejected parameters, instead of coercing them: true or false (default: false)",
+                    "replay": "Symbol whose recorded history is replayed on the SYMBOL_REPLAY stream, alongside any live streams; requires a server started with --history-secs",
+                    "replay_from": "Start of the replayed window, RFC 3339",
+                    "replay_to": "End of the replayed window, RFC 3339",
+                    "replay_speed": "Times faster than recorded, above 0 and at most 1000 (default: 1)"
                 },
                 "examples": [
                     "/stream?streams=BTCUSD:MBP:20,ETHUSD:MBO:10",
                     "/stream?symbols=BTCUSD,ETHUSD&data_type=MBP&max_levels=15",
-                    "/stream?symbols=BTCUSD"
+                    "/stream?symbols=BTCUSD",
+                    "/stream?symbols=BTCUSD&summary=true",
+                    "/stream?symbols=ETHUSD&replay=BTCUSD&replay_from=2026-01-01T12:00:00Z&replay_to=2026-01-01T12:05:00Z&replay_speed=10"
                 ]
             },
             "/health": {
@@ -170,7 +329,36 @@ pub async fn api_info() -> axum::Json<serde_json::Value> {
             },
             "/symbols": {
                 "method": "GET",
-                "description": "List available symbols"
+                "description": "Search available symbols, with tick size, status, venue, base and quote",
+                "parameters": {
+                    "search": "Case-insensitive substring of the symbol, base or quote",
+                    "type": "spot or pair",
+                    "status": "open or halted",
+                    "limit": "Maximum number of symbols returned"
+                },
+                "examples": [
+                    "/symbols?search=BTC&type=spot&status=open"
+                ]
+            },
+            "/summary": {
+                "method": "GET",
+                "description": "Cross-symbol market summary: top gainers/losers, highest volume, widest spreads"
+            },
+            "/quota": {
+                "method": "GET",
+                "description": "Remaining daily and monthly quota of the API key the request is signed with"
+            },
+            "/admin/usage": {
+                "method": "GET",
+                "description": "Messages and bytes delivered per client and per stream"
+            },
+            "/admin/clients/{client_id}": {
+                "method": "DELETE",
+                "description": "Disconnect a client: its stream ends with a terminal error event of reason kicked. 404 if it is not connected"
+            },
+            "/metrics": {
+                "method": "GET",
+                "description": "Prometheus metrics"
             },
             "/api": {
                 "method": "GET",
@@ -178,11 +366,14 @@ pub async fn api_info() -> axum::Json<serde_json::Value> {
             }
         },
         "supported_symbols": ["BTCUSD", "ETHUSD", "ADAUSD"],
-        "data_types": ["MBO", "MBP"],
+        "data_types": ["MBO", "MBP", "FLOW"],
         "sse_events": [
             "market_data",
+            "market_summary",
             "heartbeat",
             "connection_info",
+            "usage_report",
+            "quota_exceeded",
             "error"
         ]
     }))
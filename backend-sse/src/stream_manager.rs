@@ -1,59 +1,269 @@
+use std::collections::HashMap;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{RwLock, mpsc};
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use tokio::sync::{Mutex, RwLock};
+use tokio::runtime::Handle;
 use tokio::time::interval;
-use dashmap::DashMap;
+use dashmap::{DashMap, DashSet};
 use uuid::Uuid;
-use chrono::Utc;
-use tracing::{info, debug};
+use chrono::{DateTime, Utc};
+use tracing::{info, debug, error, warn};
 
-use crate::order_book::OrderBook;
+use crate::flow::AggressorFlow;
+use crate::history::{validate_replay_speed, BookHistory, Replay};
+use crate::limits::ResourceLimits;
+use crate::load_shedding::{LoadShedder, LOAD_WINDOW};
+use crate::metrics::Metrics;
+use crate::order_book::{OrderBook, PublishedBook, SIMULATED_TICK_SIZE};
+use crate::outbound_queue::OutboundSender;
+use crate::pairs::SyntheticPair;
+use crate::quota::{QuotaDecision, QuotaTracker};
 use crate::message::{
-    SSEMessage, MarketDataUpdate, SSESubscription, DataType,
+    SSEMessage, SSEOutbound, DisconnectReason, MarketDataUpdate, SSESubscription, DataType, MarketSummary,
+    QuotaRemaining, QuotaStatus, SymbolInfo, SymbolStatus, SymbolType, StreamPriority, StreamDefinition,
+    parse_stream_definitions,
 };
+use crate::snapshot_cache::SnapshotCache;
+use crate::snapshot_schedule::{validate_snapshot_interval, SnapshotSchedule};
+use crate::subscriptions::{StreamKey, SubscriptionIndex};
+use crate::summary::{build_market_summary, SUMMARY_TOP_N};
+use crate::symbol_config::SymbolConfig;
+use crate::symbols::{split_currency_pair, SymbolQuery};
+use crate::usage::{UsageSnapshot, UsageTracker};
 
-pub type SSEClientSender = mpsc::UnboundedSender<SSEMessage>;
+pub type SSEClientSender = OutboundSender<SSEOutbound>;
+
+// Streams of a connection that names none, unless configured otherwise
+pub const DEFAULT_STREAMS: &str = "BTCUSD:MBP:20";
+
+// Symbols simulated from startup unless --symbol-config lists others
+pub const DEFAULT_SYMBOLS: [&str; 3] = ["BTCUSD", "ETHUSD", "ADAUSD"];
 
 #[derive(Debug)]
 pub struct SSEStreamManager {
-    order_books: Arc<DashMap<String, Arc<RwLock<OrderBook>>>>,
-    subscriptions: Arc<DashMap<String, Vec<SSESubscription>>>,
+    order_books: Arc<DashMap<String, Arc<PublishedBook>>>,
+    subscriptions: Arc<SubscriptionIndex<SharedPayload, SSESubscription>>,
+    snapshots: Arc<SnapshotCache<SharedPayload, Bytes>>,
+    snapshot_schedules: Arc<DashMap<StreamKey, SnapshotSchedule>>, // Streams asking for periodic full books
     clients: Arc<DashMap<Uuid, SSEClientSender>>,
-    client_streams: Arc<DashMap<Uuid, Vec<String>>>, // Track which streams each client is subscribed to
+    summary_subscribers: Arc<DashSet<Uuid>>,
+    latest_summary: Arc<RwLock<Option<MarketSummary>>>,
+    pairs: Arc<DashMap<String, SyntheticPair>>,
+    flows: Arc<DashMap<String, AggressorFlow>>,
+    usage: Arc<UsageTracker>,
+    quotas: Option<Arc<QuotaTracker>>,
+    load_shedder: Option<Arc<LoadShedder>>,
+    metrics: Arc<Metrics>,
+    usage_report_interval: Option<Duration>,
+    simulation_runtime: Option<Handle>,
+    limits: ResourceLimits,
+    pinned_symbols: Arc<DashSet<String>>,
+    venue: String,
+    quantity_decimals: HashMap<String, u32>,
+    default_streams: Vec<(String, DataType, u32)>, // Empty when connections must name their streams
+    default_symbols: Vec<String>,
+    symbol_config: HashMap<String, SymbolConfig>, // Default symbols listed by --symbol-config
+    strict_subscriptions: bool,
+    actor_tags: bool,
+    symbol_registry: Arc<Mutex<()>>, // Held while books are created for or evicted from subscriptions
+    history: Option<Arc<BookHistory>>,
+    replays: Arc<DashSet<StreamKey>>, // Replays being sent
+}
+
+impl Default for SSEStreamManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl SSEStreamManager {
     pub fn new() -> Self {
         Self {
             order_books: Arc::new(DashMap::new()),
-            subscriptions: Arc::new(DashMap::new()),
+            subscriptions: Arc::new(SubscriptionIndex::new()),
+            snapshots: Arc::new(SnapshotCache::new()),
+            snapshot_schedules: Arc::new(DashMap::new()),
             clients: Arc::new(DashMap::new()),
-            client_streams: Arc::new(DashMap::new()),
+            summary_subscribers: Arc::new(DashSet::new()),
+            latest_summary: Arc::new(RwLock::new(None)),
+            pairs: Arc::new(DashMap::new()),
+            flows: Arc::new(DashMap::new()),
+            usage: Arc::new(UsageTracker::new()),
+            quotas: None,
+            load_shedder: None,
+            metrics: Arc::new(Metrics::new()),
+            usage_report_interval: None,
+            simulation_runtime: None,
+            limits: ResourceLimits::default(),
+            pinned_symbols: Arc::new(DashSet::new()),
+            venue: "SIM".to_string(),
+            quantity_decimals: HashMap::new(),
+            default_streams: parse_stream_definitions(DEFAULT_STREAMS, DataType::MBP, 20),
+            default_symbols: DEFAULT_SYMBOLS.iter().map(|symbol| symbol.to_string()).collect(),
+            symbol_config: HashMap::new(),
+            strict_subscriptions: false,
+            actor_tags: false,
+            symbol_registry: Arc::new(Mutex::new(())),
+            history: None,
+            replays: Arc::new(DashSet::new()),
+        }
+    }
+
+    pub fn enable_usage_reports(&mut self, interval: Duration) {
+        self.usage_report_interval = Some(interval);
+    }
+
+    // Runs the market simulation on the given runtime instead of the caller's
+    pub fn set_simulation_runtime(&mut self, handle: Handle) {
+        self.simulation_runtime = Some(handle);
+    }
+
+    // Venue reported for every symbol by symbol discovery
+    pub fn set_venue(&mut self, venue: &str) {
+        self.venue = venue.to_string();
+    }
+
+    // Streams connections naming none are subscribed to; with none, they are refused
+    pub fn set_default_streams(&mut self, streams: Vec<(String, DataType, u32)>) {
+        self.default_streams = streams;
+    }
+
+    pub fn default_streams(&self) -> &[(String, DataType, u32)] {
+        &self.default_streams
+    }
+
+    // Default symbols with their starting price, quantity decimals and the data types
+    // they may be subscribed with
+    pub fn set_symbol_config(&mut self, symbols: Vec<SymbolConfig>) {
+        self.default_symbols = symbols.iter().map(|config| config.symbol.clone()).collect();
+        for config in symbols {
+            if let Some(decimals) = config.quantity_decimals {
+                self.quantity_decimals.insert(config.symbol.clone(), decimals);
+            }
+            self.symbol_config.insert(config.symbol.clone(), config);
         }
     }
 
+    // Every connection's stream parameters are validated strictly, as with `strict=true`
+    pub fn enable_strict_subscriptions(&mut self) {
+        self.strict_subscriptions = true;
+    }
+
+    pub fn strict_subscriptions(&self) -> bool {
+        self.strict_subscriptions
+    }
+
+    // Attributes the orders of simulated books to an actor class on MBO streams, as labeled
+    // training data for research
+    pub fn enable_actor_tags(&mut self) {
+        self.actor_tags = true;
+    }
+
+    // Decimals the symbol's order sizes are quoted to; 0 (whole units) unless set
+    pub fn set_quantity_decimals(&mut self, symbol: &str, decimals: u32) {
+        self.quantity_decimals.insert(symbol.to_string(), decimals);
+    }
+
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
+
+    // Meters delivery to clients bound to an API key against the tracker's quotas
+    pub fn set_quotas(&mut self, quotas: QuotaTracker) {
+        self.quotas = Some(Arc::new(quotas));
+    }
+
+    // Conflates low-priority streams while the shedder's load thresholds are exceeded
+    pub fn set_load_shedding(&mut self, load_shedder: LoadShedder) {
+        self.load_shedder = Some(Arc::new(load_shedder));
+    }
+
+    // Records the MBP history of every book for replays
+    pub fn enable_history(&mut self, history: BookHistory) {
+        self.history = Some(Arc::new(history));
+    }
+
+    pub fn add_synthetic_pair(&self, pair: SyntheticPair) {
+        info!("Registered synthetic pair: {} ({:?} of {} and {})",
+            pair.name, pair.kind, pair.base, pair.quote
+        );
+        self.pairs.insert(pair.name.clone(), pair);
+    }
+
     pub async fn start(&self) {
         info!("Starting SSE stream manager");
 
         // Initialize default symbols
-        self.initialize_symbol("BTCUSD").await;
-        self.initialize_symbol("ETHUSD").await;
-        self.initialize_symbol("ADAUSD").await;
+        for symbol in &self.default_symbols {
+            self.initialize_symbol(symbol).await;
+        }
+
+        // Initialize legs of synthetic pairs
+        let legs: Vec<String> = self.pairs
+            .iter()
+            .flat_map(|pair| [pair.base.clone(), pair.quote.clone()])
+            .collect();
+        for leg in legs {
+            if !self.order_books.contains_key(&leg) {
+                self.initialize_symbol(&leg).await;
+            }
+        }
+
+        // Default symbols and pair legs are never evicted
+        for entry in self.order_books.iter() {
+            self.pinned_symbols.insert(entry.key().clone());
+        }
 
         // Start market simulation
         self.start_market_simulation().await;
 
+        // Start market summary publication
+        self.start_market_summary().await;
+
         // Start heartbeat
         self.start_heartbeat().await;
+
+        // Start periodic usage reports
+        if let Some(report_interval) = self.usage_report_interval {
+            self.start_usage_reports(report_interval).await;
+        }
+
+        // Start idle symbol eviction
+        if let Some(ttl) = self.limits.idle_symbol_ttl {
+            self.start_symbol_eviction(ttl).await;
+        }
+
+        // Start load shedding
+        if let Some(load_shedder) = &self.load_shedder {
+            self.start_load_shedding(Arc::clone(load_shedder)).await;
+        }
+    }
+
+    fn check_symbol_limit(&self) -> Result<(), String> {
+        match self.limits.max_symbols {
+            Some(max_symbols) if self.order_books.len() >= max_symbols => {
+                Err(format!("Symbol limit of {} reached", max_symbols))
+            }
+            _ => Ok(()),
+        }
     }
 
     async fn initialize_symbol(&self, symbol: &str) {
         let mut order_book = OrderBook::new(symbol.to_string());
-        order_book.initialize_with_sample_data();
+        if let Some(limit) = self.limits.max_orders_per_book {
+            order_book.set_order_limit(limit);
+        }
+        order_book.set_quantity_decimals(self.quantity_decimals.get(symbol).copied().unwrap_or(0));
+        order_book.set_actor_tags(self.actor_tags);
+        match self.symbol_config.get(symbol).and_then(|config| config.price) {
+            Some(price) => order_book.initialize_with_sample_data_around(price),
+            None => order_book.initialize_with_sample_data(),
+        }
 
         self.order_books.insert(
             symbol.to_string(),
-            Arc::new(RwLock::new(order_book))
+            Arc::new(PublishedBook::new(order_book))
         );
 
         info!("Initialized order book for symbol: {}", symbol);
@@ -62,59 +272,159 @@ impl SSEStreamManager {
     async fn start_market_simulation(&self) {
         let order_books = Arc::clone(&self.order_books);
         let subscriptions = Arc::clone(&self.subscriptions);
+        let snapshots = Arc::clone(&self.snapshots);
+        let snapshot_schedules = Arc::clone(&self.snapshot_schedules);
         let clients = Arc::clone(&self.clients);
+        let pairs = Arc::clone(&self.pairs);
+        let flows = Arc::clone(&self.flows);
+        let metrics = Arc::clone(&self.metrics);
+        let load_shedder = self.load_shedder.clone();
+        let history = self.history.clone();
 
-        tokio::spawn(async move {
+        let simulation = async move {
             let mut interval = interval(Duration::from_millis(300));
 
             loop {
                 interval.tick().await;
+                let tick_started = Instant::now();
 
                 for entry in order_books.iter() {
-                    let symbol = entry.key().clone();
+                    let symbol: Arc<str> = Arc::from(entry.key().as_str());
                     let order_book_ref = entry.value().clone();
 
                     // Simulate market activity
-                    {
-                        let mut order_book = order_book_ref.write().await;
+                    let trades = order_book_ref.update(|order_book| {
                         order_book.simulate_activity();
+                        order_book.take_trades()
+                    });
+                    metrics.mark_updated(&symbol);
+                    if let Some(history) = &history {
+                        history.record(&symbol, &order_book_ref.snapshot());
+                    }
+
+                    // Update rolling aggressor flow from this tick's trades
+                    {
+                        let mut flow = flows.entry(symbol.to_string()).or_default();
+                        for trade in &trades {
+                            flow.record(trade);
+                        }
+                        flow.expire(Utc::now());
                     }
 
                     // Send updates to subscribed clients
-                    if let Some(symbol_subscriptions) = subscriptions.get(&symbol) {
-                        for subscription in symbol_subscriptions.iter() {
-                            if let Some(client_sender) = clients.get(&subscription.client_id) {
-                                let market_data = {
-                                    let order_book = order_book_ref.read().await;
-                                    match subscription.data_type {
-                                        DataType::MBO => {
-                                            let (bids, asks) = order_book.get_mbo_data(subscription.max_levels);
-                                            MarketDataUpdate::MBO { bids, asks }
-                                        }
-                                        DataType::MBP => {
-                                            let (bids, asks) = order_book.get_mbp_data(subscription.max_levels);
-                                            MarketDataUpdate::MBP { bids, asks }
-                                        }
+                    let fanout_started = Instant::now();
+                    if let Some(symbol_subscriptions) = subscriptions.symbol(&symbol) {
+                        let order_book = order_book_ref.snapshot();
+                        let sequence = order_book.get_sequence();
+                        let event_ts = order_book.get_event_timestamp();
+
+                        for (group, group_subscriptions) in symbol_subscriptions.iter() {
+                            // Every subscriber in a group receives the same view, serialized once
+                            // and kept for clients that subscribe before the next tick
+                            let Some(data) = build_shared_payload(&order_book, &flows, &symbol, *group) else {
+                                continue;
+                            };
+                            snapshots.insert(&symbol, *group, sequence, data.clone());
+
+                            for subscription in group_subscriptions.values() {
+                                if conflated(&load_shedder, subscription, tick_started) {
+                                    continue;
+                                }
+                                if let Some(client_sender) = clients.get(&subscription.client_id) {
+                                    let message = SSEOutbound::SharedMarketData {
+                                        stream_id: subscription.stream_id.clone(),
+                                        symbol: symbol.clone(),
+                                        data: data.clone(),
+                                        sequence,
+                                        timestamp: Utc::now(),
+                                        event_ts,
+                                    };
+
+                                    match client_sender.send(message) {
+                                        Ok(replaced) => metrics.record_conflated(replaced),
+                                        Err(_) => debug!("Client {} disconnected during market data send", subscription.client_id),
                                     }
-                                };
-
-                                let message = SSEMessage::MarketData {
-                                    stream_id: subscription.stream_id.clone(),
-                                    symbol: symbol.clone(),
-                                    data: market_data,
-                                    sequence: {
-                                        let order_book = order_book_ref.read().await;
-                                        order_book.get_sequence()
-                                    },
-                                    timestamp: Utc::now(),
-                                };
-
-                                if let Err(_) = client_sender.send(message) {
-                                    debug!("Client {} disconnected during market data send", subscription.client_id);
                                 }
                             }
                         }
                     }
+                    metrics.observe_fanout(fanout_started.elapsed());
+                    if let Some(load_shedder) = &load_shedder {
+                        load_shedder.observe_fanout(fanout_started.elapsed());
+                    }
+                }
+
+                // Publish synthetic pairs once all legs have been updated
+                for pair in pairs.iter() {
+                    let Some(pair_subscriptions) = subscriptions.symbol(pair.key()) else {
+                        continue;
+                    };
+
+                    let Some((market_data, sequence, event_ts)) = compute_pair_update(&order_books, &pair) else {
+                        continue;
+                    };
+                    let pair_symbol: Arc<str> = Arc::from(pair.name.as_str());
+                    let Some(data) = serialize_payload(&market_data) else {
+                        continue;
+                    };
+
+                    for subscription in pair_subscriptions.values().flat_map(|group| group.values()) {
+                        if conflated(&load_shedder, subscription, tick_started) {
+                            continue;
+                        }
+                        if let Some(client_sender) = clients.get(&subscription.client_id) {
+                            let message = SSEOutbound::SharedMarketData {
+                                stream_id: subscription.stream_id.clone(),
+                                symbol: Arc::clone(&pair_symbol),
+                                data: data.clone(),
+                                sequence,
+                                timestamp: Utc::now(),
+                                event_ts,
+                            };
+
+                            match client_sender.send(message) {
+                                Ok(replaced) => metrics.record_conflated(replaced),
+                                Err(_) => debug!("Client {} disconnected during pair data send", subscription.client_id),
+                            }
+                        }
+                    }
+                }
+
+                send_due_snapshots(&snapshot_schedules, &order_books, &clients);
+
+                metrics.observe_tick(tick_started.elapsed());
+            }
+        };
+
+        match &self.simulation_runtime {
+            Some(handle) => handle.spawn(simulation),
+            None => tokio::spawn(simulation),
+        };
+    }
+
+    async fn start_market_summary(&self) {
+        let order_books = Arc::clone(&self.order_books);
+        let summary_subscribers = Arc::clone(&self.summary_subscribers);
+        let clients = Arc::clone(&self.clients);
+        let latest_summary = Arc::clone(&self.latest_summary);
+
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(5));
+
+            loop {
+                interval.tick().await;
+
+                let summary = compute_market_summary(&order_books);
+                *latest_summary.write().await = Some(summary.clone());
+
+                let message = SSEMessage::MarketSummary { summary };
+
+                for client_id in summary_subscribers.iter() {
+                    if let Some(client_sender) = clients.get(&*client_id) {
+                        if client_sender.send(message.clone().into()).is_err() {
+                            debug!("Client {} disconnected during market summary send", *client_id);
+                        }
+                    }
                 }
             }
         });
@@ -134,7 +444,7 @@ impl SSEStreamManager {
                 };
 
                 for client in clients.iter() {
-                    if let Err(_) = client.send(heartbeat.clone()) {
+                    if client.send(heartbeat.clone().into()).is_err() {
                         debug!("Client {} disconnected during heartbeat", client.key());
                     }
                 }
@@ -142,87 +452,314 @@ impl SSEStreamManager {
         });
     }
 
+    async fn start_usage_reports(&self, report_interval: Duration) {
+        let clients = Arc::clone(&self.clients);
+        let usage = Arc::clone(&self.usage);
+
+        tokio::spawn(async move {
+            let mut interval = interval(report_interval);
+            // The first tick completes immediately; skip it so reports cover a full period
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                for client in clients.iter() {
+                    if let Some(client_usage) = usage.client_usage(client.key()) {
+                        let report = SSEMessage::UsageReport {
+                            connected_at: client_usage.connected_at,
+                            messages: client_usage.messages,
+                            bytes: client_usage.bytes,
+                            streams: client_usage.streams,
+                            timestamp: Utc::now(),
+                        };
+
+                        if client.send(report.into()).is_err() {
+                            debug!("Client {} disconnected during usage report", client.key());
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // Re-evaluates load every window, telling clients when their low-priority streams are
+    // conflated further or restored
+    async fn start_load_shedding(&self, load_shedder: Arc<LoadShedder>) {
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let clients = Arc::clone(&self.clients);
+
+        tokio::spawn(async move {
+            let mut interval = interval(LOAD_WINDOW);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let Some(conflation) = load_shedder.evaluate() else {
+                    continue;
+                };
+                match conflation {
+                    Some(conflation) => warn!("Server under load, conflating low-priority streams to {:?}", conflation),
+                    None => info!("Load subsided, low-priority streams back at full rate"),
+                }
+
+                let mut streams: HashMap<Uuid, Vec<String>> = HashMap::new();
+                for (client_id, stream_id) in subscriptions.find(|subscription| subscription.priority == StreamPriority::Low) {
+                    streams.entry(client_id).or_default().push(stream_id);
+                }
+
+                for (client_id, stream_ids) in streams {
+                    let Some(client_sender) = clients.get(&client_id) else {
+                        continue;
+                    };
+
+                    let notice = throttled_notice(stream_ids, conflation);
+                    if client_sender.send(notice.into()).is_err() {
+                        debug!("Client {} disconnected during throttle notice", client_id);
+                    }
+                }
+            }
+        });
+    }
+
+    async fn start_symbol_eviction(&self, ttl: Duration) {
+        let order_books = Arc::clone(&self.order_books);
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let snapshots = Arc::clone(&self.snapshots);
+        let flows = Arc::clone(&self.flows);
+        let metrics = Arc::clone(&self.metrics);
+        let pinned_symbols = Arc::clone(&self.pinned_symbols);
+        let symbol_registry = Arc::clone(&self.symbol_registry);
+        let history = self.history.clone();
+
+        tokio::spawn(async move {
+            let mut interval = interval(ttl.min(Duration::from_secs(5)));
+            let mut idle_since: HashMap<String, Instant> = HashMap::new();
+
+            loop {
+                interval.tick().await;
+                let now = Instant::now();
+                let _registry = symbol_registry.lock().await;
+
+                let symbols: Vec<String> = order_books
+                    .iter()
+                    .map(|entry| entry.key().clone())
+                    .filter(|symbol| !pinned_symbols.contains(symbol))
+                    .collect();
+                idle_since.retain(|symbol, _| order_books.contains_key(symbol));
+
+                for symbol in symbols {
+                    if subscriptions.symbol(&symbol).is_some() {
+                        idle_since.remove(&symbol);
+                        continue;
+                    }
+
+                    let since = *idle_since.entry(symbol.clone()).or_insert(now);
+                    if now.duration_since(since) >= ttl {
+                        order_books.remove(&symbol);
+                        flows.remove(&symbol);
+                        snapshots.remove_symbol(&symbol);
+                        metrics.forget_symbol(&symbol);
+                        if let Some(history) = &history {
+                            history.remove(&symbol);
+                        }
+                        idle_since.remove(&symbol);
+                        info!("Evicted idle symbol: {}", symbol);
+                    }
+                }
+            }
+        });
+    }
+
     pub fn register_client(&self, client_id: Uuid, sender: SSEClientSender) {
         self.clients.insert(client_id, sender);
-        self.client_streams.insert(client_id, Vec::new());
+        self.usage.register(client_id);
         info!("Registered SSE client: {}", client_id);
     }
 
     pub fn unregister_client(&self, client_id: &Uuid) {
         // Remove all subscriptions for this client
-        let client_streams = self.client_streams.remove(client_id);
-        if let Some((_, streams)) = client_streams {
-            for stream_id in streams {
-                self.remove_subscription(client_id, &stream_id);
-            }
+        let removed = self.subscriptions.remove_client(client_id);
+        debug!("Removed {} subscriptions for client {}", removed, client_id);
+        self.snapshot_schedules.retain(|(schedule_client, _), _| schedule_client != client_id);
+        if let Some(load_shedder) = &self.load_shedder {
+            load_shedder.forget_client(client_id);
         }
 
+        self.summary_subscribers.remove(client_id);
+        self.replays.retain(|(replay_client, _)| replay_client != client_id);
         self.clients.remove(client_id);
+        self.usage.unregister(client_id);
+        if let Some(quotas) = &self.quotas {
+            quotas.unbind(client_id);
+        }
         info!("Unregistered SSE client: {}", client_id);
     }
 
+    // Sends replay_started and then the recorded updates of a window to the client. The
+    // updates carry their recorded sequence and event_ts, so the replay stream reads like
+    // the live one did.
+    pub fn start_replay(
+        &self,
+        client_id: Uuid,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        speed: Option<f64>,
+        max_levels: u32,
+    ) -> Result<(), String> {
+        let Some(history) = &self.history else {
+            return Err("History is not recorded on this server".to_string());
+        };
+        let speed = validate_replay_speed(speed)?;
+        let max_levels = max_levels.min(history.levels());
+        let frames = history.window(symbol, from, to)?;
+
+        let stream_id = format!("{}_REPLAY", symbol);
+        let key = (client_id, stream_id.clone());
+        if !self.replays.insert(key.clone()) {
+            return Err(format!("Replay {} is already running", stream_id));
+        }
+
+        if let Some(client_sender) = self.clients.get(&client_id) {
+            let started = SSEMessage::ReplayStarted {
+                stream_id: stream_id.clone(),
+                symbol: symbol.to_string(),
+                from: frames[0].event_ts,
+                to: frames[frames.len() - 1].event_ts,
+                updates: frames.len(),
+                speed,
+            };
+            let _ = client_sender.send(started.into());
+        }
+        info!("SSE client {} started replay {}", client_id, stream_id);
+
+        let clients = Arc::clone(&self.clients);
+        let replays = Arc::clone(&self.replays);
+        let symbol: Arc<str> = Arc::from(symbol);
+        tokio::spawn(async move {
+            let mut replay = Replay::new(frames, speed);
+            while let Some(frame) = replay.next().await {
+                if !replays.contains(&key) {
+                    return;
+                }
+                let Some(client_sender) = clients.get(&client_id) else {
+                    return;
+                };
+                let (bids, asks) = frame.levels(max_levels);
+                let timestamp = Utc::now();
+                let update = SSEMessage::MarketData {
+                    stream_id: stream_id.clone(),
+                    symbol: Arc::clone(&symbol),
+                    data: MarketDataUpdate::MBP { bids, asks },
+                    sequence: frame.sequence,
+                    timestamp,
+                    event_ts: frame.event_ts,
+                    send_ts: timestamp, // Stamped again when the event is written
+                };
+                if client_sender.send(update.into()).is_err() {
+                    return;
+                }
+            }
+
+            if replays.remove(&key).is_some() {
+                if let Some(client_sender) = clients.get(&client_id) {
+                    let _ = client_sender.send(SSEMessage::ReplayComplete { stream_id: stream_id.clone() }.into());
+                }
+                info!("SSE client {} finished replay {}", client_id, stream_id);
+            }
+        });
+
+        Ok(())
+    }
+
     pub async fn subscribe_to_streams(
         &self,
         client_id: Uuid,
         stream_definitions: Vec<(String, DataType, u32)>,
+        max_orders: Option<u32>,
+        snapshot_interval_ms: Option<u64>,
+        priority: Option<StreamPriority>,
     ) -> Result<(), String> {
+        let snapshot_interval = snapshot_interval_ms.map(validate_snapshot_interval).transpose()?;
+        let priority = priority.unwrap_or_default();
+        let mut stream_ids = Vec::new();
+
         for (symbol, data_type, max_levels) in stream_definitions {
-            // Ensure the symbol exists
+            if let Some(pair) = self.pairs.get(&symbol).map(|pair| pair.clone()) {
+                stream_ids.push(self.subscribe_to_pair(client_id, pair, data_type, priority).await?);
+                continue;
+            }
+
+            if self.symbol_config.get(&symbol).is_some_and(|config| !config.allows(&data_type)) {
+                return Err(format!("{} streams are not enabled for {}", data_type.name(), symbol));
+            }
+
+            // Ensure the symbol exists; the registry lock keeps eviction away until the subscription is in place
+            let registry = self.symbol_registry.lock().await;
             if !self.order_books.contains_key(&symbol) {
+                self.check_symbol_limit()?;
                 self.initialize_symbol(&symbol).await;
             }
 
-            let stream_id = format!("{}_{:?}_{}", symbol, data_type, max_levels);
+            let stream_id = self.stream_id(&symbol, &data_type, max_levels);
 
-            let subscription = SSESubscription::new(
+            let mut subscription = SSESubscription::new(
                 stream_id.clone(),
                 symbol.clone(),
                 data_type.clone(),
                 Some(max_levels),
+                max_orders,
                 client_id,
             );
+            subscription.priority = priority;
+            let payload = shared_payload(&subscription);
 
             // Add subscription
-            self.subscriptions
-                .entry(symbol.clone())
-                .or_insert_with(Vec::new)
-                .push(subscription);
-
-            // Track this stream for the client
-            self.client_streams
-                .entry(client_id)
-                .or_insert_with(Vec::new)
-                .push(stream_id.clone());
-
-            // Send initial snapshot
+            self.subscriptions.insert(
+                client_id,
+                &stream_id,
+                &symbol,
+                payload,
+                subscription,
+            );
+            drop(registry);
+
+            // Full-book snapshots only make sense for book streams; flow streams go without
+            if let (Some(interval), DataType::MBP | DataType::MBO) = (snapshot_interval, &data_type) {
+                let by_order = matches!(data_type, DataType::MBO);
+                let schedule = SnapshotSchedule::new(&symbol, by_order, interval);
+                self.snapshot_schedules.insert((client_id, stream_id.clone()), schedule);
+            }
+
+            // Send initial snapshot, reusing this tick's view when another subscriber already built it
             if let Some(order_book_ref) = self.order_books.get(&symbol) {
                 if let Some(client_sender) = self.clients.get(&client_id) {
-                    let market_data = {
-                        let order_book = order_book_ref.read().await;
-                        match data_type {
-                            DataType::MBO => {
-                                let (bids, asks) = order_book.get_mbo_data(max_levels);
-                                MarketDataUpdate::MBO { bids, asks }
-                            }
-                            DataType::MBP => {
-                                let (bids, asks) = order_book.get_mbp_data(max_levels);
-                                MarketDataUpdate::MBP { bids, asks }
-                            }
+                    let order_book = order_book_ref.snapshot();
+                    let (sequence, event_ts) = (order_book.get_sequence(), order_book.get_event_timestamp());
+
+                    let data = match self.snapshots.get(&symbol, &payload, sequence) {
+                        Some(data) => data,
+                        None => {
+                            let Some(data) = build_shared_payload(&order_book, &self.flows, &symbol, payload) else {
+                                return Err("Failed to build initial snapshot".to_string());
+                            };
+                            self.snapshots.insert(&symbol, payload, sequence, data.clone());
+                            data
                         }
                     };
 
-                    let initial_message = SSEMessage::MarketData {
+                    let initial_message = SSEOutbound::SharedMarketData {
                         stream_id: stream_id.clone(),
-                        symbol: symbol.clone(),
-                        data: market_data,
-                        sequence: {
-                            let order_book = order_book_ref.read().await;
-                            order_book.get_sequence()
-                        },
+                        symbol: symbol.as_str().into(),
+                        data,
+                        sequence,
                         timestamp: Utc::now(),
+                        event_ts,
                     };
 
-                    if let Err(_) = client_sender.send(initial_message) {
+                    if client_sender.send(initial_message).is_err() {
                         return Err("Failed to send initial snapshot".to_string());
                     }
                 }
@@ -231,49 +768,413 @@ impl SSEStreamManager {
             info!("Client {} subscribed to {} stream {} ({:?})",
                 client_id, symbol, stream_id, data_type
             );
+            stream_ids.push(stream_id);
+        }
+
+        // Streams opened while low priority is being conflated are told so straight away
+        let conflation = self.load_shedder.as_ref().and_then(|load_shedder| load_shedder.interval());
+        if let (StreamPriority::Low, Some(conflation)) = (priority, conflation) {
+            if let Some(client_sender) = self.clients.get(&client_id) {
+                let _ = client_sender.send(throttled_notice(stream_ids, Some(conflation)).into());
+            }
         }
 
         Ok(())
     }
 
-    fn remove_subscription(&self, client_id: &Uuid, stream_id: &str) {
-        for mut entry in self.subscriptions.iter_mut() {
-            let initial_len = entry.value().len();
-            entry.value_mut().retain(|sub|
-                !(sub.client_id == *client_id && sub.stream_id == *stream_id)
-            );
+    // Returns the stream id the pair is published under
+    async fn subscribe_to_pair(
+        &self,
+        client_id: Uuid,
+        pair: SyntheticPair,
+        data_type: DataType,
+        priority: StreamPriority,
+    ) -> Result<String, String> {
+        if !matches!(data_type, DataType::MBP) {
+            return Err(format!("Synthetic pair {} only supports MBP streams", pair.name));
+        }
+
+        let stream_id = self.stream_id(&pair.name, &data_type, 0);
+
+        let mut subscription = SSESubscription::new(
+            stream_id.clone(),
+            pair.name.clone(),
+            data_type,
+            None,
+            None,
+            client_id,
+        );
+        subscription.priority = priority;
 
-            if entry.value().len() != initial_len {
-                debug!("Removed subscription for client {} stream {}", client_id, stream_id);
-                break;
+        self.subscriptions.insert(
+            client_id,
+            &stream_id,
+            &pair.name,
+            shared_payload(&subscription),
+            subscription,
+        );
+
+        // Send initial synthetic quote
+        if let Some((market_data, sequence, event_ts)) = compute_pair_update(&self.order_books, &pair) {
+            if let Some(client_sender) = self.clients.get(&client_id) {
+                let timestamp = Utc::now();
+                let initial_message = SSEMessage::MarketData {
+                    stream_id: stream_id.clone(),
+                    symbol: pair.name.as_str().into(),
+                    data: market_data,
+                    sequence,
+                    timestamp,
+                    event_ts,
+                    send_ts: timestamp, // Stamped again when the event is written
+                };
+
+                if client_sender.send(initial_message.into()).is_err() {
+                    return Err("Failed to send initial snapshot".to_string());
+                }
             }
         }
 
-        // Clean up empty subscription lists
-        self.subscriptions.retain(|_, v| !v.is_empty());
+        info!("Client {} subscribed to synthetic pair {} stream {}", client_id, pair.name, stream_id);
+
+        Ok(stream_id)
+    }
+
+    pub async fn subscribe_to_summary(&self, client_id: Uuid) -> Result<(), String> {
+        self.summary_subscribers.insert(client_id);
+
+        // Send the current summary so the client does not wait for the next cycle
+        if let Some(client_sender) = self.clients.get(&client_id) {
+            let initial_message = SSEMessage::MarketSummary {
+                summary: self.get_market_summary().await,
+            };
+
+            if client_sender.send(initial_message.into()).is_err() {
+                return Err("Failed to send initial summary".to_string());
+            }
+        }
+
+        info!("Client {} subscribed to market summary", client_id);
+
+        Ok(())
     }
 
     pub async fn get_symbols(&self) -> Vec<String> {
-        self.order_books.iter().map(|entry| entry.key().clone()).collect()
+        self.order_books
+            .iter()
+            .map(|entry| entry.key().clone())
+            .chain(self.pairs.iter().map(|entry| entry.key().clone()))
+            .collect()
     }
 
-    pub fn get_client_sender(&self, client_id: &Uuid) -> Option<dashmap::mapref::one::Ref<Uuid, SSEClientSender>> {
+    // Books and synthetic pairs matching the query
+    pub fn search_symbols(&self, query: &SymbolQuery) -> Vec<SymbolInfo> {
+        let info = |symbol: &str, symbol_type, (base, quote), tick_size, quantity_decimals| SymbolInfo {
+            symbol: symbol.to_string(),
+            symbol_type,
+            status: SymbolStatus::Open,
+            venue: self.venue.clone(),
+            base,
+            quote,
+            tick_size,
+            quantity_decimals,
+        };
+
+        let books = self.order_books.iter().map(|entry| {
+            let quantity_decimals = self.quantity_decimals.get(entry.key()).copied().unwrap_or(0);
+            info(entry.key(), SymbolType::Spot, split_currency_pair(entry.key()), Some(SIMULATED_TICK_SIZE), Some(quantity_decimals))
+        });
+        let pairs = self.pairs.iter().map(|pair| {
+            info(pair.key(), SymbolType::Pair, (Some(pair.base.clone()), Some(pair.quote.clone())), None, None)
+        });
+
+        query.apply(books.chain(pairs).collect())
+    }
+
+    pub async fn get_market_summary(&self) -> MarketSummary {
+        if let Some(summary) = self.latest_summary.read().await.clone() {
+            return summary;
+        }
+
+        compute_market_summary(&self.order_books)
+    }
+
+    pub fn record_usage(&self, client_id: &Uuid, stream_id: Option<&str>, bytes: usize) {
+        self.usage.record(client_id, stream_id, bytes);
+        if let Some(quotas) = &self.quotas {
+            quotas.record(client_id, bytes);
+        }
+        self.metrics.record_sent(bytes);
+    }
+
+    // Bills the client's deliveries to an API key
+    pub fn bind_api_key(&self, client_id: Uuid, api_key: &str) {
+        if let Some(quotas) = &self.quotas {
+            quotas.bind(client_id, api_key);
+        }
+    }
+
+    pub fn check_quota(&self, client_id: &Uuid, stream: &str) -> QuotaDecision {
+        self.quotas
+            .as_ref()
+            .map_or(QuotaDecision::Deliver, |quotas| quotas.check(client_id, stream))
+    }
+
+    pub fn quota_exceeded(&self, client_id: &Uuid) -> Option<QuotaRemaining> {
+        self.quotas.as_ref()?.exceeded(client_id)
+    }
+
+    // Cuts a client off from its streams once a quota is used up, telling it which one
+    pub fn cut_off_quota(&self, client_id: &Uuid, quota: QuotaRemaining) {
+        let removed = self.subscriptions.remove_client(client_id)
+            + usize::from(self.summary_subscribers.remove(client_id).is_some());
+        if removed == 0 {
+            return;
+        }
+
+        info!("SSE client {} exceeded its {:?} {:?} quota, removed {} subscriptions", client_id, quota.period, quota.unit, removed);
+        if let Some(quotas) = &self.quotas {
+            quotas.report_exceeded(client_id, &quota);
+        }
+        if let Some(client_sender) = self.clients.get(client_id) {
+            let _ = client_sender.send(SSEMessage::QuotaExceeded { quota }.into());
+        }
+    }
+
+    pub fn quota_status(&self, api_key: &str) -> Option<QuotaStatus> {
+        self.quotas.as_ref().map(|quotas| quotas.status(api_key))
+    }
+
+    pub fn record_queue_depth(&self, depth: usize) {
+        self.metrics.observe_queue_depth(depth);
+        if let Some(load_shedder) = &self.load_shedder {
+            load_shedder.observe_queue_depth(depth);
+        }
+    }
+
+    // Whether a client with this many messages queued has fallen too far behind
+    pub fn is_slow_consumer(&self, queued: usize) -> bool {
+        self.limits.max_client_queue.is_some_and(|limit| queued > limit)
+    }
+
+    // Jumps any queued market data; the connection closes once it is sent. False if the
+    // client is not connected.
+    pub fn disconnect_client(&self, client_id: &Uuid, reason: DisconnectReason) -> bool {
+        let Some(client_sender) = self.clients.get(client_id) else {
+            return false;
+        };
+        info!("Disconnecting client {}: {}", client_id, reason);
+        client_sender.send(SSEMessage::disconnected(reason).into()).is_ok()
+    }
+
+    // Returns how many clients were told to disconnect
+    pub fn disconnect_all(&self, reason: DisconnectReason) -> usize {
+        self.clients.iter().filter(|client_sender| client_sender.send(SSEMessage::disconnected(reason).into()).is_ok()).count()
+    }
+
+    pub fn connected_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    pub fn render_metrics(&self) -> String {
+        let subscription_counts = self.subscriptions.counts();
+
+        self.metrics.set_connected_clients(self.clients.len());
+        self.metrics.set_subscriptions(
+            subscription_counts.iter().map(|(symbol, count)| (symbol.as_str(), *count)),
+        );
+        self.metrics.render()
+    }
+
+    pub fn get_usage_snapshot(&self) -> UsageSnapshot {
+        self.usage.snapshot()
+    }
+
+    pub fn get_client_sender(&self, client_id: &Uuid) -> Option<dashmap::mapref::one::Ref<'_, Uuid, SSEClientSender>> {
         self.clients.get(client_id)
     }
 
-    pub async fn send_connection_info(&self, client_id: Uuid) {
+    // Stream id a definition is published under; pairs have one stream whatever the levels
+    fn stream_id(&self, symbol: &str, data_type: &DataType, max_levels: u32) -> String {
+        if self.pairs.contains_key(symbol) {
+            format!("{}_PAIR", symbol)
+        } else {
+            format!("{}_{:?}_{}", symbol, data_type, max_levels)
+        }
+    }
+
+    // Echoes the streams the client is about to be subscribed to
+    pub async fn send_connection_info(&self, client_id: Uuid, stream_definitions: &[(String, DataType, u32)], summary: bool) {
         let symbols = self.get_symbols().await;
+        let subscriptions = stream_definitions
+            .iter()
+            .map(|(symbol, data_type, max_levels)| StreamDefinition {
+                stream_id: self.stream_id(symbol, data_type, *max_levels),
+                symbol: symbol.clone(),
+                data_type: data_type.clone(),
+                max_levels: Some(*max_levels).filter(|_| !self.pairs.contains_key(symbol)),
+            })
+            .collect();
 
         if let Some(client_sender) = self.clients.get(&client_id) {
             let connection_info = SSEMessage::ConnectionInfo {
                 client_id: client_id.to_string(),
                 server_time: Utc::now(),
                 supported_symbols: symbols,
+                subscriptions,
+                summary,
             };
 
-            if let Err(_) = client_sender.send(connection_info) {
+            if client_sender.send(connection_info.into()).is_err() {
                 debug!("Failed to send connection info to client {}", client_id);
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn compute_market_summary(
+    order_books: &DashMap<String, Arc<PublishedBook>>,
+) -> MarketSummary {
+    let symbols = order_books
+        .iter()
+        .map(|entry| entry.value().snapshot().get_symbol_summary())
+        .collect();
+
+    build_market_summary(symbols, SUMMARY_TOP_N)
+}
+
+// Sends every full-book snapshot that is due, building each book's view once. Schedules
+// of disconnected clients and of evicted books are dropped.
+fn send_due_snapshots(
+    schedules: &DashMap<StreamKey, SnapshotSchedule>,
+    order_books: &DashMap<String, Arc<PublishedBook>>,
+    clients: &DashMap<Uuid, SSEClientSender>,
+) {
+    let now = Instant::now();
+    let mut views: HashMap<(String, bool), (MarketDataUpdate, u64)> = HashMap::new();
+
+    schedules.retain(|(client_id, stream_id), schedule| {
+        let Some(client_sender) = clients.get(client_id) else {
+            return false;
+        };
+        if !schedule.poll(now) {
+            return true;
+        }
+        let Some(order_book_ref) = order_books.get(&schedule.symbol).map(|entry| entry.value().clone()) else {
+            return false;
+        };
+
+        let (data, sequence) = views.entry((schedule.symbol.clone(), schedule.by_order)).or_insert_with(|| {
+            let order_book = order_book_ref.snapshot();
+            (full_book_update(&order_book, schedule.by_order), order_book.get_sequence())
+        });
+        let message = SSEMessage::Snapshot {
+            stream_id: stream_id.clone(),
+            symbol: schedule.symbol.clone(),
+            data: data.clone(),
+            sequence: *sequence,
+            timestamp: Utc::now(),
+        };
+
+        if client_sender.send(message.into()).is_err() {
+            debug!("Client {} disconnected during snapshot send", client_id);
+        }
+        true
+    });
+}
+
+fn full_book_update(order_book: &OrderBook, by_order: bool) -> MarketDataUpdate {
+    if by_order {
+        let (bids, asks) = order_book.get_mbo_data(u32::MAX, u32::MAX);
+        MarketDataUpdate::MBO { bids, asks }
+    } else {
+        let (bids, asks) = order_book.get_mbp_data(u32::MAX);
+        MarketDataUpdate::MBP { bids, asks }
+    }
+}
+
+// The pair's quote, sequence and event time: the later of its legs' last changes
+fn compute_pair_update(
+    order_books: &DashMap<String, Arc<PublishedBook>>,
+    pair: &SyntheticPair,
+) -> Option<(MarketDataUpdate, u64, DateTime<Utc>)> {
+    let base_book = order_books.get(&pair.base)?.snapshot();
+    let quote_book = order_books.get(&pair.quote)?.snapshot();
+
+    let quote = pair.compute_quote(&base_book, &quote_book);
+    let sequence = base_book.get_sequence() + quote_book.get_sequence();
+    let event_ts = base_book.get_event_timestamp().max(quote_book.get_event_timestamp());
+
+    Some((MarketDataUpdate::Pair { quote }, sequence, event_ts))
+}
+
+// Whether a low-priority subscription's update is held back by load shedding this tick
+fn conflated(load_shedder: &Option<Arc<LoadShedder>>, subscription: &SSESubscription, now: Instant) -> bool {
+    subscription.priority == StreamPriority::Low
+        && load_shedder
+            .as_ref()
+            .is_some_and(|load_shedder| !load_shedder.deliver(&subscription.client_id, &subscription.stream_id, now))
+}
+
+fn throttled_notice(stream_ids: Vec<String>, conflation: Option<Duration>) -> SSEMessage {
+    SSEMessage::Throttled {
+        stream_ids,
+        interval_ms: conflation.map(|conflation| conflation.as_millis() as u64),
+        timestamp: Utc::now(),
+    }
+}
+
+fn flow_update(flows: &DashMap<String, AggressorFlow>, symbol: &str) -> MarketDataUpdate {
+    let flow = flows
+        .get(symbol)
+        .map(|flow| flow.stats())
+        .unwrap_or_else(|| AggressorFlow::default().stats());
+
+    MarketDataUpdate::AggressorFlow { flow }
+}
+
+// Snapshot views that are identical for every subscriber asking for the same depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SharedPayload {
+    ByOrder(u32, u32),
+    ByPrice(u32),
+    AggressorFlow,
+}
+
+fn build_shared_payload(
+    order_book: &OrderBook,
+    flows: &DashMap<String, AggressorFlow>,
+    symbol: &str,
+    key: SharedPayload,
+) -> Option<Bytes> {
+    let market_data = match key {
+        SharedPayload::ByOrder(max_levels, max_orders) => {
+            let (bids, asks) = order_book.get_mbo_data(max_levels, max_orders);
+            MarketDataUpdate::MBO { bids, asks }
+        }
+        SharedPayload::ByPrice(max_levels) => {
+            let (bids, asks) = order_book.get_mbp_data(max_levels);
+            MarketDataUpdate::MBP { bids, asks }
+        }
+        SharedPayload::AggressorFlow => flow_update(flows, symbol),
+    };
+
+    serialize_payload(&market_data)
+}
+
+fn serialize_payload(market_data: &MarketDataUpdate) -> Option<Bytes> {
+    match serde_json::to_vec(market_data) {
+        Ok(json) => Some(Bytes::from(json)),
+        Err(e) => {
+            error!("Failed to serialize market data payload: {}", e);
+            None
+        }
+    }
+}
+
+fn shared_payload(subscription: &SSESubscription) -> SharedPayload {
+    match subscription.data_type {
+        DataType::MBO => SharedPayload::ByOrder(subscription.max_levels, subscription.max_orders),
+        DataType::MBP => SharedPayload::ByPrice(subscription.max_levels),
+        DataType::AggressorFlow => SharedPayload::AggressorFlow,
+    }
+}
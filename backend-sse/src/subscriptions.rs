@@ -0,0 +1,124 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+use dashmap::mapref::one::Ref;
+use dashmap::DashMap;
+use uuid::Uuid;
+
+// A subscription is identified by the client that opened it and its stream id
+pub type StreamKey = (Uuid, String);
+
+// Subscriptions of one symbol, grouped by the fan-out view they receive
+pub type SymbolSubscriptions<G, S> = HashMap<G, HashMap<StreamKey, S>>;
+
+// Subscriptions indexed by symbol for fan-out, and by stream and client so that
+// unsubscribing and disconnecting do not scan every symbol
+#[derive(Debug)]
+pub struct SubscriptionIndex<G, S> {
+    by_symbol: DashMap<String, SymbolSubscriptions<G, S>>,
+    by_stream: DashMap<StreamKey, (String, G)>,
+    by_client: DashMap<Uuid, HashSet<String>>,
+}
+
+impl<G: Clone + Eq + Hash, S> Default for SubscriptionIndex<G, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G: Clone + Eq + Hash, S> SubscriptionIndex<G, S> {
+    pub fn new() -> Self {
+        Self {
+            by_symbol: DashMap::new(),
+            by_stream: DashMap::new(),
+            by_client: DashMap::new(),
+        }
+    }
+
+    // Adds a subscription, replacing and returning any the client already had under the same stream id
+    pub fn insert(&self, client_id: Uuid, stream_id: &str, symbol: &str, group: G, subscription: S) -> Option<S> {
+        let key = (client_id, stream_id.to_string());
+        let replaced = self.remove_entry(&key);
+
+        self.by_symbol
+            .entry(symbol.to_string())
+            .or_default()
+            .entry(group.clone())
+            .or_default()
+            .insert(key.clone(), subscription);
+        self.by_stream.insert(key, (symbol.to_string(), group));
+        self.by_client
+            .entry(client_id)
+            .or_default()
+            .insert(stream_id.to_string());
+
+        replaced
+    }
+
+    pub fn remove(&self, client_id: &Uuid, stream_id: &str) -> Option<S> {
+        let key = (*client_id, stream_id.to_string());
+        let removed = self.remove_entry(&key)?;
+
+        if let Some(mut streams) = self.by_client.get_mut(client_id) {
+            streams.remove(stream_id);
+        }
+        self.by_client.remove_if(client_id, |_, streams| streams.is_empty());
+
+        Some(removed)
+    }
+
+    // Removes every subscription of a client, returning how many there were
+    pub fn remove_client(&self, client_id: &Uuid) -> usize {
+        let Some((_, streams)) = self.by_client.remove(client_id) else {
+            return 0;
+        };
+
+        streams
+            .into_iter()
+            .filter_map(|stream_id| self.remove_entry(&(*client_id, stream_id)))
+            .count()
+    }
+
+    // Symbol and group of one subscription
+    pub fn stream(&self, key: &StreamKey) -> Option<(String, G)> {
+        self.by_stream.get(key).map(|entry| entry.value().clone())
+    }
+
+    pub fn symbol(&self, symbol: &str) -> Option<Ref<'_, String, SymbolSubscriptions<G, S>>> {
+        self.by_symbol.get(symbol)
+    }
+
+    // Keys of every subscription the predicate accepts, across all symbols
+    pub fn find(&self, predicate: impl Fn(&S) -> bool) -> Vec<StreamKey> {
+        let mut keys = Vec::new();
+        for entry in self.by_symbol.iter() {
+            for subscriptions in entry.value().values() {
+                keys.extend(subscriptions.iter().filter(|(_, subscription)| predicate(subscription)).map(|(key, _)| key.clone()));
+            }
+        }
+        keys
+    }
+
+    pub fn counts(&self) -> Vec<(String, usize)> {
+        self.by_symbol
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().values().map(HashMap::len).sum()))
+            .collect()
+    }
+
+    fn remove_entry(&self, key: &StreamKey) -> Option<S> {
+        let (_, (symbol, group)) = self.by_stream.remove(key)?;
+
+        let removed = {
+            let mut groups = self.by_symbol.get_mut(&symbol)?;
+            let subscriptions = groups.get_mut(&group)?;
+            let removed = subscriptions.remove(key);
+            if subscriptions.is_empty() {
+                groups.remove(&group);
+            }
+            removed
+        };
+        self.by_symbol.remove_if(&symbol, |_, groups| groups.is_empty());
+
+        removed
+    }
+}
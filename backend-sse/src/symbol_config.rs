@@ -0,0 +1,83 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use clap::Args;
+use serde::Deserialize;
+
+use crate::message::DataType;
+use crate::stream_validation::validate_symbol;
+
+const SYMBOL_DATA_TYPES: [&str; 3] = ["MBO", "MBP", "AggressorFlow"];
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct SymbolConfigOptions {
+    /// JSON file listing the books created at startup, each with its feed source, starting
+    /// price, quantity decimals and enabled data types (default: BTCUSD, ETHUSD and ADAUSD,
+    /// simulated with every data type)
+    #[arg(long, value_name = "PATH")]
+    pub symbol_config: Option<PathBuf>,
+}
+
+impl SymbolConfigOptions {
+    pub fn symbols(&self) -> Result<Option<Vec<SymbolConfig>>, String> {
+        self.symbol_config.as_deref().map(load_symbol_config).transpose()
+    }
+}
+
+// Where a configured book's orders come from; the file is shared with the WebSocket
+// server, but this server only simulates books
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeedSource {
+    #[default]
+    Sim,
+    Ingest,
+    Binance,
+    Replay,
+}
+
+// One book of the startup symbol list
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SymbolConfig {
+    pub symbol: String,
+    #[serde(default)]
+    pub source: FeedSource,
+    pub price: Option<f64>, // The mid the book's sample orders start around
+    pub quantity_decimals: Option<u32>, // Overrides --quantity-decimals
+    pub data_types: Option<Vec<String>>, // Every data type when left out
+}
+
+impl SymbolConfig {
+    pub fn allows(&self, data_type: &DataType) -> bool {
+        self.data_types.as_ref().is_none_or(|names| names.iter().any(|name| name == data_type.name()))
+    }
+}
+
+pub fn load_symbol_config(path: &Path) -> Result<Vec<SymbolConfig>, String> {
+    let contents = std::fs::read(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let symbols: Vec<SymbolConfig> = serde_json::from_slice(&contents).map_err(|e| format!("{}: {}", path.display(), e))?;
+    validate_symbol_config(&symbols).map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(symbols)
+}
+
+pub fn validate_symbol_config(symbols: &[SymbolConfig]) -> Result<(), String> {
+    let mut seen = HashSet::new();
+    for config in symbols {
+        let symbol = &config.symbol;
+        validate_symbol(symbol)?;
+        if !seen.insert(symbol.as_str()) {
+            return Err(format!("{} is listed more than once", symbol));
+        }
+
+        if config.source != FeedSource::Sim {
+            return Err(format!("{}: the {:?} source is not available on the SSE server, use sim", symbol, config.source));
+        }
+        if let Some(price) = config.price.filter(|price| !price.is_finite() || *price <= 0.0) {
+            return Err(format!("{}: invalid price {}", symbol, price));
+        }
+        if let Some(name) = config.data_types.iter().flatten().find(|name| !SYMBOL_DATA_TYPES.contains(&name.as_str())) {
+            return Err(format!("{}: unknown data type {}, expected one of {}", symbol, name, SYMBOL_DATA_TYPES.join(", ")));
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,118 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use clap::Args;
+#[cfg(unix)]
+use axum::{extract::ConnectInfo, Extension, Router};
+#[cfg(unix)]
+use hyper_util::{rt::{TokioExecutor, TokioIo}, server::conn::auto, service::TowerToHyperService};
+#[cfg(unix)]
+use tokio::net::UnixListener;
+#[cfg(unix)]
+use tracing::debug;
+
+// Unix domain sockets served next to the TCP listener, for sidecar deployments where a
+// local proxy terminates external traffic. Peers on a socket have no address of their
+// own: they count as the loopback address for access rules and are exempt from per-IP
+// rate limits, which would otherwise lump every one of them together.
+#[derive(Debug, Clone, Default, Args)]
+pub struct UnixSocketOptions {
+    /// Also listen on this Unix domain socket; `@NAME` binds an abstract socket (Linux only)
+    #[arg(long, value_name = "PATH")]
+    pub unix_socket: Option<String>,
+
+    /// Permissions of Unix socket files in octal, e.g. 660 (default: from the umask)
+    #[arg(long, value_name = "MODE", value_parser = parse_socket_mode)]
+    pub unix_socket_mode: Option<u32>,
+}
+
+pub const UNIX_PEER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+// The address per-IP limits apply to, None for a Unix socket peer
+pub fn peer_ip(peer_addr: SocketAddr) -> Option<IpAddr> {
+    (peer_addr != UNIX_PEER).then_some(peer_addr.ip())
+}
+
+fn parse_socket_mode(value: &str) -> Result<u32, String> {
+    match u32::from_str_radix(value, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(mode),
+        _ => Err(format!("Invalid socket mode {}, expected octal permissions such as 660", value)),
+    }
+}
+
+pub fn unsupported_unix_socket() -> io::Error {
+    io::Error::new(io::ErrorKind::Unsupported, "Unix domain sockets are not available on this platform")
+}
+
+impl UnixSocketOptions {
+    // Binds `path`, replacing a socket file left behind by a server that is gone. With a
+    // mode, the socket is bound in a directory only the server can enter and moved to
+    // `path` once it has the mode, so it is never reachable with the umask's permissions.
+    #[cfg(unix)]
+    pub fn bind(&self, path: &str) -> io::Result<UnixListener> {
+        use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+
+        if let Some(name) = path.strip_prefix('@') {
+            return bind_abstract(name);
+        }
+
+        if let Ok(metadata) = std::fs::symlink_metadata(path) {
+            if !metadata.file_type().is_socket() {
+                return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("{} exists and is not a socket", path)));
+            }
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(io::ErrorKind::AddrInUse, format!("{} is in use by another server", path)));
+            }
+            std::fs::remove_file(path)?;
+        }
+
+        let Some(mode) = self.unix_socket_mode else {
+            return UnixListener::bind(path);
+        };
+        let private = format!("{}.{}.tmp", path, std::process::id());
+        std::fs::DirBuilder::new().mode(0o700).create(&private)?;
+        let staged = format!("{}/s", private);
+        let bound = UnixListener::bind(&staged).and_then(|listener| {
+            std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(mode))?;
+            std::fs::rename(&staged, path)?;
+            Ok(listener)
+        });
+        let _ = std::fs::remove_file(&staged);
+        let _ = std::fs::remove_dir(&private);
+        bound
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn bind_abstract(name: &str) -> io::Result<UnixListener> {
+    #[cfg(target_os = "android")]
+    use std::os::android::net::SocketAddrExt;
+    #[cfg(target_os = "linux")]
+    use std::os::linux::net::SocketAddrExt;
+
+    let addr = std::os::unix::net::SocketAddr::from_abstract_name(name)?;
+    let listener = std::os::unix::net::UnixListener::bind_addr(&addr)?;
+    listener.set_nonblocking(true)?;
+    UnixListener::from_std(listener)
+}
+
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android"))))]
+fn bind_abstract(_name: &str) -> io::Result<UnixListener> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "Abstract sockets are only available on Linux"))
+}
+
+// Serves an HTTP app on a Unix socket until accepting fails. Handlers see every
+// connection as coming from UNIX_PEER.
+#[cfg(unix)]
+pub async fn serve_unix(listener: UnixListener, app: Router) -> io::Result<()> {
+    let app = app.layer(Extension(ConnectInfo(UNIX_PEER)));
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let service = TowerToHyperService::new(app.clone());
+        tokio::spawn(async move {
+            let connection = auto::Builder::new(TokioExecutor::new());
+            if let Err(e) = connection.serve_connection_with_upgrades(TokioIo::new(stream), service).await {
+                debug!("Unix socket connection ended: {}", e);
+            }
+        });
+    }
+}
@@ -0,0 +1,152 @@
+// Minimal exchange bridge: forwards order events to a server started with --ingest
+// over the ingest WebSocket. Events are read as NDJSON from stdin, so a decoder for
+// any feed can be piped in; --demo generates a random order flow instead.
+//
+//   my-feed-decoder | cargo run --example exchange_bridge -- XYZUSD
+//   cargo run --example exchange_bridge -- XYZUSD --demo
+use std::time::Duration;
+use clap::Parser;
+use futures_util::{SinkExt, StreamExt};
+use market_depth_server::Side;
+use rand::Rng;
+use serde_json::{json, Value};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+#[derive(Parser)]
+#[command(about = "Forward order events to the market depth server's ingest API")]
+struct Args {
+    /// Symbol to publish
+    symbol: String,
+
+    /// Admin API address of the server
+    #[arg(long, default_value = "127.0.0.1:8090")]
+    admin_addr: String,
+
+    /// Generate a random order flow instead of reading events from stdin
+    #[arg(long)]
+    demo: bool,
+
+    /// Maximum events per frame
+    #[arg(long, default_value_t = 100)]
+    batch: usize,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let url = format!("ws://{}/ingest/{}", args.admin_addr, args.symbol);
+    let (socket, _) = connect_async(&url).await?;
+    let (mut sink, mut source) = socket.split();
+    eprintln!("Connected to {}", url);
+
+    let (events, mut pending) = mpsc::channel::<Value>(10_000);
+    if args.demo {
+        tokio::spawn(demo_feed(events));
+    } else {
+        tokio::spawn(stdin_feed(events));
+    }
+
+    // Reports come back in frame order; only rejections are worth printing
+    let reports = tokio::spawn(async move {
+        while let Some(Ok(Message::Text(text))) = source.next().await {
+            let report: Value = serde_json::from_str(&text).unwrap_or_default();
+            if report.get("error").is_some() || report["rejected"].as_array().is_some_and(|rejected| !rejected.is_empty()) {
+                eprintln!("{}", report);
+            }
+        }
+    });
+
+    // Everything queued since the last frame goes out together
+    let mut batch = Vec::with_capacity(args.batch);
+    while pending.recv_many(&mut batch, args.batch.max(1)).await > 0 {
+        sink.send(Message::Text(Value::Array(std::mem::take(&mut batch)).to_string())).await?;
+    }
+
+    sink.close().await?;
+    let _ = reports.await;
+    Ok(())
+}
+
+async fn stdin_feed(events: mpsc::Sender<Value>) {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(event) => {
+                if events.send(event).await.is_err() {
+                    break;
+                }
+            }
+            Err(e) => eprintln!("Skipping invalid line: {}", e),
+        }
+    }
+}
+
+// Quotes an order either side of a drifting mid price every 50ms, cancels orders the
+// mid has moved through and the oldest beyond 40, and occasionally fills one. Ids are
+// tracked so every event refers to a resting order.
+async fn demo_feed(events: mpsc::Sender<Value>) {
+    let mut mid = 100.0;
+    let mut resting: Vec<(u64, Side, f64, u64)> = Vec::new();
+    let mut next_id = 1u64;
+    let mut interval = tokio::time::interval(Duration::from_millis(50));
+
+    loop {
+        interval.tick().await;
+        let mut tick = Vec::new();
+        {
+            let mut rng = rand::thread_rng();
+            mid = (mid + rng.gen_range(-0.05..0.05_f64)).max(1.0);
+
+            resting.retain(|(order_id, side, price, _)| {
+                let crossed = match side {
+                    Side::Bid => *price >= mid,
+                    Side::Ask => *price <= mid,
+                };
+                if crossed {
+                    tick.push(json!({ "type": "cancel", "order_id": order_id.to_string() }));
+                }
+                !crossed
+            });
+            while resting.len() > 40 {
+                let (order_id, ..) = resting.remove(0);
+                tick.push(json!({ "type": "cancel", "order_id": order_id.to_string() }));
+            }
+
+            for side in [Side::Bid, Side::Ask] {
+                let offset = rng.gen_range(0.01..0.50_f64);
+                let price = match side {
+                    Side::Bid => mid - offset,
+                    Side::Ask => mid + offset,
+                };
+                let price = (price * 100.0).round() / 100.0;
+                let quantity = rng.gen_range(100..=5000);
+                tick.push(json!({
+                    "type": "add",
+                    "order_id": next_id.to_string(),
+                    "side": side,
+                    "price": price,
+                    "quantity": quantity,
+                }));
+                resting.push((next_id, side, price, quantity));
+                next_id += 1;
+            }
+
+            if rng.gen_bool(0.2) {
+                let (order_id, .., quantity) = resting.remove(rng.gen_range(0..resting.len()));
+                tick.push(json!({ "type": "trade", "order_id": order_id.to_string(), "quantity": quantity }));
+            }
+        }
+
+        for event in tick {
+            if events.send(event).await.is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// Consumes a simulated book in the same process as the stream manager, the way a
+// strategy engine or recorder embedding the server would, and prints the top of book.
+//
+//   cargo run --example local_subscriber -- BTCUSD --updates 20
+use std::sync::Arc;
+use clap::Parser;
+use futures_util::StreamExt;
+use market_depth_server::{DataType, MarketDataUpdate, StreamManager};
+
+#[derive(Parser)]
+#[command(about = "Print the top of a simulated book from an in-process subscription")]
+struct Args {
+    /// Symbol to follow
+    #[arg(default_value = "BTCUSD")]
+    symbol: String,
+
+    /// Price levels per side
+    #[arg(long, default_value_t = 5)]
+    depth: u32,
+
+    /// Stop after this many updates
+    #[arg(long, default_value_t = 20)]
+    updates: usize,
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let stream_manager = Arc::new(StreamManager::new());
+    stream_manager.start().await;
+
+    let updates = stream_manager
+        .subscribe_local(&args.symbol, DataType::MBP, args.depth)
+        .await
+        .map_err(anyhow::Error::msg)?;
+    let mut updates = Box::pin(updates.take(args.updates));
+
+    while let Some(update) = updates.next().await {
+        if let MarketDataUpdate::MBP { bids, asks } = update {
+            match (bids.first(), asks.first()) {
+                (Some(bid), Some(ask)) => println!("{} {} @ {} / {} @ {}", args.symbol, bid.quantity, bid.price, ask.quantity, ask.price),
+                _ => println!("{} one-sided book", args.symbol),
+            }
+        }
+    }
+
+    Ok(())
+}
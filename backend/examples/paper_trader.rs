@@ -0,0 +1,106 @@
+// Trades a simulated book on paper in the same process as the stream manager, the way a
+// strategy under development would, with its own fee and latency model, and prints the
+// order updates and fills.
+//
+//   cargo run --example paper_trader -- BTCUSD --quantity 2 --offset 0.5
+use std::sync::Arc;
+use std::time::Duration;
+use clap::Parser;
+use futures_util::StreamExt;
+use market_depth_server::{
+    DataType, MarketDataUpdate, PaperLiquidity, PaperOrder, PaperOrderRequest, PaperOrderStatus, PaperTradingModel,
+    Quantity, ServerMessage, Side, StreamManager,
+};
+
+#[derive(Parser)]
+#[command(about = "Buy at market and offer the position above the mid on paper")]
+struct Args {
+    /// Symbol to trade
+    #[arg(default_value = "BTCUSD")]
+    symbol: String,
+
+    /// Units to buy, then offer
+    #[arg(long, default_value_t = 1)]
+    quantity: u64,
+
+    /// Distance of the offer above the mid
+    #[arg(long, default_value_t = 0.25)]
+    offset: f64,
+
+    /// Give up on the offer after this many seconds
+    #[arg(long, default_value_t = 30)]
+    timeout_secs: u64,
+}
+
+// Flat 5 bps to take, a rebate of 1 bp to make, and 20ms from strategy to book
+struct ExampleModel;
+
+impl PaperTradingModel for ExampleModel {
+    fn latency(&self, _order: &PaperOrder) -> Duration {
+        Duration::from_millis(20)
+    }
+
+    fn fee(&self, _symbol: &str, liquidity: PaperLiquidity, price: f64, quantity: Quantity) -> f64 {
+        let bps = match liquidity {
+            PaperLiquidity::Taker => 5.0,
+            PaperLiquidity::Maker => -1.0,
+        };
+        price * quantity.to_f64() * bps / 10_000.0
+    }
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    let mut stream_manager = StreamManager::new();
+    stream_manager.enable_paper_trading(Arc::new(ExampleModel));
+    let stream_manager = Arc::new(stream_manager);
+    stream_manager.start().await;
+
+    let (account, mut reports) = stream_manager.open_paper_account().map_err(anyhow::Error::msg)?;
+    let quantity = Quantity::from_units(args.quantity);
+    let mut top = Box::pin(stream_manager.subscribe_local(&args.symbol, DataType::MBP, 1).await.map_err(anyhow::Error::msg)?);
+    let mid = match top.next().await {
+        Some(MarketDataUpdate::MBP { bids, asks }) if !bids.is_empty() && !asks.is_empty() => (bids[0].price + asks[0].price) / 2.0,
+        _ => anyhow::bail!("{} has no mid price", args.symbol),
+    };
+
+    let request = |side, limit_price| PaperOrderRequest {
+        symbol: args.symbol.clone(),
+        side,
+        quantity,
+        limit_price,
+        client_order_id: None,
+    };
+    stream_manager.submit_paper_order(account, None, request(Side::Bid, None)).map_err(anyhow::Error::msg)?;
+    let offer = stream_manager
+        .submit_paper_order(account, None, request(Side::Ask, Some(mid + args.offset)))
+        .map_err(anyhow::Error::msg)?;
+
+    let deadline = tokio::time::sleep(Duration::from_secs(args.timeout_secs));
+    tokio::pin!(deadline);
+    loop {
+        tokio::select! {
+            report = reports.recv() => match report {
+                Some(ServerMessage::PaperFill { fill }) => {
+                    println!("{:?} {} {} @ {} ({:?}, fee {:.6})", fill.side, fill.quantity, fill.symbol, fill.price, fill.liquidity, fill.fee);
+                }
+                Some(ServerMessage::PaperOrderUpdate { order }) => {
+                    println!("order {} {:?}: {} of {} filled", order.order_id, order.status, order.filled, order.quantity);
+                    if order.order_id == offer.order_id && order.status != PaperOrderStatus::Open {
+                        break;
+                    }
+                }
+                Some(_) => {}
+                None => break,
+            },
+            _ = &mut deadline => {
+                let order = stream_manager.cancel_paper_order(account, offer.order_id).map_err(anyhow::Error::msg)?;
+                println!("order {} not filled in {}s, cancelled with {} filled", order.order_id, args.timeout_secs, order.filled);
+                break;
+            }
+        }
+    }
+
+    Ok(())
+}
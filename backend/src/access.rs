@@ -0,0 +1,258 @@
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use clap::Args;
+
+use crate::audit::{AuditEvent, AuditLog};
+
+// Address-based access rules evaluated when a client connects
+#[derive(Debug, Clone, Default, Args)]
+pub struct AccessOptions {
+    /// Only accept clients from this address or CIDR range, repeatable (default: any)
+    #[arg(long = "allow-ip", value_name = "CIDR")]
+    pub allow: Vec<IpNet>,
+
+    /// Reject clients from this address or CIDR range, repeatable; takes precedence over --allow-ip
+    #[arg(long = "deny-ip", value_name = "CIDR")]
+    pub deny: Vec<IpNet>,
+}
+
+// An address range such as 10.0.0.0/8 or 2001:db8::/32; a bare address is a single host
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+
+        let addr: IpAddr = addr.parse().map_err(|_| format!("Invalid IP address: {}", addr))?;
+        let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max_prefix)
+                .ok_or_else(|| format!("Invalid prefix length: {}", prefix))?,
+            None => max_prefix,
+        };
+
+        // An IPv4-mapped range is kept as the IPv4 range it maps, since addresses are
+        // matched in their canonical form
+        match addr {
+            IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+                Some(v4) if prefix >= 96 => Ok(Self { addr: IpAddr::V4(v4), prefix: prefix - 96 }),
+                _ => Ok(Self { addr, prefix }),
+            },
+            IpAddr::V4(_) => Ok(Self { addr, prefix }),
+        }
+    }
+}
+
+impl fmt::Display for IpNet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix)
+    }
+}
+
+// Deployer-supplied check run after the allow and deny lists, e.g. a reputation or geo lookup
+pub trait ConnectionHook: Send + Sync {
+    fn allow(&self, ip: IpAddr) -> bool;
+}
+
+impl<F: Fn(IpAddr) -> bool + Send + Sync> ConnectionHook for F {
+    fn allow(&self, ip: IpAddr) -> bool {
+        self(ip)
+    }
+}
+
+#[derive(Default)]
+pub struct AccessControl {
+    allow: Vec<IpNet>,
+    deny: Vec<IpNet>,
+    hook: Option<Arc<dyn ConnectionHook>>,
+    audit: Arc<AuditLog>,
+}
+
+impl fmt::Debug for AccessControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AccessControl")
+            .field("allow", &self.allow)
+            .field("deny", &self.deny)
+            .field("hook", &self.hook.is_some())
+            .field("audit", &self.audit)
+            .finish()
+    }
+}
+
+impl AccessControl {
+    pub fn new(options: &AccessOptions) -> Self {
+        Self {
+            allow: options.allow.clone(),
+            deny: options.deny.clone(),
+            hook: None,
+            audit: Arc::new(AuditLog::disabled()),
+        }
+    }
+
+    // Records every rejected client
+    pub fn set_audit_log(&mut self, audit: Arc<AuditLog>) {
+        self.audit = audit;
+    }
+
+    pub fn set_hook(&mut self, hook: Arc<dyn ConnectionHook>) {
+        self.hook = Some(hook);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        !self.allow.is_empty() || !self.deny.is_empty() || self.hook.is_some()
+    }
+
+    pub fn is_allowed(&self, ip: IpAddr) -> bool {
+        let allowed = self.check(ip);
+        if !allowed {
+            self.audit.record(AuditEvent::AccessDenied { peer: ip });
+        }
+        allowed
+    }
+
+    fn check(&self, ip: IpAddr) -> bool {
+        if self.deny.iter().any(|net| net.contains(ip)) {
+            return false;
+        }
+
+        if !self.allow.is_empty() && !self.allow.iter().any(|net| net.contains(ip)) {
+            return false;
+        }
+
+        self.hook.as_ref().is_none_or(|hook| hook.allow(ip))
+    }
+}
+
+// Axum middleware rejecting clients that fail the access rules with 403 Forbidden
+pub async fn access_control_middleware(
+    State(access): State<Arc<AccessControl>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    match ip {
+        Some(ip) if !access.is_allowed(ip) => (StatusCode::FORBIDDEN, "Forbidden").into_response(),
+        _ => next.run(request).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn ranges_match_addresses_under_their_prefix() {
+        let private = net("10.0.0.0/8");
+        assert!(private.contains(ip("10.0.0.0")));
+        assert!(private.contains(ip("10.255.255.255")));
+        assert!(!private.contains(ip("11.0.0.0")));
+
+        let subnet = net("192.168.1.128/25");
+        assert!(subnet.contains(ip("192.168.1.200")));
+        assert!(!subnet.contains(ip("192.168.1.127")));
+
+        // Host bits past the prefix are ignored
+        assert!(net("172.16.5.4/12").contains(ip("172.31.0.1")));
+
+        let documentation = net("2001:db8::/32");
+        assert!(documentation.contains(ip("2001:db8:ffff::1")));
+        assert!(!documentation.contains(ip("2001:db9::1")));
+    }
+
+    #[test]
+    fn bare_addresses_and_zero_prefixes() {
+        let host = net("203.0.113.7");
+        assert_eq!(host.to_string(), "203.0.113.7/32");
+        assert!(host.contains(ip("203.0.113.7")));
+        assert!(!host.contains(ip("203.0.113.8")));
+        assert_eq!(net("::1").to_string(), "::1/128");
+
+        assert!(net("0.0.0.0/0").contains(ip("198.51.100.1")));
+        assert!(net("::/0").contains(ip("fe80::1")));
+        assert!(!net("0.0.0.0/0").contains(ip("fe80::1")));
+    }
+
+    #[test]
+    fn ipv4_mapped_addresses_match_ipv4_ranges() {
+        assert!(net("10.0.0.0/8").contains(ip("::ffff:10.1.2.3")));
+        assert_eq!(net("::ffff:10.1.2.3"), net("10.1.2.3"));
+        assert_eq!(net("::ffff:10.0.0.0/104"), net("10.0.0.0/8"));
+        assert!(net("::ffff:10.0.0.0/104").contains(ip("10.20.30.40")));
+        assert!(!net("::ffff:0:0/64").contains(ip("10.20.30.40")));
+    }
+
+    #[test]
+    fn malformed_ranges_are_refused() {
+        for invalid in ["", "10.0.0.0/33", "::/129", "10.0.0.0/", "10.0.0.0/-1", "10.0.0/8", "example.com"] {
+            assert!(invalid.parse::<IpNet>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn deny_takes_precedence_over_allow_and_the_hook_runs_last() {
+        let mut access = AccessControl::new(&AccessOptions {
+            allow: vec![net("10.0.0.0/8")],
+            deny: vec![net("10.0.0.66")],
+        });
+        assert!(access.is_enabled());
+        assert!(access.is_allowed(ip("10.0.0.1")));
+        assert!(!access.is_allowed(ip("10.0.0.66")));
+        assert!(!access.is_allowed(ip("192.0.2.1")));
+
+        access.set_hook(Arc::new(|ip: IpAddr| ip != IpAddr::from([10, 0, 0, 2])));
+        assert!(access.is_allowed(ip("10.0.0.1")));
+        assert!(!access.is_allowed(ip("10.0.0.2")));
+
+        let open = AccessControl::new(&AccessOptions::default());
+        assert!(!open.is_enabled());
+        assert!(open.is_allowed(ip("192.0.2.1")));
+    }
+}
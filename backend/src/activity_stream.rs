@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+
+use crate::message::{ActivityType, DataType, OrderActivity, OrderId, Quantity, Side};
+use crate::order_book::OrderBook;
+
+pub const MAX_ACTIVITY_DISTANCE_BPS: f64 = 10_000.0;
+
+// Orders tracked past the book's own before the tracker is pruned against it; it only
+// outgrows the book when cancels were missed, e.g. after falling behind the event bus
+const PRUNE_SLACK: usize = 1024;
+
+pub fn validate_activity_filter(max_distance_bps: Option<f64>, min_quantity: Option<Quantity>) -> Result<(), String> {
+    if let Some(distance) = max_distance_bps {
+        if !(distance > 0.0 && distance <= MAX_ACTIVITY_DISTANCE_BPS) {
+            return Err(format!("max_distance_bps must be above 0 and at most {}, got {}", MAX_ACTIVITY_DISTANCE_BPS, distance));
+        }
+    }
+    if min_quantity.is_some_and(|quantity| quantity.is_zero()) {
+        return Err("min_quantity must be above 0".to_string());
+    }
+    Ok(())
+}
+
+// Side, price and size of the order an event is about: the size it was added or updated
+// to, or the size it rested with when cancelled or filled
+#[derive(Debug, Clone)]
+pub struct ActivityOrder {
+    pub side: Side,
+    pub price: f64,
+    pub quantity: Quantity,
+}
+
+// Resting orders of one symbol as its order activity reveals them. Updates and cancels do
+// not carry the side or price of their order, so filtered activity streams look them up here.
+#[derive(Debug, Default)]
+pub struct ActivityOrders {
+    orders: HashMap<OrderId, ActivityOrder>,
+}
+
+impl ActivityOrders {
+    // None for orders resting before the first activity seen
+    pub fn apply(&mut self, activity: &OrderActivity) -> Option<ActivityOrder> {
+        match activity.activity_type {
+            ActivityType::Add => {
+                let order = ActivityOrder {
+                    side: activity.side.clone()?,
+                    price: activity.price?,
+                    quantity: activity.quantity?,
+                };
+                self.orders.insert(activity.order_id, order.clone());
+                Some(order)
+            }
+            ActivityType::Update => {
+                let order = self.orders.get_mut(&activity.order_id)?;
+                if let Some(quantity) = activity.quantity {
+                    order.quantity = quantity;
+                }
+                Some(order.clone())
+            }
+            ActivityType::Cancel => self.orders.remove(&activity.order_id),
+            // Fills leave the remaining size, none once the order is used up
+            ActivityType::Fill => match activity.quantity {
+                Some(left) => {
+                    let order = self.orders.get_mut(&activity.order_id)?;
+                    let resting = order.clone();
+                    order.quantity = left;
+                    Some(resting)
+                }
+                None => self.orders.remove(&activity.order_id),
+            },
+        }
+    }
+
+    // Drops orders the book no longer holds once more are tracked than it could explain
+    pub fn prune(&mut self, order_book: &OrderBook) {
+        if self.orders.len() > order_book.order_count() + PRUNE_SLACK {
+            self.orders.retain(|order_id, _| order_book.get_order(*order_id).is_some());
+        }
+    }
+}
+
+// Whether an OrderActivity stream receives the event. The distance is taken from the mid
+// after the book update the event belongs to. Events of unknown orders, and every event
+// while the book has no mid for a distance filter, only reach unfiltered streams.
+pub fn activity_matches(data_type: &DataType, order: Option<&ActivityOrder>, mid_price: Option<f64>) -> bool {
+    let DataType::OrderActivity { side, max_distance_bps, min_quantity } = data_type else {
+        return false;
+    };
+    if side.is_none() && max_distance_bps.is_none() && min_quantity.is_none() {
+        return true;
+    }
+    let Some(order) = order else {
+        return false;
+    };
+
+    if side.as_ref().is_some_and(|side| !matches!((side, &order.side), (Side::Bid, Side::Bid) | (Side::Ask, Side::Ask))) {
+        return false;
+    }
+    if min_quantity.is_some_and(|min_quantity| order.quantity < min_quantity) {
+        return false;
+    }
+    match (max_distance_bps, mid_price) {
+        (Some(max_distance_bps), Some(mid_price)) => (order.price - mid_price).abs() / mid_price * 10_000.0 <= *max_distance_bps,
+        (Some(_), None) => false,
+        (None, _) => true,
+    }
+}
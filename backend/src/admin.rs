@@ -0,0 +1,383 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+use axum::{
+    extract::{Path, Query},
+    http::{header, HeaderMap, StatusCode},
+    middleware,
+    response::{IntoResponse, Response},
+    routing::{delete, get, post},
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tracing::info;
+use uuid::Uuid;
+
+use crate::access::{access_control_middleware, AccessControl};
+use crate::audit::{audit_middleware, AuditLog};
+use crate::auth::{signature_middleware, AuthenticatedKey, Authenticator};
+use crate::candle_store::{parse_period, CandleGapFill};
+use crate::dead_letters::DeadLetterBatch;
+use crate::ingest::ingest_router;
+use crate::message::{BulkSnapshot, Candle, DisconnectReason, FundingRate, FutureContract, MarkPrice, OptionQuote, OrderLifetimeStats, StreamSpec, StreamValidation, SymbolInfo};
+use crate::popularity::SymbolPopularityStats;
+use crate::rate_limit::{key_rate_limit_middleware, rate_limit_middleware, RateLimiter};
+use crate::reconcile::{ReconcileReport, ReconcileRequest};
+use crate::sandbox::sandbox_router;
+use crate::stream_manager::DepthPoll;
+use crate::symbols::SymbolQuery;
+use crate::tenants::{TenantRegistry, TenantStreams};
+#[cfg(unix)]
+use crate::unix_socket::serve_unix;
+#[cfg(not(unix))]
+use crate::unix_socket::unsupported_unix_socket;
+use crate::unix_socket::UnixSocketOptions;
+use crate::usage::UsageSnapshot;
+
+// HTTP admin API and Prometheus metrics served next to the WebSocket listener. Each
+// request is served from the universe of the tenant that signed it.
+pub fn admin_router(tenants: Arc<TenantRegistry>) -> Router {
+    Router::new()
+        .route("/admin/usage", get(usage_handler))
+        .route("/admin/popularity", get(popularity_handler))
+        .route("/admin/clients/:client_id", delete(disconnect_handler))
+        .route("/admin/dead-letters", get(dead_letters_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/health", get(health_handler))
+        .route("/symbols", get(symbols_handler))
+        .route("/reference", get(reference_handler))
+        .route("/mark-price/:symbol", get(mark_price_handler))
+        .route("/funding-rate/:symbol", get(funding_rate_handler))
+        .route("/depth", get(depth_handler))
+        .route("/depth/:symbol", get(symbol_depth_handler))
+        .route("/candles/:symbol", get(candles_handler))
+        .route("/option-chain/:underlying", get(option_chain_handler))
+        .route("/order-stats/:symbol", get(order_stats_handler))
+        .route("/futures/:root", get(futures_handler))
+        .route("/reconcile/:symbol", post(reconcile_handler))
+        .route("/validate", post(validate_handler))
+        .with_state(tenants)
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn serve_admin(
+    addr: &str,
+    unix_socket: UnixSocketOptions,
+    tenants: Arc<TenantRegistry>,
+    rate_limiter: Arc<RateLimiter>,
+    access_control: Arc<AccessControl>,
+    authenticator: Arc<Authenticator>,
+    audit: Arc<AuditLog>,
+    ingest: bool,
+) -> anyhow::Result<()> {
+    let listener = tokio::net::TcpListener::bind(addr).await?;
+    info!("Admin API listening on: {}", addr);
+
+    // Order events for external books share the admin API's signing and access rules
+    let mut app = admin_router(Arc::clone(&tenants)).merge(sandbox_router(Arc::clone(&tenants)));
+    if ingest {
+        info!("Accepting order events on http://{}/ingest/SYMBOL", addr);
+        app = app.merge(ingest_router(tenants));
+    }
+
+    let app = app
+        .layer(middleware::from_fn_with_state(audit, audit_middleware))
+        .layer(middleware::from_fn_with_state(Arc::clone(&rate_limiter), key_rate_limit_middleware))
+        .layer(middleware::from_fn_with_state(authenticator, signature_middleware))
+        .layer(middleware::from_fn_with_state(rate_limiter, rate_limit_middleware))
+        .layer(middleware::from_fn_with_state(access_control, access_control_middleware));
+
+    if let Some(path) = &unix_socket.unix_socket {
+        #[cfg(unix)]
+        {
+            let unix_listener = unix_socket.bind(path)?;
+            info!("Admin API listening on Unix socket: {}", path);
+            tokio::select! {
+                result = axum::serve(listener, app.clone().into_make_service_with_connect_info::<SocketAddr>()) => result?,
+                result = serve_unix(unix_listener, app) => result?,
+            }
+            return Ok(());
+        }
+        #[cfg(not(unix))]
+        return Err(unsupported_unix_socket().into());
+    }
+
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
+
+    Ok(())
+}
+
+async fn usage_handler(TenantStreams(stream_manager): TenantStreams) -> Json<UsageSnapshot> {
+    Json(stream_manager.get_usage_snapshot())
+}
+
+#[derive(Debug, Deserialize)]
+struct PopularityQuery {
+    limit: Option<usize>,
+}
+
+// Most subscribed symbols first, with their subscribers and deliveries over the last hour
+async fn popularity_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Query(query): Query<PopularityQuery>,
+) -> Json<Vec<SymbolPopularityStats>> {
+    Json(stream_manager.get_popularity(query.limit.unwrap_or(50)))
+}
+
+// Closes a client's connection with close code 4003 (kicked)
+async fn disconnect_handler(TenantStreams(stream_manager): TenantStreams, Path(client_id): Path<String>) -> StatusCode {
+    let Ok(client_id) = client_id.parse::<Uuid>() else {
+        return StatusCode::NOT_FOUND;
+    };
+    if stream_manager.disconnect_client(&client_id, DisconnectReason::Kicked) {
+        StatusCode::NO_CONTENT
+    } else {
+        StatusCode::NOT_FOUND
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct DeadLetterQuery {
+    client_id: Option<String>,
+    limit: Option<usize>,
+}
+
+// Undelivered messages of failed and server-closed connections, newest first; 404 unless
+// --dead-letter-capacity is set
+async fn dead_letters_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Query(query): Query<DeadLetterQuery>,
+) -> Result<Json<Vec<DeadLetterBatch>>, StatusCode> {
+    stream_manager
+        .get_dead_letters(query.client_id.as_deref(), query.limit.unwrap_or(50))
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn metrics_handler(TenantStreams(stream_manager): TenantStreams) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        stream_manager.render_metrics(),
+    )
+}
+
+// 200 while every background task runs, 503 while one is down waiting to be restarted
+async fn health_handler(TenantStreams(stream_manager): TenantStreams) -> impl IntoResponse {
+    let tasks = stream_manager.task_health();
+    let status = if tasks.iter().all(|task| task.up) { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(tasks))
+}
+
+async fn symbols_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Query(query): Query<SymbolQuery>,
+) -> Json<Vec<SymbolInfo>> {
+    Json(stream_manager.search_symbols(&query))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReferenceQuery {
+    since: Option<u64>,
+}
+
+// Every listed symbol with the registry's version, or with `since=VERSION` only the
+// changes after it; 410 Gone once those are no longer kept, for the client to start over
+async fn reference_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Query(query): Query<ReferenceQuery>,
+) -> Result<Response, StatusCode> {
+    match query.since {
+        Some(version) => stream_manager
+            .reference_changes(version)
+            .map(|changes| Json(changes).into_response())
+            .ok_or(StatusCode::GONE),
+        None => Ok(Json(stream_manager.reference_snapshot()).into_response()),
+    }
+}
+
+async fn mark_price_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Path(symbol): Path<String>,
+) -> Result<Json<MarkPrice>, StatusCode> {
+    stream_manager.get_mark_price(&symbol).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Debug, Deserialize)]
+struct FundingQuery {
+    limit: Option<usize>,
+}
+
+// Most recent settlements, oldest first
+async fn funding_rate_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Path(symbol): Path<String>,
+    Query(query): Query<FundingQuery>,
+) -> Result<Json<Vec<FundingRate>>, StatusCode> {
+    stream_manager
+        .get_funding_history(&symbol, query.limit.unwrap_or(100))
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+#[derive(Debug, Deserialize)]
+struct DepthQuery {
+    symbols: String,
+    levels: Option<u32>,
+}
+
+// MBP books of several comma-separated symbols at once, e.g. `symbols=BTCUSD,ETHUSD&levels=10`
+async fn depth_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+    Query(query): Query<DepthQuery>,
+) -> Result<Json<BulkSnapshot>, StatusCode> {
+    let api_key = key.as_ref().map(|Extension(AuthenticatedKey(api_key))| api_key.as_str());
+    let symbols: Vec<String> = query
+        .symbols
+        .split(',')
+        .map(str::trim)
+        .filter(|symbol| !symbol.is_empty())
+        .map(str::to_string)
+        .collect();
+    stream_manager
+        .bulk_snapshot(api_key, &symbols, query.levels.unwrap_or(20))
+        .map(Json)
+        .map_err(|_| StatusCode::BAD_REQUEST)
+}
+
+#[derive(Debug, Deserialize)]
+struct SymbolDepthQuery {
+    levels: Option<u32>,
+}
+
+// MBP book of one symbol for pollers, tagged with its version. A request whose
+// If-None-Match names that version or a later one is answered 304 Not Modified without
+// the levels, so polling an unchanged book is cheap.
+async fn symbol_depth_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+    Path(symbol): Path<String>,
+    Query(query): Query<SymbolDepthQuery>,
+    headers: HeaderMap,
+) -> Result<Response, StatusCode> {
+    let api_key = key.as_ref().map(|Extension(AuthenticatedKey(api_key))| api_key.as_str());
+    let if_none_match: Vec<&str> =
+        headers.get_all(header::IF_NONE_MATCH).iter().filter_map(|value| value.to_str().ok()).collect();
+    let levels = query.levels.unwrap_or(20);
+    let poll = stream_manager
+        .poll_depth(api_key, &symbol, levels, |epoch, sequence| {
+            if_none_match.iter().any(|tags| not_modified(tags, epoch, sequence, levels))
+        })
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(match poll {
+        DepthPoll::Unchanged { epoch, sequence } => {
+            let etag = book_etag(epoch, sequence, levels);
+            (StatusCode::NOT_MODIFIED, [(header::ETAG, etag), (header::CACHE_CONTROL, "no-cache".to_string())]).into_response()
+        }
+        DepthPoll::Changed(snapshot) => {
+            let etag = book_etag(snapshot.epoch, snapshot.sequence, levels);
+            ([(header::ETAG, etag), (header::CACHE_CONTROL, "no-cache".to_string())], Json(snapshot)).into_response()
+        }
+    })
+}
+
+// Tags the representation as well as the book, so a poller changing `levels` gets the new view
+fn book_etag(epoch: u64, sequence: u64, levels: u32) -> String {
+    format!("\"{}-{}-{}\"", epoch, sequence, levels)
+}
+
+// Whether a comma-separated If-None-Match names the book at `sequence` of `epoch` or
+// later at `levels`: an ETag this server sent, or a bare sequence the poller has seen,
+// taken to be of the current epoch and levels. `*` matches any book.
+fn not_modified(tags: &str, epoch: u64, sequence: u64, levels: u32) -> bool {
+    tags.split(',').map(str::trim).any(|tag| {
+        if tag == "*" {
+            return true;
+        }
+        let tag = tag.trim_start_matches("W/").trim_matches('"');
+        let parts: Vec<&str> = tag.split('-').collect();
+        let (tag_epoch, tag_sequence, tag_levels) = match parts[..] {
+            [tag_sequence] => (Some(epoch), tag_sequence, Some(levels)),
+            [tag_epoch, tag_sequence, tag_levels] => (tag_epoch.parse::<u64>().ok(), tag_sequence, tag_levels.parse::<u32>().ok()),
+            _ => return false,
+        };
+        tag_epoch == Some(epoch) && tag_levels == Some(levels) && tag_sequence.parse::<u64>().is_ok_and(|seen| seen >= sequence)
+    })
+}
+
+#[derive(Debug, Deserialize)]
+struct CandleQuery {
+    interval: Option<String>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    limit: Option<usize>,
+    fill: Option<CandleGapFill>,
+}
+
+// Stored candles opening between `from` and `to`, oldest first; the finest kept interval
+// unless one is given, e.g. `interval=1m`
+async fn candles_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Path(symbol): Path<String>,
+    Query(query): Query<CandleQuery>,
+) -> Result<Json<Vec<Candle>>, StatusCode> {
+    let interval_secs = match &query.interval {
+        Some(interval) => Some(parse_period(interval).ok_or(StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+    stream_manager
+        .get_candles(&symbol, interval_secs, query.from, query.to, query.limit.unwrap_or(500), query.fill)
+        .map(Json)
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+// Every contract's latest quote, by expiry then strike
+async fn option_chain_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Path(underlying): Path<String>,
+) -> Result<Json<Vec<OptionQuote>>, StatusCode> {
+    stream_manager.get_option_chain(&underlying).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn order_stats_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+    Path(symbol): Path<String>,
+) -> Result<Json<OrderLifetimeStats>, StatusCode> {
+    let api_key = key.as_ref().map(|Extension(AuthenticatedKey(api_key))| api_key.as_str());
+    stream_manager.get_order_stats(api_key, &symbol).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+// Contracts of a futures root by expiry, delisted ones included
+async fn futures_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Path(root): Path<String>,
+) -> Result<Json<Vec<FutureContract>>, StatusCode> {
+    stream_manager.get_futures_curve(&root).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
+
+// Compares a client's reconstructed top of book with the live one, level by level
+#[derive(Debug, Deserialize)]
+struct ValidateRequest {
+    streams: Vec<StreamSpec>,
+}
+
+// Dry run of subscriptions, with the entitlements of the key that signed the request
+async fn validate_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+    Json(request): Json<ValidateRequest>,
+) -> Json<Vec<StreamValidation>> {
+    let api_key = key.as_ref().map(|Extension(AuthenticatedKey(api_key))| api_key.as_str());
+    Json(stream_manager.validate_streams(api_key, request.streams))
+}
+
+async fn reconcile_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Path(symbol): Path<String>,
+    Json(request): Json<ReconcileRequest>,
+) -> Result<Json<ReconcileReport>, StatusCode> {
+    stream_manager.reconcile(&symbol, &request).map(Json).ok_or(StatusCode::NOT_FOUND)
+}
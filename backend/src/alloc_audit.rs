@@ -0,0 +1,56 @@
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+thread_local! {
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+// The system allocator, counting the allocations made on each thread. The server binary
+// installs it as the global allocator when built with the alloc-audit feature.
+pub struct CountingAllocator;
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc_zeroed(layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+// The counter is gone while a thread is being torn down
+fn count_allocation() {
+    let _ = ALLOCATIONS.try_with(|allocations| allocations.set(allocations.get() + 1));
+}
+
+fn thread_allocations() -> u64 {
+    ALLOCATIONS.try_with(Cell::get).unwrap_or(0)
+}
+
+// Allocations made on this thread since the scope started, reallocations included. The
+// code measured must not await, since a task may resume on another worker thread.
+pub struct AllocationScope {
+    started_at: u64,
+}
+
+impl AllocationScope {
+    pub fn start() -> Self {
+        Self { started_at: thread_allocations() }
+    }
+
+    pub fn allocations(&self) -> u64 {
+        thread_allocations() - self.started_at
+    }
+}
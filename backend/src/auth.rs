@@ -0,0 +1,413 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use axum::{
+    body::{self, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, StatusCode, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use clap::Args;
+use dashmap::DashMap;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tokio::time::interval;
+
+use crate::audit::{AuditEvent, AuditLog};
+use crate::rate_limit::api_key;
+
+pub const SIGNATURE_HEADER: &str = "x-signature";
+
+// Largest request body buffered for signature verification
+const MAX_SIGNED_BODY: usize = 1024 * 1024;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Signed requests: the client sends its API key, a `timestamp` query parameter in
+// milliseconds and `signature`, the hex HMAC-SHA256 of the query string (without the
+// signature) followed by the body, keyed with the API secret
+#[derive(Debug, Clone, Args)]
+pub struct AuthOptions {
+    /// API credential allowed to sign requests, as KEY:SECRET, repeatable
+    #[arg(long = "api-credential", value_name = "KEY:SECRET")]
+    pub credentials: Vec<ApiCredential>,
+
+    /// Reject REST and SSE requests that are not signed
+    #[arg(long)]
+    pub require_signature: bool,
+
+    /// How far a signed request's timestamp may be from server time, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 5000)]
+    pub signature_window_ms: u64,
+}
+
+#[derive(Clone)]
+pub struct ApiCredential {
+    pub key: String,
+    secret: String,
+}
+
+impl fmt::Debug for ApiCredential {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ApiCredential").field("key", &self.key).finish_non_exhaustive()
+    }
+}
+
+impl FromStr for ApiCredential {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some((key, secret)) if !key.is_empty() && !secret.is_empty() => Ok(Self {
+                key: key.to_string(),
+                secret: secret.to_string(),
+            }),
+            _ => Err("Expected KEY:SECRET".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthError {
+    MissingSignature,
+    UnknownApiKey,
+    InvalidTimestamp,
+    TimestampOutsideWindow,
+    InvalidSignature,
+    Replayed,
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            AuthError::MissingSignature => "Request must be signed",
+            AuthError::UnknownApiKey => "Unknown API key",
+            AuthError::InvalidTimestamp => "Missing or invalid timestamp",
+            AuthError::TimestampOutsideWindow => "Timestamp outside the signature window",
+            AuthError::InvalidSignature => "Invalid signature",
+            AuthError::Replayed => "Signature already used",
+        };
+        f.write_str(message)
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl IntoResponse for AuthError {
+    fn into_response(self) -> Response {
+        (StatusCode::UNAUTHORIZED, self.to_string()).into_response()
+    }
+}
+
+// API key whose signature was verified, stored in the request extensions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuthenticatedKey(pub String);
+
+#[derive(Debug)]
+pub struct Authenticator {
+    secrets: HashMap<String, String>,
+    require_signature: bool,
+    window_ms: i64,
+    // Signatures accepted inside the window, with their timestamps, to reject replays
+    seen: DashMap<String, i64>,
+    audit: Arc<AuditLog>,
+}
+
+impl Authenticator {
+    pub fn new(options: &AuthOptions) -> Self {
+        Self {
+            secrets: options
+                .credentials
+                .iter()
+                .map(|credential| (credential.key.clone(), credential.secret.clone()))
+                .collect(),
+            require_signature: options.require_signature,
+            window_ms: options.signature_window_ms.max(1) as i64,
+            seen: DashMap::new(),
+            audit: Arc::new(AuditLog::disabled()),
+        }
+    }
+
+    // Records every verified and rejected signature
+    pub fn set_audit_log(&mut self, audit: Arc<AuditLog>) {
+        self.audit = audit;
+    }
+
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.require_signature || !self.secrets.is_empty()
+    }
+
+    pub fn requires_signature(&self) -> bool {
+        self.require_signature
+    }
+
+    // Verifies an HTTP request, taking the API key, timestamp and signature from its headers and query
+    pub fn verify_request(
+        &self,
+        headers: &HeaderMap,
+        uri: &Uri,
+        body: &[u8],
+        peer: Option<IpAddr>,
+    ) -> Result<Option<String>, AuthError> {
+        self.verify(api_key(headers, uri), uri.query(), body, signature_header(headers), peer)
+    }
+
+    // Verifies a signed request, returning the API key it was signed with, or None for
+    // an unsigned request when signatures are optional
+    pub fn verify(
+        &self,
+        api_key: Option<&str>,
+        query: Option<&str>,
+        body: &[u8],
+        signature_header: Option<&str>,
+        peer: Option<IpAddr>,
+    ) -> Result<Option<String>, AuthError> {
+        let verified = self.check_signature(api_key, query, body, signature_header);
+
+        match &verified {
+            Ok(Some(api_key)) => self.audit.record(AuditEvent::AuthSucceeded {
+                api_key: api_key.clone(),
+                peer,
+            }),
+            Ok(None) => {}
+            Err(e) => self.audit.record(AuditEvent::AuthFailed {
+                api_key: api_key.map(str::to_string),
+                peer,
+                reason: e.to_string(),
+            }),
+        }
+
+        verified
+    }
+
+    fn check_signature(
+        &self,
+        api_key: Option<&str>,
+        query: Option<&str>,
+        body: &[u8],
+        signature_header: Option<&str>,
+    ) -> Result<Option<String>, AuthError> {
+        let query = query.unwrap_or("");
+        let signature = signature_header.or_else(|| query_param(query, "signature"));
+
+        let Some(signature) = signature else {
+            return if self.require_signature { Err(AuthError::MissingSignature) } else { Ok(None) };
+        };
+
+        let api_key = api_key.ok_or(AuthError::UnknownApiKey)?;
+        let secret = self.secrets.get(api_key).ok_or(AuthError::UnknownApiKey)?;
+
+        let timestamp: i64 = query_param(query, "timestamp")
+            .and_then(|timestamp| timestamp.parse().ok())
+            .ok_or(AuthError::InvalidTimestamp)?;
+        let now = chrono::Utc::now().timestamp_millis();
+        if (now - timestamp).abs() > self.window_ms {
+            return Err(AuthError::TimestampOutsideWindow);
+        }
+
+        let expected = hex::decode(signature).map_err(|_| AuthError::InvalidSignature)?;
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).map_err(|_| AuthError::InvalidSignature)?;
+        mac.update(signed_query(query).as_bytes());
+        mac.update(body);
+        mac.verify_slice(&expected).map_err(|_| AuthError::InvalidSignature)?;
+
+        // Only verified signatures are recorded, so forgeries cannot evict entries
+        if self.seen.insert(signature.to_ascii_lowercase(), timestamp).is_some() {
+            return Err(AuthError::Replayed);
+        }
+
+        Ok(Some(api_key.to_string()))
+    }
+
+    // Periodically forgets signatures whose timestamps have left the window
+    pub fn start_pruning(self: &Arc<Self>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        let authenticator = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_millis(authenticator.window_ms.max(1000) as u64));
+
+            loop {
+                interval.tick().await;
+                let cutoff = chrono::Utc::now().timestamp_millis() - authenticator.window_ms;
+                authenticator.seen.retain(|_, timestamp| *timestamp >= cutoff);
+            }
+        });
+    }
+}
+
+// Axum middleware rejecting requests with a missing or invalid signature with 401 Unauthorized
+pub async fn signature_middleware(
+    State(authenticator): State<Arc<Authenticator>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (mut parts, body) = request.into_parts();
+    let body = match body::to_bytes(body, MAX_SIGNED_BODY).await {
+        Ok(body) => body,
+        Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+    };
+
+    let peer = parts
+        .extensions
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+
+    match authenticator.verify_request(&parts.headers, &parts.uri, &body, peer) {
+        Ok(api_key) => {
+            if let Some(api_key) = api_key {
+                parts.extensions.insert(AuthenticatedKey(api_key));
+            }
+            next.run(Request::from_parts(parts, Body::from(body))).await
+        }
+        Err(error) => error.into_response(),
+    }
+}
+
+fn signature_header(headers: &HeaderMap) -> Option<&str> {
+    headers.get(SIGNATURE_HEADER).and_then(|value| value.to_str().ok())
+}
+
+pub fn query_param<'a>(query: &'a str, name: &str) -> Option<&'a str> {
+    query.split('&').find_map(|pair| {
+        let (key, value) = pair.split_once('=')?;
+        (key == name).then_some(value)
+    })
+}
+
+// The query string as signed by the client: every parameter except the signature
+fn signed_query(query: &str) -> String {
+    query
+        .split('&')
+        .filter(|pair| !pair.starts_with("signature="))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "client";
+    const SECRET: &str = "s3cret";
+
+    fn authenticator(require_signature: bool) -> Authenticator {
+        Authenticator::new(&AuthOptions {
+            credentials: vec![format!("{}:{}", KEY, SECRET).parse().unwrap()],
+            require_signature,
+            signature_window_ms: 5000,
+        })
+    }
+
+    fn sign(secret: &str, query: &str, body: &[u8]) -> String {
+        let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).unwrap();
+        mac.update(query.as_bytes());
+        mac.update(body);
+        hex::encode(mac.finalize().into_bytes())
+    }
+
+    fn now_ms() -> i64 {
+        chrono::Utc::now().timestamp_millis()
+    }
+
+    #[test]
+    fn credentials_need_a_key_and_a_secret() {
+        let credential: ApiCredential = "key:secret:with:colons".parse().unwrap();
+        assert_eq!((credential.key.as_str(), credential.secret.as_str()), ("key", "secret:with:colons"));
+        for invalid in ["", "key", "key:", ":secret"] {
+            assert!(invalid.parse::<ApiCredential>().is_err(), "{}", invalid);
+        }
+        assert!(!format!("{:?}", credential).contains("secret:with"));
+    }
+
+    #[test]
+    fn signatures_cover_the_query_and_the_body() {
+        let authenticator = authenticator(true);
+        let query = format!("symbol=BTCUSD&timestamp={}", now_ms());
+        let body = br#"{"price":65000}"#;
+        let signature = sign(SECRET, &query, body);
+
+        let signed = format!("{}&signature={}", query, signature);
+        assert_eq!(authenticator.check_signature(Some(KEY), Some(&signed), body, None), Ok(Some(KEY.to_string())));
+
+        let query = format!("timestamp={}", now_ms());
+        let tampered = format!("{}&signature={}", query, sign(SECRET, &query, b"{}"));
+        assert_eq!(authenticator.check_signature(Some(KEY), Some(&tampered), b"{ }", None), Err(AuthError::InvalidSignature));
+
+        let query = format!("timestamp={}", now_ms());
+        let forged = sign("guess", &query, b"");
+        assert_eq!(authenticator.check_signature(Some(KEY), Some(&query), b"", Some(&forged)), Err(AuthError::InvalidSignature));
+        assert_eq!(authenticator.check_signature(Some(KEY), Some(&query), b"", Some("not-hex")), Err(AuthError::InvalidSignature));
+    }
+
+    #[test]
+    fn the_signature_header_stands_in_for_the_query_parameter() {
+        let authenticator = authenticator(true);
+        let query = format!("timestamp={}", now_ms());
+        let signature = sign(SECRET, &query, b"");
+        assert_eq!(authenticator.check_signature(Some(KEY), Some(&query), b"", Some(&signature)), Ok(Some(KEY.to_string())));
+    }
+
+    #[test]
+    fn signatures_are_accepted_once() {
+        let authenticator = authenticator(false);
+        let query = format!("timestamp={}", now_ms());
+        let signature = sign(SECRET, &query, b"");
+
+        assert!(authenticator.check_signature(Some(KEY), Some(&query), b"", Some(&signature)).is_ok());
+        assert_eq!(authenticator.check_signature(Some(KEY), Some(&query), b"", Some(&signature)), Err(AuthError::Replayed));
+        let uppercase = signature.to_ascii_uppercase();
+        assert_eq!(authenticator.check_signature(Some(KEY), Some(&query), b"", Some(&uppercase)), Err(AuthError::Replayed));
+    }
+
+    #[test]
+    fn timestamps_must_fall_inside_the_window() {
+        let authenticator = authenticator(true);
+        for offset in [-6000, 6000] {
+            let query = format!("timestamp={}", now_ms() + offset);
+            let signature = sign(SECRET, &query, b"");
+            assert_eq!(
+                authenticator.check_signature(Some(KEY), Some(&query), b"", Some(&signature)),
+                Err(AuthError::TimestampOutsideWindow),
+            );
+        }
+
+        let query = format!("timestamp={}", now_ms() - 4000);
+        let signature = sign(SECRET, &query, b"");
+        assert!(authenticator.check_signature(Some(KEY), Some(&query), b"", Some(&signature)).is_ok());
+
+        for query in ["symbol=BTCUSD", "timestamp=soon"] {
+            let signature = sign(SECRET, query, b"");
+            assert_eq!(authenticator.check_signature(Some(KEY), Some(query), b"", Some(&signature)), Err(AuthError::InvalidTimestamp));
+        }
+    }
+
+    #[test]
+    fn unsigned_requests_pass_only_when_signatures_are_optional() {
+        assert_eq!(authenticator(false).check_signature(Some(KEY), Some("symbol=BTCUSD"), b"", None), Ok(None));
+        assert_eq!(authenticator(true).check_signature(Some(KEY), None, b"", None), Err(AuthError::MissingSignature));
+
+        let query = format!("timestamp={}", now_ms());
+        let signature = sign(SECRET, &query, b"");
+        assert_eq!(authenticator(true).check_signature(None, Some(&query), b"", Some(&signature)), Err(AuthError::UnknownApiKey));
+        assert_eq!(authenticator(true).check_signature(Some("other"), Some(&query), b"", Some(&signature)), Err(AuthError::UnknownApiKey));
+    }
+
+    #[test]
+    fn signed_queries_leave_out_only_the_signature() {
+        assert_eq!(signed_query("a=1&signature=ff&timestamp=2"), "a=1&timestamp=2");
+        assert_eq!(signed_query("signature=ff"), "");
+        assert_eq!(query_param("a=1&timestamp=2", "timestamp"), Some("2"));
+        assert_eq!(query_param("a=1&timestamps=2", "timestamp"), None);
+    }
+}
@@ -0,0 +1,383 @@
+use serde::{Deserialize, Serialize};
+
+use crate::message::{delta_checksum, MBPLevel, MarketDataUpdate, Quantity};
+
+const CODEC_VERSION: u8 = 1;
+const FLAG_RLE: u8 = 1;
+// Prices are encoded as whole ticks of 10^-decimals, exact up to 2^53 ticks
+const MAX_PRICE_DECIMALS: u32 = 12;
+const MAX_TICKS: f64 = 9_007_199_254_740_992.0;
+// Guards decoding against runs of removals no book has
+const MAX_DECODED_LEVELS: u64 = 1_000_000;
+
+// A level of the later view that is new or changed, or one it no longer has, sent with
+// zero quantity and orders and flagged as deleted
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct LevelChange {
+    pub price: f64,
+    pub quantity: Quantity,
+    pub order_count: u32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    #[cfg_attr(feature = "ts", ts(as = "Option<bool>", optional))]
+    pub deleted: bool,
+}
+
+impl LevelChange {
+    pub fn removed(price: f64) -> Self {
+        Self { price, quantity: Quantity::ZERO, order_count: 0, deleted: true }
+    }
+
+    pub fn is_removal(&self) -> bool {
+        self.quantity.is_zero()
+    }
+}
+
+impl From<&MBPLevel> for LevelChange {
+    fn from(level: &MBPLevel) -> Self {
+        Self { price: level.price, quantity: level.quantity, order_count: level.order_count, deleted: false }
+    }
+}
+
+// Fewest level changes that turn one MBP view of a book into another, best price first
+// on each side, in the zero-quantity-removes form of the client's BookDelta. Levels are
+// compared by quantity and order count; cumulative totals and ages follow from those or
+// from time and are left out.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct BookDiff {
+    pub bids: Vec<LevelChange>,
+    pub asks: Vec<LevelChange>,
+}
+
+impl BookDiff {
+    // Both views best first, as MBP updates and snapshots carry them. Levels that left a
+    // view's depth count as removed.
+    pub fn between(before_bids: &[MBPLevel], before_asks: &[MBPLevel], after_bids: &[MBPLevel], after_asks: &[MBPLevel]) -> Self {
+        Self {
+            bids: diff_side(before_bids, after_bids, |a, b| a > b),
+            asks: diff_side(before_asks, after_asks, |a, b| a < b),
+        }
+    }
+
+    // None unless both updates are MBP views
+    pub fn between_updates(before: &MarketDataUpdate, after: &MarketDataUpdate) -> Option<Self> {
+        match (before, after) {
+            (MarketDataUpdate::MBP { bids: before_bids, asks: before_asks }, MarketDataUpdate::MBP { bids, asks }) => {
+                Some(Self::between(before_bids, before_asks, bids, asks))
+            }
+            _ => None,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bids.is_empty() && self.asks.is_empty()
+    }
+
+    // Continues a stream's rolling delta checksum with this diff sent as a delta
+    pub fn checksum(&self, previous: u32) -> u32 {
+        delta_checksum(
+            previous,
+            self.bids.iter().map(|level| (level.price, level.quantity)),
+            self.asks.iter().map(|level| (level.price, level.quantity)),
+        )
+    }
+
+    // Binary form: a version byte, a flags byte and the price decimals, then for bids and
+    // asks the number of changes and each change as its price in ticks of
+    // 10^-price_decimals, zigzag varint relative to the change before, its quantity in
+    // units of 10^-8 as a varint, and for levels that are not removed their order count.
+    // With `rle` a removal is followed by the number of removals after it at each next
+    // tick away from the best price, which are left out. Fails for prices off that grid.
+    pub fn encode(&self, price_decimals: u32, rle: bool) -> Result<Vec<u8>, String> {
+        if price_decimals > MAX_PRICE_DECIMALS {
+            return Err(format!("At most {} price decimals", MAX_PRICE_DECIMALS));
+        }
+        let scale = 10f64.powi(price_decimals as i32);
+        let mut out = vec![CODEC_VERSION, if rle { FLAG_RLE } else { 0 }];
+        write_varint(&mut out, price_decimals as u64);
+
+        for (changes, away) in [(&self.bids, -1), (&self.asks, 1)] {
+            let ticks = changes.iter().map(|change| to_ticks(change.price, scale)).collect::<Result<Vec<i64>, String>>()?;
+            let mut entries = Vec::new();
+            let mut index = 0;
+            while index < changes.len() {
+                let mut run = 0;
+                if rle && changes[index].is_removal() {
+                    while index + run + 1 < changes.len()
+                        && changes[index + run + 1].is_removal()
+                        && ticks[index + run + 1] == ticks[index + run] + away
+                    {
+                        run += 1;
+                    }
+                }
+                entries.push((index, run));
+                index += run + 1;
+            }
+
+            write_varint(&mut out, entries.len() as u64);
+            let mut previous = 0;
+            for (index, run) in entries {
+                let change = &changes[index];
+                write_varint(&mut out, zigzag(ticks[index] - previous));
+                write_varint(&mut out, change.quantity.lots(Quantity::DECIMALS));
+                if change.is_removal() {
+                    if rle {
+                        write_varint(&mut out, run as u64);
+                    }
+                } else {
+                    write_varint(&mut out, change.order_count as u64);
+                }
+                previous = ticks[index] + away * run as i64;
+            }
+        }
+
+        Ok(out)
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { bytes, position: 0 };
+        let version = reader.byte()?;
+        if version != CODEC_VERSION {
+            return Err(format!("Unknown book diff version {}", version));
+        }
+        let rle = reader.byte()? & FLAG_RLE != 0;
+        let price_decimals = reader.varint()?;
+        if price_decimals > MAX_PRICE_DECIMALS as u64 {
+            return Err(format!("At most {} price decimals", MAX_PRICE_DECIMALS));
+        }
+        let scale = 10f64.powi(price_decimals as i32);
+
+        let mut sides = [Vec::new(), Vec::new()];
+        for (changes, away) in sides.iter_mut().zip([-1i64, 1]) {
+            let entries = reader.varint()?;
+            let mut previous: i64 = 0;
+            for _ in 0..entries {
+                let ticks = previous.checked_add(unzigzag(reader.varint()?)).ok_or("Price out of range")?;
+                let quantity = Quantity::from_lots(reader.varint()?, Quantity::DECIMALS);
+                if !quantity.is_zero() {
+                    let order_count = u32::try_from(reader.varint()?).map_err(|_| "Order count out of range")?;
+                    changes.push(LevelChange { price: ticks as f64 / scale, quantity, order_count, deleted: false });
+                    previous = ticks;
+                    continue;
+                }
+
+                let run = if rle { reader.varint()? } else { 0 };
+                if changes.len() as u64 + run >= MAX_DECODED_LEVELS {
+                    return Err(format!("More than {} levels on a side", MAX_DECODED_LEVELS));
+                }
+                let mut level = ticks;
+                changes.push(LevelChange::removed(level as f64 / scale));
+                for _ in 0..run {
+                    level += away;
+                    changes.push(LevelChange::removed(level as f64 / scale));
+                }
+                previous = level;
+            }
+        }
+
+        if reader.remaining() > 0 {
+            return Err(format!("{} trailing bytes", reader.remaining()));
+        }
+        let [bids, asks] = sides;
+        Ok(Self { bids, asks })
+    }
+}
+
+// Merges both sides best first; `better` orders two prices of the side
+fn diff_side(before: &[MBPLevel], after: &[MBPLevel], better: fn(f64, f64) -> bool) -> Vec<LevelChange> {
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    loop {
+        let removed = match (before.get(i), after.get(j)) {
+            (None, None) => break,
+            (Some(old), Some(new)) if old.price == new.price => {
+                if old.quantity != new.quantity || old.order_count != new.order_count {
+                    changes.push(LevelChange::from(new));
+                }
+                i += 1;
+                j += 1;
+                continue;
+            }
+            (Some(old), Some(new)) => better(old.price, new.price),
+            (Some(_), None) => true,
+            (None, Some(_)) => false,
+        };
+
+        if removed {
+            changes.push(LevelChange::removed(before[i].price));
+            i += 1;
+        } else {
+            changes.push(LevelChange::from(&after[j]));
+            j += 1;
+        }
+    }
+    changes
+}
+
+// Whole ticks of the price, which must decode back to the same f64
+fn to_ticks(price: f64, scale: f64) -> Result<i64, String> {
+    let ticks = (price * scale).round();
+    if !ticks.is_finite() || ticks.abs() >= MAX_TICKS || ticks / scale != price {
+        return Err(format!("Price {} is not a multiple of {}", price, 1.0 / scale));
+    }
+    Ok(ticks as i64)
+}
+
+fn zigzag(value: i64) -> u64 {
+    ((value << 1) ^ (value >> 63)) as u64
+}
+
+fn unzigzag(value: u64) -> i64 {
+    (value >> 1) as i64 ^ -((value & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn remaining(&self) -> usize {
+        self.bytes.len() - self.position
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let byte = *self.bytes.get(self.position).ok_or("Book diff ends early")?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn varint(&mut self) -> Result<u64, String> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err("Varint longer than 64 bits".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::message::Side;
+
+    fn level(side: Side, price: f64, quantity: u64, order_count: u32) -> MBPLevel {
+        MBPLevel {
+            price,
+            quantity: Quantity::from_units(quantity),
+            order_count,
+            side,
+            total_quantity: Quantity::from_units(quantity),
+            avg_age_ms: 0,
+        }
+    }
+
+    fn diff() -> BookDiff {
+        BookDiff {
+            bids: vec![
+                LevelChange { price: 100.25, quantity: Quantity::from_lots(150, 2), order_count: 3, deleted: false },
+                LevelChange::removed(100.0),
+                LevelChange::removed(99.99),
+                LevelChange::removed(99.98),
+                LevelChange { price: 99.5, quantity: Quantity::from_units(7), order_count: 1, deleted: false },
+            ],
+            asks: vec![
+                LevelChange::removed(100.5),
+                LevelChange { price: 101.75, quantity: Quantity::from_units(1_000_000), order_count: 42, deleted: false },
+            ],
+        }
+    }
+
+    #[test]
+    fn between_lists_changed_new_and_removed_levels_best_first() {
+        let before_bids = [level(Side::Bid, 100.0, 5, 1), level(Side::Bid, 99.0, 3, 1), level(Side::Bid, 98.0, 2, 1)];
+        let after_bids = [level(Side::Bid, 100.5, 1, 1), level(Side::Bid, 100.0, 5, 1), level(Side::Bid, 99.0, 4, 2)];
+        let asks = [level(Side::Ask, 101.0, 2, 1)];
+
+        let diff = BookDiff::between(&before_bids, &asks, &after_bids, &asks);
+        assert_eq!(
+            diff.bids,
+            vec![
+                LevelChange { price: 100.5, quantity: Quantity::from_units(1), order_count: 1, deleted: false },
+                LevelChange { price: 99.0, quantity: Quantity::from_units(4), order_count: 2, deleted: false },
+                LevelChange::removed(98.0),
+            ]
+        );
+        assert!(diff.asks.is_empty());
+    }
+
+    #[test]
+    fn encode_round_trips_with_and_without_rle() {
+        let diff = diff();
+        for rle in [false, true] {
+            let bytes = diff.encode(2, rle).unwrap();
+            assert_eq!(BookDiff::decode(&bytes).unwrap(), diff, "rle {}", rle);
+        }
+    }
+
+    #[test]
+    fn rle_folds_adjacent_removals() {
+        let diff = diff();
+        let plain = diff.encode(2, false).unwrap();
+        let folded = diff.encode(2, true).unwrap();
+        assert!(folded.len() < plain.len());
+    }
+
+    #[test]
+    fn varints_span_bytes_for_large_values() {
+        let diff = BookDiff {
+            bids: vec![LevelChange { price: 65_000.5, quantity: Quantity::from_units(123_456_789), order_count: 300, deleted: false }],
+            asks: Vec::new(),
+        };
+        let bytes = diff.encode(1, false).unwrap();
+        assert_eq!(BookDiff::decode(&bytes).unwrap(), diff);
+
+        let mut out = Vec::new();
+        write_varint(&mut out, 300);
+        assert_eq!(out, vec![0xac, 0x02]);
+        assert_eq!(Reader { bytes: &out, position: 0 }.varint().unwrap(), 300);
+        assert_eq!((zigzag(-1), zigzag(1), unzigzag(zigzag(-12_345))), (1, 2, -12_345));
+    }
+
+    #[test]
+    fn encode_refuses_prices_off_the_grid() {
+        let diff = BookDiff { bids: vec![LevelChange::removed(100.125)], asks: Vec::new() };
+        assert!(diff.encode(2, false).is_err());
+        assert!(diff.encode(MAX_PRICE_DECIMALS + 1, false).is_err());
+    }
+
+    #[test]
+    fn decode_refuses_malformed_input() {
+        let bytes = diff().encode(2, true).unwrap();
+        assert!(BookDiff::decode(&bytes[..bytes.len() - 1]).is_err());
+        assert!(BookDiff::decode(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(BookDiff::decode(&[CODEC_VERSION + 1, 0, 2, 0, 0]).is_err());
+        assert!(BookDiff::decode(&[CODEC_VERSION, 0, 2, 1, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]).is_err());
+    }
+
+    #[test]
+    fn checksum_rolls_over_deltas() {
+        let first = BookDiff { bids: vec![LevelChange::removed(100.0)], asks: Vec::new() };
+        let second = BookDiff {
+            bids: Vec::new(),
+            asks: vec![LevelChange { price: 101.5, quantity: Quantity::from_lots(5, 2), order_count: 1, deleted: false }],
+        };
+
+        let rolled = second.checksum(first.checksum(0));
+        assert_eq!(rolled, crc32fast::hash(b"b100:0:a101.5:0.05:"));
+        assert_ne!(rolled, first.checksum(second.checksum(0)));
+        assert_eq!(BookDiff::default().checksum(rolled), rolled);
+    }
+}
@@ -0,0 +1,367 @@
+use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, TimeZone, Utc};
+use clap::Args;
+use dashmap::DashMap;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::message::{Candle, Quantity};
+
+// Candles returned by one query at most
+pub const MAX_CANDLES_PER_QUERY: usize = 5000;
+
+// Candles per CandleHistory message of a backfill
+pub const CANDLE_HISTORY_PAGE_SIZE: usize = 500;
+
+// How long the candles of one interval are kept: "1m:30d", or "1h:forever" to keep
+// them as long as the book exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CandleRetention {
+    pub interval_secs: u64,
+    pub retention_secs: Option<u64>,
+}
+
+// Candle history of every book at the configured intervals. Only the finest interval is
+// built from the feed; coarser candles are compacted from finer ones in the background,
+// and candles past their retention are dropped once they have been compacted.
+#[derive(Debug, Clone, Args)]
+pub struct CandleStoreOptions {
+    /// Candle intervals kept for /candles and for how long, finest first, e.g.
+    /// 1s:24h,1m:30d,1h:forever; each interval must divide the next (default: no candle store)
+    #[arg(long, value_name = "INTERVAL:RETENTION", value_delimiter = ',', value_parser = parse_candle_retention)]
+    pub candle_retention: Vec<CandleRetention>,
+
+    /// How often finer candles are rolled into coarser ones and expired candles dropped
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub candle_compaction_secs: u64,
+
+    /// Intervals without updates in /candles responses: none (omitted), carry (flat at the
+    /// previous close) or empty (no prices); a request's `fill` overrides it
+    #[arg(long, value_name = "POLICY", default_value = "none")]
+    pub candle_gap_fill: CandleGapFill,
+}
+
+impl Default for CandleStoreOptions {
+    fn default() -> Self {
+        Self { candle_retention: Vec::new(), candle_compaction_secs: 60, candle_gap_fill: CandleGapFill::None }
+    }
+}
+
+// Candles synthesized for intervals in which a book had no updates, so charts get a
+// continuous series. Gap candles have no volume and `gap` set.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CandleGapFill {
+    // Missing intervals are left out
+    #[default]
+    None,
+    // Open, high, low and close are the previous candle's close
+    Carry,
+    // Prices are NaN, which serializes as null
+    Empty,
+}
+
+impl FromStr for CandleGapFill {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "carry" => Ok(Self::Carry),
+            "empty" => Ok(Self::Empty),
+            _ => Err(format!("Unknown gap fill policy '{}', expected none, carry or empty", s)),
+        }
+    }
+}
+
+impl CandleStoreOptions {
+    pub fn store(&self) -> Result<Option<CandleStore>, String> {
+        if self.candle_retention.is_empty() {
+            return Ok(None);
+        }
+        if self.candle_compaction_secs == 0 {
+            return Err("--candle-compaction-secs must be at least 1".to_string());
+        }
+        CandleStore::new(self.candle_retention.clone(), self.candle_gap_fill).map(Some)
+    }
+
+    pub fn compaction_interval(&self) -> Duration {
+        Duration::from_secs(self.candle_compaction_secs)
+    }
+}
+
+// "90", "90s", "5m", "24h", "30d" or "2w", in seconds
+pub fn parse_period(period: &str) -> Option<u64> {
+    let period = period.trim();
+    let (count, unit) = match period.find(|c: char| !c.is_ascii_digit()) {
+        Some(split) => period.split_at(split),
+        None => (period, "s"),
+    };
+    let unit = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 3_600,
+        "d" => 86_400,
+        "w" => 604_800,
+        _ => return None,
+    };
+    count.parse::<u64>().ok().filter(|count| *count > 0)?.checked_mul(unit)
+}
+
+pub fn parse_candle_retention(definition: &str) -> Result<CandleRetention, String> {
+    let invalid = |reason: &str| format!("Invalid candle retention '{}': {}", definition, reason);
+
+    let (interval, retention) = definition.trim().split_once(':').ok_or_else(|| invalid("expected INTERVAL:RETENTION"))?;
+    let interval_secs = parse_period(interval).ok_or_else(|| invalid("the interval must be a period such as 1s, 5m or 1h"))?;
+    let retention_secs = match retention.trim() {
+        "forever" => None,
+        retention => Some(parse_period(retention).ok_or_else(|| invalid("the retention must be a period such as 24h or 30d, or forever"))?),
+    };
+    if retention_secs.is_some_and(|retention| retention < interval_secs) {
+        return Err(invalid("the retention must be at least the interval"));
+    }
+    Ok(CandleRetention { interval_secs, retention_secs })
+}
+
+#[derive(Debug)]
+struct BookCandles {
+    // Closed candles of each interval, oldest first
+    closed: Vec<VecDeque<Candle>>,
+    // Candle of the finest interval still being built from the feed
+    current: Option<Candle>,
+}
+
+#[derive(Debug)]
+pub struct CandleStore {
+    tiers: Vec<CandleRetention>,
+    gap_fill: CandleGapFill,
+    books: DashMap<String, BookCandles>,
+    // Time of the last compaction in UNIX seconds, 0 before the first; coarser candles
+    // are complete up to it
+    compacted_at: AtomicI64,
+}
+
+impl CandleStore {
+    pub fn new(tiers: Vec<CandleRetention>, gap_fill: CandleGapFill) -> Result<Self, String> {
+        for pair in tiers.windows(2) {
+            let (finer, coarser) = (pair[0].interval_secs, pair[1].interval_secs);
+            if coarser <= finer || coarser % finer != 0 {
+                return Err(format!(
+                    "Candle intervals must be finest first and each a multiple of the one before, got {}s then {}s",
+                    finer, coarser
+                ));
+            }
+        }
+        Ok(Self {
+            tiers,
+            gap_fill,
+            books: DashMap::new(),
+            compacted_at: AtomicI64::new(0),
+        })
+    }
+
+    pub fn intervals(&self) -> impl Iterator<Item = u64> + '_ {
+        self.tiers.iter().map(|tier| tier.interval_secs)
+    }
+
+    pub fn record(&self, symbol: &str, price: f64, volume: Quantity, timestamp: DateTime<Utc>) {
+        let interval_secs = self.tiers[0].interval_secs;
+        let open_time = bucket_start(timestamp, interval_secs);
+        let mut book = self.books.entry(symbol.to_string()).or_insert_with(|| BookCandles {
+            closed: vec![VecDeque::new(); self.tiers.len()],
+            current: None,
+        });
+        let book = &mut *book;
+
+        // A clock stepped back keeps extending the candle being built
+        if let Some(candle) = book.current.as_mut().filter(|candle| candle.open_time >= open_time) {
+            candle.update(price, volume);
+            return;
+        }
+        // An update racing the compaction that closed its candle
+        if book.current.is_none() {
+            if let Some(candle) = book.closed[0].back_mut().filter(|candle| candle.open_time >= open_time) {
+                candle.update(price, volume);
+                return;
+            }
+        }
+
+        book.closed[0].extend(book.current.take());
+        book.current = Some(Candle::new(open_time, interval_secs, price, volume));
+    }
+
+    pub fn remove(&self, symbol: &str) {
+        self.books.remove(symbol);
+    }
+
+    // Rolls every finished bucket of each interval into the next coarser one, then drops
+    // candles past their retention that are already part of a coarser candle
+    pub fn compact(&self, now: DateTime<Utc>) {
+        let mut compacted = 0;
+        let mut expired = 0;
+
+        for mut entry in self.books.iter_mut() {
+            let book = entry.value_mut();
+            let finest = self.tiers[0].interval_secs;
+            if book.current.as_ref().is_some_and(|candle| candle_end(candle.open_time, finest) <= now) {
+                book.closed[0].extend(book.current.take());
+            }
+
+            for tier in 1..self.tiers.len() {
+                let interval_secs = self.tiers[tier].interval_secs;
+                let (finer, coarser) = book.closed.split_at_mut(tier);
+                let finer = &finer[tier - 1];
+                let coarser = &mut coarser[0];
+
+                let built_until = coarser.back().map(|candle| candle_end(candle.open_time, interval_secs));
+                let start = built_until.map_or(0, |until| finer.partition_point(|candle| candle.open_time < until));
+                let mut pending: Option<Candle> = None;
+                for candle in finer.range(start..) {
+                    let open_time = bucket_start(candle.open_time, interval_secs);
+                    if candle_end(open_time, interval_secs) > now {
+                        break;
+                    }
+                    match &mut pending {
+                        Some(bucket) if bucket.open_time == open_time => bucket.merge(candle),
+                        _ => {
+                            coarser.extend(pending.take());
+                            pending = Some(Candle { open_time, interval_secs, ..candle.clone() });
+                            compacted += 1;
+                        }
+                    }
+                }
+                coarser.extend(pending);
+            }
+
+            for (tier, retention) in self.tiers.iter().enumerate() {
+                let Some(retention_secs) = retention.retention_secs else {
+                    continue;
+                };
+                let cutoff = now - chrono::Duration::seconds(retention_secs as i64);
+                // Candles not yet in a coarser one are kept, so nothing is lost to a slow compaction
+                let compacted_until = match book.closed.get(tier + 1) {
+                    Some(coarser) => coarser.back().map(|candle| candle_end(candle.open_time, self.tiers[tier + 1].interval_secs)),
+                    None => Some(now),
+                };
+                let Some(compacted_until) = compacted_until else {
+                    continue;
+                };
+
+                let closed = &mut book.closed[tier];
+                while closed.front().is_some_and(|candle| {
+                    let end = candle_end(candle.open_time, retention.interval_secs);
+                    end <= cutoff && end <= compacted_until
+                }) {
+                    closed.pop_front();
+                    expired += 1;
+                }
+            }
+        }
+
+        self.compacted_at.store(now.timestamp(), Ordering::Relaxed);
+        if compacted > 0 || expired > 0 {
+            debug!("Candle compaction built {} candles and dropped {} expired ones", compacted, expired);
+        }
+    }
+
+    // Closed candles of one interval opening between `from` and `to`, the most recent
+    // `limit` of them oldest first, with gaps filled by `fill` or the store's policy;
+    // None if the symbol or interval is not kept
+    #[allow(clippy::too_many_arguments)]
+    pub fn candles(
+        &self,
+        symbol: &str,
+        interval_secs: u64,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: usize,
+        fill: Option<CandleGapFill>,
+        now: DateTime<Utc>,
+    ) -> Option<Vec<Candle>> {
+        let tier = self.tiers.iter().position(|tier| tier.interval_secs == interval_secs)?;
+        let book = self.books.get(symbol)?;
+        let fill = fill.unwrap_or(self.gap_fill);
+        let in_window = |open_time: DateTime<Utc>| from.is_none_or(|from| open_time >= from) && to.is_none_or(|to| open_time <= to);
+
+        let mut candles = Vec::new();
+        let mut previous: Option<&Candle> = None;
+        for candle in &book.closed[tier] {
+            if let Some(previous) = previous {
+                fill_gap(&mut candles, previous, candle.open_time, fill, in_window);
+            }
+            if in_window(candle.open_time) {
+                candles.push(candle.clone());
+            }
+            previous = Some(candle);
+        }
+
+        // Intervals after the last candle are only gaps once they could have been built:
+        // before the forming candle for the finest interval, and up to the last compaction
+        // for coarser ones
+        if let Some(previous) = previous {
+            let built_until = if tier == 0 {
+                book.current.as_ref().map_or_else(|| bucket_start(now, interval_secs), |candle| candle.open_time)
+            } else {
+                let compacted_at = self.compacted_at.load(Ordering::Relaxed);
+                Utc.timestamp_opt(compacted_at, 0).single().map_or(previous.open_time, |at| bucket_start(at, interval_secs))
+            };
+            fill_gap(&mut candles, previous, built_until, fill, in_window);
+        }
+
+        let limit = limit.min(MAX_CANDLES_PER_QUERY);
+        if candles.len() > limit {
+            candles.drain(..candles.len() - limit);
+        }
+        Some(candles)
+    }
+}
+
+// Synthesizes the candles between `previous` and the one opening at `until`
+fn fill_gap(
+    candles: &mut Vec<Candle>,
+    previous: &Candle,
+    until: DateTime<Utc>,
+    fill: CandleGapFill,
+    in_window: impl Fn(DateTime<Utc>) -> bool,
+) {
+    let price = match fill {
+        CandleGapFill::None => return,
+        CandleGapFill::Carry => previous.close,
+        CandleGapFill::Empty => f64::NAN,
+    };
+
+    let mut open_time = candle_end(previous.open_time, previous.interval_secs);
+    while open_time < until {
+        if in_window(open_time) {
+            candles.push(Candle {
+                gap: true,
+                ..Candle::new(open_time, previous.interval_secs, price, Quantity::ZERO)
+            });
+        }
+        open_time = candle_end(open_time, previous.interval_secs);
+    }
+}
+
+// Compacts every book's candles on a fixed interval
+pub async fn run_candle_compaction(store: Arc<CandleStore>, every: Duration) {
+    let mut interval = tokio::time::interval(every);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        store.compact(Utc::now());
+    }
+}
+
+fn bucket_start(timestamp: DateTime<Utc>, interval_secs: u64) -> DateTime<Utc> {
+    let secs = timestamp.timestamp();
+    let start = secs - secs.rem_euclid(interval_secs as i64);
+    Utc.timestamp_opt(start, 0).single().unwrap_or(timestamp)
+}
+
+fn candle_end(open_time: DateTime<Utc>, interval_secs: u64) -> DateTime<Utc> {
+    open_time + chrono::Duration::seconds(interval_secs as i64)
+}
@@ -0,0 +1,122 @@
+use std::collections::VecDeque;
+use chrono::{DateTime, TimeZone, Utc};
+
+use crate::message::{Candle, Quantity};
+
+// Candle intervals maintained for every symbol
+pub const CANDLE_INTERVALS_SECS: [u64; 3] = [1, 5, 60];
+
+// Closed candles retained per series
+pub const MAX_CANDLES: usize = 500;
+
+impl Candle {
+    pub(crate) fn new(open_time: DateTime<Utc>, interval_secs: u64, price: f64, volume: Quantity) -> Self {
+        Self {
+            open_time,
+            interval_secs,
+            open: price,
+            high: price,
+            low: price,
+            close: price,
+            volume,
+            gap: false,
+        }
+    }
+
+    pub(crate) fn update(&mut self, price: f64, volume: Quantity) {
+        self.high = self.high.max(price);
+        self.low = self.low.min(price);
+        self.close = price;
+        self.volume += volume;
+    }
+
+    // Extends the candle with a later, finer one
+    pub(crate) fn merge(&mut self, later: &Candle) {
+        self.high = self.high.max(later.high);
+        self.low = self.low.min(later.low);
+        self.close = later.close;
+        self.volume += later.volume;
+    }
+}
+
+#[derive(Debug)]
+pub struct CandleSeries {
+    interval_secs: u64,
+    closed: VecDeque<Candle>,
+    current: Option<Candle>,
+}
+
+impl CandleSeries {
+    pub fn new(interval_secs: u64) -> Self {
+        Self {
+            interval_secs,
+            closed: VecDeque::with_capacity(MAX_CANDLES),
+            current: None,
+        }
+    }
+
+    pub fn record(&mut self, price: f64, volume: Quantity, timestamp: DateTime<Utc>) {
+        let open_time = self.bucket_start(timestamp);
+
+        match &mut self.current {
+            Some(candle) if candle.open_time == open_time => candle.update(price, volume),
+            _ => {
+                if let Some(finished) = self.current.take() {
+                    if self.closed.len() == MAX_CANDLES {
+                        self.closed.pop_front();
+                    }
+                    self.closed.push_back(finished);
+                }
+                self.current = Some(Candle::new(open_time, self.interval_secs, price, volume));
+            }
+        }
+    }
+
+    // Close prices oldest first, including the forming candle
+    pub fn closes(&self) -> Vec<f64> {
+        self.closed
+            .iter()
+            .chain(self.current.iter())
+            .map(|candle| candle.close)
+            .collect()
+    }
+
+    pub fn interval_secs(&self) -> u64 {
+        self.interval_secs
+    }
+
+    fn bucket_start(&self, timestamp: DateTime<Utc>) -> DateTime<Utc> {
+        let secs = timestamp.timestamp();
+        let start = secs - secs.rem_euclid(self.interval_secs as i64);
+        Utc.timestamp_opt(start, 0).single().unwrap_or(timestamp)
+    }
+}
+
+#[derive(Debug)]
+pub struct CandleAggregator {
+    series: Vec<CandleSeries>,
+}
+
+impl Default for CandleAggregator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CandleAggregator {
+    pub fn new() -> Self {
+        Self {
+            series: CANDLE_INTERVALS_SECS.iter().map(|&secs| CandleSeries::new(secs)).collect(),
+        }
+    }
+
+    pub fn record(&mut self, price: f64, volume: Quantity, timestamp: DateTime<Utc>) {
+        for series in &mut self.series {
+            series.record(price, volume, timestamp);
+        }
+    }
+
+    pub fn series(&self, interval_secs: u64) -> Option<&CandleSeries> {
+        self.series.iter().find(|series| series.interval_secs() == interval_secs)
+    }
+}
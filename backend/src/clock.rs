@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use std::sync::OnceLock;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use chrono::{DateTime, Utc};
+
+use crate::message::{ClockOffset, ServerMessage};
+
+// Handshake query parameter that starts a clock-sync exchange as soon as the client connects
+pub const CLOCK_SYNC_PARAM: &str = "clock_sync";
+
+// Source of market data timestamps, in nanoseconds since the UNIX epoch
+pub trait ClockSource: Send + Sync {
+    fn now_ns(&self) -> u64;
+
+    fn now(&self) -> DateTime<Utc> {
+        DateTime::from_timestamp_nanos(self.now_ns() as i64)
+    }
+}
+
+// Wall-clock time read once and advanced by Instant from then on, so timestamps never
+// go backwards when the system clock is stepped, e.g. by NTP
+#[derive(Debug)]
+pub struct MonotonicClock {
+    anchor_ns: u64,
+    anchor: Instant,
+}
+
+impl MonotonicClock {
+    pub fn new() -> Self {
+        let anchor_ns = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |since| since.as_nanos() as u64);
+        Self { anchor_ns, anchor: Instant::now() }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ClockSource for MonotonicClock {
+    fn now_ns(&self) -> u64 {
+        self.anchor_ns + self.anchor.elapsed().as_nanos() as u64
+    }
+}
+
+// Process-wide clock behind event_ts and send_ts
+pub fn clock() -> &'static MonotonicClock {
+    static CLOCK: OnceLock<MonotonicClock> = OnceLock::new();
+    CLOCK.get_or_init(MonotonicClock::new)
+}
+
+// Last event timestamp sent on each of one client's streams. An update built from an
+// older state than one already sent, e.g. an initial snapshot overtaken by a tick,
+// reports the later time so event_ts never decreases within a stream.
+#[derive(Debug, Default)]
+pub struct EventOrdering {
+    last_event: HashMap<String, DateTime<Utc>>,
+}
+
+impl EventOrdering {
+    pub fn order(&mut self, stream_id: &str, event_ts: DateTime<Utc>) -> DateTime<Utc> {
+        match self.last_event.get_mut(stream_id) {
+            Some(last) => {
+                *last = (*last).max(event_ts);
+                *last
+            }
+            None => {
+                self.last_event.insert(stream_id.to_string(), event_ts);
+                event_ts
+            }
+        }
+    }
+
+    pub fn forget(&mut self, stream_id: &str) {
+        self.last_event.remove(stream_id);
+    }
+}
+
+// Server side of one connection's clock-sync exchange: ClockSync carries the server's
+// time, the client answers with its own on receipt, and the reply's arrival closes the
+// round trip. The offset is the client's time less the server's at the midpoint.
+#[derive(Debug, Default)]
+pub struct ClockSync {
+    probe: Option<DateTime<Utc>>,
+}
+
+impl ClockSync {
+    // Starts an exchange, replacing any the client has not answered yet
+    pub fn probe(&mut self) -> ServerMessage {
+        let server_ts = clock().now();
+        self.probe = Some(server_ts);
+        ServerMessage::ClockSync { server_ts }
+    }
+
+    pub fn complete(&mut self, server_ts: DateTime<Utc>, client_ts: DateTime<Utc>) -> Result<ClockOffset, String> {
+        match self.probe {
+            None => return Err("No clock sync in progress".to_string()),
+            Some(probe) if probe != server_ts => return Err("Reply to an earlier clock sync".to_string()),
+            Some(_) => self.probe = None,
+        }
+
+        let received = clock().now();
+        let round_trip_us = (received - server_ts).num_microseconds().unwrap_or(i64::MAX).max(0);
+        let midpoint = server_ts + chrono::Duration::microseconds(round_trip_us / 2);
+        let offset_us = (client_ts - midpoint).num_microseconds().ok_or("Client clock out of range")?;
+
+        Ok(ClockOffset {
+            offset_us,
+            round_trip_us: round_trip_us as u64,
+            measured_at: received,
+        })
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use serde::Serialize;
+
+// Handshake query parameter choosing how messages are encoded: `json` (the default) or
+// `deflate`
+pub const ENCODING_PARAM: &str = "encoding";
+
+// Messages shorter than this go out as text frames even on deflate connections, as
+// deflate's framing outweighs what it saves on them
+pub const MIN_COMPRESSED_BYTES: usize = 128;
+
+// Latest compressed messages a connection's compression is judged over. Once that many
+// are in, each new one moves the window on and the decision is made again
+const FALLBACK_WINDOW: usize = 256;
+
+// Compressed over uncompressed size above which compression saves too little bandwidth
+const FALLBACK_RATIO: f64 = 0.9;
+
+// CPU time per byte saved above which compression costs more than the bandwidth it saves
+// is worth, i.e. it must save at least 10MB per second spent compressing
+const MAX_NANOS_PER_BYTE_SAVED: f64 = 100.0;
+
+// While a connection is back on text frames, every this many messages is still compressed
+// as a probe, so the window can tell when compression pays off again
+const PROBE_INTERVAL: u64 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Json,
+    Deflate,
+}
+
+impl Encoding {
+    // None for an encoding the server does not offer
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(Encoding::Json),
+            "deflate" => Some(Encoding::Deflate),
+            _ => None,
+        }
+    }
+}
+
+// A message as it goes out on a deflate connection
+pub enum Encoded {
+    Text(String),
+    // Raw deflate of the JSON, each message compressed on its own
+    Deflated { data: Vec<u8>, compressed: Compressed },
+}
+
+// One compressed message
+#[derive(Debug, Clone, Copy)]
+pub struct Compressed {
+    pub input_bytes: usize,
+    pub output_bytes: usize,
+    pub elapsed: Duration,
+}
+
+// Compression of one connection's messages so far
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CompressionStats {
+    pub messages: u64,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    pub cpu_seconds: f64,
+    // Whether the connection is back on text frames, bar probes
+    pub fell_back: bool,
+    // Times it went back to text frames, and back to compressing
+    pub fallbacks: u64,
+    pub resumes: u64,
+}
+
+impl CompressionStats {
+    pub fn record(&mut self, compressed: &Compressed) {
+        self.messages += 1;
+        self.input_bytes += compressed.input_bytes as u64;
+        self.output_bytes += compressed.output_bytes as u64;
+        self.cpu_seconds += compressed.elapsed.as_secs_f64();
+    }
+
+    pub fn switch(&mut self, switch: Switch) {
+        self.fell_back = switch == Switch::FellBack;
+        match switch {
+            Switch::FellBack => self.fallbacks += 1,
+            Switch::Resumed => self.resumes += 1,
+        }
+    }
+
+    // Compressed over uncompressed size, None before anything was compressed
+    pub fn ratio(&self) -> Option<f64> {
+        (self.input_bytes > 0).then(|| self.output_bytes as f64 / self.input_bytes as f64)
+    }
+}
+
+// A connection going back to text frames, or back to compressing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Switch {
+    FellBack,
+    Resumed,
+}
+
+// The latest FALLBACK_WINDOW compressed messages, with their running totals
+#[derive(Debug, Default)]
+struct Window {
+    samples: VecDeque<Compressed>,
+    input_bytes: usize,
+    output_bytes: usize,
+    elapsed: Duration,
+}
+
+impl Window {
+    fn push(&mut self, compressed: Compressed) {
+        if self.samples.len() == FALLBACK_WINDOW {
+            if let Some(oldest) = self.samples.pop_front() {
+                self.input_bytes -= oldest.input_bytes;
+                self.output_bytes -= oldest.output_bytes;
+                self.elapsed -= oldest.elapsed;
+            }
+        }
+        self.input_bytes += compressed.input_bytes;
+        self.output_bytes += compressed.output_bytes;
+        self.elapsed += compressed.elapsed;
+        self.samples.push_back(compressed);
+    }
+
+    fn is_full(&self) -> bool {
+        self.samples.len() == FALLBACK_WINDOW
+    }
+
+    // Whether the window's messages shrank enough, for little enough CPU time per byte saved
+    fn pays_off(&self) -> bool {
+        let saved = self.input_bytes.saturating_sub(self.output_bytes);
+        saved > 0
+            && self.output_bytes as f64 <= self.input_bytes as f64 * FALLBACK_RATIO
+            && self.elapsed.as_nanos() as f64 / saved as f64 <= MAX_NANOS_PER_BYTE_SAVED
+    }
+}
+
+// Compresses the messages of a deflate connection while, over the latest FALLBACK_WINDOW
+// of them, compression pays for its CPU time, and sends text frames while it does not,
+// e.g. for payloads that do not repeat themselves or a CPU-starved server. Probes keep
+// the window moving on text frames, so a connection switches back once it pays off again
+#[derive(Debug, Default)]
+pub struct Compressor {
+    stats: CompressionStats,
+    window: Window,
+    // Messages long enough to compress since the connection went back to text frames
+    skipped: u64,
+    // Whether a message was compressed since the decision was last made
+    pending: bool,
+}
+
+impl Compressor {
+    pub fn encode(&mut self, json: String) -> Encoded {
+        if json.len() < MIN_COMPRESSED_BYTES {
+            return Encoded::Text(json);
+        }
+        if self.stats.fell_back {
+            self.skipped += 1;
+            if !self.skipped.is_multiple_of(PROBE_INTERVAL) {
+                return Encoded::Text(json);
+            }
+        }
+
+        let started = Instant::now();
+        let mut encoder = DeflateEncoder::new(Vec::with_capacity(json.len() / 2), Compression::fast());
+        let Ok(data) = encoder.write_all(json.as_bytes()).and_then(|()| encoder.finish()) else {
+            return Encoded::Text(json);
+        };
+        let compressed = Compressed {
+            input_bytes: json.len(),
+            output_bytes: data.len(),
+            elapsed: started.elapsed(),
+        };
+        self.observe(compressed);
+        Encoded::Deflated { data, compressed }
+    }
+
+    fn observe(&mut self, compressed: Compressed) {
+        self.stats.record(&compressed);
+        self.window.push(compressed);
+        self.pending = true;
+    }
+
+    // Makes the decision again after a compressed message once the window is full; Some
+    // when the connection switches. The window starts over after a switch, so the next
+    // one is judged only on messages sent since
+    pub fn reevaluate(&mut self) -> Option<Switch> {
+        if !std::mem::take(&mut self.pending) || !self.window.is_full() {
+            return None;
+        }
+        let switch = match (self.stats.fell_back, self.window.pays_off()) {
+            (false, false) => Switch::FellBack,
+            (true, true) => Switch::Resumed,
+            _ => return None,
+        };
+        self.stats.switch(switch);
+        self.window = Window::default();
+        self.skipped = 0;
+        Some(switch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(input_bytes: usize, output_bytes: usize, elapsed_nanos: u64) -> Compressed {
+        Compressed { input_bytes, output_bytes, elapsed: Duration::from_nanos(elapsed_nanos) }
+    }
+
+    fn observe_window(compressor: &mut Compressor, compressed: Compressed) -> Vec<Switch> {
+        (0..FALLBACK_WINDOW)
+            .filter_map(|_| {
+                compressor.observe(compressed);
+                compressor.reevaluate()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn keeps_compressing_while_it_pays_off() {
+        let mut compressor = Compressor::default();
+        assert!(observe_window(&mut compressor, sample(1000, 200, 8_000)).is_empty());
+        assert!(observe_window(&mut compressor, sample(1000, 200, 8_000)).is_empty());
+        assert!(!compressor.stats.fell_back);
+        assert_eq!(compressor.stats.messages, 2 * FALLBACK_WINDOW as u64);
+    }
+
+    #[test]
+    fn falls_back_when_messages_barely_shrink() {
+        let mut compressor = Compressor::default();
+        assert_eq!(observe_window(&mut compressor, sample(1000, 950, 1_000)), [Switch::FellBack]);
+        assert!(compressor.stats.fell_back);
+        assert_eq!(compressor.stats.fallbacks, 1);
+    }
+
+    #[test]
+    fn falls_back_when_each_byte_saved_costs_too_much_cpu() {
+        let mut compressor = Compressor::default();
+        // Saves 800 bytes a message, for 200ns a byte
+        assert_eq!(observe_window(&mut compressor, sample(1000, 200, 160_000)), [Switch::FellBack]);
+    }
+
+    #[test]
+    fn decides_again_as_the_window_moves() {
+        let mut compressor = Compressor::default();
+        assert!(observe_window(&mut compressor, sample(1000, 200, 8_000)).is_empty());
+        // Payloads turn incompressible: the decision flips before a whole window of them
+        let switches: Vec<_> = (1..=FALLBACK_WINDOW)
+            .filter_map(|n| {
+                compressor.observe(sample(1000, 1000, 1_000));
+                compressor.reevaluate().map(|switch| (n, switch))
+            })
+            .collect();
+        assert_eq!(switches.len(), 1);
+        assert_eq!(switches[0].1, Switch::FellBack);
+        assert!(switches[0].0 < FALLBACK_WINDOW);
+
+        assert_eq!(observe_window(&mut compressor, sample(1000, 200, 8_000)), [Switch::Resumed]);
+        assert!(!compressor.stats.fell_back);
+        assert_eq!((compressor.stats.fallbacks, compressor.stats.resumes), (1, 1));
+    }
+
+    #[test]
+    fn probes_every_interval_after_falling_back() {
+        let mut compressor = Compressor::default();
+        observe_window(&mut compressor, sample(1000, 1000, 1_000));
+        let json = "x".repeat(MIN_COMPRESSED_BYTES);
+        let probes = (0..PROBE_INTERVAL * 4)
+            .filter(|_| matches!(compressor.encode(json.clone()), Encoded::Deflated { .. }))
+            .count();
+        assert_eq!(probes, 4);
+        assert!(matches!(compressor.encode("short".to_string()), Encoded::Text(_)));
+    }
+}
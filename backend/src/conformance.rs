@@ -0,0 +1,109 @@
+use std::time::Duration;
+use clap::Args;
+
+use crate::message::MBPLevel;
+use crate::order_book::OrderBook;
+use crate::stream_manager::SIMULATION_INTERVAL;
+
+// Levels per side of each golden update, enough for any client checksum
+pub const GOLDEN_DEPTH: u32 = 25;
+
+// A deterministic market for certifying client implementations: every simulated book is
+// seeded from the conformance seed and the simulation stops after a fixed number of
+// ticks, so each book goes through the same states on every run. `md-verify` regenerates
+// those states and checks a client's capture against them.
+#[derive(Debug, Clone, Default, Args)]
+pub struct ConformanceOptions {
+    /// Emit the golden stream of this seed instead of a random market
+    #[arg(long = "conformance-seed", value_name = "SEED")]
+    pub conformance_seed: Option<u64>,
+
+    /// Length of the golden stream; the books stop changing after it
+    #[arg(long = "conformance-duration-secs", default_value_t = 60)]
+    pub conformance_duration_secs: u64,
+}
+
+impl ConformanceOptions {
+    pub fn ticks(&self) -> u64 {
+        conformance_ticks(Duration::from_secs(self.conformance_duration_secs))
+    }
+}
+
+// Simulation ticks in a golden stream of this length
+pub fn conformance_ticks(duration: Duration) -> u64 {
+    (duration.as_millis() as u64).div_ceil(SIMULATION_INTERVAL.as_millis() as u64)
+}
+
+// Seed of one book: the conformance seed mixed with an FNV-1a hash of the symbol, which
+// unlike the std hashers is stable across builds
+pub fn book_seed(seed: u64, symbol: &str) -> u64 {
+    symbol
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325, |hash: u64, byte| (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3))
+        ^ seed
+}
+
+// The state of a golden book after a tick; tick 0 is the book before the first tick
+#[derive(Debug, Clone)]
+pub struct GoldenUpdate {
+    pub symbol: String,
+    pub tick: u64,
+    pub sequence: u64,
+    pub bids: Vec<MBPLevel>,
+    pub asks: Vec<MBPLevel>,
+}
+
+// Replays the server's simulation of the given symbols for a seed, tick by tick and in
+// symbol order within a tick. Books must use the default order limit and quantity decimals.
+pub struct GoldenStream {
+    books: Vec<OrderBook>,
+    ticks: u64,
+    tick: u64,
+    next_book: usize,
+}
+
+impl GoldenStream {
+    pub fn new(seed: u64, symbols: &[String], ticks: u64) -> Self {
+        let books = symbols
+            .iter()
+            .map(|symbol| {
+                let mut order_book = OrderBook::new(symbol.clone());
+                order_book.set_seed(book_seed(seed, symbol));
+                order_book.initialize_with_sample_data();
+                order_book
+            })
+            .collect();
+
+        Self { books, ticks, tick: 0, next_book: 0 }
+    }
+}
+
+impl Iterator for GoldenStream {
+    type Item = GoldenUpdate;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.next_book == self.books.len() {
+            if self.tick == self.ticks || self.books.is_empty() {
+                return None;
+            }
+            self.tick += 1;
+            self.next_book = 0;
+        }
+
+        let order_book = &mut self.books[self.next_book];
+        self.next_book += 1;
+        if self.tick > 0 {
+            order_book.simulate_activity();
+            order_book.take_trades();
+        }
+
+        let (bids, asks) = order_book.get_mbp_data(GOLDEN_DEPTH);
+        Some(GoldenUpdate {
+            symbol: order_book.symbol.to_string(),
+            tick: self.tick,
+            sequence: order_book.get_sequence(),
+            bids,
+            asks,
+        })
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::Serialize;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::clock::EventOrdering;
+use crate::message::{DisconnectReason, OutboundMessage};
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct DeadLetterOptions {
+    /// Keep the undelivered messages of this many of the latest connections whose socket
+    /// failed or that the server closed with messages queued, for GET /admin/dead-letters
+    /// (default: disabled)
+    #[arg(long, value_name = "N")]
+    pub dead_letter_capacity: Option<usize>,
+
+    /// Messages kept of each such connection, the first undelivered ones; the rest are only
+    /// counted
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    pub dead_letter_messages: usize,
+}
+
+impl DeadLetterOptions {
+    pub fn log(&self) -> Result<Option<DeadLetterLog>, String> {
+        let Some(capacity) = self.dead_letter_capacity else {
+            return Ok(None);
+        };
+        if capacity == 0 {
+            return Err("--dead-letter-capacity must be at least 1".to_string());
+        }
+        Ok(Some(DeadLetterLog::new(capacity, self.dead_letter_messages)))
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "reason", rename_all = "snake_case")]
+pub enum DeadLetterReason {
+    // Writing to the socket failed; the message being written is the first undelivered
+    SendFailed { error: String },
+    // The server closed the connection, e.g. as a slow consumer, with messages queued
+    Disconnected { disconnect: DisconnectReason },
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetter {
+    pub stream_id: Option<String>,
+    pub message: String, // As it would have been sent
+}
+
+// What one connection was never sent, in the order it would have been
+#[derive(Debug, Clone, Serialize)]
+pub struct DeadLetterBatch {
+    pub client_id: String,
+    pub api_key: Option<String>,
+    #[serde(flatten)]
+    pub reason: DeadLetterReason,
+    pub timestamp: DateTime<Utc>,
+    pub undelivered: usize,
+    pub messages: Vec<DeadLetter>,
+}
+
+// Bounded log of undeliverable messages for postmortems of slow consumers and failing
+// connections; the oldest batch makes way for a new one
+#[derive(Debug)]
+pub struct DeadLetterLog {
+    capacity: usize,
+    messages_per_batch: usize,
+    batches: Mutex<VecDeque<DeadLetterBatch>>,
+}
+
+impl DeadLetterLog {
+    pub fn new(capacity: usize, messages_per_batch: usize) -> Self {
+        Self { capacity, messages_per_batch, batches: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn capture(&self, client_id: Uuid, api_key: Option<String>, reason: DeadLetterReason, undelivered: Vec<OutboundMessage>) {
+        let undelivered: Vec<OutboundMessage> =
+            undelivered.into_iter().filter(|message| !matches!(message, OutboundMessage::Disconnect(_))).collect();
+        if undelivered.is_empty() {
+            return;
+        }
+
+        // Captured with their own event times, not raised to those already sent on the stream
+        let mut ordering = EventOrdering::default();
+        let messages = undelivered
+            .iter()
+            .take(self.messages_per_batch)
+            .filter_map(|message| match message.to_json(&mut ordering, false) {
+                Ok(json) => Some(DeadLetter { stream_id: message.stream_id().map(str::to_string), message: json }),
+                Err(e) => {
+                    error!("Failed to serialize dead letter of client {}: {}", client_id, e);
+                    None
+                }
+            })
+            .collect();
+        let batch = DeadLetterBatch {
+            client_id: client_id.to_string(),
+            api_key,
+            reason,
+            timestamp: Utc::now(),
+            undelivered: undelivered.len(),
+            messages,
+        };
+
+        let mut batches = self.batches.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if batches.len() == self.capacity {
+            batches.pop_front();
+        }
+        batches.push_back(batch);
+    }
+
+    // Newest first, optionally of one client only
+    pub fn recent(&self, client_id: Option<&str>, limit: usize) -> Vec<DeadLetterBatch> {
+        let batches = self.batches.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        batches
+            .iter()
+            .rev()
+            .filter(|batch| client_id.is_none_or(|client_id| batch.client_id == client_id))
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use clap::Args;
+use tracing::warn;
+
+// Epochs of the symbols' books. A symbol's epoch goes up each time a book is created for
+// it, since the new book's sequence starts over; market data carries both so clients can
+// tell a restart from a gap.
+#[derive(Debug, Clone, Default, Args)]
+pub struct EpochOptions {
+    /// Keep the last book epoch of every symbol in this JSON file, so epochs keep
+    /// increasing across server restarts (default: epochs start at 1 on every run)
+    #[arg(long, value_name = "PATH")]
+    pub epoch_file: Option<PathBuf>,
+}
+
+impl EpochOptions {
+    pub fn epochs(&self) -> io::Result<BookEpochs> {
+        match &self.epoch_file {
+            Some(path) => BookEpochs::load(path),
+            None => Ok(BookEpochs::default()),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct BookEpochs {
+    epochs: Mutex<HashMap<String, u64>>,
+    file: Option<PathBuf>,
+}
+
+impl BookEpochs {
+    // Continues from the epochs saved in `path`, which need not exist yet
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let epochs = match std::fs::read(path) {
+            Ok(contents) => serde_json::from_slice(&contents)
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e),
+        };
+        Ok(Self { epochs: Mutex::new(epochs), file: Some(path.to_path_buf()) })
+    }
+
+    // Epoch of a new book of `symbol`, saved before the book publishes anything
+    pub fn next(&self, symbol: &str) -> u64 {
+        let mut epochs = self.epochs.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let epoch = epochs.entry(symbol.to_string()).or_insert(0);
+        *epoch += 1;
+        let epoch = *epoch;
+
+        if let Some(path) = &self.file {
+            if let Err(e) = save(path, &epochs) {
+                warn!("Failed to save book epochs to {}: {}", path.display(), e);
+            }
+        }
+        epoch
+    }
+}
+
+// Written next to the file and renamed over it, so a crash never leaves it half written
+fn save(path: &Path, epochs: &HashMap<String, u64>) -> io::Result<()> {
+    let staged = path.with_extension("tmp");
+    std::fs::write(&staged, serde_json::to_vec(epochs)?)?;
+    std::fs::rename(staged, path)
+}
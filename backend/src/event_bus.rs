@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use tokio::sync::broadcast;
+use tokio::sync::broadcast::error::RecvError;
+use tracing::warn;
+
+use crate::integrity::IntegrityViolation;
+use crate::message::{FutureContract, OrderActivity, OutOfBandEvent, Trade};
+
+// Events a consumer may fall behind by before it starts missing them
+const BUS_CAPACITY: usize = 16384;
+
+// What happened to the market. The simulator, the ingest API, futures expiry, symbol
+// eviction, load shedding, integrity checks and price bands publish these; distribution, analytics
+// and any other sink consume them.
+#[derive(Debug, Clone)]
+pub enum MarketEvent {
+    // A book changed and was republished; `mid_price` is the mid after the change.
+    // `previous_sequence` is the sequence it was last published at when the order activity
+    // published since then makes up the change, and None when it does not, as after a repair.
+    BookUpdated {
+        symbol: Arc<str>,
+        sequence: u64,
+        previous_sequence: Option<u64>,
+        event_ts: DateTime<Utc>,
+        mid_price: Option<f64>,
+    },
+    Activity(OrderActivity),
+    // Published before the BookUpdated of the change that filled it
+    Trade(Trade),
+    // A dated future halted or was delisted, published for the contract and for every
+    // calendar spread on it
+    Status {
+        symbol: Arc<str>,
+        contract: FutureContract,
+    },
+    // The book was evicted or delisted; consumers drop whatever they keep for it
+    BookRemoved {
+        symbol: Arc<str>,
+    },
+    // Load shedding changed how often low-priority streams are updated; None at full rate
+    Conflation {
+        interval: Option<Duration>,
+    },
+    // Every book of a simulation tick has been updated
+    TickCompleted {
+        timestamp: DateTime<Utc>,
+    },
+    // A book failed its integrity check; a repaired book is republished with a BookUpdated
+    IntegrityViolation {
+        symbol: Arc<str>,
+        violations: Vec<IntegrityViolation>,
+        repaired: bool,
+    },
+    // An order or trade the book's price band quarantined
+    Quarantined(OutOfBandEvent),
+}
+
+// Typed broadcast of market events. Publishing never waits on consumers; each consumer
+// has its own receiver and sees events in publication order.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<MarketEvent>,
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EventBus {
+    pub fn new() -> Self {
+        let (sender, _) = broadcast::channel(BUS_CAPACITY);
+        Self { sender }
+    }
+
+    // Events published while nobody is subscribed are dropped
+    pub fn publish(&self, event: MarketEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    // Receives every event published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<MarketEvent> {
+        self.sender.subscribe()
+    }
+}
+
+// The next event for a consumer, logging any it missed by falling behind; None once
+// the bus is gone
+pub async fn next_event(receiver: &mut broadcast::Receiver<MarketEvent>, consumer: &str) -> Option<MarketEvent> {
+    loop {
+        match next_event_or_missed(receiver).await? {
+            Ok(event) => return Some(event),
+            Err(missed) => warn!("{} fell behind the event bus and missed {} events", consumer, missed),
+        }
+    }
+}
+
+// The next event for a consumer, or the number of events it missed by falling behind for
+// consumers that must make up for them; None once the bus is gone
+pub async fn next_event_or_missed(receiver: &mut broadcast::Receiver<MarketEvent>) -> Option<Result<MarketEvent, u64>> {
+    match receiver.recv().await {
+        Ok(event) => Some(Ok(event)),
+        Err(RecvError::Lagged(missed)) => Some(Err(missed)),
+        Err(RecvError::Closed) => None,
+    }
+}
@@ -0,0 +1,69 @@
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Utc};
+
+struct FeedState {
+    last_update: Instant,
+    last_update_ts: DateTime<Utc>,
+    stale: bool,
+}
+
+// Books that have gone without an update for too long. Only books that have updated at
+// least once are watched, so a book nothing feeds is never reported.
+#[derive(Default)]
+pub struct FeedMonitor {
+    stale_after: Duration,
+    feeds: HashMap<Arc<str>, FeedState>,
+    // Halted books no longer change, so they are never stale
+    halted: HashSet<Arc<str>>,
+}
+
+impl FeedMonitor {
+    pub fn new(stale_after: Duration) -> Self {
+        Self {
+            stale_after,
+            ..Self::default()
+        }
+    }
+
+    // Records an update, returning how long the feed had been silent if it was stale
+    pub fn updated(&mut self, symbol: &Arc<str>, event_ts: DateTime<Utc>) -> Option<Duration> {
+        let now = Instant::now();
+        let Some(feed) = self.feeds.get_mut(symbol) else {
+            self.feeds.insert(Arc::clone(symbol), FeedState { last_update: now, last_update_ts: event_ts, stale: false });
+            return None;
+        };
+
+        let silence = std::mem::take(&mut feed.stale).then(|| now - feed.last_update);
+        feed.last_update = now;
+        feed.last_update_ts = event_ts;
+        silence
+    }
+
+    pub fn set_halted(&mut self, symbol: &Arc<str>, halted: bool) {
+        if halted {
+            self.halted.insert(Arc::clone(symbol));
+        } else {
+            self.halted.remove(symbol);
+        }
+    }
+
+    pub fn remove(&mut self, symbol: &str) {
+        self.feeds.remove(symbol);
+        self.halted.remove(symbol);
+    }
+
+    // Books that went stale since the last check, with the time of their last update
+    pub fn newly_stale(&mut self) -> Vec<(Arc<str>, DateTime<Utc>)> {
+        let now = Instant::now();
+        self.feeds
+            .iter_mut()
+            .filter(|(symbol, feed)| !feed.stale && now - feed.last_update >= self.stale_after && !self.halted.contains(*symbol))
+            .map(|(symbol, feed)| {
+                feed.stale = true;
+                (Arc::clone(symbol), feed.last_update_ts)
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,80 @@
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+
+use crate::message::{OutboundMessage, ServerMessage};
+
+const MAX_FILTER_INTERVAL_MS: u64 = 3_600_000;
+const MAX_MUTED_SYMBOLS: usize = 1000;
+
+// Messages a client muted connection-wide with SetFilters, e.g. while its UI is in the
+// background. Subscriptions stay in place; muted messages are dropped as they are dequeued.
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilters {
+    mute_heartbeats: bool,
+    min_interval: Option<Duration>,
+    muted_symbols: HashSet<String>,
+}
+
+impl MessageFilters {
+    pub fn new(mute_heartbeats: bool, min_interval_ms: Option<u64>, symbols_mute: Vec<String>) -> Result<Self, String> {
+        if min_interval_ms.is_some_and(|interval_ms| interval_ms > MAX_FILTER_INTERVAL_MS) {
+            return Err(format!("min_interval_ms must be at most {}", MAX_FILTER_INTERVAL_MS));
+        }
+        if symbols_mute.len() > MAX_MUTED_SYMBOLS {
+            return Err(format!("At most {} symbols may be muted, got {}", MAX_MUTED_SYMBOLS, symbols_mute.len()));
+        }
+
+        Ok(Self {
+            mute_heartbeats,
+            min_interval: min_interval_ms.filter(|interval_ms| *interval_ms > 0).map(Duration::from_millis),
+            muted_symbols: symbols_mute.into_iter().collect(),
+        })
+    }
+}
+
+// Applies the filters the connection's reader last set to the messages its writer dequeues
+pub struct MessageFilter {
+    filters: watch::Receiver<MessageFilters>,
+    // When each stream last had an update delivered, while an interval is set
+    last_sent: HashMap<String, Instant>,
+}
+
+impl MessageFilter {
+    pub fn new(filters: watch::Receiver<MessageFilters>) -> Self {
+        Self { filters, last_sent: HashMap::new() }
+    }
+
+    // Whether the message is dropped. Control messages other than heartbeats always pass.
+    pub fn mutes(&mut self, message: &OutboundMessage) -> bool {
+        let filters = self.filters.borrow();
+        match message {
+            OutboundMessage::Message(ServerMessage::HeartBeat { .. }) => return filters.mute_heartbeats,
+            OutboundMessage::Message(ServerMessage::Unsubscribed { stream_id }) => {
+                self.last_sent.remove(stream_id);
+                return false;
+            }
+            _ if !message.is_stream_update() => return false,
+            _ => {}
+        }
+        if message.symbol().is_some_and(|symbol| filters.muted_symbols.contains(symbol)) {
+            return true;
+        }
+
+        let Some(min_interval) = filters.min_interval else {
+            self.last_sent.clear();
+            return false;
+        };
+        let Some(stream_id) = message.stream_id() else {
+            return false;
+        };
+        let now = Instant::now();
+        match self.last_sent.get(stream_id) {
+            Some(sent) if now.duration_since(*sent) < min_interval => true,
+            _ => {
+                self.last_sent.insert(stream_id.to_string(), now);
+                false
+            }
+        }
+    }
+}
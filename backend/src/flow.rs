@@ -0,0 +1,66 @@
+use std::collections::VecDeque;
+use chrono::{DateTime, Duration, Utc};
+
+use crate::message::{AggressorFlowStats, Quantity, Side, Trade};
+
+// Rolling window used for buy/sell aggressor volume
+pub const FLOW_WINDOW_SECS: u64 = 60;
+
+// Rolling buy-vs-sell aggressor volume and cumulative volume delta for one symbol
+#[derive(Debug, Default)]
+pub struct AggressorFlow {
+    window: VecDeque<(DateTime<Utc>, Side, Quantity)>,
+    window_buy_volume: Quantity,
+    window_sell_volume: Quantity,
+    cumulative_buy_volume: Quantity,
+    cumulative_sell_volume: Quantity,
+    trade_count: u64,
+}
+
+impl AggressorFlow {
+    pub fn record(&mut self, trade: &Trade) {
+        match trade.aggressor_side {
+            Side::Bid => {
+                self.window_buy_volume += trade.quantity;
+                self.cumulative_buy_volume += trade.quantity;
+            }
+            Side::Ask => {
+                self.window_sell_volume += trade.quantity;
+                self.cumulative_sell_volume += trade.quantity;
+            }
+        }
+
+        self.trade_count += 1;
+        self.window.push_back((trade.timestamp, trade.aggressor_side.clone(), trade.quantity));
+    }
+
+    pub fn expire(&mut self, now: DateTime<Utc>) {
+        let cutoff = now - Duration::seconds(FLOW_WINDOW_SECS as i64);
+
+        while let Some((timestamp, _, _)) = self.window.front() {
+            if *timestamp >= cutoff {
+                break;
+            }
+
+            if let Some((_, side, quantity)) = self.window.pop_front() {
+                match side {
+                    Side::Bid => self.window_buy_volume -= quantity,
+                    Side::Ask => self.window_sell_volume -= quantity,
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> AggressorFlowStats {
+        AggressorFlowStats {
+            window_secs: FLOW_WINDOW_SECS,
+            buy_volume: self.window_buy_volume,
+            sell_volume: self.window_sell_volume,
+            delta: self.window_buy_volume.to_f64() - self.window_sell_volume.to_f64(),
+            cumulative_buy_volume: self.cumulative_buy_volume,
+            cumulative_sell_volume: self.cumulative_sell_volume,
+            cvd: self.cumulative_buy_volume.to_f64() - self.cumulative_sell_volume.to_f64(),
+            trade_count: self.trade_count,
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use chrono::{DateTime, Duration, Utc};
+use clap::Args;
+
+use crate::message::{FutureContract, PairKind, TradingStatus};
+use crate::option_chain::parse_expiry;
+use crate::pairs::SyntheticPair;
+
+const SECS_PER_YEAR: f64 = 365.0 * 86400.0;
+
+// Books of dated futures open around this price before carry
+const SPOT_PRICE: f64 = 100.0;
+
+// Dated futures listed as simulated books, halted at expiry and delisted after a delay
+#[derive(Debug, Clone, Args)]
+pub struct FuturesOptions {
+    /// Dated futures to list, repeatable: ROOT:EXPIRIES[:CONTRACT_SIZE] with comma-separated
+    /// expiries (YYYY-MM-DD, or days from startup such as 30d, settling 08:00 UTC)
+    #[arg(long = "futures", value_name = "DEFINITION")]
+    pub futures: Vec<String>,
+
+    /// Annualized carry: each contract's book opens at 100 × (1 + carry × years to expiry)
+    #[arg(long, value_name = "RATE", default_value_t = 0.05, allow_hyphen_values = true)]
+    pub futures_carry: f64,
+
+    /// Seconds an expired contract stays halted before it is delisted
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub futures_delist_delay_secs: u64,
+
+    /// On each delisting, list a new contract this many days after the last expiry
+    #[arg(long, value_name = "DAYS")]
+    pub futures_roll_days: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub enum CurveEvent {
+    Halted(FutureContract),
+    Delisted(FutureContract),
+    Listed(FutureContract),
+}
+
+// Contracts of one root by expiry, delisted ones included. Consecutive contracts still
+// listed are quoted as calendar spreads, front minus back.
+#[derive(Debug)]
+pub struct FuturesCurve {
+    pub root: String,
+    contract_size: f64,
+    carry: f64,
+    delist_delay: Duration,
+    roll: Option<Duration>,
+    contracts: Vec<FutureContract>,
+}
+
+impl FuturesCurve {
+    // "BTCUSD:2026-12-25,2027-03-26:0.1" lists BTCUSD-20261225 and BTCUSD-20270326 at a tenth
+    // of the underlying per contract
+    pub fn parse(definition: &str, options: &FuturesOptions, now: DateTime<Utc>) -> Result<Self, String> {
+        let definition = definition.trim();
+        let invalid = |reason: &str| format!("Invalid futures '{}': {}", definition, reason);
+
+        let mut parts = definition.split(':');
+        let (Some(root), Some(expiries), contract_size, None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid("expected ROOT:EXPIRIES[:CONTRACT_SIZE]"));
+        };
+        let root = root.trim();
+        if root.is_empty() {
+            return Err(invalid("the root must be named"));
+        }
+
+        let contract_size = match contract_size.map(|size| size.trim().parse::<f64>()) {
+            None => 1.0,
+            Some(Ok(size)) if size.is_finite() && size > 0.0 => size,
+            Some(_) => return Err(invalid("invalid contract size")),
+        };
+        let mut expiries = expiries
+            .split(',')
+            .map(|expiry| parse_expiry(expiry.trim(), now).map_err(|reason| invalid(&reason)))
+            .collect::<Result<Vec<_>, _>>()?;
+        expiries.sort();
+        expiries.dedup();
+
+        let mut curve = Self {
+            root: root.to_string(),
+            contract_size,
+            carry: options.futures_carry,
+            delist_delay: Duration::seconds(options.futures_delist_delay_secs as i64),
+            roll: options.futures_roll_days.map(|days| Duration::days(days.max(1) as i64)),
+            contracts: Vec::new(),
+        };
+        for expiry in expiries {
+            curve.list(expiry);
+        }
+
+        Ok(curve)
+    }
+
+    fn list(&mut self, expiry: DateTime<Utc>) -> FutureContract {
+        let contract = FutureContract {
+            symbol: format!("{}-{}", self.root, expiry.format("%Y%m%d")),
+            root: self.root.clone(),
+            expiry,
+            contract_size: self.contract_size,
+            status: TradingStatus::Trading,
+            mid_price: None,
+            settlement_price: None,
+        };
+        self.contracts.push(contract.clone());
+        contract
+    }
+
+    pub fn contracts(&self) -> &[FutureContract] {
+        &self.contracts
+    }
+
+    pub fn contract(&self, symbol: &str) -> Option<&FutureContract> {
+        self.contracts.iter().find(|contract| contract.symbol == symbol)
+    }
+
+    // Price a new contract's book opens at
+    pub fn opening_price(&self, contract: &FutureContract, now: DateTime<Utc>) -> f64 {
+        let years = (contract.expiry - now).num_seconds().max(0) as f64 / SECS_PER_YEAR;
+        ((SPOT_PRICE * (1.0 + self.carry * years)) * 100.0).round() / 100.0
+    }
+
+    // Calendar spread between each listed contract and the next, named FRONT-BACKEXPIRY
+    pub fn calendar_spreads(&self) -> Vec<SyntheticPair> {
+        let listed: Vec<&FutureContract> = self
+            .contracts
+            .iter()
+            .filter(|contract| contract.status != TradingStatus::Delisted)
+            .collect();
+
+        listed
+            .windows(2)
+            .map(|legs| SyntheticPair {
+                name: format!("{}-{}", legs[0].symbol, legs[1].expiry.format("%Y%m%d")),
+                kind: PairKind::Spread,
+                base: legs[0].symbol.clone(),
+                quote: legs[1].symbol.clone(),
+            })
+            .collect()
+    }
+
+    // Halts contracts past expiry at the settlement price `mid_price` gives, delists those
+    // halted for longer than the delay and lists their replacements when rolling
+    pub fn expire(&mut self, now: DateTime<Utc>, mid_price: impl Fn(&str) -> Option<f64>) -> Vec<CurveEvent> {
+        let mut events = Vec::new();
+        let mut delisted = 0;
+
+        for contract in &mut self.contracts {
+            match contract.status {
+                TradingStatus::Trading if now >= contract.expiry => {
+                    contract.status = TradingStatus::Halted;
+                    contract.settlement_price = mid_price(&contract.symbol);
+                    events.push(CurveEvent::Halted(contract.clone()));
+                }
+                TradingStatus::Halted if now >= contract.expiry + self.delist_delay => {
+                    contract.status = TradingStatus::Delisted;
+                    events.push(CurveEvent::Delisted(contract.clone()));
+                    delisted += 1;
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(roll) = self.roll {
+            for _ in 0..delisted {
+                let last_expiry = self.contracts.iter().map(|contract| contract.expiry).max().unwrap_or(now);
+                let contract = self.list(last_expiry.max(now) + roll);
+                events.push(CurveEvent::Listed(contract));
+            }
+        }
+
+        events
+    }
+}
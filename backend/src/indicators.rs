@@ -0,0 +1,108 @@
+use crate::candles::{CANDLE_INTERVALS_SECS, MAX_CANDLES};
+use crate::message::{Indicator, IndicatorSpec, IndicatorValue};
+
+pub fn validate_indicator_spec(spec: &IndicatorSpec) -> Result<(), String> {
+    if !CANDLE_INTERVALS_SECS.contains(&spec.interval_secs) {
+        return Err(format!(
+            "Unsupported indicator interval {}s, expected one of {:?}",
+            spec.interval_secs, CANDLE_INTERVALS_SECS
+        ));
+    }
+
+    if spec.indicators.is_empty() {
+        return Err("Indicator spec must select at least one indicator".to_string());
+    }
+
+    for indicator in &spec.indicators {
+        let period = match indicator {
+            Indicator::EMA { period } | Indicator::RSI { period } => *period,
+            Indicator::Bollinger { period, std_dev } => {
+                if !std_dev.is_finite() || *std_dev <= 0.0 {
+                    return Err(format!("Bollinger std_dev must be positive, got {}", std_dev));
+                }
+                *period
+            }
+        };
+
+        if period == 0 || period as usize > MAX_CANDLES {
+            return Err(format!("Indicator period must be between 1 and {}, got {}", MAX_CANDLES, period));
+        }
+    }
+
+    Ok(())
+}
+
+pub fn compute_indicators(spec: &IndicatorSpec, closes: &[f64]) -> Vec<IndicatorValue> {
+    spec.indicators
+        .iter()
+        .map(|indicator| match indicator {
+            Indicator::EMA { period } => IndicatorValue::EMA {
+                period: *period,
+                value: ema(closes, *period as usize),
+            },
+            Indicator::RSI { period } => IndicatorValue::RSI {
+                period: *period,
+                value: rsi(closes, *period as usize),
+            },
+            Indicator::Bollinger { period, std_dev } => {
+                let bands = bollinger(closes, *period as usize, *std_dev);
+                IndicatorValue::Bollinger {
+                    period: *period,
+                    std_dev: *std_dev,
+                    middle: bands.map(|(middle, _, _)| middle),
+                    upper: bands.map(|(_, upper, _)| upper),
+                    lower: bands.map(|(_, _, lower)| lower),
+                }
+            }
+        })
+        .collect()
+}
+
+// Exponential moving average seeded with the SMA of the first `period` closes
+fn ema(closes: &[f64], period: usize) -> Option<f64> {
+    if period == 0 || closes.len() < period {
+        return None;
+    }
+
+    let alpha = 2.0 / (period as f64 + 1.0);
+    let seed = closes[..period].iter().sum::<f64>() / period as f64;
+
+    Some(closes[period..].iter().fold(seed, |ema, close| alpha * close + (1.0 - alpha) * ema))
+}
+
+// Wilder's relative strength index
+fn rsi(closes: &[f64], period: usize) -> Option<f64> {
+    if period == 0 || closes.len() <= period {
+        return None;
+    }
+
+    let changes: Vec<f64> = closes.windows(2).map(|w| w[1] - w[0]).collect();
+
+    let mut avg_gain = changes[..period].iter().filter(|c| **c > 0.0).sum::<f64>() / period as f64;
+    let mut avg_loss = -changes[..period].iter().filter(|c| **c < 0.0).sum::<f64>() / period as f64;
+
+    for change in &changes[period..] {
+        avg_gain = (avg_gain * (period as f64 - 1.0) + change.max(0.0)) / period as f64;
+        avg_loss = (avg_loss * (period as f64 - 1.0) + (-change).max(0.0)) / period as f64;
+    }
+
+    if avg_loss == 0.0 {
+        return Some(if avg_gain == 0.0 { 50.0 } else { 100.0 });
+    }
+
+    Some(100.0 - 100.0 / (1.0 + avg_gain / avg_loss))
+}
+
+// Bollinger bands over the last `period` closes: (middle, upper, lower)
+fn bollinger(closes: &[f64], period: usize, std_dev: f64) -> Option<(f64, f64, f64)> {
+    if period == 0 || closes.len() < period {
+        return None;
+    }
+
+    let window = &closes[closes.len() - period..];
+    let mean = window.iter().sum::<f64>() / period as f64;
+    let variance = window.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / period as f64;
+    let width = std_dev * variance.sqrt();
+
+    Some((mean, mean + width, mean - width))
+}
@@ -0,0 +1,353 @@
+use std::fmt;
+use std::sync::Arc;
+use axum::{
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, Path},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::{debug, info};
+
+use crate::message::{ActivityType, OrderActivity, OrderId, OutOfBandKind, Quantity, SequenceMapping, Side};
+use crate::order_book::{Order, OrderBook};
+use crate::stream_manager::StreamManager;
+use crate::tenants::{TenantRegistry, TenantStreams};
+
+// Order event pushed by an external feed, e.g. a bridge decoding an exchange's
+// order-by-order protocol. Order ids are the feed's own, as decimal strings, and
+// `venue_sequence` the venue's sequence number of the event, when the feed has one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum IngestEvent {
+    Add {
+        order_id: OrderId,
+        side: Side,
+        price: f64,
+        quantity: Quantity,
+        venue_sequence: Option<u64>,
+    },
+    // A new price moves the order to the back of its new level; zero quantity cancels it
+    Modify {
+        order_id: OrderId,
+        quantity: Quantity,
+        price: Option<f64>,
+        venue_sequence: Option<u64>,
+    },
+    Cancel {
+        order_id: OrderId,
+        venue_sequence: Option<u64>,
+    },
+    // Execution against the named resting order, or without one an aggressive order
+    // on `side` that sweeps the book in price-time priority
+    Trade {
+        order_id: Option<OrderId>,
+        side: Option<Side>,
+        quantity: Quantity,
+        venue_sequence: Option<u64>,
+    },
+}
+
+impl IngestEvent {
+    pub fn venue_sequence(&self) -> Option<u64> {
+        match self {
+            IngestEvent::Add { venue_sequence, .. }
+            | IngestEvent::Modify { venue_sequence, .. }
+            | IngestEvent::Cancel { venue_sequence, .. }
+            | IngestEvent::Trade { venue_sequence, .. } => *venue_sequence,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestRejection {
+    pub index: usize,
+    pub error: String,
+}
+
+// Outcome of one batch; rejected events are skipped and the rest still applied
+#[derive(Debug, Clone, Serialize)]
+pub struct IngestReport {
+    pub sequence: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub venue_sequence: Option<u64>, // Of the last applied event that carried one
+    pub applied: usize,
+    pub rejected: Vec<IngestRejection>,
+}
+
+#[derive(Debug, Clone)]
+pub enum IngestError {
+    // The symbol has a simulated book or is a synthetic pair
+    NotExternal(String),
+    SymbolLimit(String),
+    InvalidEvents(String),
+}
+
+impl fmt::Display for IngestError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IngestError::NotExternal(symbol) => write!(f, "{} is published by the server and cannot be ingested", symbol),
+            IngestError::SymbolLimit(message) | IngestError::InvalidEvents(message) => f.write_str(message),
+        }
+    }
+}
+
+impl IntoResponse for IngestError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            IngestError::NotExternal(_) => StatusCode::CONFLICT,
+            IngestError::SymbolLimit(_) => StatusCode::SERVICE_UNAVAILABLE,
+            IngestError::InvalidEvents(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+// A request or frame holds one event object or an array of them
+pub fn parse_events(body: &[u8]) -> Result<Vec<IngestEvent>, IngestError> {
+    let invalid = |e: serde_json::Error| IngestError::InvalidEvents(format!("Invalid events: {}", e));
+
+    match serde_json::from_slice(body).map_err(invalid)? {
+        Value::Array(events) => events
+            .into_iter()
+            .map(serde_json::from_value)
+            .collect::<Result<_, _>>()
+            .map_err(invalid),
+        event => Ok(vec![serde_json::from_value(event).map_err(invalid)?]),
+    }
+}
+
+// Applies a batch to the book, returning the report, the resulting activities and the
+// book sequence each applied event with a venue sequence left the book at
+pub fn apply_events(book: &mut OrderBook, events: &[IngestEvent]) -> (IngestReport, Vec<OrderActivity>, Vec<SequenceMapping>) {
+    let mut activities = Vec::new();
+    let mut mappings = Vec::new();
+    let mut rejected = Vec::new();
+
+    for (index, event) in events.iter().enumerate() {
+        match apply_event(book, event) {
+            Ok(mut event_activities) => {
+                if let Some(venue_sequence) = event.venue_sequence() {
+                    for activity in &mut event_activities {
+                        activity.venue_sequence = Some(venue_sequence);
+                    }
+                    book.set_venue_sequence(venue_sequence);
+                    mappings.push(SequenceMapping { sequence: book.get_sequence(), venue_sequence });
+                }
+                activities.extend(event_activities);
+            }
+            Err(error) => rejected.push(IngestRejection { index, error }),
+        }
+    }
+
+    let report = IngestReport {
+        sequence: book.get_sequence(),
+        venue_sequence: book.venue_sequence(),
+        applied: events.len() - rejected.len(),
+        rejected,
+    };
+    (report, activities, mappings)
+}
+
+fn apply_event(book: &mut OrderBook, event: &IngestEvent) -> Result<Vec<OrderActivity>, String> {
+    match event {
+        IngestEvent::Add { order_id, side, price, quantity, .. } => {
+            if book.get_order(*order_id).is_some() {
+                return Err(format!("Order {} already exists", order_id));
+            }
+            check_order(book, *price, *quantity)?;
+            check_band(book, *price, OutOfBandKind::Add, Some(*order_id), Some(side.clone()), *quantity, event.venue_sequence())?;
+            add(book, *order_id, side.clone(), *price, *quantity).map(|activity| vec![activity])
+        }
+        IngestEvent::Modify { order_id, quantity, price, .. } => {
+            let Some(order) = book.get_order(*order_id) else {
+                return Err(format!("Unknown order {}", order_id));
+            };
+
+            match price {
+                Some(price) if *price != order.price && !quantity.is_zero() => {
+                    // Checked first so a rejected modify leaves the order resting
+                    let side = order.side.clone();
+                    check_order(book, *price, *quantity)?;
+                    check_band(book, *price, OutOfBandKind::Modify, Some(*order_id), Some(side.clone()), *quantity, event.venue_sequence())?;
+                    book.remove_order(*order_id);
+                    let cancel = activity(book, ActivityType::Cancel, *order_id, None, None, None);
+                    let add = add(book, *order_id, side, *price, *quantity)?;
+                    Ok(vec![cancel, add])
+                }
+                _ => {
+                    check_quantity(book, *quantity)?;
+                    book.update_order(*order_id, *quantity);
+                    let activity = match quantity.is_zero() {
+                        true => activity(book, ActivityType::Cancel, *order_id, None, None, None),
+                        false => activity(book, ActivityType::Update, *order_id, None, Some(*quantity), None),
+                    };
+                    Ok(vec![activity])
+                }
+            }
+        }
+        IngestEvent::Cancel { order_id, .. } => {
+            if !book.remove_order(*order_id) {
+                return Err(format!("Unknown order {}", order_id));
+            }
+            Ok(vec![activity(book, ActivityType::Cancel, *order_id, None, None, None)])
+        }
+        IngestEvent::Trade { order_id, side, quantity, .. } => {
+            if quantity.is_zero() {
+                return Err("Trade quantity must be positive".to_string());
+            }
+            check_quantity(book, *quantity)?;
+
+            match (order_id, side) {
+                (Some(order_id), _) => {
+                    let Some(price) = book.get_order(*order_id).map(|order| order.price) else {
+                        return Err(format!("Unknown order {}", order_id));
+                    };
+                    check_band(book, price, OutOfBandKind::Trade, Some(*order_id), side.clone(), *quantity, event.venue_sequence())?;
+                    book
+                        .execute_against_order(*order_id, *quantity)
+                        .map(|fill| vec![fill])
+                        .ok_or_else(|| format!("Unknown order {}", order_id))
+                }
+                (None, Some(side)) => {
+                    if let Some(price) = book.sweep_price(side, *quantity) {
+                        check_band(book, price, OutOfBandKind::Trade, None, Some(side.clone()), *quantity, event.venue_sequence())?;
+                    }
+                    let fills = book.execute_market_order(side.clone(), *quantity);
+                    if fills.is_empty() {
+                        return Err(format!("No resting {:?} orders to trade against", side));
+                    }
+                    Ok(fills)
+                }
+                (None, None) => Err("Trade needs an order_id or an aggressor side".to_string()),
+            }
+        }
+    }
+}
+
+fn check_order(book: &OrderBook, price: f64, quantity: Quantity) -> Result<(), String> {
+    if !price.is_finite() || price <= 0.0 {
+        return Err(format!("Invalid price {}", price));
+    }
+    if quantity.is_zero() {
+        return Err("Order quantity must be positive".to_string());
+    }
+    check_quantity(book, quantity)
+}
+
+// An event priced outside the book's band is held back from it and rejected
+fn check_band(
+    book: &mut OrderBook,
+    price: f64,
+    kind: OutOfBandKind,
+    order_id: Option<OrderId>,
+    side: Option<Side>,
+    quantity: Quantity,
+    venue_sequence: Option<u64>,
+) -> Result<(), String> {
+    let Err(breach) = book.check_price_band(price) else {
+        return Ok(());
+    };
+    let event = breach.event(kind, &book.symbol, order_id, side, quantity, venue_sequence);
+    book.hold_back(event);
+    Err(breach.to_string())
+}
+
+// Sizes must be whole lots of the book's quantity decimals, and no larger than any one
+// order may be
+fn check_quantity(book: &OrderBook, quantity: Quantity) -> Result<(), String> {
+    if quantity > Quantity::MAX_ORDER {
+        return Err(format!("Quantity {} is larger than {}", quantity, Quantity::MAX_ORDER));
+    }
+    if !quantity.has_decimals(book.quantity_decimals()) {
+        return Err(format!("Quantity {} has more than {} decimals", quantity, book.quantity_decimals()));
+    }
+    Ok(())
+}
+
+fn add(book: &mut OrderBook, order_id: OrderId, side: Side, price: f64, quantity: Quantity) -> Result<OrderActivity, String> {
+    check_order(book, price, quantity)?;
+    if !book.add_order(Order::new(order_id, price, quantity, side.clone())) {
+        return Err("Order book is full".to_string());
+    }
+
+    Ok(activity(book, ActivityType::Add, order_id, Some(price), Some(quantity), Some(side)))
+}
+
+fn activity(
+    book: &OrderBook,
+    activity_type: ActivityType,
+    order_id: OrderId,
+    price: Option<f64>,
+    quantity: Option<Quantity>,
+    side: Option<Side>,
+) -> OrderActivity {
+    OrderActivity {
+        activity_type,
+        order_id,
+        symbol: book.symbol.clone(),
+        price,
+        quantity,
+        side,
+        timestamp: Utc::now(),
+        actor: None,
+        venue_sequence: None,
+    }
+}
+
+// POST /ingest/:symbol applies one batch; GET upgrades to a WebSocket on which every
+// text frame is a batch, answered with its report
+pub fn ingest_router(tenants: Arc<TenantRegistry>) -> Router {
+    Router::new()
+        .route("/ingest/:symbol", post(ingest_handler).get(ingest_socket_handler))
+        .with_state(tenants)
+}
+
+async fn ingest_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Path(symbol): Path<String>,
+    body: axum::body::Bytes,
+) -> Result<Json<IngestReport>, IngestError> {
+    let events = parse_events(&body)?;
+    Ok(Json(stream_manager.ingest(&symbol, &events).await?))
+}
+
+async fn ingest_socket_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    Path(symbol): Path<String>,
+    upgrade: WebSocketUpgrade,
+) -> Response {
+    upgrade.on_upgrade(move |socket| ingest_socket(socket, stream_manager, symbol))
+}
+
+async fn ingest_socket(mut socket: WebSocket, stream_manager: Arc<StreamManager>, symbol: String) {
+    info!("Ingest feed connected for {}", symbol);
+
+    while let Some(Ok(message)) = socket.recv().await {
+        let body = match message {
+            Message::Text(text) => text.into_bytes(),
+            Message::Binary(bytes) => bytes,
+            Message::Close(_) => break,
+            _ => continue,
+        };
+
+        let reply = match parse_events(&body) {
+            Ok(events) => stream_manager.ingest(&symbol, &events).await,
+            Err(e) => Err(e),
+        };
+        let reply = match reply {
+            Ok(report) => serde_json::to_value(report).unwrap_or_default(),
+            Err(e) => serde_json::json!({ "error": e.to_string() }),
+        };
+
+        if socket.send(Message::Text(reply.to_string())).await.is_err() {
+            debug!("Ingest feed for {} went away", symbol);
+            break;
+        }
+    }
+
+    info!("Ingest feed disconnected for {}", symbol);
+}
@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use std::time::Duration;
+use clap::Args;
+use dashmap::DashMap;
+use serde::Serialize;
+use tokio::time::{interval, MissedTickBehavior};
+use tracing::{info, warn};
+
+use crate::event_bus::{EventBus, MarketEvent};
+use crate::message::{OrderId, Quantity, Side};
+use crate::order_book::PublishedBook;
+
+// Background self-check of every book's invariants, for catching bugs in the book
+// itself or in what an ingest feed made of it
+#[derive(Debug, Clone, Default, Args)]
+pub struct IntegrityOptions {
+    /// Check every book this often: each order a price level queues is resting at that
+    /// price and side, every resting order is queued, and level totals match their orders
+    /// (default: no checks)
+    #[arg(long, value_name = "SECS")]
+    pub integrity_check_secs: Option<u64>,
+
+    /// Also report books whose best bid is at or above their best ask, for feeds matched
+    /// upstream; simulated books may cross
+    #[arg(long)]
+    pub integrity_uncrossed: bool,
+
+    /// Rebuild the price levels of a book failing a check from its resting orders
+    #[arg(long)]
+    pub integrity_repair: bool,
+}
+
+impl IntegrityOptions {
+    pub fn checks(&self) -> Result<Option<IntegrityChecks>, String> {
+        let Some(secs) = self.integrity_check_secs else {
+            if self.integrity_uncrossed || self.integrity_repair {
+                return Err("--integrity-uncrossed and --integrity-repair need --integrity-check-secs".to_string());
+            }
+            return Ok(None);
+        };
+        if secs == 0 {
+            return Err("--integrity-check-secs must be at least 1".to_string());
+        }
+        Ok(Some(IntegrityChecks {
+            every: Duration::from_secs(secs),
+            uncrossed: self.integrity_uncrossed,
+            repair: self.integrity_repair,
+        }))
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct IntegrityChecks {
+    pub every: Duration,
+    pub uncrossed: bool,
+    pub repair: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "violation", rename_all = "snake_case")]
+pub enum IntegrityViolation {
+    // The level's queue links a slot without an order, or loops
+    BrokenQueue {
+        side: Side,
+        price: f64,
+    },
+    // A queued order the order map holds elsewhere, or that rests at another price or side
+    MisplacedOrder {
+        side: Side,
+        price: f64,
+        order_id: OrderId,
+    },
+    // The level's order count and total quantity, next to those of the orders it queues
+    LevelTotals {
+        side: Side,
+        price: f64,
+        order_count: usize,
+        quantity: Quantity,
+        queued_count: usize,
+        queued_quantity: Quantity,
+    },
+    // Resting orders no level queues
+    UnqueuedOrders {
+        count: usize,
+    },
+    Crossed {
+        best_bid: f64,
+        best_ask: f64,
+    },
+}
+
+impl IntegrityViolation {
+    // Whether rebuilding the price levels fixes it
+    pub fn is_repairable(&self) -> bool {
+        !matches!(self, IntegrityViolation::Crossed { .. })
+    }
+}
+
+impl fmt::Display for IntegrityViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IntegrityViolation::BrokenQueue { side, price } => write!(f, "{:?} level {} has a broken queue", side, price),
+            IntegrityViolation::MisplacedOrder { side, price, order_id } => {
+                write!(f, "{:?} level {} queues order {} which does not rest there", side, price, order_id)
+            }
+            IntegrityViolation::LevelTotals { side, price, order_count, quantity, queued_count, queued_quantity } => write!(
+                f,
+                "{:?} level {} totals {} orders of {}, its queue {} orders of {}",
+                side, price, order_count, quantity, queued_count, queued_quantity
+            ),
+            IntegrityViolation::UnqueuedOrders { count } => write!(f, "{} resting orders are in no level", count),
+            IntegrityViolation::Crossed { best_bid, best_ask } => write!(f, "best bid {} is not below best ask {}", best_bid, best_ask),
+        }
+    }
+}
+
+// Checks every book each interval against its published snapshot, so the simulation is
+// only held up by books being repaired. A book that keeps failing unrepaired is reported
+// once, and again after it passed a check.
+pub async fn run_integrity_checks(order_books: Arc<DashMap<String, Arc<PublishedBook>>>, events: EventBus, checks: IntegrityChecks) {
+    let mut check = interval(checks.every);
+    check.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    let mut failing: HashSet<String> = HashSet::new();
+    loop {
+        check.tick().await;
+        failing.retain(|symbol| order_books.contains_key(symbol));
+
+        let books: Vec<(String, Arc<PublishedBook>)> =
+            order_books.iter().map(|entry| (entry.key().clone(), Arc::clone(entry.value()))).collect();
+        for (symbol, book) in books {
+            let violations = book.snapshot().check_integrity(checks.uncrossed);
+            if violations.is_empty() {
+                if failing.remove(&symbol) {
+                    info!("Order book {} passes its integrity check again", symbol);
+                }
+                continue;
+            }
+
+            let repaired = checks.repair && violations.iter().any(IntegrityViolation::is_repairable) && repair(&events, &book, checks.uncrossed);
+            if repaired {
+                failing.remove(&symbol);
+            } else if !failing.insert(symbol.clone()) {
+                continue;
+            }
+
+            let details: Vec<String> = violations.iter().map(ToString::to_string).collect();
+            warn!("Order book {} failed its integrity check{}: {}", symbol, if repaired { " and was rebuilt" } else { "" }, details.join("; "));
+            events.publish(MarketEvent::IntegrityViolation { symbol: Arc::from(symbol.as_str()), violations, repaired });
+        }
+    }
+}
+
+// Rebuilds the book under its writer lock if it still fails there, and republishes it
+fn repair(events: &EventBus, book: &PublishedBook, uncrossed: bool) -> bool {
+    let repaired = book.update(|order_book| {
+        if !order_book.check_integrity(uncrossed).iter().any(IntegrityViolation::is_repairable) {
+            return None;
+        }
+        order_book.rebuild_levels();
+        let (_, mid_price, _) = order_book.get_spread_info();
+        Some((Arc::clone(&order_book.symbol), order_book.get_sequence(), order_book.get_event_timestamp(), mid_price))
+    });
+    let Some((symbol, sequence, event_ts, mid_price)) = repaired else {
+        return false;
+    };
+
+    events.publish(MarketEvent::BookUpdated { symbol, sequence, previous_sequence: None, event_ts, mid_price });
+    true
+}
@@ -0,0 +1,73 @@
+use crate::message::{LadderRow, MarketDataUpdate, Quantity, Side};
+use crate::order_book::{OrderBook, SIMULATED_TICK_SIZE};
+
+pub const MAX_LADDER_ROWS: u32 = 200;
+
+pub fn validate_ladder(rows: u32, tick_group: u32) -> Result<(), String> {
+    if rows == 0 || rows > MAX_LADDER_ROWS {
+        return Err(format!("Ladder rows must be between 1 and {}, got {}", MAX_LADDER_ROWS, rows));
+    }
+    if tick_group == 0 {
+        return Err("Ladder tick_group must be at least 1".to_string());
+    }
+    Ok(())
+}
+
+// Exactly `rows` rows of `tick_group` ticks each, the row holding the center price in the
+// middle (one row above it for an even count). Prices are worked in whole ticks so row
+// prices stay on the grid instead of accumulating float error.
+pub fn ladder_update(order_book: &OrderBook, rows: u32, tick_group: u32) -> MarketDataUpdate {
+    let group = tick_group as i64;
+    let row_size = from_ticks(group);
+    let center_price = match order_book.get_best_bid_ask() {
+        (Some(bid), Some(ask)) => Some((bid + ask) / 2.0),
+        (bid, ask) => bid.or(ask),
+    };
+    let Some(center) = center_price else {
+        return MarketDataUpdate::Ladder { center_price, row_size, rows: Vec::new() };
+    };
+
+    let top_row = ((center / SIMULATED_TICK_SIZE).floor() as i64).div_euclid(group) + rows as i64 / 2;
+    let bottom_row = top_row - rows as i64 + 1;
+
+    let mut ladder: Vec<LadderRow> = (bottom_row..=top_row)
+        .rev()
+        .map(|row| LadderRow {
+            price: from_ticks(row * group),
+            bid_quantity: Quantity::ZERO,
+            bid_orders: 0,
+            ask_quantity: Quantity::ZERO,
+            ask_orders: 0,
+        })
+        .collect();
+
+    // Half a tick of slack either side catches level prices a float error off the grid
+    let low = from_ticks(bottom_row * group) - SIMULATED_TICK_SIZE / 2.0;
+    let high = from_ticks((top_row + 1) * group) - SIMULATED_TICK_SIZE / 2.0;
+    for side in [Side::Bid, Side::Ask] {
+        for (price, quantity, orders) in order_book.levels_between(&side, low, high) {
+            let row = ((price / SIMULATED_TICK_SIZE).round() as i64).div_euclid(group);
+            if row < bottom_row || row > top_row {
+                continue;
+            }
+
+            let entry = &mut ladder[(top_row - row) as usize];
+            match side {
+                Side::Bid => {
+                    entry.bid_quantity += quantity;
+                    entry.bid_orders += orders;
+                }
+                Side::Ask => {
+                    entry.ask_quantity += quantity;
+                    entry.ask_orders += orders;
+                }
+            }
+        }
+    }
+
+    MarketDataUpdate::Ladder { center_price, row_size, rows: ladder }
+}
+
+fn from_ticks(ticks: i64) -> f64 {
+    ticks as f64 / (1.0 / SIMULATED_TICK_SIZE).round()
+}
@@ -0,0 +1,50 @@
+use std::sync::Mutex;
+
+use crate::book_diff::BookDiff;
+use crate::message::MBPLevel;
+
+// The sequence of the levels an MBPDelta stream was last sent, which its next delta must
+// start from. Everything the stream is sent goes out under the lock, so messages leave in
+// the order their sequences are recorded.
+#[derive(Debug, Default)]
+pub struct LevelBase {
+    sequence: Mutex<Option<u64>>,
+}
+
+impl LevelBase {
+    // Sends the stream's first levels, taken at `sequence`, unless an update already did
+    pub(crate) fn prime(&self, sequence: u64, send: impl FnOnce() -> Result<(), ()>) -> Result<(), ()> {
+        let mut base = self.sequence.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if base.is_some() {
+            return Ok(());
+        }
+        send()?;
+        *base = Some(sequence);
+        Ok(())
+    }
+
+    // `send` is told whether the stream holds the levels at `previous`, so a delta from
+    // them applies, or needs the whole view; the view's `sequence` is recorded once sent
+    pub fn advance(&self, previous: Option<u64>, sequence: u64, send: impl FnOnce(bool) -> bool) {
+        let mut base = self.sequence.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let holds_previous = previous.is_some() && *base == previous;
+        if send(holds_previous) {
+            *base = Some(sequence);
+        }
+    }
+}
+
+// An MBP view of one depth as MBPDelta streams of that depth were last sent it
+#[derive(Debug, Clone)]
+pub struct LevelView {
+    pub epoch: u64,
+    pub sequence: u64,
+    pub bids: Vec<MBPLevel>,
+    pub asks: Vec<MBPLevel>,
+}
+
+impl LevelView {
+    pub fn diff(&self, bids: &[MBPLevel], asks: &[MBPLevel]) -> BookDiff {
+        BookDiff::between(&self.bids, &self.asks, bids, asks)
+    }
+}
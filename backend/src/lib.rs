@@ -1,9 +1,214 @@
-pub mod order_book;
+// Protocol message types and book diffs, the only modules built without the `server`
+// feature
+pub mod book_diff;
 pub mod message;
+
+#[cfg(feature = "alloc-audit")]
+pub mod alloc_audit;
+#[cfg(feature = "server")]
+pub mod access;
+#[cfg(feature = "server")]
+pub mod activity_stream;
+#[cfg(feature = "server")]
+pub mod admin;
+#[cfg(feature = "server")]
+pub mod audit;
+#[cfg(feature = "server")]
+pub mod auth;
+#[cfg(feature = "server")]
+pub mod candle_store;
+#[cfg(feature = "server")]
+pub mod candles;
+#[cfg(feature = "server")]
+pub mod clock;
+#[cfg(feature = "server")]
+pub mod compression;
+#[cfg(feature = "server")]
+pub mod conformance;
+#[cfg(feature = "server")]
+pub mod dead_letters;
+#[cfg(feature = "server")]
+pub mod epochs;
+#[cfg(feature = "server")]
+pub mod event_bus;
+#[cfg(feature = "server")]
+pub mod feed_monitor;
+#[cfg(feature = "server")]
+pub mod filters;
+#[cfg(feature = "server")]
+pub mod flow;
+#[cfg(feature = "server")]
+pub mod futures;
+#[cfg(feature = "server")]
+pub mod history;
+#[cfg(feature = "server")]
+pub mod indicators;
+#[cfg(feature = "server")]
+pub mod ingest;
+#[cfg(feature = "server")]
+pub mod integrity;
+#[cfg(feature = "server")]
+pub mod ladder;
+#[cfg(feature = "server")]
+pub mod level_deltas;
+#[cfg(feature = "server")]
+pub mod limits;
+#[cfg(feature = "server")]
+pub mod liquidity_age;
+#[cfg(feature = "server")]
+pub mod listener;
+#[cfg(feature = "server")]
+pub mod load_shedding;
+#[cfg(feature = "server")]
+pub mod metrics;
+#[cfg(feature = "server")]
+pub mod notifications;
+#[cfg(feature = "server")]
+pub mod option_chain;
+#[cfg(feature = "server")]
+pub mod order_book;
+#[cfg(feature = "server")]
+pub mod order_stats;
+#[cfg(feature = "server")]
+pub mod outbound_queue;
+#[cfg(feature = "server")]
+pub mod paper_trading;
+#[cfg(feature = "server")]
+pub mod pairs;
+#[cfg(feature = "server")]
+pub mod perpetual;
+#[cfg(feature = "server")]
+pub mod popularity;
+#[cfg(feature = "server")]
+pub mod price_bands;
+#[cfg(feature = "server")]
+pub mod profiles;
+#[cfg(feature = "server")]
+pub mod quota;
+#[cfg(feature = "server")]
+pub mod rate_limit;
+#[cfg(feature = "server")]
+pub mod reconcile;
+#[cfg(feature = "server")]
+pub mod reference;
+#[cfg(feature = "server")]
+pub mod runtime;
+#[cfg(feature = "server")]
+pub mod sampling;
+#[cfg(feature = "server")]
+pub mod sandbox;
+#[cfg(feature = "server")]
+pub mod session;
+#[cfg(feature = "server")]
+pub mod snapshot_cache;
+#[cfg(feature = "server")]
+pub mod snapshot_schedule;
+#[cfg(feature = "server")]
 pub mod stream_manager;
+#[cfg(feature = "server")]
+pub mod stream_stats;
+#[cfg(feature = "server")]
+pub mod stream_validation;
+#[cfg(feature = "server")]
+pub mod subscriptions;
+#[cfg(feature = "server")]
+pub mod summary;
+#[cfg(feature = "server")]
+pub mod supervisor;
+#[cfg(feature = "server")]
+pub mod symbol_config;
+#[cfg(feature = "server")]
+pub mod symbols;
+#[cfg(feature = "server")]
+pub mod tenants;
+#[cfg(feature = "test-harness")]
+pub mod test_server;
+#[cfg(feature = "server")]
+pub mod unix_socket;
+#[cfg(feature = "server")]
+pub mod usage;
+#[cfg(feature = "server")]
+pub mod venue_sequences;
+#[cfg(feature = "server")]
+pub mod warmup;
+#[cfg(feature = "server")]
+pub mod webhooks;
+#[cfg(feature = "server")]
 pub mod websocket_handler;
 
-pub use order_book::*;
+pub use book_diff::*;
 pub use message::*;
-pub use stream_manager::*;
-pub use websocket_handler::*;
\ No newline at end of file
+
+#[cfg(feature = "alloc-audit")]
+pub use alloc_audit::*;
+
+#[cfg(feature = "test-harness")]
+pub use test_server::*;
+
+#[cfg(feature = "server")]
+pub use {
+    access::*,
+    activity_stream::*,
+    admin::*,
+    audit::*,
+    auth::*,
+    candle_store::*,
+    candles::*,
+    clock::*,
+    compression::*,
+    conformance::*,
+    dead_letters::*,
+    epochs::*,
+    event_bus::*,
+    feed_monitor::*,
+    filters::*,
+    flow::*,
+    futures::*,
+    history::*,
+    indicators::*,
+    ingest::*,
+    integrity::*,
+    ladder::*,
+    level_deltas::*,
+    limits::*,
+    liquidity_age::*,
+    listener::*,
+    load_shedding::*,
+    metrics::*,
+    notifications::*,
+    option_chain::*,
+    order_book::*,
+    order_stats::*,
+    outbound_queue::*,
+    paper_trading::*,
+    pairs::*,
+    perpetual::*,
+    popularity::*,
+    price_bands::*,
+    profiles::*,
+    quota::*,
+    rate_limit::*,
+    reconcile::*,
+    reference::*,
+    runtime::*,
+    sampling::*,
+    sandbox::*,
+    session::*,
+    snapshot_cache::*,
+    snapshot_schedule::*,
+    stream_manager::*,
+    stream_stats::*,
+    stream_validation::*,
+    subscriptions::*,
+    summary::*,
+    supervisor::*,
+    symbol_config::*,
+    symbols::*,
+    tenants::*,
+    unix_socket::*,
+    usage::*,
+    venue_sequences::*,
+    warmup::*,
+    webhooks::*,
+    websocket_handler::*,
+};
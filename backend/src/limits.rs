@@ -0,0 +1,14 @@
+use std::time::Duration;
+
+// Caps on simulated state and client backlogs. Subscribing to an unknown symbol creates a book for it, so
+// without these a client cycling through symbols could grow memory without bound.
+#[derive(Debug, Clone, Default)]
+pub struct ResourceLimits {
+    pub max_symbols: Option<usize>,
+    pub max_orders_per_book: Option<usize>,
+    // Books nobody has subscribed to for this long are dropped; default symbols and pair legs are kept
+    pub idle_symbol_ttl: Option<Duration>,
+    // Clients with more messages queued than this are disconnected as slow consumers; their
+    // queues are unbounded otherwise
+    pub max_client_queue: Option<usize>,
+}
@@ -0,0 +1,46 @@
+use chrono::Utc;
+
+use crate::message::{AgeBucket, MarketDataUpdate, Quantity, RestingAge, Side};
+use crate::order_book::OrderBook;
+
+// Ages the resting size of LiquidityAge streams is bucketed by
+pub const LIQUIDITY_AGE_BUCKETS_MS: [u64; 3] = [1_000, 10_000, 60_000];
+
+pub fn liquidity_age_update(order_book: &OrderBook, max_levels: u32) -> MarketDataUpdate {
+    MarketDataUpdate::LiquidityAge {
+        bids: resting_age(order_book, &Side::Bid, max_levels),
+        asks: resting_age(order_book, &Side::Ask, max_levels),
+    }
+}
+
+fn resting_age(order_book: &OrderBook, side: &Side, max_levels: u32) -> RestingAge {
+    let now = Utc::now();
+    let mut quantity = Quantity::ZERO;
+    let mut order_count = 0;
+    let mut older = [(Quantity::ZERO, 0); LIQUIDITY_AGE_BUCKETS_MS.len()];
+
+    for order in order_book.resting_orders(side, max_levels) {
+        let age_ms = (now - order.timestamp).num_milliseconds().max(0) as u64;
+        quantity += order.quantity;
+        order_count += 1;
+        for (&bucket_ms, (bucket_quantity, bucket_orders)) in LIQUIDITY_AGE_BUCKETS_MS.iter().zip(older.iter_mut()) {
+            if age_ms >= bucket_ms {
+                *bucket_quantity += order.quantity;
+                *bucket_orders += 1;
+            }
+        }
+    }
+
+    let older_than = LIQUIDITY_AGE_BUCKETS_MS
+        .iter()
+        .zip(older)
+        .map(|(&age_ms, (bucket_quantity, bucket_orders))| AgeBucket {
+            age_ms,
+            quantity: bucket_quantity,
+            order_count: bucket_orders,
+            share: if quantity.is_zero() { 0.0 } else { bucket_quantity.to_f64() / quantity.to_f64() },
+        })
+        .collect();
+
+    RestingAge { quantity, order_count, older_than }
+}
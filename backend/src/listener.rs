@@ -0,0 +1,85 @@
+use std::io;
+use std::net::SocketAddr;
+use std::time::Duration;
+use clap::Args;
+use socket2::{SockRef, TcpKeepalive};
+use tokio::net::{lookup_host, TcpListener, TcpSocket, TcpStream};
+use tracing::warn;
+
+// Socket tuning of the WebSocket listener for deployments with high connection rates
+#[derive(Debug, Clone, Args)]
+pub struct ListenerOptions {
+    /// Disable Nagle's algorithm on client sockets so small updates are not batched (also set by --hf-mode)
+    #[arg(long)]
+    pub tcp_nodelay: bool,
+
+    /// Listeners bound to the address with SO_REUSEPORT, each accepting on its own task; the
+    /// kernel spreads new connections across them (Unix only)
+    #[arg(long, value_name = "N", default_value_t = 1)]
+    pub acceptors: usize,
+
+    /// Pending connections each listener queues before the kernel refuses more, capped by
+    /// net.core.somaxconn on Linux
+    #[arg(long, value_name = "N", default_value_t = 1024)]
+    pub listen_backlog: u32,
+
+    /// Send TCP keepalive probes on client sockets idle for this long, so dead peers are
+    /// dropped (default: disabled)
+    #[arg(long, value_name = "SECS")]
+    pub tcp_keepalive_secs: Option<u64>,
+}
+
+impl Default for ListenerOptions {
+    fn default() -> Self {
+        Self {
+            tcp_nodelay: false,
+            acceptors: 1,
+            listen_backlog: 1024,
+            tcp_keepalive_secs: None,
+        }
+    }
+}
+
+impl ListenerOptions {
+    // One listener per acceptor, all on the same address
+    pub async fn bind(&self, addr: &str) -> io::Result<Vec<TcpListener>> {
+        let addr = lookup_host(addr)
+            .await?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("No address for {}", addr)))?;
+
+        let mut acceptors = self.acceptors.max(1);
+        if acceptors > 1 && !cfg!(unix) {
+            warn!("SO_REUSEPORT is not available on this platform, using a single acceptor");
+            acceptors = 1;
+        }
+
+        (0..acceptors).map(|_| self.bind_one(addr, acceptors > 1)).collect()
+    }
+
+    fn bind_one(&self, addr: SocketAddr, reuse_port: bool) -> io::Result<TcpListener> {
+        let socket = if addr.is_ipv4() { TcpSocket::new_v4()? } else { TcpSocket::new_v6()? };
+        socket.set_reuseaddr(true)?;
+        #[cfg(unix)]
+        if reuse_port {
+            socket.set_reuseport(true)?;
+        }
+        #[cfg(not(unix))]
+        let _ = reuse_port;
+
+        socket.bind(addr)?;
+        socket.listen(self.listen_backlog)
+    }
+
+    // Applies the per-connection options to an accepted client socket
+    pub fn configure(&self, stream: &TcpStream) -> io::Result<()> {
+        if self.tcp_nodelay {
+            stream.set_nodelay(true)?;
+        }
+        if let Some(secs) = self.tcp_keepalive_secs {
+            let keepalive = TcpKeepalive::new().with_time(Duration::from_secs(secs.max(1)));
+            SockRef::from(stream).set_tcp_keepalive(&keepalive)?;
+        }
+        Ok(())
+    }
+}
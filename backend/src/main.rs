@@ -1,9 +1,19 @@
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 use clap::Parser;
-use tracing::{info, error};
+use tokio::runtime::Handle;
+use tracing::{info, error, warn};
 use tracing_subscriber::{EnvFilter, FmtSubscriber};
 
-use market_depth_server::{StreamManager, WebSocketHandler};
+use market_depth_server::{serve_admin, shutdown_signal, AccessControl, AccessOptions, AuditLog, AuditOptions, AuthOptions, Authenticator, BookEpochs, CandleStoreOptions, ConformanceOptions, DeadLetterOptions, DEFAULT_MAX_DEPTH_SYMBOLS, DEFAULT_MAX_LEVELS, DisconnectReason, EpochOptions, FeedSource, FuturesCurve, FuturesOptions, HistoryOptions, IntegrityOptions, LoadShedder, LoadSheddingOptions, ListenerOptions, NotificationOptions, Notifier, OptionChain, OptionChainOptions, PaperTradingOptions, parse_quantity_decimals, PerpetualOptions, PriceBandOptions, ProfileOptions, QuotaOptions, QuotaTracker, RateLimitOptions, RateLimiter, ResourceLimits, RuntimeOptions, SandboxOptions, SessionOptions, SessionStore, StreamManager, SymbolConfigOptions, SyntheticPair, TenantDefinition, TenantOptions, TenantRegistry, UnixSocketOptions, WarmupOptions, WebhookDispatcher, WebhookOptions, WebSocketHandler};
+
+#[cfg(feature = "alloc-audit")]
+#[global_allocator]
+static ALLOCATOR: market_depth_server::CountingAllocator = market_depth_server::CountingAllocator;
+
+// How long clients are given to receive their Close frames on shutdown
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(2);
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -15,10 +25,172 @@ struct Args {
     /// Log level (trace, debug, info, warn, error)
     #[arg(short, long, default_value = "info")]
     log_level: String,
+
+    /// Synthetic pair to publish, repeatable: BASE/QUOTE (ratio) or BASE-QUOTE (spread)
+    #[arg(long = "pair", value_name = "PAIR")]
+    pairs: Vec<String>,
+
+    /// Admin HTTP API address
+    #[arg(long, default_value = "127.0.0.1:8090")]
+    admin_addr: String,
+
+    /// Also serve the admin API on this Unix domain socket; `@NAME` binds an abstract socket (Linux only)
+    #[arg(long, value_name = "PATH")]
+    admin_unix_socket: Option<String>,
+
+    /// Venue reported for every symbol by symbol discovery
+    #[arg(long, default_value = "SIM")]
+    venue: String,
+
+    /// Decimals a symbol's order sizes are quoted to, repeatable: SYMBOL:DECIMALS, e.g.
+    /// BTCUSD:4 for lots of 0.0001 (default: whole units)
+    #[arg(long = "quantity-decimals", value_name = "SYMBOL:DECIMALS")]
+    quantity_decimals: Vec<String>,
+
+    /// Accept order events for external books on the admin API at /ingest/SYMBOL
+    #[arg(long)]
+    ingest: bool,
+
+    /// Simulate no books: no default symbols, and books created for subscriptions stay
+    /// empty until fed through the ingest API
+    #[arg(long)]
+    no_simulation: bool,
+
+    /// Refuse subscriptions with a malformed symbol or max_levels outside 1-500 with a
+    /// 422 Error instead of serving them as given
+    #[arg(long)]
+    strict_subscriptions: bool,
+
+    /// Anchor MBP and MBO updates with a checksum of the client's book each time a book's
+    /// sequence passes a multiple of N (default: no anchors)
+    #[arg(long, value_name = "N")]
+    anchor_interval: Option<u64>,
+
+    /// Tag the orders of simulated books on MBO streams with the class of actor that placed
+    /// them (maker, taker or noise), as ground truth for research datasets
+    #[arg(long)]
+    research_actor_tags: bool,
+
+    /// Add `latency_us` to market data, the time from the book change to serialization, to
+    /// debug the server's own delay apart from the network's
+    #[arg(long)]
+    debug_latency_tag: bool,
+
+    /// Send each client a UsageReport at this interval (e.g. 86400 for daily)
+    #[arg(long, value_name = "SECS")]
+    usage_report_interval_secs: Option<u64>,
+
+    /// Maximum number of symbols with a simulated book (default: unlimited)
+    #[arg(long, value_name = "N")]
+    max_symbols: Option<usize>,
+
+    /// Most symbols one GET /depth or SnapshotManyRequest may ask for
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_DEPTH_SYMBOLS)]
+    max_depth_symbols: usize,
+
+    /// Deepest max_levels a stream is served; deeper subscriptions are clamped to it and
+    /// answered with SubscriptionAdjusted
+    #[arg(long, value_name = "N", default_value_t = DEFAULT_MAX_LEVELS)]
+    max_levels: u32,
+
+    /// Maximum resting orders per simulated book (default: unlimited)
+    #[arg(long, value_name = "N")]
+    max_orders_per_book: Option<usize>,
+
+    /// Drop books that have had no subscribers for this long (default: never)
+    #[arg(long, value_name = "SECS")]
+    idle_symbol_ttl_secs: Option<u64>,
+
+    /// Disconnect clients with more messages than this queued as slow consumers (default: never)
+    #[arg(long, value_name = "N")]
+    max_client_queue: Option<usize>,
+
+    #[command(flatten)]
+    perpetuals: PerpetualOptions,
+
+    #[command(flatten)]
+    option_chains: OptionChainOptions,
+
+    #[command(flatten)]
+    futures: FuturesOptions,
+
+    #[command(flatten)]
+    access: AccessOptions,
+
+    #[command(flatten)]
+    auth: AuthOptions,
+
+    #[command(flatten)]
+    sessions: SessionOptions,
+
+    #[command(flatten)]
+    quotas: QuotaOptions,
+
+    #[command(flatten)]
+    audit: AuditOptions,
+
+    #[command(flatten)]
+    rate_limits: RateLimitOptions,
+
+    #[command(flatten)]
+    load_shedding: LoadSheddingOptions,
+
+    #[command(flatten)]
+    tenants: TenantOptions,
+
+    #[command(flatten)]
+    sandboxes: SandboxOptions,
+
+    #[command(flatten)]
+    conformance: ConformanceOptions,
+
+    #[command(flatten)]
+    webhooks: WebhookOptions,
+
+    #[command(flatten)]
+    notifications: NotificationOptions,
+
+    #[command(flatten)]
+    runtime: RuntimeOptions,
+
+    #[command(flatten)]
+    listener: ListenerOptions,
+
+    #[command(flatten)]
+    history: HistoryOptions,
+
+    #[command(flatten)]
+    candle_store: CandleStoreOptions,
+
+    #[command(flatten)]
+    integrity: IntegrityOptions,
+
+    #[command(flatten)]
+    paper_trading: PaperTradingOptions,
+
+    #[command(flatten)]
+    dead_letters: DeadLetterOptions,
+
+    #[command(flatten)]
+    price_bands: PriceBandOptions,
+
+    #[command(flatten)]
+    unix_socket: UnixSocketOptions,
+
+    #[command(flatten)]
+    warmup: WarmupOptions,
+
+    #[command(flatten)]
+    epochs: EpochOptions,
+
+    #[command(flatten)]
+    profiles: ProfileOptions,
+
+    #[command(flatten)]
+    symbols: SymbolConfigOptions,
 }
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
+fn main() -> anyhow::Result<()> {
     let args = Args::parse();
 
     // Initialize tracing
@@ -35,25 +207,279 @@ async fn main() -> anyhow::Result<()> {
 
     tracing::subscriber::set_global_default(subscriber)?;
 
+    let runtime = args.runtime.build_io_runtime()?;
+    runtime.block_on(run(args))
+}
+
+async fn run(args: Args) -> anyhow::Result<()> {
+
     info!("Starting Market Depth Server");
     info!("Log level: {}", args.log_level);
 
-    // Create stream manager
-    let stream_manager = Arc::new(StreamManager::new());
+    let audit = Arc::new(AuditLog::open(&args.audit)?);
+    if let Some(path) = &args.audit.audit_log {
+        info!("Writing audit log to {}", path.display());
+    }
+
+    let epochs = Arc::new(args.epochs.epochs()?);
+    if let Some(path) = &args.epochs.epoch_file {
+        info!("Keeping book epochs in {}", path.display());
+    }
+
+    let profiles = Arc::new(args.profiles.store()?);
+    if let Some(path) = &args.profiles.profile_file {
+        info!("Keeping subscription profiles in {}", path.display());
+    }
+
+    let simulation_runtime = args.runtime.spawn_simulation_runtime()?;
+    let stream_manager = Arc::new(build_stream_manager(&args, &audit, &epochs, simulation_runtime.clone(), None)?);
+    let mut tenants = TenantRegistry::new(Arc::clone(&stream_manager));
+    let credentials: HashSet<&str> = args.auth.credentials.iter().map(|credential| credential.key.as_str()).collect();
+    for tenant in args.tenants.definitions().map_err(anyhow::Error::msg)? {
+        // Tenants are told apart by verified keys only, so a client cannot claim one
+        if let Some(api_key) = tenant.api_keys.iter().find(|api_key| !credentials.contains(api_key.as_str())) {
+            anyhow::bail!("API key {} of tenant {} is not an --api-credential", api_key, tenant.name);
+        }
+        let tenant_manager = build_stream_manager(&args, &audit, &epochs, simulation_runtime.clone(), Some(&tenant))?;
+        tenants.add_tenant(&tenant.name, &tenant.api_keys, Arc::new(tenant_manager)).map_err(anyhow::Error::msg)?;
+        info!("Registered tenant {} with {} API keys", tenant.name, tenant.api_keys.len());
+    }
+    let tenants = Arc::new(tenants);
+
+    // Subscribed before the simulation starts so the first events are seen
+    if args.webhooks.is_enabled() {
+        let webhooks = WebhookDispatcher::new(&args.webhooks).map_err(anyhow::Error::msg)?;
+        webhooks.start(stream_manager.subscribe_events());
+        info!("Delivering events to {} webhooks", args.webhooks.webhooks.len());
+    }
+    if args.notifications.is_enabled() {
+        let notifier = Notifier::new(&args.notifications).map_err(anyhow::Error::msg)?;
+        notifier.start(stream_manager.subscribe_events());
+    }
 
     // Start stream manager background tasks
-    stream_manager.start().await;
+    for stream_manager in tenants.stream_managers() {
+        stream_manager.start().await;
+    }
+
+    // Shared by the WebSocket and admin listeners
+    let rate_limiter = Arc::new(RateLimiter::new(&args.rate_limits));
+    rate_limiter.start_pruning();
+    let mut access_control = AccessControl::new(&args.access);
+    access_control.set_audit_log(Arc::clone(&audit));
+    let access_control = Arc::new(access_control);
+
+    // Signed requests apply to the admin API and WebSocket upgrades
+    let mut authenticator = Authenticator::new(&args.auth);
+    authenticator.set_audit_log(Arc::clone(&audit));
+    let authenticator = Arc::new(authenticator);
+    authenticator.start_pruning();
+
+    // Start admin API
+    let admin_tenants = Arc::clone(&tenants);
+    let admin_rate_limiter = Arc::clone(&rate_limiter);
+    let admin_access_control = Arc::clone(&access_control);
+    let admin_authenticator = Arc::clone(&authenticator);
+    let admin_addr = args.admin_addr.clone();
+    let admin_unix_socket = UnixSocketOptions { unix_socket: args.admin_unix_socket.clone(), ..args.unix_socket.clone() };
+    let ingest = args.ingest;
+    tokio::spawn(async move {
+        if let Err(e) = serve_admin(&admin_addr, admin_unix_socket, admin_tenants, admin_rate_limiter, admin_access_control, admin_authenticator, audit, ingest).await {
+            error!("Admin API error: {}", e);
+        }
+    });
 
     // Create and start WebSocket handler
-    let ws_handler = WebSocketHandler::new(Arc::clone(&stream_manager));
+    let mut ws_handler = WebSocketHandler::new(Arc::clone(&stream_manager));
+    ws_handler.set_tenants(Arc::clone(&tenants));
+    ws_handler.set_listener_options(args.listener.clone());
+    ws_handler.set_unix_socket(args.unix_socket.clone());
+    ws_handler.set_profiles(profiles);
+    if args.runtime.tcp_nodelay() {
+        ws_handler.enable_tcp_nodelay();
+    }
+    if rate_limiter.is_enabled() {
+        ws_handler.set_rate_limiter(rate_limiter);
+    }
+    if access_control.is_enabled() {
+        ws_handler.set_access_control(access_control);
+    }
+    if authenticator.is_enabled() {
+        let sessions = Arc::new(SessionStore::new(&args.sessions));
+        sessions.start_pruning();
+        ws_handler.set_authentication(authenticator, sessions);
+    }
 
     info!("Server starting on: {}", args.addr);
 
-    // Start the server
-    if let Err(e) = ws_handler.start(&args.addr).await {
-        error!("Server error: {}", e);
-        return Err(e);
+    // Start the server once the books are warmed up, until it is told to shut down
+    let serve = async {
+        args.warmup.wait(&tenants).await;
+        ws_handler.start(&args.addr).await
+    };
+    tokio::select! {
+        result = serve => {
+            if let Err(e) = result {
+                error!("Server error: {}", e);
+                return Err(e);
+            }
+        }
+        _ = shutdown_signal() => {
+            let disconnected: usize = tenants
+                .stream_managers()
+                .map(|stream_manager| stream_manager.disconnect_all(DisconnectReason::ServerShutdown))
+                .sum();
+            info!("Shutting down, disconnecting {} clients", disconnected);
+            drain_clients(&tenants).await;
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Gives the clients' send tasks a moment to deliver their Close frames
+async fn drain_clients(tenants: &TenantRegistry) {
+    let deadline = tokio::time::Instant::now() + SHUTDOWN_GRACE;
+    while tenants.stream_managers().any(|stream_manager| stream_manager.connected_clients() > 0) {
+        if tokio::time::Instant::now() >= deadline {
+            warn!("Shutdown grace period over, dropping the remaining clients");
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+}
+
+// Stream manager of the default universe, or of a tenant with its own symbols and limits
+fn build_stream_manager(
+    args: &Args,
+    audit: &Arc<AuditLog>,
+    epochs: &Arc<BookEpochs>,
+    simulation_runtime: Option<Handle>,
+    tenant: Option<&TenantDefinition>,
+) -> anyhow::Result<StreamManager> {
+    let mut stream_manager = StreamManager::new();
+    stream_manager.set_epochs(Arc::clone(epochs));
+
+    if args.no_simulation || tenant.is_some_and(|tenant| !tenant.simulate) {
+        stream_manager.disable_simulation();
+    }
+    if let Some(symbols) = tenant.and_then(|tenant| tenant.symbols.clone()) {
+        stream_manager.set_default_symbols(symbols);
+    }
+    stream_manager.set_venue(&args.venue);
+    if args.strict_subscriptions {
+        stream_manager.enable_strict_subscriptions();
+    }
+    if let Some(interval) = args.anchor_interval {
+        stream_manager.set_anchor_interval(interval);
+    }
+    if args.research_actor_tags {
+        stream_manager.enable_actor_tags();
+    }
+    if args.debug_latency_tag {
+        stream_manager.enable_latency_tags();
+    }
+
+    if let Some(secs) = args.usage_report_interval_secs {
+        stream_manager.enable_usage_reports(Duration::from_secs(secs.max(1)));
+    }
+
+    stream_manager.set_resource_limits(ResourceLimits {
+        max_symbols: tenant.and_then(|tenant| tenant.max_symbols).or(args.max_symbols),
+        max_orders_per_book: args.max_orders_per_book,
+        idle_symbol_ttl: args.idle_symbol_ttl_secs.map(|secs| Duration::from_secs(secs.max(1))),
+        max_client_queue: args.max_client_queue,
+    });
+    stream_manager.set_max_depth_symbols(args.max_depth_symbols);
+    stream_manager.set_max_levels(args.max_levels);
+
+    if args.quotas.is_enabled() {
+        let mut quotas = QuotaTracker::new(&args.quotas);
+        quotas.set_audit_log(Arc::clone(audit));
+        stream_manager.set_quotas(quotas);
+    }
+
+    if let Some(seed) = args.conformance.conformance_seed {
+        // The golden stream is generated with the default book settings
+        let changed = args.max_orders_per_book.is_some() || !args.quantity_decimals.is_empty() || args.symbols.symbol_config.is_some();
+        if changed || args.price_bands.price_band_pct.is_some() {
+            anyhow::bail!(
+                "--conformance-seed cannot be combined with --max-orders-per-book, --quantity-decimals, --symbol-config or --price-band-pct"
+            );
+        }
+        stream_manager.enable_conformance(seed, args.conformance.ticks());
+    }
+
+    if let Some(per_key) = args.sandboxes.sandboxes_per_key {
+        stream_manager.enable_sandboxes(per_key);
+    }
+
+    if args.load_shedding.is_enabled() {
+        stream_manager.set_load_shedding(LoadShedder::new(&args.load_shedding));
+    }
+
+    if let Some(history) = args.history.history() {
+        stream_manager.enable_history(history);
+    }
+    if let Some(store) = args.candle_store.store().map_err(anyhow::Error::msg)? {
+        stream_manager.enable_candle_store(store, args.candle_store.compaction_interval());
+    }
+    if let Some(checks) = args.integrity.checks().map_err(anyhow::Error::msg)? {
+        stream_manager.enable_integrity_checks(checks);
+    }
+    if let Some(model) = args.paper_trading.model().map_err(anyhow::Error::msg)? {
+        stream_manager.enable_paper_trading(Arc::new(model));
+    }
+    if let Some(dead_letters) = args.dead_letters.log().map_err(anyhow::Error::msg)? {
+        stream_manager.enable_dead_letters(dead_letters);
+    }
+    if let Some(band) = args.price_bands.band().map_err(anyhow::Error::msg)? {
+        stream_manager.set_price_band(band);
+    }
+
+    if let Some(handle) = simulation_runtime {
+        stream_manager.set_simulation_runtime(handle);
+    }
+    if let Some(window) = args.runtime.tick_spread().map_err(anyhow::Error::msg)? {
+        stream_manager.set_tick_spread(window);
+    }
+    if let Some(timeout) = args.runtime.symbol_fanout_timeout().map_err(anyhow::Error::msg)? {
+        stream_manager.set_fanout_timeout(timeout);
+    }
+
+    for definition in &args.quantity_decimals {
+        let (symbol, decimals) = parse_quantity_decimals(definition).map_err(anyhow::Error::msg)?;
+        stream_manager.set_quantity_decimals(&symbol, decimals);
+    }
+
+    // Tenants listing their own symbols keep them
+    if tenant.is_none_or(|tenant| tenant.symbols.is_none()) {
+        if let Some(symbols) = args.symbols.symbols().map_err(anyhow::Error::msg)? {
+            if !args.ingest && symbols.iter().any(|config| config.source == FeedSource::Ingest) {
+                warn!("--symbol-config lists ingest books, but without --ingest nothing can feed them");
+            }
+            stream_manager.set_symbol_config(symbols);
+        }
+    }
+
+    for definition in &args.pairs {
+        let pair = SyntheticPair::parse(definition).map_err(anyhow::Error::msg)?;
+        stream_manager.add_synthetic_pair(pair);
+    }
+
+    for symbol in &args.perpetuals.perpetuals {
+        stream_manager.add_perpetual(symbol, &args.perpetuals);
+    }
+
+    for definition in &args.option_chains.option_chains {
+        let chain = OptionChain::parse(definition, &args.option_chains, chrono::Utc::now()).map_err(anyhow::Error::msg)?;
+        stream_manager.add_option_chain(chain);
+    }
+
+    for definition in &args.futures.futures {
+        let curve = FuturesCurve::parse(definition, &args.futures, chrono::Utc::now()).map_err(anyhow::Error::msg)?;
+        stream_manager.add_futures(curve);
+    }
+
+    Ok(stream_manager)
+}
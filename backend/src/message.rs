@@ -1,25 +1,177 @@
-use serde::{Deserialize, Serialize};
+#[cfg(feature = "server")]
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+#[cfg(feature = "server")]
+use bytes::Bytes;
 use chrono::{DateTime, Utc};
+#[cfg(feature = "server")]
 use uuid::Uuid;
 
+use crate::book_diff::LevelChange;
+#[cfg(feature = "server")]
+use crate::clock::{clock, ClockSource, EventOrdering};
+#[cfg(feature = "server")]
+use crate::level_deltas::LevelBase;
+#[cfg(feature = "server")]
+use crate::order_book::default_max_orders;
+#[cfg(feature = "server")]
+use crate::outbound_queue::{DeliveryTier, Prioritized};
+#[cfg(feature = "server")]
+use crate::sampling::UpdateSampler;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 #[serde(tag = "type")]
 pub enum ClientMessage {
     Subscribe {
         stream_id: String,
         symbol: String,
         data_type: DataType,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
         max_levels: Option<u32>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        max_orders: Option<u32>, // MBO only: orders per side, default 3 per level
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        snapshot_interval_ms: Option<u64>, // MBP and MBO only: also send the whole book this often
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        priority: Option<StreamPriority>, // Normal by default
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        sample_every_n: Option<u32>, // Only every Nth update, and every anchored one
     },
     Unsubscribe {
         stream_id: String,
     },
+    // Saves streams under a name for the session's API key, replacing any profile of that
+    // name; no streams deletes it. Answered with ProfileSaved.
+    SaveProfile {
+        name: String,
+        streams: Vec<StreamSpec>,
+    },
+    // Subscribes every stream of a saved profile: ProfileLoaded, then Subscribed or an
+    // Error for each stream as for Subscribe
+    LoadProfile {
+        name: String,
+    },
+    // Resends the current view of an MBP or MBO stream with an anchor, e.g. after the
+    // client's book failed an anchor check
+    Resync {
+        stream_id: String,
+    },
     Ping {
         timestamp: DateTime<Utc>,
     },
+    // Extends the session with a fresh credential proof: the hex HMAC-SHA256 of
+    // `timestamp=<timestamp>` keyed with the API secret
+    RenewSession {
+        api_key: String,
+        timestamp: i64,
+        signature: String,
+    },
+    GetQuota,
+    // Answered with StreamStats, or a 404 Error when the client has no such stream
+    StreamStats {
+        stream_id: String,
+    },
+    // Answered with Symbols; every filter is optional
+    SearchSymbols {
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        search: Option<String>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        symbol_type: Option<SymbolType>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        status: Option<SymbolStatus>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        limit: Option<usize>,
+    },
+    // Checks streams as Subscribe would without subscribing them; answered with Validated
+    ValidateSubscribe {
+        streams: Vec<StreamSpec>,
+    },
+    // Replays the recorded MBP history of a symbol between `from` and `to` on its own
+    // stream, `speed` times faster than recorded (1 by default). Answered with
+    // ReplayStarted, the updates and ReplayComplete; Unsubscribe stops it.
+    ReplayRequest {
+        stream_id: String,
+        symbol: String,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        speed: Option<f64>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        max_levels: Option<u32>,
+    },
+    // Top of several books at once, answered with SnapshotMany; at most --max-depth-symbols
+    // symbols, `max_levels` 20 by default
+    SnapshotManyRequest {
+        request_id: String,
+        symbols: Vec<String>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        max_levels: Option<u32>,
+    },
+    // Starts a clock-sync exchange, as connecting with `clock_sync=true` does; answered with ClockSync
+    SyncClock,
+    // Answers ClockSync with its `server_ts` and the client's time on receiving it
+    ClockSyncReply {
+        server_ts: DateTime<Utc>,
+        client_ts: DateTime<Utc>,
+    },
+    // Mutes messages connection-wide without unsubscribing, replacing the filters set
+    // before; a SetFilters with every field left out lifts them. Answered with FiltersSet.
+    SetFilters {
+        #[serde(default)]
+        mute_heartbeats: bool,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        min_interval_ms: Option<u64>, // At most one update per stream this often
+        #[serde(default)]
+        symbols_mute: Vec<String>,
+    },
+    // Puts the best bid and ask and the last trade of these books on every heartbeat of the
+    // connection, replacing the symbols set before; none stops it. At most
+    // --max-depth-symbols symbols. Answered with HeartbeatQuotesSet.
+    SetHeartbeatQuotes {
+        symbols: Vec<String>,
+    },
+    // Pairs a book sequence of a book fed through the ingest API with the venue sequence
+    // of the feed event behind it, looked up by exactly one of the two. Answered with
+    // SequenceMapped.
+    MapSequence {
+        symbol: String,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        sequence: Option<u64>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        venue_sequence: Option<u64>,
+    },
+    // Stored candles of one of the --candle-retention intervals, e.g. "1m", opening between
+    // `from` and `to`, the latest 5000 when there are more. Answered with CandleHistory pages.
+    CandleBackfill {
+        symbol: String,
+        interval: String,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+    // Paper order under --paper-trading, filled against the live book without changing
+    // it: a market order without `limit_price`, whose unfilled rest is cancelled, or a
+    // limit order resting until trades print at or through its price. Answered with
+    // PaperOrderUpdate, then a PaperOrderUpdate and PaperFill for every fill.
+    SubmitPaperOrder {
+        symbol: String,
+        side: Side, // Bid buys, Ask sells
+        quantity: Quantity,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        limit_price: Option<f64>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        client_order_id: Option<String>,
+    },
+    // Answered with the cancelled PaperOrderUpdate
+    CancelPaperOrder {
+        order_id: u64,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 #[serde(tag = "type")]
 pub enum ServerMessage {
     Subscribed {
@@ -27,18 +179,182 @@ pub enum ServerMessage {
         symbol: String,
         data_type: DataType,
     },
+    // Follows Subscribed when the stream asked for more levels than --max-levels
+    SubscriptionAdjusted {
+        stream_id: String,
+        requested: u32,
+        granted: u32,
+    },
     Unsubscribed {
         stream_id: String,
     },
+    ProfileSaved {
+        name: String,
+        streams: usize,
+    },
+    ProfileLoaded {
+        name: String,
+        streams: Vec<StreamSpec>,
+    },
+    FiltersSet {
+        mute_heartbeats: bool,
+        min_interval_ms: Option<u64>,
+        symbols_mute: Vec<String>,
+    },
+    HeartbeatQuotesSet {
+        symbols: Vec<String>,
+    },
+    // None when no event the book still remembers matches
+    SequenceMapped {
+        symbol: String,
+        mapping: Option<SequenceMapping>,
+    },
+    // One page of a CandleBackfill, oldest candles first; the last page is `complete`
+    CandleHistory {
+        symbol: String,
+        interval_secs: u64,
+        candles: Vec<Candle>,
+        complete: bool,
+    },
+    // A paper order on being accepted, filled or cancelled
+    PaperOrderUpdate {
+        order: PaperOrder,
+    },
+    PaperFill {
+        fill: PaperFill,
+    },
+    // `event_ts` is when the book (or other source) last changed, never earlier than the
+    // stream's previous update; `send_ts` is when the message was serialized for the client,
+    // and `timestamp` when the update was built for fan-out. MBP and MBO updates carry an
+    // `anchor` every --anchor-interval book sequences. `sequence` starts over whenever the
+    // book is recreated, and `epoch` then goes up. With --debug-latency-tag, `latency_us`
+    // is the time from the book change to serialization. Views of books fed through the
+    // ingest API carry the `venue_sequence` of the last feed event that had one.
     MarketData {
+        stream_id: String,
+        symbol: Arc<str>,
+        data: MarketDataUpdate,
+        sequence: u64,
+        #[serde(default)]
+        epoch: u64,
+        timestamp: DateTime<Utc>,
+        event_ts: DateTime<Utc>,
+        send_ts: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "ts", ts(optional))]
+        anchor: Option<BookAnchor>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "ts", ts(optional))]
+        latency_us: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        #[cfg_attr(feature = "ts", ts(optional))]
+        venue_sequence: Option<u64>,
+    },
+    // Every level (and for MBO every order) of the book, sent on streams subscribed with
+    // `snapshot_interval_ms`; it replaces the client's book whether or not it saw a gap
+    Snapshot {
         stream_id: String,
         symbol: String,
         data: MarketDataUpdate,
         sequence: u64,
+        #[serde(default)]
+        epoch: u64,
         timestamp: DateTime<Utc>,
     },
+    MarketSummary {
+        stream_id: String,
+        summary: MarketSummary,
+    },
+    // Changes of the symbol registry up to `version`, oldest first; none on subscribing
+    ReferenceData {
+        stream_id: String,
+        version: u64,
+        changes: Vec<ReferenceChange>,
+    },
+    // `quotes` holds the books asked for with SetHeartbeatQuotes that exist
     HeartBeat {
         timestamp: DateTime<Utc>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        #[cfg_attr(feature = "ts", ts(as = "Option<Vec<TopOfBook>>", optional))]
+        quotes: Vec<TopOfBook>,
+    },
+    UsageReport {
+        connected_at: DateTime<Utc>,
+        messages: u64,
+        bytes: u64,
+        streams: Vec<StreamUsage>,
+        timestamp: DateTime<Utc>,
+    },
+    SessionStarted {
+        token: String,
+        expires_at: DateTime<Utc>,
+    },
+    SessionRenewed {
+        token: String,
+        expires_at: DateTime<Utc>,
+    },
+    SessionExpiring {
+        expires_at: DateTime<Utc>,
+    },
+    SessionExpired,
+    Quota {
+        status: QuotaStatus,
+    },
+    StreamStats {
+        stats: StreamStats,
+    },
+    Symbols {
+        symbols: Vec<SymbolInfo>,
+    },
+    // Answers SnapshotManyRequest with the books in the order asked for
+    SnapshotMany {
+        request_id: String,
+        snapshots: Vec<BookSnapshot>,
+        missing: Vec<String>,
+    },
+    // One result per stream of a ValidateSubscribe, in order
+    Validated {
+        streams: Vec<StreamValidation>,
+    },
+    QuotaExceeded {
+        quota: QuotaRemaining,
+    },
+    // Sent when server load changes how often low-priority streams are updated: at most
+    // once per `interval_ms`, or at full rate again once it is null
+    Throttled {
+        stream_ids: Vec<String>,
+        interval_ms: Option<u64>,
+        timestamp: DateTime<Utc>,
+    },
+    // Sent on every stream of a dated future when it halts at expiry and again when it
+    // is delisted, which also removes the stream; calendar spreads on it are included
+    InstrumentStatus {
+        stream_id: String,
+        symbol: String,
+        status: TradingStatus,
+        settlement_price: Option<f64>,
+        timestamp: DateTime<Utc>,
+    },
+    // `from` and `to` are the event times of the first and last update to be replayed
+    ReplayStarted {
+        stream_id: String,
+        symbol: String,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        updates: usize,
+        speed: f64,
+    },
+    // The last update of a replay was sent, which ended its stream
+    ReplayComplete {
+        stream_id: String,
+    },
+    // First message of a clock-sync exchange; the client answers with ClockSyncReply at once
+    ClockSync {
+        server_ts: DateTime<Utc>,
+    },
+    // Last message of a clock-sync exchange, with the offset it estimated
+    ClockSynced {
+        offset: ClockOffset,
     },
     Error {
         code: u32,
@@ -47,13 +363,316 @@ pub enum ServerMessage {
     },
 }
 
+impl ServerMessage {
+    pub fn stream_id(&self) -> Option<&str> {
+        match self {
+            ServerMessage::Subscribed { stream_id, .. }
+            | ServerMessage::SubscriptionAdjusted { stream_id, .. }
+            | ServerMessage::Unsubscribed { stream_id }
+            | ServerMessage::MarketData { stream_id, .. }
+            | ServerMessage::Snapshot { stream_id, .. }
+            | ServerMessage::MarketSummary { stream_id, .. }
+            | ServerMessage::ReferenceData { stream_id, .. }
+            | ServerMessage::InstrumentStatus { stream_id, .. }
+            | ServerMessage::ReplayStarted { stream_id, .. }
+            | ServerMessage::ReplayComplete { stream_id } => Some(stream_id),
+            ServerMessage::Error { stream_id, .. } => stream_id.as_deref(),
+            ServerMessage::HeartBeat { .. }
+            | ServerMessage::UsageReport { .. }
+            | ServerMessage::SessionStarted { .. }
+            | ServerMessage::SessionRenewed { .. }
+            | ServerMessage::SessionExpiring { .. }
+            | ServerMessage::SessionExpired
+            | ServerMessage::Quota { .. }
+            | ServerMessage::StreamStats { .. }
+            | ServerMessage::Symbols { .. }
+            | ServerMessage::Validated { .. }
+            | ServerMessage::SnapshotMany { .. }
+            | ServerMessage::ProfileSaved { .. }
+            | ServerMessage::ProfileLoaded { .. }
+            | ServerMessage::FiltersSet { .. }
+            | ServerMessage::HeartbeatQuotesSet { .. }
+            | ServerMessage::SequenceMapped { .. }
+            | ServerMessage::CandleHistory { .. }
+            | ServerMessage::PaperOrderUpdate { .. }
+            | ServerMessage::PaperFill { .. }
+            | ServerMessage::QuotaExceeded { .. }
+            | ServerMessage::Throttled { .. }
+            | ServerMessage::ClockSync { .. }
+            | ServerMessage::ClockSynced { .. } => None,
+        }
+    }
+}
+
+// Item queued for a client's send task. Market data snapshots are serialized once
+// per tick and the payload bytes are shared by every subscriber of the same view.
+#[cfg(feature = "server")]
+#[derive(Debug, Clone)]
+pub enum OutboundMessage {
+    Message(ServerMessage),
+    SharedMarketData {
+        stream_id: String,
+        symbol: Arc<str>,
+        data: Bytes,
+        sequence: u64,
+        epoch: u64,
+        timestamp: DateTime<Utc>,
+        event_ts: DateTime<Utc>,
+        anchor: Option<BookAnchor>,
+        venue_sequence: Option<u64>,
+    },
+    // Ends the connection with a Close frame carrying the reason
+    Disconnect(DisconnectReason),
+}
+
+#[cfg(feature = "server")]
+impl From<ServerMessage> for OutboundMessage {
+    fn from(message: ServerMessage) -> Self {
+        OutboundMessage::Message(message)
+    }
+}
+
+#[cfg(feature = "server")]
+impl OutboundMessage {
+    pub fn stream_id(&self) -> Option<&str> {
+        match self {
+            OutboundMessage::Message(message) => message.stream_id(),
+            OutboundMessage::SharedMarketData { stream_id, .. } => Some(stream_id),
+            OutboundMessage::Disconnect(_) => None,
+        }
+    }
+
+    // Symbol of market data and snapshots
+    pub fn symbol(&self) -> Option<&str> {
+        match self {
+            OutboundMessage::Message(ServerMessage::MarketData { symbol, .. })
+            | OutboundMessage::SharedMarketData { symbol, .. } => Some(symbol),
+            OutboundMessage::Message(ServerMessage::Snapshot { symbol, .. }) => Some(symbol),
+            OutboundMessage::Message(_) | OutboundMessage::Disconnect(_) => None,
+        }
+    }
+
+    // When the book behind market data last changed
+    pub fn event_ts(&self) -> Option<DateTime<Utc>> {
+        match self {
+            OutboundMessage::Message(ServerMessage::MarketData { event_ts, .. })
+            | OutboundMessage::SharedMarketData { event_ts, .. } => Some(*event_ts),
+            OutboundMessage::Message(_) | OutboundMessage::Disconnect(_) => None,
+        }
+    }
+
+    pub fn sequence(&self) -> Option<u64> {
+        match self {
+            OutboundMessage::Message(ServerMessage::MarketData { sequence, .. } | ServerMessage::Snapshot { sequence, .. })
+            | OutboundMessage::SharedMarketData { sequence, .. } => Some(*sequence),
+            OutboundMessage::Message(_) | OutboundMessage::Disconnect(_) => None,
+        }
+    }
+
+    // Market data and summaries, as opposed to control messages; the part metered by quotas
+    pub fn is_stream_update(&self) -> bool {
+        matches!(
+            self,
+            OutboundMessage::SharedMarketData { .. }
+                | OutboundMessage::Message(
+                    ServerMessage::MarketData { .. }
+                        | ServerMessage::Snapshot { .. }
+                        | ServerMessage::MarketSummary { .. }
+                        | ServerMessage::ReferenceData { .. }
+                )
+        )
+    }
+
+    // Serializes market data with the same JSON as ServerMessage::MarketData, stamping
+    // send_ts now and holding event_ts in order for the client's stream. `tag_latency`
+    // adds latency_us, measured from the unordered event_ts.
+    pub fn to_json(&self, ordering: &mut EventOrdering, tag_latency: bool) -> serde_json::Result<String> {
+        match self {
+            OutboundMessage::Message(ServerMessage::MarketData {
+                stream_id, symbol, data, sequence, epoch, timestamp, event_ts, anchor, venue_sequence, ..
+            }) => {
+                let data = serde_json::to_string(data)?;
+                market_data_json(stream_id, symbol, &data, *sequence, *epoch, timestamp, *event_ts, ordering, anchor, *venue_sequence, tag_latency)
+            }
+            OutboundMessage::Message(message) => {
+                if let ServerMessage::Unsubscribed { stream_id } = message {
+                    ordering.forget(stream_id);
+                }
+                serde_json::to_string(message)
+            }
+            OutboundMessage::SharedMarketData { stream_id, symbol, data, sequence, epoch, timestamp, event_ts, anchor, venue_sequence } => {
+                let data = std::str::from_utf8(data).map_err(serde::ser::Error::custom)?;
+                market_data_json(stream_id, symbol, data, *sequence, *epoch, timestamp, *event_ts, ordering, anchor, *venue_sequence, tag_latency)
+            }
+            OutboundMessage::Disconnect(_) => Err(serde::ser::Error::custom("Disconnects are sent as a Close frame")),
+        }
+    }
+}
+
+// Control messages jump any backlog, then full-book snapshots; indicators, flow, order
+// stats and reports wait behind book updates
+#[cfg(feature = "server")]
+impl Prioritized for OutboundMessage {
+    fn tier(&self) -> DeliveryTier {
+        match self {
+            OutboundMessage::SharedMarketData { .. } => DeliveryTier::Delta,
+            OutboundMessage::Message(ServerMessage::Snapshot { .. }) => DeliveryTier::Snapshot,
+            OutboundMessage::Message(ServerMessage::MarketData {
+                data: MarketDataUpdate::Indicators { .. } | MarketDataUpdate::AggressorFlow { .. } | MarketDataUpdate::OrderStats { .. },
+                ..
+            }) => DeliveryTier::Analytics,
+            OutboundMessage::Message(ServerMessage::MarketData { .. }) => DeliveryTier::Delta,
+            // Queued behind the replay's last update
+            OutboundMessage::Message(ServerMessage::ReplayComplete { .. }) => DeliveryTier::Delta,
+            OutboundMessage::Message(ServerMessage::MarketSummary { .. } | ServerMessage::UsageReport { .. }) => {
+                DeliveryTier::Analytics
+            }
+            OutboundMessage::Message(_) | OutboundMessage::Disconnect(_) => DeliveryTier::Control,
+        }
+    }
+
+    fn stream(&self) -> Option<&str> {
+        self.stream_id()
+    }
+
+    fn supersedes(&self) -> Option<&str> {
+        match self {
+            OutboundMessage::Message(ServerMessage::Snapshot { stream_id, .. } | ServerMessage::Unsubscribed { stream_id }) => {
+                Some(stream_id)
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "server")]
+#[allow(clippy::too_many_arguments)]
+fn market_data_json(
+    stream_id: &str,
+    symbol: &str,
+    data: &str,
+    sequence: u64,
+    epoch: u64,
+    timestamp: &DateTime<Utc>,
+    event_ts: DateTime<Utc>,
+    ordering: &mut EventOrdering,
+    anchor: &Option<BookAnchor>,
+    venue_sequence: Option<u64>,
+    tag_latency: bool,
+) -> serde_json::Result<String> {
+    let send_ts = clock().now();
+    let mut extra = match anchor {
+        Some(anchor) => format!(r#","anchor":{}"#, serde_json::to_string(anchor)?),
+        None => String::new(),
+    };
+    if tag_latency {
+        let latency_us = (send_ts - event_ts).num_microseconds().unwrap_or(i64::MAX).max(0);
+        extra.push_str(&format!(r#","latency_us":{}"#, latency_us));
+    }
+    if let Some(venue_sequence) = venue_sequence {
+        extra.push_str(&format!(r#","venue_sequence":{}"#, venue_sequence));
+    }
+    Ok(format!(
+        r#"{{"type":"MarketData","stream_id":{},"symbol":{},"data":{},"sequence":{},"epoch":{},"timestamp":{},"event_ts":{},"send_ts":{}{}}}"#,
+        serde_json::to_string(stream_id)?,
+        serde_json::to_string(symbol)?,
+        data,
+        sequence,
+        epoch,
+        serde_json::to_string(timestamp)?,
+        serde_json::to_string(&ordering.order(stream_id, event_ts))?,
+        serde_json::to_string(&send_ts)?,
+        extra,
+    ))
+}
+
+// Market data streams of one client that are sent live. Replays are left out of latency
+// measurements, since they carry the recorded event times.
+#[cfg(feature = "server")]
+#[derive(Debug, Default)]
+pub struct LiveStreams {
+    replays: HashSet<String>,
+}
+
+#[cfg(feature = "server")]
+impl LiveStreams {
+    // Whether the message is live market data, following replays as they start and end
+    pub fn is_live(&mut self, message: &OutboundMessage) -> bool {
+        match message {
+            OutboundMessage::Message(ServerMessage::ReplayStarted { stream_id, .. }) => {
+                self.replays.insert(stream_id.clone());
+                false
+            }
+            OutboundMessage::Message(ServerMessage::ReplayComplete { stream_id } | ServerMessage::Unsubscribed { stream_id }) => {
+                self.replays.remove(stream_id);
+                false
+            }
+            _ => message.event_ts().is_some() && !message.stream_id().is_some_and(|stream_id| self.replays.contains(stream_id)),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub enum DataType {
     MBO, // Market By Order
     MBP, // Market By Price
+    AggressorFlow, // Rolling aggressor volume and cumulative volume delta
+    Summary, // Cross-symbol market summary
+    Indicators { spec: IndicatorSpec }, // Technical indicators computed from candles
+    MarkPrice, // Perpetuals only: mark and index price with the estimated funding rate
+    Funding, // Perpetuals only: funding rate settlements
+    OptionQuote, // Option contracts only: simulated BBO and greeks
+    OptionChain, // Underlyings with an options chain: quotes for every contract
+    Ladder { rows: u32, tick_group: u32 }, // Fixed grid of price rows around the mid, `tick_group` ticks per row
+    LiquidityAge, // Share of the resting size on each side older than 1s, 10s and 1min
+    OrderStats, // Order lifetimes until cancel or fill, and the cancel-to-trade ratio
+    // Every order add, update, cancel and fill, sent after the book update it belongs to.
+    // Filters keep to one side, to orders within `max_distance_bps` of the mid, or to
+    // orders of at least `min_quantity`.
+    OrderActivity {
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        side: Option<Side>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        max_distance_bps: Option<f64>,
+        #[cfg_attr(feature = "ts", ts(optional = nullable))]
+        min_quantity: Option<Quantity>,
+    },
+    Quarantine, // Orders and trades the book's price band quarantined, sent as they are held back
+    Trades, // Every trade, sent after the book update it belongs to
+    MBODelta, // The whole MBO book on subscribing, then the order changes of each book update
+    MBPDelta, // The MBP view on subscribing, then only the levels each book update changed in it
+    Reference, // Cross-symbol changes of the symbol registry
+}
+
+impl DataType {
+    // The variant's name as subscriptions spell it, whatever its parameters
+    pub fn name(&self) -> &'static str {
+        match self {
+            DataType::MBO => "MBO",
+            DataType::MBP => "MBP",
+            DataType::AggressorFlow => "AggressorFlow",
+            DataType::Summary => "Summary",
+            DataType::Indicators { .. } => "Indicators",
+            DataType::MarkPrice => "MarkPrice",
+            DataType::Funding => "Funding",
+            DataType::OptionQuote => "OptionQuote",
+            DataType::OptionChain => "OptionChain",
+            DataType::Ladder { .. } => "Ladder",
+            DataType::LiquidityAge => "LiquidityAge",
+            DataType::OrderStats => "OrderStats",
+            DataType::OrderActivity { .. } => "OrderActivity",
+            DataType::Quarantine => "Quarantine",
+            DataType::Trades => "Trades",
+            DataType::MBODelta => "MBODelta",
+            DataType::MBPDelta => "MBPDelta",
+            DataType::Reference => "Reference",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 #[serde(tag = "format")]
 pub enum MarketDataUpdate {
     MBO {
@@ -64,78 +683,1281 @@ pub enum MarketDataUpdate {
         bids: Vec<MBPLevel>,
         asks: Vec<MBPLevel>,
     },
+    Pair {
+        quote: PairQuote,
+    },
+    AggressorFlow {
+        flow: AggressorFlowStats,
+    },
+    Indicators {
+        interval_secs: u64,
+        values: Vec<IndicatorValue>,
+    },
+    MarkPrice {
+        mark: MarkPrice,
+    },
+    Funding {
+        funding: FundingRate,
+    },
+    OptionQuote {
+        quote: OptionQuote,
+    },
+    OptionChain {
+        underlying: String,
+        quotes: Vec<OptionQuote>,
+    },
     OrderActivity {
         activity: OrderActivity,
     },
+    // Rows highest price first; `center_price` is the mid, or the only side's best price,
+    // and None for an empty book, which has no rows
+    Ladder {
+        center_price: Option<f64>,
+        row_size: f64,
+        rows: Vec<LadderRow>,
+    },
+    // Resting size of the `max_levels` best levels on each side
+    LiquidityAge {
+        bids: RestingAge,
+        asks: RestingAge,
+    },
+    OrderStats {
+        stats: OrderLifetimeStats,
+    },
+    Quarantine {
+        event: OutOfBandEvent,
+    },
+    Trade {
+        trade: Trade,
+    },
+    // The orders one book update changed, bringing a book at `previous_sequence` to the
+    // message's sequence
+    MBODelta {
+        previous_sequence: u64,
+        changes: Vec<OrderChange>,
+    },
+    // The levels of an MBP view that changed since the view at `previous_sequence`, best
+    // first, deleted ones flagged and with zero quantity
+    MBPDelta {
+        previous_sequence: u64,
+        bids: Vec<LevelChange>,
+        asks: Vec<LevelChange>,
+    },
+}
+
+impl MarketDataUpdate {
+    // Checksum of the book a client holds after applying this MBP or MBO view, with the
+    // orders of an MBO view summed per price
+    pub fn checksum(&self) -> Option<u32> {
+        match self {
+            MarketDataUpdate::MBP { bids, asks } => Some(book_checksum(
+                bids.iter().map(|level| (level.price, level.quantity)),
+                asks.iter().map(|level| (level.price, level.quantity)),
+            )),
+            MarketDataUpdate::MBO { bids, asks } => Some(book_checksum(order_levels(bids, true), order_levels(asks, false))),
+            _ => None,
+        }
+    }
+}
+
+// Levels per side covered by book checksums
+pub const CHECKSUM_DEPTH: usize = 25;
+
+// Checksum of a stream's book at `sequence`, for clients to check the book they rebuilt
+// without asking for a snapshot
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct BookAnchor {
+    pub sequence: u64,
+    pub checksum: u32,
+}
+
+// CRC32 of the top CHECKSUM_DEPTH levels per side, best first, interleaved as
+// `bid_price:bid_quantity:ask_price:ask_quantity:...`, a side that runs out skipped
+pub fn book_checksum(
+    bids: impl IntoIterator<Item = (f64, Quantity)>,
+    asks: impl IntoIterator<Item = (f64, Quantity)>,
+) -> u32 {
+    let mut bids = bids.into_iter().take(CHECKSUM_DEPTH);
+    let mut asks = asks.into_iter().take(CHECKSUM_DEPTH);
+    let mut fields = Vec::with_capacity(CHECKSUM_DEPTH * 4);
+
+    loop {
+        let (bid, ask) = (bids.next(), asks.next());
+        if bid.is_none() && ask.is_none() {
+            break;
+        }
+        for (price, quantity) in [bid, ask].into_iter().flatten() {
+            fields.push(price.to_string());
+            fields.push(quantity.to_string());
+        }
+    }
+
+    crc32fast::hash(fields.join(":").as_bytes())
+}
+
+// Rolling CRC32 over the deltas of a stream since its last snapshot or anchor, which
+// restart it from 0. Each delta continues the previous value with its levels as sent,
+// `b<price>:<quantity>:` for each bid and then `a<price>:<quantity>:` for each ask.
+pub fn delta_checksum(
+    previous: u32,
+    bids: impl IntoIterator<Item = (f64, Quantity)>,
+    asks: impl IntoIterator<Item = (f64, Quantity)>,
+) -> u32 {
+    let mut hasher = crc32fast::Hasher::new_with_initial(previous);
+    let bids = bids.into_iter().map(|level| ('b', level));
+    let asks = asks.into_iter().map(|level| ('a', level));
+    for (side, (price, quantity)) in bids.chain(asks) {
+        hasher.update(format!("{}{}:{}:", side, price, quantity).as_bytes());
+    }
+    hasher.finalize()
+}
+
+// Orders summed into price levels, best first
+fn order_levels(orders: &[MBOLevel], bids: bool) -> Vec<(f64, Quantity)> {
+    let mut levels: Vec<(f64, Quantity)> = Vec::new();
+    for order in orders {
+        match levels.iter_mut().find(|(price, _)| *price == order.price) {
+            Some((_, quantity)) => *quantity += order.quantity,
+            None => levels.push((order.price, order.quantity)),
+        }
+    }
+    levels.sort_by(|a, b| if bids { b.0.total_cmp(&a.0) } else { a.0.total_cmp(&b.0) });
+    levels
+}
+
+// Numeric order identifier used inside the book; serialized as a string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(type = "string"))]
+pub struct OrderId(pub u64);
+
+impl fmt::Display for OrderId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Serialize for OrderId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for OrderId {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let id = String::deserialize(deserializer)?;
+        id.parse().map(OrderId).map_err(serde::de::Error::custom)
+    }
+}
+
+// Order size as a fixed-point number with Quantity::DECIMALS places, so fractional sizes
+// such as 0.05 BTC add up exactly. Serialized as a JSON number of units, or as a decimal
+// string such as "123456789.12345678" when no JSON number parses back to it exactly;
+// strings are accepted for any quantity. Arithmetic saturates rather than wrapping.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS), ts(type = "number | string"))]
+pub struct Quantity(u64);
+
+impl Quantity {
+    pub const DECIMALS: u32 = 8;
+    pub const ZERO: Quantity = Quantity(0);
+    // Largest size one order or trade may have, so that a book's levels and volumes add
+    // up far from what a quantity holds
+    pub const MAX_ORDER: Quantity = Quantity::from_units(1_000_000_000);
+    const SCALE: u64 = 10u64.pow(Self::DECIMALS);
+
+    // MAX for more units than a quantity holds
+    pub const fn from_units(units: u64) -> Self {
+        Self(units.saturating_mul(Self::SCALE))
+    }
+
+    // `lots` increments of 10^-decimals, e.g. 5 lots at 2 decimals is 0.05
+    pub fn from_lots(lots: u64, decimals: u32) -> Self {
+        Self(lots.saturating_mul(Self::lot_scale(decimals)))
+    }
+
+    // Whole increments of 10^-decimals, rounding down
+    pub fn lots(self, decimals: u32) -> u64 {
+        self.0 / Self::lot_scale(decimals)
+    }
+
+    // Nearest quantity to a non-negative finite value
+    pub fn from_f64(value: f64) -> Option<Self> {
+        let scaled = (value * Self::SCALE as f64).round();
+        (scaled.is_finite() && scaled >= 0.0 && scaled < u64::MAX as f64).then_some(Self(scaled as u64))
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.0 as f64 / Self::SCALE as f64
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.0 == 0
+    }
+
+    // Whether the quantity is a whole number of 10^-decimals increments
+    pub fn has_decimals(self, decimals: u32) -> bool {
+        self.0.is_multiple_of(Self::lot_scale(decimals))
+    }
+
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.0.checked_add(rhs.0).map(Self)
+    }
+
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.0.checked_sub(rhs.0).map(Self)
+    }
+
+    pub fn saturating_sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+
+    fn lot_scale(decimals: u32) -> u64 {
+        10u64.pow(Self::DECIMALS - decimals.min(Self::DECIMALS))
+    }
+}
+
+impl std::ops::Add for Quantity {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0.saturating_add(rhs.0))
+    }
+}
+
+impl std::ops::Sub for Quantity {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0.saturating_sub(rhs.0))
+    }
+}
+
+impl std::ops::AddAssign for Quantity {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl std::ops::SubAssign for Quantity {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl std::iter::Sum for Quantity {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::ZERO, |total, quantity| total + quantity)
+    }
+}
+
+// Shortest exact decimal: 1500, 0.05
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (units, fraction) = (self.0 / Self::SCALE, self.0 % Self::SCALE);
+        if fraction == 0 {
+            return f.pad(&units.to_string());
+        }
+
+        let fraction = format!("{:0width$}", fraction, width = Self::DECIMALS as usize);
+        f.pad(&format!("{}.{}", units, fraction.trim_end_matches('0')))
+    }
+}
+
+impl std::str::FromStr for Quantity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid quantity '{}'", s);
+        let (units, fraction) = s.trim().split_once('.').unwrap_or((s.trim(), ""));
+        if units.is_empty() && fraction.is_empty() {
+            return Err(invalid());
+        }
+        if fraction.len() > Self::DECIMALS as usize {
+            return Err(format!("Quantity '{}' has more than {} decimals", s, Self::DECIMALS));
+        }
+
+        let digits = |part: &str| -> Result<u64, String> {
+            if !part.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(invalid());
+            }
+            Ok(if part.is_empty() { 0 } else { part.parse().map_err(|_| invalid())? })
+        };
+        let (units, fraction) = (digits(units)?, digits(fraction)? * 10u64.pow(Self::DECIMALS - fraction.len() as u32));
+
+        units
+            .checked_mul(Self::SCALE)
+            .and_then(|units| units.checked_add(fraction))
+            .map(Self)
+            .ok_or_else(invalid)
+    }
+}
+
+impl Serialize for Quantity {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.0.is_multiple_of(Self::SCALE) {
+            return serializer.serialize_u64(self.0 / Self::SCALE);
+        }
+        // The nearest f64 is written as the shortest decimal that parses back to it, which
+        // rounds to this quantity again unless it has too many significant digits
+        let value = self.to_f64();
+        if Quantity::from_f64(value) == Some(*self) {
+            serializer.serialize_f64(value)
+        } else {
+            serializer.collect_str(self)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Quantity {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct QuantityVisitor;
+
+        impl serde::de::Visitor<'_> for QuantityVisitor {
+            type Value = Quantity;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("a non-negative quantity")
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, value: u64) -> Result<Quantity, E> {
+                value
+                    .checked_mul(Quantity::SCALE)
+                    .map(Quantity)
+                    .ok_or_else(|| E::custom(format!("Quantity {} is too large", value)))
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, value: i64) -> Result<Quantity, E> {
+                let value = u64::try_from(value).map_err(|_| E::custom("Quantity must not be negative"))?;
+                self.visit_u64(value)
+            }
+
+            fn visit_f64<E: serde::de::Error>(self, value: f64) -> Result<Quantity, E> {
+                Quantity::from_f64(value).ok_or_else(|| E::custom(format!("Invalid quantity {}", value)))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, value: &str) -> Result<Quantity, E> {
+                value.parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(QuantityVisitor)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub struct MBOLevel {
-    pub order_id: String,
+    pub order_id: OrderId,
     pub price: f64,
-    pub quantity: u64,
+    pub quantity: Quantity,
     pub side: Side,
     pub timestamp: DateTime<Utc>,
     pub age_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub actor: Option<ActorClass>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub struct MBPLevel {
     pub price: f64,
-    pub quantity: u64,
+    pub quantity: Quantity,
     pub order_count: u32,
     pub side: Side,
-    pub total_quantity: u64,
+    pub total_quantity: Quantity,
     pub avg_age_ms: u64,
 }
 
+// One row of a depth-of-market ladder: the resting size and orders priced from `price`
+// up to the next row. Rows without orders are sent with zero sizes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct LadderRow {
+    pub price: f64,
+    pub bid_quantity: Quantity,
+    pub bid_orders: u32,
+    pub ask_quantity: Quantity,
+    pub ask_orders: u32,
+}
+
+// Resting size of one side of a book and how much of it has rested at least each
+// bucket's age. An order's age restarts whenever its size changes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct RestingAge {
+    pub quantity: Quantity,
+    pub order_count: u32,
+    pub older_than: Vec<AgeBucket>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct AgeBucket {
+    pub age_ms: u64,
+    pub quantity: Quantity,
+    pub order_count: u32,
+    pub share: f64, // Of the side's resting size, 0 for an empty side
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub struct OrderActivity {
     pub activity_type: ActivityType,
-    pub order_id: String,
-    pub symbol: String,
+    pub order_id: OrderId,
+    pub symbol: Arc<str>,
     pub price: Option<f64>,
-    pub quantity: Option<u64>,
+    pub quantity: Option<Quantity>,
     pub side: Option<Side>,
     pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub actor: Option<ActorClass>,
+    // Sequence of the feed event behind it, on books fed through the ingest API
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub venue_sequence: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum OrderChangeAction {
+    Add,
+    Update,
+    Delete,
 }
 
+// An order's state after a change, so applying a change twice does no harm: an added
+// order with its side, price and size, the new size of a resting one, or its removal
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct OrderChange {
+    pub action: OrderChangeAction,
+    pub order_id: OrderId,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub side: Option<Side>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub price: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub quantity: Option<Quantity>,
+}
+
+impl OrderChange {
+    // Fills are updates to the remaining size, or deletes once nothing remains. None for
+    // an add without its side, price or size.
+    pub fn from_activity(activity: &OrderActivity) -> Option<Self> {
+        let change = |action, side, price, quantity| OrderChange { action, order_id: activity.order_id, side, price, quantity };
+        match activity.activity_type {
+            ActivityType::Add => {
+                let (Some(side), Some(price), Some(quantity)) = (&activity.side, activity.price, activity.quantity) else {
+                    return None;
+                };
+                Some(change(OrderChangeAction::Add, Some(side.clone()), Some(price), Some(quantity)))
+            }
+            ActivityType::Update | ActivityType::Fill => match activity.quantity {
+                Some(quantity) if !quantity.is_zero() => Some(change(OrderChangeAction::Update, None, None, Some(quantity))),
+                _ => Some(change(OrderChangeAction::Delete, None, None, None)),
+            },
+            ActivityType::Cancel => Some(change(OrderChangeAction::Delete, None, None, None)),
+        }
+    }
+}
+
+// An order or trade kept out of its book for a price too far from the book's reference
+// price, the mid or else the last trade
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct OutOfBandEvent {
+    pub event: OutOfBandKind,
+    pub order_id: Option<OrderId>, // None for a trade sweeping the book
+    pub symbol: Arc<str>,
+    pub side: Option<Side>, // Of the order, or of a trade's aggressor when the feed named it
+    pub price: f64, // For a trade, the furthest price it would have traded at
+    pub quantity: Quantity,
+    pub reference_price: f64,
+    pub band_pct: f64,
+    pub action: BandAction,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub venue_sequence: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum OutOfBandKind {
+    Add,
+    Modify, // A move to a new price
+    Trade,
+}
+
+// What becomes of an event outside its band: either way it leaves the book unchanged, and a
+// quarantined one is also published on the symbol's Quarantine streams
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum BandAction {
+    #[default]
+    Reject,
+    Quarantine,
+}
+
+// Simulated participant an order is attributed to with --research-actor-tags: makers join
+// or improve the touch, takers post marketable orders and noise traders rest behind it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum ActorClass {
+    Maker,
+    Taker,
+    Noise,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub enum ActivityType {
     Add,
     Update,
     Cancel,
+    Fill, // Resting order traded against; quantity is the remaining size
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct Trade {
+    pub trade_id: String,
+    pub symbol: Arc<str>,
+    pub price: f64,
+    pub quantity: Quantity,
+    pub aggressor_side: Side,
+    pub maker_order_id: OrderId,
+    pub timestamp: DateTime<Utc>,
 }
 
+// Best bid and ask of a book and its last trade, as heartbeats carry them
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct TopOfBook {
+    pub symbol: String,
+    pub bid: Option<f64>,
+    pub bid_quantity: Option<Quantity>,
+    pub ask: Option<f64>,
+    pub ask_quantity: Option<Quantity>,
+    pub last_trade: Option<LastTrade>, // None until the book has traded
+}
+
+// Book sequence a feed event was published at, next to the venue's own sequence of it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct SequenceMapping {
+    pub sequence: u64,
+    pub venue_sequence: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum PaperOrderStatus {
+    Open,
+    Filled,
+    // By the client, or with the unfilled rest of a market order or the book
+    Cancelled,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum PaperLiquidity {
+    Maker,
+    Taker,
+}
+
+// A paper order as last changed; `limit_price` is None for market orders
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct PaperOrder {
+    pub order_id: u64,
+    pub client_order_id: Option<String>,
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: Quantity,
+    pub limit_price: Option<f64>,
+    pub filled: Quantity,
+    pub average_price: Option<f64>,
+    pub fees: f64,
+    pub status: PaperOrderStatus,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Quantity of a paper order filled at one price, and the fee charged for it in quote
+// currency, negative for a rebate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct PaperFill {
+    pub order_id: u64,
+    pub symbol: String,
+    pub side: Side,
+    pub price: f64,
+    pub quantity: Quantity,
+    pub fee: f64,
+    pub liquidity: PaperLiquidity,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct LastTrade {
+    pub price: f64,
+    pub quantity: Quantity,
+    pub aggressor_side: Side,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct AggressorFlowStats {
+    pub window_secs: u64,
+    pub buy_volume: Quantity,
+    pub sell_volume: Quantity,
+    pub delta: f64,
+    pub cumulative_buy_volume: Quantity,
+    pub cumulative_sell_volume: Quantity,
+    pub cvd: f64,
+    pub trade_count: u64,
+}
+
+// Outcomes of the orders seen since the book was created. Lifetime percentiles cover the
+// last `sample_size` orders of each outcome; the ratio is None until the first trade.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct OrderLifetimeStats {
+    pub orders_added: u64,
+    pub live_orders: u64,
+    pub cancelled: LifetimeStats,
+    pub filled: LifetimeStats, // Orders traded down to nothing
+    pub partial_fills: u64,
+    pub trades: u64,
+    pub cancel_to_trade_ratio: Option<f64>,
+    pub sample_size: u32,
+}
+
+// Lifetimes are None until an order added after tracking began reaches this outcome
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct LifetimeStats {
+    pub count: u64,
+    pub mean_ms: Option<f64>,
+    pub p50_ms: Option<f64>,
+    pub p90_ms: Option<f64>,
+}
+
+// Mark price is the index plus the smoothed basis of the book mid over the index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct MarkPrice {
+    pub mark_price: f64,
+    pub index_price: f64,
+    pub basis: f64,
+    pub estimated_funding_rate: f64, // Rate the next settlement would apply at the current premium
+    pub next_funding_time: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct FundingRate {
+    pub funding_rate: f64,
+    pub funding_time: DateTime<Utc>,
+    pub mark_price: f64,
+    pub index_price: f64,
+}
+
+// OHLCV of the mid price over one interval, as served by /candles and CandleHistory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct Candle {
+    pub open_time: DateTime<Utc>,
+    pub interval_secs: u64,
+    // Prices are null in gap candles of the `empty` policy
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub open: f64,
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub high: f64,
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub low: f64,
+    #[cfg_attr(feature = "ts", ts(type = "number | null"))]
+    pub close: f64,
+    pub volume: Quantity,
+    // Synthesized for an interval without updates by a CandleGapFill policy
+    pub gap: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum OptionKind {
+    Call,
+    Put,
+}
+
+// Quote of one European option priced with Black-Scholes off the underlying's mid.
+// Vega and rho are per percentage point of volatility and rate, theta per calendar day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct OptionQuote {
+    pub contract: String,
+    pub underlying: String,
+    pub kind: OptionKind,
+    pub strike: f64,
+    pub expiry: DateTime<Utc>,
+    pub underlying_price: f64,
+    pub bid: Option<f64>, // None when the bid would round to zero
+    pub ask: f64,
+    pub bid_size: u64,
+    pub ask_size: u64,
+    pub mark_price: f64,
+    pub implied_vol: f64,
+    pub delta: f64,
+    pub gamma: f64,
+    pub vega: f64,
+    pub theta: f64,
+    pub rho: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum TradingStatus {
+    Trading,
+    Halted, // Expired and settled; the book no longer changes
+    Delisted,
+}
+
+// Dated futures contract. Prices are per unit of the underlying, so one contract is
+// worth price × contract_size.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct FutureContract {
+    pub symbol: String,
+    pub root: String,
+    pub expiry: DateTime<Utc>,
+    pub contract_size: f64,
+    pub status: TradingStatus,
+    pub mid_price: Option<f64>,
+    pub settlement_price: Option<f64>, // Mid price at expiry
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct IndicatorSpec {
+    #[serde(default = "default_indicator_interval")]
+    pub interval_secs: u64,
+    pub indicators: Vec<Indicator>,
+}
+
+fn default_indicator_interval() -> u64 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(tag = "kind")]
+pub enum Indicator {
+    EMA { period: u32 },
+    RSI { period: u32 },
+    Bollinger {
+        period: u32,
+        #[serde(default = "default_bollinger_std_dev")]
+        std_dev: f64,
+    },
+}
+
+fn default_bollinger_std_dev() -> f64 {
+    2.0
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(tag = "indicator")]
+pub enum IndicatorValue {
+    EMA {
+        period: u32,
+        value: Option<f64>,
+    },
+    RSI {
+        period: u32,
+        value: Option<f64>,
+    },
+    Bollinger {
+        period: u32,
+        std_dev: f64,
+        middle: Option<f64>,
+        upper: Option<f64>,
+        lower: Option<f64>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum PairKind {
+    Ratio,
+    Spread,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct PairQuote {
+    pub pair: String,
+    pub kind: PairKind,
+    pub base: String,
+    pub quote: String,
+    pub bid: Option<f64>,
+    pub ask: Option<f64>,
+    pub value: Option<f64>,
+    pub spread: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct SymbolSummary {
+    pub symbol: String,
+    pub last_price: Option<f64>,
+    pub open_price: Option<f64>,
+    pub change_pct: Option<f64>,
+    pub volume: Quantity,
+    pub spread: Option<f64>,
+    pub spread_bps: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolType {
+    Spot,
+    Pair,
+    Perpetual,
+    Future,
+    Option,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum SymbolStatus {
+    Open,
+    Halted,
+}
+
+// A stream as a Subscribe message would ask for it, checked by ValidateSubscribe and
+// POST /validate
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct StreamSpec {
+    pub stream_id: String,
+    pub symbol: String,
+    pub data_type: DataType,
+    #[cfg_attr(feature = "ts", ts(optional = nullable))]
+    pub max_levels: Option<u32>,
+    #[cfg_attr(feature = "ts", ts(optional = nullable))]
+    pub max_orders: Option<u32>,
+    #[cfg_attr(feature = "ts", ts(optional = nullable))]
+    pub snapshot_interval_ms: Option<u64>,
+    #[cfg_attr(feature = "ts", ts(optional = nullable))]
+    pub priority: Option<StreamPriority>,
+    #[cfg_attr(feature = "ts", ts(optional = nullable))]
+    pub sample_every_n: Option<u32>,
+}
+
+// Whether a StreamSpec would be subscribed, with the defaults Subscribe fills in.
+// `exists` is false when subscribing would create the symbol's book.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct StreamValidation {
+    pub stream_id: String,
+    pub symbol: String,
+    pub data_type: DataType,
+    pub valid: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "ts", ts(optional))]
+    pub error: Option<String>,
+    pub max_levels: u32,
+    pub max_orders: u32,
+    pub snapshot_interval_ms: Option<u64>,
+    pub priority: StreamPriority,
+    pub sample_every_n: Option<u32>,
+    pub exists: bool,
+}
+
+// MBP view of one book at `sequence`, as listed by GET /depth and SnapshotMany
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct BookSnapshot {
+    pub symbol: String,
+    pub data: MarketDataUpdate,
+    pub sequence: u64,
+    pub epoch: u64,
+    pub event_ts: DateTime<Utc>,
+}
+
+// Books of several symbols; `missing` are those asked for that have no book
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct BulkSnapshot {
+    pub snapshots: Vec<BookSnapshot>,
+    pub missing: Vec<String>,
+}
+
+// Discovery metadata for one subscribable symbol. Base and quote are the currencies
+// of a spot-style name such as BTCUSD, or the two legs of a synthetic pair.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct SymbolInfo {
+    pub symbol: String,
+    #[serde(rename = "type")]
+    pub symbol_type: SymbolType,
+    pub status: SymbolStatus,
+    pub venue: String,
+    pub base: Option<String>,
+    pub quote: Option<String>,
+    pub tick_size: Option<f64>, // None for synthetic pairs, whose prices are not on a grid
+    pub quantity_decimals: Option<u32>, // Decimals order sizes are quoted to; None for synthetic pairs
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(rename_all = "lowercase")]
+pub enum ReferenceChangeKind {
+    Added,
+    Removed,
+    Halted,
+    Resumed,
+    Updated, // Any other field, such as the tick size or quantity decimals
+}
+
+// One change of a symbol's metadata; `info` is the symbol as it is now, and null once removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct ReferenceChange {
+    pub version: u64,
+    pub change: ReferenceChangeKind,
+    pub symbol: String,
+    pub info: Option<SymbolInfo>,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Every listed symbol at `version`, by name, as answered by GET /reference
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct ReferenceSnapshot {
+    pub version: u64,
+    pub symbols: Vec<SymbolInfo>,
+}
+
+// Changes after the version a client holds, up to `version`, oldest first
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct ReferenceChanges {
+    pub version: u64,
+    pub changes: Vec<ReferenceChange>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct MarketSummary {
+    pub top_gainers: Vec<SymbolSummary>,
+    pub top_losers: Vec<SymbolSummary>,
+    pub highest_volume: Vec<SymbolSummary>,
+    pub widest_spreads: Vec<SymbolSummary>,
+    pub symbol_count: usize,
+    pub timestamp: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct StreamUsage {
+    pub stream_id: String,
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum QuotaPeriod {
+    Daily,
+    Monthly,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum QuotaUnit {
+    Messages,
+    Bytes,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct QuotaRemaining {
+    pub period: QuotaPeriod,
+    pub unit: QuotaUnit,
+    pub limit: u64,
+    pub used: u64,
+    pub remaining: u64,
+    pub resets_at: DateTime<Utc>,
+}
+
+// Delivery of one of the client's streams since it was subscribed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct StreamStats {
+    pub stream_id: String,
+    pub subscribed_at: DateTime<Utc>,
+    pub messages_sent: u64,
+    pub last_sequence: Option<u64>, // Of the last update sent
+    pub conflated: u64, // Updates held back by load shedding or quota throttling
+    pub conflation_ratio: f64, // Share of the stream's updates that were conflated
+    pub dropped: u64, // Updates discarded from the outbound queue, superseded by a full-book snapshot
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct QuotaStatus {
+    pub api_key: String,
+    pub quotas: Vec<QuotaRemaining>,
+    pub throttled: bool,
+    pub exceeded: bool,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Low-priority streams are the first to be conflated when the server is under load
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub enum StreamPriority {
+    #[default]
+    Normal,
+    Low,
+}
+
+// Offset of the client's clock from the server's, estimated from one clock-sync exchange
+// on the assumption that the round trip took as long each way. Positive when the
+// client's clock is ahead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+pub struct ClockOffset {
+    pub offset_us: i64,
+    pub round_trip_us: u64, // The error of the estimate is at most half of it
+    pub measured_at: DateTime<Utc>, // Server time
+}
+
+impl ClockOffset {
+    // Server time corresponding to a time read from the client's clock, e.g. to compare a
+    // receive time with `event_ts`
+    pub fn to_server_time(&self, client_time: DateTime<Utc>) -> DateTime<Utc> {
+        client_time - chrono::Duration::microseconds(self.offset_us)
+    }
+}
+
+// Why the server ended a connection. WebSocket clients get it as the close code and
+// reason of the Close frame, SSE clients as a terminal `error` event; a connection that
+// ends without one was lost to the network.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectReason {
+    // The server is shutting down; reconnect to another instance or after a delay
+    ServerShutdown,
+    // The session expired and the connection could not continue unauthenticated; reconnect with fresh credentials
+    SessionExpired,
+    // The client fell too far behind its updates; reconnect and consume faster, or subscribe to less
+    SlowConsumer,
+    // An operator disconnected the client; do not reconnect automatically
+    Kicked,
+}
+
+impl DisconnectReason {
+    pub const ALL: [DisconnectReason; 4] = [
+        DisconnectReason::ServerShutdown,
+        DisconnectReason::SessionExpired,
+        DisconnectReason::SlowConsumer,
+        DisconnectReason::Kicked,
+    ];
+
+    // Shutdown uses the standard 1001 Going Away; the rest are application codes in 4000-4999
+    pub fn close_code(self) -> u16 {
+        match self {
+            DisconnectReason::ServerShutdown => 1001,
+            DisconnectReason::SessionExpired => 4001,
+            DisconnectReason::SlowConsumer => 4002,
+            DisconnectReason::Kicked => 4003,
+        }
+    }
+
+    pub fn from_close_code(code: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|reason| reason.close_code() == code)
+    }
+
+    // The close frame's reason string, and the `reason` of the SSE event
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DisconnectReason::ServerShutdown => "server_shutdown",
+            DisconnectReason::SessionExpired => "session_expired",
+            DisconnectReason::SlowConsumer => "slow_consumer",
+            DisconnectReason::Kicked => "kicked",
+        }
+    }
+
+    pub fn should_reconnect(self) -> bool {
+        self != DisconnectReason::Kicked
+    }
+}
+
+impl fmt::Display for DisconnectReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DisconnectReason::ServerShutdown => "Server shutting down",
+            DisconnectReason::SessionExpired => "Session expired",
+            DisconnectReason::SlowConsumer => "Client too slow to keep up with its updates",
+            DisconnectReason::Kicked => "Disconnected by an operator",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "ts", derive(ts_rs::TS))]
 pub enum Side {
     Bid,
     Ask,
 }
 
+#[cfg(feature = "server")]
 #[derive(Debug, Clone)]
 pub struct Subscription {
     pub stream_id: String,
     pub symbol: String,
     pub data_type: DataType,
     pub max_levels: u32,
+    pub max_orders: u32,
     pub client_id: Uuid,
+    pub priority: StreamPriority,
+    pub sampler: Option<Arc<UpdateSampler>>,
+    pub level_base: Option<Arc<LevelBase>>,
 }
 
+#[cfg(feature = "server")]
 impl Subscription {
     pub fn new(
         stream_id: String,
         symbol: String,
         data_type: DataType,
         max_levels: Option<u32>,
+        max_orders: Option<u32>,
         client_id: Uuid,
     ) -> Self {
+        let max_levels = max_levels.unwrap_or(20);
+
         Self {
             stream_id,
             symbol,
             data_type,
-            max_levels: max_levels.unwrap_or(20),
+            max_levels,
+            max_orders: max_orders.unwrap_or_else(|| default_max_orders(max_levels)),
             client_id,
+            priority: StreamPriority::Normal,
+            sampler: None,
+            level_base: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quantity_parses_decimal_strings() {
+        assert_eq!("0.05".parse::<Quantity>().unwrap(), Quantity::from_lots(5, 2));
+        assert_eq!("1500".parse::<Quantity>().unwrap(), Quantity::from_units(1500));
+        assert_eq!(".5".parse::<Quantity>().unwrap(), Quantity::from_lots(5, 1));
+        assert_eq!(" 2. ".parse::<Quantity>().unwrap(), Quantity::from_units(2));
+        for invalid in ["", ".", "-1", "1e3", "0.123456789", "1.2.3", "184467440738"] {
+            assert!(invalid.parse::<Quantity>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn quantity_displays_the_shortest_exact_decimal() {
+        assert_eq!(Quantity::from_units(1500).to_string(), "1500");
+        assert_eq!(Quantity::from_lots(5, 2).to_string(), "0.05");
+        assert_eq!(Quantity::from_lots(12_345_678_912_345_678, 8).to_string(), "123456789.12345678");
+    }
+
+    #[test]
+    fn quantity_serializes_exactly() {
+        let json = |quantity: Quantity| serde_json::to_string(&quantity).unwrap();
+        assert_eq!(json(Quantity::from_units(1500)), "1500");
+        assert_eq!(json(Quantity::from_lots(5, 2)), "0.05");
+        let large = Quantity::from_lots(1_234_567_891_234_567_891, 8);
+        assert_eq!(json(large), "\"12345678912.34567891\"");
+
+        for quantity in [Quantity::ZERO, Quantity::from_lots(5, 2), Quantity::from_lots(1, 8), large, Quantity::MAX_ORDER] {
+            assert_eq!(serde_json::from_str::<Quantity>(&json(quantity)).unwrap(), quantity);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn quantity_deserializes_numbers_and_strings() {
+        let parse = |json: &str| serde_json::from_str::<Quantity>(json);
+        assert_eq!(parse("250").unwrap(), Quantity::from_units(250));
+        assert_eq!(parse("0.1").unwrap(), Quantity::from_lots(1, 1));
+        assert_eq!(parse("\"0.00000001\"").unwrap(), Quantity::from_lots(1, 8));
+        assert!(parse("-1").is_err());
+        assert!(parse("\"abc\"").is_err());
+        assert!(parse("184467440738").is_err());
+    }
+
+    #[test]
+    fn quantity_arithmetic_saturates() {
+        let huge = Quantity::from_units(u64::MAX);
+        assert_eq!(huge + Quantity::from_units(1), huge);
+        assert_eq!(Quantity::from_units(1) - Quantity::from_units(2), Quantity::ZERO);
+        assert_eq!(huge.checked_add(Quantity::from_lots(1, 8)), None);
+        assert_eq!(Quantity::ZERO.checked_sub(Quantity::from_lots(1, 8)), None);
+
+        let mut total = Quantity::from_lots(5, 2);
+        total += Quantity::from_lots(5, 2);
+        total -= Quantity::from_lots(1, 2);
+        assert_eq!(total, Quantity::from_lots(9, 2));
+        assert_eq!([Quantity::from_lots(1, 1); 3].into_iter().sum::<Quantity>(), Quantity::from_lots(3, 1));
+    }
+
+    #[test]
+    fn quantity_lots_follow_decimals() {
+        let quantity = Quantity::from_lots(1234, 2);
+        assert_eq!(quantity.lots(2), 1234);
+        assert_eq!(quantity.lots(0), 12);
+        assert!(quantity.has_decimals(2));
+        assert!(!quantity.has_decimals(1));
+        assert_eq!(Quantity::from_f64(12.34), Some(quantity));
+        assert_eq!(Quantity::from_f64(-1.0), None);
+        assert_eq!(Quantity::from_f64(f64::NAN), None);
+    }
+
+    #[test]
+    fn book_checksum_interleaves_sides_best_first() {
+        let bids = [(100.5, Quantity::from_units(3)), (100.0, Quantity::from_lots(5, 2))];
+        let asks = [(101.0, Quantity::from_units(2))];
+        assert_eq!(book_checksum(bids, asks), crc32fast::hash(b"100.5:3:101:2:100:0.05"));
+        assert_eq!(book_checksum([], []), crc32fast::hash(b""));
+
+        let deep = (0..CHECKSUM_DEPTH + 5).map(|index| (100.0 - index as f64, Quantity::from_units(1)));
+        let top = (0..CHECKSUM_DEPTH).map(|index| (100.0 - index as f64, Quantity::from_units(1)));
+        assert_eq!(book_checksum(deep, []), book_checksum(top, []));
+    }
+
+    #[test]
+    fn delta_checksum_restarts_from_zero() {
+        let bids = [(100.0, Quantity::ZERO)];
+        let asks = [(101.0, Quantity::from_units(4))];
+        let first = delta_checksum(0, bids, asks);
+        assert_eq!(first, crc32fast::hash(b"b100:0:a101:4:"));
+        assert_eq!(delta_checksum(first, [], asks), crc32fast::hash(b"b100:0:a101:4:a101:4:"));
+        assert_eq!(delta_checksum(0, bids, asks), first);
+    }
+}
@@ -0,0 +1,350 @@
+use std::time::{Duration, Instant};
+use dashmap::DashMap;
+use prometheus::{
+    exponential_buckets, Counter, CounterVec, Encoder, GaugeVec, Histogram, HistogramOpts, IntCounter, IntCounterVec,
+    IntGauge, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+
+use crate::compression::{Compressed, Switch};
+use crate::message::BandAction;
+
+// Core server metrics exported in the Prometheus text format on /metrics
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    connected_clients: IntGauge,
+    subscriptions: IntGaugeVec,
+    messages_sent: IntCounter,
+    bytes_sent: IntCounter,
+    fanout_latency: Histogram,
+    delivery_latency: Histogram,
+    queue_depth: Histogram,
+    tick_duration: Histogram,
+    #[cfg(feature = "alloc-audit")]
+    tick_allocations: Histogram,
+    #[cfg(feature = "alloc-audit")]
+    fanout_allocations: Histogram,
+    feed_staleness: GaugeVec,
+    task_up: IntGaugeVec,
+    task_restarts: IntCounterVec,
+    symbol_fanout_seconds: CounterVec,
+    symbol_fanouts: IntCounterVec,
+    symbol_fanout_timeouts: IntCounterVec,
+    pipeline_restarts: IntCounterVec,
+    out_of_band: IntCounterVec,
+    compression_input_bytes: IntCounter,
+    compression_output_bytes: IntCounter,
+    compression_seconds: Counter,
+    compression_fallbacks: IntCounter,
+    compression_resumes: IntCounter,
+    last_updates: DashMap<String, Instant>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let connected_clients = IntGauge::new(
+            "market_data_connected_clients",
+            "Number of connected clients",
+        ).unwrap();
+        let subscriptions = IntGaugeVec::new(
+            Opts::new("market_data_subscriptions", "Active subscriptions per symbol"),
+            &["symbol"],
+        ).unwrap();
+        let messages_sent = IntCounter::new(
+            "market_data_messages_sent_total",
+            "Messages delivered to clients",
+        ).unwrap();
+        let bytes_sent = IntCounter::new(
+            "market_data_bytes_sent_total",
+            "Serialized bytes delivered to clients",
+        ).unwrap();
+        let fanout_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_fanout_latency_seconds",
+                "Time to fan a symbol update out to all of its subscribers",
+            )
+            .buckets(exponential_buckets(0.00005, 2.0, 14).unwrap()),
+        ).unwrap();
+        let delivery_latency = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_delivery_latency_seconds",
+                "Time from a book change to the socket write of each live market data message",
+            )
+            .buckets(exponential_buckets(0.00005, 2.0, 16).unwrap()),
+        ).unwrap();
+        let queue_depth = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_client_queue_depth",
+                "Messages still queued for a client when one is dequeued for sending",
+            )
+            .buckets(vec![0.0, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0, 500.0, 1000.0]),
+        ).unwrap();
+        let tick_duration = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_simulation_tick_duration_seconds",
+                "Duration of one market simulation tick across all symbols",
+            )
+            .buckets(exponential_buckets(0.0001, 2.0, 14).unwrap()),
+        ).unwrap();
+        #[cfg(feature = "alloc-audit")]
+        let tick_allocations = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_tick_allocations",
+                "Allocations made by one market simulation tick across all symbols",
+            )
+            .buckets(exponential_buckets(1.0, 4.0, 12).unwrap()),
+        ).unwrap();
+        #[cfg(feature = "alloc-audit")]
+        let fanout_allocations = Histogram::with_opts(
+            HistogramOpts::new(
+                "market_data_fanout_allocations",
+                "Allocations made building, serializing and queueing the views of one symbol update",
+            )
+            .buckets(exponential_buckets(1.0, 4.0, 12).unwrap()),
+        ).unwrap();
+        let feed_staleness = GaugeVec::new(
+            Opts::new(
+                "market_data_feed_staleness_seconds",
+                "Seconds since the order book of a symbol was last updated",
+            ),
+            &["symbol"],
+        ).unwrap();
+        let task_up = IntGaugeVec::new(
+            Opts::new("market_data_task_up", "Whether a supervised background task is running"),
+            &["task"],
+        ).unwrap();
+        let task_restarts = IntCounterVec::new(
+            Opts::new("market_data_task_restarts_total", "Restarts of a supervised background task after it panicked"),
+            &["task"],
+        ).unwrap();
+        let symbol_fanout_seconds = CounterVec::new(
+            Opts::new("market_data_symbol_fanout_seconds_total", "Time spent fanning out the updates of a symbol"),
+            &["symbol"],
+        ).unwrap();
+        let symbol_fanouts = IntCounterVec::new(
+            Opts::new("market_data_symbol_fanouts_total", "Updates of a symbol fanned out to its subscribers"),
+            &["symbol"],
+        ).unwrap();
+        let symbol_fanout_timeouts = IntCounterVec::new(
+            Opts::new(
+                "market_data_symbol_fanout_timeouts_total",
+                "Fan-outs of a symbol update stopped by the timeout before reaching every subscriber",
+            ),
+            &["symbol"],
+        ).unwrap();
+        let pipeline_restarts = IntCounterVec::new(
+            Opts::new("market_data_symbol_pipeline_restarts_total", "Restarts of a symbol's fan-out pipeline after it panicked"),
+            &["symbol"],
+        ).unwrap();
+        let out_of_band = IntCounterVec::new(
+            Opts::new("market_data_out_of_band_events_total", "Orders and trades a book's price band rejected or quarantined"),
+            &["symbol", "action"],
+        ).unwrap();
+        let compression_input_bytes = IntCounter::new(
+            "market_data_compression_input_bytes_total",
+            "Bytes of JSON compressed for deflate connections",
+        ).unwrap();
+        let compression_output_bytes = IntCounter::new(
+            "market_data_compression_output_bytes_total",
+            "Bytes the JSON of deflate connections compressed to",
+        ).unwrap();
+        let compression_seconds = Counter::new(
+            "market_data_compression_seconds_total",
+            "Time spent compressing messages for deflate connections",
+        ).unwrap();
+        let compression_fallbacks = IntCounter::new(
+            "market_data_compression_fallbacks_total",
+            "Times deflate connections went back to text frames as compression did not pay off",
+        ).unwrap();
+        let compression_resumes = IntCounter::new(
+            "market_data_compression_resumes_total",
+            "Times deflate connections went back to compressing as it paid off again",
+        ).unwrap();
+
+        registry.register(Box::new(connected_clients.clone())).unwrap();
+        registry.register(Box::new(subscriptions.clone())).unwrap();
+        registry.register(Box::new(messages_sent.clone())).unwrap();
+        registry.register(Box::new(bytes_sent.clone())).unwrap();
+        registry.register(Box::new(fanout_latency.clone())).unwrap();
+        registry.register(Box::new(delivery_latency.clone())).unwrap();
+        registry.register(Box::new(queue_depth.clone())).unwrap();
+        registry.register(Box::new(tick_duration.clone())).unwrap();
+        #[cfg(feature = "alloc-audit")]
+        registry.register(Box::new(tick_allocations.clone())).unwrap();
+        #[cfg(feature = "alloc-audit")]
+        registry.register(Box::new(fanout_allocations.clone())).unwrap();
+        registry.register(Box::new(feed_staleness.clone())).unwrap();
+        registry.register(Box::new(task_up.clone())).unwrap();
+        registry.register(Box::new(task_restarts.clone())).unwrap();
+        registry.register(Box::new(symbol_fanout_seconds.clone())).unwrap();
+        registry.register(Box::new(symbol_fanouts.clone())).unwrap();
+        registry.register(Box::new(symbol_fanout_timeouts.clone())).unwrap();
+        registry.register(Box::new(pipeline_restarts.clone())).unwrap();
+        registry.register(Box::new(out_of_band.clone())).unwrap();
+        registry.register(Box::new(compression_input_bytes.clone())).unwrap();
+        registry.register(Box::new(compression_output_bytes.clone())).unwrap();
+        registry.register(Box::new(compression_seconds.clone())).unwrap();
+        registry.register(Box::new(compression_fallbacks.clone())).unwrap();
+        registry.register(Box::new(compression_resumes.clone())).unwrap();
+
+        Self {
+            registry,
+            connected_clients,
+            subscriptions,
+            messages_sent,
+            bytes_sent,
+            fanout_latency,
+            delivery_latency,
+            queue_depth,
+            tick_duration,
+            #[cfg(feature = "alloc-audit")]
+            tick_allocations,
+            #[cfg(feature = "alloc-audit")]
+            fanout_allocations,
+            feed_staleness,
+            task_up,
+            task_restarts,
+            symbol_fanout_seconds,
+            symbol_fanouts,
+            symbol_fanout_timeouts,
+            pipeline_restarts,
+            out_of_band,
+            compression_input_bytes,
+            compression_output_bytes,
+            compression_seconds,
+            compression_fallbacks,
+            compression_resumes,
+            last_updates: DashMap::new(),
+        }
+    }
+
+    pub fn record_sent(&self, bytes: usize) {
+        self.messages_sent.inc();
+        self.bytes_sent.inc_by(bytes as u64);
+    }
+
+    pub fn record_compression(&self, compressed: &Compressed) {
+        self.compression_input_bytes.inc_by(compressed.input_bytes as u64);
+        self.compression_output_bytes.inc_by(compressed.output_bytes as u64);
+        self.compression_seconds.inc_by(compressed.elapsed.as_secs_f64());
+    }
+
+    pub fn record_compression_switch(&self, switch: Switch) {
+        match switch {
+            Switch::FellBack => self.compression_fallbacks.inc(),
+            Switch::Resumed => self.compression_resumes.inc(),
+        }
+    }
+
+    pub fn observe_fanout(&self, elapsed: Duration) {
+        self.fanout_latency.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_delivery_latency(&self, elapsed: Duration) {
+        self.delivery_latency.observe(elapsed.as_secs_f64());
+    }
+
+    pub fn observe_queue_depth(&self, depth: usize) {
+        self.queue_depth.observe(depth as f64);
+    }
+
+    pub fn observe_tick(&self, elapsed: Duration) {
+        self.tick_duration.observe(elapsed.as_secs_f64());
+    }
+
+    #[cfg(feature = "alloc-audit")]
+    pub fn observe_tick_allocations(&self, allocations: u64) {
+        self.tick_allocations.observe(allocations as f64);
+    }
+
+    #[cfg(feature = "alloc-audit")]
+    pub fn observe_fanout_allocations(&self, allocations: u64) {
+        self.fanout_allocations.observe(allocations as f64);
+    }
+
+    pub fn mark_updated(&self, symbol: &str) {
+        self.last_updates.insert(symbol.to_string(), Instant::now());
+    }
+
+    // Drops per-symbol series of a symbol that no longer has a book
+    pub fn forget_symbol(&self, symbol: &str) {
+        self.last_updates.remove(symbol);
+        let _ = self.feed_staleness.remove_label_values(&[symbol]);
+        let _ = self.symbol_fanout_seconds.remove_label_values(&[symbol]);
+        let _ = self.symbol_fanouts.remove_label_values(&[symbol]);
+        let _ = self.symbol_fanout_timeouts.remove_label_values(&[symbol]);
+        let _ = self.pipeline_restarts.remove_label_values(&[symbol]);
+        for action in ["reject", "quarantine"] {
+            let _ = self.out_of_band.remove_label_values(&[symbol, action]);
+        }
+    }
+
+    pub fn observe_symbol_fanout(&self, symbol: &str, elapsed: Duration, timed_out: bool) {
+        self.symbol_fanout_seconds.with_label_values(&[symbol]).inc_by(elapsed.as_secs_f64());
+        self.symbol_fanouts.with_label_values(&[symbol]).inc();
+        if timed_out {
+            self.symbol_fanout_timeouts.with_label_values(&[symbol]).inc();
+        }
+    }
+
+    pub fn record_pipeline_restart(&self, symbol: &str) {
+        self.pipeline_restarts.with_label_values(&[symbol]).inc();
+    }
+
+    pub fn record_out_of_band(&self, symbol: &str, action: BandAction) {
+        let action = match action {
+            BandAction::Reject => "reject",
+            BandAction::Quarantine => "quarantine",
+        };
+        self.out_of_band.with_label_values(&[symbol, action]).inc();
+    }
+
+    pub fn set_task_up(&self, task: &str, up: bool) {
+        self.task_up.with_label_values(&[task]).set(up as i64);
+    }
+
+    pub fn record_task_restart(&self, task: &str) {
+        self.task_restarts.with_label_values(&[task]).inc();
+    }
+
+    // Drops the series of a task that finished
+    pub fn forget_task(&self, task: &str) {
+        let _ = self.task_up.remove_label_values(&[task]);
+        let _ = self.task_restarts.remove_label_values(&[task]);
+    }
+
+    pub fn set_connected_clients(&self, count: usize) {
+        self.connected_clients.set(count as i64);
+    }
+
+    // Replaces all per-symbol subscription counts so removed symbols drop out
+    pub fn set_subscriptions<'a>(&self, counts: impl IntoIterator<Item = (&'a str, usize)>) {
+        self.subscriptions.reset();
+        for (symbol, count) in counts {
+            self.subscriptions.with_label_values(&[symbol]).set(count as i64);
+        }
+    }
+
+    pub fn render(&self) -> String {
+        for entry in self.last_updates.iter() {
+            self.feed_staleness
+                .with_label_values(&[entry.key()])
+                .set(entry.value().elapsed().as_secs_f64());
+        }
+
+        let mut buffer = Vec::new();
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            return format!("# failed to encode metrics: {}\n", e);
+        }
+
+        String::from_utf8(buffer).unwrap_or_default()
+    }
+}
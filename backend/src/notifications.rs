@@ -0,0 +1,434 @@
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use futures_util::future::BoxFuture;
+use serde::Serialize;
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{interval, sleep, timeout, MissedTickBehavior};
+use tracing::warn;
+
+use crate::event_bus::{next_event, MarketEvent};
+use crate::feed_monitor::FeedMonitor;
+use crate::integrity::IntegrityViolation;
+use crate::message::TradingStatus;
+use crate::sandbox::is_sandbox;
+use crate::webhooks::check_http_url;
+
+// Incidents a sink may fall behind by before new ones are dropped
+const SINK_QUEUE: usize = 256;
+const SEND_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_ATTEMPTS: u32 = 4;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+
+// Operator notifications of server-level incidents, as opposed to the market events
+// integrators receive through webhooks
+#[derive(Debug, Clone, Default, Args)]
+pub struct NotificationOptions {
+    /// Sink notified of server incidents, repeatable: slack=URL for a Slack incoming
+    /// webhook, webhook=URL for a JSON POST, or email=ADDRESS[,ADDRESS...]
+    #[arg(long = "notify", value_name = "KIND=TARGET")]
+    pub sinks: Vec<SinkConfig>,
+
+    /// SMTP relay email notifications are handed to, without TLS or authentication
+    #[arg(long, value_name = "HOST:PORT", default_value = "127.0.0.1:25")]
+    pub smtp_relay: String,
+
+    /// Sender address of email notifications
+    #[arg(long, value_name = "ADDRESS", default_value = "market-data@localhost")]
+    pub smtp_from: String,
+
+    /// Report a book whose feed has been silent this long while it is trading
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub notify_stale_feed_secs: u64,
+}
+
+impl NotificationOptions {
+    pub fn is_enabled(&self) -> bool {
+        !self.sinks.is_empty()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum SinkConfig {
+    Slack(String),
+    Webhook(String),
+    Email(Vec<String>),
+}
+
+impl FromStr for SinkConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((kind, target)) = s.split_once('=') else {
+            return Err("Expected KIND=TARGET".to_string());
+        };
+
+        match kind {
+            "slack" => check_http_url(target).map(|()| Self::Slack(target.to_string())),
+            "webhook" => check_http_url(target).map(|()| Self::Webhook(target.to_string())),
+            "email" => {
+                let recipients: Vec<String> = target.split(',').map(str::to_string).collect();
+                match recipients.iter().find(|address| !is_address(address)) {
+                    Some(address) => Err(format!("Invalid email address '{}'", address)),
+                    None => Ok(Self::Email(recipients)),
+                }
+            }
+            _ => Err(format!("Unknown notification sink '{}', expected slack, webhook or email", kind)),
+        }
+    }
+}
+
+fn is_address(address: &str) -> bool {
+    address
+        .split_once('@')
+        .is_some_and(|(local, domain)| !local.is_empty() && !domain.is_empty() && !address.contains(['<', '>', '\r', '\n', ' ']))
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "incident", rename_all = "snake_case")]
+pub enum Incident {
+    FeedStale {
+        symbol: String,
+        last_update: DateTime<Utc>,
+    },
+    FeedRecovered {
+        symbol: String,
+        stale_secs: f64,
+    },
+    // A contract halted before its expiry, which is when halts are scheduled
+    UnexpectedHalt {
+        symbol: String,
+        contract: String,
+        expiry: DateTime<Utc>,
+    },
+    // Clients could not keep up and low-priority streams started being conflated
+    LoadShedding {
+        interval_ms: u64,
+    },
+    LoadRecovered,
+    // The book broke its own invariants; `repaired` when its levels were rebuilt
+    BookIntegrity {
+        symbol: String,
+        violations: Vec<IntegrityViolation>,
+        repaired: bool,
+    },
+}
+
+impl Incident {
+    pub fn summary(&self) -> String {
+        match self {
+            Incident::FeedStale { symbol, last_update } => {
+                format!("Feed for {} is stale, last update at {}", symbol, last_update.to_rfc3339())
+            }
+            Incident::FeedRecovered { symbol, stale_secs } => {
+                format!("Feed for {} recovered after {:.1}s without updates", symbol, stale_secs)
+            }
+            Incident::UnexpectedHalt { symbol, contract, expiry } if symbol == contract => {
+                format!("{} halted before its expiry at {}", symbol, expiry.to_rfc3339())
+            }
+            Incident::UnexpectedHalt { symbol, contract, expiry } => {
+                format!("{} halted as {} halted before its expiry at {}", symbol, contract, expiry.to_rfc3339())
+            }
+            Incident::LoadShedding { interval_ms } => {
+                format!("Server under load, low-priority streams conflated to one update per {}ms", interval_ms)
+            }
+            Incident::LoadRecovered => "Load subsided, low-priority streams back at full rate".to_string(),
+            Incident::BookIntegrity { symbol, violations, repaired } => {
+                let details: Vec<String> = violations.iter().map(ToString::to_string).collect();
+                let outcome = if *repaired { ", rebuilt" } else { "" };
+                format!("Order book {} failed its integrity check{}: {}", symbol, outcome, details.join("; "))
+            }
+        }
+    }
+}
+
+// Somewhere incidents are delivered to. Sends that fail are retried with backoff.
+pub trait NotificationSink: Send + Sync {
+    // Identifies the sink in logs
+    fn describe(&self) -> String;
+
+    fn send<'a>(&'a self, timestamp: DateTime<Utc>, incident: &'a Incident) -> BoxFuture<'a, Result<(), String>>;
+}
+
+pub struct SlackSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl NotificationSink for SlackSink {
+    fn describe(&self) -> String {
+        format!("Slack webhook {}", self.url)
+    }
+
+    fn send<'a>(&'a self, _timestamp: DateTime<Utc>, incident: &'a Incident) -> BoxFuture<'a, Result<(), String>> {
+        let body = json!({ "text": incident.summary() });
+        Box::pin(post(&self.client, &self.url, body))
+    }
+}
+
+pub struct WebhookSink {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl NotificationSink for WebhookSink {
+    fn describe(&self) -> String {
+        format!("webhook {}", self.url)
+    }
+
+    fn send<'a>(&'a self, timestamp: DateTime<Utc>, incident: &'a Incident) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let mut body = serde_json::to_value(incident).map_err(|e| e.to_string())?;
+            body["timestamp"] = json!(timestamp);
+            body["summary"] = json!(incident.summary());
+            post(&self.client, &self.url, body).await
+        })
+    }
+}
+
+async fn post(client: &reqwest::Client, url: &str, body: serde_json::Value) -> Result<(), String> {
+    let response = client
+        .post(url)
+        .header("content-type", "application/json")
+        .body(body.to_string())
+        .send()
+        .await
+        .map_err(|e| e.to_string())?;
+    if !response.status().is_success() {
+        return Err(response.status().to_string());
+    }
+    Ok(())
+}
+
+// Plain-text mail handed to an SMTP relay, which does any TLS, authentication and onward delivery
+pub struct EmailSink {
+    relay: String,
+    from: String,
+    recipients: Vec<String>,
+}
+
+impl NotificationSink for EmailSink {
+    fn describe(&self) -> String {
+        format!("email to {}", self.recipients.join(", "))
+    }
+
+    fn send<'a>(&'a self, timestamp: DateTime<Utc>, incident: &'a Incident) -> BoxFuture<'a, Result<(), String>> {
+        Box::pin(async move {
+            let details = serde_json::to_string_pretty(incident).map_err(|e| e.to_string())?;
+            let message = format!(
+                "From: {}\r\nTo: {}\r\nSubject: [market-data] {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n\r\n{}\r\n",
+                self.from,
+                self.recipients.join(", "),
+                incident.summary(),
+                timestamp.to_rfc2822(),
+                incident.summary(),
+                details.replace('\n', "\r\n"),
+            );
+            self.deliver(&message).await
+        })
+    }
+}
+
+impl EmailSink {
+    async fn deliver(&self, message: &str) -> Result<(), String> {
+        let stream = TcpStream::connect(&self.relay).await.map_err(|e| e.to_string())?;
+        let (reader, mut writer) = stream.into_split();
+        let mut reader = BufReader::new(reader);
+
+        expect_reply(&mut reader, 220).await?;
+        let mut commands = vec!["EHLO localhost".to_string(), format!("MAIL FROM:<{}>", self.from)];
+        commands.extend(self.recipients.iter().map(|recipient| format!("RCPT TO:<{}>", recipient)));
+        for command in commands {
+            writer.write_all(format!("{}\r\n", command).as_bytes()).await.map_err(|e| e.to_string())?;
+            expect_reply(&mut reader, 250).await?;
+        }
+
+        writer.write_all(b"DATA\r\n").await.map_err(|e| e.to_string())?;
+        expect_reply(&mut reader, 354).await?;
+
+        // Lines starting with a dot are doubled so none ends the message early
+        let mut data = String::new();
+        for line in message.split("\r\n") {
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+            data.push_str("\r\n");
+        }
+        data.push_str(".\r\n");
+        writer.write_all(data.as_bytes()).await.map_err(|e| e.to_string())?;
+        expect_reply(&mut reader, 250).await?;
+
+        let _ = writer.write_all(b"QUIT\r\n").await;
+        Ok(())
+    }
+}
+
+// Reads a possibly multi-line SMTP reply, failing unless it has the expected code
+async fn expect_reply(reader: &mut BufReader<tokio::net::tcp::OwnedReadHalf>, expected: u16) -> Result<(), String> {
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await.map_err(|e| e.to_string())? == 0 {
+            return Err("SMTP relay closed the connection".to_string());
+        }
+
+        let code = line.get(..3).and_then(|code| code.parse::<u16>().ok());
+        if code != Some(expected) {
+            return Err(format!("SMTP relay replied '{}'", line.trim_end()));
+        }
+        if line.as_bytes().get(3) != Some(&b'-') {
+            return Ok(());
+        }
+    }
+}
+
+// Watches the event bus for server incidents and hands each to every sink, one queue
+// per sink so a slow sink only delays itself
+pub struct Notifier {
+    sinks: Vec<Box<dyn NotificationSink>>,
+    stale_after: Duration,
+}
+
+impl Notifier {
+    pub fn new(options: &NotificationOptions) -> Result<Self, String> {
+        let client = reqwest::Client::builder()
+            .timeout(SEND_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create notification client: {}", e))?;
+
+        let mut notifier = Self {
+            sinks: Vec::new(),
+            stale_after: Duration::from_secs(options.notify_stale_feed_secs.max(1)),
+        };
+        for sink in &options.sinks {
+            match sink {
+                SinkConfig::Slack(url) => notifier.add_sink(Box::new(SlackSink { client: client.clone(), url: url.clone() })),
+                SinkConfig::Webhook(url) => notifier.add_sink(Box::new(WebhookSink { client: client.clone(), url: url.clone() })),
+                SinkConfig::Email(recipients) => notifier.add_sink(Box::new(EmailSink {
+                    relay: options.smtp_relay.clone(),
+                    from: options.smtp_from.clone(),
+                    recipients: recipients.clone(),
+                })),
+            }
+        }
+        Ok(notifier)
+    }
+
+    pub fn add_sink(&mut self, sink: Box<dyn NotificationSink>) {
+        self.sinks.push(sink);
+    }
+
+    pub fn start(self, mut receiver: broadcast::Receiver<MarketEvent>) {
+        let queues: Vec<_> = self
+            .sinks
+            .into_iter()
+            .map(|sink| {
+                let (sender, incidents) = mpsc::channel(SINK_QUEUE);
+                let name = sink.describe();
+                tokio::spawn(notify(sink, incidents));
+                (name, sender)
+            })
+            .collect();
+
+        let mut feeds = FeedMonitor::new(self.stale_after);
+        tokio::spawn(async move {
+            let mut check = interval(Duration::from_secs(1));
+            check.set_missed_tick_behavior(MissedTickBehavior::Skip);
+            let mut shedding = false;
+
+            loop {
+                let incidents = tokio::select! {
+                    event = next_event(&mut receiver, "Notifications") => match event {
+                        Some(event) => incidents(&mut feeds, &mut shedding, event),
+                        None => break,
+                    },
+                    _ = check.tick() => feeds
+                        .newly_stale()
+                        .into_iter()
+                        .map(|(symbol, last_update)| Incident::FeedStale { symbol: symbol.to_string(), last_update })
+                        .collect(),
+                };
+
+                for incident in incidents {
+                    let incident = Arc::new((Utc::now(), incident));
+                    for (name, sender) in &queues {
+                        if sender.try_send(Arc::clone(&incident)).is_err() {
+                            warn!("Notification sink {} is too far behind, dropping: {}", name, incident.1.summary());
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+fn incidents(feeds: &mut FeedMonitor, shedding: &mut bool, event: MarketEvent) -> Vec<Incident> {
+    match event {
+        // Sandboxes are driven by their owners, whose halts are no incident
+        MarketEvent::BookUpdated { symbol, .. } if is_sandbox(&symbol) => Vec::new(),
+        MarketEvent::BookUpdated { symbol, event_ts, .. } => feeds
+            .updated(&symbol, event_ts)
+            .map(|silence| Incident::FeedRecovered { symbol: symbol.to_string(), stale_secs: silence.as_secs_f64() })
+            .into_iter()
+            .collect(),
+        MarketEvent::Status { symbol, contract } => {
+            feeds.set_halted(&symbol, contract.status != TradingStatus::Trading);
+            if contract.status == TradingStatus::Halted && Utc::now() < contract.expiry {
+                return vec![Incident::UnexpectedHalt { symbol: symbol.to_string(), contract: contract.symbol, expiry: contract.expiry }];
+            }
+            Vec::new()
+        }
+        MarketEvent::BookRemoved { symbol } => {
+            feeds.remove(&symbol);
+            Vec::new()
+        }
+        // Only the start and end of load shedding are reported, not each step in between
+        MarketEvent::Conflation { interval } => match interval {
+            Some(interval) if !*shedding => {
+                *shedding = true;
+                vec![Incident::LoadShedding { interval_ms: interval.as_millis() as u64 }]
+            }
+            None if *shedding => {
+                *shedding = false;
+                vec![Incident::LoadRecovered]
+            }
+            _ => Vec::new(),
+        },
+        MarketEvent::IntegrityViolation { symbol, violations, repaired } => {
+            vec![Incident::BookIntegrity { symbol: symbol.to_string(), violations, repaired }]
+        }
+        MarketEvent::Activity(_)
+        | MarketEvent::Trade(_)
+        | MarketEvent::TickCompleted { .. }
+        | MarketEvent::Quarantined(_) => Vec::new(),
+    }
+}
+
+async fn notify(sink: Box<dyn NotificationSink>, mut incidents: mpsc::Receiver<Arc<(DateTime<Utc>, Incident)>>) {
+    while let Some(incident) = incidents.recv().await {
+        let (timestamp, incident) = incident.as_ref();
+        let mut backoff = INITIAL_BACKOFF;
+
+        for attempt in 1..=MAX_ATTEMPTS {
+            let result = match timeout(SEND_TIMEOUT, sink.send(*timestamp, incident)).await {
+                Ok(result) => result,
+                Err(_) => Err("timed out".to_string()),
+            };
+            let Err(e) = result else {
+                break;
+            };
+
+            if attempt == MAX_ATTEMPTS {
+                warn!("Giving up notifying {} after {} attempts: {}: {}", sink.describe(), attempt, incident.summary(), e);
+                break;
+            }
+            sleep(backoff).await;
+            backoff *= 2;
+        }
+    }
+}
@@ -0,0 +1,320 @@
+use std::f64::consts::{PI, SQRT_2};
+use chrono::{DateTime, Duration, NaiveDate, NaiveTime, Utc};
+use clap::Args;
+use rand::{thread_rng, Rng};
+
+use crate::message::{OptionKind, OptionQuote};
+
+const SECS_PER_YEAR: f64 = 365.0 * 86400.0;
+
+// Quotes are rounded outwards to the cent
+const TICKS_PER_UNIT: f64 = 100.0;
+
+// Expiries settle at this time of day, UTC
+const EXPIRY_TIME: (u32, u32) = (8, 0);
+
+// Bound on the drift of the whole surface away from the configured levels
+const MAX_VOL_SHIFT: f64 = 0.1;
+
+// Options chains listed on simulated underlyings, repriced every tick off the book mid
+#[derive(Debug, Clone, Args)]
+pub struct OptionChainOptions {
+    /// Options chain to list, repeatable: UNDERLYING:EXPIRIES:STRIKES with comma-separated
+    /// expiries (YYYY-MM-DD, or days from startup such as 30d, settling 08:00 UTC) and strikes
+    #[arg(long = "option-chain", value_name = "CHAIN")]
+    pub option_chains: Vec<String>,
+
+    /// Implied volatility of at-the-money options about to expire, annualized
+    #[arg(long, value_name = "VOL", default_value_t = 0.6)]
+    pub vol_atm: f64,
+
+    /// Change in at-the-money volatility per year to expiry
+    #[arg(long, value_name = "VOL", default_value_t = -0.05, allow_hyphen_values = true)]
+    pub vol_term_slope: f64,
+
+    /// Change in volatility per unit of moneyness, ln(strike / underlying) / sqrt(years)
+    #[arg(long, value_name = "VOL", default_value_t = -0.1, allow_hyphen_values = true)]
+    pub vol_skew: f64,
+
+    /// Curvature of the smile: volatility added per squared unit of moneyness
+    #[arg(long, value_name = "VOL", default_value_t = 0.05)]
+    pub vol_smile: f64,
+
+    /// Continuously compounded interest rate used in pricing
+    #[arg(long, value_name = "RATE", default_value_t = 0.0, allow_hyphen_values = true)]
+    pub option_interest_rate: f64,
+
+    /// Volatility taken off for the bid and added for the ask
+    #[arg(long, value_name = "VOL", default_value_t = 0.01)]
+    pub option_spread_vol: f64,
+}
+
+#[derive(Debug, Clone)]
+pub struct OptionContract {
+    pub name: String,
+    pub kind: OptionKind,
+    pub strike: f64,
+    pub expiry: DateTime<Utc>,
+}
+
+// Parametric volatility surface: a linear term structure at the money, with a skew and
+// smile in moneyness scaled by the square root of time
+#[derive(Debug, Clone)]
+struct VolSurface {
+    atm: f64,
+    term_slope: f64,
+    skew: f64,
+    smile: f64,
+}
+
+impl VolSurface {
+    fn vol(&self, underlying_price: f64, strike: f64, years: f64) -> f64 {
+        // Floored at a day so moneyness stays bounded into expiry
+        let years = years.max(1.0 / 365.0);
+        let moneyness = (strike / underlying_price).ln() / years.sqrt();
+
+        (self.atm + self.term_slope * years + self.skew * moneyness + self.smile * moneyness * moneyness).max(0.05)
+    }
+}
+
+// Calls and puts at every listed strike and expiry of one underlying. The surface as a
+// whole drifts by a small mean-reverting shift so greeks move even when the mid does not.
+#[derive(Debug)]
+pub struct OptionChain {
+    pub underlying: String,
+    contracts: Vec<OptionContract>,
+    surface: VolSurface,
+    interest_rate: f64,
+    spread_vol: f64,
+    vol_shift: f64,
+    quotes: Vec<OptionQuote>,
+}
+
+impl OptionChain {
+    // "BTCUSD:30d,2026-12-25:90,100,110" lists six strikes' calls and puts at two expiries
+    pub fn parse(definition: &str, options: &OptionChainOptions, now: DateTime<Utc>) -> Result<Self, String> {
+        let definition = definition.trim();
+        let invalid = |reason: &str| format!("Invalid options chain '{}': {}", definition, reason);
+
+        let mut parts = definition.split(':');
+        let (Some(underlying), Some(expiries), Some(strikes), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid("expected UNDERLYING:EXPIRIES:STRIKES"));
+        };
+        let underlying = underlying.trim();
+        if underlying.is_empty() {
+            return Err(invalid("the underlying must be named"));
+        }
+
+        let expiries = expiries
+            .split(',')
+            .map(|expiry| parse_expiry(expiry.trim(), now).map_err(|reason| invalid(&reason)))
+            .collect::<Result<Vec<_>, _>>()?;
+        let strikes = strikes
+            .split(',')
+            .map(|strike| match strike.trim().parse::<f64>() {
+                Ok(strike) if strike.is_finite() && strike > 0.0 => Ok(strike),
+                _ => Err(invalid(&format!("invalid strike '{}'", strike.trim()))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut chain = Self {
+            underlying: underlying.to_string(),
+            contracts: Vec::new(),
+            surface: VolSurface {
+                atm: options.vol_atm,
+                term_slope: options.vol_term_slope,
+                skew: options.vol_skew,
+                smile: options.vol_smile,
+            },
+            interest_rate: options.option_interest_rate,
+            spread_vol: options.option_spread_vol.abs(),
+            vol_shift: 0.0,
+            quotes: Vec::new(),
+        };
+        for expiry in &expiries {
+            for strike in &strikes {
+                for kind in [OptionKind::Call, OptionKind::Put] {
+                    chain.add_contract(OptionContract {
+                        name: contract_name(underlying, *expiry, *strike, kind),
+                        kind,
+                        strike: *strike,
+                        expiry: *expiry,
+                    });
+                }
+            }
+        }
+        chain.sort_contracts();
+
+        Ok(chain)
+    }
+
+    // Contracts of another definition on the same underlying; duplicates are skipped
+    pub fn merge(&mut self, other: OptionChain) {
+        for contract in other.contracts {
+            self.add_contract(contract);
+        }
+        self.sort_contracts();
+    }
+
+    fn add_contract(&mut self, contract: OptionContract) {
+        if !self.contracts.iter().any(|existing| existing.name == contract.name) {
+            self.contracts.push(contract);
+        }
+    }
+
+    // By expiry, then strike, with each call ahead of its put
+    fn sort_contracts(&mut self) {
+        self.contracts.sort_by(|a, b| {
+            a.expiry
+                .cmp(&b.expiry)
+                .then(a.strike.total_cmp(&b.strike))
+                .then((a.kind == OptionKind::Put).cmp(&(b.kind == OptionKind::Put)))
+        });
+    }
+
+    pub fn contracts(&self) -> &[OptionContract] {
+        &self.contracts
+    }
+
+    // Reprices every contract off the underlying's current mid price
+    pub fn update(&mut self, underlying_price: f64, now: DateTime<Utc>) {
+        let mut rng = thread_rng();
+        self.vol_shift = (self.vol_shift * 0.99 + rng.gen_range(-0.002..0.002)).clamp(-MAX_VOL_SHIFT, MAX_VOL_SHIFT);
+
+        self.quotes = self
+            .contracts
+            .iter()
+            .map(|contract| {
+                let years = ((contract.expiry - now).num_milliseconds().max(0) as f64 / 1000.0) / SECS_PER_YEAR;
+                let vol = (self.surface.vol(underlying_price, contract.strike, years) + self.vol_shift).max(0.01);
+                let price = |vol: f64| black_scholes(contract.kind, underlying_price, contract.strike, years, self.interest_rate, vol);
+                let greeks = price(vol);
+
+                let bid = (price((vol - self.spread_vol).max(0.01)).price * TICKS_PER_UNIT).floor() / TICKS_PER_UNIT;
+                let ask = (price(vol + self.spread_vol).price * TICKS_PER_UNIT).ceil().max(1.0) / TICKS_PER_UNIT;
+
+                OptionQuote {
+                    contract: contract.name.clone(),
+                    underlying: self.underlying.clone(),
+                    kind: contract.kind,
+                    strike: contract.strike,
+                    expiry: contract.expiry,
+                    underlying_price,
+                    bid: (bid > 0.0).then_some(bid),
+                    ask,
+                    bid_size: if bid > 0.0 { rng.gen_range(1..=500) } else { 0 },
+                    ask_size: rng.gen_range(1..=500),
+                    mark_price: greeks.price,
+                    implied_vol: vol,
+                    delta: greeks.delta,
+                    gamma: greeks.gamma,
+                    vega: greeks.vega,
+                    theta: greeks.theta,
+                    rho: greeks.rho,
+                }
+            })
+            .collect();
+    }
+
+    // Empty until the underlying has had a mid price
+    pub fn quotes(&self) -> &[OptionQuote] {
+        &self.quotes
+    }
+
+    pub fn quote(&self, contract: &str) -> Option<&OptionQuote> {
+        self.quotes.iter().find(|quote| quote.contract == contract)
+    }
+}
+
+// BTCUSD-20261225-100-C
+fn contract_name(underlying: &str, expiry: DateTime<Utc>, strike: f64, kind: OptionKind) -> String {
+    let kind = match kind {
+        OptionKind::Call => 'C',
+        OptionKind::Put => 'P',
+    };
+    format!("{}-{}-{}-{}", underlying, expiry.format("%Y%m%d"), strike, kind)
+}
+
+// YYYY-MM-DD, or a number of days from now such as 30d, settling at 08:00 UTC
+pub fn parse_expiry(expiry: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>, String> {
+    let invalid = || format!("invalid expiry '{}'", expiry);
+    let date = match expiry.strip_suffix('d') {
+        Some(days) => match days.parse::<u32>() {
+            Ok(days) if days > 0 => (now + Duration::days(days as i64)).date_naive(),
+            _ => return Err(invalid()),
+        },
+        None => NaiveDate::parse_from_str(expiry, "%Y-%m-%d").map_err(|_| invalid())?,
+    };
+    let time = NaiveTime::from_hms_opt(EXPIRY_TIME.0, EXPIRY_TIME.1, 0).unwrap_or_default();
+    let expiry = date.and_time(time).and_utc();
+
+    if expiry <= now {
+        return Err(format!("expiry {} has passed", expiry.format("%Y-%m-%d %H:%M UTC")));
+    }
+    Ok(expiry)
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Greeks {
+    price: f64,
+    delta: f64,
+    gamma: f64,
+    vega: f64,
+    theta: f64,
+    rho: f64,
+}
+
+// European option on a non-dividend underlying; at expiry the option is worth its
+// intrinsic value and only delta is left
+fn black_scholes(kind: OptionKind, spot: f64, strike: f64, years: f64, rate: f64, vol: f64) -> Greeks {
+    if years <= 0.0 {
+        let (price, delta) = match kind {
+            OptionKind::Call => ((spot - strike).max(0.0), if spot > strike { 1.0 } else { 0.0 }),
+            OptionKind::Put => ((strike - spot).max(0.0), if spot < strike { -1.0 } else { 0.0 }),
+        };
+        return Greeks { price, delta, gamma: 0.0, vega: 0.0, theta: 0.0, rho: 0.0 };
+    }
+
+    let sqrt_years = years.sqrt();
+    let d1 = ((spot / strike).ln() + (rate + vol * vol / 2.0) * years) / (vol * sqrt_years);
+    let d2 = d1 - vol * sqrt_years;
+    let discount = (-rate * years).exp();
+    let density = (-d1 * d1 / 2.0).exp() / (2.0 * PI).sqrt();
+    let decay = -spot * density * vol / (2.0 * sqrt_years);
+
+    let (price, delta, theta, rho) = match kind {
+        OptionKind::Call => (
+            spot * norm_cdf(d1) - strike * discount * norm_cdf(d2),
+            norm_cdf(d1),
+            decay - rate * strike * discount * norm_cdf(d2),
+            strike * years * discount * norm_cdf(d2),
+        ),
+        OptionKind::Put => (
+            strike * discount * norm_cdf(-d2) - spot * norm_cdf(-d1),
+            norm_cdf(d1) - 1.0,
+            decay + rate * strike * discount * norm_cdf(-d2),
+            -strike * years * discount * norm_cdf(-d2),
+        ),
+    };
+
+    Greeks {
+        price: price.max(0.0),
+        delta,
+        gamma: density / (spot * vol * sqrt_years),
+        vega: spot * density * sqrt_years / 100.0,
+        theta: theta / 365.0,
+        rho: rho / 100.0,
+    }
+}
+
+fn norm_cdf(x: f64) -> f64 {
+    0.5 * (1.0 + erf(x / SQRT_2))
+}
+
+// Abramowitz and Stegun 7.1.26, accurate to 1.5e-7
+fn erf(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x.abs());
+    let poly = t * (0.254829592 + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    let y = 1.0 - poly * (-x * x).exp();
+    if x < 0.0 { -y } else { y }
+}
@@ -0,0 +1,108 @@
+use std::collections::{HashMap, VecDeque};
+use chrono::{DateTime, Utc};
+
+use crate::message::{ActivityType, LifetimeStats, OrderActivity, OrderId, OrderLifetimeStats};
+
+// Most recent lifetimes the percentiles of each outcome are taken over
+const LIFETIME_SAMPLES: usize = 1000;
+
+// How long orders of one symbol rest before they are cancelled or fully filled, and how
+// often they are cancelled rather than traded. Orders resting before the first activity
+// seen still count towards the outcome totals but have no known lifetime.
+#[derive(Debug, Default)]
+pub struct OrderLifetimes {
+    live: HashMap<OrderId, DateTime<Utc>>,
+    orders_added: u64,
+    partial_fills: u64,
+    trades: u64,
+    cancelled: Outcome,
+    filled: Outcome,
+    stats: Option<OrderLifetimeStats>,
+}
+
+#[derive(Debug, Default)]
+struct Outcome {
+    count: u64,
+    total_ms: f64,
+    timed: u64,
+    recent: VecDeque<f64>,
+}
+
+impl Outcome {
+    fn record(&mut self, added: Option<DateTime<Utc>>, at: DateTime<Utc>) {
+        self.count += 1;
+        let Some(added) = added else {
+            return;
+        };
+
+        let lifetime_ms = (at - added).num_microseconds().unwrap_or(i64::MAX).max(0) as f64 / 1000.0;
+        self.total_ms += lifetime_ms;
+        self.timed += 1;
+        if self.recent.len() == LIFETIME_SAMPLES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(lifetime_ms);
+    }
+
+    fn stats(&self) -> LifetimeStats {
+        let mut recent: Vec<f64> = self.recent.iter().copied().collect();
+        recent.sort_by(f64::total_cmp);
+        let percentile = |p: f64| (!recent.is_empty()).then(|| recent[((recent.len() - 1) as f64 * p).round() as usize]);
+
+        LifetimeStats {
+            count: self.count,
+            mean_ms: (self.timed > 0).then(|| self.total_ms / self.timed as f64),
+            p50_ms: percentile(0.5),
+            p90_ms: percentile(0.9),
+        }
+    }
+}
+
+impl OrderLifetimes {
+    pub fn record_activity(&mut self, activity: &OrderActivity) {
+        match activity.activity_type {
+            ActivityType::Add => {
+                self.orders_added += 1;
+                self.live.insert(activity.order_id, activity.timestamp);
+            }
+            ActivityType::Update => {}
+            ActivityType::Cancel => {
+                let added = self.live.remove(&activity.order_id);
+                self.cancelled.record(added, activity.timestamp);
+            }
+            // Fills leave the remaining size, none once the order is used up
+            ActivityType::Fill if activity.quantity.is_some_and(|quantity| !quantity.is_zero()) => self.partial_fills += 1,
+            ActivityType::Fill => {
+                let added = self.live.remove(&activity.order_id);
+                self.filled.record(added, activity.timestamp);
+            }
+        }
+    }
+
+    pub fn record_trade(&mut self) {
+        self.trades += 1;
+    }
+
+    // Percentiles are recomputed here rather than on every read, since each stream reads them
+    // once per book update
+    pub fn refresh(&mut self) {
+        self.stats = Some(self.compute());
+    }
+
+    pub fn stats(&self) -> OrderLifetimeStats {
+        self.stats.clone().unwrap_or_else(|| self.compute())
+    }
+
+    fn compute(&self) -> OrderLifetimeStats {
+        OrderLifetimeStats {
+            orders_added: self.orders_added,
+            live_orders: self.live.len() as u64,
+            cancelled: self.cancelled.stats(),
+            filled: self.filled.stats(),
+            partial_fills: self.partial_fills,
+            trades: self.trades,
+            cancel_to_trade_ratio: (self.trades > 0).then(|| self.cancelled.count as f64 / self.trades as f64),
+            sample_size: LIFETIME_SAMPLES as u32,
+        }
+    }
+}
@@ -0,0 +1,180 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::future::poll_fn;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{Context, Poll, Waker};
+use tokio::sync::mpsc::error::SendError;
+
+// Delivery tiers of a client's outbound queue, most urgent first. Each tier is FIFO, and
+// a tier is only drained once every tier above it is empty.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum DeliveryTier {
+    Control,
+    Snapshot,
+    Delta,
+    Analytics,
+}
+
+const TIERS: usize = 4;
+
+pub trait Prioritized {
+    fn tier(&self) -> DeliveryTier;
+
+    // Stream the message belongs to, if any
+    fn stream(&self) -> Option<&str>;
+
+    // Stream whose queued deltas are obsolete once this message is queued, such as
+    // the stream of a full-book snapshot or one that was unsubscribed
+    fn supersedes(&self) -> Option<&str> {
+        None
+    }
+}
+
+struct Queues<T> {
+    tiers: [VecDeque<T>; TIERS],
+    waker: Option<Waker>,
+    senders: usize,
+    receiver_dropped: bool,
+}
+
+struct Shared<T> {
+    queues: Mutex<Queues<T>>,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, Queues<T>> {
+        self.queues.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+// Unbounded per-client queue replacing a FIFO channel: senders never block, and the
+// receiver takes the oldest message of the most urgent non-empty tier
+pub fn outbound_queue<T: Prioritized>() -> (OutboundSender<T>, OutboundReceiver<T>) {
+    let shared = Arc::new(Shared {
+        queues: Mutex::new(Queues {
+            tiers: Default::default(),
+            waker: None,
+            senders: 1,
+            receiver_dropped: false,
+        }),
+    });
+
+    (OutboundSender { shared: Arc::clone(&shared) }, OutboundReceiver { shared })
+}
+
+pub struct OutboundSender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T: Prioritized> OutboundSender<T> {
+    // Returns how many queued messages the new one superseded. Fails only once the
+    // receiver is gone, handing the message back.
+    pub fn send(&self, message: T) -> Result<usize, SendError<T>> {
+        let (waker, superseded) = {
+            let mut queues = self.shared.lock();
+            if queues.receiver_dropped {
+                return Err(SendError(message));
+            }
+
+            let mut superseded = 0;
+            if let Some(stream) = message.supersedes() {
+                for tier in [DeliveryTier::Delta, DeliveryTier::Analytics] {
+                    let queue = &mut queues.tiers[tier as usize];
+                    let queued = queue.len();
+                    queue.retain(|queued| queued.stream() != Some(stream));
+                    superseded += queued - queue.len();
+                }
+            }
+            queues.tiers[message.tier() as usize].push_back(message);
+            (queues.waker.take(), superseded)
+        };
+
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+        Ok(superseded)
+    }
+}
+
+impl<T> Clone for OutboundSender<T> {
+    fn clone(&self) -> Self {
+        self.shared.lock().senders += 1;
+        Self { shared: Arc::clone(&self.shared) }
+    }
+}
+
+impl<T> Drop for OutboundSender<T> {
+    fn drop(&mut self) {
+        let waker = {
+            let mut queues = self.shared.lock();
+            queues.senders -= 1;
+            if queues.senders > 0 {
+                return;
+            }
+            queues.waker.take()
+        };
+
+        // The receiver sees the queue closed once it has drained it
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+}
+
+impl<T> fmt::Debug for OutboundSender<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutboundSender").finish_non_exhaustive()
+    }
+}
+
+pub struct OutboundReceiver<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> OutboundReceiver<T> {
+    // None once every sender is dropped and the queue is drained
+    pub async fn recv(&mut self) -> Option<T> {
+        poll_fn(|cx| self.poll_recv(cx)).await
+    }
+
+    pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+        let mut queues = self.shared.lock();
+        if let Some(message) = queues.tiers.iter_mut().find_map(VecDeque::pop_front) {
+            return Poll::Ready(Some(message));
+        }
+        if queues.senders == 0 {
+            return Poll::Ready(None);
+        }
+
+        queues.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+
+    // Takes every queued message, in the order they would have been received
+    pub fn drain(&mut self) -> Vec<T> {
+        self.shared.lock().tiers.iter_mut().flat_map(|queue| queue.drain(..)).collect()
+    }
+
+    // Messages still queued across all tiers
+    pub fn len(&self) -> usize {
+        self.shared.lock().tiers.iter().map(VecDeque::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Drop for OutboundReceiver<T> {
+    fn drop(&mut self) {
+        let mut queues = self.shared.lock();
+        queues.receiver_dropped = true;
+        queues.tiers.iter_mut().for_each(VecDeque::clear);
+    }
+}
+
+impl<T> fmt::Debug for OutboundReceiver<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OutboundReceiver").field("len", &self.len()).finish()
+    }
+}
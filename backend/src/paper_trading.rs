@@ -0,0 +1,323 @@
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::Utc;
+use clap::Args;
+use dashmap::DashMap;
+use tokio::sync::mpsc;
+use uuid::Uuid;
+
+use crate::message::{PaperFill, PaperLiquidity, PaperOrder, PaperOrderStatus, Quantity, ServerMessage, Side, Trade};
+use crate::order_book::OrderBook;
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct PaperTradingOptions {
+    /// Accept paper orders over the WebSocket, filled against the live books without
+    /// changing them
+    #[arg(long)]
+    pub paper_trading: bool,
+
+    /// Fee of paper fills taking liquidity, in basis points of their notional
+    #[arg(long, value_name = "BPS", default_value_t = 0.0, allow_negative_numbers = true)]
+    pub paper_taker_fee_bps: f64,
+
+    /// Fee of paper fills of resting orders, in basis points of their notional; negative
+    /// for a rebate
+    #[arg(long, value_name = "BPS", default_value_t = 0.0, allow_negative_numbers = true)]
+    pub paper_maker_fee_bps: f64,
+
+    /// Delay before a paper order reaches the book
+    #[arg(long, value_name = "MS", default_value_t = 0)]
+    pub paper_latency_ms: u64,
+}
+
+impl PaperTradingOptions {
+    pub fn model(&self) -> Result<Option<FlatFeeModel>, String> {
+        if !self.paper_trading {
+            if self.paper_taker_fee_bps != 0.0 || self.paper_maker_fee_bps != 0.0 || self.paper_latency_ms != 0 {
+                return Err("--paper-taker-fee-bps, --paper-maker-fee-bps and --paper-latency-ms need --paper-trading".to_string());
+            }
+            return Ok(None);
+        }
+        if !self.paper_taker_fee_bps.is_finite() || !self.paper_maker_fee_bps.is_finite() {
+            return Err("Paper trading fees must be finite".to_string());
+        }
+        Ok(Some(FlatFeeModel {
+            taker_bps: self.paper_taker_fee_bps,
+            maker_bps: self.paper_maker_fee_bps,
+            latency: Duration::from_millis(self.paper_latency_ms),
+        }))
+    }
+}
+
+// How paper orders are delayed and charged; embedders pass their own to
+// StreamManager::enable_paper_trading
+pub trait PaperTradingModel: Send + Sync {
+    // Time from submitting an order to it reaching the book
+    fn latency(&self, _order: &PaperOrder) -> Duration {
+        Duration::ZERO
+    }
+
+    // Fee of one fill in quote currency, negative for a rebate
+    fn fee(&self, symbol: &str, liquidity: PaperLiquidity, price: f64, quantity: Quantity) -> f64;
+}
+
+// Basis points of the notional by liquidity, and the same latency for every order
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlatFeeModel {
+    pub taker_bps: f64,
+    pub maker_bps: f64,
+    pub latency: Duration,
+}
+
+impl PaperTradingModel for FlatFeeModel {
+    fn latency(&self, _order: &PaperOrder) -> Duration {
+        self.latency
+    }
+
+    fn fee(&self, _symbol: &str, liquidity: PaperLiquidity, price: f64, quantity: Quantity) -> f64 {
+        let bps = match liquidity {
+            PaperLiquidity::Maker => self.maker_bps,
+            PaperLiquidity::Taker => self.taker_bps,
+        };
+        price * quantity.to_f64() * bps / 10_000.0
+    }
+}
+
+// A paper order as submitted
+#[derive(Debug, Clone)]
+pub struct PaperOrderRequest {
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: Quantity,
+    pub limit_price: Option<f64>,
+    pub client_order_id: Option<String>,
+}
+
+impl PaperOrderRequest {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.quantity.is_zero() {
+            return Err("Quantity must be positive".to_string());
+        }
+        if self.quantity > Quantity::MAX_ORDER {
+            return Err(format!("Quantity {} is larger than {}", self.quantity, Quantity::MAX_ORDER));
+        }
+        if let Some(price) = self.limit_price.filter(|price| !price.is_finite() || *price <= 0.0) {
+            return Err(format!("Invalid limit price {}", price));
+        }
+        Ok(())
+    }
+}
+
+struct PaperEntry {
+    owner: Uuid,
+    order: PaperOrder,
+    notional: f64,
+    arrived: bool, // Submitted orders wait out the model's latency before they can fill
+}
+
+impl PaperEntry {
+    fn fill(&mut self, model: &dyn PaperTradingModel, liquidity: PaperLiquidity, price: f64, quantity: Quantity) -> PaperFill {
+        let timestamp = Utc::now();
+        let fee = model.fee(&self.order.symbol, liquidity, price, quantity);
+        self.notional += price * quantity.to_f64();
+        self.order.filled += quantity;
+        self.order.average_price = Some(self.notional / self.order.filled.to_f64());
+        self.order.fees += fee;
+        self.order.timestamp = timestamp;
+        if self.order.filled == self.order.quantity {
+            self.order.status = PaperOrderStatus::Filled;
+        }
+
+        PaperFill {
+            order_id: self.order.order_id,
+            symbol: self.order.symbol.clone(),
+            side: self.order.side.clone(),
+            price,
+            quantity,
+            fee,
+            liquidity,
+            timestamp,
+        }
+    }
+
+    fn remaining(&self) -> Quantity {
+        self.order.quantity - self.order.filled
+    }
+}
+
+// Open paper orders of every client and embedded account. Fills leave the books as they
+// are, so paper orders neither use up the liquidity they take nor trade with each other.
+pub struct PaperDesk {
+    model: Arc<dyn PaperTradingModel>,
+    next_order_id: AtomicU64,
+    orders: DashMap<u64, PaperEntry>,
+    accounts: DashMap<Uuid, mpsc::UnboundedSender<ServerMessage>>, // Embedded accounts; clients are sent to directly
+}
+
+impl fmt::Debug for PaperDesk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PaperDesk").field("open_orders", &self.orders.len()).field("accounts", &self.accounts.len()).finish()
+    }
+}
+
+impl PaperDesk {
+    pub fn new(model: Arc<dyn PaperTradingModel>) -> Self {
+        Self { model, next_order_id: AtomicU64::new(1), orders: DashMap::new(), accounts: DashMap::new() }
+    }
+
+    pub fn open_account(&self) -> (Uuid, mpsc::UnboundedReceiver<ServerMessage>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let account = Uuid::new_v4();
+        self.accounts.insert(account, sender);
+        (account, receiver)
+    }
+
+    // The embedded account's channel, if the owner is one
+    pub fn account(&self, owner: &Uuid) -> Option<mpsc::UnboundedSender<ServerMessage>> {
+        self.accounts.get(owner).map(|sender| sender.clone())
+    }
+
+    // Drops the owner's open orders, e.g. when its client disconnects
+    pub fn forget_owner(&self, owner: &Uuid) {
+        self.accounts.remove(owner);
+        self.orders.retain(|_, entry| entry.owner != *owner);
+    }
+
+    // Opens the order and returns it with the latency it reaches the book after
+    pub fn accept(&self, owner: Uuid, request: PaperOrderRequest) -> (PaperOrder, Duration) {
+        let order = PaperOrder {
+            order_id: self.next_order_id.fetch_add(1, Ordering::Relaxed),
+            client_order_id: request.client_order_id,
+            symbol: request.symbol,
+            side: request.side,
+            quantity: request.quantity,
+            limit_price: request.limit_price,
+            filled: Quantity::ZERO,
+            average_price: None,
+            fees: 0.0,
+            status: PaperOrderStatus::Open,
+            timestamp: Utc::now(),
+        };
+        let latency = self.model.latency(&order);
+        self.orders.insert(order.order_id, PaperEntry { owner, order: order.clone(), notional: 0.0, arrived: false });
+        (order, latency)
+    }
+
+    pub fn cancel(&self, owner: &Uuid, order_id: u64) -> Result<PaperOrder, String> {
+        let Some((_, mut entry)) = self.orders.remove_if(&order_id, |_, entry| entry.owner == *owner) else {
+            return Err(format!("No open paper order {}", order_id));
+        };
+        entry.order.status = PaperOrderStatus::Cancelled;
+        entry.order.timestamp = Utc::now();
+        Ok(entry.order)
+    }
+
+    // The order reaching the book: it takes what the opposite side offers best price
+    // first, up to its limit. A market order's unfilled rest is cancelled, a limit
+    // order's rests. Returns the order's owner and what to send it.
+    pub fn arrive(&self, order_id: u64, book: impl FnOnce(&str) -> Option<Arc<OrderBook>>) -> Option<(Uuid, Vec<ServerMessage>)> {
+        let mut entry = self.orders.get_mut(&order_id)?;
+        entry.arrived = true;
+        let book = book(&entry.order.symbol);
+        let mut reports = Vec::new();
+
+        if let Some(book) = &book {
+            let opposite = match entry.order.side {
+                Side::Bid => Side::Ask,
+                Side::Ask => Side::Bid,
+            };
+            let mut levels: Vec<(f64, Quantity)> = Vec::new();
+            let mut wanted = entry.remaining();
+            for resting in book.resting_orders(&opposite, u32::MAX) {
+                let marketable = entry.order.limit_price.is_none_or(|limit| match entry.order.side {
+                    Side::Bid => resting.price <= limit,
+                    Side::Ask => resting.price >= limit,
+                });
+                if wanted.is_zero() || !marketable {
+                    break;
+                }
+                let quantity = wanted.min(resting.quantity);
+                wanted -= quantity;
+                match levels.last_mut() {
+                    Some((price, level_quantity)) if *price == resting.price => *level_quantity += quantity,
+                    _ => levels.push((resting.price, quantity)),
+                }
+            }
+
+            for (price, quantity) in levels {
+                let fill = entry.fill(self.model.as_ref(), PaperLiquidity::Taker, price, quantity);
+                reports.push(ServerMessage::PaperOrderUpdate { order: entry.order.clone() });
+                reports.push(ServerMessage::PaperFill { fill });
+            }
+        }
+
+        let owner = entry.owner;
+        let done = entry.order.status == PaperOrderStatus::Filled;
+        let unfilled = !done && (book.is_none() || entry.order.limit_price.is_none());
+        drop(entry);
+        if unfilled {
+            let (_, mut entry) = self.orders.remove(&order_id)?;
+            entry.order.status = PaperOrderStatus::Cancelled;
+            entry.order.timestamp = Utc::now();
+            reports.push(ServerMessage::PaperOrderUpdate { order: entry.order });
+        } else if done {
+            self.orders.remove(&order_id);
+        }
+
+        Some((owner, reports))
+    }
+
+    // Fills resting limit orders the trade printed at or through, at their limit, as much
+    // as the trade's quantity each
+    pub fn on_trade(&self, trade: &Trade) -> Vec<(Uuid, ServerMessage)> {
+        let mut reports = Vec::new();
+        let mut filled = Vec::new();
+        for mut entry in self.orders.iter_mut() {
+            let Some(limit) = entry.order.limit_price else {
+                continue;
+            };
+            if !entry.arrived || entry.order.symbol != *trade.symbol {
+                continue;
+            }
+            let crossed = match (&entry.order.side, &trade.aggressor_side) {
+                (Side::Bid, Side::Ask) => trade.price <= limit,
+                (Side::Ask, Side::Bid) => trade.price >= limit,
+                _ => false,
+            };
+            if !crossed {
+                continue;
+            }
+
+            let quantity = entry.remaining().min(trade.quantity);
+            let fill = entry.fill(self.model.as_ref(), PaperLiquidity::Maker, limit, quantity);
+            reports.push((entry.owner, ServerMessage::PaperOrderUpdate { order: entry.order.clone() }));
+            reports.push((entry.owner, ServerMessage::PaperFill { fill }));
+            if entry.order.status == PaperOrderStatus::Filled {
+                filled.push(entry.order.order_id);
+            }
+        }
+
+        for order_id in filled {
+            self.orders.remove(&order_id);
+        }
+        reports
+    }
+
+    // Cancels the open orders on a book that was removed
+    pub fn remove_symbol(&self, symbol: &str) -> Vec<(Uuid, ServerMessage)> {
+        let order_ids: Vec<u64> =
+            self.orders.iter().filter(|entry| entry.order.symbol == symbol).map(|entry| entry.order.order_id).collect();
+
+        order_ids
+            .into_iter()
+            .filter_map(|order_id| self.orders.remove(&order_id))
+            .map(|(_, mut entry)| {
+                entry.order.status = PaperOrderStatus::Cancelled;
+                entry.order.timestamp = Utc::now();
+                (entry.owner, ServerMessage::PaperOrderUpdate { order: entry.order })
+            })
+            .collect()
+    }
+}
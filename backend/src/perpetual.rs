@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+use chrono::{DateTime, TimeZone, Utc};
+use clap::Args;
+use rand::{thread_rng, Rng};
+
+use crate::message::{FundingRate, MarkPrice};
+
+// Settlements retained per perpetual for the funding history endpoint
+pub const MAX_FUNDING_HISTORY: usize = 500;
+
+// Time constant of the basis moving average
+const BASIS_SMOOTHING_SECS: f64 = 60.0;
+
+// Bound on the interest-minus-premium adjustment of each funding rate
+const FUNDING_ADJUSTMENT_CLAMP: f64 = 0.0005;
+
+// Symbols published as perpetual swaps, with mark price and scheduled funding
+#[derive(Debug, Clone, Args)]
+pub struct PerpetualOptions {
+    /// Publish mark price and funding streams for this symbol, repeatable
+    #[arg(long = "perpetual", value_name = "SYMBOL")]
+    pub perpetuals: Vec<String>,
+
+    /// Seconds between funding settlements, aligned to the UTC epoch
+    #[arg(long, value_name = "SECS", default_value_t = 28800)]
+    pub funding_interval_secs: u64,
+
+    /// Interest component of each funding rate
+    #[arg(long, value_name = "RATE", default_value_t = 0.0001)]
+    pub funding_interest_rate: f64,
+
+    /// Largest absolute funding rate per settlement
+    #[arg(long, value_name = "RATE", default_value_t = 0.0075)]
+    pub funding_rate_cap: f64,
+}
+
+// Derived prices for one perpetual. The index stands in for the spot price of the
+// underlying: it trails the book mid at a small mean-reverting premium, so the
+// basis and therefore funding drift between positive and negative.
+#[derive(Debug)]
+pub struct Perpetual {
+    interval_secs: i64,
+    interest_rate: f64,
+    rate_cap: f64,
+    premium: f64,
+    basis: Option<f64>,
+    premium_sum: f64,
+    premium_samples: u64,
+    last_update: Option<DateTime<Utc>>,
+    next_funding: DateTime<Utc>,
+    latest: Option<MarkPrice>,
+    history: VecDeque<FundingRate>,
+}
+
+impl Perpetual {
+    pub fn new(options: &PerpetualOptions, now: DateTime<Utc>) -> Self {
+        let interval_secs = options.funding_interval_secs.max(1) as i64;
+
+        Self {
+            interval_secs,
+            interest_rate: options.funding_interest_rate,
+            rate_cap: options.funding_rate_cap.abs(),
+            premium: thread_rng().gen_range(-0.001..0.001),
+            basis: None,
+            premium_sum: 0.0,
+            premium_samples: 0,
+            last_update: None,
+            next_funding: next_funding_time(now, interval_secs),
+            latest: None,
+            history: VecDeque::new(),
+        }
+    }
+
+    // Folds in the book's current mid price and returns the settlement when the
+    // funding time has passed
+    pub fn update(&mut self, mid_price: f64, now: DateTime<Utc>) -> Option<FundingRate> {
+        let mut rng = thread_rng();
+        self.premium = self.premium * 0.998 + rng.gen_range(-0.0002..0.0002);
+        let index_price = mid_price / (1.0 + self.premium);
+
+        let elapsed_secs = self.last_update.map_or(0.0, |last| (now - last).num_milliseconds().max(0) as f64 / 1000.0);
+        let alpha = 1.0 - (-elapsed_secs / BASIS_SMOOTHING_SECS).exp();
+        let basis = match self.basis {
+            Some(basis) => basis + alpha * (mid_price - index_price - basis),
+            None => mid_price - index_price,
+        };
+        self.basis = Some(basis);
+        self.last_update = Some(now);
+
+        let mark_price = index_price + basis;
+        self.premium_sum += basis / index_price;
+        self.premium_samples += 1;
+
+        let settlement = (now >= self.next_funding).then(|| {
+            let funding = FundingRate {
+                funding_rate: self.estimated_rate(),
+                funding_time: self.next_funding,
+                mark_price,
+                index_price,
+            };
+            self.premium_sum = 0.0;
+            self.premium_samples = 0;
+            self.next_funding = next_funding_time(now, self.interval_secs);
+
+            self.history.push_back(funding.clone());
+            if self.history.len() > MAX_FUNDING_HISTORY {
+                self.history.pop_front();
+            }
+            funding
+        });
+
+        self.latest = Some(MarkPrice {
+            mark_price,
+            index_price,
+            basis,
+            estimated_funding_rate: self.estimated_rate(),
+            next_funding_time: self.next_funding,
+        });
+
+        settlement
+    }
+
+    // Average premium since the last settlement plus the clamped interest adjustment
+    fn estimated_rate(&self) -> f64 {
+        if self.premium_samples == 0 {
+            return self.interest_rate.clamp(-self.rate_cap, self.rate_cap);
+        }
+
+        let premium = self.premium_sum / self.premium_samples as f64;
+        let adjustment = (self.interest_rate - premium).clamp(-FUNDING_ADJUSTMENT_CLAMP, FUNDING_ADJUSTMENT_CLAMP);
+        (premium + adjustment).clamp(-self.rate_cap, self.rate_cap)
+    }
+
+    pub fn mark_price(&self) -> Option<&MarkPrice> {
+        self.latest.as_ref()
+    }
+
+    pub fn last_funding(&self) -> Option<&FundingRate> {
+        self.history.back()
+    }
+
+    // Up to `limit` most recent settlements, oldest first
+    pub fn funding_history(&self, limit: usize) -> Vec<FundingRate> {
+        self.history.iter().skip(self.history.len().saturating_sub(limit)).cloned().collect()
+    }
+}
+
+fn next_funding_time(now: DateTime<Utc>, interval_secs: i64) -> DateTime<Utc> {
+    let next = (now.timestamp().div_euclid(interval_secs) + 1) * interval_secs;
+    Utc.timestamp_opt(next, 0).single().unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
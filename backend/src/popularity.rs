@@ -0,0 +1,126 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::time::Duration;
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+
+// How often subscriber counts are sampled and the simulation order is revised
+pub const POPULARITY_SAMPLE_INTERVAL: Duration = Duration::from_secs(10);
+
+// Samples kept per symbol, an hour's worth
+const POPULARITY_SAMPLES: usize = 360;
+
+// Subscribers and delivered messages per symbol over time. The simulation updates the
+// books with the most subscribers first in every tick, so under load the most watched
+// books reach their subscribers first.
+#[derive(Debug, Default)]
+pub struct SymbolPopularity {
+    symbols: DashMap<String, SymbolActivity>,
+    // Subscribers of each symbol at the last sample
+    priorities: ArcSwap<HashMap<String, usize>>,
+}
+
+#[derive(Debug, Default)]
+struct SymbolActivity {
+    subscribers: usize,
+    messages: u64,
+    bytes: u64,
+    // Delivered since the last sample
+    pending_messages: u64,
+    pending_bytes: u64,
+    history: VecDeque<PopularitySample>,
+}
+
+// Subscribers when the sample was taken, and what was delivered since the previous one
+#[derive(Debug, Clone, Serialize)]
+pub struct PopularitySample {
+    pub timestamp: DateTime<Utc>,
+    pub subscribers: usize,
+    pub messages: u64,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SymbolPopularityStats {
+    pub symbol: String,
+    pub subscribers: usize,
+    pub messages: u64,
+    pub bytes: u64,
+    pub history: Vec<PopularitySample>,
+}
+
+impl SymbolPopularity {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, symbol: &str, bytes: usize) {
+        let bytes = bytes as u64;
+        let mut activity = match self.symbols.get_mut(symbol) {
+            Some(activity) => activity,
+            None => self.symbols.entry(symbol.to_string()).or_default(),
+        };
+        activity.messages += 1;
+        activity.bytes += bytes;
+        activity.pending_messages += 1;
+        activity.pending_bytes += bytes;
+    }
+
+    // Closes the current interval with the subscriber count of every symbol. Symbols with
+    // nothing in their history are forgotten.
+    pub fn sample(&self, subscribers: &[(String, usize)], now: DateTime<Utc>) {
+        for (symbol, _) in subscribers {
+            if !self.symbols.contains_key(symbol) {
+                self.symbols.insert(symbol.clone(), SymbolActivity::default());
+            }
+        }
+
+        let counts: HashMap<&str, usize> = subscribers.iter().map(|(symbol, count)| (symbol.as_str(), *count)).collect();
+        self.symbols.retain(|symbol, activity| {
+            activity.subscribers = counts.get(symbol.as_str()).copied().unwrap_or(0);
+            if activity.history.len() == POPULARITY_SAMPLES {
+                activity.history.pop_front();
+            }
+            activity.history.push_back(PopularitySample {
+                timestamp: now,
+                subscribers: activity.subscribers,
+                messages: std::mem::take(&mut activity.pending_messages),
+                bytes: std::mem::take(&mut activity.pending_bytes),
+            });
+            activity.history.iter().any(|sample| sample.subscribers > 0 || sample.messages > 0)
+        });
+
+        self.priorities.store(Arc::new(subscribers.iter().cloned().collect()));
+    }
+
+    // Orders books most subscribed first, as of the last sample
+    pub fn rank<T>(&self, books: &mut [(String, T)]) {
+        let priorities = self.priorities.load();
+        books.sort_by_key(|(symbol, _)| Reverse(priorities.get(symbol).copied().unwrap_or(0)));
+    }
+
+    pub fn forget(&self, symbol: &str) {
+        self.symbols.remove(symbol);
+    }
+
+    // Most subscribed first, then most messages delivered
+    pub fn snapshot(&self, limit: usize) -> Vec<SymbolPopularityStats> {
+        let mut stats: Vec<SymbolPopularityStats> = self
+            .symbols
+            .iter()
+            .map(|entry| SymbolPopularityStats {
+                symbol: entry.key().clone(),
+                subscribers: entry.subscribers,
+                messages: entry.messages,
+                bytes: entry.bytes,
+                history: entry.history.iter().cloned().collect(),
+            })
+            .collect();
+        stats.sort_by_key(|stats| (Reverse(stats.subscribers), Reverse(stats.messages)));
+        stats.truncate(limit);
+        stats
+    }
+}
@@ -0,0 +1,108 @@
+use std::fmt;
+use std::sync::Arc;
+use chrono::Utc;
+use clap::Args;
+use serde::Deserialize;
+
+use crate::message::{BandAction, OrderId, OutOfBandEvent, OutOfBandKind, Quantity, Side};
+
+// Widest band, ten times the reference price
+const MAX_BAND_PCT: f64 = 1000.0;
+
+#[derive(Debug, Clone, Default, Args)]
+pub struct PriceBandOptions {
+    /// Keep orders and trades of every book within this percentage of its reference price,
+    /// the mid or else the last trade; a symbol's price_band in --symbol-config overrides it
+    /// (default: no band)
+    #[arg(long, value_name = "PERCENT")]
+    pub price_band_pct: Option<f64>,
+
+    /// What becomes of events outside the band: reject, or quarantine to also publish them
+    /// on the symbol's Quarantine streams
+    #[arg(long, value_name = "ACTION", value_parser = parse_band_action, default_value = "reject")]
+    pub price_band_action: BandAction,
+}
+
+impl PriceBandOptions {
+    pub fn band(&self) -> Result<Option<PriceBand>, String> {
+        let Some(pct) = self.price_band_pct else {
+            return Ok(None);
+        };
+        let band = PriceBand { pct, action: self.price_band_action };
+        band.validate().map_err(|e| format!("--price-band-pct: {}", e))?;
+        Ok(Some(band))
+    }
+}
+
+fn parse_band_action(s: &str) -> Result<BandAction, String> {
+    match s {
+        "reject" => Ok(BandAction::Reject),
+        "quarantine" => Ok(BandAction::Quarantine),
+        _ => Err(format!("Unknown band action '{}', expected reject or quarantine", s)),
+    }
+}
+
+// How far from its reference price a book takes orders and trades
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PriceBand {
+    pub pct: f64,
+    #[serde(default)]
+    pub action: BandAction,
+}
+
+impl PriceBand {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.pct.is_finite() || self.pct <= 0.0 || self.pct > MAX_BAND_PCT {
+            return Err(format!("band must be above 0 and at most {}%, got {}", MAX_BAND_PCT, self.pct));
+        }
+        Ok(())
+    }
+
+    pub fn check(&self, reference_price: f64, price: f64) -> Result<(), BandBreach> {
+        if (price - reference_price).abs() > reference_price * self.pct / 100.0 {
+            return Err(BandBreach { price, reference_price, band: *self });
+        }
+        Ok(())
+    }
+}
+
+// A price outside its book's band
+#[derive(Debug, Clone, Copy)]
+pub struct BandBreach {
+    pub price: f64,
+    pub reference_price: f64,
+    pub band: PriceBand,
+}
+
+impl BandBreach {
+    pub fn event(
+        &self,
+        event: OutOfBandKind,
+        symbol: &Arc<str>,
+        order_id: Option<OrderId>,
+        side: Option<Side>,
+        quantity: Quantity,
+        venue_sequence: Option<u64>,
+    ) -> OutOfBandEvent {
+        OutOfBandEvent {
+            event,
+            order_id,
+            symbol: Arc::clone(symbol),
+            side,
+            price: self.price,
+            quantity,
+            reference_price: self.reference_price,
+            band_pct: self.band.pct,
+            action: self.band.action,
+            timestamp: Utc::now(),
+            venue_sequence,
+        }
+    }
+}
+
+impl fmt::Display for BandBreach {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Price {} is more than {}% from the reference price {}", self.price, self.band.pct, self.reference_price)
+    }
+}
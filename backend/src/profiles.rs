@@ -0,0 +1,123 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use clap::Args;
+use tracing::warn;
+
+use crate::message::StreamSpec;
+
+const MAX_PROFILE_NAME_LEN: usize = 64;
+
+// Named subscription profiles saved by authenticated clients, so a reconnecting client or
+// another device using the same API key restores its streams with one LoadProfile
+#[derive(Debug, Clone, Args)]
+pub struct ProfileOptions {
+    /// Keep subscription profiles in this JSON file so they survive restarts (default: in
+    /// memory only)
+    #[arg(long, value_name = "PATH")]
+    pub profile_file: Option<PathBuf>,
+
+    /// Profiles each API key may save
+    #[arg(long, value_name = "N", default_value_t = 20)]
+    pub max_profiles_per_key: usize,
+
+    /// Streams one profile may hold
+    #[arg(long, value_name = "N", default_value_t = 100)]
+    pub max_profile_streams: usize,
+}
+
+impl ProfileOptions {
+    pub fn store(&self) -> io::Result<ProfileStore> {
+        let profiles = match &self.profile_file {
+            Some(path) => load(path)?,
+            None => HashMap::new(),
+        };
+        Ok(ProfileStore {
+            profiles: Mutex::new(profiles),
+            file: self.profile_file.clone(),
+            max_profiles_per_key: self.max_profiles_per_key,
+            max_profile_streams: self.max_profile_streams,
+        })
+    }
+}
+
+// Profiles by API key, then by name
+type Profiles = HashMap<String, HashMap<String, Vec<StreamSpec>>>;
+
+#[derive(Debug)]
+pub struct ProfileStore {
+    profiles: Mutex<Profiles>,
+    file: Option<PathBuf>,
+    max_profiles_per_key: usize,
+    max_profile_streams: usize,
+}
+
+impl Default for ProfileStore {
+    fn default() -> Self {
+        Self {
+            profiles: Mutex::new(HashMap::new()),
+            file: None,
+            max_profiles_per_key: 20,
+            max_profile_streams: 100,
+        }
+    }
+}
+
+impl ProfileStore {
+    // Replaces the key's profile of that name; saving no streams deletes it
+    pub fn save(&self, api_key: &str, name: &str, streams: Vec<StreamSpec>) -> Result<(), String> {
+        if name.is_empty() || name.len() > MAX_PROFILE_NAME_LEN {
+            return Err(format!("Profile name must be 1 to {} characters", MAX_PROFILE_NAME_LEN));
+        }
+        if streams.len() > self.max_profile_streams {
+            return Err(format!("A profile holds at most {} streams, got {}", self.max_profile_streams, streams.len()));
+        }
+        let mut stream_ids = HashSet::new();
+        if let Some(spec) = streams.iter().find(|spec| !stream_ids.insert(spec.stream_id.as_str())) {
+            return Err(format!("Stream {} appears more than once", spec.stream_id));
+        }
+
+        let mut profiles = self.profiles.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let saved = profiles.entry(api_key.to_string()).or_default();
+        if streams.is_empty() {
+            saved.remove(name);
+            if saved.is_empty() {
+                profiles.remove(api_key);
+            }
+        } else {
+            if !saved.contains_key(name) && saved.len() >= self.max_profiles_per_key {
+                return Err(format!("At most {} profiles per API key", self.max_profiles_per_key));
+            }
+            saved.insert(name.to_string(), streams);
+        }
+
+        if let Some(path) = &self.file {
+            if let Err(e) = save(path, &profiles) {
+                warn!("Failed to save subscription profiles to {}: {}", path.display(), e);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn load(&self, api_key: &str, name: &str) -> Option<Vec<StreamSpec>> {
+        let profiles = self.profiles.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        profiles.get(api_key)?.get(name).cloned()
+    }
+}
+
+fn load(path: &Path) -> io::Result<Profiles> {
+    match std::fs::read(path) {
+        Ok(contents) => serde_json::from_slice(&contents)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(HashMap::new()),
+        Err(e) => Err(e),
+    }
+}
+
+// Written next to the file and renamed over it, so a crash never leaves it half written
+fn save(path: &Path, profiles: &Profiles) -> io::Result<()> {
+    let staged = path.with_extension("tmp");
+    std::fs::write(&staged, serde_json::to_vec(profiles)?)?;
+    std::fs::rename(staged, path)
+}
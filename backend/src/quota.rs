@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use chrono::{DateTime, Datelike, NaiveDate, Utc};
+use clap::Args;
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::audit::{AuditEvent, AuditLog};
+use crate::message::{QuotaPeriod, QuotaRemaining, QuotaStatus, QuotaUnit};
+
+// Market data delivered per stream while a key is throttled
+const THROTTLED_INTERVAL: Duration = Duration::from_secs(1);
+
+// Delivery quotas per authenticated API key, reset at the start of each UTC day and month
+#[derive(Debug, Clone, Default, Args)]
+pub struct QuotaOptions {
+    /// Messages each API key may receive per UTC day (default: unlimited)
+    #[arg(long, value_name = "N")]
+    pub daily_message_quota: Option<u64>,
+
+    /// Bytes each API key may receive per UTC day (default: unlimited)
+    #[arg(long, value_name = "BYTES")]
+    pub daily_byte_quota: Option<u64>,
+
+    /// Messages each API key may receive per UTC month (default: unlimited)
+    #[arg(long, value_name = "N")]
+    pub monthly_message_quota: Option<u64>,
+
+    /// Bytes each API key may receive per UTC month (default: unlimited)
+    #[arg(long, value_name = "BYTES")]
+    pub monthly_byte_quota: Option<u64>,
+
+    /// Share of a quota after which market data is throttled to one update per stream per second
+    #[arg(long, value_name = "RATIO", default_value_t = 0.8)]
+    pub quota_throttle_ratio: f64,
+}
+
+impl QuotaOptions {
+    pub fn is_enabled(&self) -> bool {
+        self.limits().next().is_some()
+    }
+
+    fn limits(&self) -> impl Iterator<Item = (QuotaPeriod, QuotaUnit, u64)> {
+        [
+            (QuotaPeriod::Daily, QuotaUnit::Messages, self.daily_message_quota),
+            (QuotaPeriod::Daily, QuotaUnit::Bytes, self.daily_byte_quota),
+            (QuotaPeriod::Monthly, QuotaUnit::Messages, self.monthly_message_quota),
+            (QuotaPeriod::Monthly, QuotaUnit::Bytes, self.monthly_byte_quota),
+        ]
+        .into_iter()
+        .filter_map(|(period, unit, limit)| Some((period, unit, limit?)))
+    }
+}
+
+impl QuotaPeriod {
+    fn start(self, now: DateTime<Utc>) -> DateTime<Utc> {
+        let date = match self {
+            QuotaPeriod::Daily => now.date_naive(),
+            QuotaPeriod::Monthly => now.date_naive().with_day(1).unwrap_or(now.date_naive()),
+        };
+        midnight(date)
+    }
+
+    fn end(self, start: DateTime<Utc>) -> DateTime<Utc> {
+        let date = start.date_naive();
+        let next = match self {
+            QuotaPeriod::Daily => date.succ_opt(),
+            QuotaPeriod::Monthly if date.month() == 12 => NaiveDate::from_ymd_opt(date.year() + 1, 1, 1),
+            QuotaPeriod::Monthly => NaiveDate::from_ymd_opt(date.year(), date.month() + 1, 1),
+        };
+        next.map_or(DateTime::<Utc>::MAX_UTC, midnight)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum QuotaDecision {
+    Deliver,
+    // Over the throttle threshold and this stream was updated too recently
+    Skip,
+    Exceeded(QuotaRemaining),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct PeriodUsage {
+    start: DateTime<Utc>,
+    messages: u64,
+    bytes: u64,
+}
+
+impl PeriodUsage {
+    fn current(&mut self, period: QuotaPeriod, now: DateTime<Utc>) -> &mut Self {
+        let start = period.start(now);
+        if self.start != start {
+            *self = PeriodUsage { start, messages: 0, bytes: 0 };
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct KeyUsage {
+    daily: PeriodUsage,
+    monthly: PeriodUsage,
+}
+
+impl KeyUsage {
+    fn new(now: DateTime<Utc>) -> Self {
+        Self {
+            daily: PeriodUsage { start: QuotaPeriod::Daily.start(now), messages: 0, bytes: 0 },
+            monthly: PeriodUsage { start: QuotaPeriod::Monthly.start(now), messages: 0, bytes: 0 },
+        }
+    }
+
+    fn period(&mut self, period: QuotaPeriod, now: DateTime<Utc>) -> &mut PeriodUsage {
+        match period {
+            QuotaPeriod::Daily => self.daily.current(period, now),
+            QuotaPeriod::Monthly => self.monthly.current(period, now),
+        }
+    }
+
+    fn used(&mut self, period: QuotaPeriod, unit: QuotaUnit, now: DateTime<Utc>) -> u64 {
+        let period_usage = self.period(period, now);
+        match unit {
+            QuotaUnit::Messages => period_usage.messages,
+            QuotaUnit::Bytes => period_usage.bytes,
+        }
+    }
+}
+
+// Usage per API key, with the key each connected client is billed to
+#[derive(Debug)]
+pub struct QuotaTracker {
+    limits: Vec<(QuotaPeriod, QuotaUnit, u64)>,
+    throttle_ratio: f64,
+    keys: DashMap<Arc<str>, KeyUsage>,
+    clients: DashMap<Uuid, Arc<str>>,
+    // When each throttled stream of a client was last delivered to
+    last_delivery: DashMap<Uuid, HashMap<String, Instant>>,
+    audit: Arc<AuditLog>,
+}
+
+impl QuotaTracker {
+    pub fn new(options: &QuotaOptions) -> Self {
+        Self {
+            limits: options.limits().collect(),
+            throttle_ratio: options.quota_throttle_ratio.clamp(0.0, 1.0),
+            keys: DashMap::new(),
+            clients: DashMap::new(),
+            last_delivery: DashMap::new(),
+            audit: Arc::new(AuditLog::disabled()),
+        }
+    }
+
+    // Records every client cut off by a quota
+    pub fn set_audit_log(&mut self, audit: Arc<AuditLog>) {
+        self.audit = audit;
+    }
+
+    pub fn bind(&self, client_id: Uuid, api_key: &str) {
+        self.clients.insert(client_id, Arc::from(api_key));
+    }
+
+    pub fn unbind(&self, client_id: &Uuid) {
+        self.clients.remove(client_id);
+        self.last_delivery.remove(client_id);
+    }
+
+    pub fn api_key(&self, client_id: &Uuid) -> Option<Arc<str>> {
+        self.clients.get(client_id).map(|api_key| Arc::clone(&api_key))
+    }
+
+    pub fn record(&self, client_id: &Uuid, bytes: usize) {
+        let Some(api_key) = self.api_key(client_id) else {
+            return;
+        };
+
+        let now = Utc::now();
+        let mut usage = match self.keys.get_mut(&api_key) {
+            Some(usage) => usage,
+            None => self.keys.entry(api_key).or_insert_with(|| KeyUsage::new(now)),
+        };
+        for period in [QuotaPeriod::Daily, QuotaPeriod::Monthly] {
+            let period = usage.period(period, now);
+            period.messages += 1;
+            period.bytes += bytes as u64;
+        }
+    }
+
+    // Decides whether a stream update may be delivered to a client; clients without a key are not limited
+    pub fn check(&self, client_id: &Uuid, stream: &str) -> QuotaDecision {
+        let Some(api_key) = self.api_key(client_id) else {
+            return QuotaDecision::Deliver;
+        };
+
+        let now = Utc::now();
+        let mut usage = self.usage(&api_key, now);
+        let mut throttled = false;
+        for &(period, unit, limit) in &self.limits {
+            let used = usage.used(period, unit, now);
+            if used >= limit {
+                return QuotaDecision::Exceeded(quota_remaining(&mut usage, period, unit, limit, now));
+            }
+            throttled |= self.is_throttled(used, limit);
+        }
+        if !throttled {
+            return QuotaDecision::Deliver;
+        }
+
+        let now = Instant::now();
+        let mut streams = self.last_delivery.entry(*client_id).or_default();
+        match streams.get_mut(stream) {
+            Some(last) if now.duration_since(*last) < THROTTLED_INTERVAL => QuotaDecision::Skip,
+            Some(last) => {
+                *last = now;
+                QuotaDecision::Deliver
+            }
+            None => {
+                streams.insert(stream.to_string(), now);
+                QuotaDecision::Deliver
+            }
+        }
+    }
+
+    pub fn report_exceeded(&self, client_id: &Uuid, quota: &QuotaRemaining) {
+        if let Some(api_key) = self.api_key(client_id) {
+            self.audit.record(AuditEvent::QuotaExceeded {
+                api_key: api_key.to_string(),
+                period: quota.period,
+                unit: quota.unit,
+                limit: quota.limit,
+            });
+        }
+    }
+
+    // The first quota of the client's key that is used up, if any
+    pub fn exceeded(&self, client_id: &Uuid) -> Option<QuotaRemaining> {
+        self.exceeded_key(&self.api_key(client_id)?)
+    }
+
+    pub fn exceeded_key(&self, api_key: &str) -> Option<QuotaRemaining> {
+        self.remaining(api_key).into_iter().find(|quota| quota.remaining == 0)
+    }
+
+    pub fn status(&self, api_key: &str) -> QuotaStatus {
+        let quotas = self.remaining(api_key);
+
+        QuotaStatus {
+            api_key: api_key.to_string(),
+            throttled: quotas.iter().any(|quota| self.is_throttled(quota.used, quota.limit)),
+            exceeded: quotas.iter().any(|quota| quota.remaining == 0),
+            quotas,
+            timestamp: Utc::now(),
+        }
+    }
+
+    fn remaining(&self, api_key: &str) -> Vec<QuotaRemaining> {
+        let now = Utc::now();
+        let mut usage = self.usage(api_key, now);
+        self.limits
+            .iter()
+            .map(|&(period, unit, limit)| quota_remaining(&mut usage, period, unit, limit, now))
+            .collect()
+    }
+
+    fn usage(&self, api_key: &str, now: DateTime<Utc>) -> KeyUsage {
+        self.keys.get(api_key).map(|usage| *usage).unwrap_or_else(|| KeyUsage::new(now))
+    }
+
+    fn is_throttled(&self, used: u64, limit: u64) -> bool {
+        used as f64 >= limit as f64 * self.throttle_ratio
+    }
+}
+
+fn quota_remaining(usage: &mut KeyUsage, period: QuotaPeriod, unit: QuotaUnit, limit: u64, now: DateTime<Utc>) -> QuotaRemaining {
+    let used = usage.used(period, unit, now);
+    QuotaRemaining {
+        period,
+        unit,
+        limit,
+        used,
+        remaining: limit.saturating_sub(used),
+        resets_at: period.end(usage.period(period, now).start),
+    }
+}
+
+fn midnight(date: NaiveDate) -> DateTime<Utc> {
+    date.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc()
+}
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::message::{MBPLevel, Quantity, Side};
+use crate::order_book::OrderBook;
+
+// Prices are matched once rounded to this many units per 1.0, so a client's
+// float round-tripping does not show up as a difference
+const PRICE_SCALE: f64 = 1e8;
+
+// A client's reconstructed view of the top of a book. Levels are matched by price in
+// any order; MBP levels from a MarketData update can be posted as they are.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReconcileRequest {
+    #[serde(default)]
+    pub bids: Vec<ClientLevel>,
+    #[serde(default)]
+    pub asks: Vec<ClientLevel>,
+    pub depth: Option<u32>, // Levels compared per side; the longer side submitted by default
+    pub sequence: Option<u64>, // Last sequence the client applied, echoed back
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClientLevel {
+    pub price: f64,
+    pub quantity: Quantity,
+    pub order_count: Option<u32>, // Compared only when given
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelIssue {
+    Missing,    // On the server but not in the client's view
+    Unexpected, // In the client's view but not on the server
+    Quantity,
+    OrderCount,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct LevelDiff {
+    pub price: f64,
+    pub issue: LevelIssue,
+    pub server_quantity: Option<Quantity>,
+    pub client_quantity: Option<Quantity>,
+    pub server_orders: Option<u32>,
+    pub client_orders: Option<u32>,
+}
+
+// Differences best price first; both lists empty means the view matches the book
+#[derive(Debug, Clone, Serialize)]
+pub struct ReconcileReport {
+    pub symbol: String,
+    pub sequence: u64,
+    pub client_sequence: Option<u64>,
+    pub depth: u32,
+    pub in_sync: bool,
+    pub bids: Vec<LevelDiff>,
+    pub asks: Vec<LevelDiff>,
+    pub timestamp: DateTime<Utc>,
+}
+
+pub fn reconcile(order_book: &OrderBook, request: &ReconcileRequest) -> ReconcileReport {
+    let depth = request.depth.unwrap_or_else(|| match request.bids.len().max(request.asks.len()) {
+        0 => 10,
+        longest => longest as u32,
+    });
+    let (server_bids, server_asks) = order_book.get_mbp_data(depth);
+
+    let bids = diff_side(&Side::Bid, &server_bids, &request.bids, depth);
+    let asks = diff_side(&Side::Ask, &server_asks, &request.asks, depth);
+
+    ReconcileReport {
+        symbol: order_book.symbol.to_string(),
+        sequence: order_book.get_sequence(),
+        client_sequence: request.sequence,
+        depth,
+        in_sync: bids.is_empty() && asks.is_empty(),
+        bids,
+        asks,
+        timestamp: Utc::now(),
+    }
+}
+
+fn diff_side(side: &Side, server: &[MBPLevel], client: &[ClientLevel], depth: u32) -> Vec<LevelDiff> {
+    // The client's best `depth` levels, so both sides cover the same part of the book
+    let mut client: Vec<&ClientLevel> = client.iter().collect();
+    match side {
+        Side::Bid => client.sort_by(|a, b| b.price.total_cmp(&a.price)),
+        Side::Ask => client.sort_by(|a, b| a.price.total_cmp(&b.price)),
+    }
+    client.truncate(depth as usize);
+
+    let mut levels: BTreeMap<i64, (Option<&MBPLevel>, Option<&ClientLevel>)> = BTreeMap::new();
+    for level in server {
+        levels.entry(price_key(level.price)).or_default().0 = Some(level);
+    }
+    for level in client {
+        levels.entry(price_key(level.price)).or_default().1 = Some(level);
+    }
+
+    let ordered: Box<dyn Iterator<Item = _>> = match side {
+        Side::Bid => Box::new(levels.into_values().rev()),
+        Side::Ask => Box::new(levels.into_values()),
+    };
+
+    ordered
+        .filter_map(|(server, client)| {
+            let price = server.map(|level| level.price).or(client.map(|level| level.price))?;
+            let issue = match (server, client) {
+                (Some(_), None) => LevelIssue::Missing,
+                (None, Some(_)) => LevelIssue::Unexpected,
+                (Some(server), Some(client)) if server.quantity != client.quantity => LevelIssue::Quantity,
+                (Some(server), Some(client)) if client.order_count.is_some_and(|orders| orders != server.order_count) => {
+                    LevelIssue::OrderCount
+                }
+                _ => return None,
+            };
+
+            Some(LevelDiff {
+                price,
+                issue,
+                server_quantity: server.map(|level| level.quantity),
+                client_quantity: client.map(|level| level.quantity),
+                server_orders: server.map(|level| level.order_count),
+                client_orders: client.and_then(|level| level.order_count),
+            })
+        })
+        .collect()
+}
+
+fn price_key(price: f64) -> i64 {
+    (price * PRICE_SCALE).round() as i64
+}
@@ -0,0 +1,104 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use chrono::Utc;
+
+use crate::message::{ReferenceChange, ReferenceChangeKind, ReferenceChanges, ReferenceSnapshot, SymbolInfo, SymbolStatus};
+
+// Changes kept for clients catching up; those holding an older version refetch the registry
+const REFERENCE_LOG_CAPACITY: usize = 10_000;
+
+// Versioned copy of the symbols GET /symbols lists. Every change of a symbol's metadata
+// takes the next version, so caches of it can apply what changed since the version they
+// hold instead of refetching every symbol. Versions start over with the server.
+#[derive(Debug, Default)]
+pub struct ReferenceRegistry {
+    state: Mutex<RegistryState>,
+}
+
+#[derive(Debug, Default)]
+struct RegistryState {
+    version: u64,
+    symbols: HashMap<String, SymbolInfo>,
+    changes: VecDeque<ReferenceChange>,
+}
+
+impl ReferenceRegistry {
+    // Brings the registry up to the symbols listed now. `publish` is handed the changes,
+    // if any, before another update can make newer ones.
+    pub fn update(&self, symbols: Vec<SymbolInfo>, publish: impl FnOnce(u64, &[ReferenceChange])) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut listed: HashMap<String, SymbolInfo> = symbols.into_iter().map(|info| (info.symbol.clone(), info)).collect();
+        let mut names: Vec<String> = state.symbols.keys().chain(listed.keys()).cloned().collect();
+        names.sort();
+        names.dedup();
+
+        let timestamp = Utc::now();
+        let mut changes = Vec::new();
+        for symbol in names {
+            let info = listed.remove(&symbol);
+            let kinds = match (state.symbols.get(&symbol), &info) {
+                (None, Some(_)) => vec![ReferenceChangeKind::Added],
+                (Some(_), None) => vec![ReferenceChangeKind::Removed],
+                (Some(old), Some(new)) => {
+                    let mut kinds = Vec::new();
+                    match (old.status, new.status) {
+                        (SymbolStatus::Open, SymbolStatus::Halted) => kinds.push(ReferenceChangeKind::Halted),
+                        (SymbolStatus::Halted, SymbolStatus::Open) => kinds.push(ReferenceChangeKind::Resumed),
+                        _ => {}
+                    }
+                    if *old != (SymbolInfo { status: old.status, ..new.clone() }) {
+                        kinds.push(ReferenceChangeKind::Updated);
+                    }
+                    kinds
+                }
+                (None, None) => Vec::new(),
+            };
+            if kinds.is_empty() {
+                continue;
+            }
+
+            for change in kinds {
+                state.version += 1;
+                changes.push(ReferenceChange { version: state.version, change, symbol: symbol.clone(), info: info.clone(), timestamp });
+            }
+            match info {
+                Some(info) => state.symbols.insert(symbol, info),
+                None => state.symbols.remove(&symbol),
+            };
+        }
+
+        if changes.is_empty() {
+            return;
+        }
+        state.changes.extend(changes.iter().cloned());
+        let excess = state.changes.len().saturating_sub(REFERENCE_LOG_CAPACITY);
+        state.changes.drain(..excess);
+        publish(state.version, &changes);
+    }
+
+    pub fn version(&self) -> u64 {
+        self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).version
+    }
+
+    pub fn snapshot(&self) -> ReferenceSnapshot {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut symbols: Vec<SymbolInfo> = state.symbols.values().cloned().collect();
+        symbols.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        ReferenceSnapshot { version: state.version, symbols }
+    }
+
+    // None when the changes after `version` are no longer kept, or `version` is from
+    // before a restart and ahead of the registry
+    pub fn changes_since(&self, version: u64) -> Option<ReferenceChanges> {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if version > state.version {
+            return None;
+        }
+        let oldest_kept = state.changes.front().map_or(state.version + 1, |change| change.version);
+        if version < state.version && version + 1 < oldest_kept {
+            return None;
+        }
+        let changes = state.changes.iter().filter(|change| change.version > version).cloned().collect();
+        Some(ReferenceChanges { version: state.version, changes })
+    }
+}
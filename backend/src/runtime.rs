@@ -0,0 +1,146 @@
+use std::future;
+use std::io;
+use std::thread;
+use std::time::Duration;
+use clap::Args;
+use tokio::runtime::{Builder, Handle, Runtime};
+use tracing::{info, warn};
+
+use crate::stream_manager::SIMULATION_INTERVAL;
+
+// Runtime layout and latency knobs for relay deployments
+#[derive(Debug, Clone, Args)]
+pub struct RuntimeOptions {
+    /// Tokio worker threads for client I/O (default: one per CPU core)
+    #[arg(long, value_name = "N")]
+    pub worker_threads: Option<usize>,
+
+    /// Run the market simulation on its own single-threaded runtime instead of the I/O workers
+    #[arg(long)]
+    pub dedicated_simulation_runtime: bool,
+
+    /// Pin the simulation runtime thread to this CPU core (implies --dedicated-simulation-runtime)
+    #[arg(long, value_name = "CORE")]
+    pub simulation_core: Option<usize>,
+
+    /// Latency-focused mode: dedicated simulation runtime, TCP_NODELAY on client sockets and
+    /// more frequent I/O polling at some cost in throughput
+    #[arg(long)]
+    pub hf_mode: bool,
+
+    /// Spread each simulation tick's book updates, and so their fan-out, over this share of
+    /// the tick interval in deadline-paced slices instead of one burst (default: no spreading)
+    #[arg(long, value_name = "PERCENT")]
+    pub tick_spread_pct: Option<u32>,
+
+    /// Stop fanning out one update of a symbol after this long; its subscribers not reached
+    /// are sent the symbol's next update first (default: no limit)
+    #[arg(long, value_name = "MS")]
+    pub symbol_fanout_timeout_ms: Option<u64>,
+}
+
+impl RuntimeOptions {
+    pub fn tcp_nodelay(&self) -> bool {
+        self.hf_mode
+    }
+
+    // Window of each tick its updates are spread over
+    pub fn tick_spread(&self) -> Result<Option<Duration>, String> {
+        match self.tick_spread_pct {
+            None => Ok(None),
+            Some(pct) if (1..=90).contains(&pct) => Ok(Some(SIMULATION_INTERVAL * pct / 100)),
+            Some(_) => Err("--tick-spread-pct must be between 1 and 90".to_string()),
+        }
+    }
+
+    pub fn symbol_fanout_timeout(&self) -> Result<Option<Duration>, String> {
+        match self.symbol_fanout_timeout_ms {
+            None => Ok(None),
+            Some(0) => Err("--symbol-fanout-timeout-ms must be at least 1".to_string()),
+            Some(ms) => Ok(Some(Duration::from_millis(ms))),
+        }
+    }
+
+    pub fn build_io_runtime(&self) -> io::Result<Runtime> {
+        let mut builder = Builder::new_multi_thread();
+        builder.enable_all().thread_name("io-worker");
+
+        if let Some(worker_threads) = self.worker_threads {
+            builder.worker_threads(worker_threads.max(1));
+        }
+
+        if self.hf_mode {
+            // Poll the I/O driver after every task and drain the global queue more often,
+            // so socket readiness and cross-runtime wakeups are not delayed behind busy workers
+            builder.event_interval(1).global_queue_interval(8);
+        }
+
+        builder.build()
+    }
+
+    // Starts the simulation runtime on its own thread when configured, returning a handle to spawn onto
+    pub fn spawn_simulation_runtime(&self) -> io::Result<Option<Handle>> {
+        if !(self.dedicated_simulation_runtime || self.hf_mode || self.simulation_core.is_some()) {
+            return Ok(None);
+        }
+
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        let handle = runtime.handle().clone();
+        let core = self.simulation_core;
+
+        thread::Builder::new()
+            .name("simulation".to_string())
+            .spawn(move || {
+                if let Some(core) = core {
+                    pin_to_core(core);
+                }
+                runtime.block_on(future::pending::<()>());
+            })?;
+
+        info!("Market simulation running on a dedicated runtime");
+        Ok(Some(handle))
+    }
+}
+
+fn pin_to_core(core: usize) {
+    let core_id = core_affinity::get_core_ids()
+        .and_then(|ids| ids.into_iter().find(|id| id.id == core));
+
+    match core_id {
+        Some(core_id) if core_affinity::set_for_current(core_id) => {
+            info!("Pinned simulation thread to core {}", core);
+        }
+        Some(_) => warn!("Failed to pin simulation thread to core {}", core),
+        None => warn!("Core {} is not available, simulation thread is not pinned", core),
+    }
+}
+
+// Resolves on Ctrl-C, or on SIGTERM on Unix, so the server can close its connections before exiting
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Failed to listen for Ctrl-C: {}", e);
+            future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                warn!("Failed to listen for SIGTERM: {}", e);
+                future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
@@ -0,0 +1,39 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+
+// Widest sampling a subscription can ask for, about five minutes of a simulated book
+pub const MAX_SAMPLE_EVERY_N: u32 = 1000;
+
+pub fn validate_sample_every_n(every_n: u32) -> Result<u32, String> {
+    if every_n == 0 || every_n > MAX_SAMPLE_EVERY_N {
+        return Err(format!("sample_every_n must be between 1 and {}, got {}", MAX_SAMPLE_EVERY_N, every_n));
+    }
+    Ok(every_n)
+}
+
+// Which updates of a sampled stream are sent: every Nth after the last one sent, and every
+// anchored one, which starts the count over. Only the distribution task polls it.
+#[derive(Debug)]
+pub struct UpdateSampler {
+    every_n: u32,
+    since_sent: AtomicU32,
+}
+
+impl UpdateSampler {
+    pub fn new(every_n: u32) -> Self {
+        Self { every_n, since_sent: AtomicU32::new(0) }
+    }
+
+    pub fn every_n(&self) -> u32 {
+        self.every_n
+    }
+
+    pub fn deliver(&self, anchored: bool) -> bool {
+        let since_sent = self.since_sent.load(Ordering::Relaxed) + 1;
+        if anchored || since_sent >= self.every_n {
+            self.since_sent.store(0, Ordering::Relaxed);
+            return true;
+        }
+        self.since_sent.store(since_sent, Ordering::Relaxed);
+        false
+    }
+}
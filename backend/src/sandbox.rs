@@ -0,0 +1,159 @@
+use std::fmt;
+use std::sync::Arc;
+use axum::{
+    body::Bytes,
+    extract::Path,
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::post,
+    Extension, Json, Router,
+};
+use chrono::{DateTime, Utc};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+
+use crate::auth::AuthenticatedKey;
+use crate::tenants::{TenantRegistry, TenantStreams};
+
+// Sandbox symbols are named SANDBOX-<uuid>; no shared book can use the prefix
+pub const SANDBOX_PREFIX: &str = "SANDBOX-";
+
+pub fn is_sandbox(symbol: &str) -> bool {
+    symbol.starts_with(SANDBOX_PREFIX)
+}
+
+// Private books an API key creates through the admin API and drives itself: their price
+// set, crashed or halted on request, and their simulation seeded so a run can be replayed.
+// Only clients authenticated with the owning key can see or subscribe to them.
+#[derive(Debug, Clone, Default, Args)]
+pub struct SandboxOptions {
+    /// Sandbox symbols each API key may hold at once (default: sandboxes are disabled)
+    #[arg(long = "sandboxes-per-key", value_name = "N")]
+    pub sandboxes_per_key: Option<usize>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SandboxRequest {
+    // Mid the book starts around (default: 100)
+    pub price: Option<f64>,
+    // Seed of the simulated order flow (default: random, reported back)
+    pub seed: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum SandboxCommand {
+    // Replaces the resting orders with a book around the price
+    SetPrice { price: f64 },
+    // Sells through the bids down to `percent` below the mid and rebuilds the book there
+    Crash { percent: f64 },
+    Halt,
+    Resume,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SandboxInfo {
+    pub symbol: String,
+    pub seed: u64,
+    pub halted: bool,
+    pub mid_price: Option<f64>,
+    pub sequence: u64,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone)]
+pub enum SandboxError {
+    Unauthenticated,
+    Disabled,
+    LimitReached(usize),
+    SymbolLimit(String),
+    // Unknown, or owned by another key
+    NotFound(String),
+    Invalid(String),
+}
+
+impl fmt::Display for SandboxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SandboxError::Unauthenticated => f.write_str("Sandboxes require a signed request"),
+            SandboxError::Disabled => f.write_str("Sandboxes are disabled"),
+            SandboxError::LimitReached(limit) => write!(f, "Sandbox limit of {} reached", limit),
+            SandboxError::NotFound(symbol) => write!(f, "Unknown sandbox {}", symbol),
+            SandboxError::SymbolLimit(message) | SandboxError::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl IntoResponse for SandboxError {
+    fn into_response(self) -> Response {
+        let status = match self {
+            SandboxError::Unauthenticated => StatusCode::UNAUTHORIZED,
+            SandboxError::Disabled => StatusCode::FORBIDDEN,
+            SandboxError::LimitReached(_) => StatusCode::TOO_MANY_REQUESTS,
+            SandboxError::SymbolLimit(_) => StatusCode::SERVICE_UNAVAILABLE,
+            SandboxError::NotFound(_) => StatusCode::NOT_FOUND,
+            SandboxError::Invalid(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        };
+        (status, self.to_string()).into_response()
+    }
+}
+
+// Served on the admin API and signed like the rest of it; the signing key owns the sandbox
+pub fn sandbox_router(tenants: Arc<TenantRegistry>) -> Router {
+    Router::new()
+        .route("/sandboxes", post(create_handler).get(list_handler))
+        .route("/sandboxes/:symbol", post(command_handler).get(get_handler).delete(delete_handler))
+        .with_state(tenants)
+}
+
+fn owner(key: Option<Extension<AuthenticatedKey>>) -> Result<String, SandboxError> {
+    key.map(|Extension(AuthenticatedKey(api_key))| api_key).ok_or(SandboxError::Unauthenticated)
+}
+
+async fn create_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+    body: Bytes,
+) -> Result<(StatusCode, Json<SandboxInfo>), SandboxError> {
+    // The body is optional; without one the book starts at 100 with a random seed
+    let request: SandboxRequest = if body.is_empty() {
+        SandboxRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| SandboxError::Invalid(format!("Invalid sandbox request: {}", e)))?
+    };
+    let sandbox = stream_manager.create_sandbox(&owner(key)?, &request).await?;
+    Ok((StatusCode::CREATED, Json(sandbox)))
+}
+
+async fn list_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+) -> Result<Json<Vec<SandboxInfo>>, SandboxError> {
+    Ok(Json(stream_manager.list_sandboxes(&owner(key)?)))
+}
+
+async fn get_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+    Path(symbol): Path<String>,
+) -> Result<Json<SandboxInfo>, SandboxError> {
+    stream_manager.get_sandbox(&owner(key)?, &symbol).map(Json)
+}
+
+async fn command_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+    Path(symbol): Path<String>,
+    Json(command): Json<SandboxCommand>,
+) -> Result<Json<SandboxInfo>, SandboxError> {
+    stream_manager.control_sandbox(&owner(key)?, &symbol, &command).map(Json)
+}
+
+async fn delete_handler(
+    TenantStreams(stream_manager): TenantStreams,
+    key: Option<Extension<AuthenticatedKey>>,
+    Path(symbol): Path<String>,
+) -> Result<StatusCode, SandboxError> {
+    stream_manager.delete_sandbox(&owner(key)?, &symbol).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
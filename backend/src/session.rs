@@ -0,0 +1,102 @@
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use dashmap::DashMap;
+use tokio::time::interval;
+use uuid::Uuid;
+
+pub const SESSION_TOKEN_PARAM: &str = "session_token";
+
+// Sessions issued to WebSocket clients that connect with a signed handshake
+#[derive(Debug, Clone, Args)]
+pub struct SessionOptions {
+    /// Lifetime of a session before it must be renewed, in seconds
+    #[arg(long, value_name = "SECS", default_value_t = 3600)]
+    pub session_ttl_secs: u64,
+
+    /// Send SessionExpiring this many seconds before a session expires
+    #[arg(long, value_name = "SECS", default_value_t = 60)]
+    pub session_warning_secs: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub token: String,
+    pub api_key: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl Session {
+    pub fn remaining(&self) -> Duration {
+        (self.expires_at - Utc::now()).to_std().unwrap_or_default()
+    }
+}
+
+// Live sessions by token, so that a client can resume its session on reconnect
+#[derive(Debug)]
+pub struct SessionStore {
+    ttl: Duration,
+    warning: Duration,
+    sessions: DashMap<String, Session>,
+}
+
+impl SessionStore {
+    pub fn new(options: &SessionOptions) -> Self {
+        Self {
+            ttl: Duration::from_secs(options.session_ttl_secs.max(1)),
+            warning: Duration::from_secs(options.session_warning_secs),
+            sessions: DashMap::new(),
+        }
+    }
+
+    pub fn warning(&self) -> Duration {
+        self.warning
+    }
+
+    pub fn issue(&self, api_key: &str) -> Session {
+        let session = Session {
+            token: Uuid::new_v4().simple().to_string(),
+            api_key: api_key.to_string(),
+            expires_at: Utc::now() + self.ttl,
+        };
+
+        self.sessions.insert(session.token.clone(), session.clone());
+        session
+    }
+
+    // Returns the session for a token if it has not expired
+    pub fn resume(&self, token: &str) -> Option<Session> {
+        self.sessions
+            .get(token)
+            .filter(|session| session.expires_at > Utc::now())
+            .map(|session| session.clone())
+    }
+
+    // Replaces the previous session, if any, with a fresh one for the given key
+    pub fn renew(&self, previous: Option<&Session>, api_key: &str) -> Session {
+        if let Some(previous) = previous {
+            self.revoke(&previous.token);
+        }
+
+        self.issue(api_key)
+    }
+
+    pub fn revoke(&self, token: &str) {
+        self.sessions.remove(token);
+    }
+
+    // Periodically drops sessions that expired without their connection revoking them
+    pub fn start_pruning(self: &Arc<Self>) {
+        let store = Arc::clone(self);
+        tokio::spawn(async move {
+            let mut interval = interval(Duration::from_secs(60));
+
+            loop {
+                interval.tick().await;
+                let now = Utc::now();
+                store.sessions.retain(|_, session| session.expires_at > now);
+            }
+        });
+    }
+}
@@ -0,0 +1,44 @@
+use std::time::{Duration, Instant};
+
+// Full-book snapshots can be asked for at most this often
+pub const MIN_SNAPSHOT_INTERVAL_MS: u64 = 1000;
+
+pub fn validate_snapshot_interval(interval_ms: u64) -> Result<Duration, String> {
+    if interval_ms < MIN_SNAPSHOT_INTERVAL_MS {
+        return Err(format!(
+            "snapshot_interval_ms must be at least {}, got {}",
+            MIN_SNAPSHOT_INTERVAL_MS, interval_ms
+        ));
+    }
+    Ok(Duration::from_millis(interval_ms))
+}
+
+// When one subscription's next full-book snapshot is due. The first one follows a whole
+// interval after subscribing, as the initial update already carried the book.
+#[derive(Debug, Clone)]
+pub struct SnapshotSchedule {
+    pub symbol: String,
+    pub by_order: bool, // MBO rather than MBP levels
+    interval: Duration,
+    next_due: Instant,
+}
+
+impl SnapshotSchedule {
+    pub fn new(symbol: &str, by_order: bool, interval: Duration) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            by_order,
+            interval,
+            next_due: Instant::now() + interval,
+        }
+    }
+
+    // True once the snapshot is due, scheduling the next one an interval from now
+    pub fn poll(&mut self, now: Instant) -> bool {
+        if now < self.next_due {
+            return false;
+        }
+        self.next_due = now + self.interval;
+        true
+    }
+}
@@ -1,289 +1,3834 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::mem;
 use std::sync::Arc;
-use std::time::Duration;
-use tokio::sync::{RwLock, mpsc, broadcast};
-use tokio::time::interval;
-use dashmap::DashMap;
+use std::time::{Duration, Instant};
+use bytes::Bytes;
+use tokio::sync::{Mutex, RwLock, broadcast, mpsc};
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use tokio::time::{interval, sleep};
+use dashmap::{DashMap, DashSet};
+use futures_util::FutureExt;
+use futures_util::stream::{self, Stream, StreamExt};
 use uuid::Uuid;
-use chrono::Utc;
-use tracing::{info, debug};
+use chrono::{DateTime, Utc};
+use tracing::{info, debug, error, warn};
 
-use crate::order_book::OrderBook;
+use crate::activity_stream::{activity_matches, validate_activity_filter, ActivityOrder, ActivityOrders};
+#[cfg(feature = "alloc-audit")]
+use crate::alloc_audit::AllocationScope;
+use crate::candle_store::{parse_period, run_candle_compaction, CandleGapFill, CandleStore, MAX_CANDLES_PER_QUERY};
+use crate::candles::CandleAggregator;
+use crate::clock::{clock, ClockSource};
+use crate::compression::{Compressed, Switch};
+use crate::conformance::book_seed;
+use crate::dead_letters::{DeadLetterBatch, DeadLetterLog, DeadLetterReason};
+use crate::epochs::BookEpochs;
+use crate::event_bus::{next_event, next_event_or_missed, EventBus, MarketEvent};
+use crate::flow::AggressorFlow;
+use crate::futures::{CurveEvent, FuturesCurve};
+use crate::history::{validate_replay_speed, BookHistory, Replay};
+use crate::integrity::{run_integrity_checks, IntegrityChecks};
+use crate::indicators::{compute_indicators, validate_indicator_spec};
+use crate::ingest::{apply_events, IngestError, IngestEvent, IngestReport};
+use crate::ladder::{ladder_update, validate_ladder};
+use crate::level_deltas::{LevelBase, LevelView};
+use crate::liquidity_age::liquidity_age_update;
+use crate::limits::ResourceLimits;
+use crate::load_shedding::{LoadShedder, LOAD_WINDOW};
+use crate::metrics::Metrics;
+use crate::option_chain::OptionChain;
+use crate::paper_trading::{PaperDesk, PaperOrderRequest, PaperTradingModel};
+use crate::order_book::{default_max_orders, OrderBook, PublishedBook, SIMULATED_TICK_SIZE};
+use crate::order_stats::OrderLifetimes;
+use crate::outbound_queue::OutboundSender;
+use crate::pairs::SyntheticPair;
+use crate::perpetual::{Perpetual, PerpetualOptions};
+use crate::popularity::{SymbolPopularity, SymbolPopularityStats, POPULARITY_SAMPLE_INTERVAL};
+use crate::price_bands::PriceBand;
+use crate::quota::{QuotaDecision, QuotaTracker};
+use crate::reconcile::{reconcile, ReconcileReport, ReconcileRequest};
+use crate::reference::ReferenceRegistry;
+use crate::sampling::{validate_sample_every_n, UpdateSampler};
+use crate::sandbox::{is_sandbox, SandboxCommand, SandboxError, SandboxInfo, SandboxRequest, SANDBOX_PREFIX};
 use crate::message::{
-    ServerMessage, MarketDataUpdate, Subscription, DataType, OrderActivity,
+    ServerMessage, MarketDataUpdate, DisconnectReason, Subscription, DataType, MarketSummary,
+    IndicatorSpec, OutboundMessage, BookAnchor, QuotaRemaining, QuotaStatus, MarkPrice, FundingRate, OptionQuote,
+    FutureContract, OrderActivity, TradingStatus, SymbolInfo, SymbolStatus, SymbolType, Quantity, StreamPriority, StreamStats,
+    MBOLevel, MBPLevel, StreamSpec, StreamValidation, BookSnapshot, BulkSnapshot, OrderLifetimeStats, TopOfBook,
+    SequenceMapping, Candle, PaperOrder, OutOfBandEvent, BandAction, ReferenceChanges, ReferenceSnapshot, Trade, OrderChange,
 };
+use crate::snapshot_cache::SnapshotCache;
+use crate::snapshot_schedule::{validate_snapshot_interval, SnapshotSchedule};
+use crate::stream_stats::StreamStatsTracker;
+use crate::stream_validation::{validate_levels, validate_symbol};
+use crate::subscriptions::{StreamKey, SubscriptionIndex};
+use crate::summary::{build_market_summary, SUMMARY_TOP_N};
+use crate::supervisor::{panic_message, Supervisor, TaskHealth};
+use crate::symbol_config::{FeedSource, SymbolConfig};
+use crate::symbols::{split_currency_pair, SymbolQuery};
+use crate::usage::{UsageSnapshot, UsageTracker};
+use crate::venue_sequences::VenueSequences;
 
-pub type ClientSender = mpsc::UnboundedSender<ServerMessage>;
+pub type ClientSender = OutboundSender<OutboundMessage>;
+
+// Symbols simulated from startup unless the simulation is disabled
+pub const DEFAULT_SYMBOLS: [&str; 3] = ["BTCUSD", "ETHUSD", "ADAUSD"];
+
+// Symbols one GET /depth or SnapshotManyRequest may ask for unless configured otherwise
+pub const DEFAULT_MAX_DEPTH_SYMBOLS: usize = 50;
+pub const DEFAULT_MAX_LEVELS: u32 = 500;
+
+// Every simulated book changes once per interval
+pub const SIMULATION_INTERVAL: Duration = Duration::from_millis(300);
+// Shortest slice a spread tick is cut into, so pacing does not cost more than it smooths
+const MIN_TICK_SLICE: Duration = Duration::from_millis(5);
+// Subscribers a timed fan-out sends to between looks at the clock
+const TIMEOUT_CHECK_INTERVAL: usize = 64;
+// How often the symbol registry is compared with the symbols listed
+const REFERENCE_REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+// What GET /depth/SYMBOL answers a poller
+#[derive(Debug)]
+pub enum DepthPoll {
+    Unchanged { epoch: u64, sequence: u64 },
+    Changed(Box<BookSnapshot>),
+}
+
+#[derive(Debug, Clone)]
+struct Sandbox {
+    owner: String, // API key that created it
+    seed: u64,
+    created_at: DateTime<Utc>,
+}
 
 #[derive(Debug)]
 pub struct StreamManager {
-    order_books: Arc<DashMap<String, Arc<RwLock<OrderBook>>>>,
-    subscriptions: Arc<DashMap<String, Vec<Subscription>>>,
+    order_books: Arc<DashMap<String, Arc<PublishedBook>>>,
+    subscriptions: Arc<SubscriptionIndex<Option<SharedPayload>, Subscription>>,
+    snapshots: Arc<SnapshotCache<SharedPayload, Bytes>>,
+    snapshot_schedules: Arc<DashMap<StreamKey, SnapshotSchedule>>, // Streams asking for periodic full books
     clients: Arc<DashMap<Uuid, ClientSender>>,
-    activity_broadcast: broadcast::Sender<(String, OrderActivity)>,
+    events: EventBus,
+    summary_subscriptions: Arc<DashMap<Uuid, Vec<Subscription>>>,
+    reference_subscriptions: Arc<DashMap<Uuid, Vec<Subscription>>>,
+    reference: Arc<ReferenceRegistry>,
+    heartbeat_quotes: Arc<DashMap<Uuid, Vec<String>>>, // Books whose top each client's heartbeats carry
+    latest_summary: Arc<RwLock<Option<MarketSummary>>>,
+    pairs: Arc<DashMap<String, SyntheticPair>>,
+    perpetuals: Arc<DashMap<String, Perpetual>>,
+    option_chains: Arc<DashMap<String, OptionChain>>, // By underlying
+    option_contracts: Arc<DashMap<String, String>>, // Contract to its underlying
+    futures: Arc<DashMap<String, FuturesCurve>>, // By root
+    halted_symbols: Arc<DashSet<String>>, // Expired futures awaiting delisting, and halted sandboxes
+    candles: Arc<DashMap<String, CandleAggregator>>,
+    flows: Arc<DashMap<String, AggressorFlow>>,
+    order_stats: Arc<DashMap<String, OrderLifetimes>>,
+    usage: Arc<UsageTracker>,
+    popularity: Arc<SymbolPopularity>,
+    stream_stats: Arc<StreamStatsTracker>,
+    quotas: Option<Arc<QuotaTracker>>,
+    load_shedder: Option<Arc<LoadShedder>>,
+    metrics: Arc<Metrics>,
+    supervisor: Supervisor,
+    usage_report_interval: Option<Duration>,
+    simulation_runtime: Option<Handle>,
+    tick_spread: Option<Duration>, // Window of each tick its book updates are paced over
+    fanout_timeout: Option<Duration>,
+    limits: ResourceLimits,
+    pinned_symbols: Arc<DashSet<String>>,
+    sandboxes: Arc<DashMap<String, Sandbox>>,
+    sandboxes_per_key: Option<usize>,
+    client_keys: Arc<DashMap<Uuid, String>>, // API key each authenticated client is bound to
+    // Books fed through the ingest API rather than simulated
+    external_symbols: Arc<DashSet<String>>,
+    venue_sequences: Arc<DashMap<String, VenueSequences>>,
+    simulate: bool,
+    // Seed of every simulated book and the ticks after which the simulation stops
+    conformance: Option<(u64, u64)>,
+    default_symbols: Vec<String>,
+    symbol_config: HashMap<String, SymbolConfig>, // Default symbols listed by --symbol-config
+    strict_subscriptions: bool,
+    venue: String,
+    quantity_decimals: HashMap<String, u32>,
+    price_band: Option<PriceBand>, // Of books without one in the symbol config
+    symbol_registry: Arc<Mutex<()>>, // Held while books are created for or evicted from subscriptions
+    history: Option<Arc<BookHistory>>,
+    replays: Arc<DashSet<StreamKey>>, // Replays being sent
+    candle_store: Option<(Arc<CandleStore>, Duration)>, // With its compaction interval
+    integrity_checks: Option<IntegrityChecks>,
+    paper_desk: Option<Arc<PaperDesk>>,
+    dead_letters: Option<Arc<DeadLetterLog>>,
+    anchor_interval: Option<u64>,
+    actor_tags: bool,
+    epochs: Arc<BookEpochs>,
+    latency_tags: bool,
+    max_depth_symbols: usize,
+    max_levels: u32, // Deepest view a stream is served; deeper subscriptions are clamped
+}
+
+impl Default for StreamManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl StreamManager {
     pub fn new() -> Self {
-        let (activity_broadcast, _) = broadcast::channel(1000);
-
+        let metrics = Arc::new(Metrics::new());
         Self {
             order_books: Arc::new(DashMap::new()),
-            subscriptions: Arc::new(DashMap::new()),
+            subscriptions: Arc::new(SubscriptionIndex::new()),
+            snapshots: Arc::new(SnapshotCache::new()),
+            snapshot_schedules: Arc::new(DashMap::new()),
             clients: Arc::new(DashMap::new()),
-            activity_broadcast,
+            events: EventBus::new(),
+            summary_subscriptions: Arc::new(DashMap::new()),
+            reference_subscriptions: Arc::new(DashMap::new()),
+            reference: Arc::new(ReferenceRegistry::default()),
+            heartbeat_quotes: Arc::new(DashMap::new()),
+            latest_summary: Arc::new(RwLock::new(None)),
+            pairs: Arc::new(DashMap::new()),
+            perpetuals: Arc::new(DashMap::new()),
+            option_chains: Arc::new(DashMap::new()),
+            option_contracts: Arc::new(DashMap::new()),
+            futures: Arc::new(DashMap::new()),
+            halted_symbols: Arc::new(DashSet::new()),
+            candles: Arc::new(DashMap::new()),
+            flows: Arc::new(DashMap::new()),
+            order_stats: Arc::new(DashMap::new()),
+            usage: Arc::new(UsageTracker::new()),
+            popularity: Arc::new(SymbolPopularity::new()),
+            stream_stats: Arc::new(StreamStatsTracker::new()),
+            quotas: None,
+            load_shedder: None,
+            supervisor: Supervisor::new(Arc::clone(&metrics)),
+            metrics,
+            usage_report_interval: None,
+            simulation_runtime: None,
+            tick_spread: None,
+            fanout_timeout: None,
+            limits: ResourceLimits::default(),
+            pinned_symbols: Arc::new(DashSet::new()),
+            sandboxes: Arc::new(DashMap::new()),
+            sandboxes_per_key: None,
+            client_keys: Arc::new(DashMap::new()),
+            external_symbols: Arc::new(DashSet::new()),
+            venue_sequences: Arc::new(DashMap::new()),
+            simulate: true,
+            conformance: None,
+            default_symbols: DEFAULT_SYMBOLS.iter().map(|symbol| symbol.to_string()).collect(),
+            symbol_config: HashMap::new(),
+            strict_subscriptions: false,
+            venue: "SIM".to_string(),
+            quantity_decimals: HashMap::new(),
+            price_band: None,
+            symbol_registry: Arc::new(Mutex::new(())),
+            history: None,
+            candle_store: None,
+            integrity_checks: None,
+            paper_desk: None,
+            dead_letters: None,
+            anchor_interval: None,
+            actor_tags: false,
+            epochs: Arc::new(BookEpochs::default()),
+            latency_tags: false,
+            max_depth_symbols: DEFAULT_MAX_DEPTH_SYMBOLS,
+            max_levels: DEFAULT_MAX_LEVELS,
+            replays: Arc::new(DashSet::new()),
         }
     }
 
-    pub async fn start(&self) {
-        info!("Starting stream manager");
+    pub fn enable_usage_reports(&mut self, interval: Duration) {
+        self.usage_report_interval = Some(interval);
+    }
 
-        // Initialize default symbols
-        self.initialize_symbol("BTCUSD").await;
-        self.initialize_symbol("ETHUSD").await;
-        self.initialize_symbol("ADAUSD").await;
+    // Runs the market simulation on the given runtime instead of the caller's
+    pub fn set_simulation_runtime(&mut self, handle: Handle) {
+        self.simulation_runtime = Some(handle);
+    }
 
-        // Start market simulation
-        self.start_market_simulation().await;
+    // Paces each tick's book updates over the window in slices, so their fan-out does not
+    // arrive as one burst
+    pub fn set_tick_spread(&mut self, window: Duration) {
+        self.tick_spread = Some(window);
+    }
 
-        // Start heartbeat
-        self.start_heartbeat().await;
+    // Caps the time one update of a symbol is fanned out for; subscribers it did not reach
+    // are sent the symbol's next update first
+    pub fn set_fanout_timeout(&mut self, timeout: Duration) {
+        self.fanout_timeout = Some(timeout);
     }
 
-    async fn initialize_symbol(&self, symbol: &str) {
-        let mut order_book = OrderBook::new(symbol.to_string());
-        order_book.initialize_with_sample_data();
+    // No default symbols, and books created for subscriptions start empty and wait
+    // for events from the ingest API
+    pub fn disable_simulation(&mut self) {
+        self.simulate = false;
+    }
 
-        self.order_books.insert(
-            symbol.to_string(),
-            Arc::new(RwLock::new(order_book))
-        );
+    // Symbols simulated from startup, in place of DEFAULT_SYMBOLS
+    pub fn enable_conformance(&mut self, seed: u64, ticks: u64) {
+        self.conformance = Some((seed, ticks));
+        info!("Conformance mode: seed {}, stopping after {} ticks", seed, ticks);
+    }
 
-        info!("Initialized order book for symbol: {}", symbol);
+    pub fn set_default_symbols(&mut self, symbols: Vec<String>) {
+        self.default_symbols = symbols;
     }
 
-    async fn start_market_simulation(&self) {
-        let order_books = Arc::clone(&self.order_books);
-        let subscriptions = Arc::clone(&self.subscriptions);
-        let clients = Arc::clone(&self.clients);
-        let activity_broadcast = self.activity_broadcast.clone();
+    // Default symbols with their feed source, starting price, quantity decimals and the
+    // data types they may be subscribed with
+    pub fn set_symbol_config(&mut self, symbols: Vec<SymbolConfig>) {
+        self.default_symbols = symbols.iter().map(|config| config.symbol.clone()).collect();
+        for config in symbols {
+            if let Some(decimals) = config.quantity_decimals {
+                self.quantity_decimals.insert(config.symbol.clone(), decimals);
+            }
+            self.symbol_config.insert(config.symbol.clone(), config);
+        }
+    }
 
-        tokio::spawn(async move {
-            let mut interval = interval(Duration::from_millis(300));
+    // Subscriptions with a malformed symbol or out-of-bounds levels are refused rather
+    // than served as given
+    pub fn enable_strict_subscriptions(&mut self) {
+        self.strict_subscriptions = true;
+    }
 
-            loop {
-                interval.tick().await;
+    // Why strict subscriptions refuse a Subscribe, if they are enabled and do
+    pub fn validate_strict(&self, symbol: &str, data_type: &DataType, max_levels: Option<u32>) -> Result<(), String> {
+        if !self.strict_subscriptions {
+            return Ok(());
+        }
+        // Summary and Reference streams are not of one symbol
+        if !matches!(data_type, DataType::Summary | DataType::Reference) {
+            validate_symbol(symbol)?;
+        }
+        if let Some(max_levels) = max_levels {
+            validate_levels(max_levels)?;
+        }
+        Ok(())
+    }
 
-                for entry in order_books.iter() {
-                    let symbol = entry.key().clone();
-                    let order_book_ref = entry.value().clone();
+    // Anchors MBP and MBO updates with the book checksum each time a book's sequence
+    // passes a multiple of `interval`
+    pub fn set_anchor_interval(&mut self, interval: u64) {
+        self.anchor_interval = Some(interval.max(1));
+    }
 
-                    // Simulate market activity
-                    let activities = {
-                        let mut order_book = order_book_ref.write().await;
-                        order_book.simulate_activity()
-                    };
+    // Attributes the orders of simulated books to an actor class on MBO streams, as labeled
+    // training data for research
+    pub fn enable_actor_tags(&mut self) {
+        self.actor_tags = true;
+    }
 
-                    // Broadcast activities for real-time updates
-                    for activity in &activities {
-                        let _ = activity_broadcast.send((symbol.clone(), activity.clone()));
-                    }
-
-                    // Send updates to subscribed clients
-                    if let Some(symbol_subscriptions) = subscriptions.get(&symbol) {
-                        for subscription in symbol_subscriptions.iter() {
-                            if let Some(client_sender) = clients.get(&subscription.client_id) {
-                                let market_data = {
-                                    let order_book = order_book_ref.read().await;
-                                    match subscription.data_type {
-                                        DataType::MBO => {
-                                            let (bids, asks) = order_book.get_mbo_data(subscription.max_levels);
-                                            MarketDataUpdate::MBO { bids, asks }
-                                        }
-                                        DataType::MBP => {
-                                            let (bids, asks) = order_book.get_mbp_data(subscription.max_levels);
-                                            MarketDataUpdate::MBP { bids, asks }
-                                        }
-                                    }
-                                };
+    // Adds latency_us to market data, the server's own delay for each message
+    pub fn enable_latency_tags(&mut self) {
+        self.latency_tags = true;
+    }
 
-                                let message = ServerMessage::MarketData {
-                                    stream_id: subscription.stream_id.clone(),
-                                    symbol: symbol.clone(),
-                                    data: market_data,
-                                    sequence: {
-                                        let order_book = order_book_ref.read().await;
-                                        order_book.get_sequence()
-                                    },
-                                    timestamp: Utc::now(),
-                                };
+    pub fn latency_tags(&self) -> bool {
+        self.latency_tags
+    }
 
-                                if let Err(_) = client_sender.send(message) {
-                                    debug!("Client {} disconnected during market data send", subscription.client_id);
-                                }
-                            }
-                        }
-                    }
-                }
-            }
-        });
+    // Epochs handed to new books; tenants share one so each symbol's epochs keep increasing
+    // Most symbols a bulk snapshot may ask for
+    pub fn set_max_depth_symbols(&mut self, max_symbols: usize) {
+        self.max_depth_symbols = max_symbols;
     }
 
-    async fn start_heartbeat(&self) {
-        let clients = Arc::clone(&self.clients);
+    pub fn set_max_levels(&mut self, max_levels: u32) {
+        self.max_levels = max_levels.max(1);
+    }
 
-        tokio::spawn(async move {
-            let mut interval = interval(Duration::from_secs(30));
+    // The depth a stream asking for `max_levels` is served, the default of 20 included
+    pub fn granted_levels(&self, max_levels: Option<u32>) -> u32 {
+        max_levels.unwrap_or(20).min(self.max_levels)
+    }
 
-            loop {
-                interval.tick().await;
+    pub fn set_epochs(&mut self, epochs: Arc<BookEpochs>) {
+        self.epochs = epochs;
+    }
 
-                let heartbeat = ServerMessage::HeartBeat {
-                    timestamp: Utc::now(),
-                };
+    // Venue reported for every symbol by symbol discovery
+    pub fn set_venue(&mut self, venue: &str) {
+        self.venue = venue.to_string();
+    }
 
-                for client in clients.iter() {
-                    if let Err(_) = client.send(heartbeat.clone()) {
-                        debug!("Client {} disconnected during heartbeat", client.key());
-                    }
-                }
-            }
-        });
+    // Decimals the symbol's order sizes are quoted to; 0 (whole units) unless set
+    pub fn set_quantity_decimals(&mut self, symbol: &str, decimals: u32) {
+        self.quantity_decimals.insert(symbol.to_string(), decimals);
     }
 
-    pub fn register_client(&self, client_id: Uuid, sender: ClientSender) {
-        self.clients.insert(client_id, sender);
-        info!("Registered client: {}", client_id);
+    // Band of every spot book, simulated or ingested, not given one by the symbol config
+    pub fn set_price_band(&mut self, band: PriceBand) {
+        self.price_band = Some(band);
     }
 
-    pub fn unregister_client(&self, client_id: &Uuid) {
-        self.clients.remove(client_id);
+    pub fn enable_sandboxes(&mut self, per_key: usize) {
+        self.sandboxes_per_key = Some(per_key);
+        info!("Sandboxes enabled: {} per API key", per_key);
+    }
 
-        // Remove all subscriptions for this client
-        for mut entry in self.subscriptions.iter_mut() {
-            entry.value_mut().retain(|sub| sub.client_id != *client_id);
-        }
+    pub fn set_resource_limits(&mut self, limits: ResourceLimits) {
+        self.limits = limits;
+    }
 
-        // Clean up empty subscription lists
-        self.subscriptions.retain(|_, v| !v.is_empty());
+    // Meters delivery to clients bound to an API key against the tracker's quotas
+    pub fn set_quotas(&mut self, quotas: QuotaTracker) {
+        self.quotas = Some(Arc::new(quotas));
+    }
 
-        info!("Unregistered client: {}", client_id);
+    // Conflates low-priority streams while the shedder's load thresholds are exceeded
+    pub fn set_load_shedding(&mut self, load_shedder: LoadShedder) {
+        self.load_shedder = Some(Arc::new(load_shedder));
     }
 
-    pub async fn subscribe(
-        &self,
-        client_id: Uuid,
-        stream_id: String,
-        symbol: String,
-        data_type: DataType,
-        max_levels: Option<u32>,
-    ) -> Result<(), String> {
-        // Ensure the symbol exists
-        if !self.order_books.contains_key(&symbol) {
-            self.initialize_symbol(&symbol).await;
+    // Records the MBP history of every book for ReplayRequest
+    pub fn enable_history(&mut self, history: BookHistory) {
+        self.history = Some(Arc::new(history));
+    }
+
+    // Keeps candles of every book for GET /candles, compacted every `compaction_interval`
+    pub fn enable_candle_store(&mut self, store: CandleStore, compaction_interval: Duration) {
+        self.candle_store = Some((Arc::new(store), compaction_interval));
+    }
+
+    pub fn enable_integrity_checks(&mut self, checks: IntegrityChecks) {
+        self.integrity_checks = Some(checks);
+    }
+
+    // Accepts paper orders, delayed and charged by the model
+    pub fn enable_paper_trading(&mut self, model: Arc<dyn PaperTradingModel>) {
+        self.paper_desk = Some(Arc::new(PaperDesk::new(model)));
+    }
+
+    // Keeps the messages of failed and server-closed connections for GET /admin/dead-letters
+    pub fn enable_dead_letters(&mut self, dead_letters: DeadLetterLog) {
+        self.dead_letters = Some(Arc::new(dead_letters));
+    }
+
+    pub fn add_synthetic_pair(&self, pair: SyntheticPair) {
+        info!("Registered synthetic pair: {} ({:?} of {} and {})",
+            pair.name, pair.kind, pair.base, pair.quote
+        );
+        self.pairs.insert(pair.name.clone(), pair);
+    }
+
+    pub fn add_perpetual(&self, symbol: &str, options: &PerpetualOptions) {
+        info!("Registered perpetual: {} (funding every {}s)", symbol, options.funding_interval_secs);
+        self.perpetuals.insert(symbol.to_string(), Perpetual::new(options, Utc::now()));
+    }
+
+    // Chains on an underlying that already has one are merged into it
+    pub fn add_option_chain(&self, chain: OptionChain) {
+        info!("Registered options chain on {}: {} contracts", chain.underlying, chain.contracts().len());
+        for contract in chain.contracts() {
+            self.option_contracts.insert(contract.name.clone(), chain.underlying.clone());
         }
 
-        let subscription = Subscription::new(
-            stream_id.clone(),
-            symbol.clone(),
-            data_type.clone(),
-            max_levels,
-            client_id,
+        match self.option_chains.get_mut(&chain.underlying) {
+            Some(mut existing) => existing.merge(chain),
+            None => {
+                self.option_chains.insert(chain.underlying.clone(), chain);
+            }
+        }
+    }
+
+    // Lists the curve's contracts and the calendar spreads between them
+    pub fn add_futures(&self, curve: FuturesCurve) {
+        info!("Registered futures on {}: {}", curve.root,
+            curve.contracts().iter().map(|contract| contract.symbol.as_str()).collect::<Vec<_>>().join(", ")
         );
+        for spread in curve.calendar_spreads() {
+            self.add_synthetic_pair(spread);
+        }
+        self.futures.insert(curve.root.clone(), curve);
+    }
 
-        // Add subscription
-        self.subscriptions
-            .entry(symbol.clone())
-            .or_insert_with(Vec::new)
-            .push(subscription);
+    pub async fn start(self: &Arc<Self>) {
+        info!("Starting stream manager");
 
-        // Send initial snapshot
-        if let Some(order_book_ref) = self.order_books.get(&symbol) {
-            if let Some(client_sender) = self.clients.get(&client_id) {
-                let market_data = {
-                    let order_book = order_book_ref.read().await;
-                    match data_type {
-                        DataType::MBO => {
-                            let (bids, asks) = order_book.get_mbo_data(max_levels.unwrap_or(20));
-                            MarketDataUpdate::MBO { bids, asks }
-                        }
-                        DataType::MBP => {
-                            let (bids, asks) = order_book.get_mbp_data(max_levels.unwrap_or(20));
-                            MarketDataUpdate::MBP { bids, asks }
-                        }
-                    }
-                };
+        // Initialize default symbols; configured ingest books are created empty either way
+        for symbol in &self.default_symbols {
+            match self.symbol_config.get(symbol).map(|config| config.source) {
+                Some(FeedSource::Ingest) => self.create_book(symbol, false),
+                _ if self.simulate => self.initialize_symbol(symbol).await,
+                _ => {}
+            }
+        }
 
-                let initial_message = ServerMessage::MarketData {
-                    stream_id: stream_id.clone(),
-                    symbol: symbol.clone(),
-                    data: market_data,
-                    sequence: {
-                        let order_book = order_book_ref.read().await;
-                        order_book.get_sequence()
-                    },
-                    timestamp: Utc::now(),
-                };
+        // Initialize dated futures at their carry-adjusted prices
+        let now = Utc::now();
+        for curve in self.futures.iter() {
+            for contract in curve.contracts() {
+                list_future(&self.order_books, &self.limits, &self.quantity_decimals, self.actor_tags, &self.epochs, contract, curve.opening_price(contract, now));
+            }
+        }
 
-                if let Err(_) = client_sender.send(initial_message) {
-                    return Err("Failed to send initial snapshot".to_string());
-                }
+        // Initialize legs of synthetic pairs, perpetuals and option underlyings
+        let legs: Vec<String> = self.pairs
+            .iter()
+            .flat_map(|pair| [pair.base.clone(), pair.quote.clone()])
+            .chain(self.perpetuals.iter().map(|perpetual| perpetual.key().clone()))
+            .chain(self.option_chains.iter().map(|chain| chain.key().clone()))
+            .collect();
+        for leg in legs {
+            if !self.order_books.contains_key(&leg) {
+                self.initialize_symbol(&leg).await;
             }
         }
 
-        info!("Client {} subscribed to {} stream {} ({})",
-            client_id, symbol, stream_id,
-            match data_type { DataType::MBO => "MBO", DataType::MBP => "MBP" }
-        );
+        // Default symbols, pair legs, perpetuals, option underlyings and futures are never evicted
+        for entry in self.order_books.iter() {
+            self.pinned_symbols.insert(entry.key().clone());
+        }
 
-        Ok(())
-    }
+        // Start the event bus consumers, then the market simulation publishing to it
+        self.supervise("distribution", true, |manager| manager.distribution_task());
+        self.supervise("analytics", true, |manager| manager.analytics_task());
+        if let Some(history) = &self.history {
+            let history = Arc::clone(history);
+            self.supervise("history", true, move |manager| manager.history_task(Arc::clone(&history)));
+        }
+        if let Some((store, compaction_interval)) = &self.candle_store {
+            let (store, compaction_interval) = (Arc::clone(store), *compaction_interval);
+            self.supervise("candle_compaction", false, move |_| run_candle_compaction(Arc::clone(&store), compaction_interval));
+        }
+        if let Some(checks) = self.integrity_checks {
+            self.supervise("integrity_checks", false, move |manager| {
+                run_integrity_checks(Arc::clone(&manager.order_books), manager.events.clone(), checks)
+            });
+        }
+        if let Some(paper_desk) = &self.paper_desk {
+            let paper_desk = Arc::clone(paper_desk);
+            self.supervise("paper_trading", false, move |manager| manager.paper_trading_task(Arc::clone(&paper_desk)));
+        }
+        self.supervise("market_simulation", true, |manager| manager.market_simulation_task());
 
-    pub fn unsubscribe(&self, client_id: Uuid, stream_id: &str) -> bool {
-        for mut entry in self.subscriptions.iter_mut() {
-            let initial_len = entry.value().len();
-            entry.value_mut().retain(|sub|
-                !(sub.client_id == client_id && sub.stream_id == stream_id)
-            );
+        // Start market summary publication
+        self.supervise("market_summary", false, |manager| manager.market_summary_task());
+
+        // Start reference data publication
+        self.supervise("reference_data", false, |manager| manager.reference_data_task());
+
+        // Start heartbeat
+        self.supervise("heartbeat", false, |manager| manager.heartbeat_task());
+
+        // Start sampling symbol popularity
+        self.supervise("popularity_sampling", false, |manager| manager.popularity_sampling_task());
+
+        // Start periodic usage reports
+        if let Some(report_interval) = self.usage_report_interval {
+            self.supervise("usage_reports", false, move |manager| manager.usage_reports_task(report_interval));
+        }
+
+        // Start futures expiry
+        if !self.futures.is_empty() {
+            self.supervise("futures_expiry", false, |manager| manager.futures_expiry_task());
+        }
+
+        // Start idle symbol eviction
+        if let Some(ttl) = self.limits.idle_symbol_ttl {
+            self.supervise("symbol_eviction", false, move |manager| manager.symbol_eviction_task(ttl));
+        }
+
+        // Start load shedding
+        if let Some(load_shedder) = &self.load_shedder {
+            let load_shedder = Arc::clone(load_shedder);
+            self.supervise("load_shedding", false, move |manager| manager.load_shedding_task(Arc::clone(&load_shedder)));
+        }
+    }
 
-            if entry.value().len() != initial_len {
-                info!("Client {} unsubscribed from stream {}", client_id, stream_id);
-                return true;
+    fn check_symbol_limit(&self) -> Result<(), String> {
+        match self.limits.max_symbols {
+            Some(max_symbols) if self.order_books.len() >= max_symbols => {
+                Err(format!("Symbol limit of {} reached", max_symbols))
             }
+            _ => Ok(()),
         }
+    }
 
-        false
+    async fn initialize_symbol(&self, symbol: &str) {
+        self.create_book(symbol, self.simulate);
     }
 
-    pub fn get_activity_receiver(&self) -> broadcast::Receiver<(String, OrderActivity)> {
-        self.activity_broadcast.subscribe()
+    // Simulated books start with sample orders; external ones start empty
+    fn create_book(&self, symbol: &str, simulated: bool) {
+        let mut order_book = OrderBook::new(symbol.to_string());
+        if let Some(limit) = self.limits.max_orders_per_book {
+            order_book.set_order_limit(limit);
+        }
+        order_book.set_quantity_decimals(self.quantity_decimals.get(symbol).copied().unwrap_or(0));
+        order_book.set_price_band(self.symbol_config.get(symbol).and_then(|config| config.price_band).or(self.price_band));
+        order_book.set_epoch(self.epochs.next(symbol));
+        if simulated {
+            order_book.set_actor_tags(self.actor_tags);
+            if let Some((seed, _)) = self.conformance {
+                order_book.set_seed(book_seed(seed, symbol));
+            }
+            match self.symbol_config.get(symbol).and_then(|config| config.price) {
+                Some(price) => order_book.initialize_with_sample_data_around(price),
+                None => order_book.initialize_with_sample_data(),
+            }
+        } else {
+            self.external_symbols.insert(symbol.to_string());
+        }
+
+        self.order_books.insert(
+            symbol.to_string(),
+            Arc::new(PublishedBook::new(order_book))
+        );
+
+        info!("Initialized {} order book for symbol: {}", if simulated { "simulated" } else { "external" }, symbol);
     }
 
-    pub async fn get_symbols(&self) -> Vec<String> {
-        self.order_books.iter().map(|entry| entry.key().clone()).collect()
+    // Runs a background task under the supervisor, which builds it anew from the manager
+    // after a panic. The first run is built here, so consumers subscribe before the
+    // simulator starts and see its first tick.
+    fn supervise<F, Fut>(self: &Arc<Self>, name: &'static str, simulation: bool, task: F)
+    where
+        F: Fn(&Arc<StreamManager>) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let manager = Arc::downgrade(self);
+        let runtime = if simulation { self.simulation_runtime.clone() } else { None };
+        self.supervisor.spawn(name, runtime, move || manager.upgrade().map(|manager| task(&manager)));
     }
 
-    pub async fn get_order_book_snapshot(&self, symbol: &str, data_type: DataType, max_levels: u32) -> Option<MarketDataUpdate> {
-        if let Some(order_book_ref) = self.order_books.get(symbol) {
-            let order_book = order_book_ref.read().await;
+    // Mutates the books and publishes what changed; distribution and analytics run as
+    // consumers of the event bus
+    fn market_simulation_task(&self) -> impl Future<Output = ()> + Send + 'static {
+        let order_books = Arc::clone(&self.order_books);
+        let events = self.events.clone();
+        let metrics = Arc::clone(&self.metrics);
+        let external_symbols = Arc::clone(&self.external_symbols);
+        let halted_symbols = Arc::clone(&self.halted_symbols);
+        let popularity = Arc::clone(&self.popularity);
+        let tick_spread = self.tick_spread;
+        let mut remaining_ticks = self.conformance.map(|(_, ticks)| ticks);
 
-            let market_data = match data_type {
-                DataType::MBO => {
-                    let (bids, asks) = order_book.get_mbo_data(max_levels);
-                    MarketDataUpdate::MBO { bids, asks }
+        async move {
+            let mut interval = interval(SIMULATION_INTERVAL);
+            // Sequence each external book was last published at
+            let mut published: HashMap<String, u64> = HashMap::new();
+
+            loop {
+                interval.tick().await;
+                match remaining_ticks.as_mut() {
+                    Some(0) => {
+                        info!("Conformance stream complete; the books no longer change");
+                        break;
+                    }
+                    Some(remaining) => *remaining -= 1,
+                    None => {}
                 }
-                DataType::MBP => {
-                    let (bids, asks) = order_book.get_mbp_data(max_levels);
-                    MarketDataUpdate::MBP { bids, asks }
+                let tick_started = Instant::now();
+                #[cfg(feature = "alloc-audit")]
+                let tick_allocations = AllocationScope::start();
+                published.retain(|symbol, _| external_symbols.contains(symbol));
+
+                // Most watched books first, so their updates are fanned out first
+                let mut books: Vec<_> = order_books.iter().map(|entry| (entry.key().clone(), entry.value().clone())).collect();
+                popularity.rank(&mut books);
+
+                // With a spread, books are cut into slices of the window in their order, each
+                // started at its deadline; a slice running late starts the next one at once
+                let book_count = books.len();
+                let slices = tick_spread.map_or(1, |window| {
+                    let slices = (window.as_millis() / MIN_TICK_SLICE.as_millis()).max(1) as usize;
+                    slices.min(book_count.max(1))
+                });
+                let mut slice = 0;
+                let mut paced = Duration::ZERO;
+
+                for (index, (name, order_book_ref)) in books.into_iter().enumerate() {
+                    if let Some(window) = tick_spread {
+                        let book_slice = index * slices / book_count;
+                        if book_slice > slice {
+                            slice = book_slice;
+                            let deadline = tick_started + window * slice as u32 / slices as u32;
+                            if let Some(ahead) = deadline.checked_duration_since(Instant::now()) {
+                                sleep(ahead).await;
+                                paced += ahead;
+                            }
+                        }
+                    }
+                    if halted_symbols.contains(&name) {
+                        continue;
+                    }
+                    let symbol: Arc<str> = Arc::from(name.as_str());
+
+                    // External books are republished only after the ingest API changed them,
+                    // and their activities were already published when ingested
+                    let external = external_symbols.contains(&name);
+                    let mut previous_sequence = None;
+                    if external {
+                        let sequence = order_book_ref.snapshot().get_sequence();
+                        previous_sequence = published.insert(name, sequence);
+                        if previous_sequence == Some(sequence) {
+                            continue;
+                        }
+                    }
+
+                    // Simulate market activity
+                    let (activities, trades, out_of_band, mid_price, sequence, event_ts) = order_book_ref.update(|order_book| {
+                        let activities = if external {
+                            Vec::new()
+                        } else {
+                            previous_sequence = Some(order_book.get_sequence());
+                            order_book.simulate_activity()
+                        };
+                        (
+                            activities,
+                            order_book.take_trades(),
+                            order_book.take_out_of_band(),
+                            order_book.get_spread_info().1,
+                            order_book.get_sequence(),
+                            order_book.get_event_timestamp(),
+                        )
+                    });
+
+                    for trade in trades {
+                        events.publish(MarketEvent::Trade(trade));
+                    }
+                    for activity in activities {
+                        events.publish(MarketEvent::Activity(activity));
+                    }
+                    publish_out_of_band(&events, &metrics, out_of_band);
+                    events.publish(MarketEvent::BookUpdated { symbol, sequence, previous_sequence, event_ts, mid_price });
                 }
-            };
 
-            Some(market_data)
-        } else {
-            None
+                events.publish(MarketEvent::TickCompleted { timestamp: Utc::now() });
+                metrics.observe_tick(tick_started.elapsed().saturating_sub(paced));
+                #[cfg(feature = "alloc-audit")]
+                metrics.observe_tick_allocations(tick_allocations.allocations());
+            }
         }
     }
 
-    pub fn get_client_sender(&self, client_id: &Uuid) -> Option<dashmap::mapref::one::Ref<Uuid, ClientSender>> {
-        self.clients.get(client_id)
-    }
-}
\ No newline at end of file
+    // Hands book updates to their symbols' fan-out pipelines, publishes derived instruments
+    // once every book of a tick is updated, and relays status changes of dated futures
+    fn distribution_task(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.events.subscribe();
+        let order_books = Arc::clone(&self.order_books);
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let snapshots = Arc::clone(&self.snapshots);
+        let snapshot_schedules = Arc::clone(&self.snapshot_schedules);
+        let clients = Arc::clone(&self.clients);
+        let pairs = Arc::clone(&self.pairs);
+        let perpetuals = Arc::clone(&self.perpetuals);
+        let option_chains = Arc::clone(&self.option_chains);
+        let metrics = Arc::clone(&self.metrics);
+        let popularity = Arc::clone(&self.popularity);
+        let load_shedder = self.load_shedder.clone();
+        let stream_stats = Arc::clone(&self.stream_stats);
+        let fanout = Arc::new(FanOut {
+            order_books: Arc::clone(&order_books),
+            subscriptions: Arc::clone(&subscriptions),
+            snapshots: Arc::clone(&snapshots),
+            clients: Arc::clone(&clients),
+            candles: Arc::clone(&self.candles),
+            flows: Arc::clone(&self.flows),
+            order_stats: Arc::clone(&self.order_stats),
+            metrics: Arc::clone(&metrics),
+            load_shedder: load_shedder.clone(),
+            stream_stats: Arc::clone(&stream_stats),
+            anchor_interval: self.anchor_interval,
+            timeout: self.fanout_timeout,
+        });
+
+        async move {
+            // Each symbol's updates are fanned out by a task of its own, so a panic or a slow
+            // fan-out only holds up that symbol
+            let mut pipelines: HashMap<Arc<str>, SymbolPipeline> = HashMap::new();
+
+            while let Some(event) = next_event_or_missed(&mut receiver).await {
+                let event = match event {
+                    Ok(event) => event,
+                    // The events missed may include order activity, so every pipeline sends its
+                    // MBODelta streams the whole book with its next update
+                    Err(missed) => {
+                        warn!("Distribution fell behind the event bus and missed {} events", missed);
+                        let symbols: Vec<Arc<str>> = pipelines.keys().cloned().collect();
+                        for symbol in symbols {
+                            dispatch(&mut pipelines, &fanout, symbol, PipelineEvent::Resync);
+                        }
+                        continue;
+                    }
+                };
+                match event {
+                    MarketEvent::BookUpdated { symbol, sequence, previous_sequence, event_ts, mid_price } => {
+                        metrics.mark_updated(&symbol);
+                        dispatch(&mut pipelines, &fanout, symbol, PipelineEvent::Updated { sequence, previous_sequence, event_ts, mid_price });
+                    }
+                    MarketEvent::TickCompleted { timestamp: now } => {
+                        let tick_completed = Instant::now();
+                        // Publish synthetic pairs once all legs have been updated
+                        for pair in pairs.iter() {
+                            let Some(pair_subscriptions) = subscriptions.symbol(pair.key()) else {
+                                continue;
+                            };
+
+                            let Some((market_data, sequence, epoch, event_ts)) = compute_pair_update(&order_books, &pair) else {
+                                continue;
+                            };
+                            let pair_symbol: Arc<str> = Arc::from(pair.name.as_str());
+                            let Some(data) = serialize_payload(&market_data) else {
+                                continue;
+                            };
+
+                            for subscription in pair_subscriptions.values().flat_map(|group| group.values()) {
+                                if conflated(&load_shedder, &stream_stats, subscription, tick_completed) {
+                                    continue;
+                                }
+                                if let Some(client_sender) = clients.get(&subscription.client_id) {
+                                    let message = OutboundMessage::SharedMarketData {
+                                        stream_id: subscription.stream_id.clone(),
+                                        symbol: Arc::clone(&pair_symbol),
+                                        data: data.clone(),
+                                        sequence,
+                                        epoch,
+                                        timestamp: Utc::now(),
+                                        event_ts,
+                                        anchor: None,
+                                        venue_sequence: None,
+                                    };
+
+                                    if client_sender.send(message).is_err() {
+                                        debug!("Client {} disconnected during pair data send", subscription.client_id);
+                                    }
+                                }
+                            }
+                        }
+
+                        // Derive perpetual mark prices from the post-tick books, settling funding when due
+                        for mut perpetual in perpetuals.iter_mut() {
+                            let Some(order_book_ref) = order_books.get(perpetual.key()).map(|entry| entry.value().clone()) else {
+                                continue;
+                            };
+                            let order_book = order_book_ref.snapshot();
+                            let Some(mid_price) = order_book.get_spread_info().1 else {
+                                continue;
+                            };
+
+                            let settlement = perpetual.update(mid_price, now);
+                            let Some(perpetual_subscriptions) = subscriptions.symbol(perpetual.key()) else {
+                                continue;
+                            };
+
+                            let symbol: Arc<str> = Arc::from(perpetual.key().as_str());
+                            let mark = perpetual.mark_price().and_then(|mark| {
+                                serialize_payload(&MarketDataUpdate::MarkPrice { mark: mark.clone() })
+                            });
+                            let funding = settlement.and_then(|funding| serialize_payload(&MarketDataUpdate::Funding { funding }));
+
+                            for subscription in perpetual_subscriptions.values().flat_map(|group| group.values()) {
+                                let data = match subscription.data_type {
+                                    DataType::MarkPrice => &mark,
+                                    DataType::Funding => &funding,
+                                    _ => continue,
+                                };
+                                if conflated(&load_shedder, &stream_stats, subscription, tick_completed) {
+                                    continue;
+                                }
+                                let (Some(data), Some(client_sender)) = (data, clients.get(&subscription.client_id)) else {
+                                    continue;
+                                };
+
+                                let message = OutboundMessage::SharedMarketData {
+                                    stream_id: subscription.stream_id.clone(),
+                                    symbol: Arc::clone(&symbol),
+                                    data: data.clone(),
+                                    sequence: order_book.get_sequence(),
+                                    epoch: order_book.get_epoch(),
+                                    timestamp: now,
+                                    event_ts: order_book.get_event_timestamp(),
+                                    anchor: None,
+                                    venue_sequence: None,
+                                };
+
+                                if client_sender.send(message).is_err() {
+                                    debug!("Client {} disconnected during perpetual data send", subscription.client_id);
+                                }
+                            }
+                        }
+
+                        // Reprice options chains off their underlyings' post-tick mids
+                        for mut chain in option_chains.iter_mut() {
+                            let Some(order_book_ref) = order_books.get(chain.key()).map(|entry| entry.value().clone()) else {
+                                continue;
+                            };
+                            let order_book = order_book_ref.snapshot();
+                            let Some(mid_price) = order_book.get_spread_info().1 else {
+                                continue;
+                            };
+                            chain.update(mid_price, now);
+                            let (sequence, epoch, event_ts) = (order_book.get_sequence(), order_book.get_epoch(), order_book.get_event_timestamp());
+
+                            // Whole-chain streams are subscribed on the underlying, quotes on each contract
+                            let chain_data = subscriptions.symbol(chain.key()).map(|chain_subscriptions| {
+                                let data = serialize_payload(&option_chain_update(&chain));
+                                (Arc::from(chain.key().as_str()), chain_subscriptions, data)
+                            });
+                            let contract_data = chain.quotes().iter().filter_map(|quote| {
+                                let contract_subscriptions = subscriptions.symbol(&quote.contract)?;
+                                let data = serialize_payload(&MarketDataUpdate::OptionQuote { quote: quote.clone() });
+                                Some((Arc::from(quote.contract.as_str()), contract_subscriptions, data))
+                            });
+
+                            for (symbol, symbol_subscriptions, data) in chain_data.into_iter().chain(contract_data) {
+                                let Some(data) = data else {
+                                    continue;
+                                };
+
+                                for subscription in symbol_subscriptions.values().flat_map(|group| group.values()) {
+                                    if !matches!(subscription.data_type, DataType::OptionQuote | DataType::OptionChain) {
+                                        continue;
+                                    }
+                                    if conflated(&load_shedder, &stream_stats, subscription, tick_completed) {
+                                        continue;
+                                    }
+                                    let Some(client_sender) = clients.get(&subscription.client_id) else {
+                                        continue;
+                                    };
+
+                                    let message = OutboundMessage::SharedMarketData {
+                                        stream_id: subscription.stream_id.clone(),
+                                        symbol: Arc::clone(&symbol),
+                                        data: data.clone(),
+                                        sequence,
+                                        epoch,
+                                        timestamp: now,
+                                        event_ts,
+                                        anchor: None,
+                                        venue_sequence: None,
+                                    };
+
+                                    if client_sender.send(message).is_err() {
+                                        debug!("Client {} disconnected during option data send", subscription.client_id);
+                                    }
+                                }
+                            }
+                        }
+
+                        send_due_snapshots(&snapshot_schedules, &order_books, &clients, &stream_stats);
+                    }
+                    MarketEvent::Status { symbol, contract } => {
+                        let delisted = contract.status == TradingStatus::Delisted;
+                        notify_instrument_status(&subscriptions, &clients, &symbol, &contract, delisted);
+                    }
+                    MarketEvent::BookRemoved { symbol } => {
+                        snapshots.remove_symbol(&symbol);
+                        metrics.forget_symbol(&symbol);
+                        popularity.forget(&symbol);
+                        // Its pipeline ends once it has drained what was queued
+                        pipelines.remove(&symbol);
+                    }
+                    MarketEvent::Activity(activity) => {
+                        dispatch(&mut pipelines, &fanout, Arc::clone(&activity.symbol), PipelineEvent::Activity(activity));
+                    }
+                    MarketEvent::Quarantined(event) => {
+                        dispatch(&mut pipelines, &fanout, Arc::clone(&event.symbol), PipelineEvent::Quarantined(event));
+                    }
+                    MarketEvent::Trade(trade) => {
+                        dispatch(&mut pipelines, &fanout, Arc::clone(&trade.symbol), PipelineEvent::Trade(trade));
+                    }
+                    MarketEvent::Conflation { .. } | MarketEvent::IntegrityViolation { .. } => {}
+                }
+            }
+        }
+    }
+
+    // Keeps aggressor flow, order lifetimes and candles current from order activity, trades
+    // and post-update mids. Indicator, flow and order stats streams read these, so they may
+    // trail the book they are sent with by one update.
+    fn analytics_task(&self) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.events.subscribe();
+        let candles = Arc::clone(&self.candles);
+        let flows = Arc::clone(&self.flows);
+        let order_stats = Arc::clone(&self.order_stats);
+        let candle_store = self.candle_store.as_ref().map(|(store, _)| Arc::clone(store));
+
+        async move {
+            // Traded volume of each book since its last update
+            let mut volumes: HashMap<Arc<str>, Quantity> = HashMap::new();
+
+            while let Some(event) = next_event(&mut receiver, "Analytics").await {
+                match event {
+                    MarketEvent::Activity(activity) => {
+                        order_stats.entry(activity.symbol.to_string()).or_default().record_activity(&activity);
+                    }
+                    MarketEvent::Trade(trade) => {
+                        flows.entry(trade.symbol.to_string()).or_default().record(&trade);
+                        order_stats.entry(trade.symbol.to_string()).or_default().record_trade();
+                        *volumes.entry(Arc::clone(&trade.symbol)).or_insert(Quantity::ZERO) += trade.quantity;
+                    }
+                    MarketEvent::BookUpdated { symbol, mid_price, .. } => {
+                        let now = Utc::now();
+                        flows.entry(symbol.to_string()).or_default().expire(now);
+                        order_stats.entry(symbol.to_string()).or_default().refresh();
+
+                        let volume = volumes.remove(&symbol).unwrap_or(Quantity::ZERO);
+                        if let Some(mid_price) = mid_price {
+                            candles.entry(symbol.to_string()).or_default().record(mid_price, volume, now);
+                            // Sandboxes are private to their owner and kept out of the store
+                            if let Some(store) = candle_store.as_ref().filter(|_| !is_sandbox(&symbol)) {
+                                store.record(&symbol, mid_price, volume, now);
+                            }
+                        }
+                    }
+                    MarketEvent::BookRemoved { symbol } => {
+                        candles.remove(symbol.as_ref());
+                        if let Some(store) = &candle_store {
+                            store.remove(&symbol);
+                        }
+                        flows.remove(symbol.as_ref());
+                        order_stats.remove(symbol.as_ref());
+                        volumes.remove(&symbol);
+                    }
+                    MarketEvent::Status { .. }
+                    | MarketEvent::Conflation { .. }
+                    | MarketEvent::TickCompleted { .. }
+                    | MarketEvent::IntegrityViolation { .. }
+                    | MarketEvent::Quarantined(_) => {}
+                }
+            }
+        }
+    }
+
+    // Sandboxes are private to their owner and kept out of the history
+    fn history_task(&self, history: Arc<BookHistory>) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.events.subscribe();
+        let order_books = Arc::clone(&self.order_books);
+
+        async move {
+            while let Some(event) = next_event(&mut receiver, "History").await {
+                match event {
+                    MarketEvent::BookUpdated { symbol, .. } if !is_sandbox(&symbol) => {
+                        if let Some(order_book_ref) = order_books.get(symbol.as_ref()) {
+                            history.record(&symbol, &order_book_ref.snapshot());
+                        }
+                    }
+                    MarketEvent::BookRemoved { symbol } => history.remove(&symbol),
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    fn market_summary_task(&self) -> impl Future<Output = ()> + Send + 'static {
+        let order_books = Arc::clone(&self.order_books);
+        let summary_subscriptions = Arc::clone(&self.summary_subscriptions);
+        let clients = Arc::clone(&self.clients);
+        let latest_summary = Arc::clone(&self.latest_summary);
+
+        async move {
+            let mut interval = interval(Duration::from_secs(5));
+
+            loop {
+                interval.tick().await;
+
+                let summary = compute_market_summary(&order_books);
+                *latest_summary.write().await = Some(summary.clone());
+
+                for entry in summary_subscriptions.iter() {
+                    if let Some(client_sender) = clients.get(entry.key()) {
+                        for subscription in entry.value() {
+                            let message = ServerMessage::MarketSummary {
+                                stream_id: subscription.stream_id.clone(),
+                                summary: summary.clone(),
+                            };
+
+                            if client_sender.send(message.into()).is_err() {
+                                debug!("Client {} disconnected during market summary send", entry.key());
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Compares the registry with the symbols listed, sending what changed to Reference
+    // streams; only a weak reference is held between rounds, as by the supervisor
+    fn reference_data_task(self: &Arc<Self>) -> impl Future<Output = ()> + Send + 'static {
+        let manager = Arc::downgrade(self);
+
+        async move {
+            let mut interval = interval(REFERENCE_REFRESH_INTERVAL);
+
+            loop {
+                interval.tick().await;
+                let Some(manager) = manager.upgrade() else {
+                    return;
+                };
+                manager.refresh_reference();
+            }
+        }
+    }
+
+    fn refresh_reference(&self) {
+        let symbols = self.search_symbols(&SymbolQuery::default());
+        self.reference.update(symbols, |version, changes| {
+            for entry in self.reference_subscriptions.iter() {
+                if let Some(client_sender) = self.clients.get(entry.key()) {
+                    for subscription in entry.value() {
+                        let message = ServerMessage::ReferenceData {
+                            stream_id: subscription.stream_id.clone(),
+                            version,
+                            changes: changes.to_vec(),
+                        };
+
+                        if client_sender.send(message.into()).is_err() {
+                            debug!("Client {} disconnected during reference data send", entry.key());
+                        }
+                    }
+                }
+            }
+        });
+    }
+
+    // Every listed symbol at the registry's current version
+    pub fn reference_snapshot(&self) -> ReferenceSnapshot {
+        self.refresh_reference();
+        self.reference.snapshot()
+    }
+
+    // Registry changes after `version`; None once they are no longer kept
+    pub fn reference_changes(&self, version: u64) -> Option<ReferenceChanges> {
+        self.refresh_reference();
+        self.reference.changes_since(version)
+    }
+
+    fn heartbeat_task(&self) -> impl Future<Output = ()> + Send + 'static {
+        let clients = Arc::clone(&self.clients);
+        let order_books = Arc::clone(&self.order_books);
+        let heartbeat_quotes = Arc::clone(&self.heartbeat_quotes);
+
+        async move {
+            let mut interval = interval(Duration::from_secs(30));
+
+            loop {
+                interval.tick().await;
+
+                let timestamp = Utc::now();
+                let mut tops = HashMap::new();
+                for client in clients.iter() {
+                    let heartbeat = client_heartbeat(&order_books, &heartbeat_quotes, client.key(), timestamp, &mut tops);
+                    if client.send(heartbeat.into()).is_err() {
+                        debug!("Client {} disconnected during heartbeat", client.key());
+                    }
+                }
+            }
+        }
+    }
+
+    fn popularity_sampling_task(&self) -> impl Future<Output = ()> + Send + 'static {
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let popularity = Arc::clone(&self.popularity);
+
+        async move {
+            let mut interval = interval(POPULARITY_SAMPLE_INTERVAL);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+                popularity.sample(&subscriptions.counts(), Utc::now());
+            }
+        }
+    }
+
+    fn usage_reports_task(&self, report_interval: Duration) -> impl Future<Output = ()> + Send + 'static {
+        let clients = Arc::clone(&self.clients);
+        let usage = Arc::clone(&self.usage);
+
+        async move {
+            let mut interval = interval(report_interval);
+            // The first tick completes immediately; skip it so reports cover a full period
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                for client in clients.iter() {
+                    if let Some(client_usage) = usage.client_usage(client.key()) {
+                        let report = ServerMessage::UsageReport {
+                            connected_at: client_usage.connected_at,
+                            messages: client_usage.messages,
+                            bytes: client_usage.bytes,
+                            streams: client_usage.streams,
+                            timestamp: Utc::now(),
+                        };
+
+                        if client.send(report.into()).is_err() {
+                            debug!("Client {} disconnected during usage report", client.key());
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Re-evaluates load every window, telling clients when their low-priority streams are
+    // conflated further or restored
+    fn load_shedding_task(&self, load_shedder: Arc<LoadShedder>) -> impl Future<Output = ()> + Send + 'static {
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let clients = Arc::clone(&self.clients);
+        let events = self.events.clone();
+
+        async move {
+            let mut interval = interval(LOAD_WINDOW);
+            interval.tick().await;
+
+            loop {
+                interval.tick().await;
+
+                let Some(conflation) = load_shedder.evaluate() else {
+                    continue;
+                };
+                match conflation {
+                    Some(conflation) => warn!("Server under load, conflating low-priority streams to {:?}", conflation),
+                    None => info!("Load subsided, low-priority streams back at full rate"),
+                }
+                events.publish(MarketEvent::Conflation { interval: conflation });
+
+                let mut streams: HashMap<Uuid, Vec<String>> = HashMap::new();
+                for (client_id, stream_id) in subscriptions.find(|subscription| subscription.priority == StreamPriority::Low) {
+                    streams.entry(client_id).or_default().push(stream_id);
+                }
+
+                for (client_id, stream_ids) in streams {
+                    let Some(client_sender) = clients.get(&client_id) else {
+                        continue;
+                    };
+
+                    let notice = throttled_notice(stream_ids, conflation);
+                    if client_sender.send(notice.into()).is_err() {
+                        debug!("Client {} disconnected during throttle notice", client_id);
+                    }
+                }
+            }
+        }
+    }
+
+    // Halts futures at expiry and delists them after the delay, taking their books,
+    // streams and calendar spreads with them, then lists any rolled replacements
+    fn futures_expiry_task(&self) -> impl Future<Output = ()> + Send + 'static {
+        let futures = Arc::clone(&self.futures);
+        let order_books = Arc::clone(&self.order_books);
+        let events = self.events.clone();
+        let pairs = Arc::clone(&self.pairs);
+        let pinned_symbols = Arc::clone(&self.pinned_symbols);
+        let halted_symbols = Arc::clone(&self.halted_symbols);
+        let limits = self.limits.clone();
+        let quantity_decimals = self.quantity_decimals.clone();
+        let actor_tags = self.actor_tags;
+        let epochs = Arc::clone(&self.epochs);
+
+        async move {
+            let mut interval = interval(Duration::from_secs(1));
+
+            loop {
+                interval.tick().await;
+                let now = Utc::now();
+
+                for mut curve in futures.iter_mut() {
+                    let curve_events = curve.expire(now, |symbol| {
+                        order_books.get(symbol).and_then(|order_book_ref| order_book_ref.snapshot().get_spread_info().1)
+                    });
+
+                    for curve_event in curve_events {
+                        match curve_event {
+                            CurveEvent::Halted(contract) => {
+                                halted_symbols.insert(contract.symbol.clone());
+                                info!("Halted expired future {} at {:?}", contract.symbol, contract.settlement_price);
+                                publish_status(&events, &pairs, &contract);
+                            }
+                            CurveEvent::Delisted(contract) => {
+                                publish_status(&events, &pairs, &contract);
+                                pairs.retain(|_, pair| pair.base != contract.symbol && pair.quote != contract.symbol);
+
+                                order_books.remove(&contract.symbol);
+                                events.publish(MarketEvent::BookRemoved { symbol: Arc::from(contract.symbol.as_str()) });
+                                pinned_symbols.remove(&contract.symbol);
+                                halted_symbols.remove(&contract.symbol);
+                                info!("Delisted future {}", contract.symbol);
+                            }
+                            CurveEvent::Listed(contract) => {
+                                list_future(&order_books, &limits, &quantity_decimals, actor_tags, &epochs, &contract, curve.opening_price(&contract, now));
+                                pinned_symbols.insert(contract.symbol.clone());
+                                info!("Listed future {} expiring {}", contract.symbol, contract.expiry);
+                            }
+                        }
+                    }
+
+                    for spread in curve.calendar_spreads() {
+                        if !pairs.contains_key(&spread.name) {
+                            info!("Registered calendar spread: {}", spread.name);
+                            pairs.insert(spread.name.clone(), spread);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Fills resting paper orders from the trades they are crossed by, and cancels those of
+    // removed books
+    fn paper_trading_task(&self, paper_desk: Arc<PaperDesk>) -> impl Future<Output = ()> + Send + 'static {
+        let mut receiver = self.events.subscribe();
+        let clients = Arc::clone(&self.clients);
+
+        async move {
+            while let Some(event) = next_event(&mut receiver, "Paper trading").await {
+                let reports = match event {
+                    MarketEvent::Trade(trade) => paper_desk.on_trade(&trade),
+                    MarketEvent::BookRemoved { symbol } => paper_desk.remove_symbol(&symbol),
+                    _ => continue,
+                };
+                for (owner, report) in reports {
+                    deliver_paper_report(&paper_desk, &clients, owner, report);
+                }
+            }
+        }
+    }
+
+    fn symbol_eviction_task(&self, ttl: Duration) -> impl Future<Output = ()> + Send + 'static {
+        let order_books = Arc::clone(&self.order_books);
+        let subscriptions = Arc::clone(&self.subscriptions);
+        let events = self.events.clone();
+        let pinned_symbols = Arc::clone(&self.pinned_symbols);
+        let external_symbols = Arc::clone(&self.external_symbols);
+        let symbol_registry = Arc::clone(&self.symbol_registry);
+
+        async move {
+            let mut interval = interval(ttl.min(Duration::from_secs(5)));
+            let mut idle_since: HashMap<String, Instant> = HashMap::new();
+
+            loop {
+                interval.tick().await;
+                let now = Instant::now();
+                let _registry = symbol_registry.lock().await;
+
+                let symbols: Vec<String> = order_books
+                    .iter()
+                    .map(|entry| entry.key().clone())
+                    .filter(|symbol| !pinned_symbols.contains(symbol))
+                    .collect();
+                idle_since.retain(|symbol, _| order_books.contains_key(symbol));
+
+                for symbol in symbols {
+                    if subscriptions.symbol(&symbol).is_some() {
+                        idle_since.remove(&symbol);
+                        continue;
+                    }
+
+                    let since = *idle_since.entry(symbol.clone()).or_insert(now);
+                    if now.duration_since(since) >= ttl {
+                        order_books.remove(&symbol);
+                        events.publish(MarketEvent::BookRemoved { symbol: Arc::from(symbol.as_str()) });
+                        external_symbols.remove(&symbol);
+                        idle_since.remove(&symbol);
+                        info!("Evicted idle symbol: {}", symbol);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn register_client(&self, client_id: Uuid, sender: ClientSender) {
+        self.clients.insert(client_id, sender);
+        self.usage.register(client_id);
+        info!("Registered client: {}", client_id);
+    }
+
+    pub fn unregister_client(&self, client_id: &Uuid) {
+        self.clients.remove(client_id);
+        self.usage.unregister(client_id);
+        self.stream_stats.forget_client(client_id);
+        self.unbind_api_key(client_id);
+        self.summary_subscriptions.remove(client_id);
+        self.reference_subscriptions.remove(client_id);
+        self.heartbeat_quotes.remove(client_id);
+        if let Some(paper_desk) = &self.paper_desk {
+            paper_desk.forget_owner(client_id);
+        }
+        if let Some(load_shedder) = &self.load_shedder {
+            load_shedder.forget_client(client_id);
+        }
+
+        self.subscriptions.remove_client(client_id);
+        self.snapshot_schedules.retain(|(schedule_client, _), _| schedule_client != client_id);
+        self.replays.retain(|(replay_client, _)| replay_client != client_id);
+
+        info!("Unregistered client: {}", client_id);
+    }
+
+    // Stream statistics start with the subscription, so that its initial update is counted
+    #[allow(clippy::too_many_arguments)]
+    pub async fn subscribe(
+        &self,
+        client_id: Uuid,
+        stream_id: String,
+        symbol: String,
+        data_type: DataType,
+        max_levels: Option<u32>,
+        max_orders: Option<u32>,
+        snapshot_interval_ms: Option<u64>,
+        priority: Option<StreamPriority>,
+        sample_every_n: Option<u32>,
+    ) -> Result<(), String> {
+        self.stream_stats.open(client_id, &stream_id);
+        let result = self
+            .subscribe_stream(
+                client_id,
+                stream_id.clone(),
+                symbol,
+                data_type,
+                max_levels,
+                max_orders,
+                snapshot_interval_ms,
+                priority,
+                sample_every_n,
+            )
+            .await;
+        if result.is_err() {
+            self.stream_stats.close(&client_id, &stream_id);
+        }
+        result
+    }
+
+    // Everything a subscription is refused for before its book is created, for the API key
+    // the stream would be billed to. Returns the full-book snapshot interval.
+    fn check_subscription(
+        &self,
+        api_key: Option<&str>,
+        symbol: &str,
+        data_type: &DataType,
+        snapshot_interval_ms: Option<u64>,
+    ) -> Result<Option<Duration>, String> {
+        if is_sandbox(symbol) {
+            self.check_sandbox_access(api_key, symbol)?;
+        }
+        if let Some(quota) = api_key.zip(self.quotas.as_ref()).and_then(|(api_key, quotas)| quotas.exceeded_key(api_key)) {
+            return Err(format!("{:?} {:?} quota of {} exceeded", quota.period, quota.unit, quota.limit));
+        }
+
+        let snapshot_interval = match snapshot_interval_ms {
+            Some(interval_ms) => {
+                let by_book = matches!(data_type, DataType::MBP | DataType::MBO);
+                if !by_book || self.pairs.contains_key(symbol) || self.option_contracts.contains_key(symbol) {
+                    return Err("snapshot_interval_ms is only supported on MBP and MBO book streams".to_string());
+                }
+                Some(validate_snapshot_interval(interval_ms)?)
+            }
+            None => None,
+        };
+
+        if let DataType::Summary | DataType::Reference = data_type {
+            return Ok(snapshot_interval);
+        }
+        if self.symbol_config.get(symbol).is_some_and(|config| !config.allows(data_type)) {
+            return Err(format!("{} streams are not enabled for {}", data_type.name(), symbol));
+        }
+
+        if self.pairs.contains_key(symbol) {
+            if !matches!(data_type, DataType::MBP) {
+                return Err(format!("Synthetic pair {} only supports MBP streams", symbol));
+            }
+            return Ok(snapshot_interval);
+        }
+
+        if self.get_future(symbol).is_some_and(|contract| contract.status == TradingStatus::Delisted) {
+            return Err(format!("{} has been delisted", symbol));
+        }
+
+        if self.option_contracts.contains_key(symbol) {
+            if !matches!(data_type, DataType::OptionQuote) {
+                return Err(format!("Option contract {} only supports OptionQuote streams", symbol));
+            }
+            return Ok(snapshot_interval);
+        }
+
+        match data_type {
+            DataType::Indicators { spec } => validate_indicator_spec(spec)?,
+            DataType::Ladder { rows, tick_group } => validate_ladder(*rows, *tick_group)?,
+            DataType::OrderActivity { max_distance_bps, min_quantity, .. } => validate_activity_filter(*max_distance_bps, *min_quantity)?,
+            _ => {}
+        }
+
+        if matches!(data_type, DataType::MarkPrice | DataType::Funding) && !self.perpetuals.contains_key(symbol) {
+            return Err(format!("{} is not a perpetual", symbol));
+        }
+
+        match data_type {
+            DataType::OptionQuote => return Err(format!("{} is not an option contract", symbol)),
+            DataType::OptionChain if !self.option_chains.contains_key(symbol) => {
+                return Err(format!("{} has no options chain", symbol));
+            }
+            _ => {}
+        }
+
+        Ok(snapshot_interval)
+    }
+
+    // Sampling applies to streams sent with every update of their own book
+    fn check_sampling(&self, symbol: &str, data_type: &DataType, sample_every_n: Option<u32>) -> Result<Option<u32>, String> {
+        let Some(every_n) = sample_every_n else {
+            return Ok(None);
+        };
+        let per_update = matches!(
+            data_type,
+            DataType::MBP
+                | DataType::MBO
+                | DataType::AggressorFlow
+                | DataType::Ladder { .. }
+                | DataType::LiquidityAge
+                | DataType::Indicators { .. }
+                | DataType::OrderStats
+        );
+        if !per_update || self.pairs.contains_key(symbol) || self.option_contracts.contains_key(symbol) {
+            return Err(format!("sample_every_n is not supported on {} streams of {}", data_type.name(), symbol));
+        }
+        validate_sample_every_n(every_n).map(Some)
+    }
+
+    // Checks streams as subscribe would, without creating subscriptions or books. New
+    // symbols of the batch count against the symbol limit together.
+    pub fn validate_streams(&self, api_key: Option<&str>, streams: Vec<StreamSpec>) -> Vec<StreamValidation> {
+        let mut new_symbols: HashSet<String> = HashSet::new();
+
+        streams
+            .into_iter()
+            .map(|spec| {
+                // Sandboxes of other keys are as unknown as in subscribe's error
+                let hidden = is_sandbox(&spec.symbol) && self.check_sandbox_access(api_key, &spec.symbol).is_err();
+                let exists = matches!(spec.data_type, DataType::Summary | DataType::Reference)
+                    || (!hidden && self.order_books.contains_key(&spec.symbol))
+                    || self.pairs.contains_key(&spec.symbol)
+                    || self.option_contracts.contains_key(&spec.symbol);
+                let result = self
+                    .validate_strict(&spec.symbol, &spec.data_type, spec.max_levels)
+                    .and_then(|_| self.check_sampling(&spec.symbol, &spec.data_type, spec.sample_every_n))
+                    .and_then(|_| self.check_subscription(api_key, &spec.symbol, &spec.data_type, spec.snapshot_interval_ms))
+                    .and_then(|snapshot_interval| {
+                        if !exists && !new_symbols.contains(&spec.symbol) {
+                            if let Some(max_symbols) = self.limits.max_symbols.filter(|max| self.order_books.len() + new_symbols.len() >= *max) {
+                                return Err(format!("Symbol limit of {} reached", max_symbols));
+                            }
+                            new_symbols.insert(spec.symbol.clone());
+                        }
+                        Ok(snapshot_interval)
+                    });
+
+                let max_levels = self.granted_levels(spec.max_levels);
+                StreamValidation {
+                    valid: result.is_ok(),
+                    snapshot_interval_ms: result.as_ref().ok().copied().flatten().map(|interval| interval.as_millis() as u64),
+                    error: result.err(),
+                    max_levels,
+                    max_orders: spec.max_orders.unwrap_or_else(|| default_max_orders(max_levels)),
+                    priority: spec.priority.unwrap_or_default(),
+                    sample_every_n: spec.sample_every_n,
+                    exists,
+                    stream_id: spec.stream_id,
+                    symbol: spec.symbol,
+                    data_type: spec.data_type,
+                }
+            })
+            .collect()
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn subscribe_stream(
+        &self,
+        client_id: Uuid,
+        stream_id: String,
+        symbol: String,
+        data_type: DataType,
+        max_levels: Option<u32>,
+        max_orders: Option<u32>,
+        snapshot_interval_ms: Option<u64>,
+        priority: Option<StreamPriority>,
+        sample_every_n: Option<u32>,
+    ) -> Result<(), String> {
+        let priority = priority.unwrap_or_default();
+        let api_key = self.client_keys.get(&client_id).map(|api_key| api_key.clone());
+        let sample_every_n = self.check_sampling(&symbol, &data_type, sample_every_n)?;
+        let snapshot_interval = self.check_subscription(api_key.as_deref(), &symbol, &data_type, snapshot_interval_ms)?;
+
+        if let DataType::Summary = data_type {
+            return self.subscribe_summary(client_id, stream_id, symbol).await;
+        }
+        if let DataType::Reference = data_type {
+            return self.subscribe_reference(client_id, stream_id, symbol);
+        }
+
+        if let Some(pair) = self.pairs.get(&symbol).map(|pair| pair.clone()) {
+            return self.subscribe_pair(client_id, stream_id, pair, data_type, priority).await;
+        }
+
+        if let Some(underlying) = self.option_contracts.get(&symbol).map(|underlying| underlying.clone()) {
+            return self.subscribe_option(client_id, stream_id, symbol, underlying, data_type, priority).await;
+        }
+
+        // Ensure the symbol exists; the registry lock keeps eviction away until the subscription is in place
+        let registry = self.symbol_registry.lock().await;
+        if !self.order_books.contains_key(&symbol) {
+            self.check_symbol_limit()?;
+            self.initialize_symbol(&symbol).await;
+        }
+
+        let mut subscription = Subscription::new(
+            stream_id.clone(),
+            symbol.clone(),
+            data_type.clone(),
+            max_levels,
+            max_orders,
+            client_id,
+        );
+        subscription.priority = priority;
+        subscription.sampler = sample_every_n.filter(|every_n| *every_n > 1).map(|every_n| Arc::new(UpdateSampler::new(every_n)));
+        subscription.level_base = matches!(data_type, DataType::MBPDelta).then(|| Arc::new(LevelBase::default()));
+        let (depth, level_base) = (subscription.max_levels, subscription.level_base.clone());
+        let payload = shared_payload(&subscription);
+
+        // Add subscription
+        self.subscriptions.insert(
+            client_id,
+            &stream_id,
+            &symbol,
+            payload,
+            subscription,
+        );
+        drop(registry);
+
+        let key = (client_id, stream_id.clone());
+        match snapshot_interval {
+            Some(interval) => {
+                let by_order = matches!(data_type, DataType::MBO);
+                self.snapshot_schedules.insert(key, SnapshotSchedule::new(&symbol, by_order, interval));
+            }
+            None => {
+                self.snapshot_schedules.remove(&key);
+            }
+        }
+
+        // Send initial snapshot, reusing this tick's view when another subscriber already built it
+        if let Some(order_book_ref) = self.order_books.get(&symbol) {
+            if let Some(client_sender) = self.clients.get(&client_id) {
+                let order_book = order_book_ref.snapshot();
+                let (sequence, epoch, event_ts) = (order_book.get_sequence(), order_book.get_epoch(), order_book.get_event_timestamp());
+
+                let initial_message = match (payload, &data_type) {
+                    (Some(key), _) => {
+                        let data = match self.snapshots.get(&symbol, &key, sequence) {
+                            Some(data) => data,
+                            None => {
+                                let Some(data) = build_shared_payload(&order_book, &self.flows, &symbol, key) else {
+                                    return Err("Failed to build initial snapshot".to_string());
+                                };
+                                self.snapshots.insert(&symbol, key, sequence, data.clone());
+                                data
+                            }
+                        };
+
+                        // The first view is anchored so the client can check its book from the start
+                        Some(OutboundMessage::SharedMarketData {
+                            stream_id: stream_id.clone(),
+                            symbol: symbol.as_str().into(),
+                            data,
+                            sequence,
+                            epoch,
+                            timestamp: Utc::now(),
+                            event_ts,
+                            anchor: self.anchor_interval.and_then(|_| view_anchor(&order_book, key)),
+                            venue_sequence: order_book.venue_sequence(),
+                        })
+                    }
+                    (None, DataType::Indicators { spec }) => {
+                        Some(market_data(&stream_id, &symbol, indicator_update(&self.candles, &symbol, spec), sequence, epoch, event_ts))
+                    }
+                    (None, DataType::OrderStats) => {
+                        Some(market_data(&stream_id, &symbol, order_stats_update(&self.order_stats, &symbol), sequence, epoch, event_ts))
+                    }
+                    // Nothing to send before the first mark price or settlement
+                    (None, DataType::MarkPrice | DataType::Funding) => perpetual_update(&self.perpetuals, &symbol, &data_type)
+                        .map(|data| market_data(&stream_id, &symbol, data, sequence, epoch, event_ts)),
+                    (None, DataType::OptionChain) => self.option_chains
+                        .get(&symbol)
+                        .filter(|chain| !chain.quotes().is_empty())
+                        .map(|chain| market_data(&stream_id, &symbol, option_chain_update(&chain), sequence, epoch, event_ts)),
+                    // Events stream from the next book update, trade or quarantined event on
+                    (None, DataType::OrderActivity { .. } | DataType::Quarantine | DataType::Trades) => None,
+                    // Every order of the book, for the order changes of the next updates to apply to
+                    (None, DataType::MBODelta) => {
+                        Some(market_data(&stream_id, &symbol, full_book_update(&order_book, true), sequence, epoch, event_ts))
+                    }
+                    // The view the level changes of the next updates apply to
+                    (None, DataType::MBPDelta) => {
+                        let (bids, asks) = order_book.get_mbp_data(depth);
+                        Some(market_data(&stream_id, &symbol, MarketDataUpdate::MBP { bids, asks }, sequence, epoch, event_ts))
+                    }
+                    (None, _) => unreachable!("summary, reference and option contract subscriptions are handled separately"),
+                };
+
+                if let Some(initial_message) = initial_message {
+                    let sent = match &level_base {
+                        // Left out when the book's next update already sent the stream its view
+                        Some(level_base) => level_base.prime(sequence, || client_sender.send(initial_message).map(|_| ()).map_err(|_| ())),
+                        None => client_sender.send(initial_message).map(|_| ()).map_err(|_| ()),
+                    };
+                    if sent.is_err() {
+                        return Err("Failed to send initial snapshot".to_string());
+                    }
+                }
+            }
+        }
+
+        info!("Client {} subscribed to {} stream {} ({:?})",
+            client_id, symbol, stream_id, data_type
+        );
+
+        Ok(())
+    }
+
+    async fn subscribe_summary(
+        &self,
+        client_id: Uuid,
+        stream_id: String,
+        symbol: String,
+    ) -> Result<(), String> {
+        let subscription = Subscription::new(
+            stream_id.clone(),
+            symbol,
+            DataType::Summary,
+            None,
+            None,
+            client_id,
+        );
+
+        self.summary_subscriptions
+            .entry(client_id)
+            .or_default()
+            .push(subscription);
+
+        // Send the current summary so the client does not wait for the next cycle
+        if let Some(client_sender) = self.clients.get(&client_id) {
+            let initial_message = ServerMessage::MarketSummary {
+                stream_id: stream_id.clone(),
+                summary: self.get_market_summary().await,
+            };
+
+            if client_sender.send(initial_message.into()).is_err() {
+                return Err("Failed to send initial summary".to_string());
+            }
+        }
+
+        info!("Client {} subscribed to market summary stream {}", client_id, stream_id);
+
+        Ok(())
+    }
+
+    fn subscribe_reference(&self, client_id: Uuid, stream_id: String, symbol: String) -> Result<(), String> {
+        let subscription = Subscription::new(
+            stream_id.clone(),
+            symbol,
+            DataType::Reference,
+            None,
+            None,
+            client_id,
+        );
+
+        self.reference_subscriptions
+            .entry(client_id)
+            .or_default()
+            .push(subscription);
+
+        // The version the client's copy of the registry is to be brought up to
+        if let Some(client_sender) = self.clients.get(&client_id) {
+            let initial_message = ServerMessage::ReferenceData {
+                stream_id: stream_id.clone(),
+                version: self.reference.version(),
+                changes: Vec::new(),
+            };
+
+            if client_sender.send(initial_message.into()).is_err() {
+                return Err("Failed to send reference data version".to_string());
+            }
+        }
+
+        info!("Client {} subscribed to reference data stream {}", client_id, stream_id);
+
+        Ok(())
+    }
+
+    async fn subscribe_pair(
+        &self,
+        client_id: Uuid,
+        stream_id: String,
+        pair: SyntheticPair,
+        data_type: DataType,
+        priority: StreamPriority,
+    ) -> Result<(), String> {
+        let mut subscription = Subscription::new(
+            stream_id.clone(),
+            pair.name.clone(),
+            data_type,
+            None,
+            None,
+            client_id,
+        );
+        subscription.priority = priority;
+
+        self.subscriptions.insert(
+            client_id,
+            &stream_id,
+            &pair.name,
+            shared_payload(&subscription),
+            subscription,
+        );
+
+        // Send initial synthetic quote
+        if let Some((data, sequence, epoch, event_ts)) = compute_pair_update(&self.order_books, &pair) {
+            if let Some(client_sender) = self.clients.get(&client_id) {
+                let initial_message = market_data(&stream_id, &pair.name, data, sequence, epoch, event_ts);
+
+                if client_sender.send(initial_message).is_err() {
+                    return Err("Failed to send initial snapshot".to_string());
+                }
+            }
+        }
+
+        info!("Client {} subscribed to synthetic pair {} stream {}", client_id, pair.name, stream_id);
+
+        Ok(())
+    }
+
+    // Option contracts have no book of their own and are quoted off their underlying's
+    async fn subscribe_option(
+        &self,
+        client_id: Uuid,
+        stream_id: String,
+        contract: String,
+        underlying: String,
+        data_type: DataType,
+        priority: StreamPriority,
+    ) -> Result<(), String> {
+        let mut subscription = Subscription::new(
+            stream_id.clone(),
+            contract.clone(),
+            data_type,
+            None,
+            None,
+            client_id,
+        );
+        subscription.priority = priority;
+
+        self.subscriptions.insert(
+            client_id,
+            &stream_id,
+            &contract,
+            shared_payload(&subscription),
+            subscription,
+        );
+
+        // Send the latest quote, once the underlying has had a mid price
+        let quote = self.option_chains.get(&underlying).and_then(|chain| chain.quote(&contract).cloned());
+        let (sequence, epoch, event_ts) = self.order_books.get(&underlying).map_or((0, 0, Utc::now()), |order_book_ref| {
+            let order_book = order_book_ref.snapshot();
+            (order_book.get_sequence(), order_book.get_epoch(), order_book.get_event_timestamp())
+        });
+        if let (Some(quote), Some(client_sender)) = (quote, self.clients.get(&client_id)) {
+            let initial_message = market_data(&stream_id, &contract, MarketDataUpdate::OptionQuote { quote }, sequence, epoch, event_ts);
+
+            if client_sender.send(initial_message).is_err() {
+                return Err("Failed to send initial snapshot".to_string());
+            }
+        }
+
+        info!("Client {} subscribed to option contract {} stream {}", client_id, contract, stream_id);
+
+        Ok(())
+    }
+
+    pub fn unsubscribe(&self, client_id: Uuid, stream_id: &str) -> bool {
+        for cross_symbol in [&self.summary_subscriptions, &self.reference_subscriptions] {
+            if let Some(mut subs) = cross_symbol.get_mut(&client_id) {
+                let initial_len = subs.len();
+                subs.retain(|sub| sub.stream_id != stream_id);
+
+                if subs.len() != initial_len {
+                    self.stream_stats.close(&client_id, stream_id);
+                    info!("Client {} unsubscribed from stream {}", client_id, stream_id);
+                    return true;
+                }
+            }
+        }
+
+        if self.subscriptions.remove(&client_id, stream_id).is_some() {
+            self.snapshot_schedules.remove(&(client_id, stream_id.to_string()));
+            if let Some(load_shedder) = &self.load_shedder {
+                load_shedder.forget_stream(&client_id, stream_id);
+            }
+            self.stream_stats.close(&client_id, stream_id);
+            info!("Client {} unsubscribed from stream {}", client_id, stream_id);
+            return true;
+        }
+
+        if self.replays.remove(&(client_id, stream_id.to_string())).is_some() {
+            info!("Client {} stopped replay {}", client_id, stream_id);
+            return true;
+        }
+
+        false
+    }
+
+    // Sends ReplayStarted and then the recorded updates of a window to the client. The
+    // updates carry their recorded sequence and event_ts, so the replay stream reads like
+    // the live one did.
+    #[allow(clippy::too_many_arguments)]
+    pub fn start_replay(
+        &self,
+        client_id: Uuid,
+        stream_id: String,
+        symbol: String,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        speed: Option<f64>,
+        max_levels: Option<u32>,
+    ) -> Result<(), String> {
+        let Some(history) = &self.history else {
+            return Err("History is not recorded on this server".to_string());
+        };
+        let speed = validate_replay_speed(speed)?;
+        let max_levels = max_levels.unwrap_or(history.levels()).min(history.levels());
+        let frames = history.window(&symbol, from, to)?;
+
+        let key = (client_id, stream_id.clone());
+        if !self.replays.insert(key.clone()) {
+            return Err(format!("Replay {} is already running", stream_id));
+        }
+
+        let started = ServerMessage::ReplayStarted {
+            stream_id: stream_id.clone(),
+            symbol: symbol.clone(),
+            from: frames[0].event_ts,
+            to: frames[frames.len() - 1].event_ts,
+            updates: frames.len(),
+            speed,
+        };
+
+        if let Some(client_sender) = self.clients.get(&client_id) {
+            let _ = client_sender.send(started.into());
+        }
+        info!("Client {} started replay {} of {}", client_id, stream_id, symbol);
+
+        let clients = Arc::clone(&self.clients);
+        let replays = Arc::clone(&self.replays);
+        tokio::spawn(async move {
+            let mut replay = Replay::new(frames, speed);
+            while let Some(frame) = replay.next().await {
+                if !replays.contains(&key) {
+                    return;
+                }
+                let Some(client_sender) = clients.get(&client_id) else {
+                    return;
+                };
+                let (bids, asks) = frame.levels(max_levels);
+                let update = market_data(&stream_id, &symbol, MarketDataUpdate::MBP { bids, asks }, frame.sequence, frame.epoch, frame.event_ts);
+                if client_sender.send(update).is_err() {
+                    return;
+                }
+            }
+
+            if replays.remove(&key).is_some() {
+                if let Some(client_sender) = clients.get(&client_id) {
+                    let _ = client_sender.send(ServerMessage::ReplayComplete { stream_id: stream_id.clone() }.into());
+                }
+                info!("Client {} finished replay {}", client_id, stream_id);
+            }
+        });
+
+        Ok(())
+    }
+
+    // Applies order events from an external feed. The first batch for an unknown symbol
+    // creates an empty book, which is kept from then on; simulated books are refused.
+    // Events outside the book's price band are rejected, and quarantined ones published.
+    pub async fn ingest(&self, symbol: &str, events: &[IngestEvent]) -> Result<IngestReport, IngestError> {
+        if self.pairs.contains_key(symbol) || self.get_future(symbol).is_some() || is_sandbox(symbol) {
+            return Err(IngestError::NotExternal(symbol.to_string()));
+        }
+
+        let registry = self.symbol_registry.lock().await;
+        if !self.order_books.contains_key(symbol) {
+            self.check_symbol_limit().map_err(IngestError::SymbolLimit)?;
+            self.create_book(symbol, false);
+        } else if !self.external_symbols.contains(symbol) {
+            return Err(IngestError::NotExternal(symbol.to_string()));
+        }
+        self.pinned_symbols.insert(symbol.to_string());
+        let order_book_ref = self.order_books.get(symbol).map(|entry| entry.value().clone());
+        drop(registry);
+
+        let Some(order_book_ref) = order_book_ref else {
+            return Err(IngestError::NotExternal(symbol.to_string()));
+        };
+        let (report, out_of_band) = order_book_ref.update(|order_book| {
+            let (report, activities, mappings) = apply_events(order_book, events);
+            // Recorded under the book's lock, so concurrent batches keep them in sequence order
+            if !mappings.is_empty() {
+                let mut venue_sequences = self.venue_sequences.entry(symbol.to_string()).or_default();
+                for mapping in mappings {
+                    venue_sequences.record(mapping);
+                }
+            }
+            // Published under the lock too, so they come before the BookUpdated of any
+            // sequence they led to and order change streams get every one of them
+            for activity in activities {
+                self.events.publish(MarketEvent::Activity(activity));
+            }
+            (report, order_book.take_out_of_band())
+        });
+
+        publish_out_of_band(&self.events, &self.metrics, out_of_band);
+
+        Ok(report)
+    }
+
+    // Looks up the feed event behind a book sequence of an external book, or the book
+    // sequence a venue sequence was published at; exactly one of the two is given
+    pub fn map_sequence(&self, symbol: &str, sequence: Option<u64>, venue_sequence: Option<u64>) -> Result<Option<SequenceMapping>, String> {
+        if !self.external_symbols.contains(symbol) {
+            return Err(format!("{} is not fed through the ingest API", symbol));
+        }
+
+        let venue_sequences = self.venue_sequences.get(symbol);
+        match (sequence, venue_sequence) {
+            (Some(sequence), None) => Ok(venue_sequences.and_then(|mappings| mappings.by_sequence(sequence))),
+            (None, Some(venue_sequence)) => Ok(venue_sequences.and_then(|mappings| mappings.by_venue_sequence(venue_sequence))),
+            _ => Err("Give either sequence or venue_sequence".to_string()),
+        }
+    }
+
+    // An account for a paper trading consumer embedded in the process, and the channel its
+    // order updates and fills arrive on; the account closes with the receiver
+    pub fn open_paper_account(&self) -> Result<(Uuid, mpsc::UnboundedReceiver<ServerMessage>), String> {
+        let paper_desk = self.paper_desk.as_ref().ok_or("Paper trading is not enabled")?;
+        Ok(paper_desk.open_account())
+    }
+
+    // Accepts a paper order of a client or embedded account and sends it its updates and
+    // fills, the first ones after the model's latency
+    pub fn submit_paper_order(&self, owner: Uuid, api_key: Option<&str>, request: PaperOrderRequest) -> Result<PaperOrder, String> {
+        let paper_desk = self.paper_desk.as_ref().ok_or("Paper trading is not enabled")?;
+        request.validate()?;
+        let symbol = &request.symbol;
+        if !self.order_books.contains_key(symbol) || is_sandbox(symbol) && self.check_sandbox_access(api_key, symbol).is_err() {
+            return Err(format!("Unknown symbol {}", symbol));
+        }
+        if self.halted_symbols.contains(symbol) {
+            return Err(format!("{} is halted", symbol));
+        }
+
+        let (order, latency) = paper_desk.accept(owner, request);
+        deliver_paper_report(paper_desk, &self.clients, owner, ServerMessage::PaperOrderUpdate { order: order.clone() });
+
+        let paper_desk = Arc::clone(paper_desk);
+        let order_books = Arc::clone(&self.order_books);
+        let clients = Arc::clone(&self.clients);
+        let order_id = order.order_id;
+        let arrive = move || {
+            let arrived = paper_desk.arrive(order_id, |symbol| order_books.get(symbol).map(|order_book_ref| order_book_ref.snapshot()));
+            if let Some((owner, reports)) = arrived {
+                for report in reports {
+                    deliver_paper_report(&paper_desk, &clients, owner, report);
+                }
+            }
+        };
+        if latency.is_zero() {
+            arrive();
+        } else {
+            tokio::spawn(async move {
+                sleep(latency).await;
+                arrive();
+            });
+        }
+
+        Ok(order)
+    }
+
+    pub fn cancel_paper_order(&self, owner: Uuid, order_id: u64) -> Result<PaperOrder, String> {
+        let paper_desk = self.paper_desk.as_ref().ok_or("Paper trading is not enabled")?;
+        let order = paper_desk.cancel(&owner, order_id)?;
+        deliver_paper_report(paper_desk, &self.clients, owner, ServerMessage::PaperOrderUpdate { order: order.clone() });
+        Ok(order)
+    }
+
+    // Creates a private simulated book for the API key, seeded so its order flow can be replayed
+    pub async fn create_sandbox(&self, owner: &str, request: &SandboxRequest) -> Result<SandboxInfo, SandboxError> {
+        let per_key = self.sandboxes_per_key.ok_or(SandboxError::Disabled)?;
+        let price = request.price.unwrap_or(100.0);
+        validate_sandbox_price(price)?;
+
+        let registry = self.symbol_registry.lock().await;
+        if self.sandboxes.iter().filter(|sandbox| sandbox.owner == owner).count() >= per_key {
+            return Err(SandboxError::LimitReached(per_key));
+        }
+        self.check_symbol_limit().map_err(SandboxError::SymbolLimit)?;
+
+        let symbol = format!("{}{}", SANDBOX_PREFIX, Uuid::new_v4());
+        let sandbox = Sandbox {
+            owner: owner.to_string(),
+            seed: request.seed.unwrap_or_else(rand::random),
+            created_at: Utc::now(),
+        };
+        let mut order_book = OrderBook::new(symbol.clone());
+        if let Some(limit) = self.limits.max_orders_per_book {
+            order_book.set_order_limit(limit);
+        }
+        order_book.set_seed(sandbox.seed);
+        order_book.set_actor_tags(self.actor_tags);
+        // Sandbox symbols are never reused, so each has a single epoch
+        order_book.set_epoch(1);
+        order_book.initialize_with_sample_data_around(price);
+
+        self.order_books.insert(symbol.clone(), Arc::new(PublishedBook::new(order_book)));
+        self.pinned_symbols.insert(symbol.clone());
+        self.sandboxes.insert(symbol.clone(), sandbox);
+        drop(registry);
+
+        info!("Created sandbox {} for API key {}", symbol, owner);
+        self.get_sandbox(owner, &symbol)
+    }
+
+    pub fn list_sandboxes(&self, owner: &str) -> Vec<SandboxInfo> {
+        let mut symbols: Vec<String> = self.sandboxes
+            .iter()
+            .filter(|sandbox| sandbox.owner == owner)
+            .map(|sandbox| sandbox.key().clone())
+            .collect();
+        symbols.sort();
+        symbols.iter().filter_map(|symbol| self.get_sandbox(owner, symbol).ok()).collect()
+    }
+
+    pub fn get_sandbox(&self, owner: &str, symbol: &str) -> Result<SandboxInfo, SandboxError> {
+        let sandbox = self.owned_sandbox(owner, symbol)?;
+        let order_book_ref = self.order_books
+            .get(symbol)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| SandboxError::NotFound(symbol.to_string()))?;
+        let order_book = order_book_ref.snapshot();
+
+        Ok(SandboxInfo {
+            symbol: symbol.to_string(),
+            seed: sandbox.seed,
+            halted: self.halted_symbols.contains(symbol),
+            mid_price: order_book.get_spread_info().1,
+            sequence: order_book.get_sequence(),
+            created_at: sandbox.created_at,
+        })
+    }
+
+    // Applies the command and publishes the resulting changes right away, halted or not
+    pub fn control_sandbox(&self, owner: &str, symbol: &str, command: &SandboxCommand) -> Result<SandboxInfo, SandboxError> {
+        self.owned_sandbox(owner, symbol)?;
+        let order_book_ref = self.order_books
+            .get(symbol)
+            .map(|entry| entry.value().clone())
+            .ok_or_else(|| SandboxError::NotFound(symbol.to_string()))?;
+
+        // The change and the trades it caused are taken together, so the simulation cannot
+        // publish the trades first
+        let publish = |change: &dyn Fn(&mut OrderBook) -> Vec<OrderActivity>| {
+            let (previous_sequence, activities, trades, mid_price, sequence, event_ts) = order_book_ref.update(|order_book| {
+                (
+                    order_book.get_sequence(),
+                    change(order_book),
+                    order_book.take_trades(),
+                    order_book.get_spread_info().1,
+                    order_book.get_sequence(),
+                    order_book.get_event_timestamp(),
+                )
+            });
+            for trade in trades {
+                self.events.publish(MarketEvent::Trade(trade));
+            }
+            for activity in activities {
+                self.events.publish(MarketEvent::Activity(activity));
+            }
+            self.events.publish(MarketEvent::BookUpdated {
+                symbol: Arc::from(symbol),
+                sequence,
+                previous_sequence: Some(previous_sequence),
+                event_ts,
+                mid_price,
+            });
+        };
+
+        match command {
+            SandboxCommand::SetPrice { price } => {
+                validate_sandbox_price(*price)?;
+                publish(&|order_book| order_book.recenter(*price));
+            }
+            SandboxCommand::Crash { percent } => {
+                if !(*percent > 0.0 && *percent < 100.0) {
+                    return Err(SandboxError::Invalid("percent must be between 0 and 100".to_string()));
+                }
+                publish(&|order_book| order_book.crash(*percent));
+            }
+            SandboxCommand::Halt => {
+                self.halted_symbols.insert(symbol.to_string());
+            }
+            SandboxCommand::Resume => {
+                self.halted_symbols.remove(symbol);
+            }
+        }
+        info!("Sandbox {}: {:?}", symbol, command);
+
+        self.get_sandbox(owner, symbol)
+    }
+
+    // Removes the book, ending every stream on it
+    pub async fn delete_sandbox(&self, owner: &str, symbol: &str) -> Result<(), SandboxError> {
+        self.owned_sandbox(owner, symbol)?;
+
+        let registry = self.symbol_registry.lock().await;
+        self.sandboxes.remove(symbol);
+        self.order_books.remove(symbol);
+        self.pinned_symbols.remove(symbol);
+        self.halted_symbols.remove(symbol);
+        drop(registry);
+        self.events.publish(MarketEvent::BookRemoved { symbol: Arc::from(symbol) });
+
+        let streams: Vec<(Uuid, String)> = match self.subscriptions.symbol(symbol) {
+            Some(symbol_subscriptions) => symbol_subscriptions.values().flat_map(|group| group.keys().cloned()).collect(),
+            None => Vec::new(),
+        };
+        for (client_id, stream_id) in streams {
+            self.unsubscribe(client_id, &stream_id);
+            if let Some(client_sender) = self.clients.get(&client_id) {
+                if client_sender.send(ServerMessage::Unsubscribed { stream_id }.into()).is_err() {
+                    debug!("Client {} disconnected during sandbox removal", client_id);
+                }
+            }
+        }
+
+        info!("Deleted sandbox {}", symbol);
+        Ok(())
+    }
+
+    fn owned_sandbox(&self, owner: &str, symbol: &str) -> Result<Sandbox, SandboxError> {
+        self.sandboxes
+            .get(symbol)
+            .filter(|sandbox| sandbox.owner == owner)
+            .map(|sandbox| sandbox.clone())
+            .ok_or_else(|| SandboxError::NotFound(symbol.to_string()))
+    }
+
+    // Only clients bound to the owning API key may stream a sandbox, and the name of one that
+    // does not exist is never turned into a new book
+    fn check_sandbox_access(&self, api_key: Option<&str>, symbol: &str) -> Result<(), String> {
+        match api_key {
+            Some(owner) if self.owned_sandbox(owner, symbol).is_ok() => Ok(()),
+            _ => Err(format!("Unknown sandbox {}", symbol)),
+        }
+    }
+
+    // Book, trade, activity and status events as they are published
+    pub fn subscribe_events(&self) -> broadcast::Receiver<MarketEvent> {
+        self.events.subscribe()
+    }
+
+    // Updates of one symbol for consumers in the same process, such as strategy engines,
+    // recorders and tests, without a socket or JSON in between. The stream starts with the
+    // current view and yields the whole view again after every change of the book, so a
+    // consumer that falls behind skips ahead rather than queueing. It ends once the book
+    // is removed. Local subscribers do not keep a symbol from being evicted.
+    pub async fn subscribe_local(
+        self: &Arc<Self>,
+        symbol: &str,
+        data_type: DataType,
+        depth: u32,
+    ) -> Result<impl Stream<Item = MarketDataUpdate>, String> {
+        match &data_type {
+            DataType::Summary => return Err("Market summaries are not a symbol stream".to_string()),
+            DataType::Reference => return Err("Reference data is not a symbol stream".to_string()),
+            DataType::Indicators { spec } => validate_indicator_spec(spec)?,
+            DataType::Ladder { rows, tick_group } => validate_ladder(*rows, *tick_group)?,
+            DataType::OrderActivity { .. } => return Err("Order activity is not a view; subscribe_events has every event".to_string()),
+            DataType::Quarantine => return Err("Quarantined events are not a view; subscribe_events has every event".to_string()),
+            DataType::Trades => return Err("Trades are not a view; subscribe_events has every event".to_string()),
+            DataType::MBODelta => return Err("Order changes are not a view; subscribe to MBO for the whole book".to_string()),
+            DataType::MBPDelta => return Err("Level changes are not a view; subscribe to MBP for the levels".to_string()),
+            _ => {}
+        }
+
+        // Option contracts change with their underlying's book
+        let book = self.option_contracts.get(symbol).map_or_else(|| symbol.to_string(), |underlying| underlying.clone());
+        if !self.order_books.contains_key(&book) {
+            return Err(format!("Unknown symbol {}", symbol));
+        }
+
+        // Subscribed before the first view is taken, so no change goes unnoticed
+        let events = self.subscribe_events();
+        let initial = self.get_order_book_snapshot(symbol, data_type.clone(), depth).await;
+
+        let state = (Arc::clone(self), events, Arc::<str>::from(book), symbol.to_string(), data_type);
+        let updates = stream::unfold(state, move |(stream_manager, mut events, book, symbol, data_type)| async move {
+            loop {
+                match next_event(&mut events, "Local subscriber").await? {
+                    MarketEvent::BookUpdated { symbol: updated, .. } if updated == book => {
+                        if let Some(update) = stream_manager.get_order_book_snapshot(&symbol, data_type.clone(), depth).await {
+                            return Some((update, (stream_manager, events, book, symbol, data_type)));
+                        }
+                    }
+                    MarketEvent::BookRemoved { symbol: removed } if removed == book => return None,
+                    _ => {}
+                }
+            }
+        });
+
+        Ok(stream::iter(initial).chain(updates))
+    }
+
+    pub async fn get_symbols(&self) -> Vec<String> {
+        self.order_books
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|symbol| !is_sandbox(symbol))
+            .chain(self.pairs.iter().map(|entry| entry.key().clone()))
+            .chain(self.option_contracts.iter().map(|entry| entry.key().clone()))
+            .collect()
+    }
+
+    // Books, synthetic pairs and option contracts matching the query; sandboxes are private
+    // and listed only to their owner
+    pub fn search_symbols(&self, query: &SymbolQuery) -> Vec<SymbolInfo> {
+        let info = |symbol: &str, symbol_type, status, (base, quote), tick_size, quantity_decimals| SymbolInfo {
+            symbol: symbol.to_string(),
+            symbol_type,
+            status,
+            venue: self.venue.clone(),
+            base,
+            quote,
+            tick_size,
+            quantity_decimals,
+        };
+        let decimals = |symbol: &str| self.quantity_decimals.get(symbol).copied();
+
+        // Collected first so no other map is read while the books are iterated
+        let book_symbols: Vec<String> = self.order_books
+            .iter()
+            .map(|entry| entry.key().clone())
+            .filter(|symbol| !is_sandbox(symbol))
+            .collect();
+        let future_roots: HashMap<String, String> = self.futures
+            .iter()
+            .flat_map(|curve| curve.contracts().iter().map(|contract| (contract.symbol.clone(), contract.root.clone())).collect::<Vec<_>>())
+            .collect();
+
+        let books = book_symbols.iter().map(|symbol| {
+            let tick_size = (!self.external_symbols.contains(symbol)).then_some(SIMULATED_TICK_SIZE);
+            let status = if self.halted_symbols.contains(symbol) { SymbolStatus::Halted } else { SymbolStatus::Open };
+
+            match future_roots.get(symbol) {
+                Some(root) => {
+                    let quantity_decimals = decimals(symbol).or_else(|| decimals(root)).unwrap_or(0);
+                    info(symbol, SymbolType::Future, status, split_currency_pair(root), tick_size, Some(quantity_decimals))
+                }
+                None => {
+                    let symbol_type = if self.perpetuals.contains_key(symbol) { SymbolType::Perpetual } else { SymbolType::Spot };
+                    let quantity_decimals = decimals(symbol).unwrap_or(0);
+                    info(symbol, symbol_type, status, split_currency_pair(symbol), tick_size, Some(quantity_decimals))
+                }
+            }
+        });
+        let pairs = self.pairs.iter().map(|pair| {
+            let legs = (Some(pair.base.clone()), Some(pair.quote.clone()));
+            info(pair.key(), SymbolType::Pair, SymbolStatus::Open, legs, None, None)
+        });
+        // Options trade in whole contracts
+        let options = self.option_contracts.iter().map(|entry| {
+            info(entry.key(), SymbolType::Option, SymbolStatus::Open, split_currency_pair(entry.value()), Some(SIMULATED_TICK_SIZE), Some(0))
+        });
+
+        query.apply(books.chain(pairs).chain(options).collect())
+    }
+
+    pub async fn get_order_book_snapshot(&self, symbol: &str, data_type: DataType, max_levels: u32) -> Option<MarketDataUpdate> {
+        if let DataType::OptionQuote = data_type {
+            let quote = self.get_option_quote(symbol)?;
+            return Some(MarketDataUpdate::OptionQuote { quote });
+        }
+
+        if let Some(order_book_ref) = self.order_books.get(symbol) {
+            let order_book = order_book_ref.snapshot();
+
+            let market_data = match data_type {
+                DataType::MBO => {
+                    let (bids, asks) = order_book.get_mbo_data(max_levels, default_max_orders(max_levels));
+                    MarketDataUpdate::MBO { bids, asks }
+                }
+                DataType::MBP => {
+                    let (bids, asks) = order_book.get_mbp_data(max_levels);
+                    MarketDataUpdate::MBP { bids, asks }
+                }
+                DataType::AggressorFlow => flow_update(&self.flows, symbol),
+                DataType::Summary | DataType::Reference => return None,
+                DataType::MarkPrice | DataType::Funding => return perpetual_update(&self.perpetuals, symbol, &data_type),
+                DataType::OptionQuote
+                | DataType::OrderActivity { .. }
+                | DataType::Quarantine
+                | DataType::Trades
+                | DataType::MBODelta
+                | DataType::MBPDelta => return None,
+                DataType::OptionChain => return self.option_chains.get(symbol).map(|chain| option_chain_update(&chain)),
+                DataType::Indicators { spec } => indicator_update(&self.candles, symbol, &spec),
+                DataType::Ladder { rows, tick_group } => {
+                    validate_ladder(rows, tick_group).ok()?;
+                    ladder_update(&order_book, rows, tick_group)
+                }
+                DataType::LiquidityAge => liquidity_age_update(&order_book, max_levels),
+                DataType::OrderStats => order_stats_update(&self.order_stats, symbol),
+            };
+
+            Some(market_data)
+        } else {
+            None
+        }
+    }
+
+    // Differences between a client's view of a book and the book itself; None for symbols
+    // without one, such as synthetic pairs
+    pub fn reconcile(&self, symbol: &str, request: &ReconcileRequest) -> Option<ReconcileReport> {
+        let order_book_ref = self.order_books.get(symbol).map(|entry| entry.value().clone())?;
+        Some(reconcile(&order_book_ref.snapshot(), request))
+    }
+
+    // Books whose top the client's heartbeats carry from now on, replacing those set before.
+    // Symbols without a book are left out of the heartbeats until they have one.
+    pub fn set_heartbeat_quotes(&self, client_id: Uuid, api_key: Option<&str>, symbols: Vec<String>) -> Result<Vec<String>, String> {
+        let mut seen = HashSet::new();
+        let symbols: Vec<String> = symbols.into_iter().filter(|symbol| seen.insert(symbol.clone())).collect();
+        if symbols.len() > self.max_depth_symbols {
+            return Err(format!("At most {} symbols per connection, got {}", self.max_depth_symbols, symbols.len()));
+        }
+        for symbol in symbols.iter().filter(|symbol| is_sandbox(symbol)) {
+            self.check_sandbox_access(api_key, symbol)?;
+        }
+
+        if symbols.is_empty() {
+            self.heartbeat_quotes.remove(&client_id);
+        } else {
+            self.heartbeat_quotes.insert(client_id, symbols.clone());
+        }
+        Ok(symbols)
+    }
+
+    pub fn heartbeat(&self, client_id: &Uuid) -> ServerMessage {
+        client_heartbeat(&self.order_books, &self.heartbeat_quotes, client_id, Utc::now(), &mut HashMap::new())
+    }
+
+    // Top `max_levels` price levels of each symbol's book, in the order asked for and each
+    // symbol once. Symbols without a book of their own, such as synthetic pairs, unknown
+    // names and sandboxes of other keys, are listed as missing; no book is created.
+    pub fn bulk_snapshot(&self, api_key: Option<&str>, symbols: &[String], max_levels: u32) -> Result<BulkSnapshot, String> {
+        validate_levels(max_levels)?;
+        let mut seen = HashSet::new();
+        let symbols: Vec<&String> = symbols.iter().filter(|symbol| seen.insert(symbol.as_str())).collect();
+        if symbols.is_empty() {
+            return Err("No symbols given".to_string());
+        }
+        if symbols.len() > self.max_depth_symbols {
+            return Err(format!("At most {} symbols per request, got {}", self.max_depth_symbols, symbols.len()));
+        }
+
+        let mut bulk = BulkSnapshot { snapshots: Vec::with_capacity(symbols.len()), missing: Vec::new() };
+        for symbol in symbols {
+            match self.visible_book(api_key, symbol) {
+                Some(order_book) => bulk.snapshots.push(book_snapshot(symbol, &order_book, max_levels)),
+                None => bulk.missing.push(symbol.clone()),
+            }
+        }
+
+        Ok(bulk)
+    }
+
+    // The book of one symbol for pollers of GET /depth/SYMBOL, or only its version while
+    // `unchanged(epoch, sequence)` holds for it, so an unchanged book costs no levels.
+    // None for the symbols bulk_snapshot lists as missing.
+    pub fn poll_depth(
+        &self,
+        api_key: Option<&str>,
+        symbol: &str,
+        max_levels: u32,
+        unchanged: impl Fn(u64, u64) -> bool,
+    ) -> Result<Option<DepthPoll>, String> {
+        validate_levels(max_levels)?;
+        let Some(order_book) = self.visible_book(api_key, symbol) else {
+            return Ok(None);
+        };
+        let (epoch, sequence) = (order_book.get_epoch(), order_book.get_sequence());
+        if unchanged(epoch, sequence) {
+            return Ok(Some(DepthPoll::Unchanged { epoch, sequence }));
+        }
+        Ok(Some(DepthPoll::Changed(Box::new(book_snapshot(symbol, &order_book, max_levels)))))
+    }
+
+    fn visible_book(&self, api_key: Option<&str>, symbol: &str) -> Option<Arc<OrderBook>> {
+        if is_sandbox(symbol) && self.check_sandbox_access(api_key, symbol).is_err() {
+            return None;
+        }
+        self.order_books.get(symbol).map(|entry| entry.value().snapshot())
+    }
+
+    pub async fn get_market_summary(&self) -> MarketSummary {
+        if let Some(summary) = self.latest_summary.read().await.clone() {
+            return summary;
+        }
+
+        compute_market_summary(&self.order_books)
+    }
+
+    // A message written to the client, counted against the statistics of its stream
+    pub fn record_delivery(&self, client_id: &Uuid, message: &OutboundMessage, bytes: usize) {
+        if let Some(stream_id) = message.stream_id() {
+            self.stream_stats.record_sent(client_id, stream_id, message.is_stream_update(), message.sequence());
+        }
+        if let Some(symbol) = message.symbol() {
+            self.popularity.record(symbol, bytes);
+        }
+    }
+
+    // Trade streams carry every print and each MBODelta or MBPDelta applies to the book the
+    // one before left, so message filters and quota throttling leave them alone
+    pub fn carries_every_update(&self, client_id: &Uuid, stream_id: &str) -> bool {
+        self.subscriptions
+            .with_stream(&(*client_id, stream_id.to_string()), |subscription| matches!(subscription.data_type, DataType::Trades | DataType::MBODelta | DataType::MBPDelta))
+            .unwrap_or(false)
+    }
+
+    // An update quota throttling held back
+    pub fn record_throttled(&self, client_id: &Uuid, stream_id: &str) {
+        self.stream_stats.record_conflated(client_id, stream_id);
+    }
+
+    // Sends the current view of an MBP or MBO stream again, anchored, so the client can
+    // replace a book that failed an anchor check
+    pub fn resync_stream(&self, client_id: Uuid, stream_id: &str) -> Result<(), String> {
+        let key = (client_id, stream_id.to_string());
+        let Some((symbol, group)) = self.subscriptions.stream(&key) else {
+            return Err(format!("No stream {}", stream_id));
+        };
+        let Some(key) = group.filter(|key| matches!(key, SharedPayload::ByOrder(..) | SharedPayload::ByPrice(..))) else {
+            return Err(format!("Stream {} is not an MBP or MBO stream", stream_id));
+        };
+        let order_book_ref = self.order_books.get(&symbol).map(|entry| entry.value().clone()).ok_or_else(|| format!("No book for {}", symbol))?;
+        let client_sender = self.clients.get(&client_id).ok_or_else(|| "Client is gone".to_string())?;
+
+        let order_book = order_book_ref.snapshot();
+        let sequence = order_book.get_sequence();
+        let data = match self.snapshots.get(&symbol, &key, sequence) {
+            Some(data) => data,
+            None => build_shared_payload(&order_book, &self.flows, &symbol, key).ok_or_else(|| "Failed to build the view".to_string())?,
+        };
+        let message = OutboundMessage::SharedMarketData {
+            stream_id: stream_id.to_string(),
+            symbol: symbol.as_str().into(),
+            data,
+            sequence,
+            epoch: order_book.get_epoch(),
+            timestamp: Utc::now(),
+            event_ts: order_book.get_event_timestamp(),
+            anchor: view_anchor(&order_book, key),
+            venue_sequence: order_book.venue_sequence(),
+        };
+        client_sender.send(message).map_err(|_| "Client is gone".to_string())?;
+
+        debug!("Resynced stream {} of client {} at sequence {}", stream_id, client_id, sequence);
+        Ok(())
+    }
+
+    pub fn stream_stats(&self, client_id: &Uuid, stream_id: &str) -> Option<StreamStats> {
+        self.stream_stats.get(client_id, stream_id)
+    }
+
+    pub fn record_usage(&self, client_id: &Uuid, stream_id: Option<&str>, bytes: usize) {
+        self.usage.record(client_id, stream_id, bytes);
+        if let Some(quotas) = &self.quotas {
+            quotas.record(client_id, bytes);
+        }
+        self.metrics.record_sent(bytes);
+    }
+
+    // A message compressed for a deflate connection
+    pub fn record_compression(&self, client_id: &Uuid, compressed: &Compressed) {
+        self.usage.record_compression(client_id, compressed);
+        self.metrics.record_compression(compressed);
+    }
+
+    // A deflate connection that went back to text frames, or back to compressing
+    pub fn record_compression_switch(&self, client_id: &Uuid, switch: Switch) {
+        self.usage.record_compression_switch(client_id, switch);
+        self.metrics.record_compression_switch(switch);
+    }
+
+    // Bills the client's deliveries to an API key,
+    // and gives it access to the key's sandboxes
+    pub fn bind_api_key(&self, client_id: Uuid, api_key: &str) {
+        self.client_keys.insert(client_id, api_key.to_string());
+        if let Some(quotas) = &self.quotas {
+            quotas.bind(client_id, api_key);
+        }
+    }
+
+    pub fn unbind_api_key(&self, client_id: &Uuid) {
+        self.client_keys.remove(client_id);
+        if let Some(quotas) = &self.quotas {
+            quotas.unbind(client_id);
+        }
+    }
+
+    pub fn check_quota(&self, client_id: &Uuid, stream_id: &str) -> QuotaDecision {
+        self.quotas
+            .as_ref()
+            .map_or(QuotaDecision::Deliver, |quotas| quotas.check(client_id, stream_id))
+    }
+
+    // Cuts a client off from its streams once a quota is used up, telling it which one
+    pub fn cut_off_quota(&self, client_id: &Uuid, quota: QuotaRemaining) {
+        let removed = self.subscriptions.remove_client(client_id)
+            + self.summary_subscriptions.remove(client_id).map_or(0, |(_, subscriptions)| subscriptions.len())
+            + self.reference_subscriptions.remove(client_id).map_or(0, |(_, subscriptions)| subscriptions.len());
+        if removed == 0 {
+            return;
+        }
+
+        info!("Client {} exceeded its {:?} {:?} quota, removed {} subscriptions", client_id, quota.period, quota.unit, removed);
+        if let Some(quotas) = &self.quotas {
+            quotas.report_exceeded(client_id, &quota);
+        }
+        if let Some(client_sender) = self.clients.get(client_id) {
+            let _ = client_sender.send(ServerMessage::QuotaExceeded { quota }.into());
+        }
+    }
+
+    pub fn quota_status(&self, api_key: &str) -> Option<QuotaStatus> {
+        self.quotas.as_ref().map(|quotas| quotas.status(api_key))
+    }
+
+    // Called once a live market data message is written to the client's socket
+    pub fn record_delivery_latency(&self, message: &OutboundMessage) {
+        if let Some(event_ts) = message.event_ts() {
+            self.metrics.observe_delivery_latency((clock().now() - event_ts).to_std().unwrap_or_default());
+        }
+    }
+
+    pub fn record_queue_depth(&self, depth: usize) {
+        self.metrics.observe_queue_depth(depth);
+        if let Some(load_shedder) = &self.load_shedder {
+            load_shedder.observe_queue_depth(depth);
+        }
+    }
+
+    // Keeps what a connection could not be sent, when dead letters are enabled
+    pub fn capture_dead_letters(&self, client_id: &Uuid, reason: DeadLetterReason, undelivered: Vec<OutboundMessage>) {
+        let Some(dead_letters) = &self.dead_letters else {
+            return;
+        };
+        let api_key = self.client_keys.get(client_id).map(|api_key| api_key.clone());
+        dead_letters.capture(*client_id, api_key, reason, undelivered);
+    }
+
+    // None unless dead letters are enabled
+    pub fn get_dead_letters(&self, client_id: Option<&str>, limit: usize) -> Option<Vec<DeadLetterBatch>> {
+        self.dead_letters.as_ref().map(|dead_letters| dead_letters.recent(client_id, limit))
+    }
+
+    // Whether a client with this many messages queued has fallen too far behind
+    pub fn is_slow_consumer(&self, queued: usize) -> bool {
+        self.limits.max_client_queue.is_some_and(|limit| queued > limit)
+    }
+
+    // Jumps any queued market data; the connection closes once it is sent. False if the
+    // client is not connected.
+    pub fn disconnect_client(&self, client_id: &Uuid, reason: DisconnectReason) -> bool {
+        let Some(client_sender) = self.clients.get(client_id) else {
+            return false;
+        };
+        info!("Disconnecting client {}: {}", client_id, reason);
+        client_sender.send(OutboundMessage::Disconnect(reason)).is_ok()
+    }
+
+    // Returns how many clients were told to disconnect
+    pub fn disconnect_all(&self, reason: DisconnectReason) -> usize {
+        self.clients.iter().filter(|client_sender| client_sender.send(OutboundMessage::Disconnect(reason)).is_ok()).count()
+    }
+
+    // Whether `symbol` has a book that has changed at least once: simulated books from
+    // their sample orders, external ones from their first ingested event
+    pub fn has_book_data(&self, symbol: &str) -> bool {
+        self.order_books.get(symbol).is_some_and(|order_book_ref| order_book_ref.snapshot().get_sequence() > 0)
+    }
+
+    pub fn connected_clients(&self) -> usize {
+        self.clients.len()
+    }
+
+    // The notice owed to a low-priority stream subscribed while it is being conflated
+    pub fn throttle_notice(&self, stream_id: &str, priority: Option<StreamPriority>) -> Option<ServerMessage> {
+        if priority != Some(StreamPriority::Low) {
+            return None;
+        }
+        let conflation = self.load_shedder.as_ref()?.interval()?;
+        Some(throttled_notice(vec![stream_id.to_string()], Some(conflation)))
+    }
+
+    pub fn task_health(&self) -> Vec<TaskHealth> {
+        self.supervisor.tasks()
+    }
+
+    pub fn render_metrics(&self) -> String {
+        let subscription_counts = self.subscriptions.counts();
+
+        self.metrics.set_connected_clients(self.clients.len());
+        self.metrics.set_subscriptions(
+            subscription_counts.iter().map(|(symbol, count)| (symbol.as_str(), *count)),
+        );
+        self.metrics.render()
+    }
+
+    // None for unknown symbols and for sandboxes the key does not own
+    pub fn get_order_stats(&self, api_key: Option<&str>, symbol: &str) -> Option<OrderLifetimeStats> {
+        if !self.order_books.contains_key(symbol) || is_sandbox(symbol) && self.check_sandbox_access(api_key, symbol).is_err() {
+            return None;
+        }
+        Some(lifetime_stats(&self.order_stats, symbol))
+    }
+
+    pub fn get_mark_price(&self, symbol: &str) -> Option<MarkPrice> {
+        self.perpetuals.get(symbol)?.mark_price().cloned()
+    }
+
+    // None when the symbol is not a perpetual
+    // Stored candles of a symbol at one of the --candle-retention intervals
+    pub fn get_candles(
+        &self,
+        symbol: &str,
+        interval_secs: Option<u64>,
+        from: Option<DateTime<Utc>>,
+        to: Option<DateTime<Utc>>,
+        limit: usize,
+        fill: Option<CandleGapFill>,
+    ) -> Option<Vec<Candle>> {
+        let (store, _) = self.candle_store.as_ref()?;
+        let interval_secs = interval_secs.or_else(|| store.intervals().next())?;
+        store.candles(symbol, interval_secs, from, to, limit, fill, Utc::now())
+    }
+
+    // Candles of a CandleBackfill with their interval; a symbol without candles yet has none
+    pub fn candle_backfill(&self, symbol: &str, interval: &str, from: DateTime<Utc>, to: DateTime<Utc>) -> Result<(u64, Vec<Candle>), String> {
+        let (store, _) = self.candle_store.as_ref().ok_or("the candle store is not enabled")?;
+        validate_symbol(symbol)?;
+        let interval_secs = parse_period(interval).ok_or_else(|| format!("invalid interval {}", interval))?;
+        if !store.intervals().any(|kept| kept == interval_secs) {
+            return Err(format!("{} candles are not kept", interval));
+        }
+        if from > to {
+            return Err("from is after to".to_string());
+        }
+
+        let candles = store
+            .candles(symbol, interval_secs, Some(from), Some(to), MAX_CANDLES_PER_QUERY, None, Utc::now())
+            .unwrap_or_default();
+        Ok((interval_secs, candles))
+    }
+
+    pub fn get_funding_history(&self, symbol: &str, limit: usize) -> Option<Vec<FundingRate>> {
+        Some(self.perpetuals.get(symbol)?.funding_history(limit))
+    }
+
+    // None when the symbol has no options chain
+    pub fn get_option_chain(&self, underlying: &str) -> Option<Vec<OptionQuote>> {
+        Some(self.option_chains.get(underlying)?.quotes().to_vec())
+    }
+
+    pub fn get_option_quote(&self, contract: &str) -> Option<OptionQuote> {
+        let underlying = self.option_contracts.get(contract)?;
+        self.option_chains.get(underlying.as_str())?.quote(contract).cloned()
+    }
+
+    // Contracts of a futures root by expiry, with the current mid of those still listed;
+    // None when no futures have that root
+    pub fn get_futures_curve(&self, root: &str) -> Option<Vec<FutureContract>> {
+        let curve = self.futures.get(root)?;
+
+        Some(curve.contracts().iter().map(|contract| self.with_mid_price(contract.clone())).collect())
+    }
+
+    pub fn get_future(&self, symbol: &str) -> Option<FutureContract> {
+        let contract = self.futures.iter().find_map(|curve| curve.contract(symbol).cloned())?;
+        Some(self.with_mid_price(contract))
+    }
+
+    fn with_mid_price(&self, mut contract: FutureContract) -> FutureContract {
+        contract.mid_price = self
+            .order_books
+            .get(&contract.symbol)
+            .and_then(|order_book_ref| order_book_ref.snapshot().get_spread_info().1);
+        contract
+    }
+
+    pub fn get_usage_snapshot(&self) -> UsageSnapshot {
+        self.usage.snapshot()
+    }
+
+    pub fn get_popularity(&self, limit: usize) -> Vec<SymbolPopularityStats> {
+        self.popularity.snapshot(limit)
+    }
+
+    pub fn get_client_sender(&self, client_id: &Uuid) -> Option<dashmap::mapref::one::Ref<'_, Uuid, ClientSender>> {
+        self.clients.get(client_id)
+    }
+}
+
+// Sends a paper order report to the embedded account or client owning the order
+fn deliver_paper_report(paper_desk: &PaperDesk, clients: &DashMap<Uuid, ClientSender>, owner: Uuid, report: ServerMessage) {
+    if let Some(account) = paper_desk.account(&owner) {
+        if account.send(report).is_err() {
+            paper_desk.forget_owner(&owner);
+        }
+    } else if let Some(client_sender) = clients.get(&owner) {
+        if client_sender.send(report.into()).is_err() {
+            debug!("Client {} disconnected during paper order report", owner);
+        }
+    }
+}
+
+fn validate_sandbox_price(price: f64) -> Result<(), SandboxError> {
+    if !(price.is_finite() && price >= 1.0) {
+        return Err(SandboxError::Invalid("price must be at least 1".to_string()));
+    }
+    Ok(())
+}
+
+fn compute_market_summary(
+    order_books: &DashMap<String, Arc<PublishedBook>>,
+) -> MarketSummary {
+    let symbols = order_books
+        .iter()
+        .filter(|entry| !is_sandbox(entry.key()))
+        .map(|entry| entry.value().snapshot().get_symbol_summary())
+        .collect();
+
+    build_market_summary(symbols, SUMMARY_TOP_N)
+}
+
+// Creates a future's book seeded around its opening price
+fn list_future(
+    order_books: &DashMap<String, Arc<PublishedBook>>,
+    limits: &ResourceLimits,
+    quantity_decimals: &HashMap<String, u32>,
+    actor_tags: bool,
+    epochs: &BookEpochs,
+    contract: &FutureContract,
+    opening_price: f64,
+) {
+    let symbol = &contract.symbol;
+    let mut order_book = OrderBook::new(symbol.to_string());
+    if let Some(limit) = limits.max_orders_per_book {
+        order_book.set_order_limit(limit);
+    }
+    order_book.set_quantity_decimals(future_quantity_decimals(quantity_decimals, contract));
+    order_book.set_actor_tags(actor_tags);
+    order_book.set_epoch(epochs.next(symbol));
+    order_book.initialize_with_sample_data_around(opening_price);
+
+    order_books.insert(symbol.to_string(), Arc::new(PublishedBook::new(order_book)));
+    info!("Initialized future order book for symbol: {} around {}", symbol, opening_price);
+}
+
+// Set for the contract itself, or for its root so rolled contracts inherit it
+fn future_quantity_decimals(quantity_decimals: &HashMap<String, u32>, contract: &FutureContract) -> u32 {
+    quantity_decimals
+        .get(&contract.symbol)
+        .or_else(|| quantity_decimals.get(&contract.root))
+        .copied()
+        .unwrap_or(0)
+}
+
+// The contract itself and every synthetic pair it is a leg of
+fn instrument_symbols(pairs: &DashMap<String, SyntheticPair>, symbol: &str) -> Vec<String> {
+    std::iter::once(symbol.to_string())
+        .chain(
+            pairs
+                .iter()
+                .filter(|pair| pair.base == symbol || pair.quote == symbol)
+                .map(|pair| pair.key().clone()),
+        )
+        .collect()
+}
+
+// Publishes the contract's new status for the contract and every pair it is a leg of
+fn publish_status(events: &EventBus, pairs: &DashMap<String, SyntheticPair>, contract: &FutureContract) {
+    for symbol in instrument_symbols(pairs, &contract.symbol) {
+        events.publish(MarketEvent::Status { symbol: Arc::from(symbol.as_str()), contract: contract.clone() });
+    }
+}
+
+// Tells every stream on the symbol about the contract's new status, removing the
+// streams when it has been delisted. Only the contract's own streams carry its settlement.
+fn notify_instrument_status(
+    subscriptions: &SubscriptionIndex<Option<SharedPayload>, Subscription>,
+    clients: &DashMap<Uuid, ClientSender>,
+    symbol: &str,
+    contract: &FutureContract,
+    remove: bool,
+) {
+    let settlement_price = contract.settlement_price.filter(|_| symbol == contract.symbol);
+
+    let streams: Vec<(Uuid, String)> = match subscriptions.symbol(symbol) {
+        Some(symbol_subscriptions) => symbol_subscriptions.values().flat_map(|group| group.keys().cloned()).collect(),
+        None => return,
+    };
+
+    for (client_id, stream_id) in streams {
+        if remove {
+            subscriptions.remove(&client_id, &stream_id);
+        }
+        if let Some(client_sender) = clients.get(&client_id) {
+            let message = ServerMessage::InstrumentStatus {
+                stream_id,
+                symbol: symbol.to_string(),
+                status: contract.status,
+                settlement_price,
+                timestamp: Utc::now(),
+            };
+
+            if client_sender.send(message.into()).is_err() {
+                debug!("Client {} disconnected during instrument status send", client_id);
+            }
+        }
+    }
+}
+
+// A client's heartbeat with the top of the books it asked for. Each book is read once per
+// round of heartbeats, through `tops`.
+fn client_heartbeat(
+    order_books: &DashMap<String, Arc<PublishedBook>>,
+    heartbeat_quotes: &DashMap<Uuid, Vec<String>>,
+    client_id: &Uuid,
+    timestamp: DateTime<Utc>,
+    tops: &mut HashMap<String, Option<TopOfBook>>,
+) -> ServerMessage {
+    let quotes = match heartbeat_quotes.get(client_id) {
+        Some(symbols) => symbols
+            .iter()
+            .filter_map(|symbol| {
+                tops.entry(symbol.clone())
+                    .or_insert_with(|| top_of_book(order_books, symbol))
+                    .clone()
+            })
+            .collect(),
+        None => Vec::new(),
+    };
+    ServerMessage::HeartBeat { timestamp, quotes }
+}
+
+fn top_of_book(order_books: &DashMap<String, Arc<PublishedBook>>, symbol: &str) -> Option<TopOfBook> {
+    let order_book = order_books.get(symbol)?.snapshot();
+    let (bids, asks) = order_book.get_mbp_data(1);
+    let (bid, ask) = (bids.first(), asks.first());
+
+    Some(TopOfBook {
+        symbol: symbol.to_string(),
+        bid: bid.map(|level| level.price),
+        bid_quantity: bid.map(|level| level.quantity),
+        ask: ask.map(|level| level.price),
+        ask_quantity: ask.map(|level| level.quantity),
+        last_trade: order_book.last_trade().cloned(),
+    })
+}
+
+fn book_snapshot(symbol: &str, order_book: &OrderBook, max_levels: u32) -> BookSnapshot {
+    let (bids, asks) = order_book.get_mbp_data(max_levels);
+    BookSnapshot {
+        symbol: symbol.to_string(),
+        data: MarketDataUpdate::MBP { bids, asks },
+        sequence: order_book.get_sequence(),
+        epoch: order_book.get_epoch(),
+        event_ts: order_book.get_event_timestamp(),
+    }
+}
+
+// Events of one symbol handed to its fan-out pipeline, in bus order
+enum PipelineEvent {
+    Activity(OrderActivity),
+    Trade(Trade),
+    Quarantined(OutOfBandEvent),
+    Updated { sequence: u64, previous_sequence: Option<u64>, event_ts: DateTime<Utc>, mid_price: Option<f64> },
+    // Events of the symbol may have been lost on the way
+    Resync,
+}
+
+// What every symbol's fan-out pipeline reads and sends to
+struct FanOut {
+    order_books: Arc<DashMap<String, Arc<PublishedBook>>>,
+    subscriptions: Arc<SubscriptionIndex<Option<SharedPayload>, Subscription>>,
+    snapshots: Arc<SnapshotCache<SharedPayload, Bytes>>,
+    clients: Arc<DashMap<Uuid, ClientSender>>,
+    candles: Arc<DashMap<String, CandleAggregator>>,
+    flows: Arc<DashMap<String, AggressorFlow>>,
+    order_stats: Arc<DashMap<String, OrderLifetimes>>,
+    metrics: Arc<Metrics>,
+    load_shedder: Option<Arc<LoadShedder>>,
+    stream_stats: Arc<StreamStatsTracker>,
+    anchor_interval: Option<u64>,
+    timeout: Option<Duration>,
+}
+
+// A symbol's fan-out task and the queue of events it works through
+struct SymbolPipeline {
+    events: mpsc::UnboundedSender<PipelineEvent>,
+    task: JoinHandle<()>,
+}
+
+// Queues the event for the symbol's pipeline, starting one for a symbol without, or in
+// place of one that panicked
+fn dispatch(pipelines: &mut HashMap<Arc<str>, SymbolPipeline>, fanout: &Arc<FanOut>, symbol: Arc<str>, event: PipelineEvent) {
+    let event = match pipelines.get_mut(&symbol) {
+        Some(pipeline) => match pipeline.events.send(event) {
+            Ok(()) => return,
+            // Its receiver only goes away with the task
+            Err(mpsc::error::SendError(event)) => {
+                let panic = match (&mut pipeline.task).now_or_never() {
+                    Some(Err(e)) if e.is_panic() => panic_message(e.into_panic()),
+                    _ => "task ended".to_string(),
+                };
+                error!("Fan-out pipeline of {} panicked: {}; restarting it", symbol, panic);
+                fanout.metrics.record_pipeline_restart(&symbol);
+                event
+            }
+        },
+        None => event,
+    };
+
+    let (events, receiver) = mpsc::unbounded_channel();
+    let _ = events.send(event);
+    let task = tokio::spawn(run_symbol_pipeline(Arc::clone(fanout), Arc::clone(&symbol), receiver));
+    pipelines.insert(symbol, SymbolPipeline { events, task });
+}
+
+// Sends a symbol's order events and updates to its subscribers. A pipeline that falls
+// behind skips the fan-out of updates a later one of its queue supersedes.
+async fn run_symbol_pipeline(fanout: Arc<FanOut>, symbol: Arc<str>, mut events: mpsc::UnboundedReceiver<PipelineEvent>) {
+    // Multiple of the anchor interval the book's sequence last reached
+    let mut anchored = None;
+    // Order events for activity streams, held until the update they belong to
+    let mut pending_activity: Vec<(OrderActivity, Option<ActivityOrder>)> = Vec::new();
+    // Trades for trade streams, held likewise
+    let mut pending_trades: Vec<Trade> = Vec::new();
+    // Order changes for MBODelta streams, kept with or without such streams so one opened
+    // partway through an update still gets all of the update's changes
+    let mut pending_changes: Vec<OrderChange> = Vec::new();
+    // Views last sent to MBPDelta streams, by depth
+    let mut level_views: HashMap<u32, LevelView> = HashMap::new();
+    let mut activity_orders = ActivityOrders::default();
+    // Whether order changes may have been lost, as by a pipeline this one replaces, so the
+    // next update sends MBODelta streams the whole book
+    let mut resync = true;
+    // Subscriber the next fan-out starts at after one ran out of time
+    let mut resume = 0;
+    let mut timing_out = false;
+
+    while let Some(event) = events.recv().await {
+        let (sequence, previous_sequence, event_ts, mid_price) = match event {
+            PipelineEvent::Activity(activity) => {
+                pending_changes.extend(OrderChange::from_activity(&activity));
+                let order = activity_orders.apply(&activity);
+                if has_streams(&fanout.subscriptions, &symbol, |data_type| matches!(data_type, DataType::OrderActivity { .. })) {
+                    pending_activity.push((activity, order));
+                }
+                continue;
+            }
+            PipelineEvent::Trade(trade) => {
+                if has_streams(&fanout.subscriptions, &symbol, |data_type| matches!(data_type, DataType::Trades)) {
+                    pending_trades.push(trade);
+                }
+                continue;
+            }
+            PipelineEvent::Quarantined(event) => {
+                if let Some(order_book_ref) = fanout.order_books.get(symbol.as_ref()).map(|entry| entry.value().clone()) {
+                    send_quarantined(&fanout.subscriptions, &fanout.clients, &symbol, event, &order_book_ref.snapshot());
+                }
+                continue;
+            }
+            PipelineEvent::Resync => {
+                pending_changes.clear();
+                resync = true;
+                continue;
+            }
+            PipelineEvent::Updated { sequence, previous_sequence, event_ts, mid_price } => (sequence, previous_sequence, event_ts, mid_price),
+        };
+
+        let Some(order_book_ref) = fanout.order_books.get(symbol.as_ref()).map(|entry| entry.value().clone()) else {
+            pending_changes.clear();
+            continue;
+        };
+        let order_book = order_book_ref.snapshot();
+        activity_orders.prune(&order_book);
+        let previous_sequence = previous_sequence.filter(|_| !mem::take(&mut resync));
+        if has_streams(&fanout.subscriptions, &symbol, |data_type| matches!(data_type, DataType::MBODelta)) {
+            let changes = mem::take(&mut pending_changes);
+            send_order_changes(&fanout.subscriptions, &fanout.clients, &symbol, &order_book, changes, previous_sequence, sequence, event_ts);
+        } else {
+            pending_changes.clear();
+        }
+        if !pending_trades.is_empty() {
+            let trades = mem::take(&mut pending_trades);
+            send_trades(&fanout.subscriptions, &fanout.clients, &symbol, trades, sequence, order_book.get_epoch(), event_ts);
+        }
+        if !pending_activity.is_empty() {
+            let events = mem::take(&mut pending_activity);
+            send_activity(&fanout.subscriptions, &fanout.clients, &symbol, events, mid_price, sequence, order_book.get_epoch(), event_ts);
+        }
+        // A later update of the book is already queued
+        if order_book.get_sequence() != sequence {
+            continue;
+        }
+        send_level_changes(&fanout.subscriptions, &fanout.clients, &symbol, &order_book, &mut level_views);
+
+        let fanout_started = Instant::now();
+        #[cfg(feature = "alloc-audit")]
+        let fanout_allocations = AllocationScope::start();
+        let anchor_due = fanout.anchor_interval.is_some_and(|interval| {
+            let reached = sequence / interval;
+            anchored.replace(reached) != Some(reached)
+        });
+        let completed = fanout.publish(&symbol, &order_book, sequence, anchor_due, fanout_started, &mut resume);
+
+        let elapsed = fanout_started.elapsed();
+        fanout.metrics.observe_fanout(elapsed);
+        fanout.metrics.observe_symbol_fanout(&symbol, elapsed, !completed);
+        #[cfg(feature = "alloc-audit")]
+        fanout.metrics.observe_fanout_allocations(fanout_allocations.allocations());
+        if let Some(load_shedder) = &fanout.load_shedder {
+            load_shedder.observe_fanout(elapsed);
+        }
+        if !completed && !timing_out {
+            warn!("Fan-out of {} is running out of time; subscribers are taking turns at its updates", symbol);
+        } else if completed && timing_out {
+            info!("Fan-out of {} completes in time again", symbol);
+        }
+        timing_out = !completed;
+    }
+}
+
+impl FanOut {
+    // Sends one update to the symbol's subscribers, starting at `resume`. Past the timeout
+    // it stops, leaving `resume` at the first subscriber not sent the update, so the next
+    // update reaches those first; returns whether every subscriber was served.
+    fn publish(&self, symbol: &Arc<str>, order_book: &OrderBook, sequence: u64, anchor_due: bool, started: Instant, resume: &mut usize) -> bool {
+        let Some(symbol_subscriptions) = self.subscriptions.symbol(symbol) else {
+            return true;
+        };
+        let (epoch, event_ts) = (order_book.get_epoch(), order_book.get_event_timestamp());
+        let views = DeepestViews::new(order_book, symbol_subscriptions.keys().flatten());
+        let groups: Vec<_> = symbol_subscriptions.iter().collect();
+        let subscribers: Vec<(usize, &Subscription)> = groups
+            .iter()
+            .enumerate()
+            .flat_map(|(index, (_, group_subscriptions))| group_subscriptions.values().map(move |subscription| (index, subscription)))
+            .collect();
+        let mut group_views: Vec<Option<(Option<Bytes>, Option<BookAnchor>)>> = groups.iter().map(|_| None).collect();
+
+        let start = if *resume < subscribers.len() { *resume } else { 0 };
+        for offset in 0..subscribers.len() {
+            let position = (start + offset) % subscribers.len();
+            let checked = offset > 0 && offset % TIMEOUT_CHECK_INTERVAL == 0;
+            if checked && self.timeout.is_some_and(|timeout| started.elapsed() >= timeout) {
+                *resume = position;
+                return false;
+            }
+
+            let (index, subscription) = subscribers[position];
+            // Views shared by the whole group are built and serialized once, and kept for
+            // clients that subscribe before the next tick
+            let (data, anchor) = group_views[index].get_or_insert_with(|| {
+                let group = *groups[index].0;
+                let update = group.and_then(|key| views.view(key).or_else(|| shared_update(order_book, &self.flows, symbol, key)));
+                let data = group.zip(update.as_ref()).and_then(|(key, update)| {
+                    let data = serialize_payload(update)?;
+                    self.snapshots.insert(symbol, key, sequence, data.clone());
+                    Some(data)
+                });
+                let anchor = update
+                    .as_ref()
+                    .filter(|_| anchor_due)
+                    .and_then(|update| Some(BookAnchor { sequence, checksum: update.checksum()? }));
+                (data, anchor)
+            });
+
+            if subscription.sampler.as_ref().is_some_and(|sampler| !sampler.deliver(anchor.is_some())) {
+                continue;
+            }
+            if conflated(&self.load_shedder, &self.stream_stats, subscription, started) {
+                continue;
+            }
+            let Some(client_sender) = self.clients.get(&subscription.client_id) else {
+                continue;
+            };
+            let message = match (&*data, &subscription.data_type) {
+                (Some(data), _) => OutboundMessage::SharedMarketData {
+                    stream_id: subscription.stream_id.clone(),
+                    symbol: symbol.clone(),
+                    data: data.clone(),
+                    sequence,
+                    epoch,
+                    timestamp: Utc::now(),
+                    event_ts,
+                    anchor: *anchor,
+                    venue_sequence: order_book.venue_sequence(),
+                },
+                (None, DataType::Indicators { spec }) => {
+                    market_data(&subscription.stream_id, symbol, indicator_update(&self.candles, symbol, spec), sequence, epoch, event_ts)
+                }
+                (None, DataType::OrderStats) => {
+                    market_data(&subscription.stream_id, symbol, order_stats_update(&self.order_stats, symbol), sequence, epoch, event_ts)
+                }
+                (None, _) => continue,
+            };
+
+            if client_sender.send(message).is_err() {
+                debug!("Client {} disconnected during market data send", subscription.client_id);
+            }
+        }
+
+        *resume = 0;
+        true
+    }
+}
+
+// Counts the events books' price bands kept out and publishes those quarantined
+fn publish_out_of_band(events: &EventBus, metrics: &Metrics, out_of_band: Vec<OutOfBandEvent>) {
+    for event in out_of_band {
+        metrics.record_out_of_band(&event.symbol, event.action);
+        debug!("{:?} of {} at {} held back by its price band around {}", event.event, event.symbol, event.price, event.reference_price);
+        if event.action == BandAction::Quarantine {
+            events.publish(MarketEvent::Quarantined(event));
+        }
+    }
+}
+
+// Sends a quarantined event to the symbol's Quarantine streams as it is held back, with
+// the sequence of the book it was kept out of
+fn send_quarantined(
+    subscriptions: &SubscriptionIndex<Option<SharedPayload>, Subscription>,
+    clients: &DashMap<Uuid, ClientSender>,
+    symbol: &Arc<str>,
+    event: OutOfBandEvent,
+    order_book: &OrderBook,
+) {
+    let Some(symbol_subscriptions) = subscriptions.symbol(symbol) else {
+        return;
+    };
+    let Some(group) = symbol_subscriptions.get(&None) else {
+        return;
+    };
+    let mut streams = group.values().filter(|subscription| matches!(subscription.data_type, DataType::Quarantine)).peekable();
+    if streams.peek().is_none() {
+        return;
+    }
+    let Some(data) = serialize_payload(&MarketDataUpdate::Quarantine { event }) else {
+        return;
+    };
+
+    for subscription in streams {
+        let Some(client_sender) = clients.get(&subscription.client_id) else {
+            continue;
+        };
+        let message = OutboundMessage::SharedMarketData {
+            stream_id: subscription.stream_id.clone(),
+            symbol: Arc::clone(symbol),
+            data: data.clone(),
+            sequence: order_book.get_sequence(),
+            epoch: order_book.get_epoch(),
+            timestamp: Utc::now(),
+            event_ts: order_book.get_event_timestamp(),
+            anchor: None,
+            venue_sequence: None,
+        };
+
+        if client_sender.send(message).is_err() {
+            debug!("Client {} disconnected during quarantine send", subscription.client_id);
+        }
+    }
+}
+
+// Whether any stream of the symbol outside the shared views is of a wanted data type
+fn has_streams(
+    subscriptions: &SubscriptionIndex<Option<SharedPayload>, Subscription>,
+    symbol: &str,
+    wanted: impl Fn(&DataType) -> bool,
+) -> bool {
+    subscriptions.symbol(symbol).is_some_and(|symbol_subscriptions| {
+        symbol_subscriptions
+            .get(&None)
+            .is_some_and(|group| group.values().any(|subscription| wanted(&subscription.data_type)))
+    })
+}
+
+// Sends the trades of a book update to the symbol's trade streams, each serialized once
+// and all with the update's sequence
+fn send_trades(
+    subscriptions: &SubscriptionIndex<Option<SharedPayload>, Subscription>,
+    clients: &DashMap<Uuid, ClientSender>,
+    symbol: &Arc<str>,
+    trades: Vec<Trade>,
+    sequence: u64,
+    epoch: u64,
+    event_ts: DateTime<Utc>,
+) {
+    let Some(symbol_subscriptions) = subscriptions.symbol(symbol) else {
+        return;
+    };
+    let Some(group) = symbol_subscriptions.get(&None) else {
+        return;
+    };
+    let streams: Vec<&Subscription> = group.values().filter(|subscription| matches!(subscription.data_type, DataType::Trades)).collect();
+
+    for trade in trades {
+        let Some(data) = serialize_payload(&MarketDataUpdate::Trade { trade }) else {
+            continue;
+        };
+        for subscription in &streams {
+            let Some(client_sender) = clients.get(&subscription.client_id) else {
+                continue;
+            };
+            let message = OutboundMessage::SharedMarketData {
+                stream_id: subscription.stream_id.clone(),
+                symbol: Arc::clone(symbol),
+                data: data.clone(),
+                sequence,
+                epoch,
+                timestamp: Utc::now(),
+                event_ts,
+                anchor: None,
+                venue_sequence: None,
+            };
+
+            if client_sender.send(message).is_err() {
+                debug!("Client {} disconnected during trade send", subscription.client_id);
+            }
+        }
+    }
+}
+
+// Sends the order changes of a book update to the symbol's MBODelta streams with the
+// update's sequence, even when there are none so the streams' sequences stay unbroken.
+// A change the published activity does not describe, e.g. a repair, sends the whole book
+// instead, at its current sequence.
+#[allow(clippy::too_many_arguments)]
+fn send_order_changes(
+    subscriptions: &SubscriptionIndex<Option<SharedPayload>, Subscription>,
+    clients: &DashMap<Uuid, ClientSender>,
+    symbol: &Arc<str>,
+    order_book: &OrderBook,
+    changes: Vec<OrderChange>,
+    previous_sequence: Option<u64>,
+    sequence: u64,
+    event_ts: DateTime<Utc>,
+) {
+    let Some(symbol_subscriptions) = subscriptions.symbol(symbol) else {
+        return;
+    };
+    let Some(group) = symbol_subscriptions.get(&None) else {
+        return;
+    };
+    let (data, sequence, event_ts) = match previous_sequence {
+        Some(previous_sequence) => (MarketDataUpdate::MBODelta { previous_sequence, changes }, sequence, event_ts),
+        None => (full_book_update(order_book, true), order_book.get_sequence(), order_book.get_event_timestamp()),
+    };
+    let Some(data) = serialize_payload(&data) else {
+        return;
+    };
+
+    for subscription in group.values().filter(|subscription| matches!(subscription.data_type, DataType::MBODelta)) {
+        let Some(client_sender) = clients.get(&subscription.client_id) else {
+            continue;
+        };
+        let message = OutboundMessage::SharedMarketData {
+            stream_id: subscription.stream_id.clone(),
+            symbol: Arc::clone(symbol),
+            data: data.clone(),
+            sequence,
+            epoch: order_book.get_epoch(),
+            timestamp: Utc::now(),
+            event_ts,
+            anchor: None,
+            venue_sequence: None,
+        };
+
+        if client_sender.send(message).is_err() {
+            debug!("Client {} disconnected during order change send", subscription.client_id);
+        }
+    }
+}
+
+// Sends the symbol's MBPDelta streams the levels of their depth that changed since the
+// view last sent, or the whole view to streams that hold another one, such as those that
+// subscribed since. Views that did not change are not sent, so the streams keep the
+// sequence they are at.
+fn send_level_changes(
+    subscriptions: &SubscriptionIndex<Option<SharedPayload>, Subscription>,
+    clients: &DashMap<Uuid, ClientSender>,
+    symbol: &Arc<str>,
+    order_book: &OrderBook,
+    views: &mut HashMap<u32, LevelView>,
+) {
+    let mut streams: HashMap<u32, Vec<&Subscription>> = HashMap::new();
+    let symbol_subscriptions = subscriptions.symbol(symbol);
+    if let Some(group) = symbol_subscriptions.as_ref().and_then(|symbol_subscriptions| symbol_subscriptions.get(&None)) {
+        for subscription in group.values().filter(|subscription| matches!(subscription.data_type, DataType::MBPDelta)) {
+            streams.entry(subscription.max_levels).or_default().push(subscription);
+        }
+    }
+    let (sequence, epoch, event_ts) = (order_book.get_sequence(), order_book.get_epoch(), order_book.get_event_timestamp());
+    // A recreated book starts its streams over with whole views
+    views.retain(|depth, view| streams.contains_key(depth) && view.epoch == epoch);
+
+    for (depth, streams) in streams {
+        let (bids, asks) = order_book.get_mbp_data(depth);
+        let view = views.get(&depth);
+        let diff = view.map(|view| view.diff(&bids, &asks));
+        if diff.as_ref().is_some_and(|diff| diff.is_empty()) {
+            continue;
+        }
+        let previous_sequence = view.map(|view| view.sequence);
+        let delta = previous_sequence.zip(diff).and_then(|(previous_sequence, diff)| {
+            serialize_payload(&MarketDataUpdate::MBPDelta { previous_sequence, bids: diff.bids, asks: diff.asks })
+        });
+        let mut whole = None;
+
+        for subscription in streams {
+            let (Some(level_base), Some(client_sender)) = (&subscription.level_base, clients.get(&subscription.client_id)) else {
+                continue;
+            };
+            level_base.advance(previous_sequence, sequence, |holds_previous| {
+                let data = if holds_previous {
+                    delta.clone()
+                } else {
+                    whole.get_or_insert_with(|| serialize_payload(&MarketDataUpdate::MBP { bids: bids.clone(), asks: asks.clone() })).clone()
+                };
+                let Some(data) = data else {
+                    return false;
+                };
+                let message = OutboundMessage::SharedMarketData {
+                    stream_id: subscription.stream_id.clone(),
+                    symbol: Arc::clone(symbol),
+                    data,
+                    sequence,
+                    epoch,
+                    timestamp: Utc::now(),
+                    event_ts,
+                    anchor: None,
+                    venue_sequence: order_book.venue_sequence(),
+                };
+                if client_sender.send(message).is_err() {
+                    debug!("Client {} disconnected during level change send", subscription.client_id);
+                    return false;
+                }
+                true
+            });
+        }
+        views.insert(depth, LevelView { epoch, sequence, bids, asks });
+    }
+}
+
+// Sends the order events of a book update to the symbol's activity streams whose filters
+// they pass, each serialized once and all with the update's sequence
+#[allow(clippy::too_many_arguments)]
+fn send_activity(
+    subscriptions: &SubscriptionIndex<Option<SharedPayload>, Subscription>,
+    clients: &DashMap<Uuid, ClientSender>,
+    symbol: &Arc<str>,
+    events: Vec<(OrderActivity, Option<ActivityOrder>)>,
+    mid_price: Option<f64>,
+    sequence: u64,
+    epoch: u64,
+    event_ts: DateTime<Utc>,
+) {
+    let Some(symbol_subscriptions) = subscriptions.symbol(symbol) else {
+        return;
+    };
+    let Some(group) = symbol_subscriptions.get(&None) else {
+        return;
+    };
+
+    for (activity, order) in events {
+        let mut data = None;
+        for subscription in group.values() {
+            if !activity_matches(&subscription.data_type, order.as_ref(), mid_price) {
+                continue;
+            }
+            let Some(client_sender) = clients.get(&subscription.client_id) else {
+                continue;
+            };
+            if data.is_none() {
+                data = serialize_payload(&MarketDataUpdate::OrderActivity { activity: activity.clone() });
+            }
+            let Some(data) = &data else {
+                break;
+            };
+
+            let message = OutboundMessage::SharedMarketData {
+                stream_id: subscription.stream_id.clone(),
+                symbol: Arc::clone(symbol),
+                data: data.clone(),
+                sequence,
+                epoch,
+                timestamp: Utc::now(),
+                event_ts,
+                anchor: None,
+                venue_sequence: None,
+            };
+
+            if client_sender.send(message).is_err() {
+                debug!("Client {} disconnected during order activity send", subscription.client_id);
+            }
+        }
+    }
+}
+
+// The pair's quote, sequence, epoch and event time: the later of its legs' last changes.
+// Sequence and epoch add up the legs', so the epoch goes up whenever a leg is recreated.
+fn compute_pair_update(
+    order_books: &DashMap<String, Arc<PublishedBook>>,
+    pair: &SyntheticPair,
+) -> Option<(MarketDataUpdate, u64, u64, DateTime<Utc>)> {
+    let base_book = order_books.get(&pair.base)?.snapshot();
+    let quote_book = order_books.get(&pair.quote)?.snapshot();
+
+    let quote = pair.compute_quote(&base_book, &quote_book);
+    let sequence = base_book.get_sequence() + quote_book.get_sequence();
+    let epoch = base_book.get_epoch() + quote_book.get_epoch();
+    let event_ts = base_book.get_event_timestamp().max(quote_book.get_event_timestamp());
+
+    Some((MarketDataUpdate::Pair { quote }, sequence, epoch, event_ts))
+}
+
+// Sends every full-book snapshot that is due, building each book's view once. Schedules
+// of disconnected clients and of books that were delisted or evicted are dropped.
+fn send_due_snapshots(
+    schedules: &DashMap<StreamKey, SnapshotSchedule>,
+    order_books: &DashMap<String, Arc<PublishedBook>>,
+    clients: &DashMap<Uuid, ClientSender>,
+    stream_stats: &StreamStatsTracker,
+) {
+    let now = Instant::now();
+    let mut views: HashMap<(String, bool), (MarketDataUpdate, u64, u64)> = HashMap::new();
+
+    schedules.retain(|(client_id, stream_id), schedule| {
+        let Some(client_sender) = clients.get(client_id) else {
+            return false;
+        };
+        if !schedule.poll(now) {
+            return true;
+        }
+        let Some(order_book_ref) = order_books.get(&schedule.symbol).map(|entry| entry.value().clone()) else {
+            return false;
+        };
+
+        let (data, sequence, epoch) = views.entry((schedule.symbol.clone(), schedule.by_order)).or_insert_with(|| {
+            let order_book = order_book_ref.snapshot();
+            (full_book_update(&order_book, schedule.by_order), order_book.get_sequence(), order_book.get_epoch())
+        });
+        let message = ServerMessage::Snapshot {
+            stream_id: stream_id.clone(),
+            symbol: schedule.symbol.clone(),
+            data: data.clone(),
+            sequence: *sequence,
+            epoch: *epoch,
+            timestamp: Utc::now(),
+        };
+
+        match client_sender.send(message.into()) {
+            Ok(superseded) => stream_stats.record_dropped(client_id, stream_id, superseded),
+            Err(_) => debug!("Client {} disconnected during snapshot send", client_id),
+        }
+        true
+    });
+}
+
+fn full_book_update(order_book: &OrderBook, by_order: bool) -> MarketDataUpdate {
+    if by_order {
+        let (bids, asks) = order_book.get_mbo_data(u32::MAX, u32::MAX);
+        MarketDataUpdate::MBO { bids, asks }
+    } else {
+        let (bids, asks) = order_book.get_mbp_data(u32::MAX);
+        MarketDataUpdate::MBP { bids, asks }
+    }
+}
+
+// Market data built for one subscriber; send_ts is stamped again when it is serialized
+fn market_data(stream_id: &str, symbol: &str, data: MarketDataUpdate, sequence: u64, epoch: u64, event_ts: DateTime<Utc>) -> OutboundMessage {
+    let timestamp = Utc::now();
+    ServerMessage::MarketData {
+        stream_id: stream_id.to_string(),
+        symbol: symbol.into(),
+        data,
+        sequence,
+        epoch,
+        timestamp,
+        event_ts,
+        send_ts: timestamp,
+        anchor: None,
+        latency_us: None,
+        venue_sequence: None,
+    }
+    .into()
+}
+
+fn indicator_update(
+    candles: &DashMap<String, CandleAggregator>,
+    symbol: &str,
+    spec: &IndicatorSpec,
+) -> MarketDataUpdate {
+    let closes = candles
+        .get(symbol)
+        .and_then(|aggregator| aggregator.series(spec.interval_secs).map(|series| series.closes()))
+        .unwrap_or_default();
+
+    MarketDataUpdate::Indicators {
+        interval_secs: spec.interval_secs,
+        values: compute_indicators(spec, &closes),
+    }
+}
+
+fn perpetual_update(
+    perpetuals: &DashMap<String, Perpetual>,
+    symbol: &str,
+    data_type: &DataType,
+) -> Option<MarketDataUpdate> {
+    let perpetual = perpetuals.get(symbol)?;
+
+    match data_type {
+        DataType::MarkPrice => Some(MarketDataUpdate::MarkPrice { mark: perpetual.mark_price()?.clone() }),
+        DataType::Funding => Some(MarketDataUpdate::Funding { funding: perpetual.last_funding()?.clone() }),
+        _ => None,
+    }
+}
+
+fn option_chain_update(chain: &OptionChain) -> MarketDataUpdate {
+    MarketDataUpdate::OptionChain {
+        underlying: chain.underlying.clone(),
+        quotes: chain.quotes().to_vec(),
+    }
+}
+
+fn flow_update(flows: &DashMap<String, AggressorFlow>, symbol: &str) -> MarketDataUpdate {
+    let flow = flows
+        .get(symbol)
+        .map(|flow| flow.stats())
+        .unwrap_or_else(|| AggressorFlow::default().stats());
+
+    MarketDataUpdate::AggressorFlow { flow }
+}
+
+fn order_stats_update(order_stats: &DashMap<String, OrderLifetimes>, symbol: &str) -> MarketDataUpdate {
+    MarketDataUpdate::OrderStats { stats: lifetime_stats(order_stats, symbol) }
+}
+
+fn lifetime_stats(order_stats: &DashMap<String, OrderLifetimes>, symbol: &str) -> OrderLifetimeStats {
+    order_stats
+        .get(symbol)
+        .map(|lifetimes| lifetimes.stats())
+        .unwrap_or_else(|| OrderLifetimes::default().stats())
+}
+
+// Snapshot views that are identical for every subscriber asking for the same depth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum SharedPayload {
+    ByOrder(u32, u32),
+    ByPrice(u32),
+    AggressorFlow,
+    Ladder(u32, u32),
+    LiquidityAge(u32),
+}
+
+// Whether a low-priority subscription's update is held back by load shedding this tick
+fn conflated(
+    load_shedder: &Option<Arc<LoadShedder>>,
+    stream_stats: &StreamStatsTracker,
+    subscription: &Subscription,
+    now: Instant,
+) -> bool {
+    let conflated = subscription.priority == StreamPriority::Low
+        && load_shedder
+            .as_ref()
+            .is_some_and(|load_shedder| !load_shedder.deliver(&subscription.client_id, &subscription.stream_id, now));
+    if conflated {
+        stream_stats.record_conflated(&subscription.client_id, &subscription.stream_id);
+    }
+    conflated
+}
+
+fn throttled_notice(stream_ids: Vec<String>, conflation: Option<Duration>) -> ServerMessage {
+    ServerMessage::Throttled {
+        stream_ids,
+        interval_ms: conflation.map(|conflation| conflation.as_millis() as u64),
+        timestamp: Utc::now(),
+    }
+}
+
+fn shared_payload(subscription: &Subscription) -> Option<SharedPayload> {
+    match subscription.data_type {
+        DataType::MBO => Some(SharedPayload::ByOrder(subscription.max_levels, subscription.max_orders)),
+        DataType::MBP => Some(SharedPayload::ByPrice(subscription.max_levels)),
+        DataType::AggressorFlow => Some(SharedPayload::AggressorFlow),
+        DataType::Ladder { rows, tick_group } => Some(SharedPayload::Ladder(rows, tick_group)),
+        DataType::LiquidityAge => Some(SharedPayload::LiquidityAge(subscription.max_levels)),
+        DataType::Summary
+        | DataType::Reference
+        | DataType::Indicators { .. }
+        | DataType::OrderStats
+        | DataType::OrderActivity { .. }
+        | DataType::Quarantine
+        | DataType::Trades
+        | DataType::MBODelta
+        | DataType::MBPDelta
+        | DataType::MarkPrice
+        | DataType::Funding
+        | DataType::OptionQuote
+        | DataType::OptionChain => None,
+    }
+}
+
+fn build_shared_payload(
+    order_book: &OrderBook,
+    flows: &DashMap<String, AggressorFlow>,
+    symbol: &str,
+    key: SharedPayload,
+) -> Option<Bytes> {
+    serialize_payload(&shared_update(order_book, flows, symbol, key)?)
+}
+
+fn shared_update(
+    order_book: &OrderBook,
+    flows: &DashMap<String, AggressorFlow>,
+    symbol: &str,
+    key: SharedPayload,
+) -> Option<MarketDataUpdate> {
+    match key {
+        SharedPayload::ByOrder(..) | SharedPayload::ByPrice(..) => book_view(order_book, key),
+        SharedPayload::AggressorFlow => Some(flow_update(flows, symbol)),
+        SharedPayload::Ladder(rows, tick_group) => Some(ladder_update(order_book, rows, tick_group)),
+        SharedPayload::LiquidityAge(max_levels) => Some(liquidity_age_update(order_book, max_levels)),
+    }
+}
+
+// The deepest MBP and MBO views the groups of a book ask for, taken from the book once
+// per tick. Shallower views are prefixes of them: an MBP view of N levels is the first N
+// levels, and an MBO view of N levels and M orders is the orders of the first N prices,
+// cut at M, which the deepest view holds as long as it asks for as many orders.
+struct DeepestViews {
+    by_price: Option<(Vec<MBPLevel>, Vec<MBPLevel>)>,
+    by_order: Option<(Vec<MBOLevel>, Vec<MBOLevel>)>,
+}
+
+impl DeepestViews {
+    fn new<'a>(order_book: &OrderBook, keys: impl Iterator<Item = &'a SharedPayload>) -> Self {
+        let mut price_levels = None;
+        let mut order_depth: Option<(u32, u32)> = None;
+        for key in keys {
+            match *key {
+                SharedPayload::ByPrice(max_levels) => price_levels = price_levels.max(Some(max_levels)),
+                SharedPayload::ByOrder(max_levels, max_orders) => {
+                    order_depth = Some(order_depth.map_or((max_levels, max_orders), |(levels, orders)| {
+                        (levels.max(max_levels), orders.max(max_orders))
+                    }));
+                }
+                SharedPayload::AggressorFlow | SharedPayload::Ladder(..) | SharedPayload::LiquidityAge(..) => {}
+            }
+        }
+
+        Self {
+            by_price: price_levels.map(|max_levels| order_book.get_mbp_data(max_levels)),
+            by_order: order_depth.map(|(max_levels, max_orders)| order_book.get_mbo_data(max_levels, max_orders)),
+        }
+    }
+
+    fn view(&self, key: SharedPayload) -> Option<MarketDataUpdate> {
+        match key {
+            SharedPayload::ByPrice(max_levels) => {
+                let (bids, asks) = self.by_price.as_ref()?;
+                let depth = max_levels as usize;
+                Some(MarketDataUpdate::MBP {
+                    bids: bids.iter().take(depth).cloned().collect(),
+                    asks: asks.iter().take(depth).cloned().collect(),
+                })
+            }
+            SharedPayload::ByOrder(max_levels, max_orders) => {
+                let (bids, asks) = self.by_order.as_ref()?;
+                Some(MarketDataUpdate::MBO {
+                    bids: order_prefix(bids, max_levels, max_orders),
+                    asks: order_prefix(asks, max_levels, max_orders),
+                })
+            }
+            SharedPayload::AggressorFlow | SharedPayload::Ladder(..) | SharedPayload::LiquidityAge(..) => None,
+        }
+    }
+}
+
+// The orders of the first `max_levels` prices of a side, at most `max_orders` of them
+fn order_prefix(orders: &[MBOLevel], max_levels: u32, max_orders: u32) -> Vec<MBOLevel> {
+    let mut prices = 0;
+    let mut last_price = None;
+    orders
+        .iter()
+        .take_while(|order| {
+            if last_price != Some(order.price) {
+                last_price = Some(order.price);
+                prices += 1;
+            }
+            prices <= max_levels
+        })
+        .take(max_orders as usize)
+        .cloned()
+        .collect()
+}
+
+fn book_view(order_book: &OrderBook, key: SharedPayload) -> Option<MarketDataUpdate> {
+    match key {
+        SharedPayload::ByOrder(max_levels, max_orders) => {
+            let (bids, asks) = order_book.get_mbo_data(max_levels, max_orders);
+            Some(MarketDataUpdate::MBO { bids, asks })
+        }
+        SharedPayload::ByPrice(max_levels) => {
+            let (bids, asks) = order_book.get_mbp_data(max_levels);
+            Some(MarketDataUpdate::MBP { bids, asks })
+        }
+        SharedPayload::AggressorFlow | SharedPayload::Ladder(..) | SharedPayload::LiquidityAge(..) => None,
+    }
+}
+
+// Checksum of the book a subscriber to an MBP or MBO view holds at the book's sequence
+fn view_anchor(order_book: &OrderBook, key: SharedPayload) -> Option<BookAnchor> {
+    Some(BookAnchor {
+        sequence: order_book.get_sequence(),
+        checksum: book_view(order_book, key)?.checksum()?,
+    })
+}
+
+fn serialize_payload(market_data: &MarketDataUpdate) -> Option<Bytes> {
+    match serde_json::to_vec(market_data) {
+        Ok(json) => Some(Bytes::from(json)),
+        Err(e) => {
+            error!("Failed to serialize market data payload: {}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,97 @@
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use uuid::Uuid;
+
+use crate::message::StreamStats;
+
+#[derive(Debug)]
+struct StreamCounters {
+    subscribed_at: DateTime<Utc>,
+    messages_sent: u64,
+    updates_sent: u64,
+    last_sequence: Option<u64>,
+    conflated: u64,
+    dropped: u64,
+}
+
+// Delivery counters of every client's streams, kept from subscribing until unsubscribing
+// so that a client can tell missing data from a quiet market
+#[derive(Debug, Default)]
+pub struct StreamStatsTracker {
+    clients: DashMap<Uuid, HashMap<String, StreamCounters>>,
+}
+
+impl StreamStatsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Starts the stream's counters afresh, replacing those of an earlier stream with the same id
+    pub fn open(&self, client_id: Uuid, stream_id: &str) {
+        self.clients.entry(client_id).or_default().insert(stream_id.to_string(), StreamCounters {
+            subscribed_at: Utc::now(),
+            messages_sent: 0,
+            updates_sent: 0,
+            last_sequence: None,
+            conflated: 0,
+            dropped: 0,
+        });
+    }
+
+    pub fn close(&self, client_id: &Uuid, stream_id: &str) {
+        if let Some(mut streams) = self.clients.get_mut(client_id) {
+            streams.remove(stream_id);
+        }
+        self.clients.remove_if(client_id, |_, streams| streams.is_empty());
+    }
+
+    pub fn forget_client(&self, client_id: &Uuid) {
+        self.clients.remove(client_id);
+    }
+
+    // A message of the stream was written to the client; updates carry their sequence
+    pub fn record_sent(&self, client_id: &Uuid, stream_id: &str, update: bool, sequence: Option<u64>) {
+        self.update(client_id, stream_id, |counters| {
+            counters.messages_sent += 1;
+            if update {
+                counters.updates_sent += 1;
+            }
+            if sequence.is_some() {
+                counters.last_sequence = sequence;
+            }
+        });
+    }
+
+    pub fn record_conflated(&self, client_id: &Uuid, stream_id: &str) {
+        self.update(client_id, stream_id, |counters| counters.conflated += 1);
+    }
+
+    pub fn record_dropped(&self, client_id: &Uuid, stream_id: &str, dropped: usize) {
+        if dropped > 0 {
+            self.update(client_id, stream_id, |counters| counters.dropped += dropped as u64);
+        }
+    }
+
+    pub fn get(&self, client_id: &Uuid, stream_id: &str) -> Option<StreamStats> {
+        let streams = self.clients.get(client_id)?;
+        let counters = streams.get(stream_id)?;
+        let updates = counters.updates_sent + counters.conflated;
+
+        Some(StreamStats {
+            stream_id: stream_id.to_string(),
+            subscribed_at: counters.subscribed_at,
+            messages_sent: counters.messages_sent,
+            last_sequence: counters.last_sequence,
+            conflated: counters.conflated,
+            conflation_ratio: if updates == 0 { 0.0 } else { counters.conflated as f64 / updates as f64 },
+            dropped: counters.dropped,
+        })
+    }
+
+    fn update(&self, client_id: &Uuid, stream_id: &str, apply: impl FnOnce(&mut StreamCounters)) {
+        if let Some(counters) = self.clients.get_mut(client_id).as_deref_mut().and_then(|streams| streams.get_mut(stream_id)) {
+            apply(counters);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+// Bounds strict subscription validation holds stream parameters to
+pub const MAX_STREAM_LEVELS: u32 = 500;
+const MAX_SYMBOL_LEN: usize = 32;
+
+// Symbols are upper-case letters and digits, optionally joined by `.`, `-`, `_` or `/`,
+// so that a typo or a lower-cased name is refused rather than given an empty new book
+pub fn validate_symbol(symbol: &str) -> Result<(), String> {
+    if symbol.is_empty() {
+        return Err("Symbol must not be empty".to_string());
+    }
+    if symbol.len() > MAX_SYMBOL_LEN {
+        return Err(format!("Symbol '{}' is longer than {} characters", symbol, MAX_SYMBOL_LEN));
+    }
+
+    let valid_char = |c: char| c.is_ascii_uppercase() || c.is_ascii_digit() || matches!(c, '.' | '-' | '_' | '/');
+    let starts_alphanumeric = symbol.starts_with(|c: char| c.is_ascii_uppercase() || c.is_ascii_digit());
+    if !starts_alphanumeric || !symbol.chars().all(valid_char) {
+        return Err(format!(
+            "Symbol '{}' must be upper-case letters and digits, optionally joined by '.', '-', '_' or '/'",
+            symbol
+        ));
+    }
+    Ok(())
+}
+
+pub fn validate_levels(max_levels: u32) -> Result<(), String> {
+    if max_levels == 0 || max_levels > MAX_STREAM_LEVELS {
+        return Err(format!("max_levels must be between 1 and {}, got {}", MAX_STREAM_LEVELS, max_levels));
+    }
+    Ok(())
+}
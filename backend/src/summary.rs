@@ -0,0 +1,42 @@
+use std::cmp::Ordering;
+use chrono::Utc;
+
+use crate::message::{MarketSummary, SymbolSummary};
+
+// Number of entries kept in each ranking
+pub const SUMMARY_TOP_N: usize = 5;
+
+pub fn build_market_summary(symbols: Vec<SymbolSummary>, top_n: usize) -> MarketSummary {
+    let top_gainers = rank_by(&symbols, top_n, |s| s.change_pct.filter(|c| *c > 0.0), true);
+    let top_losers = rank_by(&symbols, top_n, |s| s.change_pct.filter(|c| *c < 0.0), false);
+    let highest_volume = rank_by(&symbols, top_n, |s| Some(s.volume.to_f64()), true);
+    let widest_spreads = rank_by(&symbols, top_n, |s| s.spread_bps, true);
+
+    MarketSummary {
+        top_gainers,
+        top_losers,
+        highest_volume,
+        widest_spreads,
+        symbol_count: symbols.len(),
+        timestamp: Utc::now(),
+    }
+}
+
+fn rank_by(
+    symbols: &[SymbolSummary],
+    top_n: usize,
+    key: impl Fn(&SymbolSummary) -> Option<f64>,
+    descending: bool,
+) -> Vec<SymbolSummary> {
+    let mut ranked: Vec<(f64, &SymbolSummary)> = symbols
+        .iter()
+        .filter_map(|s| key(s).map(|value| (value, s)))
+        .collect();
+
+    ranked.sort_by(|a, b| {
+        let ordering = a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal);
+        if descending { ordering.reverse() } else { ordering }
+    });
+
+    ranked.into_iter().take(top_n).map(|(_, s)| s.clone()).collect()
+}
@@ -0,0 +1,118 @@
+use std::any::Any;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use tokio::runtime::Handle;
+use tokio::time::{sleep, Instant};
+use tracing::{error, info};
+
+use crate::metrics::Metrics;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// A task that ran this long before panicking is restarted after the initial backoff again
+const STABLE_RUN: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskHealth {
+    pub task: &'static str,
+    pub up: bool,
+    pub restarts: u64,
+    pub down_since: Option<DateTime<Utc>>,
+    pub last_panic: Option<String>,
+}
+
+// Keeps the background tasks of a stream manager running, rebuilding each one after it
+// panics. A task that returns is done and no longer reported.
+#[derive(Debug)]
+pub struct Supervisor {
+    tasks: Arc<DashMap<&'static str, TaskHealth>>,
+    metrics: Arc<Metrics>,
+}
+
+impl Supervisor {
+    pub fn new(metrics: Arc<Metrics>) -> Self {
+        Self { tasks: Arc::new(DashMap::new()), metrics }
+    }
+
+    // Runs the task `build` returns on the runtime, or the caller's. Its first run is built
+    // before this returns, so whatever it subscribes to is in place; later runs are built
+    // after a backoff doubling from 1s to 60s. `build` returns None once its owner is gone.
+    pub fn spawn<F, Fut>(&self, name: &'static str, runtime: Option<Handle>, build: F)
+    where
+        F: Fn() -> Option<Fut> + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let Some(task) = build() else {
+            return;
+        };
+        let runtime = runtime.unwrap_or_else(Handle::current);
+        self.tasks.insert(name, TaskHealth { task: name, up: true, restarts: 0, down_since: None, last_panic: None });
+        self.metrics.set_task_up(name, true);
+
+        let tasks = Arc::clone(&self.tasks);
+        let metrics = Arc::clone(&self.metrics);
+        let supervised = runtime.clone();
+        runtime.spawn(async move {
+            let mut running = supervised.spawn(task);
+            let mut started = Instant::now();
+            let mut backoff = INITIAL_BACKOFF;
+
+            loop {
+                let panic = match running.await {
+                    Err(e) if e.is_panic() => panic_message(e.into_panic()),
+                    // Finished, or cancelled by the runtime shutting down
+                    _ => break,
+                };
+                if started.elapsed() >= STABLE_RUN {
+                    backoff = INITIAL_BACKOFF;
+                }
+                error!("Background task {} panicked: {}; restarting in {:?}", name, panic, backoff);
+                if let Some(mut health) = tasks.get_mut(name) {
+                    health.up = false;
+                    health.down_since = Some(Utc::now());
+                    health.last_panic = Some(panic);
+                }
+                metrics.set_task_up(name, false);
+
+                sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+                let Some(task) = build() else {
+                    break;
+                };
+                running = supervised.spawn(task);
+                started = Instant::now();
+
+                if let Some(mut health) = tasks.get_mut(name) {
+                    health.up = true;
+                    health.down_since = None;
+                    health.restarts += 1;
+                }
+                metrics.set_task_up(name, true);
+                metrics.record_task_restart(name);
+                info!("Restarted background task {}", name);
+            }
+
+            tasks.remove(name);
+            metrics.forget_task(name);
+        });
+    }
+
+    // Supervised tasks by name
+    pub fn tasks(&self) -> Vec<TaskHealth> {
+        let mut tasks: Vec<TaskHealth> = self.tasks.iter().map(|entry| entry.value().clone()).collect();
+        tasks.sort_by_key(|health| health.task);
+        tasks
+    }
+}
+
+pub fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_string())
+}
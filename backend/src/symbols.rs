@@ -0,0 +1,73 @@
+use serde::Deserialize;
+
+use crate::message::{Quantity, SymbolInfo, SymbolStatus, SymbolType};
+
+// Quote currencies recognised at the end of spot-style names, longest first
+const QUOTE_CURRENCIES: [&str; 8] = ["USDT", "USDC", "USD", "EUR", "GBP", "JPY", "BTC", "ETH"];
+
+// Filters for symbol discovery, e.g. `?search=BTC&type=spot&status=open&limit=50`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SymbolQuery {
+    pub search: Option<String>, // Case-insensitive substring of the symbol, base or quote
+    #[serde(rename = "type")]
+    pub symbol_type: Option<SymbolType>,
+    pub status: Option<SymbolStatus>,
+    pub limit: Option<usize>,
+}
+
+impl SymbolQuery {
+    pub fn matches(&self, info: &SymbolInfo) -> bool {
+        if self.symbol_type.is_some_and(|symbol_type| symbol_type != info.symbol_type) {
+            return false;
+        }
+        if self.status.is_some_and(|status| status != info.status) {
+            return false;
+        }
+
+        match self.search.as_deref().map(str::trim).filter(|search| !search.is_empty()) {
+            Some(search) => {
+                let search = search.to_ascii_uppercase();
+                [Some(&info.symbol), info.base.as_ref(), info.quote.as_ref()]
+                    .into_iter()
+                    .flatten()
+                    .any(|field| field.to_ascii_uppercase().contains(&search))
+            }
+            None => true,
+        }
+    }
+
+    // Matching symbols in name order, at most `limit` of them
+    pub fn apply(&self, symbols: Vec<SymbolInfo>) -> Vec<SymbolInfo> {
+        let mut matching: Vec<SymbolInfo> = symbols.into_iter().filter(|info| self.matches(info)).collect();
+        matching.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        if let Some(limit) = self.limit {
+            matching.truncate(limit);
+        }
+        matching
+    }
+}
+
+// "BTCUSD" is BTC quoted in USD; names without a recognised quote currency have neither
+pub fn split_currency_pair(symbol: &str) -> (Option<String>, Option<String>) {
+    QUOTE_CURRENCIES
+        .iter()
+        .find_map(|quote| {
+            let base = symbol.strip_suffix(quote).filter(|base| !base.is_empty())?;
+            Some((Some(base.to_string()), Some(quote.to_string())))
+        })
+        .unwrap_or((None, None))
+}
+
+// "BTCUSD:4" quotes BTCUSD sizes to 4 decimals, i.e. in lots of 0.0001
+pub fn parse_quantity_decimals(definition: &str) -> Result<(String, u32), String> {
+    let invalid = |reason: &str| format!("Invalid quantity decimals '{}': {}", definition, reason);
+
+    let (symbol, decimals) = definition.trim().rsplit_once(':').ok_or_else(|| invalid("expected SYMBOL:DECIMALS"))?;
+    if symbol.trim().is_empty() {
+        return Err(invalid("the symbol must be named"));
+    }
+    match decimals.trim().parse::<u32>() {
+        Ok(decimals) if decimals <= Quantity::DECIMALS => Ok((symbol.trim().to_string(), decimals)),
+        _ => Err(invalid(&format!("decimals must be 0 to {}", Quantity::DECIMALS))),
+    }
+}
@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::request::Parts,
+};
+use clap::Args;
+
+use crate::auth::AuthenticatedKey;
+use crate::stream_manager::StreamManager;
+
+// Separate symbol universes within one server. Each tenant has its own stream manager,
+// so its books, simulation, ingest feeds, limits and clients share nothing with any
+// other tenant's; clients are assigned one by the API key they authenticate with.
+#[derive(Debug, Clone, Default, Args)]
+pub struct TenantOptions {
+    /// Tenant and the API keys that belong to it, repeatable: NAME=KEY[,KEY...]. Each key
+    /// must be an --api-credential; other clients use the default universe.
+    #[arg(long = "tenant", value_name = "NAME=KEYS", value_parser = parse_tenant_setting)]
+    pub tenants: Vec<(String, String)>,
+
+    /// Symbols simulated for a tenant from startup, repeatable: NAME=SYMBOL[,SYMBOL...]
+    /// (default: BTCUSD, ETHUSD and ADAUSD)
+    #[arg(long = "tenant-symbols", value_name = "NAME=SYMBOLS", value_parser = parse_tenant_setting)]
+    pub tenant_symbols: Vec<(String, String)>,
+
+    /// Maximum number of symbols with a book for a tenant, repeatable: NAME=N (default: --max-symbols)
+    #[arg(long = "tenant-max-symbols", value_name = "NAME=N", value_parser = parse_tenant_setting)]
+    pub tenant_max_symbols: Vec<(String, String)>,
+
+    /// Simulate no books for this tenant, whose books are then only fed through the
+    /// ingest API, repeatable
+    #[arg(long = "tenant-no-simulation", value_name = "NAME")]
+    pub tenant_no_simulation: Vec<String>,
+}
+
+// Settings of one tenant, resolved from the options
+#[derive(Debug, Clone, Default)]
+pub struct TenantDefinition {
+    pub name: String,
+    pub api_keys: Vec<String>,
+    pub symbols: Option<Vec<String>>,
+    pub max_symbols: Option<usize>,
+    pub simulate: bool,
+}
+
+impl TenantOptions {
+    pub fn definitions(&self) -> Result<Vec<TenantDefinition>, String> {
+        let mut definitions: Vec<TenantDefinition> = Vec::new();
+        for (name, keys) in &self.tenants {
+            definitions.push(TenantDefinition {
+                name: name.clone(),
+                api_keys: list(keys),
+                simulate: true,
+                ..TenantDefinition::default()
+            });
+        }
+
+        for (name, symbols) in &self.tenant_symbols {
+            tenant(&mut definitions, name)?.symbols = Some(list(symbols));
+        }
+        for (name, max_symbols) in &self.tenant_max_symbols {
+            let max_symbols = max_symbols
+                .parse()
+                .map_err(|_| format!("Invalid symbol limit '{}' for tenant {}", max_symbols, name))?;
+            tenant(&mut definitions, name)?.max_symbols = Some(max_symbols);
+        }
+        for name in &self.tenant_no_simulation {
+            tenant(&mut definitions, name)?.simulate = false;
+        }
+
+        Ok(definitions)
+    }
+}
+
+fn tenant<'a>(definitions: &'a mut [TenantDefinition], name: &str) -> Result<&'a mut TenantDefinition, String> {
+    definitions
+        .iter_mut()
+        .find(|definition| definition.name == name)
+        .ok_or_else(|| format!("Unknown tenant {}", name))
+}
+
+fn parse_tenant_setting(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if !name.is_empty() && !value.is_empty() => Ok((name.to_string(), value.to_string())),
+        _ => Err("Expected NAME=VALUE".to_string()),
+    }
+}
+
+fn list(values: &str) -> Vec<String> {
+    values.split(',').filter(|value| !value.is_empty()).map(str::to_string).collect()
+}
+
+// Stream manager of every tenant, and the tenant each API key belongs to
+#[derive(Debug)]
+pub struct TenantRegistry {
+    default: Arc<StreamManager>,
+    tenants: HashMap<String, Arc<StreamManager>>,
+    key_tenants: HashMap<String, String>,
+}
+
+impl TenantRegistry {
+    // A single universe; every client uses the given stream manager
+    pub fn new(default: Arc<StreamManager>) -> Self {
+        Self {
+            default,
+            tenants: HashMap::new(),
+            key_tenants: HashMap::new(),
+        }
+    }
+
+    pub fn add_tenant(&mut self, name: &str, api_keys: &[String], stream_manager: Arc<StreamManager>) -> Result<(), String> {
+        if self.tenants.contains_key(name) {
+            return Err(format!("Tenant {} is defined twice", name));
+        }
+        for api_key in api_keys {
+            if let Some(tenant) = self.key_tenants.get(api_key) {
+                return Err(format!("API key {} already belongs to tenant {}", api_key, tenant));
+            }
+            self.key_tenants.insert(api_key.clone(), name.to_string());
+        }
+        self.tenants.insert(name.to_string(), stream_manager);
+        Ok(())
+    }
+
+    // Tenant of an API key, None for the default universe
+    pub fn tenant_of(&self, api_key: Option<&str>) -> Option<&str> {
+        api_key.and_then(|api_key| self.key_tenants.get(api_key)).map(String::as_str)
+    }
+
+    pub fn stream_manager(&self, tenant: Option<&str>) -> &Arc<StreamManager> {
+        tenant.and_then(|tenant| self.tenants.get(tenant)).unwrap_or(&self.default)
+    }
+
+    pub fn for_api_key(&self, api_key: Option<&str>) -> &Arc<StreamManager> {
+        self.stream_manager(self.tenant_of(api_key))
+    }
+
+    // The default universe's stream manager and then every tenant's
+    pub fn stream_managers(&self) -> impl Iterator<Item = &Arc<StreamManager>> {
+        std::iter::once(&self.default).chain(self.tenants.values())
+    }
+}
+
+// Stream manager of the tenant whose API key signed the request; unsigned requests and
+// keys of no tenant get the default universe
+pub struct TenantStreams(pub Arc<StreamManager>);
+
+#[async_trait]
+impl FromRequestParts<Arc<TenantRegistry>> for TenantStreams {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, tenants: &Arc<TenantRegistry>) -> Result<Self, Self::Rejection> {
+        let api_key = parts.extensions.get::<AuthenticatedKey>().map(|AuthenticatedKey(api_key)| api_key.as_str());
+        Ok(Self(Arc::clone(tenants.for_api_key(api_key))))
+    }
+}
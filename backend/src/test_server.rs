@@ -0,0 +1,114 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use futures_util::{SinkExt, StreamExt};
+use tokio::io::{duplex, DuplexStream};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{client_async, WebSocketStream};
+
+use crate::message::{ClientMessage, ServerMessage};
+use crate::stream_manager::StreamManager;
+use crate::websocket_handler::WebSocketHandler;
+
+// Bytes buffered in each direction of a client's in-memory transport
+const TRANSPORT_BUFFER: usize = 256 * 1024;
+
+// Address every in-process client appears to connect from
+const TEST_PEER: SocketAddr = SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 0);
+
+// The whole server in-process for integration tests: clients are served by the same
+// handler as real sockets, over in-memory duplex streams, and tokio time is paused so
+// simulation ticks, heartbeats and session expiry only happen as the test advances it.
+// While every task is idle the paused runtime jumps to the next timer by itself, so
+// awaiting the next update never sleeps. Timestamps in messages still follow the wall
+// clock. Needs a current-thread runtime that has not paused time yet, as `#[tokio::test]`
+// without `start_paused` provides.
+pub struct TestServer {
+    stream_manager: Arc<StreamManager>,
+    handler: WebSocketHandler,
+}
+
+impl TestServer {
+    // Default symbols with simulated books, no authentication
+    pub async fn start() -> Self {
+        Self::with_handler(Arc::new(StreamManager::new()), |_| {}).await
+    }
+
+    // A configured stream manager, and handler options such as authentication or profiles
+    pub async fn with_handler(stream_manager: Arc<StreamManager>, configure: impl FnOnce(&mut WebSocketHandler)) -> Self {
+        tokio::time::pause();
+        stream_manager.start().await;
+
+        let mut handler = WebSocketHandler::new(Arc::clone(&stream_manager));
+        configure(&mut handler);
+        Self { stream_manager, handler }
+    }
+
+    pub fn stream_manager(&self) -> &Arc<StreamManager> {
+        &self.stream_manager
+    }
+
+    pub async fn connect(&self) -> anyhow::Result<TestClient> {
+        self.connect_with_query("").await
+    }
+
+    // Upgrade query parameters as a real client sends them, e.g. `clock_sync=true` or a
+    // signed `api_key=...&timestamp=...&signature=...`
+    pub async fn connect_with_query(&self, query: &str) -> anyhow::Result<TestClient> {
+        let (client_io, server_io) = duplex(TRANSPORT_BUFFER);
+        self.handler.spawn_connection(server_io, TEST_PEER);
+
+        let url = match query {
+            "" => "ws://test-server/".to_string(),
+            query => format!("ws://test-server/?{}", query),
+        };
+        let (socket, _) = client_async(url, client_io).await.context("WebSocket handshake failed")?;
+        Ok(TestClient { socket })
+    }
+
+    // Moves virtual time forward, running every timer that falls due on the way
+    pub async fn advance(&self, duration: Duration) {
+        tokio::time::advance(duration).await;
+    }
+}
+
+// One in-process connection, speaking the protocol's JSON messages
+pub struct TestClient {
+    socket: WebSocketStream<DuplexStream>,
+}
+
+impl TestClient {
+    pub async fn send(&mut self, message: &ClientMessage) -> anyhow::Result<()> {
+        self.socket.send(Message::Text(serde_json::to_string(message)?)).await?;
+        Ok(())
+    }
+
+    // The next message, or None once the server closed the connection
+    pub async fn recv(&mut self) -> anyhow::Result<Option<ServerMessage>> {
+        while let Some(frame) = self.socket.next().await {
+            match frame? {
+                Message::Text(text) => return Ok(Some(serde_json::from_str(&text)?)),
+                Message::Close(_) => return Ok(None),
+                _ => {}
+            }
+        }
+        Ok(None)
+    }
+
+    // Skips messages until one matches, e.g. the first MarketData of a stream
+    pub async fn recv_until(&mut self, matches: impl Fn(&ServerMessage) -> bool) -> anyhow::Result<ServerMessage> {
+        loop {
+            match self.recv().await? {
+                Some(message) if matches(&message) => return Ok(message),
+                Some(_) => {}
+                None => anyhow::bail!("Connection closed before a matching message arrived"),
+            }
+        }
+    }
+
+    pub async fn close(mut self) -> anyhow::Result<()> {
+        self.socket.close(None).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::compression::{Compressed, CompressionStats, Switch};
+use crate::message::StreamUsage;
+
+// Messages and bytes delivered, per client and per subscription
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    clients: DashMap<Uuid, ClientUsage>,
+    total_messages: AtomicU64,
+    total_bytes: AtomicU64,
+}
+
+#[derive(Debug)]
+struct ClientUsage {
+    connected_at: DateTime<Utc>,
+    messages: u64,
+    bytes: u64,
+    streams: HashMap<String, StreamUsage>,
+    // Only for deflate connections
+    compression: Option<CompressionStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ClientUsageSnapshot {
+    pub client_id: String,
+    pub connected_at: DateTime<Utc>,
+    pub messages: u64,
+    pub bytes: u64,
+    pub streams: Vec<StreamUsage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<CompressionStats>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct UsageSnapshot {
+    pub total_messages: u64,
+    pub total_bytes: u64,
+    pub clients: Vec<ClientUsageSnapshot>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl UsageTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register(&self, client_id: Uuid) {
+        self.clients.insert(client_id, ClientUsage {
+            connected_at: Utc::now(),
+            messages: 0,
+            bytes: 0,
+            streams: HashMap::new(),
+            compression: None,
+        });
+    }
+
+    pub fn unregister(&self, client_id: &Uuid) {
+        self.clients.remove(client_id);
+    }
+
+    pub fn record(&self, client_id: &Uuid, stream_id: Option<&str>, bytes: usize) {
+        let bytes = bytes as u64;
+        self.total_messages.fetch_add(1, Ordering::Relaxed);
+        self.total_bytes.fetch_add(bytes, Ordering::Relaxed);
+
+        if let Some(mut usage) = self.clients.get_mut(client_id) {
+            usage.messages += 1;
+            usage.bytes += bytes;
+
+            if let Some(stream_id) = stream_id {
+                let stream = usage.streams
+                    .entry(stream_id.to_string())
+                    .or_insert_with(|| StreamUsage {
+                        stream_id: stream_id.to_string(),
+                        messages: 0,
+                        bytes: 0,
+                    });
+                stream.messages += 1;
+                stream.bytes += bytes;
+            }
+        }
+    }
+
+    pub fn record_compression(&self, client_id: &Uuid, compressed: &Compressed) {
+        if let Some(mut usage) = self.clients.get_mut(client_id) {
+            usage.compression.get_or_insert_with(CompressionStats::default).record(compressed);
+        }
+    }
+
+    pub fn record_compression_switch(&self, client_id: &Uuid, switch: Switch) {
+        if let Some(mut usage) = self.clients.get_mut(client_id) {
+            usage.compression.get_or_insert_with(CompressionStats::default).switch(switch);
+        }
+    }
+
+    pub fn client_usage(&self, client_id: &Uuid) -> Option<ClientUsageSnapshot> {
+        self.clients.get(client_id).map(|usage| ClientUsageSnapshot {
+            client_id: client_id.to_string(),
+            connected_at: usage.connected_at,
+            messages: usage.messages,
+            bytes: usage.bytes,
+            streams: usage.streams.values().cloned().collect(),
+            compression: usage.compression.clone(),
+        })
+    }
+
+    pub fn snapshot(&self) -> UsageSnapshot {
+        let client_ids: Vec<Uuid> = self.clients.iter().map(|entry| *entry.key()).collect();
+
+        UsageSnapshot {
+            total_messages: self.total_messages.load(Ordering::Relaxed),
+            total_bytes: self.total_bytes.load(Ordering::Relaxed),
+            clients: client_ids
+                .iter()
+                .filter_map(|client_id| self.client_usage(client_id))
+                .collect(),
+            timestamp: Utc::now(),
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::VecDeque;
+
+use crate::message::SequenceMapping;
+
+// Mappings kept per book, the most recent ones
+const MAX_VENUE_SEQUENCES: usize = 100_000;
+
+// Venue sequences of the feed events an external book applied, oldest first, each with
+// the book sequence it was published at
+#[derive(Debug, Default)]
+pub struct VenueSequences {
+    mappings: VecDeque<SequenceMapping>,
+}
+
+impl VenueSequences {
+    pub fn record(&mut self, mapping: SequenceMapping) {
+        if self.mappings.len() == MAX_VENUE_SEQUENCES {
+            self.mappings.pop_front();
+        }
+        self.mappings.push_back(mapping);
+    }
+
+    // The latest event sent with this venue sequence, since a feed may start its
+    // numbering over
+    pub fn by_venue_sequence(&self, venue_sequence: u64) -> Option<SequenceMapping> {
+        self.mappings.iter().rev().find(|mapping| mapping.venue_sequence == venue_sequence).copied()
+    }
+
+    // The last event applied at or before the book sequence
+    pub fn by_sequence(&self, sequence: u64) -> Option<SequenceMapping> {
+        let applied = self.mappings.partition_point(|mapping| mapping.sequence <= sequence);
+        applied.checked_sub(1).map(|index| self.mappings[index])
+    }
+}
@@ -0,0 +1,61 @@
+use std::time::Duration;
+use clap::Args;
+use tokio::time::{sleep, Instant};
+use tracing::{info, warn};
+
+use crate::tenants::TenantRegistry;
+
+const WARMUP_POLL: Duration = Duration::from_millis(50);
+const WARMUP_LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+// Readiness gate holding back client listeners at startup until the books they would be
+// served from have data, so early clients do not receive empty books. Simulated books
+// have data once initialized; external ones once the ingest API applied their first event.
+#[derive(Debug, Clone, Default, Args)]
+pub struct WarmupOptions {
+    /// Accept WebSocket clients only once each of these books has data, e.g. external books
+    /// fed through the ingest API; comma-separated or repeated
+    #[arg(long = "warmup-symbols", value_name = "SYMBOLS", value_delimiter = ',')]
+    pub warmup_symbols: Vec<String>,
+
+    /// Accept clients after this long even if some warm-up books are still empty (default:
+    /// wait until they have data)
+    #[arg(long, value_name = "SECS")]
+    pub warmup_timeout_secs: Option<u64>,
+}
+
+impl WarmupOptions {
+    // Returns once every warm-up book has data in the default universe or a tenant, or
+    // once the timeout has passed
+    pub async fn wait(&self, tenants: &TenantRegistry) {
+        if self.warmup_symbols.is_empty() {
+            return;
+        }
+
+        let started = Instant::now();
+        let deadline = self.warmup_timeout_secs.map(|secs| started + Duration::from_secs(secs));
+        let mut next_log = started;
+        loop {
+            let pending: Vec<&str> = self.warmup_symbols
+                .iter()
+                .filter(|symbol| !tenants.stream_managers().any(|stream_manager| stream_manager.has_book_data(symbol)))
+                .map(String::as_str)
+                .collect();
+            if pending.is_empty() {
+                info!("Warm-up complete after {:?}, accepting clients", started.elapsed());
+                return;
+            }
+
+            let now = Instant::now();
+            if deadline.is_some_and(|deadline| now >= deadline) {
+                warn!("Warm-up timed out, accepting clients without data for {}", pending.join(", "));
+                return;
+            }
+            if now >= next_log {
+                info!("Waiting for the first data of {} before accepting clients", pending.join(", "));
+                next_log = now + WARMUP_LOG_INTERVAL;
+            }
+            sleep(WARMUP_POLL).await;
+        }
+    }
+}
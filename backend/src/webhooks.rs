@@ -0,0 +1,387 @@
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use clap::Args;
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{interval, sleep, MissedTickBehavior};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::event_bus::{next_event, MarketEvent};
+use crate::feed_monitor::FeedMonitor;
+use crate::message::TradingStatus;
+use crate::sandbox::is_sandbox;
+
+pub const WEBHOOK_ID_HEADER: &str = "x-webhook-id";
+pub const WEBHOOK_TIMESTAMP_HEADER: &str = "x-webhook-timestamp";
+pub const WEBHOOK_SIGNATURE_HEADER: &str = "x-webhook-signature";
+
+// Deliveries an endpoint may fall behind by before new ones are dropped
+const ENDPOINT_QUEUE: usize = 1024;
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Push notifications of market events to HTTP endpoints. Each delivery is a JSON POST
+// signed like the admin API: `x-webhook-signature` is the hex HMAC-SHA256 of the
+// `x-webhook-timestamp` value, a `.` and the body, keyed with the webhook secret.
+#[derive(Debug, Clone, Default, Args)]
+pub struct WebhookOptions {
+    /// Endpoint receiving event POSTs, repeatable: URL, or CATEGORIES=URL to receive only
+    /// a comma-separated list of alert, trading_status and data_quality (default: all)
+    #[arg(long = "webhook", value_name = "[CATEGORIES=]URL")]
+    pub webhooks: Vec<WebhookEndpoint>,
+
+    /// Secret signing every webhook delivery (default: unsigned)
+    #[arg(long, value_name = "SECRET")]
+    pub webhook_secret: Option<String>,
+
+    /// Redeliveries of a POST that failed or was answered 408, 429 or 5xx, with a
+    /// backoff doubling from one second up to a minute
+    #[arg(long, value_name = "N", default_value_t = 5)]
+    pub webhook_max_retries: u32,
+
+    /// Fire an alert when a book's mid price moves this many percent from where it was
+    /// when last alerted (default: no alerts)
+    #[arg(long, value_name = "PCT")]
+    pub webhook_price_move_pct: Option<f64>,
+
+    /// Report a data-quality incident when a trading book has had no update for this long
+    #[arg(long, value_name = "SECS", default_value_t = 30)]
+    pub webhook_stale_feed_secs: u64,
+}
+
+impl WebhookOptions {
+    pub fn is_enabled(&self) -> bool {
+        !self.webhooks.is_empty()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WebhookCategory {
+    Alert,
+    TradingStatus,
+    DataQuality,
+}
+
+impl FromStr for WebhookCategory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "alert" => Ok(Self::Alert),
+            "trading_status" => Ok(Self::TradingStatus),
+            "data_quality" => Ok(Self::DataQuality),
+            _ => Err(format!("Unknown webhook category '{}', expected alert, trading_status or data_quality", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WebhookEndpoint {
+    pub url: String,
+    pub categories: HashSet<WebhookCategory>,
+}
+
+impl FromStr for WebhookEndpoint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A URL's query may hold `=`, so categories are only split off before a scheme
+        let (categories, url) = match s.split_once('=') {
+            Some((categories, url)) if !categories.contains("://") => (Some(categories), url),
+            _ => (None, s),
+        };
+
+        check_http_url(url)?;
+
+        let categories = match categories {
+            Some(categories) => categories.split(',').map(str::parse).collect::<Result<HashSet<_>, _>>()?,
+            None => HashSet::from([WebhookCategory::Alert, WebhookCategory::TradingStatus, WebhookCategory::DataQuality]),
+        };
+
+        Ok(Self { url: url.to_string(), categories })
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum WebhookEvent {
+    // The mid moved by `change_pct` percent from `reference_price`
+    PriceMove {
+        symbol: String,
+        reference_price: f64,
+        mid_price: f64,
+        change_pct: f64,
+    },
+    // `contract` is the dated future whose status changed; `symbol` is it or a spread on it
+    TradingStatus {
+        symbol: String,
+        contract: String,
+        status: TradingStatus,
+        settlement_price: Option<f64>,
+    },
+    FeedStale {
+        symbol: String,
+        last_update: DateTime<Utc>,
+    },
+    FeedRecovered {
+        symbol: String,
+        stale_secs: f64,
+    },
+}
+
+impl WebhookEvent {
+    pub fn category(&self) -> WebhookCategory {
+        match self {
+            WebhookEvent::PriceMove { .. } => WebhookCategory::Alert,
+            WebhookEvent::TradingStatus { .. } => WebhookCategory::TradingStatus,
+            WebhookEvent::FeedStale { .. } | WebhookEvent::FeedRecovered { .. } => WebhookCategory::DataQuality,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    id: String,
+    timestamp: DateTime<Utc>,
+    category: WebhookCategory,
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+}
+
+#[derive(Debug)]
+struct Delivery {
+    id: Uuid,
+    body: Arc<str>,
+}
+
+// Turns bus events into webhook events and hands them to one delivery task per
+// endpoint, so a slow or failing endpoint delays only its own deliveries
+pub struct WebhookDispatcher {
+    options: WebhookOptions,
+    client: reqwest::Client,
+}
+
+impl WebhookDispatcher {
+    pub fn new(options: &WebhookOptions) -> Result<Self, String> {
+        if options.webhook_price_move_pct.is_some_and(|pct| pct.is_nan() || pct <= 0.0) {
+            return Err("--webhook-price-move-pct must be positive".to_string());
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .map_err(|e| format!("Failed to create webhook client: {}", e))?;
+
+        Ok(Self { options: options.clone(), client })
+    }
+
+    pub fn start(self, mut receiver: broadcast::Receiver<MarketEvent>) {
+        let secret: Option<Arc<str>> = self.options.webhook_secret.as_deref().map(Arc::from);
+        let endpoints: Vec<_> = self
+            .options
+            .webhooks
+            .iter()
+            .map(|endpoint| {
+                let (sender, deliveries) = mpsc::channel(ENDPOINT_QUEUE);
+                tokio::spawn(deliver(
+                    self.client.clone(),
+                    endpoint.url.clone(),
+                    secret.clone(),
+                    self.options.webhook_max_retries,
+                    deliveries,
+                ));
+                (endpoint.clone(), sender)
+            })
+            .collect();
+
+        let mut detector = IncidentDetector::new(&self.options);
+        tokio::spawn(async move {
+            let mut check = interval(Duration::from_secs(1));
+            check.set_missed_tick_behavior(MissedTickBehavior::Skip);
+
+            loop {
+                let events = tokio::select! {
+                    event = next_event(&mut receiver, "Webhooks") => match event {
+                        Some(event) => detector.observe(event),
+                        None => break,
+                    },
+                    _ = check.tick() => detector.check_stale(),
+                };
+
+                for event in events {
+                    dispatch(&endpoints, &event);
+                }
+            }
+        });
+    }
+}
+
+fn dispatch(endpoints: &[(WebhookEndpoint, mpsc::Sender<Delivery>)], event: &WebhookEvent) {
+    let category = event.category();
+    let id = Uuid::new_v4();
+    let payload = WebhookPayload { id: id.to_string(), timestamp: Utc::now(), category, event };
+    let body: Arc<str> = match serde_json::to_string(&payload) {
+        Ok(body) => Arc::from(body),
+        Err(e) => {
+            error!("Failed to serialize webhook event: {}", e);
+            return;
+        }
+    };
+
+    for (endpoint, sender) in endpoints {
+        if !endpoint.categories.contains(&category) {
+            continue;
+        }
+        if sender.try_send(Delivery { id, body: Arc::clone(&body) }).is_err() {
+            warn!("Webhook {} is too far behind, dropping event {}", endpoint.url, id);
+        }
+    }
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    url: String,
+    secret: Option<Arc<str>>,
+    max_retries: u32,
+    mut deliveries: mpsc::Receiver<Delivery>,
+) {
+    while let Some(delivery) = deliveries.recv().await {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempt = 0;
+
+        loop {
+            // Signed per attempt so the timestamp stays within the receiver's window
+            let timestamp = Utc::now().timestamp_millis().to_string();
+            let mut request = client
+                .post(&url)
+                .header("content-type", "application/json")
+                .header(WEBHOOK_ID_HEADER, delivery.id.to_string())
+                .header(WEBHOOK_TIMESTAMP_HEADER, &timestamp);
+            if let Some(secret) = &secret {
+                request = request.header(WEBHOOK_SIGNATURE_HEADER, sign(secret, &timestamp, &delivery.body));
+            }
+
+            let failure = match request.body(delivery.body.to_string()).send().await {
+                Ok(response) if response.status().is_success() => break,
+                Ok(response) => {
+                    let status = response.status();
+                    if !(status.is_server_error() || status.as_u16() == 408 || status.as_u16() == 429) {
+                        warn!("Webhook {} refused event {} with {}", url, delivery.id, status);
+                        break;
+                    }
+                    status.to_string()
+                }
+                Err(e) => e.to_string(),
+            };
+
+            if attempt == max_retries {
+                warn!("Giving up on webhook {} event {} after {} attempts: {}", url, delivery.id, attempt + 1, failure);
+                break;
+            }
+            attempt += 1;
+            sleep(backoff).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+// Deliveries are plain HTTP; TLS endpoints are reached through a local proxy
+pub fn check_http_url(url: &str) -> Result<(), String> {
+    if !url.starts_with("http://") || url.len() == "http://".len() {
+        return Err(format!("URL '{}' must be an http:// URL", url));
+    }
+    Ok(())
+}
+
+pub fn sign(secret: &str, timestamp: &str, body: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Price alerts and stale feeds, worked out from the books' updates
+struct IncidentDetector {
+    price_move_pct: Option<f64>,
+    reference_prices: HashMap<Arc<str>, f64>,
+    feeds: FeedMonitor,
+}
+
+impl IncidentDetector {
+    fn new(options: &WebhookOptions) -> Self {
+        Self {
+            price_move_pct: options.webhook_price_move_pct,
+            reference_prices: HashMap::new(),
+            feeds: FeedMonitor::new(Duration::from_secs(options.webhook_stale_feed_secs.max(1))),
+        }
+    }
+
+    fn observe(&mut self, event: MarketEvent) -> Vec<WebhookEvent> {
+        let mut events = Vec::new();
+        match event {
+            // Sandboxes are driven by their owners, whose crashes and halts are no incident
+            MarketEvent::BookUpdated { symbol, .. } if is_sandbox(&symbol) => {}
+            MarketEvent::BookUpdated { symbol, event_ts, mid_price, .. } => {
+                if let Some(silence) = self.feeds.updated(&symbol, event_ts) {
+                    events.push(WebhookEvent::FeedRecovered {
+                        symbol: symbol.to_string(),
+                        stale_secs: silence.as_secs_f64(),
+                    });
+                }
+
+                if let (Some(threshold), Some(mid_price)) = (self.price_move_pct, mid_price) {
+                    let reference_price = *self.reference_prices.entry(Arc::clone(&symbol)).or_insert(mid_price);
+                    let change_pct = (mid_price - reference_price) / reference_price * 100.0;
+                    if change_pct.abs() >= threshold {
+                        self.reference_prices.insert(Arc::clone(&symbol), mid_price);
+                        events.push(WebhookEvent::PriceMove {
+                            symbol: symbol.to_string(),
+                            reference_price,
+                            mid_price,
+                            change_pct,
+                        });
+                    }
+                }
+            }
+            MarketEvent::Status { symbol, contract } => {
+                self.feeds.set_halted(&symbol, contract.status != TradingStatus::Trading);
+                events.push(WebhookEvent::TradingStatus {
+                    symbol: symbol.to_string(),
+                    contract: contract.symbol,
+                    status: contract.status,
+                    settlement_price: contract.settlement_price,
+                });
+            }
+            MarketEvent::BookRemoved { symbol } => {
+                self.reference_prices.remove(&symbol);
+                self.feeds.remove(&symbol);
+            }
+            MarketEvent::Activity(_)
+            | MarketEvent::Trade(_)
+            | MarketEvent::Conflation { .. }
+            | MarketEvent::TickCompleted { .. }
+            | MarketEvent::IntegrityViolation { .. }
+            | MarketEvent::Quarantined(_) => {}
+        }
+        events
+    }
+
+    fn check_stale(&mut self) -> Vec<WebhookEvent> {
+        self.feeds
+            .newly_stale()
+            .into_iter()
+            .map(|(symbol, last_update)| WebhookEvent::FeedStale { symbol: symbol.to_string(), last_update })
+            .collect()
+    }
+}
@@ -1,80 +1,399 @@
+use std::future;
+use std::iter;
+use std::mem;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::Arc;
-use tokio::net::{TcpListener, TcpStream};
-use tokio_tungstenite::{accept_async, tungstenite::Message};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tokio::sync::watch;
+use tokio::task::JoinSet;
+use tokio::time::{sleep_until, Instant};
+use tokio_tungstenite::accept_hdr_async;
+use tokio_tungstenite::tungstenite::{
+    self,
+    handshake::server::{Callback, ErrorResponse, Request, Response},
+    http::{header, StatusCode},
+    protocol::CloseFrame,
+    Message,
+};
 use futures_util::{SinkExt, StreamExt};
 use uuid::Uuid;
 use chrono::Utc;
 use tracing::{info, error, warn, debug};
-use tokio::sync::mpsc;
 
+use crate::access::AccessControl;
+use crate::audit::AuditEvent;
+use crate::candle_store::CANDLE_HISTORY_PAGE_SIZE;
+use crate::clock::{ClockSync, EventOrdering, CLOCK_SYNC_PARAM};
+use crate::compression::{Compressor, Encoded, Encoding, Switch, ENCODING_PARAM};
+use crate::dead_letters::DeadLetterReason;
+use crate::filters::{MessageFilter, MessageFilters};
+use crate::listener::ListenerOptions;
+use crate::outbound_queue::outbound_queue;
+use crate::paper_trading::PaperOrderRequest;
+use crate::auth::{query_param, Authenticator};
+use crate::rate_limit::{retry_after_secs, RateLimited, RateLimiter};
+use crate::session::{Session, SessionStore, SESSION_TOKEN_PARAM};
 use crate::stream_manager::StreamManager;
-use crate::message::{ClientMessage, ServerMessage};
+use crate::symbols::SymbolQuery;
+use crate::tenants::TenantRegistry;
+#[cfg(unix)]
+use crate::unix_socket::UNIX_PEER;
+use crate::unix_socket::{peer_ip, UnixSocketOptions};
+#[cfg(not(unix))]
+use crate::unix_socket::unsupported_unix_socket;
+use crate::message::{ClientMessage, DisconnectReason, LiveStreams, OutboundMessage, ServerMessage, StreamSpec};
+use crate::profiles::ProfileStore;
+use crate::quota::QuotaDecision;
 
+// Cloned into every acceptor task
+#[derive(Clone)]
 pub struct WebSocketHandler {
-    stream_manager: Arc<StreamManager>,
+    tenants: Arc<TenantRegistry>,
+    listener: ListenerOptions,
+    unix_socket: UnixSocketOptions,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    access_control: Option<Arc<AccessControl>>,
+    authentication: Option<Authentication>,
+    profiles: Arc<ProfileStore>,
+}
+
+// Verifies signed handshakes and issues the resulting sessions
+#[derive(Clone)]
+struct Authentication {
+    authenticator: Arc<Authenticator>,
+    sessions: Arc<SessionStore>,
 }
 
 impl WebSocketHandler {
     pub fn new(stream_manager: Arc<StreamManager>) -> Self {
-        Self { stream_manager }
+        Self {
+            tenants: Arc::new(TenantRegistry::new(stream_manager)),
+            listener: ListenerOptions::default(),
+            unix_socket: UnixSocketOptions::default(),
+            rate_limiter: None,
+            access_control: None,
+            authentication: None,
+            profiles: Arc::new(ProfileStore::default()),
+        }
+    }
+
+    // Serves each client from the universe of the tenant its API key belongs to
+    pub fn set_tenants(&mut self, tenants: Arc<TenantRegistry>) {
+        self.tenants = tenants;
+    }
+
+    // Rejects WebSocket upgrades over the limit with 429 Too Many Requests
+    pub fn set_rate_limiter(&mut self, rate_limiter: Arc<RateLimiter>) {
+        self.rate_limiter = Some(rate_limiter);
+    }
+
+    // Disables Nagle's algorithm on client sockets so small updates are not batched
+    pub fn enable_tcp_nodelay(&mut self) {
+        self.listener.tcp_nodelay = true;
+    }
+
+    // Acceptors, backlog and client socket options of the listener
+    pub fn set_listener_options(&mut self, options: ListenerOptions) {
+        self.listener = options;
+    }
+
+    // Accepts clients on a Unix domain socket as well as the TCP address
+    pub fn set_unix_socket(&mut self, options: UnixSocketOptions) {
+        self.unix_socket = options;
+    }
+
+    // Drops connections from addresses that fail the access rules before the handshake
+    pub fn set_access_control(&mut self, access_control: Arc<AccessControl>) {
+        self.access_control = Some(access_control);
+    }
+
+    // Verifies signed upgrade requests and gives authenticated clients a renewable session;
+    // unsigned upgrades are rejected with 401 Unauthorized if signatures are required
+    pub fn set_authentication(&mut self, authenticator: Arc<Authenticator>, sessions: Arc<SessionStore>) {
+        self.authentication = Some(Authentication { authenticator, sessions });
+    }
+
+    // Where authenticated clients' subscription profiles are kept
+    pub fn set_profiles(&mut self, profiles: Arc<ProfileStore>) {
+        self.profiles = profiles;
     }
 
     pub async fn start(&self, addr: &str) -> anyhow::Result<()> {
-        let listener = TcpListener::bind(addr).await?;
-        info!("WebSocket server listening on: {}", addr);
+        let listeners = self.listener.bind(addr).await?;
+        match listeners.len() {
+            1 => info!("WebSocket server listening on: {}", addr),
+            acceptors => info!("WebSocket server listening on: {} with {} acceptors", addr, acceptors),
+        }
 
+        let mut acceptors = JoinSet::new();
+        for listener in listeners {
+            let handler = self.clone();
+            acceptors.spawn(async move { handler.accept(listener).await });
+        }
+
+        if let Some(path) = &self.unix_socket.unix_socket {
+            #[cfg(unix)]
+            {
+                let listener = self.unix_socket.bind(path)?;
+                info!("WebSocket server listening on Unix socket: {}", path);
+                let handler = self.clone();
+                acceptors.spawn(async move { handler.accept_unix(listener).await });
+            }
+            #[cfg(not(unix))]
+            return Err(unsupported_unix_socket().into());
+        }
+
+        // Each acceptor runs until accepting fails; the server stops with the first that does
+        if let Some(result) = acceptors.join_next().await {
+            result?;
+        }
+
+        Ok(())
+    }
+
+    async fn accept(&self, listener: TcpListener) {
         while let Ok((stream, peer_addr)) = listener.accept().await {
+            if !self.is_allowed(peer_addr) {
+                continue;
+            }
+
             info!("New connection from: {}", peer_addr);
 
-            let stream_manager = Arc::clone(&self.stream_manager);
-            tokio::spawn(async move {
-                if let Err(e) = handle_connection(stream, stream_manager).await {
-                    error!("Error handling connection from {}: {}", peer_addr, e);
-                }
-            });
+            if let Err(e) = self.listener.configure(&stream) {
+                warn!("Failed to configure the socket of {}: {}", peer_addr, e);
+            }
+
+            self.spawn_connection(stream, peer_addr);
         }
+    }
 
-        Ok(())
+    #[cfg(unix)]
+    async fn accept_unix(&self, listener: UnixListener) {
+        while let Ok((stream, _)) = listener.accept().await {
+            if !self.is_allowed(UNIX_PEER) {
+                continue;
+            }
+
+            info!("New connection on the Unix socket");
+            self.spawn_connection(stream, UNIX_PEER);
+        }
+    }
+
+    fn is_allowed(&self, peer_addr: SocketAddr) -> bool {
+        match &self.access_control {
+            Some(access_control) if !access_control.is_allowed(peer_addr.ip()) => {
+                warn!("Rejected connection from {}: blocked by access rules", peer_addr);
+                false
+            }
+            _ => true,
+        }
+    }
+
+    pub(crate) fn spawn_connection<S>(&self, stream: S, peer_addr: SocketAddr)
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    {
+        let tenants = Arc::clone(&self.tenants);
+        let rate_limiter = self.rate_limiter.clone();
+        let authentication = self.authentication.clone();
+        let profiles = Arc::clone(&self.profiles);
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, peer_addr, tenants, rate_limiter, authentication, profiles).await {
+                error!("Error handling connection from {}: {}", peer_addr, e);
+            }
+        });
     }
 }
 
-async fn handle_connection(
-    stream: TcpStream,
-    stream_manager: Arc<StreamManager>,
-) -> anyhow::Result<()> {
-    let ws_stream = accept_async(stream).await?;
+async fn handle_connection<S>(
+    stream: S,
+    peer_addr: SocketAddr,
+    tenants: Arc<TenantRegistry>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    authentication: Option<Authentication>,
+    profiles: Arc<ProfileStore>,
+) -> anyhow::Result<()>
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let mut session = None;
+    let mut sync_clock = false;
+    let mut encoding = Encoding::Json;
+    let check_handshake = HandshakeCheck {
+        peer_addr,
+        rate_limiter: rate_limiter.as_deref(),
+        authentication: authentication.as_ref(),
+        session: &mut session,
+        sync_clock: &mut sync_clock,
+        encoding: &mut encoding,
+    };
+
+    let ws_stream = match accept_hdr_async(stream, check_handshake).await {
+        Ok(ws_stream) => ws_stream,
+        Err(tungstenite::Error::Http(response)) => {
+            warn!("Rejected connection from {}: {}", peer_addr, response.status());
+            return Ok(());
+        }
+        Err(e) => return Err(e.into()),
+    };
     let (mut ws_sender, mut ws_receiver) = ws_stream.split();
 
+    // Clients only ever see the universe of the tenant they authenticated as
+    let tenant = tenants.tenant_of(session.as_ref().map(|session| session.api_key.as_str())).map(str::to_string);
+    let stream_manager = Arc::clone(tenants.stream_manager(tenant.as_deref()));
+
     let client_id = Uuid::new_v4();
-    let (tx, mut rx) = mpsc::unbounded_channel();
+    let (tx, mut rx) = outbound_queue();
 
     // Register client with stream manager
     stream_manager.register_client(client_id, tx);
 
-    info!("Client {} connected", client_id);
+    match &tenant {
+        Some(tenant) => info!("Client {} of tenant {} connected", client_id, tenant),
+        None => info!("Client {} connected", client_id),
+    }
 
     // Send welcome message
     let welcome_message = ServerMessage::HeartBeat {
         timestamp: Utc::now(),
+        quotes: Vec::new(),
     };
 
     if let Ok(welcome_json) = serde_json::to_string(&welcome_message) {
-        if let Err(e) = ws_sender.send(Message::Text(welcome_json)).await {
-            error!("Failed to send welcome message to client {}: {}", client_id, e);
+        let bytes = welcome_json.len();
+        match ws_sender.send(Message::Text(welcome_json)).await {
+            Ok(()) => stream_manager.record_usage(&client_id, None, bytes),
+            Err(e) => error!("Failed to send welcome message to client {}: {}", client_id, e),
+        }
+    }
+
+    let mut session = ConnectionSession {
+        authentication,
+        profiles,
+        tenants,
+        tenant,
+        peer: peer_addr.ip(),
+        current: session,
+        warned: false,
+    };
+
+    if let Some(current) = &session.current {
+        info!("Client {} authenticated with API key {}", client_id, current.api_key);
+        stream_manager.bind_api_key(client_id, &current.api_key);
+        let started = ServerMessage::SessionStarted {
+            token: current.token.clone(),
+            expires_at: current.expires_at,
+        };
+
+        if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+            let _ = client_sender.send(started.into());
         }
     }
 
-    // Spawn task to handle outgoing messages
+    let (filters, filter_updates) = watch::channel(MessageFilters::default());
+    let mut clock_sync = ClockSync::default();
+    if sync_clock {
+        if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+            let _ = client_sender.send(clock_sync.probe().into());
+        }
+    }
+
+    // Spawn task to handle outgoing messages; the connection ends with it
     let stream_manager_clone = Arc::clone(&stream_manager);
     let client_id_clone = client_id;
-    tokio::spawn(async move {
-        while let Some(message) = rx.recv().await {
-            match serde_json::to_string(&message) {
+    let mut outgoing = tokio::spawn(async move {
+        let mut ordering = EventOrdering::default();
+        let mut live_streams = LiveStreams::default();
+        let mut filter = MessageFilter::new(filter_updates);
+        let tag_latency = stream_manager_clone.latency_tags();
+        let mut compressor = (encoding == Encoding::Deflate).then(Compressor::default);
+        while let Some(mut message) = rx.recv().await {
+            // The backlog is dropped with the connection, or kept as dead letters; the client
+            // resynchronizes on reconnect
+            let queued = rx.len();
+            stream_manager_clone.record_queue_depth(queued);
+            let mut undelivered = Vec::new();
+            if stream_manager_clone.is_slow_consumer(queued) {
+                info!("Client {} has {} messages queued, disconnecting", client_id_clone, queued);
+                undelivered.push(mem::replace(&mut message, OutboundMessage::Disconnect(DisconnectReason::SlowConsumer)));
+            }
+
+            if let OutboundMessage::Disconnect(reason) = message {
+                undelivered.extend(rx.drain());
+                stream_manager_clone.capture_dead_letters(&client_id_clone, DeadLetterReason::Disconnected { disconnect: reason }, undelivered);
+                let frame = CloseFrame {
+                    code: reason.close_code().into(),
+                    reason: reason.as_str().into(),
+                };
+                if let Err(e) = ws_sender.send(Message::Close(Some(frame))).await {
+                    debug!("Failed to send close frame to client {}: {}", client_id_clone, e);
+                }
+                info!("Closed connection of client {}: {}", client_id_clone, reason);
+                break;
+            }
+
+            // Muted updates are counted as conflated in the stream's statistics
+            let every_update = |message: &OutboundMessage| {
+                message.stream_id().is_some_and(|stream_id| stream_manager_clone.carries_every_update(&client_id_clone, stream_id))
+            };
+            if filter.mutes(&message) && !every_update(&message) {
+                if let Some(stream_id) = message.stream_id().filter(|_| message.is_stream_update()) {
+                    stream_manager_clone.record_throttled(&client_id_clone, stream_id);
+                }
+                continue;
+            }
+
+            if message.is_stream_update() {
+                match stream_manager_clone.check_quota(&client_id_clone, message.stream_id().unwrap_or_default()) {
+                    QuotaDecision::Deliver => {}
+                    QuotaDecision::Skip if every_update(&message) => {}
+                    QuotaDecision::Skip => {
+                        stream_manager_clone.record_throttled(&client_id_clone, message.stream_id().unwrap_or_default());
+                        continue;
+                    }
+                    QuotaDecision::Exceeded(quota) => {
+                        stream_manager_clone.cut_off_quota(&client_id_clone, quota);
+                        continue;
+                    }
+                }
+            }
+
+            let live = live_streams.is_live(&message);
+            match message.to_json(&mut ordering, tag_latency && live) {
                 Ok(json) => {
-                    if let Err(e) = ws_sender.send(Message::Text(json)).await {
+                    let encoded = match compressor.as_mut() {
+                        Some(compressor) => compressor.encode(json),
+                        None => Encoded::Text(json),
+                    };
+                    let frame = match encoded {
+                        Encoded::Text(json) => Message::Text(json),
+                        Encoded::Deflated { data, compressed } => {
+                            stream_manager_clone.record_compression(&client_id_clone, &compressed);
+                            Message::Binary(data)
+                        }
+                    };
+                    if let Some(switch) = compressor.as_mut().and_then(Compressor::reevaluate) {
+                        match switch {
+                            Switch::FellBack => info!("Compression costs client {} more than it saves, sending text frames", client_id_clone),
+                            Switch::Resumed => info!("Compression pays off for client {} again, sending binary frames", client_id_clone),
+                        }
+                        stream_manager_clone.record_compression_switch(&client_id_clone, switch);
+                    }
+                    let bytes = frame.len();
+                    if let Err(e) = ws_sender.send(frame).await {
                         error!("Failed to send message to client {}: {}", client_id_clone, e);
+                        let undelivered = iter::once(message).chain(rx.drain()).collect();
+                        stream_manager_clone.capture_dead_letters(&client_id_clone, DeadLetterReason::SendFailed { error: e.to_string() }, undelivered);
                         break;
                     }
+                    if live {
+                        stream_manager_clone.record_delivery_latency(&message);
+                    }
+                    stream_manager_clone.record_usage(&client_id_clone, message.stream_id(), bytes);
+                    stream_manager_clone.record_delivery(&client_id_clone, &message, bytes);
                 }
                 Err(e) => {
                     error!("Failed to serialize message for client {}: {}", client_id_clone, e);
@@ -87,11 +406,26 @@ async fn handle_connection(
         info!("Client {} disconnected", client_id_clone);
     });
 
-    // Handle incoming messages
-    while let Some(msg) = ws_receiver.next().await {
+    // Handle incoming messages, and session expiry in between
+    loop {
+        let msg = tokio::select! {
+            msg = ws_receiver.next() => msg,
+            _ = sleep_until_deadline(session.deadline()) => {
+                if !session.on_deadline(client_id, &stream_manager) {
+                    stream_manager.disconnect_client(&client_id, DisconnectReason::SessionExpired);
+                }
+                continue;
+            }
+            // Closed by the server, or the socket failed
+            _ = &mut outgoing => break,
+        };
+        let Some(msg) = msg else {
+            break;
+        };
+
         match msg {
             Ok(Message::Text(text)) => {
-                if let Err(e) = handle_message(&text, client_id, &stream_manager).await {
+                if let Err(e) = handle_message(&text, client_id, &stream_manager, &mut session, &mut clock_sync, &filters).await {
                     error!("Error handling message from client {}: {}", client_id, e);
 
                     // Send error response
@@ -102,7 +436,7 @@ async fn handle_connection(
                             stream_id: None,
                         };
 
-                        let _ = client_sender.send(error_message);
+                        let _ = client_sender.send(error_message.into());
                     }
                 }
             }
@@ -141,6 +475,9 @@ async fn handle_message(
     text: &str,
     client_id: Uuid,
     stream_manager: &Arc<StreamManager>,
+    session: &mut ConnectionSession,
+    clock_sync: &mut ClockSync,
+    filters: &watch::Sender<MessageFilters>,
 ) -> anyhow::Result<()> {
     let client_message: ClientMessage = serde_json::from_str(text)?;
     debug!("Received message from client {}: {:?}", client_id, client_message);
@@ -151,37 +488,60 @@ async fn handle_message(
             symbol,
             data_type,
             max_levels,
+            max_orders,
+            snapshot_interval_ms,
+            priority,
+            sample_every_n,
         } => {
-            match stream_manager
-                .subscribe(client_id, stream_id.clone(), symbol.clone(), data_type.clone(), max_levels)
-                .await
-            {
+            let spec = StreamSpec { stream_id, symbol, data_type, max_levels, max_orders, snapshot_interval_ms, priority, sample_every_n };
+            subscribe_stream(client_id, stream_manager, spec).await;
+        }
+        ClientMessage::SaveProfile { name, streams } => {
+            let count = streams.len();
+            let result = match &session.current {
+                Some(current) => session.profiles.save(&current.api_key, &name, streams),
+                None => Err("Profiles need an authenticated session".to_string()),
+            };
+            let response = match result {
                 Ok(()) => {
-                    if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
-                        let response = ServerMessage::Subscribed {
-                            stream_id,
-                            symbol,
-                            data_type,
-                        };
-
-                        if let Err(e) = client_sender.send(response) {
-                            error!("Failed to send subscription confirmation to client {}: {}", client_id, e);
-                        }
-                    }
+                    info!("Client {} saved profile {} with {} streams", client_id, name, count);
+                    ServerMessage::ProfileSaved { name, streams: count }
                 }
-                Err(e) => {
-                    error!("Failed to subscribe client {} to {}: {}", client_id, symbol, e);
+                Err(e) => ServerMessage::Error {
+                    code: 422,
+                    message: format!("Cannot save profile {}: {}", name, e),
+                    stream_id: None,
+                },
+            };
 
-                    if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
-                        let error_message = ServerMessage::Error {
-                            code: 500,
-                            message: format!("Subscription failed: {}", e),
-                            stream_id: Some(stream_id),
-                        };
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::LoadProfile { name } => {
+            let streams = session
+                .current
+                .as_ref()
+                .and_then(|current| session.profiles.load(&current.api_key, &name));
+            let Some(streams) = streams else {
+                if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                    let error_message = ServerMessage::Error {
+                        code: 404,
+                        message: format!("No profile {}", name),
+                        stream_id: None,
+                    };
 
-                        let _ = client_sender.send(error_message);
-                    }
+                    let _ = client_sender.send(error_message.into());
                 }
+                return Ok(());
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let response = ServerMessage::ProfileLoaded { name, streams: streams.clone() };
+                let _ = client_sender.send(response.into());
+            }
+            for spec in streams {
+                subscribe_stream(client_id, stream_manager, spec).await;
             }
         }
         ClientMessage::Unsubscribe { stream_id } => {
@@ -190,29 +550,565 @@ async fn handle_message(
             if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
                 if success {
                     let response = ServerMessage::Unsubscribed { stream_id };
-                    let _ = client_sender.send(response);
+                    let _ = client_sender.send(response.into());
                 } else {
                     let error_message = ServerMessage::Error {
                         code: 404,
                         message: "Stream not found".to_string(),
                         stream_id: Some(stream_id),
                     };
-                    let _ = client_sender.send(error_message);
+                    let _ = client_sender.send(error_message.into());
                 }
             }
         }
         ClientMessage::Ping { timestamp: _ } => {
             if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
-                let response = ServerMessage::HeartBeat {
-                    timestamp: Utc::now(),
-                };
+                let response = stream_manager.heartbeat(&client_id);
 
-                if let Err(e) = client_sender.send(response) {
+                if let Err(e) = client_sender.send(response.into()) {
                     error!("Failed to send ping response to client {}: {}", client_id, e);
                 }
             }
         }
+        ClientMessage::RenewSession { api_key, timestamp, signature } => {
+            let response = match session.renew(&api_key, timestamp, &signature) {
+                Ok(renewed) => {
+                    info!("Client {} renewed its session with API key {}", client_id, renewed.api_key);
+                    stream_manager.bind_api_key(client_id, &renewed.api_key);
+                    ServerMessage::SessionRenewed {
+                        token: renewed.token,
+                        expires_at: renewed.expires_at,
+                    }
+                }
+                Err(e) => {
+                    warn!("Session renewal failed for client {}: {}", client_id, e);
+                    ServerMessage::Error {
+                        code: 401,
+                        message: format!("Session renewal failed: {}", e),
+                        stream_id: None,
+                    }
+                }
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::GetQuota => {
+            let status = session
+                .current
+                .as_ref()
+                .and_then(|current| stream_manager.quota_status(&current.api_key));
+            let response = match status {
+                Some(status) => ServerMessage::Quota { status },
+                None => ServerMessage::Error {
+                    code: 404,
+                    message: "No quota applies to this connection".to_string(),
+                    stream_id: None,
+                },
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::Resync { stream_id } => {
+            if let Err(e) = stream_manager.resync_stream(client_id, &stream_id) {
+                // Without a stream id, so the client does not take it for a failed subscription
+                if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                    let error_message = ServerMessage::Error {
+                        code: 404,
+                        message: format!("Cannot resync: {}", e),
+                        stream_id: None,
+                    };
+
+                    let _ = client_sender.send(error_message.into());
+                }
+            }
+        }
+        ClientMessage::StreamStats { stream_id } => {
+            let response = match stream_manager.stream_stats(&client_id, &stream_id) {
+                Some(stats) => ServerMessage::StreamStats { stats },
+                None => ServerMessage::Error {
+                    code: 404,
+                    message: format!("No stream {}", stream_id),
+                    stream_id: Some(stream_id),
+                },
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::ReplayRequest { stream_id, symbol, from, to, speed, max_levels } => {
+            if let Err(e) = stream_manager.start_replay(client_id, stream_id.clone(), symbol, from, to, speed, max_levels) {
+                info!("Refused replay {} of client {}: {}", stream_id, client_id, e);
+
+                if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                    let error_message = ServerMessage::Error {
+                        code: 422,
+                        message: format!("Invalid replay: {}", e),
+                        stream_id: Some(stream_id),
+                    };
+
+                    let _ = client_sender.send(error_message.into());
+                }
+            }
+        }
+        ClientMessage::ValidateSubscribe { streams } => {
+            let api_key = session.current.as_ref().map(|current| current.api_key.as_str());
+            let response = ServerMessage::Validated {
+                streams: stream_manager.validate_streams(api_key, streams),
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::SnapshotManyRequest { request_id, symbols, max_levels } => {
+            let api_key = session.current.as_ref().map(|current| current.api_key.as_str());
+            let response = match stream_manager.bulk_snapshot(api_key, &symbols, max_levels.unwrap_or(20)) {
+                Ok(bulk) => ServerMessage::SnapshotMany {
+                    request_id,
+                    snapshots: bulk.snapshots,
+                    missing: bulk.missing,
+                },
+                Err(e) => ServerMessage::Error {
+                    code: 400,
+                    message: format!("Invalid snapshot request {}: {}", request_id, e),
+                    stream_id: None,
+                },
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::SyncClock => {
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(clock_sync.probe().into());
+            }
+        }
+        ClientMessage::ClockSyncReply { server_ts, client_ts } => {
+            let response = match clock_sync.complete(server_ts, client_ts) {
+                Ok(offset) => {
+                    debug!("Clock of client {} is {}us off, round trip {}us", client_id, offset.offset_us, offset.round_trip_us);
+                    ServerMessage::ClockSynced { offset }
+                }
+                Err(e) => ServerMessage::Error {
+                    code: 409,
+                    message: format!("Clock sync failed: {}", e),
+                    stream_id: None,
+                },
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::SetFilters { mute_heartbeats, min_interval_ms, symbols_mute } => {
+            let response = match MessageFilters::new(mute_heartbeats, min_interval_ms, symbols_mute.clone()) {
+                Ok(updated) => {
+                    filters.send_replace(updated);
+                    ServerMessage::FiltersSet { mute_heartbeats, min_interval_ms, symbols_mute }
+                }
+                Err(e) => ServerMessage::Error {
+                    code: 422,
+                    message: e,
+                    stream_id: None,
+                },
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::SetHeartbeatQuotes { symbols } => {
+            let api_key = session.current.as_ref().map(|current| current.api_key.as_str());
+            let response = match stream_manager.set_heartbeat_quotes(client_id, api_key, symbols) {
+                Ok(symbols) => {
+                    debug!("Client {} gets heartbeat quotes of {:?}", client_id, symbols);
+                    ServerMessage::HeartbeatQuotesSet { symbols }
+                }
+                Err(e) => ServerMessage::Error {
+                    code: 422,
+                    message: format!("Invalid heartbeat quotes: {}", e),
+                    stream_id: None,
+                },
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::MapSequence { symbol, sequence, venue_sequence } => {
+            let response = match stream_manager.map_sequence(&symbol, sequence, venue_sequence) {
+                Ok(mapping) => ServerMessage::SequenceMapped { symbol, mapping },
+                Err(e) => ServerMessage::Error {
+                    code: 422,
+                    message: format!("Invalid sequence mapping request: {}", e),
+                    stream_id: None,
+                },
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
+        ClientMessage::CandleBackfill { symbol, interval, from, to } => {
+            let responses: Vec<ServerMessage> = match stream_manager.candle_backfill(&symbol, &interval, from, to) {
+                Ok((interval_secs, candles)) => {
+                    // An empty history is still answered with one complete page
+                    let pages = candles.len().div_ceil(CANDLE_HISTORY_PAGE_SIZE).max(1);
+                    let mut candles = candles.into_iter();
+                    (1..=pages)
+                        .map(|page| ServerMessage::CandleHistory {
+                            symbol: symbol.clone(),
+                            interval_secs,
+                            candles: candles.by_ref().take(CANDLE_HISTORY_PAGE_SIZE).collect(),
+                            complete: page == pages,
+                        })
+                        .collect()
+                }
+                Err(e) => vec![ServerMessage::Error {
+                    code: 422,
+                    message: format!("Invalid candle backfill: {}", e),
+                    stream_id: None,
+                }],
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                for response in responses {
+                    if client_sender.send(response.into()).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+        ClientMessage::SubmitPaperOrder { symbol, side, quantity, limit_price, client_order_id } => {
+            // Accepted orders are answered with their updates and fills as they happen
+            let api_key = session.current.as_ref().map(|current| current.api_key.as_str());
+            let request = PaperOrderRequest { symbol, side, quantity, limit_price, client_order_id };
+            if let Err(e) = stream_manager.submit_paper_order(client_id, api_key, request) {
+                if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                    let error_message = ServerMessage::Error {
+                        code: 422,
+                        message: format!("Invalid paper order: {}", e),
+                        stream_id: None,
+                    };
+
+                    let _ = client_sender.send(error_message.into());
+                }
+            }
+        }
+        ClientMessage::CancelPaperOrder { order_id } => {
+            if let Err(e) = stream_manager.cancel_paper_order(client_id, order_id) {
+                if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                    let error_message = ServerMessage::Error {
+                        code: 422,
+                        message: format!("Invalid paper order cancel: {}", e),
+                        stream_id: None,
+                    };
+
+                    let _ = client_sender.send(error_message.into());
+                }
+            }
+        }
+        ClientMessage::SearchSymbols { search, symbol_type, status, limit } => {
+            let query = SymbolQuery { search, symbol_type, status, limit };
+            let response = ServerMessage::Symbols {
+                symbols: stream_manager.search_symbols(&query),
+            };
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let _ = client_sender.send(response.into());
+            }
+        }
     }
 
     Ok(())
-}
\ No newline at end of file
+}
+
+// Subscribes a stream of a Subscribe message or loaded profile, answering with Subscribed
+// or an Error for the stream
+async fn subscribe_stream(client_id: Uuid, stream_manager: &Arc<StreamManager>, spec: StreamSpec) {
+    let StreamSpec { stream_id, symbol, data_type, max_levels, max_orders, snapshot_interval_ms, priority, sample_every_n } = spec;
+    if let Err(e) = stream_manager.validate_strict(&symbol, &data_type, max_levels) {
+        info!("Refused subscription {} of client {}: {}", stream_id, client_id, e);
+
+        if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+            let error_message = ServerMessage::Error {
+                code: 422,
+                message: format!("Invalid subscription: {}", e),
+                stream_id: Some(stream_id),
+            };
+
+            let _ = client_sender.send(error_message.into());
+        }
+        return;
+    }
+
+    let granted = stream_manager.granted_levels(max_levels);
+    match stream_manager
+        .subscribe(
+            client_id,
+            stream_id.clone(),
+            symbol.clone(),
+            data_type.clone(),
+            Some(granted),
+            max_orders,
+            snapshot_interval_ms,
+            priority,
+            sample_every_n,
+        )
+        .await
+    {
+        Ok(()) => {
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let notice = stream_manager.throttle_notice(&stream_id, priority);
+                let adjusted = max_levels.filter(|requested| *requested != granted).map(|requested| {
+                    info!("Clamped stream {} of client {} from {} to {} levels", stream_id, client_id, requested, granted);
+                    ServerMessage::SubscriptionAdjusted { stream_id: stream_id.clone(), requested, granted }
+                });
+                let response = ServerMessage::Subscribed {
+                    stream_id,
+                    symbol,
+                    data_type,
+                };
+
+                if let Err(e) = client_sender.send(response.into()) {
+                    error!("Failed to send subscription confirmation to client {}: {}", client_id, e);
+                }
+                if let Some(adjusted) = adjusted {
+                    let _ = client_sender.send(adjusted.into());
+                }
+                if let Some(notice) = notice {
+                    let _ = client_sender.send(notice.into());
+                }
+            }
+        }
+        Err(e) => {
+            error!("Failed to subscribe client {} to {}: {}", client_id, symbol, e);
+
+            if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+                let error_message = ServerMessage::Error {
+                    code: 500,
+                    message: format!("Subscription failed: {}", e),
+                    stream_id: Some(stream_id),
+                };
+
+                let _ = client_sender.send(error_message.into());
+            }
+        }
+    }
+}
+
+// Session of one connection: warned shortly before expiry, then downgraded to an
+// unauthenticated client, or disconnected if signatures are required
+struct ConnectionSession {
+    authentication: Option<Authentication>,
+    profiles: Arc<ProfileStore>, // Saved under the session's API key
+    tenants: Arc<TenantRegistry>,
+    tenant: Option<String>,
+    peer: IpAddr,
+    current: Option<Session>,
+    warned: bool,
+}
+
+impl ConnectionSession {
+    // When the next warning or expiry is due
+    fn deadline(&self) -> Option<Instant> {
+        let current = self.current.as_ref()?;
+        let remaining = current.remaining();
+
+        if self.warned {
+            return Some(Instant::now() + remaining);
+        }
+
+        let warning = self
+            .authentication
+            .as_ref()
+            .map_or(remaining, |authentication| authentication.sessions.warning());
+        Some(Instant::now() + remaining.saturating_sub(warning))
+    }
+
+    // Sends the expiry warning or expires the session; returns false if the client must be disconnected
+    fn on_deadline(&mut self, client_id: Uuid, stream_manager: &StreamManager) -> bool {
+        let Some(current) = &self.current else {
+            return true;
+        };
+
+        let message = if !self.warned && !current.remaining().is_zero() {
+            self.warned = true;
+            ServerMessage::SessionExpiring {
+                expires_at: current.expires_at,
+            }
+        } else {
+            if let Some(authentication) = &self.authentication {
+                authentication.authenticator.audit_log().record(AuditEvent::SessionExpired {
+                    api_key: current.api_key.clone(),
+                    peer: Some(self.peer),
+                });
+            }
+            self.revoke();
+            stream_manager.unbind_api_key(&client_id);
+            ServerMessage::SessionExpired
+        };
+
+        let expired = matches!(message, ServerMessage::SessionExpired);
+        if let Some(client_sender) = stream_manager.get_client_sender(&client_id) {
+            let _ = client_sender.send(message.into());
+        }
+
+        if !expired {
+            return true;
+        }
+
+        // A tenant's client cannot fall back to the default universe
+        let disconnect = self.tenant.is_some()
+            || self
+                .authentication
+                .as_ref()
+                .is_some_and(|authentication| authentication.authenticator.requires_signature());
+        if disconnect {
+            info!("Session of client {} expired, disconnecting", client_id);
+        } else {
+            info!("Session of client {} expired, continuing unauthenticated", client_id);
+        }
+
+        !disconnect
+    }
+
+    fn renew(&mut self, api_key: &str, timestamp: i64, signature: &str) -> Result<Session, String> {
+        let Some(authentication) = &self.authentication else {
+            return Err("Sessions are not enabled".to_string());
+        };
+
+        if self.tenants.tenant_of(Some(api_key)) != self.tenant.as_deref() {
+            return Err("API key belongs to another tenant".to_string());
+        }
+
+        let signed = format!("timestamp={}", timestamp);
+        authentication
+            .authenticator
+            .verify(Some(api_key), Some(&signed), b"", Some(signature), Some(self.peer))
+            .map_err(|e| e.to_string())?;
+
+        let renewed = authentication.sessions.renew(self.current.as_ref(), api_key);
+        authentication.authenticator.audit_log().record(AuditEvent::SessionRenewed {
+            api_key: renewed.api_key.clone(),
+            peer: Some(self.peer),
+        });
+        self.current = Some(renewed.clone());
+        self.warned = false;
+
+        Ok(renewed)
+    }
+
+    fn revoke(&mut self) {
+        if let (Some(authentication), Some(current)) = (&self.authentication, self.current.take()) {
+            authentication.sessions.revoke(&current.token);
+        }
+    }
+}
+
+async fn sleep_until_deadline(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => future::pending().await,
+    }
+}
+
+// Runs on the upgrade request, before the WebSocket handshake completes
+struct HandshakeCheck<'a> {
+    peer_addr: SocketAddr,
+    rate_limiter: Option<&'a RateLimiter>,
+    authentication: Option<&'a Authentication>,
+    // Session of an authenticated upgrade, either resumed from its token or newly issued
+    session: &'a mut Option<Session>,
+    // Whether the upgrade asked for a clock-sync exchange
+    sync_clock: &'a mut bool,
+    encoding: &'a mut Encoding,
+}
+
+impl Callback for HandshakeCheck<'_> {
+    fn on_request(self, request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+        *self.sync_clock = request
+            .uri()
+            .query()
+            .and_then(|query| query_param(query, CLOCK_SYNC_PARAM))
+            .is_some_and(|value| value == "true" || value == "1");
+        if let Some(value) = request.uri().query().and_then(|query| query_param(query, ENCODING_PARAM)) {
+            *self.encoding = Encoding::parse(value).ok_or_else(|| bad_request("Unsupported encoding"))?;
+        }
+
+        if let Some(limiter) = self.rate_limiter {
+            limiter.check_ip(peer_ip(self.peer_addr)).map_err(too_many_requests)?;
+        }
+
+        let Some(authentication) = self.authentication else {
+            return Ok(response);
+        };
+
+        let peer = self.peer_addr.ip();
+        let audit = authentication.authenticator.audit_log();
+        let token = request.uri().query().and_then(|query| query_param(query, SESSION_TOKEN_PARAM));
+        if let Some(token) = token {
+            let Some(session) = authentication.sessions.resume(token) else {
+                audit.record(AuditEvent::AuthFailed {
+                    api_key: None,
+                    peer: Some(peer),
+                    reason: "Invalid or expired session".to_string(),
+                });
+                return Err(unauthorized("Invalid or expired session"));
+            };
+            self.check_key_rate(&session.api_key).map_err(too_many_requests)?;
+
+            audit.record(AuditEvent::SessionResumed {
+                api_key: session.api_key.clone(),
+                peer: Some(peer),
+            });
+            *self.session = Some(session);
+            return Ok(response);
+        }
+
+        let api_key = authentication
+            .authenticator
+            .verify_request(request.headers(), request.uri(), b"", Some(peer))
+            .map_err(|e| unauthorized(&e.to_string()))?;
+        if let Some(api_key) = &api_key {
+            self.check_key_rate(api_key).map_err(too_many_requests)?;
+        }
+        *self.session = api_key.map(|api_key| authentication.sessions.issue(&api_key));
+
+        Ok(response)
+    }
+}
+
+impl HandshakeCheck<'_> {
+    // Per-key limits only apply to keys the upgrade proved it holds
+    fn check_key_rate(&self, api_key: &str) -> Result<(), RateLimited> {
+        self.rate_limiter.map_or(Ok(()), |limiter| limiter.check_key(api_key))
+    }
+}
+
+fn bad_request(message: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message.to_string()));
+    *response.status_mut() = StatusCode::BAD_REQUEST;
+    response
+}
+
+fn unauthorized(message: &str) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some(message.to_string()));
+    *response.status_mut() = StatusCode::UNAUTHORIZED;
+    response
+}
+
+fn too_many_requests(limited: RateLimited) -> ErrorResponse {
+    let mut response = ErrorResponse::new(Some("Rate limit exceeded".to_string()));
+    *response.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+    if let Ok(value) = retry_after_secs(limited.retry_after).to_string().parse() {
+        response.headers_mut().insert(header::RETRY_AFTER, value);
+    }
+    response
+}
@@ -0,0 +1,105 @@
+use std::time::Duration;
+use market_depth_server::{ClientMessage, DataType, MarketDataUpdate, ServerMessage, TestClient, TestServer};
+
+// Book updates each chain is followed for
+const UPDATES: usize = 50;
+
+fn subscribe(stream_id: &str, data_type: DataType) -> ClientMessage {
+    ClientMessage::Subscribe {
+        stream_id: stream_id.to_string(),
+        symbol: "BTCUSD".to_string(),
+        data_type,
+        max_levels: Some(10),
+        max_orders: None,
+        snapshot_interval_ms: None,
+        priority: None,
+        sample_every_n: None,
+    }
+}
+
+// The next update of a stream with its sequence
+async fn next_update(client: &mut TestClient, stream: &str) -> anyhow::Result<(u64, MarketDataUpdate)> {
+    match client.recv_until(|message| matches!(message, ServerMessage::MarketData { stream_id, .. } if stream_id == stream)).await? {
+        ServerMessage::MarketData { data, sequence, .. } => Ok((sequence, data)),
+        _ => unreachable!(),
+    }
+}
+
+#[tokio::test]
+async fn subscribing_streams_market_data() -> anyhow::Result<()> {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await?;
+
+    client.send(&subscribe("book", DataType::MBP)).await?;
+    let confirmed = client.recv_until(|message| matches!(message, ServerMessage::Subscribed { .. })).await?;
+    assert!(matches!(confirmed, ServerMessage::Subscribed { stream_id, .. } if stream_id == "book"));
+
+    let (first, data) = next_update(&mut client, "book").await?;
+    let MarketDataUpdate::MBP { bids, asks } = data else {
+        panic!("MBP stream sent {:?}", data);
+    };
+    assert!(!bids.is_empty() && !asks.is_empty());
+    assert!(bids.len() <= 10 && asks.len() <= 10);
+    assert!(bids[0].price < asks[0].price);
+    assert!(bids.windows(2).all(|pair| pair[0].price > pair[1].price));
+    assert!(asks.windows(2).all(|pair| pair[0].price < pair[1].price));
+
+    server.advance(Duration::from_secs(1)).await;
+    let (next, _) = next_update(&mut client, "book").await?;
+    assert!(next > first);
+
+    client.close().await
+}
+
+#[tokio::test]
+async fn mbo_deltas_continue_from_the_book_sent() -> anyhow::Result<()> {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await?;
+    client.send(&subscribe("orders", DataType::MBODelta)).await?;
+
+    let (mut held, data) = next_update(&mut client, "orders").await?;
+    assert!(matches!(data, MarketDataUpdate::MBO { .. }), "MBODelta stream started with {:?}", data);
+
+    for _ in 0..UPDATES {
+        let (sequence, data) = next_update(&mut client, "orders").await?;
+        assert!(sequence > held);
+        match data {
+            // Changes apply to any book from `previous_sequence` on, so the ones held
+            // since carry over
+            MarketDataUpdate::MBODelta { previous_sequence, .. } => {
+                assert!(previous_sequence <= held, "delta from {} after {}", previous_sequence, held);
+            }
+            // The whole book again, after the stream lost events
+            MarketDataUpdate::MBO { .. } => {}
+            data => panic!("MBODelta stream sent {:?}", data),
+        }
+        held = sequence;
+    }
+
+    client.close().await
+}
+
+#[tokio::test]
+async fn mbp_deltas_continue_from_the_levels_sent() -> anyhow::Result<()> {
+    let server = TestServer::start().await;
+    let mut client = server.connect().await?;
+    client.send(&subscribe("levels", DataType::MBPDelta)).await?;
+
+    let (mut held, data) = next_update(&mut client, "levels").await?;
+    assert!(matches!(data, MarketDataUpdate::MBP { .. }), "MBPDelta stream started with {:?}", data);
+
+    for _ in 0..UPDATES {
+        let (sequence, data) = next_update(&mut client, "levels").await?;
+        assert!(sequence > held);
+        match data {
+            MarketDataUpdate::MBPDelta { previous_sequence, .. } => {
+                assert_eq!(previous_sequence, held, "delta does not start from the levels held");
+            }
+            MarketDataUpdate::MBP { .. } => {}
+            data => panic!("MBPDelta stream sent {:?}", data),
+        }
+        held = sequence;
+    }
+
+    client.close().await
+}
@@ -0,0 +1,611 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use market_depth_server::{
+    book_checksum, delta_checksum, ActivityType, BookAnchor, BookDiff, LevelChange, MBPLevel, MarketDataUpdate, OrderActivity, OrderChange,
+    OrderChangeAction, OrderId, Quantity, Side,
+};
+use serde::{Deserialize, Serialize};
+
+// Levels per side covered by the book checksum
+pub use market_depth_server::CHECKSUM_DEPTH;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct BookLevel {
+    pub price: f64,
+    pub quantity: Quantity,
+    pub order_count: u32,
+}
+
+// Level changes since `previous_sequence`; a level with zero quantity is removed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BookDelta {
+    pub previous_sequence: u64,
+    pub sequence: u64,
+    pub bids: Vec<BookLevel>,
+    pub asks: Vec<BookLevel>,
+    // Rolling checksum of the stream's deltas up to and including this one, when the
+    // producer sends it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
+}
+
+impl BookDelta {
+    // Producer side: the changes between the MBP views at both sequences, carrying the
+    // rolling checksum continued from `previous_checksum` when one is given
+    pub fn from_diff(previous_sequence: u64, sequence: u64, diff: &BookDiff, previous_checksum: Option<u32>) -> Self {
+        Self {
+            previous_sequence,
+            sequence,
+            bids: book_levels(&diff.bids),
+            asks: book_levels(&diff.asks),
+            checksum: previous_checksum.map(|previous| diff.checksum(previous)),
+        }
+    }
+
+    // Consumer side: the delta of an MBPDelta update sent at `sequence`, None for other updates
+    pub fn from_update(update: &MarketDataUpdate, sequence: u64) -> Option<Self> {
+        let MarketDataUpdate::MBPDelta { previous_sequence, bids, asks } = update else {
+            return None;
+        };
+        Some(Self { previous_sequence: *previous_sequence, sequence, bids: book_levels(bids), asks: book_levels(asks), checksum: None })
+    }
+}
+
+fn book_levels(changes: &[LevelChange]) -> Vec<BookLevel> {
+    changes.iter().map(|change| BookLevel { price: change.price, quantity: change.quantity, order_count: change.order_count }).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BookError {
+    // The book has no snapshot, or was invalidated by a gap or either checksum mismatch
+    NotSynced,
+    Gap { expected: u64, received: u64 },
+    ChecksumMismatch { expected: u32, actual: u32 },
+    DeltaChecksumMismatch { expected: u32, actual: u32 },
+    UnsupportedUpdate,
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookError::NotSynced => f.write_str("Book is waiting for a snapshot"),
+            BookError::Gap { expected, received } => {
+                write!(f, "Sequence gap: expected {}, received {}", expected, received)
+            }
+            BookError::ChecksumMismatch { expected, actual } => {
+                write!(f, "Checksum mismatch: expected {}, computed {}", expected, actual)
+            }
+            BookError::DeltaChecksumMismatch { expected, actual } => {
+                write!(f, "Delta checksum mismatch: expected {}, computed {}", expected, actual)
+            }
+            BookError::UnsupportedUpdate => f.write_str("Update does not carry a book"),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+// Client-side book rebuilt from a snapshot followed by level deltas or MBO activity.
+// A gap or checksum mismatch invalidates the book, and every later delta is rejected
+// with NotSynced until the next snapshot.
+#[derive(Debug, Clone)]
+pub struct LocalOrderBook {
+    symbol: String,
+    bids: BTreeMap<PriceKey, BookLevel>,
+    asks: BTreeMap<PriceKey, BookLevel>,
+    // Resting orders known from MBO snapshots and activity
+    orders: HashMap<OrderId, RestingOrder>,
+    // Last applied sequence, None until the first snapshot
+    sequence: Option<u64>,
+    // Rolling checksum of the deltas applied since the last snapshot or anchor
+    delta_checksum: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RestingOrder {
+    side: BookSide,
+    price: f64,
+    quantity: Quantity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum BookSide {
+    Bid,
+    Ask,
+}
+
+impl From<&Side> for BookSide {
+    fn from(side: &Side) -> Self {
+        match side {
+            Side::Bid => BookSide::Bid,
+            Side::Ask => BookSide::Ask,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct PriceKey(f64);
+
+impl Eq for PriceKey {}
+
+impl PartialOrd for PriceKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for PriceKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0)
+    }
+}
+
+impl LocalOrderBook {
+    pub fn new(symbol: &str) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            orders: HashMap::new(),
+            sequence: None,
+            delta_checksum: 0,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        &self.symbol
+    }
+
+    pub fn sequence(&self) -> Option<u64> {
+        self.sequence
+    }
+
+    pub fn is_synced(&self) -> bool {
+        self.sequence.is_some()
+    }
+
+    // Drops the book until the next snapshot, e.g. after a reconnect
+    pub fn invalidate(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.orders.clear();
+        self.sequence = None;
+        self.delta_checksum = 0;
+    }
+
+    // Replaces the book with an MBP or MBO snapshot
+    pub fn apply_snapshot(&mut self, update: &MarketDataUpdate, sequence: u64) -> Result<(), BookError> {
+        match update {
+            MarketDataUpdate::MBP { bids, asks } => self.apply_mbp_snapshot(bids, asks, sequence),
+            MarketDataUpdate::MBO { bids, asks } => {
+                self.invalidate();
+                for level in bids.iter().chain(asks) {
+                    self.add_order(level.order_id, BookSide::from(&level.side), level.price, level.quantity);
+                }
+            }
+            _ => return Err(BookError::UnsupportedUpdate),
+        }
+
+        self.sequence = Some(sequence);
+        Ok(())
+    }
+
+    pub fn apply_mbp_snapshot(&mut self, bids: &[MBPLevel], asks: &[MBPLevel], sequence: u64) {
+        self.invalidate();
+        for level in bids.iter().chain(asks) {
+            self.levels_mut(BookSide::from(&level.side)).insert(
+                PriceKey(level.price),
+                BookLevel {
+                    price: level.price,
+                    quantity: level.quantity,
+                    order_count: level.order_count,
+                },
+            );
+        }
+        self.sequence = Some(sequence);
+    }
+
+    // Invalidates the book when the delta carries a rolling checksum that does not match
+    // the deltas applied since the last snapshot or anchor
+    pub fn apply_delta(&mut self, delta: &BookDelta) -> Result<(), BookError> {
+        self.advance(delta.previous_sequence, delta.sequence)?;
+
+        for (side, levels) in [(BookSide::Bid, &delta.bids), (BookSide::Ask, &delta.asks)] {
+            let book = self.levels_mut(side);
+            for level in levels {
+                if level.quantity.is_zero() {
+                    book.remove(&PriceKey(level.price));
+                } else {
+                    book.insert(PriceKey(level.price), *level);
+                }
+            }
+        }
+
+        self.delta_checksum = delta_checksum(
+            self.delta_checksum,
+            delta.bids.iter().map(|level| (level.price, level.quantity)),
+            delta.asks.iter().map(|level| (level.price, level.quantity)),
+        );
+        if let Some(expected) = delta.checksum {
+            let actual = self.delta_checksum;
+            if actual != expected {
+                self.invalidate();
+                return Err(BookError::DeltaChecksumMismatch { expected, actual });
+            }
+        }
+
+        Ok(())
+    }
+
+    // Applies one order event; each event advances the book sequence by one. Updates to
+    // orders outside the snapshot's depth are ignored.
+    pub fn apply_activity(&mut self, activity: &OrderActivity, sequence: u64) -> Result<(), BookError> {
+        self.advance(sequence.saturating_sub(1), sequence)?;
+
+        match activity.activity_type {
+            ActivityType::Add => {
+                if let (Some(price), Some(quantity), Some(side)) = (activity.price, activity.quantity, &activity.side) {
+                    self.add_order(activity.order_id, BookSide::from(side), price, quantity);
+                }
+            }
+            ActivityType::Update => {
+                if let Some(quantity) = activity.quantity {
+                    self.set_order_quantity(activity.order_id, quantity);
+                }
+            }
+            ActivityType::Fill => match activity.quantity {
+                Some(remaining) if !remaining.is_zero() => self.set_order_quantity(activity.order_id, remaining),
+                _ => self.remove_order(activity.order_id),
+            },
+            ActivityType::Cancel => self.remove_order(activity.order_id),
+        }
+
+        Ok(())
+    }
+
+    // Applies the order changes of an MBODelta sent at `sequence`. Changes are each order's
+    // state afterwards, so a delta the book already holds is skipped and one starting at or
+    // before the book's sequence applies; one starting after it is a gap.
+    pub fn apply_order_changes(&mut self, previous_sequence: u64, changes: &[OrderChange], sequence: u64) -> Result<(), BookError> {
+        let current = self.sequence.ok_or(BookError::NotSynced)?;
+        if sequence <= current {
+            return Ok(());
+        }
+        if previous_sequence > current {
+            self.invalidate();
+            return Err(BookError::Gap { expected: current + 1, received: previous_sequence + 1 });
+        }
+
+        for change in changes {
+            match change.action {
+                OrderChangeAction::Add => {
+                    if let (Some(side), Some(price), Some(quantity)) = (&change.side, change.price, change.quantity) {
+                        self.add_order(change.order_id, BookSide::from(side), price, quantity);
+                    }
+                }
+                OrderChangeAction::Update => {
+                    if let Some(quantity) = change.quantity {
+                        self.set_order_quantity(change.order_id, quantity);
+                    }
+                }
+                OrderChangeAction::Delete => self.remove_order(change.order_id),
+            }
+        }
+
+        self.sequence = Some(sequence);
+        Ok(())
+    }
+
+    // Best first
+    pub fn bids(&self) -> impl Iterator<Item = &BookLevel> {
+        self.bids.values().rev()
+    }
+
+    // Best first
+    pub fn asks(&self) -> impl Iterator<Item = &BookLevel> {
+        self.asks.values()
+    }
+
+    pub fn best_bid(&self) -> Option<&BookLevel> {
+        self.bids().next()
+    }
+
+    pub fn best_ask(&self) -> Option<&BookLevel> {
+        self.asks().next()
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        Some(self.best_ask()?.price - self.best_bid()?.price)
+    }
+
+    // CRC32 of the top CHECKSUM_DEPTH levels per side, interleaved best first as
+    // `bid_price:bid_quantity:ask_price:ask_quantity:...`, a side that runs out skipped
+    pub fn checksum(&self) -> u32 {
+        book_checksum(
+            self.bids().map(|level| (level.price, level.quantity)),
+            self.asks().map(|level| (level.price, level.quantity)),
+        )
+    }
+
+    // Invalidates the book if it does not match the checksum sent by the server
+    pub fn verify_checksum(&mut self, expected: u32) -> Result<(), BookError> {
+        if !self.is_synced() {
+            return Err(BookError::NotSynced);
+        }
+
+        let actual = self.checksum();
+        if actual != expected {
+            self.invalidate();
+            return Err(BookError::ChecksumMismatch { expected, actual });
+        }
+
+        Ok(())
+    }
+
+    // Rolling checksum of the deltas applied since the last snapshot or anchor
+    pub fn delta_checksum(&self) -> u32 {
+        self.delta_checksum
+    }
+
+    // Checks the book against an anchor sent at its sequence, restarting the delta
+    // checksum once it matches; anchors of other sequences say nothing about it and pass
+    pub fn verify_anchor(&mut self, anchor: &BookAnchor) -> Result<(), BookError> {
+        if self.sequence.is_some_and(|sequence| sequence != anchor.sequence) {
+            return Ok(());
+        }
+        self.verify_checksum(anchor.checksum)?;
+        self.delta_checksum = 0;
+        Ok(())
+    }
+
+    fn advance(&mut self, previous: u64, sequence: u64) -> Result<(), BookError> {
+        let current = self.sequence.ok_or(BookError::NotSynced)?;
+        if previous != current {
+            self.invalidate();
+            return Err(BookError::Gap { expected: current + 1, received: previous + 1 });
+        }
+
+        self.sequence = Some(sequence);
+        Ok(())
+    }
+
+    fn levels_mut(&mut self, side: BookSide) -> &mut BTreeMap<PriceKey, BookLevel> {
+        match side {
+            BookSide::Bid => &mut self.bids,
+            BookSide::Ask => &mut self.asks,
+        }
+    }
+
+    fn add_order(&mut self, order_id: OrderId, side: BookSide, price: f64, quantity: Quantity) {
+        self.remove_order(order_id);
+        self.orders.insert(order_id, RestingOrder { side, price, quantity });
+
+        let level = self.levels_mut(side).entry(PriceKey(price)).or_insert(BookLevel {
+            price,
+            quantity: Quantity::ZERO,
+            order_count: 0,
+        });
+        level.quantity += quantity;
+        level.order_count += 1;
+    }
+
+    fn set_order_quantity(&mut self, order_id: OrderId, quantity: Quantity) {
+        let Some(order) = self.orders.get_mut(&order_id) else {
+            return;
+        };
+        let previous = std::mem::replace(&mut order.quantity, quantity);
+        let order = *order;
+
+        if let Some(level) = self.levels_mut(order.side).get_mut(&PriceKey(order.price)) {
+            level.quantity = level.quantity.saturating_sub(previous) + quantity;
+        }
+    }
+
+    fn remove_order(&mut self, order_id: OrderId) {
+        let Some(order) = self.orders.remove(&order_id) else {
+            return;
+        };
+
+        let levels = self.levels_mut(order.side);
+        let key = PriceKey(order.price);
+        if let Some(level) = levels.get_mut(&key) {
+            level.quantity = level.quantity.saturating_sub(order.quantity);
+            level.order_count = level.order_count.saturating_sub(1);
+            if level.order_count == 0 {
+                levels.remove(&key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use market_depth_server::MBOLevel;
+
+    fn mbp(side: Side, price: f64, units: u64, order_count: u32) -> MBPLevel {
+        MBPLevel {
+            price,
+            quantity: Quantity::from_units(units),
+            order_count,
+            side,
+            total_quantity: Quantity::from_units(units),
+            avg_age_ms: 0,
+        }
+    }
+
+    fn mbo(order_id: u64, side: Side, price: f64, units: u64) -> MBOLevel {
+        MBOLevel {
+            order_id: OrderId(order_id),
+            price,
+            quantity: Quantity::from_units(units),
+            side,
+            timestamp: Utc::now(),
+            age_ms: 0,
+            actor: None,
+        }
+    }
+
+    fn level(price: f64, units: u64, order_count: u32) -> BookLevel {
+        BookLevel { price, quantity: Quantity::from_units(units), order_count }
+    }
+
+    fn delta(previous_sequence: u64, sequence: u64, bids: Vec<BookLevel>, asks: Vec<BookLevel>) -> BookDelta {
+        BookDelta { previous_sequence, sequence, bids, asks, checksum: None }
+    }
+
+    fn synced() -> LocalOrderBook {
+        let mut book = LocalOrderBook::new("BTCUSD");
+        book.apply_mbp_snapshot(
+            &[mbp(Side::Bid, 99.0, 3, 1), mbp(Side::Bid, 100.0, 5, 2)],
+            &[mbp(Side::Ask, 102.0, 4, 1), mbp(Side::Ask, 101.0, 2, 1)],
+            10,
+        );
+        book
+    }
+
+    fn activity(activity_type: ActivityType, order_id: u64, side: Option<Side>, price: Option<f64>, units: Option<u64>) -> OrderActivity {
+        OrderActivity {
+            activity_type,
+            order_id: OrderId(order_id),
+            symbol: "BTCUSD".into(),
+            price,
+            quantity: units.map(Quantity::from_units),
+            side,
+            timestamp: Utc::now(),
+            actor: None,
+            venue_sequence: None,
+        }
+    }
+
+    #[test]
+    fn snapshots_sort_each_side_best_first() {
+        let book = synced();
+        assert_eq!(book.sequence(), Some(10));
+        assert_eq!(book.bids().copied().collect::<Vec<_>>(), vec![level(100.0, 5, 2), level(99.0, 3, 1)]);
+        assert_eq!(book.asks().copied().collect::<Vec<_>>(), vec![level(101.0, 2, 1), level(102.0, 4, 1)]);
+        assert_eq!(book.spread(), Some(1.0));
+    }
+
+    #[test]
+    fn deltas_replace_and_remove_levels() {
+        let mut book = synced();
+        book.apply_delta(&delta(10, 11, vec![level(100.0, 0, 0), level(99.5, 1, 1)], vec![level(101.0, 7, 3)])).unwrap();
+
+        assert_eq!(book.sequence(), Some(11));
+        assert_eq!(book.best_bid(), Some(&level(99.5, 1, 1)));
+        assert_eq!(book.best_ask(), Some(&level(101.0, 7, 3)));
+        assert_eq!(book.bids().count(), 2);
+    }
+
+    #[test]
+    fn gaps_invalidate_the_book_until_the_next_snapshot() {
+        let mut book = LocalOrderBook::new("BTCUSD");
+        assert_eq!(book.apply_delta(&delta(0, 1, Vec::new(), Vec::new())), Err(BookError::NotSynced));
+
+        let mut book = synced();
+        assert_eq!(book.apply_delta(&delta(12, 13, Vec::new(), Vec::new())), Err(BookError::Gap { expected: 11, received: 13 }));
+        assert!(!book.is_synced());
+        assert!(book.best_bid().is_none());
+        assert_eq!(book.apply_delta(&delta(13, 14, Vec::new(), Vec::new())), Err(BookError::NotSynced));
+
+        book.apply_snapshot(&MarketDataUpdate::MBP { bids: vec![mbp(Side::Bid, 98.0, 1, 1)], asks: Vec::new() }, 20).unwrap();
+        assert!(book.apply_delta(&delta(20, 21, Vec::new(), vec![level(99.0, 1, 1)])).is_ok());
+        assert_eq!(book.spread(), Some(1.0));
+    }
+
+    #[test]
+    fn checksums_match_the_servers_and_invalidate_on_mismatch() {
+        let mut book = synced();
+        let expected = book_checksum(
+            [(100.0, Quantity::from_units(5)), (99.0, Quantity::from_units(3))],
+            [(101.0, Quantity::from_units(2)), (102.0, Quantity::from_units(4))],
+        );
+        assert_eq!(book.checksum(), expected);
+        assert!(book.verify_checksum(expected).is_ok());
+
+        assert_eq!(book.verify_checksum(expected ^ 1), Err(BookError::ChecksumMismatch { expected: expected ^ 1, actual: expected }));
+        assert_eq!(book.verify_checksum(expected), Err(BookError::NotSynced));
+    }
+
+    #[test]
+    fn mbo_snapshots_and_activity_sum_orders_per_level() {
+        let mut book = LocalOrderBook::new("BTCUSD");
+        let snapshot = MarketDataUpdate::MBO {
+            bids: vec![mbo(1, Side::Bid, 100.0, 2), mbo(2, Side::Bid, 100.0, 3)],
+            asks: vec![mbo(3, Side::Ask, 101.0, 4)],
+        };
+        book.apply_snapshot(&snapshot, 5).unwrap();
+        assert_eq!(book.best_bid(), Some(&level(100.0, 5, 2)));
+
+        book.apply_activity(&activity(ActivityType::Fill, 1, None, None, Some(1)), 6).unwrap();
+        assert_eq!(book.best_bid(), Some(&level(100.0, 4, 2)));
+        book.apply_activity(&activity(ActivityType::Cancel, 2, None, None, None), 7).unwrap();
+        assert_eq!(book.best_bid(), Some(&level(100.0, 1, 1)));
+        book.apply_activity(&activity(ActivityType::Add, 4, Some(Side::Ask), Some(100.5), Some(6)), 8).unwrap();
+        assert_eq!(book.best_ask(), Some(&level(100.5, 6, 1)));
+        book.apply_activity(&activity(ActivityType::Fill, 4, None, None, Some(0)), 9).unwrap();
+        assert_eq!(book.best_ask(), Some(&level(101.0, 4, 1)));
+
+        assert_eq!(book.apply_activity(&activity(ActivityType::Cancel, 3, None, None, None), 11), Err(BookError::Gap { expected: 10, received: 11 }));
+        assert_eq!(
+            book.apply_snapshot(&MarketDataUpdate::MBPDelta { previous_sequence: 0, bids: Vec::new(), asks: Vec::new() }, 1),
+            Err(BookError::UnsupportedUpdate),
+        );
+    }
+
+    fn change(action: OrderChangeAction, order_id: u64, side: Option<Side>, price: Option<f64>, units: Option<u64>) -> OrderChange {
+        OrderChange { action, order_id: OrderId(order_id), side, price, quantity: units.map(Quantity::from_units) }
+    }
+
+    fn mbo_book() -> LocalOrderBook {
+        let mut book = LocalOrderBook::new("BTCUSD");
+        let snapshot = MarketDataUpdate::MBO {
+            bids: vec![mbo(1, Side::Bid, 100.0, 2), mbo(2, Side::Bid, 99.0, 3)],
+            asks: vec![mbo(3, Side::Ask, 101.0, 4)],
+        };
+        book.apply_snapshot(&snapshot, 10).unwrap();
+        book
+    }
+
+    #[test]
+    fn order_changes_add_update_and_delete_orders() {
+        let mut book = mbo_book();
+        let changes = [
+            change(OrderChangeAction::Add, 4, Some(Side::Bid), Some(100.0), Some(1)),
+            change(OrderChangeAction::Update, 3, None, None, Some(1)),
+            change(OrderChangeAction::Delete, 2, None, None, None),
+        ];
+        book.apply_order_changes(10, &changes, 12).unwrap();
+
+        assert_eq!(book.sequence(), Some(12));
+        assert_eq!(book.bids().copied().collect::<Vec<_>>(), vec![level(100.0, 3, 2)]);
+        assert_eq!(book.asks().copied().collect::<Vec<_>>(), vec![level(101.0, 1, 1)]);
+    }
+
+    #[test]
+    fn order_changes_carry_over_from_earlier_books() {
+        let mut book = mbo_book();
+        let add = [change(OrderChangeAction::Add, 4, Some(Side::Ask), Some(100.5), Some(2))];
+
+        // A delta starting before the book's sequence still applies, as changes are states
+        book.apply_order_changes(8, &add, 11).unwrap();
+        assert_eq!(book.best_ask(), Some(&level(100.5, 2, 1)));
+
+        // One the book already holds is skipped, so applying it twice does no harm
+        book.apply_order_changes(8, &add, 11).unwrap();
+        book.apply_order_changes(9, &[change(OrderChangeAction::Delete, 4, None, None, None)], 10).unwrap();
+        assert_eq!(book.best_ask(), Some(&level(100.5, 2, 1)));
+        assert_eq!(book.sequence(), Some(11));
+    }
+
+    #[test]
+    fn order_changes_after_a_gap_invalidate_the_book() {
+        let mut book = mbo_book();
+        assert_eq!(book.apply_order_changes(12, &[], 13), Err(BookError::Gap { expected: 11, received: 13 }));
+        assert!(!book.is_synced());
+        assert_eq!(book.apply_order_changes(13, &[], 14), Err(BookError::NotSynced));
+    }
+}
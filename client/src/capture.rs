@@ -0,0 +1,93 @@
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Lines};
+use std::path::Path;
+use chrono::{DateTime, Utc};
+use market_depth_server::MarketDataUpdate;
+use serde::Deserialize;
+
+// One market data message from a recording
+#[derive(Debug, Clone)]
+pub struct CaptureRecord {
+    pub stream_id: String,
+    pub symbol: String,
+    pub data: MarketDataUpdate,
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+}
+
+// Market data messages from the WebSocket server (tagged `type`) and SSE events
+// (tagged `event`) share these fields
+#[derive(Debug, Deserialize)]
+struct Frame {
+    #[serde(rename = "type", alias = "event")]
+    kind: String,
+    stream_id: Option<String>,
+    symbol: Option<String>,
+    data: Option<MarketDataUpdate>,
+    sequence: Option<u64>,
+    timestamp: Option<DateTime<Utc>>,
+}
+
+// Reads an NDJSON recording such as `md-cli -m ndjson` output, yielding its market
+// data messages in order. Other messages and blank lines are skipped; a line that
+// is not valid JSON is an InvalidData error naming the line.
+pub struct CaptureReader<R> {
+    lines: Lines<R>,
+    line: usize,
+}
+
+impl CaptureReader<BufReader<File>> {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self::new(BufReader::new(File::open(path)?)))
+    }
+}
+
+impl<R: BufRead> CaptureReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { lines: reader.lines(), line: 0 }
+    }
+
+    fn parse(&self, text: &str) -> io::Result<Option<CaptureRecord>> {
+        let frame: Frame = serde_json::from_str(text).map_err(|e| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", self.line, e))
+        })?;
+
+        if frame.kind != "MarketData" && frame.kind != "market_data" {
+            return Ok(None);
+        }
+        let (Some(stream_id), Some(data), Some(sequence)) = (frame.stream_id, frame.data, frame.sequence) else {
+            return Ok(None);
+        };
+
+        Ok(Some(CaptureRecord {
+            stream_id,
+            symbol: frame.symbol.unwrap_or_default(),
+            data,
+            sequence,
+            timestamp: frame.timestamp.unwrap_or_default(),
+        }))
+    }
+}
+
+impl<R: BufRead> Iterator for CaptureReader<R> {
+    type Item = io::Result<CaptureRecord>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let text = match self.lines.next()? {
+                Ok(text) => text,
+                Err(e) => return Some(Err(e)),
+            };
+            self.line += 1;
+
+            if text.trim().is_empty() {
+                continue;
+            }
+            match self.parse(&text) {
+                Ok(Some(record)) => return Some(Ok(record)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use chrono::{DateTime, Utc};
+use market_depth_server::{ClientMessage, ClockOffset, DataType, StreamPriority};
+use tokio::sync::{mpsc, watch};
+
+use crate::connection::{self, Command, UpdateSender};
+use crate::stream::{MboStream, MbpStream, UpdateStream};
+
+#[derive(Debug, Clone)]
+pub struct ClientOptions {
+    // Credentials for a signed handshake; the session it starts is renewed before expiry
+    // and resumed with its token after a reconnect
+    pub api_key: Option<String>,
+    pub api_secret: Option<String>,
+    // First reconnect delay, doubled after every failed attempt up to the maximum. Each
+    // wait is drawn between half the delay and the delay, so clients dropped together do
+    // not reconnect in lockstep.
+    pub reconnect_delay: Duration,
+    pub max_reconnect_delay: Duration,
+    // Consecutive failed attempts after which the circuit breaker opens: no attempt is
+    // made for the cooldown, then a single one, whose failure opens it again. None keeps
+    // retrying at the maximum delay.
+    pub circuit_breaker_failures: Option<u32>,
+    pub circuit_breaker_cooldown: Duration,
+    // Asks for the whole book this often on every book stream, at least a second apart; it
+    // replaces the local book even when a missed update went undetected
+    pub snapshot_interval: Option<Duration>,
+    // Priority of every stream; the server conflates low-priority streams first when under load
+    pub priority: Option<StreamPriority>,
+    // Measures the offset of the local clock from the server's on every connect
+    pub clock_sync: bool,
+}
+
+impl Default for ClientOptions {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            api_secret: None,
+            reconnect_delay: Duration::from_millis(500),
+            max_reconnect_delay: Duration::from_secs(30),
+            circuit_breaker_failures: Some(10),
+            circuit_breaker_cooldown: Duration::from_secs(300),
+            snapshot_interval: None,
+            priority: None,
+            clock_sync: false,
+        }
+    }
+}
+
+// State of the background connection. A connection lost within seconds of opening counts
+// as a failed attempt, so a server that accepts and then drops clients is backed off like
+// one refusing them.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConnectionStatus {
+    Connecting,
+    Connected,
+    // Waiting `delay` before the next attempt, after `failures` consecutive failed ones
+    Reconnecting { failures: u32, delay: Duration },
+    // No attempt is made until `until`
+    CircuitOpen { failures: u32, until: DateTime<Utc> },
+    // Disconnected by an operator, or every handle was dropped
+    Stopped,
+}
+
+// Async client for the WebSocket server. The connection runs in a background task that
+// reconnects after failures and restores every open subscription; once an operator
+// disconnects the client, its streams end and it stops.
+#[derive(Debug)]
+pub struct MarketDepthClient {
+    commands: mpsc::UnboundedSender<Command>,
+    next_stream: AtomicU64,
+    snapshot_interval_ms: Option<u64>,
+    priority: Option<StreamPriority>,
+    status: watch::Receiver<ConnectionStatus>,
+    clock_offset: watch::Receiver<Option<ClockOffset>>,
+}
+
+impl MarketDepthClient {
+    // Must be called inside a Tokio runtime; connecting happens in the background
+    pub fn connect(url: &str) -> Self {
+        Self::with_options(url, ClientOptions::default())
+    }
+
+    pub fn with_options(url: &str, options: ClientOptions) -> Self {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let snapshot_interval_ms = options.snapshot_interval.map(|interval| interval.as_millis() as u64);
+        let priority = options.priority;
+        let (status_sender, status) = watch::channel(ConnectionStatus::Connecting);
+        let (clock_offset_sender, clock_offset) = watch::channel(None);
+        tokio::spawn(connection::run(url.to_string(), options, receiver, status_sender, clock_offset_sender));
+
+        Self {
+            commands,
+            next_stream: AtomicU64::new(1),
+            snapshot_interval_ms,
+            priority,
+            status,
+            clock_offset,
+        }
+    }
+
+    // Current connection state; `changed()` on the receiver waits for the next transition
+    pub fn status(&self) -> watch::Receiver<ConnectionStatus> {
+        self.status.clone()
+    }
+
+    // Offset of the local clock from the server's, measured on the latest connect when
+    // `clock_sync` is set; `to_server_time` puts local receive times on the server's clock
+    pub fn clock_offset(&self) -> Option<ClockOffset> {
+        *self.clock_offset.borrow()
+    }
+
+    // Market by price book for a symbol, limited to `depth` levels per side
+    pub fn subscribe_mbp(&self, symbol: &str, depth: u32) -> anyhow::Result<MbpStream> {
+        let stream_id = self.next_stream_id("mbp", symbol);
+        let subscribe = ClientMessage::Subscribe {
+            stream_id: stream_id.clone(),
+            symbol: symbol.to_string(),
+            data_type: DataType::MBP,
+            max_levels: Some(depth),
+            max_orders: None,
+            snapshot_interval_ms: self.snapshot_interval_ms,
+            priority: self.priority,
+            sample_every_n: None,
+        };
+
+        let (updates, receiver) = mpsc::unbounded_channel();
+        self.subscribe(stream_id, subscribe, UpdateSender::Mbp(updates), receiver)
+    }
+
+    // Recorded market by price history of a symbol between `from` and `to`, replayed
+    // `speed` times faster than recorded (1 by default). The updates arrive like live ones
+    // and the stream ends after the last; a disconnect ends it early.
+    pub fn replay_mbp(
+        &self,
+        symbol: &str,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        speed: Option<f64>,
+        depth: u32,
+    ) -> anyhow::Result<MbpStream> {
+        let stream_id = self.next_stream_id("replay", symbol);
+        let subscribe = ClientMessage::ReplayRequest {
+            stream_id: stream_id.clone(),
+            symbol: symbol.to_string(),
+            from,
+            to,
+            speed,
+            max_levels: Some(depth),
+        };
+
+        let (updates, receiver) = mpsc::unbounded_channel();
+        self.subscribe(stream_id, subscribe, UpdateSender::Mbp(updates), receiver)
+    }
+
+    // Market by order book for a symbol: `depth` levels per side and up to `max_orders`
+    // orders per side, or the server default
+    pub fn subscribe_mbo(&self, symbol: &str, depth: u32, max_orders: Option<u32>) -> anyhow::Result<MboStream> {
+        let stream_id = self.next_stream_id("mbo", symbol);
+        let subscribe = ClientMessage::Subscribe {
+            stream_id: stream_id.clone(),
+            symbol: symbol.to_string(),
+            data_type: DataType::MBO,
+            max_levels: Some(depth),
+            max_orders,
+            snapshot_interval_ms: self.snapshot_interval_ms,
+            priority: self.priority,
+            sample_every_n: None,
+        };
+
+        let (updates, receiver) = mpsc::unbounded_channel();
+        self.subscribe(stream_id, subscribe, UpdateSender::Mbo(updates), receiver)
+    }
+
+    fn next_stream_id(&self, prefix: &str, symbol: &str) -> String {
+        format!("{}-{}-{}", prefix, symbol, self.next_stream.fetch_add(1, Ordering::Relaxed))
+    }
+
+    fn subscribe<T>(
+        &self,
+        stream_id: String,
+        subscribe: ClientMessage,
+        updates: UpdateSender,
+        receiver: mpsc::UnboundedReceiver<T>,
+    ) -> anyhow::Result<UpdateStream<T>> {
+        self.commands
+            .send(Command::Subscribe { stream_id: stream_id.clone(), subscribe, updates })
+            .map_err(|_| anyhow::anyhow!("Client connection task has stopped"))?;
+
+        Ok(UpdateStream::new(stream_id, receiver, self.commands.clone()))
+    }
+}
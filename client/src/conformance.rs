@@ -0,0 +1,169 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use market_depth_server::{MBPLevel, MarketDataUpdate};
+use serde::Serialize;
+
+use crate::book::LocalOrderBook;
+use crate::capture::CaptureRecord;
+
+// Bids and asks of a golden update
+type GoldenLevels = (Vec<MBPLevel>, Vec<MBPLevel>);
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum ConformanceIssue {
+    // Not after the stream's previous update
+    OutOfOrder { previous: u64 },
+    // No golden update of the symbol has this sequence
+    UnknownSequence,
+    // The stream carried another symbol before
+    SymbolChanged { expected: String },
+    ChecksumMismatch { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for ConformanceIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConformanceIssue::OutOfOrder { previous } => write!(f, "received after sequence {}", previous),
+            ConformanceIssue::UnknownSequence => f.write_str("not in the golden stream"),
+            ConformanceIssue::SymbolChanged { expected } => write!(f, "stream was for {}", expected),
+            ConformanceIssue::ChecksumMismatch { expected, actual } => {
+                write!(f, "checksum {} does not match the golden {}", actual, expected)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ConformanceFailure {
+    pub stream_id: String,
+    pub symbol: String,
+    pub sequence: u64,
+    #[serde(flatten)]
+    pub issue: ConformanceIssue,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StreamReport {
+    pub stream_id: String,
+    pub symbol: String,
+    pub checked: usize,
+    // Golden updates between two received ones, e.g. conflated by the server
+    pub skipped: usize,
+    pub first_sequence: Option<u64>,
+    pub last_sequence: Option<u64>,
+    // The stream reached the last golden update of its symbol
+    pub complete: bool,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ConformanceReport {
+    pub streams: Vec<StreamReport>,
+    pub failures: Vec<ConformanceFailure>,
+    // Market data other than MBP, which carries no comparable book
+    pub ignored: usize,
+}
+
+impl ConformanceReport {
+    pub fn passed(&self) -> bool {
+        self.failures.is_empty()
+    }
+}
+
+// Checks captured MBP streams against a golden stream: every update must be a golden
+// update of its symbol, in sequence order, with the same book. Books are compared by
+// checksum down to the depth the client subscribed to, so any `max_levels` certifies.
+#[derive(Debug, Default)]
+pub struct ConformanceVerifier {
+    // Golden levels of each symbol by sequence
+    golden: HashMap<String, BTreeMap<u64, GoldenLevels>>,
+    streams: BTreeMap<String, StreamReport>,
+    failures: Vec<ConformanceFailure>,
+    ignored: usize,
+}
+
+impl ConformanceVerifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_golden(&mut self, symbol: &str, sequence: u64, bids: Vec<MBPLevel>, asks: Vec<MBPLevel>) {
+        self.golden.entry(symbol.to_string()).or_default().insert(sequence, (bids, asks));
+    }
+
+    // A golden recording's updates, e.g. one written by `md-verify record`
+    pub fn add_golden_record(&mut self, record: CaptureRecord) -> bool {
+        match record.data {
+            MarketDataUpdate::MBP { bids, asks } => {
+                self.add_golden(&record.symbol, record.sequence, bids, asks);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    pub fn check(&mut self, record: &CaptureRecord) {
+        let MarketDataUpdate::MBP { bids, asks } = &record.data else {
+            self.ignored += 1;
+            return;
+        };
+
+        let stream = self.streams.entry(record.stream_id.clone()).or_insert_with(|| StreamReport {
+            stream_id: record.stream_id.clone(),
+            symbol: record.symbol.clone(),
+            ..StreamReport::default()
+        });
+        let fail = |issue| ConformanceFailure {
+            stream_id: record.stream_id.clone(),
+            symbol: record.symbol.clone(),
+            sequence: record.sequence,
+            issue,
+        };
+
+        if stream.symbol != record.symbol {
+            self.failures.push(fail(ConformanceIssue::SymbolChanged { expected: stream.symbol.clone() }));
+            return;
+        }
+        if let Some(previous) = stream.last_sequence.filter(|previous| record.sequence <= *previous) {
+            self.failures.push(fail(ConformanceIssue::OutOfOrder { previous }));
+            return;
+        }
+        let Some(golden) = self.golden.get(&record.symbol) else {
+            self.failures.push(fail(ConformanceIssue::UnknownSequence));
+            return;
+        };
+        let Some((golden_bids, golden_asks)) = golden.get(&record.sequence) else {
+            self.failures.push(fail(ConformanceIssue::UnknownSequence));
+            return;
+        };
+
+        let depth = bids.len().max(asks.len());
+        let expected = checksum(&golden_bids[..depth.min(golden_bids.len())], &golden_asks[..depth.min(golden_asks.len())]);
+        let actual = checksum(bids, asks);
+        if expected != actual {
+            self.failures.push(fail(ConformanceIssue::ChecksumMismatch { expected, actual }));
+        }
+
+        if let Some(previous) = stream.last_sequence {
+            stream.skipped += golden.range(previous + 1..record.sequence).count();
+        }
+        stream.first_sequence.get_or_insert(record.sequence);
+        stream.last_sequence = Some(record.sequence);
+        stream.complete = golden.keys().next_back() == Some(&record.sequence);
+        stream.checked += 1;
+    }
+
+    pub fn report(self) -> ConformanceReport {
+        ConformanceReport {
+            streams: self.streams.into_values().collect(),
+            failures: self.failures,
+            ignored: self.ignored,
+        }
+    }
+}
+
+fn checksum(bids: &[MBPLevel], asks: &[MBPLevel]) -> u32 {
+    let mut book = LocalOrderBook::new("");
+    book.apply_mbp_snapshot(bids, asks, 0);
+    book.checksum()
+}
@@ -0,0 +1,400 @@
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use market_depth_server::{ClientMessage, ClockOffset, DisconnectReason, MarketDataUpdate, ServerMessage, CLOCK_SYNC_PARAM, SESSION_TOKEN_PARAM};
+use tokio::net::TcpStream;
+use rand::Rng;
+use tokio::sync::{mpsc, watch};
+use tokio::time::sleep;
+use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
+use tracing::{debug, info, warn};
+
+use crate::client::{ClientOptions, ConnectionStatus};
+use crate::signing::{sign, signed_query};
+use crate::stream::{MboUpdate, MbpUpdate};
+
+type Socket = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+// Connections that stay open at least this long reset the backoff
+const STABLE_CONNECTION: Duration = Duration::from_secs(10);
+
+#[derive(Debug)]
+pub(crate) enum Command {
+    Subscribe {
+        stream_id: String,
+        subscribe: ClientMessage,
+        updates: UpdateSender,
+    },
+    Unsubscribe {
+        stream_id: String,
+    },
+    Resync {
+        stream_id: String,
+    },
+}
+
+// Where a subscription's updates go; its type matches the data type subscribed to
+#[derive(Debug)]
+pub(crate) enum UpdateSender {
+    Mbp(mpsc::UnboundedSender<MbpUpdate>),
+    Mbo(mpsc::UnboundedSender<MboUpdate>),
+}
+
+// A subscription kept across reconnects
+struct Subscription {
+    subscribe: ClientMessage,
+    updates: UpdateSender,
+    last_position: Option<(u64, u64)>, // Epoch and sequence of the last update
+    gap: bool,
+}
+
+// Connection state that outlives a single socket
+struct Connection {
+    url: String,
+    options: ClientOptions,
+    subscriptions: HashMap<String, Subscription>,
+    session_token: Option<String>,
+    clock_offset: watch::Sender<Option<ClockOffset>>,
+}
+
+enum Closed {
+    // Every client handle and stream was dropped
+    Shutdown,
+    // The socket was lost, or closed by the server for the reason given
+    Disconnected(Option<DisconnectReason>),
+}
+
+// Consecutive failed attempts, and how long to wait after them
+struct Backoff {
+    failures: u32,
+    base: Duration,
+    max: Duration,
+    circuit_breaker_failures: Option<u32>,
+    cooldown: Duration,
+}
+
+impl Backoff {
+    fn new(options: &ClientOptions) -> Self {
+        Self {
+            failures: 0,
+            base: options.reconnect_delay,
+            max: options.max_reconnect_delay,
+            circuit_breaker_failures: options.circuit_breaker_failures,
+            cooldown: options.circuit_breaker_cooldown,
+        }
+    }
+
+    // The base delay doubled for every failure, up to the maximum, with jitter
+    fn next_wait(&self) -> (Duration, ConnectionStatus) {
+        if self.circuit_breaker_failures.is_some_and(|threshold| self.failures >= threshold) {
+            let until = Utc::now() + chrono::Duration::from_std(self.cooldown).unwrap_or(chrono::Duration::MAX);
+            return (self.cooldown, ConnectionStatus::CircuitOpen { failures: self.failures, until });
+        }
+
+        let delay = self.base.saturating_mul(2u32.saturating_pow(self.failures.saturating_sub(1))).min(self.max);
+        let delay = delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0));
+        (delay, ConnectionStatus::Reconnecting { failures: self.failures, delay })
+    }
+}
+
+// Runs until the client and all of its streams are dropped
+pub(crate) async fn run(
+    url: String,
+    options: ClientOptions,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    status: watch::Sender<ConnectionStatus>,
+    clock_offset: watch::Sender<Option<ClockOffset>>,
+) {
+    let mut backoff = Backoff::new(&options);
+    let mut connection = Connection {
+        url,
+        options,
+        subscriptions: HashMap::new(),
+        session_token: None,
+        clock_offset,
+    };
+
+    loop {
+        status.send_replace(ConnectionStatus::Connecting);
+        match connect_async(connection.handshake_url()).await {
+            Ok((socket, _)) => {
+                info!("Connected to {}", connection.url);
+                status.send_replace(ConnectionStatus::Connected);
+                let connected = Instant::now();
+
+                let closed = connection.serve(socket, &mut commands).await;
+                if connected.elapsed() >= STABLE_CONNECTION {
+                    backoff.failures = 0;
+                } else {
+                    backoff.failures += 1;
+                }
+                match closed {
+                    Closed::Shutdown => {
+                        status.send_replace(ConnectionStatus::Stopped);
+                        return;
+                    }
+                    Closed::Disconnected(Some(reason)) if !reason.should_reconnect() => {
+                        // Ending every stream tells the caller
+                        warn!("Disconnected from {} by the server ({}), not reconnecting", connection.url, reason);
+                        status.send_replace(ConnectionStatus::Stopped);
+                        return;
+                    }
+                    Closed::Disconnected(Some(reason)) => warn!("Disconnected from {} by the server ({}), reconnecting", connection.url, reason),
+                    Closed::Disconnected(None) => warn!("Disconnected from {}, reconnecting", connection.url),
+                }
+            }
+            Err(e) => {
+                warn!("Failed to connect to {}: {}", connection.url, e);
+                // The token may have expired meanwhile; sign the next handshake instead
+                connection.session_token = None;
+                backoff.failures += 1;
+            }
+        }
+
+        // A replay would start over on the new socket, so an interrupted one ends instead
+        connection.subscriptions.retain(|stream_id, subscription| {
+            let replay = matches!(subscription.subscribe, ClientMessage::ReplayRequest { .. });
+            if replay {
+                warn!("Replay {} interrupted by the disconnect", stream_id);
+            }
+            !replay
+        });
+        for subscription in connection.subscriptions.values_mut() {
+            subscription.gap = true;
+        }
+
+        let (delay, waiting) = backoff.next_wait();
+        if let ConnectionStatus::CircuitOpen { failures, .. } = waiting {
+            warn!("{} consecutive failures connecting to {}, pausing for {:?}", failures, connection.url, delay);
+        }
+        status.send_replace(waiting);
+
+        let retry = sleep(delay);
+        tokio::pin!(retry);
+        loop {
+            tokio::select! {
+                _ = &mut retry => break,
+                command = commands.recv() => match command {
+                    Some(command) => connection.apply(command),
+                    None => {
+                        status.send_replace(ConnectionStatus::Stopped);
+                        return;
+                    }
+                },
+            }
+        }
+    }
+}
+
+impl Connection {
+    // Resumes the previous session if there is one, otherwise signs the handshake when
+    // credentials are configured
+    fn handshake_url(&self) -> String {
+        let query = if self.options.clock_sync { format!("{}=true", CLOCK_SYNC_PARAM) } else { String::new() };
+
+        if let Some(token) = &self.session_token {
+            let separator = if query.is_empty() { "" } else { "&" };
+            return format!("{}?{}{}{}={}", self.url, query, separator, SESSION_TOKEN_PARAM, token);
+        }
+
+        let (Some(api_key), Some(secret)) = (&self.options.api_key, &self.options.api_secret) else {
+            return if query.is_empty() { self.url.clone() } else { format!("{}?{}", self.url, query) };
+        };
+
+        format!("{}?{}", self.url, signed_query(&query, api_key, secret))
+    }
+
+    async fn serve(&mut self, socket: Socket, commands: &mut mpsc::UnboundedReceiver<Command>) -> Closed {
+        let (mut sink, mut source) = socket.split();
+
+        for subscription in self.subscriptions.values() {
+            if send(&mut sink, &subscription.subscribe).await.is_err() {
+                return Closed::Disconnected(None);
+            }
+        }
+
+        loop {
+            tokio::select! {
+                command = commands.recv() => {
+                    let Some(command) = command else {
+                        let _ = sink.close().await;
+                        return Closed::Shutdown;
+                    };
+
+                    let message = match &command {
+                        Command::Subscribe { subscribe, .. } => Some(subscribe.clone()),
+                        Command::Unsubscribe { stream_id } => self
+                            .subscriptions
+                            .contains_key(stream_id)
+                            .then(|| ClientMessage::Unsubscribe { stream_id: stream_id.clone() }),
+                        Command::Resync { stream_id } => self
+                            .subscriptions
+                            .contains_key(stream_id)
+                            .then(|| ClientMessage::Resync { stream_id: stream_id.clone() }),
+                    };
+                    self.apply(command);
+
+                    if let Some(message) = message {
+                        if send(&mut sink, &message).await.is_err() {
+                            return Closed::Disconnected(None);
+                        }
+                    }
+                }
+                message = source.next() => {
+                    let text = match message {
+                        Some(Ok(Message::Text(text))) => text,
+                        Some(Ok(Message::Close(frame))) => {
+                            let reason = frame.and_then(|frame| DisconnectReason::from_close_code(frame.code.into()));
+                            return Closed::Disconnected(reason);
+                        }
+                        None => return Closed::Disconnected(None),
+                        Some(Ok(_)) => continue,
+                        Some(Err(e)) => {
+                            warn!("WebSocket error from {}: {}", self.url, e);
+                            return Closed::Disconnected(None);
+                        }
+                    };
+
+                    let message = match serde_json::from_str::<ServerMessage>(&text) {
+                        Ok(message) => message,
+                        Err(e) => {
+                            warn!("Failed to parse message from {}: {}", self.url, e);
+                            continue;
+                        }
+                    };
+
+                    if let Some(reply) = self.handle(message) {
+                        if send(&mut sink, &reply).await.is_err() {
+                            return Closed::Disconnected(None);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    // Updates the subscriptions restored on every connect
+    fn apply(&mut self, command: Command) {
+        match command {
+            Command::Subscribe { stream_id, subscribe, updates } => {
+                self.subscriptions.insert(stream_id, Subscription { subscribe, updates, last_position: None, gap: false });
+            }
+            Command::Unsubscribe { stream_id } => {
+                self.subscriptions.remove(&stream_id);
+            }
+            // The view sent after a reconnect replaces the book anyway
+            Command::Resync { .. } => {}
+        }
+    }
+
+    // Routes a server message, returning the reply to send, if any
+    fn handle(&mut self, message: ServerMessage) -> Option<ClientMessage> {
+        match message {
+            ServerMessage::MarketData { stream_id, symbol, data, sequence, epoch, timestamp, event_ts, send_ts, anchor, latency_us, venue_sequence } => {
+                let subscription = self.subscriptions.get_mut(&stream_id)?;
+                // A sequence that starts over in a later epoch is a recreated book, not a gap
+                let gap = subscription.gap || subscription.last_position.is_some_and(|last| (epoch, sequence) < last);
+                if gap {
+                    debug!("Gap in stream {} at sequence {} of epoch {}", stream_id, sequence, epoch);
+                } else if subscription.last_position.is_some_and(|(last_epoch, _)| epoch > last_epoch) {
+                    info!("Book of stream {} was recreated, now at epoch {}", stream_id, epoch);
+                }
+
+                subscription.gap = false;
+                subscription.last_position = Some((epoch, sequence));
+                let symbol = symbol.to_string();
+                match (&subscription.updates, data) {
+                    (UpdateSender::Mbp(updates), MarketDataUpdate::MBP { bids, asks }) => {
+                        let _ = updates.send(MbpUpdate { symbol, bids, asks, sequence, epoch, timestamp, event_ts, send_ts, gap, anchor, latency_us, venue_sequence });
+                    }
+                    (UpdateSender::Mbo(updates), MarketDataUpdate::MBO { bids, asks }) => {
+                        let _ = updates.send(MboUpdate { symbol, bids, asks, sequence, epoch, timestamp, event_ts, send_ts, gap, anchor, latency_us, venue_sequence });
+                    }
+                    _ => {}
+                }
+            }
+            ServerMessage::Snapshot { stream_id, symbol, data, sequence, epoch, timestamp } => {
+                // The whole book replaces the local one, so it goes out as an update that heals any gap
+                let subscription = self.subscriptions.get_mut(&stream_id)?;
+                subscription.gap = false;
+                subscription.last_position = None;
+
+                return self.handle(ServerMessage::MarketData {
+                    stream_id,
+                    symbol: symbol.into(),
+                    data,
+                    sequence,
+                    epoch,
+                    timestamp,
+                    event_ts: timestamp,
+                    send_ts: timestamp,
+                    anchor: None,
+                    latency_us: None,
+                    venue_sequence: None,
+                });
+            }
+            ServerMessage::SessionStarted { token, .. } | ServerMessage::SessionRenewed { token, .. } => {
+                self.session_token = Some(token);
+            }
+            ServerMessage::SessionExpiring { .. } => {
+                let (Some(api_key), Some(secret)) = (&self.options.api_key, &self.options.api_secret) else {
+                    return None;
+                };
+
+                let timestamp = Utc::now().timestamp_millis();
+                return Some(ClientMessage::RenewSession {
+                    api_key: api_key.clone(),
+                    timestamp,
+                    signature: sign(secret, &format!("timestamp={}", timestamp)),
+                });
+            }
+            ServerMessage::SessionExpired => {
+                self.session_token = None;
+            }
+            ServerMessage::ClockSync { server_ts } => {
+                return Some(ClientMessage::ClockSyncReply { server_ts, client_ts: Utc::now() });
+            }
+            ServerMessage::ClockSynced { offset } => {
+                debug!("Local clock is {}us off the server's, round trip {}us", offset.offset_us, offset.round_trip_us);
+                self.clock_offset.send_replace(Some(offset));
+            }
+            ServerMessage::QuotaExceeded { quota } => {
+                // The server dropped every stream; ending them tells the caller
+                warn!("{:?} {:?} quota of {} exceeded, closing all streams", quota.period, quota.unit, quota.limit);
+                self.subscriptions.clear();
+            }
+            ServerMessage::Throttled { stream_ids, interval_ms, .. } => match interval_ms {
+                Some(interval_ms) => info!("Server under load, streams {:?} conflated to one update per {}ms", stream_ids, interval_ms),
+                None => info!("Streams {:?} back at full rate", stream_ids),
+            },
+            ServerMessage::ReplayComplete { stream_id } => {
+                // The whole window was sent; ending the stream tells the caller
+                self.subscriptions.remove(&stream_id);
+            }
+            ServerMessage::Error { code, message, stream_id } => {
+                // Stream errors are failed subscriptions; ending the stream tells the caller
+                match stream_id.filter(|stream_id| self.subscriptions.remove(stream_id).is_some()) {
+                    Some(stream_id) => warn!("Stream {} closed by server ({}): {}", stream_id, code, message),
+                    None => warn!("Server error ({}): {}", code, message),
+                }
+            }
+            _ => {}
+        }
+
+        None
+    }
+}
+
+async fn send(
+    sink: &mut futures_util::stream::SplitSink<Socket, Message>,
+    message: &ClientMessage,
+) -> Result<(), tokio_tungstenite::tungstenite::Error> {
+    match serde_json::to_string(message) {
+        Ok(json) => sink.send(Message::Text(json)).await,
+        Err(e) => {
+            warn!("Failed to serialize client message: {}", e);
+            Ok(())
+        }
+    }
+}
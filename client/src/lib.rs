@@ -0,0 +1,25 @@
+pub mod book;
+pub mod capture;
+pub mod conformance;
+#[cfg(feature = "net")]
+pub mod client;
+#[cfg(feature = "net")]
+mod connection;
+#[cfg(feature = "python")]
+mod python;
+pub mod signing;
+pub mod spec;
+pub mod sse;
+#[cfg(feature = "net")]
+pub mod stream;
+
+pub use book::*;
+pub use capture::*;
+pub use conformance::*;
+#[cfg(feature = "net")]
+pub use client::*;
+pub use signing::*;
+pub use spec::*;
+pub use sse::*;
+#[cfg(feature = "net")]
+pub use stream::*;
@@ -0,0 +1,445 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use futures_util::{SinkExt, StreamExt};
+use market_depth_client::{signed_query, SseDecoder, StreamSpec};
+use market_depth_server::{ClientMessage, DisconnectReason};
+use serde::Deserialize;
+use tokio::time::{sleep, sleep_until, timeout_at, Instant};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing::{debug, warn};
+use tracing_subscriber::EnvFilter;
+
+#[derive(Parser, Debug)]
+#[command(name = "md-loadgen", author, version, about = "Load-test the market depth servers with many concurrent clients", long_about = None)]
+struct Args {
+    /// WebSocket (ws://) or SSE (http://) endpoint
+    #[arg(short, long, default_value = "ws://127.0.0.1:8080/")]
+    url: String,
+
+    /// Concurrent clients
+    #[arg(short, long, default_value_t = 100)]
+    clients: usize,
+
+    /// Stream in the subscription mix as SYMBOL:TYPE[:LEVELS][@WEIGHT], repeatable
+    #[arg(short, long = "stream", value_name = "SPEC", required = true)]
+    streams: Vec<WeightedSpec>,
+
+    /// Streams each client subscribes to, drawn from the mix by weight
+    #[arg(long, default_value_t = 1)]
+    streams_per_client: usize,
+
+    /// Test length in seconds, ramp-up included
+    #[arg(short, long, default_value_t = 30)]
+    duration: u64,
+
+    /// Seconds over which client connections are spread
+    #[arg(long, default_value_t = 5)]
+    ramp_up: u64,
+
+    /// Seconds between progress lines on stderr, 0 to disable
+    #[arg(long, default_value_t = 5)]
+    report_interval: u64,
+
+    /// Print the summary as JSON
+    #[arg(long)]
+    json: bool,
+
+    /// API key to sign every connection with
+    #[arg(long, requires = "api_secret")]
+    api_key: Option<String>,
+
+    /// API secret to sign every connection with
+    #[arg(long, requires = "api_key")]
+    api_secret: Option<String>,
+
+    /// Log level (trace, debug, info, warn, error)
+    #[arg(long, default_value = "warn")]
+    log_level: String,
+}
+
+#[derive(Debug, Clone)]
+struct WeightedSpec {
+    spec: StreamSpec,
+    weight: usize,
+}
+
+impl FromStr for WeightedSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (spec, weight) = match s.rsplit_once('@') {
+            Some((spec, weight)) => {
+                let weight = weight.parse().map_err(|_| format!("Invalid weight {}", weight))?;
+                (spec, weight)
+            }
+            None => (s, 1),
+        };
+        if weight == 0 {
+            return Err("Weight must be at least 1".to_string());
+        }
+
+        Ok(Self { spec: spec.parse()?, weight })
+    }
+}
+
+// Counters shared by every client task
+#[derive(Debug, Default)]
+struct Stats {
+    connected: AtomicU64,
+    connect_failures: AtomicU64,
+    disconnects: AtomicU64,
+    // Disconnects the server made for falling behind, a sign the load generator itself is saturated
+    slow_consumers: AtomicU64,
+    server_errors: AtomicU64,
+    messages: AtomicU64,
+    bytes: AtomicU64,
+    sequence_regressions: AtomicU64,
+    parse_failures: AtomicU64,
+}
+
+impl Stats {
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// The fields of WebSocket messages (tagged `type`) and SSE events (tagged `event`)
+// needed for measuring; the book itself is not parsed
+#[derive(Debug, Deserialize)]
+struct Frame {
+    #[serde(rename = "type", alias = "event")]
+    kind: String,
+    stream_id: Option<String>,
+    sequence: Option<u64>,
+    timestamp: Option<DateTime<Utc>>,
+    send_ts: Option<DateTime<Utc>>,
+    // Of the terminal SSE error event
+    reason: Option<DisconnectReason>,
+}
+
+// What one client saw, merged into the summary when the test ends
+#[derive(Debug, Default)]
+struct ClientReport {
+    // Receive time minus the server's send timestamp, in microseconds
+    latencies_us: Vec<u64>,
+    last_sequence: HashMap<String, u64>,
+    streams: usize,
+}
+
+impl ClientReport {
+    fn handle(&mut self, text: &str, stats: &Stats) {
+        stats.messages.fetch_add(1, Ordering::Relaxed);
+        stats.bytes.fetch_add(text.len() as u64, Ordering::Relaxed);
+
+        let frame = match serde_json::from_str::<Frame>(text) {
+            Ok(frame) => frame,
+            Err(e) => {
+                debug!("Unparseable message: {}", e);
+                Stats::count(&stats.parse_failures);
+                return;
+            }
+        };
+
+        match frame.kind.as_str() {
+            "MarketData" | "market_data" => {
+                if let Some(timestamp) = frame.send_ts.or(frame.timestamp) {
+                    let latency = (Utc::now() - timestamp).num_microseconds().unwrap_or(0).max(0);
+                    self.latencies_us.push(latency as u64);
+                }
+                if let (Some(stream_id), Some(sequence)) = (frame.stream_id, frame.sequence) {
+                    let last = self.last_sequence.insert(stream_id, sequence);
+                    if last.is_some_and(|last| sequence < last) {
+                        Stats::count(&stats.sequence_regressions);
+                    }
+                }
+            }
+            "error" if frame.reason == Some(DisconnectReason::SlowConsumer) => Stats::count(&stats.slow_consumers),
+            "Error" | "error" | "QuotaExceeded" | "quota_exceeded" => Stats::count(&stats.server_errors),
+            _ => {}
+        }
+    }
+}
+
+// The streams client `index` subscribes to: the next distinct entries of the mix
+// expanded by weight, so the whole fleet follows the weights without randomness
+fn client_specs(mix: &[StreamSpec], index: usize, count: usize) -> Vec<StreamSpec> {
+    let mut specs: Vec<StreamSpec> = Vec::new();
+    for i in 0..mix.len() {
+        if specs.len() == count {
+            break;
+        }
+        let spec = &mix[(index * count + i) % mix.len()];
+        if !specs.iter().any(|existing| existing.stream_id() == spec.stream_id()) {
+            specs.push(spec.clone());
+        }
+    }
+    specs
+}
+
+fn signed(args: &Args, url: &str, query: &str) -> String {
+    let query = match (&args.api_key, &args.api_secret) {
+        (Some(api_key), Some(secret)) => signed_query(query, api_key, secret),
+        _ => query.to_string(),
+    };
+    if query.is_empty() {
+        url.to_string()
+    } else {
+        format!("{}?{}", url, query)
+    }
+}
+
+async fn run_websocket_client(
+    args: Arc<Args>,
+    specs: Vec<StreamSpec>,
+    stats: Arc<Stats>,
+    deadline: Instant,
+) -> anyhow::Result<ClientReport> {
+    let mut report = ClientReport { streams: specs.len(), ..ClientReport::default() };
+    let (socket, _) = connect_async(signed(&args, &args.url, "")).await?;
+    Stats::count(&stats.connected);
+    let (mut sink, mut source) = socket.split();
+
+    for spec in &specs {
+        let subscribe = ClientMessage::Subscribe {
+            stream_id: spec.stream_id(),
+            symbol: spec.symbol.clone(),
+            data_type: spec.data_type(),
+            max_levels: Some(spec.levels),
+            max_orders: None,
+            snapshot_interval_ms: None,
+            priority: None,
+            sample_every_n: None,
+        };
+        sink.send(Message::Text(serde_json::to_string(&subscribe)?)).await?;
+    }
+
+    loop {
+        match timeout_at(deadline, source.next()).await {
+            Err(_) => {
+                let _ = sink.close().await;
+                return Ok(report);
+            }
+            Ok(Some(Ok(Message::Text(text)))) => report.handle(&text, &stats),
+            Ok(Some(Ok(Message::Close(frame)))) => {
+                let reason = frame.and_then(|frame| DisconnectReason::from_close_code(frame.code.into()));
+                if reason == Some(DisconnectReason::SlowConsumer) {
+                    Stats::count(&stats.slow_consumers);
+                }
+                break;
+            }
+            Ok(None) => break,
+            Ok(Some(Ok(_))) => {}
+            Ok(Some(Err(e))) => {
+                debug!("WebSocket error: {}", e);
+                break;
+            }
+        }
+    }
+
+    Stats::count(&stats.disconnects);
+    Ok(report)
+}
+
+async fn run_sse_client(
+    args: Arc<Args>,
+    http: reqwest::Client,
+    specs: Vec<StreamSpec>,
+    stats: Arc<Stats>,
+    deadline: Instant,
+) -> anyhow::Result<ClientReport> {
+    let mut report = ClientReport { streams: specs.len(), ..ClientReport::default() };
+    let base = args.url.trim_end_matches('/');
+    let endpoint = if base.ends_with("/stream") { base.to_string() } else { format!("{}/stream", base) };
+    let definitions: Vec<String> = specs.iter().map(StreamSpec::definition).collect();
+    let url = signed(&args, &endpoint, &format!("streams={}", definitions.join(",")));
+
+    let response = http.get(url).send().await?.error_for_status()?;
+    Stats::count(&stats.connected);
+    let mut body = response.bytes_stream();
+    let mut decoder = SseDecoder::new();
+
+    loop {
+        match timeout_at(deadline, body.next()).await {
+            Err(_) => return Ok(report),
+            Ok(Some(Ok(chunk))) => {
+                for data in decoder.push(&chunk) {
+                    report.handle(&data, &stats);
+                }
+            }
+            Ok(Some(Err(e))) => {
+                debug!("SSE stream error: {}", e);
+                break;
+            }
+            Ok(None) => break,
+        }
+    }
+
+    Stats::count(&stats.disconnects);
+    Ok(report)
+}
+
+fn percentile(sorted: &[u64], quantile: f64) -> f64 {
+    if sorted.is_empty() {
+        return 0.0;
+    }
+    let index = ((sorted.len() - 1) as f64 * quantile).round() as usize;
+    sorted[index] as f64 / 1000.0
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Arc::new(Args::parse());
+
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&args.log_level)))
+        .with_writer(std::io::stderr)
+        .init();
+
+    let websocket = if args.url.starts_with("ws://") {
+        true
+    } else if args.url.starts_with("http://") {
+        false
+    } else {
+        anyhow::bail!("Expected a ws:// or http:// URL, got {}", args.url)
+    };
+
+    let mix: Vec<StreamSpec> = args
+        .streams
+        .iter()
+        .flat_map(|weighted| std::iter::repeat_n(weighted.spec.clone(), weighted.weight))
+        .collect();
+    let stats = Arc::new(Stats::default());
+    let http = reqwest::Client::builder().build().context("Failed to build HTTP client")?;
+
+    let started = Instant::now();
+    let deadline = started + Duration::from_secs(args.duration);
+    let ramp_up = Duration::from_secs(args.ramp_up).min(Duration::from_secs(args.duration));
+
+    let mut tasks = Vec::with_capacity(args.clients);
+    for index in 0..args.clients {
+        let specs = client_specs(&mix, index, args.streams_per_client.max(1));
+        let (args, stats, http) = (args.clone(), stats.clone(), http.clone());
+        let start = started + ramp_up.mul_f64(index as f64 / args.clients.max(1) as f64);
+
+        tasks.push(tokio::spawn(async move {
+            sleep_until(start).await;
+            let result = if websocket {
+                run_websocket_client(args, specs, stats.clone(), deadline).await
+            } else {
+                run_sse_client(args, http, specs, stats.clone(), deadline).await
+            };
+            if let Err(e) = &result {
+                debug!("Client {} failed: {:#}", index, e);
+                Stats::count(&stats.connect_failures);
+            }
+            result.ok()
+        }));
+    }
+
+    if args.report_interval > 0 {
+        let (stats, interval) = (stats.clone(), Duration::from_secs(args.report_interval));
+        tokio::spawn(async move {
+            let mut previous = (0, 0);
+            loop {
+                sleep(interval).await;
+                let messages = stats.messages.load(Ordering::Relaxed);
+                let bytes = stats.bytes.load(Ordering::Relaxed);
+                let connected = stats.connected.load(Ordering::Relaxed) - stats.disconnects.load(Ordering::Relaxed);
+                eprintln!(
+                    "[{:>4}s] {} connected, {:.0} msg/s, {:.1} KiB/s",
+                    started.elapsed().as_secs(),
+                    connected,
+                    (messages - previous.0) as f64 / interval.as_secs_f64(),
+                    (bytes - previous.1) as f64 / 1024.0 / interval.as_secs_f64(),
+                );
+                previous = (messages, bytes);
+            }
+        });
+    }
+
+    let mut latencies = Vec::new();
+    let (mut streams, mut stalled) = (0, 0);
+    for task in tasks {
+        if let Ok(Some(report)) = task.await {
+            streams += report.streams;
+            stalled += report.streams.saturating_sub(report.last_sequence.len());
+            latencies.extend(report.latencies_us);
+        }
+    }
+    latencies.sort_unstable();
+
+    let elapsed = started.elapsed().as_secs_f64();
+    let messages = stats.messages.load(Ordering::Relaxed);
+    let bytes = stats.bytes.load(Ordering::Relaxed);
+    let connect_failures = stats.connect_failures.load(Ordering::Relaxed);
+    if connect_failures > 0 {
+        warn!("{} of {} clients failed to connect; run with --log-level debug for details", connect_failures, args.clients);
+    }
+
+    let summary = serde_json::json!({
+        "url": args.url,
+        "clients": args.clients,
+        "duration_secs": elapsed,
+        "connected": stats.connected.load(Ordering::Relaxed),
+        "connect_failures": connect_failures,
+        "disconnects": stats.disconnects.load(Ordering::Relaxed),
+        "slow_consumer_disconnects": stats.slow_consumers.load(Ordering::Relaxed),
+        "streams": streams,
+        "stalled_streams": stalled,
+        "messages": messages,
+        "messages_per_sec": messages as f64 / elapsed,
+        "bytes": bytes,
+        "bytes_per_sec": bytes as f64 / elapsed,
+        "latency_ms": {
+            "samples": latencies.len(),
+            "p50": percentile(&latencies, 0.50),
+            "p90": percentile(&latencies, 0.90),
+            "p99": percentile(&latencies, 0.99),
+            "p999": percentile(&latencies, 0.999),
+            "max": latencies.last().map_or(0.0, |max| *max as f64 / 1000.0),
+        },
+        "server_errors": stats.server_errors.load(Ordering::Relaxed),
+        "sequence_regressions": stats.sequence_regressions.load(Ordering::Relaxed),
+        "parse_failures": stats.parse_failures.load(Ordering::Relaxed),
+    });
+
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(());
+    }
+
+    println!("md-loadgen: {} clients for {:.1}s against {}", args.clients, elapsed, args.url);
+    println!(
+        "connections   {} connected, {} failed to connect, {} dropped early ({} as slow consumers)",
+        summary["connected"], connect_failures, summary["disconnects"], summary["slow_consumer_disconnects"]
+    );
+    println!("streams       {} subscribed, {} received nothing", streams, stalled);
+    println!(
+        "messages      {} ({:.0}/s), {:.1} MiB ({:.1} KiB/s)",
+        messages,
+        messages as f64 / elapsed,
+        bytes as f64 / 1024.0 / 1024.0,
+        bytes as f64 / 1024.0 / elapsed,
+    );
+    let latency = &summary["latency_ms"];
+    println!(
+        "latency (ms)  p50 {:.2}  p90 {:.2}  p99 {:.2}  p99.9 {:.2}  max {:.2}",
+        latency["p50"].as_f64().unwrap_or_default(),
+        latency["p90"].as_f64().unwrap_or_default(),
+        latency["p99"].as_f64().unwrap_or_default(),
+        latency["p999"].as_f64().unwrap_or_default(),
+        latency["max"].as_f64().unwrap_or_default(),
+    );
+    println!(
+        "drops         {} server errors, {} sequence regressions, {} unparseable",
+        summary["server_errors"], summary["sequence_regressions"], summary["parse_failures"]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,468 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use futures_util::{SinkExt, StreamExt};
+use market_depth_client::{signed_query, BookDelta, BookLevel, LocalOrderBook, SseDecoder, StreamSpec};
+use market_depth_server::{
+    AggressorFlowStats, ClientMessage, MarketDataUpdate, OptionQuote, OrderActivity, OrderLifetimeStats, Quantity, RestingAge, TradingStatus,
+};
+use serde::Deserialize;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+use tracing_subscriber::EnvFilter;
+
+// Shortest interval between two redraws of the depth ladder
+const LADDER_REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+const LADDER_BAR_WIDTH: usize = 30;
+
+#[derive(Parser, Debug)]
+#[command(name = "md-cli", author, version, about = "Subscribe to the market depth feed from the command line", long_about = None)]
+struct Args {
+    /// WebSocket (ws://) or SSE (http://) endpoint
+    #[arg(short, long, default_value = "ws://127.0.0.1:8080/")]
+    url: String,
+
+    /// Stream to subscribe to as SYMBOL:TYPE[:LEVELS], TYPE one of MBP, MBO, FLOW, MARK, FUNDING, OPTION, CHAIN or LADDER, repeatable
+    #[arg(short, long = "stream", value_name = "SPEC", required = true)]
+    streams: Vec<StreamSpec>,
+
+    /// How updates are shown
+    #[arg(short, long, value_enum, default_value_t = Mode::Top)]
+    mode: Mode,
+
+    /// File NDJSON is written to, or `-` for stdout
+    #[arg(short, long, value_name = "PATH", default_value = "-")]
+    output: PathBuf,
+
+    /// API key to sign the connection with
+    #[arg(long, requires = "api_secret")]
+    api_key: Option<String>,
+
+    /// API secret to sign the connection with
+    #[arg(long, requires = "api_key")]
+    api_secret: Option<String>,
+
+    /// Log level (trace, debug, info, warn, error)
+    #[arg(long, default_value = "warn")]
+    log_level: String,
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+enum Mode {
+    /// One line with the best bid and ask per update
+    Top,
+    /// Live depth ladder redrawn in the terminal
+    Ladder,
+    /// Every message as one line of JSON
+    Ndjson,
+}
+
+// The fields shared by WebSocket messages (tagged `type`) and SSE events (tagged `event`)
+#[derive(Debug, Deserialize)]
+struct Frame {
+    #[serde(rename = "type", alias = "event")]
+    kind: String,
+    stream_id: Option<String>,
+    data: Option<MarketDataUpdate>,
+    sequence: Option<u64>,
+    code: Option<u32>,
+    message: Option<String>,
+    symbol: Option<String>,
+    status: Option<TradingStatus>,
+    settlement_price: Option<f64>,
+}
+
+struct Output {
+    mode: Mode,
+    writer: Box<dyn Write>,
+    levels: u32,
+    books: BTreeMap<String, LocalOrderBook>,
+    flows: BTreeMap<String, AggressorFlowStats>,
+    last_redraw: Option<Instant>,
+}
+
+impl Output {
+    fn new(args: &Args) -> anyhow::Result<Self> {
+        let writer: Box<dyn Write> = if args.output.as_os_str() == "-" {
+            Box::new(io::stdout())
+        } else {
+            let file = File::create(&args.output).with_context(|| format!("Failed to create {}", args.output.display()))?;
+            Box::new(BufWriter::new(file))
+        };
+
+        Ok(Self {
+            mode: args.mode,
+            writer,
+            levels: args.streams.iter().map(|spec| spec.levels).max().unwrap_or(10),
+            books: BTreeMap::new(),
+            flows: BTreeMap::new(),
+            last_redraw: None,
+        })
+    }
+
+    fn handle(&mut self, text: &str) -> anyhow::Result<()> {
+        if let Mode::Ndjson = self.mode {
+            writeln!(self.writer, "{}", text)?;
+            return Ok(self.writer.flush()?);
+        }
+
+        let frame = match serde_json::from_str::<Frame>(text) {
+            Ok(frame) => frame,
+            Err(_) => return Ok(()),
+        };
+
+        match frame.kind.as_str() {
+            "MarketData" | "market_data" => {
+                let (Some(stream_id), Some(data)) = (frame.stream_id, frame.data) else {
+                    return Ok(());
+                };
+                self.market_data(stream_id, data, frame.sequence.unwrap_or_default())
+            }
+            "Error" | "error" => {
+                eprintln!("Server error {}: {}", frame.code.unwrap_or_default(), frame.message.unwrap_or_default());
+                Ok(())
+            }
+            "InstrumentStatus" => {
+                let settlement = frame.settlement_price.map_or(String::new(), |price| format!(", settled at {}", price));
+                eprintln!(
+                    "{} {:?}{}",
+                    frame.symbol.unwrap_or_default(),
+                    frame.status.unwrap_or(TradingStatus::Trading),
+                    settlement,
+                );
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn market_data(&mut self, stream_id: String, data: MarketDataUpdate, sequence: u64) -> anyhow::Result<()> {
+        if let MarketDataUpdate::AggressorFlow { flow } = data {
+            if let Mode::Top = self.mode {
+                writeln!(
+                    self.writer,
+                    "{} buy {} sell {} delta {} cvd {}",
+                    stream_id, flow.buy_volume, flow.sell_volume, flow.delta, flow.cvd
+                )?;
+            }
+            self.flows.insert(stream_id, flow);
+            return self.redraw();
+        }
+
+        // Perpetual and option streams are only printed in top mode
+        match &data {
+            MarketDataUpdate::MarkPrice { mark } => {
+                if let Mode::Top = self.mode {
+                    writeln!(
+                        self.writer,
+                        "{} mark {:.4} index {:.4} basis {:.4} funding {:.4}% next {}",
+                        stream_id, mark.mark_price, mark.index_price, mark.basis,
+                        mark.estimated_funding_rate * 100.0, mark.next_funding_time.format("%H:%M:%S"),
+                    )?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::Funding { funding } => {
+                if let Mode::Top = self.mode {
+                    writeln!(
+                        self.writer,
+                        "{} funding {:.4}% at {} mark {:.4} index {:.4}",
+                        stream_id, funding.funding_rate * 100.0, funding.funding_time.to_rfc3339(),
+                        funding.mark_price, funding.index_price,
+                    )?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::OptionQuote { quote } => {
+                if let Mode::Top = self.mode {
+                    writeln!(self.writer, "{} {}", stream_id, format_option(quote))?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::OptionChain { quotes, .. } => {
+                if let Mode::Top = self.mode {
+                    for quote in quotes {
+                        writeln!(self.writer, "{} {} {}", stream_id, quote.contract, format_option(quote))?;
+                    }
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::Ladder { center_price, rows, .. } => {
+                if let Mode::Top = self.mode {
+                    let bid: Quantity = rows.iter().map(|row| row.bid_quantity).sum();
+                    let ask: Quantity = rows.iter().map(|row| row.ask_quantity).sum();
+                    writeln!(
+                        self.writer,
+                        "{} seq={} ladder of {} rows around {} bid {} | ask {}",
+                        stream_id, sequence, rows.len(),
+                        center_price.map_or("-".to_string(), |price| format!("{:.4}", price)), bid, ask,
+                    )?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::LiquidityAge { bids, asks } => {
+                if let Mode::Top = self.mode {
+                    writeln!(self.writer, "{} seq={} bid {} | ask {}", stream_id, sequence, format_age(bids), format_age(asks))?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::OrderStats { stats } => {
+                if let Mode::Top = self.mode {
+                    writeln!(self.writer, "{} seq={} {}", stream_id, sequence, format_order_stats(stats))?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::OrderActivity { activity } => {
+                if let Mode::Top = self.mode {
+                    writeln!(self.writer, "{} seq={} {}", stream_id, sequence, format_activity(activity))?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::Quarantine { event } => {
+                if let Mode::Top = self.mode {
+                    writeln!(
+                        self.writer,
+                        "{} seq={} quarantined {:?} {} @ {:.4}, reference {:.4} band {}%",
+                        stream_id, sequence, event.event, event.quantity, event.price, event.reference_price, event.band_pct,
+                    )?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            MarketDataUpdate::Trade { trade } => {
+                if let Mode::Top = self.mode {
+                    writeln!(
+                        self.writer,
+                        "{} seq={} trade {} {:?} {} @ {:.4}",
+                        stream_id, sequence, trade.trade_id, trade.aggressor_side, trade.quantity, trade.price,
+                    )?;
+                }
+                return Ok(self.writer.flush()?);
+            }
+            _ => {}
+        }
+
+        let book = self
+            .books
+            .entry(stream_id.clone())
+            .or_insert_with(|| LocalOrderBook::new(&stream_id));
+        let applied = match (&data, BookDelta::from_update(&data, sequence)) {
+            (MarketDataUpdate::MBODelta { previous_sequence, changes }, _) => book.apply_order_changes(*previous_sequence, changes, sequence),
+            (_, Some(delta)) => book.apply_delta(&delta),
+            (_, None) => book.apply_snapshot(&data, sequence),
+        };
+        if applied.is_err() {
+            return Ok(());
+        }
+
+        if let Mode::Top = self.mode {
+            writeln!(
+                self.writer,
+                "{} seq={} bid {} | ask {} spread {}",
+                stream_id,
+                sequence,
+                format_level(book.best_bid()),
+                format_level(book.best_ask()),
+                book.spread().map_or("-".to_string(), |spread| format!("{:.4}", spread)),
+            )?;
+            return Ok(self.writer.flush()?);
+        }
+
+        self.redraw()
+    }
+
+    fn redraw(&mut self) -> anyhow::Result<()> {
+        if !matches!(self.mode, Mode::Ladder) {
+            return Ok(());
+        }
+        if self.last_redraw.is_some_and(|last| last.elapsed() < LADDER_REDRAW_INTERVAL) {
+            return Ok(());
+        }
+        self.last_redraw = Some(Instant::now());
+
+        let depth = self.levels as usize;
+        let mut screen = String::from("\x1b[2J\x1b[H");
+
+        for (stream_id, book) in &self.books {
+            let asks: Vec<&BookLevel> = book.asks().take(depth).collect();
+            let bids: Vec<&BookLevel> = book.bids().take(depth).collect();
+            let max_quantity = asks.iter().chain(&bids).map(|level| level.quantity).max().unwrap_or_default();
+
+            screen.push_str(&format!("\x1b[1m{}\x1b[0m  seq {}\n", stream_id, book.sequence().unwrap_or_default()));
+            for level in asks.iter().rev() {
+                screen.push_str(&ladder_row(level, max_quantity, "\x1b[31m"));
+            }
+            let spread = book.spread().map_or("-".to_string(), |spread| format!("{:.4}", spread));
+            screen.push_str(&format!("{:>12} spread {}\n", "", spread));
+            for level in &bids {
+                screen.push_str(&ladder_row(level, max_quantity, "\x1b[32m"));
+            }
+            screen.push('\n');
+        }
+
+        for (stream_id, flow) in &self.flows {
+            screen.push_str(&format!(
+                "\x1b[1m{}\x1b[0m  buy {} sell {} delta {} cvd {}\n",
+                stream_id, flow.buy_volume, flow.sell_volume, flow.delta, flow.cvd
+            ));
+        }
+
+        self.writer.write_all(screen.as_bytes())?;
+        Ok(self.writer.flush()?)
+    }
+}
+
+fn format_level(level: Option<&BookLevel>) -> String {
+    level.map_or("-".to_string(), |level| format!("{} @ {}", level.quantity, level.price))
+}
+
+fn format_option(quote: &OptionQuote) -> String {
+    format!(
+        "bid {} | ask {} @ {:.2} mark {:.4} iv {:.1}% delta {:.3} gamma {:.4} vega {:.4} theta {:.4}",
+        quote.bid.map_or("-".to_string(), |bid| format!("{} @ {:.2}", quote.bid_size, bid)),
+        quote.ask_size, quote.ask, quote.mark_price, quote.implied_vol * 100.0,
+        quote.delta, quote.gamma, quote.vega, quote.theta,
+    )
+}
+
+// Resting size and the percentage of it older than each bucket, e.g. `5200 >1s 82% >10s 40% >60s 3%`
+fn format_age(age: &RestingAge) -> String {
+    let buckets: Vec<String> = age.older_than
+        .iter()
+        .map(|bucket| format!(">{}s {:.0}%", bucket.age_ms / 1000, bucket.share * 100.0))
+        .collect();
+    format!("{} {}", age.quantity, buckets.join(" "))
+}
+
+// Outcome counts with median lifetimes, e.g. `live 412 cancelled 9120 (p50 840ms) filled 310 (p50 2310ms) c/t 18.2`
+fn format_order_stats(stats: &OrderLifetimeStats) -> String {
+    let median = |p50_ms: Option<f64>| p50_ms.map_or("-".to_string(), |ms| format!("{:.0}ms", ms));
+    format!(
+        "live {} cancelled {} (p50 {}) filled {} (p50 {}) c/t {}",
+        stats.live_orders, stats.cancelled.count, median(stats.cancelled.p50_ms),
+        stats.filled.count, median(stats.filled.p50_ms),
+        stats.cancel_to_trade_ratio.map_or("-".to_string(), |ratio| format!("{:.1}", ratio)),
+    )
+}
+
+// One order event, e.g. `Add 184467 Bid 7400 @ 50012.5000`; updates and cancels carry no side or price
+fn format_activity(activity: &OrderActivity) -> String {
+    let mut line = format!("{:?} {}", activity.activity_type, activity.order_id);
+    if let Some(side) = &activity.side {
+        line.push_str(&format!(" {:?}", side));
+    }
+    if let Some(quantity) = activity.quantity {
+        line.push_str(&format!(" {}", quantity));
+    }
+    if let Some(price) = activity.price {
+        line.push_str(&format!(" @ {:.4}", price));
+    }
+    line
+}
+
+fn ladder_row(level: &BookLevel, max_quantity: Quantity, color: &str) -> String {
+    let share = if max_quantity.is_zero() { 0.0 } else { level.quantity.to_f64() / max_quantity.to_f64() };
+    let width = (share * LADDER_BAR_WIDTH as f64).round() as usize;
+    format!(
+        "{}{:>12.4} {:>10} {:>5}  {}\x1b[0m\n",
+        color,
+        level.price,
+        level.quantity,
+        level.order_count,
+        "#".repeat(width.max(1))
+    )
+}
+
+async fn run_websocket(args: &Args, output: &mut Output) -> anyhow::Result<()> {
+    let url = match (&args.api_key, &args.api_secret) {
+        (Some(api_key), Some(secret)) => format!("{}?{}", args.url, signed_query("", api_key, secret)),
+        _ => args.url.clone(),
+    };
+
+    let (socket, _) = connect_async(url).await.with_context(|| format!("Failed to connect to {}", args.url))?;
+    let (mut sink, mut source) = socket.split();
+
+    for spec in &args.streams {
+        let subscribe = ClientMessage::Subscribe {
+            stream_id: spec.stream_id(),
+            symbol: spec.symbol.clone(),
+            data_type: spec.data_type(),
+            max_levels: Some(spec.levels),
+            max_orders: None,
+            snapshot_interval_ms: None,
+            priority: None,
+            sample_every_n: None,
+        };
+        sink.send(Message::Text(serde_json::to_string(&subscribe)?)).await?;
+    }
+
+    while let Some(message) = source.next().await {
+        match message? {
+            Message::Text(text) => output.handle(&text)?,
+            Message::Close(frame) => {
+                if let Some(frame) = frame {
+                    eprintln!("Server closed the connection {}: {}", u16::from(frame.code), frame.reason);
+                }
+                break;
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+async fn run_sse(args: &Args, output: &mut Output) -> anyhow::Result<()> {
+    let base = args.url.trim_end_matches('/');
+    let endpoint = if base.ends_with("/stream") { base.to_string() } else { format!("{}/stream", base) };
+
+    let definitions: Vec<String> = args.streams.iter().map(StreamSpec::definition).collect();
+    let query = format!("streams={}", definitions.join(","));
+    let query = match (&args.api_key, &args.api_secret) {
+        (Some(api_key), Some(secret)) => signed_query(&query, api_key, secret),
+        _ => query,
+    };
+
+    let response = reqwest::get(format!("{}?{}", endpoint, query))
+        .await
+        .with_context(|| format!("Failed to connect to {}", endpoint))?
+        .error_for_status()?;
+    let mut body = response.bytes_stream();
+    let mut decoder = SseDecoder::new();
+
+    while let Some(chunk) = body.next().await {
+        for data in decoder.push(&chunk?) {
+            output.handle(&data)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&args.log_level)))
+        .with_writer(io::stderr)
+        .init();
+
+    let mut output = Output::new(&args)?;
+
+    let result = if args.url.starts_with("ws://") {
+        run_websocket(&args, &mut output).await
+    } else if args.url.starts_with("http://") {
+        run_sse(&args, &mut output).await
+    } else {
+        anyhow::bail!("Expected a ws:// or http:// URL, got {}", args.url)
+    };
+
+    // Output piped into a command that exited early, such as `head`
+    match result {
+        Err(e) if e.downcast_ref::<io::Error>().is_some_and(|e| e.kind() == io::ErrorKind::BrokenPipe) => Ok(()),
+        result => result,
+    }
+}
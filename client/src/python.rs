@@ -0,0 +1,325 @@
+// Python extension module `market_depth`, built with `maturin develop --features python`.
+// Structured values cross the boundary as the same dicts the server puts on the wire.
+use std::fs::File;
+use std::io::BufReader;
+use market_depth_server::{default_max_orders, MarketDataUpdate, Order, OrderActivity, OrderId, OrderBook, Quantity, Side};
+use pyo3::create_exception;
+use pyo3::exceptions::{PyException, PyIOError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::{PyModule, PyString};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::book::{self, BookDelta, BookLevel, LocalOrderBook};
+use crate::capture::{CaptureReader, CaptureRecord};
+
+create_exception!(market_depth, BookError, PyException);
+
+impl From<book::BookError> for PyErr {
+    fn from(error: book::BookError) -> Self {
+        BookError::new_err(error.to_string())
+    }
+}
+
+fn to_py<'py>(py: Python<'py>, value: &impl Serialize) -> PyResult<Bound<'py, PyAny>> {
+    let json = serde_json::to_string(value).map_err(|e| PyValueError::new_err(e.to_string()))?;
+    PyModule::import(py, "json")?.call_method1("loads", (json,))
+}
+
+// Accepts a wire-format dict or its JSON text
+fn from_py<T: DeserializeOwned>(value: &Bound<'_, PyAny>) -> PyResult<T> {
+    let json: String = if value.is_instance_of::<PyString>() {
+        value.extract()?
+    } else {
+        PyModule::import(value.py(), "json")?.call_method1("dumps", (value,))?.extract()?
+    };
+    serde_json::from_str(&json).map_err(|e| PyValueError::new_err(e.to_string()))
+}
+
+fn parse_side(side: &str) -> PyResult<Side> {
+    match side.to_ascii_lowercase().as_str() {
+        "bid" | "buy" => Ok(Side::Bid),
+        "ask" | "sell" => Ok(Side::Ask),
+        _ => Err(PyValueError::new_err(format!("Unknown side {:?}, expected \"bid\" or \"ask\"", side))),
+    }
+}
+
+// Quantities cross as floats, rounded to Quantity::DECIMALS places
+fn parse_quantity(quantity: f64) -> PyResult<Quantity> {
+    Quantity::from_f64(quantity).ok_or_else(|| PyValueError::new_err(format!("Invalid quantity {}", quantity)))
+}
+
+fn level_tuple(level: &BookLevel) -> (f64, f64, u32) {
+    (level.price, level.quantity.to_f64(), level.order_count)
+}
+
+fn level_from_tuple((price, quantity, order_count): (f64, f64, u32)) -> PyResult<BookLevel> {
+    Ok(BookLevel { price, quantity: parse_quantity(quantity)?, order_count })
+}
+
+/// The server's order book: price-time priority matching and MBO/MBP views.
+#[pyclass(name = "OrderBook")]
+pub struct PyOrderBook {
+    book: OrderBook,
+}
+
+#[pymethods]
+impl PyOrderBook {
+    /// Sizes are whole lots of 10^-quantity_decimals, as set with the server's --quantity-decimals.
+    #[new]
+    #[pyo3(signature = (symbol, quantity_decimals = 0))]
+    fn new(symbol: String, quantity_decimals: u32) -> Self {
+        let mut book = OrderBook::new(symbol);
+        book.set_quantity_decimals(quantity_decimals);
+        Self { book }
+    }
+
+    #[getter]
+    fn symbol(&self) -> &str {
+        &self.book.symbol
+    }
+
+    #[getter]
+    fn sequence(&self) -> u64 {
+        self.book.get_sequence()
+    }
+
+    /// Rests a limit order and returns its id, or None when the book is full.
+    fn add_order(&mut self, side: &str, price: f64, quantity: f64) -> PyResult<Option<u64>> {
+        let (side, quantity) = (parse_side(side)?, parse_quantity(quantity)?);
+        let order_id = self.book.next_order_id();
+        let added = self.book.add_order(Order::new(order_id, price, quantity, side));
+        Ok(added.then_some(order_id.0))
+    }
+
+    fn remove_order(&mut self, order_id: u64) -> bool {
+        self.book.remove_order(OrderId(order_id))
+    }
+
+    fn update_order(&mut self, order_id: u64, quantity: f64) -> PyResult<bool> {
+        Ok(self.book.update_order(OrderId(order_id), parse_quantity(quantity)?))
+    }
+
+    /// Sweeps the opposite side and returns the resulting fill activities.
+    fn execute_market_order<'py>(&mut self, py: Python<'py>, side: &str, quantity: f64) -> PyResult<Bound<'py, PyAny>> {
+        let fills = self.book.execute_market_order(parse_side(side)?, parse_quantity(quantity)?);
+        to_py(py, &fills)
+    }
+
+    /// Trades executed since the last call.
+    fn take_trades<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_py(py, &self.book.take_trades())
+    }
+
+    /// Applies one random add, update or cancel, as the server simulation does.
+    fn simulate_activity<'py>(&mut self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_py(py, &self.book.simulate_activity())
+    }
+
+    fn initialize_with_sample_data(&mut self) {
+        self.book.initialize_with_sample_data();
+    }
+
+    /// MBP snapshot in the `data` format of a MarketData message.
+    #[pyo3(signature = (max_levels = 10))]
+    fn mbp<'py>(&self, py: Python<'py>, max_levels: u32) -> PyResult<Bound<'py, PyAny>> {
+        let (bids, asks) = self.book.get_mbp_data(max_levels);
+        to_py(py, &MarketDataUpdate::MBP { bids, asks })
+    }
+
+    /// MBO snapshot in the `data` format of a MarketData message.
+    #[pyo3(signature = (max_levels = 10, max_orders = None))]
+    fn mbo<'py>(&self, py: Python<'py>, max_levels: u32, max_orders: Option<u32>) -> PyResult<Bound<'py, PyAny>> {
+        let max_orders = max_orders.unwrap_or_else(|| default_max_orders(max_levels));
+        let (bids, asks) = self.book.get_mbo_data(max_levels, max_orders);
+        to_py(py, &MarketDataUpdate::MBO { bids, asks })
+    }
+
+    fn best_bid_ask(&self) -> (Option<f64>, Option<f64>) {
+        self.book.get_best_bid_ask()
+    }
+
+    fn summary<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_py(py, &self.book.get_symbol_summary())
+    }
+}
+
+/// Client-side book rebuilt from snapshots, level deltas or MBO activity.
+/// Levels are `(price, quantity, order_count)` tuples, best first.
+#[pyclass(name = "LocalOrderBook")]
+pub struct PyLocalOrderBook {
+    book: LocalOrderBook,
+}
+
+#[pymethods]
+impl PyLocalOrderBook {
+    #[new]
+    fn new(symbol: &str) -> Self {
+        Self { book: LocalOrderBook::new(symbol) }
+    }
+
+    #[getter]
+    fn symbol(&self) -> &str {
+        self.book.symbol()
+    }
+
+    #[getter]
+    fn sequence(&self) -> Option<u64> {
+        self.book.sequence()
+    }
+
+    fn is_synced(&self) -> bool {
+        self.book.is_synced()
+    }
+
+    fn invalidate(&mut self) {
+        self.book.invalidate();
+    }
+
+    /// Replaces the book with an MBP or MBO `data` dict.
+    fn apply_snapshot(&mut self, update: &Bound<'_, PyAny>, sequence: u64) -> PyResult<()> {
+        let update: MarketDataUpdate = from_py(update)?;
+        Ok(self.book.apply_snapshot(&update, sequence)?)
+    }
+
+    /// Replaces the book with the snapshot carried by a capture record.
+    fn apply_record(&mut self, record: &PyCaptureRecord) -> PyResult<()> {
+        Ok(self.book.apply_snapshot(&record.record.data, record.record.sequence)?)
+    }
+
+    /// Applies level changes since `previous_sequence`; zero quantity removes a level.
+    /// Raises BookError and invalidates the book when `checksum` does not match the
+    /// deltas applied since the last snapshot or anchor.
+    #[pyo3(signature = (previous_sequence, sequence, bids, asks, checksum = None))]
+    fn apply_delta(
+        &mut self,
+        previous_sequence: u64,
+        sequence: u64,
+        bids: Vec<(f64, f64, u32)>,
+        asks: Vec<(f64, f64, u32)>,
+        checksum: Option<u32>,
+    ) -> PyResult<()> {
+        let delta = BookDelta {
+            previous_sequence,
+            sequence,
+            bids: bids.into_iter().map(level_from_tuple).collect::<PyResult<_>>()?,
+            asks: asks.into_iter().map(level_from_tuple).collect::<PyResult<_>>()?,
+            checksum,
+        };
+        Ok(self.book.apply_delta(&delta)?)
+    }
+
+    /// Applies one order activity dict, which must advance the sequence by one.
+    fn apply_activity(&mut self, activity: &Bound<'_, PyAny>, sequence: u64) -> PyResult<()> {
+        let activity: OrderActivity = from_py(activity)?;
+        Ok(self.book.apply_activity(&activity, sequence)?)
+    }
+
+    #[pyo3(signature = (depth = None))]
+    fn bids(&self, depth: Option<usize>) -> Vec<(f64, f64, u32)> {
+        self.book.bids().take(depth.unwrap_or(usize::MAX)).map(level_tuple).collect()
+    }
+
+    #[pyo3(signature = (depth = None))]
+    fn asks(&self, depth: Option<usize>) -> Vec<(f64, f64, u32)> {
+        self.book.asks().take(depth.unwrap_or(usize::MAX)).map(level_tuple).collect()
+    }
+
+    fn best_bid(&self) -> Option<(f64, f64, u32)> {
+        self.book.best_bid().map(level_tuple)
+    }
+
+    fn best_ask(&self) -> Option<(f64, f64, u32)> {
+        self.book.best_ask().map(level_tuple)
+    }
+
+    fn spread(&self) -> Option<f64> {
+        self.book.spread()
+    }
+
+    fn checksum(&self) -> u32 {
+        self.book.checksum()
+    }
+
+    fn delta_checksum(&self) -> u32 {
+        self.book.delta_checksum()
+    }
+
+    /// Raises BookError and invalidates the book when the checksum does not match.
+    fn verify_checksum(&mut self, expected: u32) -> PyResult<()> {
+        Ok(self.book.verify_checksum(expected)?)
+    }
+}
+
+/// One market data message from a recording.
+#[pyclass(name = "CaptureRecord", frozen)]
+pub struct PyCaptureRecord {
+    record: CaptureRecord,
+}
+
+#[pymethods]
+impl PyCaptureRecord {
+    #[getter]
+    fn stream_id(&self) -> &str {
+        &self.record.stream_id
+    }
+
+    #[getter]
+    fn symbol(&self) -> &str {
+        &self.record.symbol
+    }
+
+    #[getter]
+    fn sequence(&self) -> u64 {
+        self.record.sequence
+    }
+
+    /// RFC 3339 timestamp of the message.
+    #[getter]
+    fn timestamp(&self) -> String {
+        self.record.timestamp.to_rfc3339()
+    }
+
+    #[getter]
+    fn data<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyAny>> {
+        to_py(py, &self.record.data)
+    }
+}
+
+/// Iterates the market data messages of an NDJSON recording, e.g. `md-cli -m ndjson` output.
+#[pyclass(name = "CaptureReader")]
+pub struct PyCaptureReader {
+    reader: CaptureReader<BufReader<File>>,
+}
+
+#[pymethods]
+impl PyCaptureReader {
+    #[new]
+    fn new(path: std::path::PathBuf) -> PyResult<Self> {
+        let reader = CaptureReader::open(&path).map_err(|e| PyIOError::new_err(format!("{}: {}", path.display(), e)))?;
+        Ok(Self { reader })
+    }
+
+    fn __iter__(slf: PyRef<'_, Self>) -> PyRef<'_, Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<PyCaptureRecord>> {
+        match self.reader.next() {
+            Some(Ok(record)) => Ok(Some(PyCaptureRecord { record })),
+            Some(Err(e)) => Err(PyIOError::new_err(e.to_string())),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Order book core of the market depth server.
+#[pymodule]
+fn market_depth(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyOrderBook>()?;
+    module.add_class::<PyLocalOrderBook>()?;
+    module.add_class::<PyCaptureRecord>()?;
+    module.add_class::<PyCaptureReader>()?;
+    module.add("BookError", module.py().get_type::<BookError>())?;
+    Ok(())
+}
@@ -0,0 +1,20 @@
+use chrono::Utc;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// Hex HMAC-SHA256 of the payload keyed with the API secret, as the server verifies it
+pub fn sign(secret: &str, payload: &str) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(payload.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+// Appends the API key, the current timestamp and the signature of the whole query
+pub fn signed_query(query: &str, api_key: &str, secret: &str) -> String {
+    let separator = if query.is_empty() { "" } else { "&" };
+    let query = format!("{}{}api_key={}&timestamp={}", query, separator, api_key, Utc::now().timestamp_millis());
+    let signature = sign(secret, &query);
+    format!("{}&signature={}", query, signature)
+}
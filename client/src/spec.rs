@@ -0,0 +1,86 @@
+use std::str::FromStr;
+use market_depth_server::DataType;
+
+// Command-line stream definition, SYMBOL:TYPE[:LEVELS] with TYPE one of MBP, MBO, FLOW,
+// MARK, FUNDING, OPTION, CHAIN, LADDER, AGE, ORDERS, ACTIVITY, QUARANTINE, TRADES, DELTA or
+// LEVELS
+#[derive(Debug, Clone)]
+pub struct StreamSpec {
+    pub symbol: String,
+    pub data_type: &'static str,
+    pub levels: u32,
+}
+
+impl FromStr for StreamSpec {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        let symbol = parts.next().filter(|symbol| !symbol.is_empty()).ok_or("Missing symbol")?;
+        let data_type = match parts.next().map(str::to_ascii_uppercase).as_deref() {
+            None | Some("MBP") => "MBP",
+            Some("MBO") => "MBO",
+            Some("FLOW") => "FLOW",
+            Some("MARK") => "MARK",
+            Some("FUNDING") => "FUNDING",
+            Some("OPTION") => "OPTION",
+            Some("CHAIN") => "CHAIN",
+            Some("LADDER") => "LADDER",
+            Some("AGE") => "AGE",
+            Some("ORDERS") => "ORDERS",
+            Some("ACTIVITY") => "ACTIVITY",
+            Some("QUARANTINE") => "QUARANTINE",
+            Some("TRADES") => "TRADES",
+            Some("DELTA") => "DELTA",
+            Some("LEVELS") => "LEVELS",
+            Some(other) => {
+                return Err(format!(
+                    "Unknown data type {}, expected MBP, MBO, FLOW, MARK, FUNDING, OPTION, CHAIN, LADDER, AGE, ORDERS, ACTIVITY, QUARANTINE, TRADES, DELTA or LEVELS",
+                    other
+                ))
+            }
+        };
+        let levels = match parts.next() {
+            Some(levels) => levels.parse().map_err(|_| format!("Invalid level count {}", levels))?,
+            None => 10,
+        };
+
+        Ok(Self {
+            symbol: symbol.to_string(),
+            data_type,
+            levels,
+        })
+    }
+}
+
+impl StreamSpec {
+    pub fn data_type(&self) -> DataType {
+        match self.data_type {
+            "MBO" => DataType::MBO,
+            "FLOW" => DataType::AggressorFlow,
+            "MARK" => DataType::MarkPrice,
+            "FUNDING" => DataType::Funding,
+            "OPTION" => DataType::OptionQuote,
+            "CHAIN" => DataType::OptionChain,
+            "LADDER" => DataType::Ladder { rows: self.levels, tick_group: 1 }, // The level count is the row count
+            "AGE" => DataType::LiquidityAge,
+            "ORDERS" => DataType::OrderStats,
+            "ACTIVITY" => DataType::OrderActivity { side: None, max_distance_bps: None, min_quantity: None },
+            "QUARANTINE" => DataType::Quarantine,
+            "TRADES" => DataType::Trades,
+            "DELTA" => DataType::MBODelta,
+            "LEVELS" => DataType::MBPDelta,
+            _ => DataType::MBP,
+        }
+    }
+
+    // Same stream id the SSE server assigns
+    pub fn stream_id(&self) -> String {
+        format!("{}_{:?}_{}", self.symbol, self.data_type(), self.levels)
+    }
+
+    // The SSE `streams` query parameter form
+    pub fn definition(&self) -> String {
+        format!("{}:{}:{}", self.symbol, self.data_type, self.levels)
+    }
+}
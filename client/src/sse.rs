@@ -0,0 +1,34 @@
+// Splits an SSE response body into event payloads. Chunks may end anywhere; events are
+// separated by a blank line and their payload is in the `data:` lines.
+#[derive(Debug, Default)]
+pub struct SseDecoder {
+    buffer: Vec<u8>,
+}
+
+impl SseDecoder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // Payloads of the events completed by `chunk`
+    pub fn push(&mut self, chunk: &[u8]) -> Vec<String> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.windows(2).position(|window| window == b"\n\n") {
+            let event: Vec<u8> = self.buffer.drain(..end + 2).collect();
+            let event = String::from_utf8_lossy(&event);
+            let data = event
+                .lines()
+                .filter_map(|line| line.strip_prefix("data:"))
+                .map(str::trim_start)
+                .collect::<Vec<_>>()
+                .join("\n");
+
+            if !data.is_empty() {
+                events.push(data);
+            }
+        }
+        events
+    }
+}
@@ -0,0 +1,125 @@
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use chrono::{DateTime, Utc};
+use futures_util::Stream;
+use market_depth_server::{BookAnchor, MBOLevel, MBPLevel, MarketDataUpdate};
+use tokio::sync::mpsc;
+
+use crate::book::{BookError, LocalOrderBook};
+use crate::connection::Command;
+
+#[derive(Debug, Clone)]
+pub struct MbpUpdate {
+    pub symbol: String,
+    pub bids: Vec<MBPLevel>,
+    pub asks: Vec<MBPLevel>,
+    pub sequence: u64,
+    // Goes up when the server's book was recreated, e.g. by a server restart; the
+    // sequence then starts over without it being a gap
+    pub epoch: u64,
+    pub timestamp: DateTime<Utc>,
+    pub event_ts: DateTime<Utc>, // When the server's book last changed
+    pub send_ts: DateTime<Utc>,  // When the server sent the update
+    // Updates may have been missed since the previous one: the first update after a
+    // reconnect, or one whose book sequence went backwards within its epoch
+    pub gap: bool,
+    // Checksum of the book after this update, on servers started with --anchor-interval
+    pub anchor: Option<BookAnchor>,
+    // Server's delay from the book change to sending, on servers started with --debug-latency-tag
+    pub latency_us: Option<u64>,
+    // Venue sequence of the last feed event behind the update, on books fed through the ingest API
+    pub venue_sequence: Option<u64>,
+}
+
+impl MbpUpdate {
+    // Each MBP update is a full snapshot of the subscribed depth
+    pub fn apply_to(&self, book: &mut LocalOrderBook) {
+        book.apply_mbp_snapshot(&self.bids, &self.asks, self.sequence);
+    }
+
+    // Checks the book this update was applied to against its anchor, if it has one
+    pub fn verify_anchor(&self, book: &mut LocalOrderBook) -> Result<(), BookError> {
+        self.anchor.map_or(Ok(()), |anchor| book.verify_anchor(&anchor))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MboUpdate {
+    pub symbol: String,
+    pub bids: Vec<MBOLevel>,
+    pub asks: Vec<MBOLevel>,
+    pub sequence: u64,
+    pub epoch: u64,
+    pub timestamp: DateTime<Utc>,
+    pub event_ts: DateTime<Utc>,
+    pub send_ts: DateTime<Utc>,
+    // Same meaning as MbpUpdate::gap
+    pub gap: bool,
+    pub anchor: Option<BookAnchor>,
+    pub latency_us: Option<u64>,
+    pub venue_sequence: Option<u64>,
+}
+
+impl MboUpdate {
+    // Each MBO update is a full snapshot of the subscribed levels and orders
+    pub fn apply_to(&self, book: &mut LocalOrderBook) -> Result<(), BookError> {
+        let snapshot = MarketDataUpdate::MBO {
+            bids: self.bids.clone(),
+            asks: self.asks.clone(),
+        };
+        book.apply_snapshot(&snapshot, self.sequence)
+    }
+
+    pub fn verify_anchor(&self, book: &mut LocalOrderBook) -> Result<(), BookError> {
+        self.anchor.map_or(Ok(()), |anchor| book.verify_anchor(&anchor))
+    }
+}
+
+// Updates of one subscription; dropping the stream unsubscribes
+#[derive(Debug)]
+pub struct UpdateStream<T> {
+    stream_id: String,
+    updates: mpsc::UnboundedReceiver<T>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+pub type MbpStream = UpdateStream<MbpUpdate>;
+pub type MboStream = UpdateStream<MboUpdate>;
+
+impl<T> UpdateStream<T> {
+    pub(crate) fn new(
+        stream_id: String,
+        updates: mpsc::UnboundedReceiver<T>,
+        commands: mpsc::UnboundedSender<Command>,
+    ) -> Self {
+        Self { stream_id, updates, commands }
+    }
+
+    pub fn stream_id(&self) -> &str {
+        &self.stream_id
+    }
+
+    // Asks the server for the stream's current view with an anchor, e.g. after a failed
+    // anchor check
+    pub fn resync(&self) {
+        let _ = self.commands.send(Command::Resync {
+            stream_id: self.stream_id.clone(),
+        });
+    }
+}
+
+impl<T> Stream for UpdateStream<T> {
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.updates.poll_recv(cx)
+    }
+}
+
+impl<T> Drop for UpdateStream<T> {
+    fn drop(&mut self) {
+        let _ = self.commands.send(Command::Unsubscribe {
+            stream_id: self.stream_id.clone(),
+        });
+    }
+}
@@ -0,0 +1,190 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use anyhow::Context;
+use chrono::{DateTime, Utc};
+use clap::{Args, Parser, Subcommand};
+use market_depth_client::{CaptureReader, ConformanceReport, ConformanceVerifier};
+use market_depth_server::{conformance_ticks, GoldenStream, GoldenUpdate, MarketDataUpdate, ServerMessage, DEFAULT_SYMBOLS, SIMULATION_INTERVAL};
+
+#[derive(Parser, Debug)]
+#[command(name = "md-verify", author, version, about = "Certify a client against the server's golden conformance stream", long_about = None)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Write the golden stream of a seed as an NDJSON recording
+    Record {
+        #[command(flatten)]
+        golden: GoldenArgs,
+
+        /// File the recording is written to, or `-` for stdout
+        #[arg(short, long, value_name = "PATH", default_value = "-")]
+        output: PathBuf,
+    },
+    /// Check a capture of client-received messages against the golden stream
+    Verify {
+        /// NDJSON capture, e.g. written by `md-cli -m ndjson`
+        capture: PathBuf,
+
+        /// Golden recording written by `md-verify record`, instead of regenerating it from --seed
+        #[arg(long, value_name = "PATH", conflicts_with = "seed", required_unless_present = "seed")]
+        golden: Option<PathBuf>,
+
+        #[command(flatten)]
+        stream: GoldenArgs,
+
+        /// Fail when updates of the golden stream are missing between received ones
+        #[arg(long)]
+        strict_gaps: bool,
+
+        /// Fail unless every stream reaches the end of the golden stream
+        #[arg(long)]
+        require_complete: bool,
+
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+// Must match the server's --conformance-seed and --conformance-duration-secs
+#[derive(Args, Debug)]
+struct GoldenArgs {
+    /// Conformance seed the server was started with
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Conformance duration the server was started with
+    #[arg(long, default_value_t = 60)]
+    duration_secs: u64,
+
+    /// Symbols the server simulates from startup, comma-separated (default: BTCUSD, ETHUSD and ADAUSD)
+    #[arg(long, value_delimiter = ',')]
+    symbols: Vec<String>,
+}
+
+impl GoldenArgs {
+    fn stream(&self) -> anyhow::Result<GoldenStream> {
+        let seed = self.seed.context("--seed is required")?;
+        let symbols = if self.symbols.is_empty() {
+            DEFAULT_SYMBOLS.iter().map(|symbol| symbol.to_string()).collect()
+        } else {
+            self.symbols.clone()
+        };
+        Ok(GoldenStream::new(seed, &symbols, conformance_ticks(Duration::from_secs(self.duration_secs))))
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    match Cli::parse().command {
+        Command::Record { golden, output } => record(golden.stream()?, &output),
+        Command::Verify { capture, golden, stream, strict_gaps, require_complete, json } => {
+            let mut verifier = ConformanceVerifier::new();
+            match golden {
+                Some(path) => {
+                    let reader = CaptureReader::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+                    for record in reader {
+                        verifier.add_golden_record(record.with_context(|| format!("Invalid golden recording {}", path.display()))?);
+                    }
+                }
+                None => {
+                    for update in stream.stream()? {
+                        verifier.add_golden(&update.symbol, update.sequence, update.bids, update.asks);
+                    }
+                }
+            }
+
+            let reader = CaptureReader::open(&capture).with_context(|| format!("Failed to open {}", capture.display()))?;
+            for record in reader {
+                verifier.check(&record.with_context(|| format!("Invalid capture {}", capture.display()))?);
+            }
+
+            let report = verifier.report();
+            if json {
+                println!("{}", serde_json::to_string_pretty(&report)?);
+            } else {
+                print_report(&report);
+            }
+
+            let mut problems = report.failures.len();
+            if strict_gaps {
+                problems += report.streams.iter().filter(|stream| stream.skipped > 0).count();
+            }
+            if require_complete {
+                problems += report.streams.iter().filter(|stream| !stream.complete).count();
+            }
+            if report.streams.is_empty() {
+                anyhow::bail!("The capture has no MBP updates to verify");
+            }
+            if problems > 0 {
+                anyhow::bail!("Conformance failed, problems: {}", problems);
+            }
+            if !json {
+                println!("PASS");
+            }
+            Ok(())
+        }
+    }
+}
+
+// Written like a capture so the recording can be read back as one; timestamps count
+// simulation ticks from the epoch, so the same seed always gives the same file
+fn record(stream: GoldenStream, output: &PathBuf) -> anyhow::Result<()> {
+    let mut writer: Box<dyn Write> = if output.as_os_str() == "-" {
+        Box::new(io::stdout())
+    } else {
+        let file = File::create(output).with_context(|| format!("Failed to create {}", output.display()))?;
+        Box::new(BufWriter::new(file))
+    };
+
+    for GoldenUpdate { symbol, tick, sequence, mut bids, mut asks } in stream {
+        for level in bids.iter_mut().chain(asks.iter_mut()) {
+            level.avg_age_ms = 0;
+        }
+        let timestamp = DateTime::<Utc>::UNIX_EPOCH + SIMULATION_INTERVAL * tick as u32;
+        let message = ServerMessage::MarketData {
+            stream_id: "golden".to_string(),
+            symbol: Arc::from(symbol.as_str()),
+            data: MarketDataUpdate::MBP { bids, asks },
+            sequence,
+            // Conformance books are each the first of their symbol
+            epoch: 1,
+            timestamp,
+            event_ts: timestamp,
+            send_ts: timestamp,
+            anchor: None,
+            latency_us: None,
+            venue_sequence: None,
+        };
+        writeln!(writer, "{}", serde_json::to_string(&message)?)?;
+    }
+
+    Ok(writer.flush()?)
+}
+
+fn print_report(report: &ConformanceReport) {
+    for stream in &report.streams {
+        println!(
+            "{} {}: {} updates checked, sequences {}..{}, {} skipped{}",
+            stream.stream_id,
+            stream.symbol,
+            stream.checked,
+            stream.first_sequence.unwrap_or_default(),
+            stream.last_sequence.unwrap_or_default(),
+            stream.skipped,
+            if stream.complete { ", complete" } else { "" },
+        );
+    }
+    if report.ignored > 0 {
+        println!("{} updates other than MBP ignored", report.ignored);
+    }
+    for failure in &report.failures {
+        println!("FAIL {} {} sequence {}: {}", failure.stream_id, failure.symbol, failure.sequence, failure.issue);
+    }
+}
@@ -0,0 +1,56 @@
+/* Prints the best bid and ask of BTCUSD for ten seconds.
+ *
+ *   cargo build --release
+ *   cc -Iinclude examples/top_of_book.c -Ltarget/release -lmarket_depth -o top_of_book
+ *   LD_LIBRARY_PATH=target/release ./top_of_book ws://127.0.0.1:8080/
+ */
+#include <stdio.h>
+#include <unistd.h>
+
+#include "market_depth.h"
+
+static void on_mbp(const md_mbp_update *update, void *user_data) {
+    (void)user_data;
+    if (update == NULL) {
+        fprintf(stderr, "stream ended\n");
+        return;
+    }
+    if (update->bid_count == 0 || update->ask_count == 0) {
+        return;
+    }
+    printf("%s seq=%llu%s bid %.2f x %g | ask %.2f x %g\n", update->symbol,
+           (unsigned long long)update->sequence, update->gap ? " (gap)" : "",
+           update->bids[0].price, update->bids[0].quantity,
+           update->asks[0].price, update->asks[0].quantity);
+}
+
+static void on_mbo(const md_mbo_update *update, void *user_data) {
+    unsigned long *count = user_data;
+    if (update != NULL) {
+        *count += update->bid_count + update->ask_count;
+    }
+}
+
+int main(int argc, char **argv) {
+    const char *url = argc > 1 ? argv[1] : "ws://127.0.0.1:8080/";
+    unsigned long orders = 0;
+
+    md_client *client = md_client_connect(url, NULL, NULL);
+    if (client == NULL) {
+        fprintf(stderr, "connect: %s\n", md_last_error());
+        return 1;
+    }
+
+    uint64_t mbp = md_subscribe_mbp(client, "BTCUSD", 5, on_mbp, NULL);
+    uint64_t mbo = md_subscribe_mbo(client, "ETHUSD", 5, 0, on_mbo, &orders);
+    if (mbp == 0 || mbo == 0) {
+        fprintf(stderr, "subscribe: %s\n", md_last_error());
+        return 1;
+    }
+
+    sleep(10);
+    md_unsubscribe(client, mbo);
+    printf("ETHUSD orders received: %lu\n", orders);
+    md_client_free(client);
+    return 0;
+}
@@ -0,0 +1,100 @@
+/* C API of the market depth client. Link against libmarket_depth (cdylib or
+ * staticlib from `cargo build --release` in ffi/).
+ *
+ * Each client runs its connection on a background worker thread and reconnects
+ * after failures. Callbacks run on that thread. The update and its level arrays
+ * are only valid for the duration of the callback; copy what you keep.
+ * Quantities may be fractional, e.g. 0.05 BTC. */
+#ifndef MARKET_DEPTH_H
+#define MARKET_DEPTH_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct MdClient md_client;
+
+typedef struct md_mbp_level {
+    double price;
+    double quantity;
+    uint32_t order_count;
+    double total_quantity;
+    uint64_t avg_age_ms;
+} md_mbp_level;
+
+typedef struct md_mbo_level {
+    uint64_t order_id;
+    double price;
+    double quantity;
+    int64_t timestamp_ms;
+    uint64_t age_ms;
+} md_mbo_level;
+
+/* Bids and asks are best first. `gap` is set when updates may have been missed,
+ * e.g. the first update after a reconnect. `epoch` goes up when the server's book
+ * was recreated, and `sequence` then starts over. `event_timestamp_ms` is when the
+ * server's book last changed and `send_timestamp_ms` when the update was sent. */
+typedef struct md_mbp_update {
+    const char *symbol;
+    uint64_t sequence;
+    uint64_t epoch;
+    int64_t timestamp_ms;
+    int64_t event_timestamp_ms;
+    int64_t send_timestamp_ms;
+    bool gap;
+    const md_mbp_level *bids;
+    size_t bid_count;
+    const md_mbp_level *asks;
+    size_t ask_count;
+} md_mbp_update;
+
+typedef struct md_mbo_update {
+    const char *symbol;
+    uint64_t sequence;
+    uint64_t epoch;
+    int64_t timestamp_ms;
+    int64_t event_timestamp_ms;
+    int64_t send_timestamp_ms;
+    bool gap;
+    const md_mbo_level *bids;
+    size_t bid_count;
+    const md_mbo_level *asks;
+    size_t ask_count;
+} md_mbo_update;
+
+/* `update` is NULL once the stream has ended, e.g. rejected by the server or
+ * closed after a quota was exceeded. */
+typedef void (*md_mbp_callback)(const md_mbp_update *update, void *user_data);
+typedef void (*md_mbo_callback)(const md_mbo_update *update, void *user_data);
+
+/* Message of the last failed call on this thread, or NULL. */
+const char *md_last_error(void);
+
+/* `api_key` and `api_secret` may be NULL for an unsigned connection. Returns NULL
+ * on failure. */
+md_client *md_client_connect(const char *url, const char *api_key, const char *api_secret);
+
+/* Closes every subscription and the connection, returning once their callbacks have
+ * finished. Not from a callback. */
+void md_client_free(md_client *client);
+
+/* Return the subscription id, or 0 on failure. `max_orders` 0 uses the server
+ * default. */
+uint64_t md_subscribe_mbp(md_client *client, const char *symbol, uint32_t depth,
+                          md_mbp_callback callback, void *user_data);
+uint64_t md_subscribe_mbo(md_client *client, const char *symbol, uint32_t depth,
+                          uint32_t max_orders, md_mbo_callback callback, void *user_data);
+
+/* Once it returns the callback is not called again. Not from a callback. Returns
+ * 0, or -1 for an unknown subscription. */
+int md_unsubscribe(md_client *client, uint64_t subscription);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,371 @@
+// C API over the Rust client, declared in include/market_depth.h. Each client owns a
+// Tokio runtime; callbacks run on its worker thread with flat level arrays that are
+// only valid for the duration of the call.
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use futures_util::{Stream, StreamExt};
+use market_depth_client::{ClientOptions, MarketDepthClient, MboUpdate, MbpUpdate};
+use market_depth_server::{MBOLevel, MBPLevel};
+use tokio::runtime::{Builder, Handle, Runtime};
+use tokio::task::JoinHandle;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_error(message: impl Into<String>) {
+    let message = CString::new(message.into()).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+#[repr(C)]
+pub struct MdMbpLevel {
+    pub price: f64,
+    pub quantity: f64,
+    pub order_count: u32,
+    pub total_quantity: f64,
+    pub avg_age_ms: u64,
+}
+
+#[repr(C)]
+pub struct MdMboLevel {
+    pub order_id: u64,
+    pub price: f64,
+    pub quantity: f64,
+    pub timestamp_ms: i64,
+    pub age_ms: u64,
+}
+
+#[repr(C)]
+pub struct MdMbpUpdate {
+    pub symbol: *const c_char,
+    pub sequence: u64,
+    pub epoch: u64,
+    pub timestamp_ms: i64,
+    pub event_timestamp_ms: i64,
+    pub send_timestamp_ms: i64,
+    pub gap: bool,
+    pub bids: *const MdMbpLevel,
+    pub bid_count: usize,
+    pub asks: *const MdMbpLevel,
+    pub ask_count: usize,
+}
+
+#[repr(C)]
+pub struct MdMboUpdate {
+    pub symbol: *const c_char,
+    pub sequence: u64,
+    pub epoch: u64,
+    pub timestamp_ms: i64,
+    pub event_timestamp_ms: i64,
+    pub send_timestamp_ms: i64,
+    pub gap: bool,
+    pub bids: *const MdMboLevel,
+    pub bid_count: usize,
+    pub asks: *const MdMboLevel,
+    pub ask_count: usize,
+}
+
+// Called with NULL once the stream has ended, e.g. rejected by the server
+pub type MdMbpCallback = Option<extern "C" fn(update: *const MdMbpUpdate, user_data: *mut c_void)>;
+pub type MdMboCallback = Option<extern "C" fn(update: *const MdMboUpdate, user_data: *mut c_void)>;
+
+// The caller promises user_data may be used from the client's worker thread
+struct UserData(*mut c_void);
+
+unsafe impl Send for UserData {}
+
+pub struct MdClient {
+    runtime: Runtime,
+    client: MarketDepthClient,
+    subscriptions: Mutex<HashMap<u64, JoinHandle<()>>>,
+    next_subscription: AtomicU64,
+}
+
+impl MdClient {
+    fn spawn<S, T>(&self, mut stream: S, mut deliver: impl FnMut(Option<&T>) + Send + 'static) -> u64
+    where
+        S: Stream<Item = T> + Send + Unpin + 'static,
+        T: Send + 'static,
+    {
+        let task = self.runtime.spawn(async move {
+            while let Some(update) = stream.next().await {
+                deliver(Some(&update));
+            }
+            deliver(None);
+        });
+
+        let id = self.next_subscription.fetch_add(1, Ordering::Relaxed);
+        self.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).insert(id, task);
+        id
+    }
+}
+
+fn mbp_level(level: &MBPLevel) -> MdMbpLevel {
+    MdMbpLevel {
+        price: level.price,
+        quantity: level.quantity.to_f64(),
+        order_count: level.order_count,
+        total_quantity: level.total_quantity.to_f64(),
+        avg_age_ms: level.avg_age_ms,
+    }
+}
+
+fn mbo_level(level: &MBOLevel) -> MdMboLevel {
+    MdMboLevel {
+        order_id: level.order_id.0,
+        price: level.price,
+        quantity: level.quantity.to_f64(),
+        timestamp_ms: level.timestamp.timestamp_millis(),
+        age_ms: level.age_ms,
+    }
+}
+
+unsafe fn optional_str(value: *const c_char) -> Result<Option<String>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map(|value| Some(value.to_string()))
+        .map_err(|_| "String is not valid UTF-8".to_string())
+}
+
+unsafe fn required_str(value: *const c_char, name: &str) -> Result<String, String> {
+    optional_str(value)?.ok_or_else(|| format!("{} must not be NULL", name))
+}
+
+/// Message of the last failed call on this thread, or NULL. Valid until the next
+/// failing call on the same thread.
+#[no_mangle]
+pub extern "C" fn md_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| error.borrow().as_ref().map_or(ptr::null(), |error| error.as_ptr()))
+}
+
+/// Starts a client that connects to `url` in the background and reconnects after
+/// failures. `api_key` and `api_secret` may be NULL for an unsigned connection.
+/// Returns NULL on failure.
+///
+/// # Safety
+/// Every non-NULL argument must be a valid NUL-terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn md_client_connect(
+    url: *const c_char,
+    api_key: *const c_char,
+    api_secret: *const c_char,
+) -> *mut MdClient {
+    let (url, api_key, api_secret) = match (required_str(url, "url"), optional_str(api_key), optional_str(api_secret)) {
+        (Ok(url), Ok(api_key), Ok(api_secret)) => (url, api_key, api_secret),
+        (Err(e), _, _) | (_, Err(e), _) | (_, _, Err(e)) => {
+            set_error(e);
+            return ptr::null_mut();
+        }
+    };
+
+    let runtime = match Builder::new_multi_thread().worker_threads(1).enable_all().build() {
+        Ok(runtime) => runtime,
+        Err(e) => {
+            set_error(format!("Failed to start runtime: {}", e));
+            return ptr::null_mut();
+        }
+    };
+
+    let options = ClientOptions { api_key, api_secret, ..ClientOptions::default() };
+    let client = {
+        let _guard = runtime.enter();
+        MarketDepthClient::with_options(&url, options)
+    };
+
+    Box::into_raw(Box::new(MdClient {
+        runtime,
+        client,
+        subscriptions: Mutex::new(HashMap::new()),
+        next_subscription: AtomicU64::new(1),
+    }))
+}
+
+/// Closes every subscription and the connection, then frees the client. Must not be
+/// called from a callback.
+///
+/// # Safety
+/// `client` must come from md_client_connect and not be used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn md_client_free(client: *mut MdClient) {
+    if client.is_null() {
+        return;
+    }
+    if Handle::try_current().is_ok() {
+        set_error("md_client_free must not be called from a callback");
+        return;
+    }
+
+    let client = Box::from_raw(client);
+    let tasks: Vec<_> = client.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).drain().map(|(_, task)| task).collect();
+    // Waits for callbacks in progress, as md_unsubscribe does, so none runs once this returns
+    for task in tasks {
+        task.abort();
+        let _ = client.runtime.block_on(task);
+    }
+    let MdClient { runtime, client, .. } = *client;
+    drop(client);
+    runtime.shutdown_timeout(Duration::from_secs(1));
+}
+
+/// Subscribes to the MBP book of `symbol` with `depth` levels per side. Returns the
+/// subscription id, or 0 on failure.
+///
+/// # Safety
+/// `client` must be live and `symbol` a valid NUL-terminated string. `user_data` is
+/// passed to `callback` from the client's worker thread.
+#[no_mangle]
+pub unsafe extern "C" fn md_subscribe_mbp(
+    client: *mut MdClient,
+    symbol: *const c_char,
+    depth: u32,
+    callback: MdMbpCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    let (Some(client), Some(callback)) = (client.as_ref(), callback) else {
+        set_error("client and callback must not be NULL");
+        return 0;
+    };
+    let symbol = match required_str(symbol, "symbol") {
+        Ok(symbol) => symbol,
+        Err(e) => {
+            set_error(e);
+            return 0;
+        }
+    };
+
+    let stream = match client.client.subscribe_mbp(&symbol, depth) {
+        Ok(stream) => stream,
+        Err(e) => {
+            set_error(e.to_string());
+            return 0;
+        }
+    };
+
+    let user_data = UserData(user_data);
+    let symbol = CString::new(symbol).unwrap_or_default();
+    client.spawn(stream, move |update: Option<&MbpUpdate>| {
+        let user_data = &user_data;
+        let Some(update) = update else {
+            callback(ptr::null(), user_data.0);
+            return;
+        };
+
+        let bids: Vec<MdMbpLevel> = update.bids.iter().map(mbp_level).collect();
+        let asks: Vec<MdMbpLevel> = update.asks.iter().map(mbp_level).collect();
+        let update = MdMbpUpdate {
+            symbol: symbol.as_ptr(),
+            sequence: update.sequence,
+            epoch: update.epoch,
+            timestamp_ms: update.timestamp.timestamp_millis(),
+            event_timestamp_ms: update.event_ts.timestamp_millis(),
+            send_timestamp_ms: update.send_ts.timestamp_millis(),
+            gap: update.gap,
+            bids: bids.as_ptr(),
+            bid_count: bids.len(),
+            asks: asks.as_ptr(),
+            ask_count: asks.len(),
+        };
+        callback(&update, user_data.0);
+    })
+}
+
+/// Subscribes to the MBO book of `symbol` with `depth` levels and up to `max_orders`
+/// orders per side, 0 for the server default. Returns the subscription id, or 0 on
+/// failure.
+///
+/// # Safety
+/// Same as md_subscribe_mbp.
+#[no_mangle]
+pub unsafe extern "C" fn md_subscribe_mbo(
+    client: *mut MdClient,
+    symbol: *const c_char,
+    depth: u32,
+    max_orders: u32,
+    callback: MdMboCallback,
+    user_data: *mut c_void,
+) -> u64 {
+    let (Some(client), Some(callback)) = (client.as_ref(), callback) else {
+        set_error("client and callback must not be NULL");
+        return 0;
+    };
+    let symbol = match required_str(symbol, "symbol") {
+        Ok(symbol) => symbol,
+        Err(e) => {
+            set_error(e);
+            return 0;
+        }
+    };
+
+    let max_orders = (max_orders > 0).then_some(max_orders);
+    let stream = match client.client.subscribe_mbo(&symbol, depth, max_orders) {
+        Ok(stream) => stream,
+        Err(e) => {
+            set_error(e.to_string());
+            return 0;
+        }
+    };
+
+    let user_data = UserData(user_data);
+    let symbol = CString::new(symbol).unwrap_or_default();
+    client.spawn(stream, move |update: Option<&MboUpdate>| {
+        let user_data = &user_data;
+        let Some(update) = update else {
+            callback(ptr::null(), user_data.0);
+            return;
+        };
+
+        let bids: Vec<MdMboLevel> = update.bids.iter().map(mbo_level).collect();
+        let asks: Vec<MdMboLevel> = update.asks.iter().map(mbo_level).collect();
+        let update = MdMboUpdate {
+            symbol: symbol.as_ptr(),
+            sequence: update.sequence,
+            epoch: update.epoch,
+            timestamp_ms: update.timestamp.timestamp_millis(),
+            event_timestamp_ms: update.event_ts.timestamp_millis(),
+            send_timestamp_ms: update.send_ts.timestamp_millis(),
+            gap: update.gap,
+            bids: bids.as_ptr(),
+            bid_count: bids.len(),
+            asks: asks.as_ptr(),
+            ask_count: asks.len(),
+        };
+        callback(&update, user_data.0);
+    })
+}
+
+/// Ends a subscription. Once it returns the callback is not called again. Must not be
+/// called from a callback. Returns 0, or -1 for an unknown subscription.
+///
+/// # Safety
+/// `client` must be live.
+#[no_mangle]
+pub unsafe extern "C" fn md_unsubscribe(client: *mut MdClient, subscription: u64) -> c_int {
+    let Some(client) = client.as_ref() else {
+        set_error("client must not be NULL");
+        return -1;
+    };
+    if Handle::try_current().is_ok() {
+        set_error("md_unsubscribe must not be called from a callback");
+        return -1;
+    }
+
+    let task = client.subscriptions.lock().unwrap_or_else(|e| e.into_inner()).remove(&subscription);
+    let Some(task) = task else {
+        set_error(format!("Unknown subscription {}", subscription));
+        return -1;
+    };
+
+    // Waits for a callback in progress; dropping the stream unsubscribes from the server
+    task.abort();
+    let _ = client.runtime.block_on(task);
+    0
+}
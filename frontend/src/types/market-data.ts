@@ -0,0 +1,137 @@
+// Generated by `cargo run --features ts --bin gen-ts` from market-depth-server 0.1.0. Do not edit.
+
+export const PROTOCOL_VERSION = "0.1.0";
+
+export type ClientMessage = { "type": "Subscribe", stream_id: string, symbol: string, data_type: DataType, max_levels?: number | null, max_orders?: number | null, snapshot_interval_ms?: number | null, priority?: StreamPriority | null, sample_every_n?: number | null, } | { "type": "Unsubscribe", stream_id: string, } | { "type": "SaveProfile", name: string, streams: Array<StreamSpec>, } | { "type": "LoadProfile", name: string, } | { "type": "Resync", stream_id: string, } | { "type": "Ping", timestamp: string, } | { "type": "RenewSession", api_key: string, timestamp: number, signature: string, } | { "type": "GetQuota" } | { "type": "StreamStats", stream_id: string, } | { "type": "SearchSymbols", search?: string | null, symbol_type?: SymbolType | null, status?: SymbolStatus | null, limit?: number | null, } | { "type": "ValidateSubscribe", streams: Array<StreamSpec>, } | { "type": "ReplayRequest", stream_id: string, symbol: string, from: string, to: string, speed?: number | null, max_levels?: number | null, } | { "type": "SnapshotManyRequest", request_id: string, symbols: Array<string>, max_levels?: number | null, } | { "type": "SyncClock" } | { "type": "ClockSyncReply", server_ts: string, client_ts: string, } | { "type": "SetFilters", mute_heartbeats: boolean, min_interval_ms?: number | null, symbols_mute: Array<string>, } | { "type": "SetHeartbeatQuotes", symbols: Array<string>, } | { "type": "MapSequence", symbol: string, sequence?: number | null, venue_sequence?: number | null, } | { "type": "CandleBackfill", symbol: string, interval: string, from: string, to: string, } | { "type": "SubmitPaperOrder", symbol: string, side: Side, quantity: Quantity, limit_price?: number | null, client_order_id?: string | null, } | { "type": "CancelPaperOrder", order_id: number, };
+
+export type ServerMessage = { "type": "Subscribed", stream_id: string, symbol: string, data_type: DataType, } | { "type": "SubscriptionAdjusted", stream_id: string, requested: number, granted: number, } | { "type": "Unsubscribed", stream_id: string, } | { "type": "ProfileSaved", name: string, streams: number, } | { "type": "ProfileLoaded", name: string, streams: Array<StreamSpec>, } | { "type": "FiltersSet", mute_heartbeats: boolean, min_interval_ms: number | null, symbols_mute: Array<string>, } | { "type": "HeartbeatQuotesSet", symbols: Array<string>, } | { "type": "SequenceMapped", symbol: string, mapping: SequenceMapping | null, } | { "type": "CandleHistory", symbol: string, interval_secs: number, candles: Array<Candle>, complete: boolean, } | { "type": "PaperOrderUpdate", order: PaperOrder, } | { "type": "PaperFill", fill: PaperFill, } | { "type": "MarketData", stream_id: string, symbol: string, data: MarketDataUpdate, sequence: number, epoch: number, timestamp: string, event_ts: string, send_ts: string, anchor?: BookAnchor, latency_us?: number, venue_sequence?: number, } | { "type": "Snapshot", stream_id: string, symbol: string, data: MarketDataUpdate, sequence: number, epoch: number, timestamp: string, } | { "type": "MarketSummary", stream_id: string, summary: MarketSummary, } | { "type": "ReferenceData", stream_id: string, version: number, changes: Array<ReferenceChange>, } | { "type": "HeartBeat", timestamp: string, quotes?: Array<TopOfBook>, } | { "type": "UsageReport", connected_at: string, messages: number, bytes: number, streams: Array<StreamUsage>, timestamp: string, } | { "type": "SessionStarted", token: string, expires_at: string, } | { "type": "SessionRenewed", token: string, expires_at: string, } | { "type": "SessionExpiring", expires_at: string, } | { "type": "SessionExpired" } | { "type": "Quota", status: QuotaStatus, } | { "type": "StreamStats", stats: StreamStats, } | { "type": "Symbols", symbols: Array<SymbolInfo>, } | { "type": "SnapshotMany", request_id: string, snapshots: Array<BookSnapshot>, missing: Array<string>, } | { "type": "Validated", streams: Array<StreamValidation>, } | { "type": "QuotaExceeded", quota: QuotaRemaining, } | { "type": "Throttled", stream_ids: Array<string>, interval_ms: number | null, timestamp: string, } | { "type": "InstrumentStatus", stream_id: string, symbol: string, status: TradingStatus, settlement_price: number | null, timestamp: string, } | { "type": "ReplayStarted", stream_id: string, symbol: string, from: string, to: string, updates: number, speed: number, } | { "type": "ReplayComplete", stream_id: string, } | { "type": "ClockSync", server_ts: string, } | { "type": "ClockSynced", offset: ClockOffset, } | { "type": "Error", code: number, message: string, stream_id: string | null, };
+
+export type DataType = "MBO" | "MBP" | "AggressorFlow" | "Summary" | { "Indicators": { spec: IndicatorSpec, } } | "MarkPrice" | "Funding" | "OptionQuote" | "OptionChain" | { "Ladder": { rows: number, tick_group: number, } } | "LiquidityAge" | "OrderStats" | { "OrderActivity": { side?: Side | null, max_distance_bps?: number | null, min_quantity?: Quantity | null, } } | "Quarantine" | "Trades" | "MBODelta" | "MBPDelta" | "Reference";
+
+export type MarketDataUpdate = { "format": "MBO", bids: Array<MBOLevel>, asks: Array<MBOLevel>, } | { "format": "MBP", bids: Array<MBPLevel>, asks: Array<MBPLevel>, } | { "format": "Pair", quote: PairQuote, } | { "format": "AggressorFlow", flow: AggressorFlowStats, } | { "format": "Indicators", interval_secs: number, values: Array<IndicatorValue>, } | { "format": "MarkPrice", mark: MarkPrice, } | { "format": "Funding", funding: FundingRate, } | { "format": "OptionQuote", quote: OptionQuote, } | { "format": "OptionChain", underlying: string, quotes: Array<OptionQuote>, } | { "format": "OrderActivity", activity: OrderActivity, } | { "format": "Ladder", center_price: number | null, row_size: number, rows: Array<LadderRow>, } | { "format": "LiquidityAge", bids: RestingAge, asks: RestingAge, } | { "format": "OrderStats", stats: OrderLifetimeStats, } | { "format": "Quarantine", event: OutOfBandEvent, } | { "format": "Trade", trade: Trade, } | { "format": "MBODelta", previous_sequence: number, changes: Array<OrderChange>, } | { "format": "MBPDelta", previous_sequence: number, bids: Array<LevelChange>, asks: Array<LevelChange>, };
+
+export type OrderId = string;
+
+export type Quantity = number | string;
+
+export type MBOLevel = { order_id: OrderId, price: number, quantity: Quantity, side: Side, timestamp: string, age_ms: number, actor?: ActorClass, };
+
+export type MBPLevel = { price: number, quantity: Quantity, order_count: number, side: Side, total_quantity: Quantity, avg_age_ms: number, };
+
+export type LevelChange = { price: number, quantity: Quantity, order_count: number, deleted?: boolean, };
+
+export type BookAnchor = { sequence: number, checksum: number, };
+
+export type LadderRow = { price: number, bid_quantity: Quantity, bid_orders: number, ask_quantity: Quantity, ask_orders: number, };
+
+export type RestingAge = { quantity: Quantity, order_count: number, older_than: Array<AgeBucket>, };
+
+export type AgeBucket = { age_ms: number, quantity: Quantity, order_count: number, share: number, };
+
+export type OrderActivity = { activity_type: ActivityType, order_id: OrderId, symbol: string, price: number | null, quantity: Quantity | null, side: Side | null, timestamp: string, actor?: ActorClass, venue_sequence?: number, };
+
+export type Trade = { trade_id: string, symbol: string, price: number, quantity: Quantity, aggressor_side: Side, maker_order_id: OrderId, timestamp: string, };
+
+export type OrderChangeAction = "Add" | "Update" | "Delete";
+
+export type OrderChange = { action: OrderChangeAction, order_id: OrderId, side?: Side, price?: number, quantity?: Quantity, };
+
+export type ActivityType = "Add" | "Update" | "Cancel" | "Fill";
+
+export type OutOfBandEvent = { event: OutOfBandKind, order_id: OrderId | null, symbol: string, side: Side | null, price: number, quantity: Quantity, reference_price: number, band_pct: number, action: BandAction, timestamp: string, venue_sequence?: number, };
+
+export type OutOfBandKind = "Add" | "Modify" | "Trade";
+
+export type BandAction = "reject" | "quarantine";
+
+export type ActorClass = "Maker" | "Taker" | "Noise";
+
+export type TopOfBook = { symbol: string, bid: number | null, bid_quantity: Quantity | null, ask: number | null, ask_quantity: Quantity | null, last_trade: LastTrade | null, };
+
+export type LastTrade = { price: number, quantity: Quantity, aggressor_side: Side, timestamp: string, };
+
+export type SequenceMapping = { sequence: number, venue_sequence: number, };
+
+export type PaperOrder = { order_id: number, client_order_id: string | null, symbol: string, side: Side, quantity: Quantity, limit_price: number | null, filled: Quantity, average_price: number | null, fees: number, status: PaperOrderStatus, timestamp: string, };
+
+export type PaperOrderStatus = "Open" | "Filled" | "Cancelled";
+
+export type PaperFill = { order_id: number, symbol: string, side: Side, price: number, quantity: Quantity, fee: number, liquidity: PaperLiquidity, timestamp: string, };
+
+export type PaperLiquidity = "Maker" | "Taker";
+
+export type AggressorFlowStats = { window_secs: number, buy_volume: Quantity, sell_volume: Quantity, delta: number, cumulative_buy_volume: Quantity, cumulative_sell_volume: Quantity, cvd: number, trade_count: number, };
+
+export type OrderLifetimeStats = { orders_added: number, live_orders: number, cancelled: LifetimeStats, filled: LifetimeStats, partial_fills: number, trades: number, cancel_to_trade_ratio: number | null, sample_size: number, };
+
+export type LifetimeStats = { count: number, mean_ms: number | null, p50_ms: number | null, p90_ms: number | null, };
+
+export type MarkPrice = { mark_price: number, index_price: number, basis: number, estimated_funding_rate: number, next_funding_time: string, };
+
+export type FundingRate = { funding_rate: number, funding_time: string, mark_price: number, index_price: number, };
+
+export type Candle = { open_time: string, interval_secs: number, open: number | null, high: number | null, low: number | null, close: number | null, volume: Quantity, gap: boolean, };
+
+export type OptionKind = "Call" | "Put";
+
+export type OptionQuote = { contract: string, underlying: string, kind: OptionKind, strike: number, expiry: string, underlying_price: number, bid: number | null, ask: number, bid_size: number, ask_size: number, mark_price: number, implied_vol: number, delta: number, gamma: number, vega: number, theta: number, rho: number, };
+
+export type TradingStatus = "Trading" | "Halted" | "Delisted";
+
+export type FutureContract = { symbol: string, root: string, expiry: string, contract_size: number, status: TradingStatus, mid_price: number | null, settlement_price: number | null, };
+
+export type IndicatorSpec = { interval_secs: number, indicators: Array<Indicator>, };
+
+export type Indicator = { "kind": "EMA", period: number, } | { "kind": "RSI", period: number, } | { "kind": "Bollinger", period: number, std_dev: number, };
+
+export type IndicatorValue = { "indicator": "EMA", period: number, value: number | null, } | { "indicator": "RSI", period: number, value: number | null, } | { "indicator": "Bollinger", period: number, std_dev: number, middle: number | null, upper: number | null, lower: number | null, };
+
+export type PairKind = "Ratio" | "Spread";
+
+export type PairQuote = { pair: string, kind: PairKind, base: string, quote: string, bid: number | null, ask: number | null, value: number | null, spread: number | null, };
+
+export type SymbolSummary = { symbol: string, last_price: number | null, open_price: number | null, change_pct: number | null, volume: Quantity, spread: number | null, spread_bps: number | null, };
+
+export type SymbolType = "spot" | "pair" | "perpetual" | "future" | "option";
+
+export type SymbolStatus = "open" | "halted";
+
+export type SymbolInfo = { symbol: string, type: SymbolType, status: SymbolStatus, venue: string, base: string | null, quote: string | null, tick_size: number | null, quantity_decimals: number | null, };
+
+export type ReferenceChangeKind = "added" | "removed" | "halted" | "resumed" | "updated";
+
+export type ReferenceChange = { version: number, change: ReferenceChangeKind, symbol: string, info: SymbolInfo | null, timestamp: string, };
+
+export type ReferenceSnapshot = { version: number, symbols: Array<SymbolInfo>, };
+
+export type ReferenceChanges = { version: number, changes: Array<ReferenceChange>, };
+
+export type StreamSpec = { stream_id: string, symbol: string, data_type: DataType, max_levels?: number | null, max_orders?: number | null, snapshot_interval_ms?: number | null, priority?: StreamPriority | null, sample_every_n?: number | null, };
+
+export type StreamValidation = { stream_id: string, symbol: string, data_type: DataType, valid: boolean, error?: string, max_levels: number, max_orders: number, snapshot_interval_ms: number | null, priority: StreamPriority, sample_every_n: number | null, exists: boolean, };
+
+export type BookSnapshot = { symbol: string, data: MarketDataUpdate, sequence: number, epoch: number, event_ts: string, };
+
+export type BulkSnapshot = { snapshots: Array<BookSnapshot>, missing: Array<string>, };
+
+export type MarketSummary = { top_gainers: Array<SymbolSummary>, top_losers: Array<SymbolSummary>, highest_volume: Array<SymbolSummary>, widest_spreads: Array<SymbolSummary>, symbol_count: number, timestamp: string, };
+
+export type StreamUsage = { stream_id: string, messages: number, bytes: number, };
+
+export type Side = "Bid" | "Ask";
+
+export type StreamPriority = "Normal" | "Low";
+
+export type ClockOffset = { offset_us: number, round_trip_us: number, measured_at: string, };
+
+export type DisconnectReason = "server_shutdown" | "session_expired" | "slow_consumer" | "kicked";
+
+export type QuotaPeriod = "Daily" | "Monthly";
+
+export type QuotaUnit = "Messages" | "Bytes";
+
+export type QuotaRemaining = { period: QuotaPeriod, unit: QuotaUnit, limit: number, used: number, remaining: number, resets_at: string, };
+
+export type QuotaStatus = { api_key: string, quotas: Array<QuotaRemaining>, throttled: boolean, exceeded: boolean, timestamp: string, };
+
+export type StreamStats = { stream_id: string, subscribed_at: string, messages_sent: number, last_sequence: number | null, conflated: number, conflation_ratio: number, dropped: number, };
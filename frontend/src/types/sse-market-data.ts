@@ -0,0 +1,55 @@
+// Generated by `cargo run --features ts --bin gen-ts` from market-depth-sse-server 0.1.0. Do not edit.
+
+export const PROTOCOL_VERSION = "0.1.0";
+
+export type SSEMessage = { "event": "market_data", stream_id: string, symbol: string, data: MarketDataUpdate, sequence: number, timestamp: string, event_ts: string, send_ts: string, } | { "event": "snapshot", stream_id: string, symbol: string, data: MarketDataUpdate, sequence: number, timestamp: string, } | { "event": "market_summary", summary: MarketSummary, } | { "event": "heartbeat", timestamp: string, } | { "event": "connection_info", client_id: string, server_time: string, supported_symbols: Array<string>, subscriptions: Array<StreamDefinition>, summary: boolean, } | { "event": "usage_report", connected_at: string, messages: number, bytes: number, streams: Array<StreamUsage>, timestamp: string, } | { "event": "quota_exceeded", quota: QuotaRemaining, } | { "event": "throttled", stream_ids: Array<string>, interval_ms: number | null, timestamp: string, } | { "event": "replay_started", stream_id: string, symbol: string, from: string, to: string, updates: number, speed: number, } | { "event": "replay_complete", stream_id: string, } | { "event": "error", code: number, message: string, stream_id: string | null, terminal: boolean, reason: DisconnectReason | null, };
+
+export type DataType = "MBO" | "MBP" | "AggressorFlow";
+
+export type MarketDataUpdate = { "format": "MBO", bids: Array<MBOLevel>, asks: Array<MBOLevel>, } | { "format": "MBP", bids: Array<MBPLevel>, asks: Array<MBPLevel>, } | { "format": "Pair", quote: PairQuote, } | { "format": "AggressorFlow", flow: AggressorFlowStats, };
+
+export type OrderId = string;
+
+export type Quantity = number | string;
+
+export type MBOLevel = { order_id: OrderId, price: number, quantity: Quantity, side: Side, timestamp: string, age_ms: number, actor?: ActorClass, };
+
+export type MBPLevel = { price: number, quantity: Quantity, order_count: number, side: Side, total_quantity: Quantity, avg_age_ms: number, };
+
+export type ActorClass = "Maker" | "Taker" | "Noise";
+
+export type AggressorFlowStats = { window_secs: number, buy_volume: Quantity, sell_volume: Quantity, delta: number, cumulative_buy_volume: Quantity, cumulative_sell_volume: Quantity, cvd: number, trade_count: number, };
+
+export type PairKind = "Ratio" | "Spread";
+
+export type PairQuote = { pair: string, kind: PairKind, base: string, quote: string, bid: number | null, ask: number | null, value: number | null, spread: number | null, };
+
+export type SymbolSummary = { symbol: string, last_price: number | null, open_price: number | null, change_pct: number | null, volume: Quantity, spread: number | null, spread_bps: number | null, };
+
+export type SymbolType = "spot" | "pair" | "perpetual" | "future" | "option";
+
+export type SymbolStatus = "open" | "halted";
+
+export type SymbolInfo = { symbol: string, type: SymbolType, status: SymbolStatus, venue: string, base: string | null, quote: string | null, tick_size: number | null, quantity_decimals: number | null, };
+
+export type MarketSummary = { top_gainers: Array<SymbolSummary>, top_losers: Array<SymbolSummary>, highest_volume: Array<SymbolSummary>, widest_spreads: Array<SymbolSummary>, symbol_count: number, timestamp: string, };
+
+export type StreamUsage = { stream_id: string, messages: number, bytes: number, };
+
+export type StreamDefinition = { stream_id: string, symbol: string, data_type: DataType, max_levels: number | null, };
+
+export type StreamRejection = { parameter: string, index: number | null, value: string, error: string, };
+
+export type InvalidStreams = { error: string, rejected: Array<StreamRejection>, };
+
+export type Side = "Bid" | "Ask";
+
+export type StreamPriority = "Normal" | "Low";
+
+export type DisconnectReason = "server_shutdown" | "session_expired" | "slow_consumer" | "kicked";
+
+export type QuotaPeriod = "Daily" | "Monthly";
+
+export type QuotaUnit = "Messages" | "Bytes";
+
+export type QuotaRemaining = { period: QuotaPeriod, unit: QuotaUnit, limit: number, used: number, remaining: number, resets_at: string, };
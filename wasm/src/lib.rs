@@ -0,0 +1,141 @@
+// Browser bindings for the protocol messages and the local order book, so web
+// clients decode and rebuild books with the same code as the Rust client.
+use market_depth_client::{BookDelta, LocalOrderBook as Book};
+use market_depth_server::{MarketDataUpdate, OrderActivity, ServerMessage};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+fn to_js(value: &impl Serialize) -> Result<JsValue, JsError> {
+    // Plain objects rather than Maps, and u64 as numbers, like JSON.parse
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+fn parse(text: &str) -> Result<ServerMessage, JsError> {
+    serde_json::from_str(text).map_err(|e| JsError::new(&format!("Invalid server message: {}", e)))
+}
+
+/// Parses and validates a WebSocket server message.
+#[wasm_bindgen(js_name = decodeMessage)]
+pub fn decode_message(text: &str) -> Result<JsValue, JsError> {
+    to_js(&parse(text)?)
+}
+
+/// Parses a server message received as a binary frame of UTF-8 JSON.
+#[wasm_bindgen(js_name = decodeBinaryMessage)]
+pub fn decode_binary_message(bytes: &[u8]) -> Result<JsValue, JsError> {
+    let text = std::str::from_utf8(bytes).map_err(|e| JsError::new(&format!("Invalid server message: {}", e)))?;
+    decode_message(text)
+}
+
+/// Client-side book rebuilt from snapshots, level deltas or MBO activity. Levels
+/// are `{ price, quantity, order_count }` objects, best first.
+#[wasm_bindgen]
+pub struct LocalOrderBook {
+    book: Book,
+}
+
+#[wasm_bindgen]
+impl LocalOrderBook {
+    #[wasm_bindgen(constructor)]
+    pub fn new(symbol: &str) -> Self {
+        Self { book: Book::new(symbol) }
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn symbol(&self) -> String {
+        self.book.symbol().to_string()
+    }
+
+    /// Last applied sequence, undefined until the first snapshot.
+    #[wasm_bindgen(getter)]
+    pub fn sequence(&self) -> Option<f64> {
+        self.book.sequence().map(|sequence| sequence as f64)
+    }
+
+    #[wasm_bindgen(js_name = isSynced)]
+    pub fn is_synced(&self) -> bool {
+        self.book.is_synced()
+    }
+
+    pub fn invalidate(&mut self) {
+        self.book.invalidate();
+    }
+
+    /// Applies a raw MarketData message with an MBP or MBO snapshot. Returns false
+    /// for any other message, which is left to the caller.
+    #[wasm_bindgen(js_name = applyMessage)]
+    pub fn apply_message(&mut self, text: &str) -> Result<bool, JsError> {
+        match parse(text)? {
+            ServerMessage::MarketData {
+                data: data @ (MarketDataUpdate::MBP { .. } | MarketDataUpdate::MBO { .. }),
+                sequence,
+                ..
+            } => {
+                self.book.apply_snapshot(&data, sequence)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Replaces the book with the `data` of an MBP or MBO MarketData message.
+    #[wasm_bindgen(js_name = applySnapshot)]
+    pub fn apply_snapshot(&mut self, update: JsValue, sequence: f64) -> Result<(), JsError> {
+        let update: MarketDataUpdate = serde_wasm_bindgen::from_value(update)?;
+        Ok(self.book.apply_snapshot(&update, sequence as u64)?)
+    }
+
+    /// Applies `{ previous_sequence, sequence, bids, asks, checksum? }`; zero quantity removes
+    /// a level. Throws and invalidates the book when `checksum` does not match.
+    #[wasm_bindgen(js_name = applyDelta)]
+    pub fn apply_delta(&mut self, delta: JsValue) -> Result<(), JsError> {
+        let delta: BookDelta = serde_wasm_bindgen::from_value(delta)?;
+        Ok(self.book.apply_delta(&delta)?)
+    }
+
+    /// Applies one order activity, which must advance the sequence by one.
+    #[wasm_bindgen(js_name = applyActivity)]
+    pub fn apply_activity(&mut self, activity: JsValue, sequence: f64) -> Result<(), JsError> {
+        let activity: OrderActivity = serde_wasm_bindgen::from_value(activity)?;
+        Ok(self.book.apply_activity(&activity, sequence as u64)?)
+    }
+
+    pub fn bids(&self, depth: Option<usize>) -> Result<JsValue, JsError> {
+        let levels: Vec<_> = self.book.bids().take(depth.unwrap_or(usize::MAX)).collect();
+        to_js(&levels)
+    }
+
+    pub fn asks(&self, depth: Option<usize>) -> Result<JsValue, JsError> {
+        let levels: Vec<_> = self.book.asks().take(depth.unwrap_or(usize::MAX)).collect();
+        to_js(&levels)
+    }
+
+    #[wasm_bindgen(js_name = bestBid)]
+    pub fn best_bid(&self) -> Result<JsValue, JsError> {
+        to_js(&self.book.best_bid())
+    }
+
+    #[wasm_bindgen(js_name = bestAsk)]
+    pub fn best_ask(&self) -> Result<JsValue, JsError> {
+        to_js(&self.book.best_ask())
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        self.book.spread()
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.book.checksum()
+    }
+
+    #[wasm_bindgen(js_name = deltaChecksum)]
+    pub fn delta_checksum(&self) -> u32 {
+        self.book.delta_checksum()
+    }
+
+    /// Throws and invalidates the book when the checksum does not match.
+    #[wasm_bindgen(js_name = verifyChecksum)]
+    pub fn verify_checksum(&mut self, expected: u32) -> Result<(), JsError> {
+        Ok(self.book.verify_checksum(expected)?)
+    }
+}